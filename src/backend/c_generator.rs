@@ -0,0 +1,1913 @@
+//! C99 code generation.
+//!
+//! Translates a checked module into a header and a source file that compile with any C99
+//! compiler. Every name declared at module level becomes a C identifier prefixed with the module
+//! name (`M_x`), nested procedures add their enclosing procedures (`M_P_Q`), and the module body
+//! becomes `M__init`, which first runs the bodies of the imported modules. The header declares
+//! what the module exports, so importing modules only have to include it.
+//!
+//! Records become structs. An extension embeds its base record as its first member `base`, so a
+//! pointer to an extension is also a pointer to its base. Every record type has a type descriptor
+//! (`M_R__type`) linking to the descriptor of its base; heap records carry theirs in a header in
+//! front of the record, as in the native code, which makes type tests and guards a walk up the
+//! chain of descriptors.
+//!
+//! Parameters are passed as in the native code: VAR parameters and structured value parameters by
+//! address, open arrays with one length per dimension (`a__len0`, ...), and VAR records with their
+//! type descriptor (`r__type`). A procedure that declares nested procedures keeps its variables in
+//! a frame struct, and its nested procedures receive a pointer to that frame as `link`.
+
+use std::collections::{HashMap, HashSet};
+use crate::frontend::analysis::{Analysis, ExpressionInfo};
+use crate::frontend::ast::{BinaryOperation, Case, Declarations, Designator, Element, Expression, Label, LabelValue, Module, ProcedureDeclaration, Selector, Statement, StatementSequence, UnaryOperation};
+use crate::frontend::const_eval::Value;
+use crate::frontend::span::Spanned;
+use crate::frontend::symbols::{Builtin, ScopeId, SymbolId, SymbolKind};
+use crate::frontend::types::{Param, Type, TypeId, REAL};
+use crate::ir::trap;
+
+/// The C translation of one module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CModule {
+    /// The module name; the files are meant to be called `<name>.h` and `<name>.c`.
+    pub name: String,
+    pub header: String,
+    pub source: String,
+}
+
+/// Translates `module` to C. The module must have passed `analysis::check`, which produced
+/// `analysis`.
+pub fn generate(module: &Module, analysis: &Analysis) -> CModule {
+    let name = &module.name.text;
+    let mut generator = Generator::new(analysis, name);
+    let scope = analysis.symbols.module_scope().expect("analysis opens the module scope");
+
+    generator.declare_records();
+    generator.declare_imports(scope);
+    generator.declare_globals(scope);
+    generator.name_procedures(&module.declarations, scope, name, None);
+    for procedure in &module.declarations.procedure_declarations {
+        generator.procedure(procedure, scope);
+    }
+
+    generator.f = FunctionState::new(1);
+    generator.line("static bool initialized = false;");
+    generator.line("if (initialized) {");
+    generator.line("    return;");
+    generator.line("}");
+    generator.line("initialized = true;");
+    for import in &module.imports {
+        generator.line(&format!("{}__init();", import.module.text));
+    }
+    if let Some(stmts) = &module.stmts {
+        generator.statements(stmts);
+    }
+    let body = std::mem::take(&mut generator.f.body);
+    generator.functions.push_str(&format!("\nvoid {name}__init(void) {{\n{body}}}\n"));
+
+    let imports: Vec<&str> = module.imports.iter().map(|import| import.module.text.as_str()).collect();
+    CModule { name: name.to_string(), header: generator.header(scope, &imports), source: generator.source() }
+}
+
+/// Emits a `main` function that runs the body of `module` and exits with status 0.
+pub fn entry_point(module: &str) -> String {
+    format!("\nint main(void) {{\n    {module}__init();\n    return 0;\n}}\n")
+}
+
+/// The run-time support every generated source file starts with.
+const RUNTIME: &str = r#"static inline void oberon_trap(int code) {
+    exit(code);
+}
+
+/* INTEGER arithmetic wraps around, as x86-64 does, where signed overflow in C is undefined. */
+static inline int64_t oberon_add(int64_t x, int64_t y) {
+    return (int64_t)((uint64_t)x + (uint64_t)y);
+}
+
+static inline int64_t oberon_sub(int64_t x, int64_t y) {
+    return (int64_t)((uint64_t)x - (uint64_t)y);
+}
+
+static inline int64_t oberon_mul(int64_t x, int64_t y) {
+    return (int64_t)((uint64_t)x * (uint64_t)y);
+}
+
+static inline int64_t oberon_neg(int64_t x) {
+    return (int64_t)(UINT64_C(0) - (uint64_t)x);
+}
+
+static inline void oberon_inc(int64_t *x, int64_t n) {
+    *x = oberon_add(*x, n);
+}
+
+static inline void oberon_dec(int64_t *x, int64_t n) {
+    *x = oberon_sub(*x, n);
+}
+
+static inline int64_t oberon_div(int64_t x, int64_t y) {
+    int64_t q = x / y;
+    return (x % y != 0 && (x < 0) != (y < 0)) ? q - 1 : q;
+}
+
+static inline int64_t oberon_mod(int64_t x, int64_t y) {
+    int64_t r = x % y;
+    return (r != 0 && (r < 0) != (y < 0)) ? r + y : r;
+}
+
+static inline int64_t oberon_index(int64_t index, int64_t length) {
+    if (index < 0 || index >= length) {
+        oberon_trap(1);
+    }
+    return index;
+}
+
+static inline int64_t oberon_abs(int64_t x) {
+    if (x == INT64_MIN) {
+        oberon_trap(8);
+    }
+    return x < 0 ? -x : x;
+}
+
+static inline double oberon_fabs(double x) {
+    return x < 0.0 ? -x : x;
+}
+
+static inline int64_t oberon_floor(double x) {
+    /* Also true for NaN. */
+    if (!(x >= -9223372036854775808.0 && x < 9223372036854775808.0)) {
+        oberon_trap(8);
+    }
+    int64_t i = (int64_t)x;
+    return (double)i > x ? i - 1 : i;
+}
+
+static inline uint64_t oberon_range(int64_t low, int64_t high) {
+    return (~UINT64_C(0) << low) & ~(~UINT64_C(1) << high);
+}
+
+/* Shift counts are taken modulo 64, as x86-64 does. */
+static inline int64_t oberon_lsl(int64_t x, int64_t n) {
+    return (int64_t)((uint64_t)x << (n & 63));
+}
+
+static inline int64_t oberon_asr(int64_t x, int64_t n) {
+    n &= 63;
+    return x < 0 ? ~(~x >> n) : x >> n;
+}
+
+static inline int64_t oberon_ror(int64_t x, int64_t n) {
+    uint64_t u = (uint64_t)x;
+    n &= 63;
+    return (int64_t)(n == 0 ? u : (u >> n) | (u << (64 - n)));
+}
+
+static inline double oberon_pack(double x, int64_t n) {
+    for (; n > 0; n--) {
+        x *= 2.0;
+    }
+    for (; n < 0; n++) {
+        x /= 2.0;
+    }
+    return x;
+}
+
+static inline void oberon_unpk(double *x, int64_t *n) {
+    *n = 0;
+    while (*x >= 2.0) {
+        *x /= 2.0;
+        ++*n;
+    }
+    while (*x > 0.0 && *x < 1.0) {
+        *x *= 2.0;
+        --*n;
+    }
+}
+
+/* Heap records are preceded by a 16 byte header whose second word is the type descriptor. */
+static inline void *oberon_new(const OberonType *type) {
+    char *block = calloc(1, 16 + type->size);
+    *(const OberonType **)(block + 8) = type;
+    return block + 16;
+}
+
+static inline void *oberon_nil(void *pointer) {
+    if (pointer == NULL) {
+        oberon_trap(4);
+    }
+    return pointer;
+}
+
+static inline const OberonType *oberon_tag(const void *pointer) {
+    if (pointer == NULL) {
+        oberon_trap(4);
+    }
+    return ((const OberonType *const *)pointer)[-1];
+}
+
+static inline bool oberon_is(const OberonType *tag, const OberonType *type) {
+    while (tag != NULL && tag != type) {
+        tag = tag->base;
+    }
+    return tag != NULL;
+}
+
+static inline void oberon_check(const OberonType *tag, const OberonType *type) {
+    if (!oberon_is(tag, type)) {
+        oberon_trap(2);
+    }
+}
+
+static inline void oberon_assert(bool condition) {
+    if (!condition) {
+        oberon_trap(7);
+    }
+}
+
+static inline void *oberon_guard(void *pointer, const OberonType *type) {
+    oberon_check(oberon_tag(pointer), type);
+    return pointer;
+}
+"#;
+
+/// Names that must not be used as C identifiers as they are: keywords, names from the included
+/// headers, and the names the generated code uses itself.
+const RESERVED: &[&str] = &[
+    "auto", "bool", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "false", "float", "for", "goto", "if", "inline", "int", "long", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "true",
+    "typedef", "union", "unsigned", "void", "volatile", "while", "_Bool", "_Complex", "_Imaginary",
+    "NULL", "calloc", "exit", "free", "main", "memcpy", "offsetof", "size_t", "strcmp",
+    "int64_t", "uint64_t", "base", "frame", "link", "initialized",
+];
+
+/// Escapes an Oberon identifier that clashes with a C name. Oberon identifiers never contain an
+/// underscore, so appending one cannot clash with another identifier.
+fn c_name(name: &str) -> String {
+    if RESERVED.contains(&name) { format!("{name}_") } else { name.to_string() }
+}
+
+/// Combines a type name with a declarator: `int64_t` and `*p` give `int64_t *p`.
+fn join(base: &str, declarator: &str) -> String {
+    if declarator.is_empty() { base.to_string() } else { format!("{base} {declarator}") }
+}
+
+/// Appends an array or function suffix to a declarator, which binds tighter than a leading `*`.
+fn suffix(declarator: &str, suffix: &str) -> String {
+    if declarator.starts_with('*') { format!("({declarator}){suffix}") } else { format!("{declarator}{suffix}") }
+}
+
+fn char_literal(c: u8) -> String {
+    match c {
+        b'\'' => "'\\''".to_string(),
+        b'\\' => "'\\\\'".to_string(),
+        0x20..=0x7e => format!("'{}'", c as char),
+        _ => format!("'\\x{c:02x}'"),
+    }
+}
+
+fn string_literal(text: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            ' '..='~' => literal.push(c),
+            _ => literal.push_str(&format!("\\{:03o}", c as u32 as u8)),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+// --------------------------- EXPRESSIONS ---------------------------
+/// C operator precedence levels, loosest first.
+const CONDITIONAL: u8 = 1;
+const LOGICAL_OR: u8 = 2;
+const LOGICAL_AND: u8 = 3;
+const BITWISE_OR: u8 = 4;
+const BITWISE_XOR: u8 = 5;
+const BITWISE_AND: u8 = 6;
+const EQUALITY: u8 = 7;
+const RELATIONAL: u8 = 8;
+const SHIFT: u8 = 9;
+const ADDITIVE: u8 = 10;
+const MULTIPLICATIVE: u8 = 11;
+const UNARY: u8 = 12;
+const POSTFIX: u8 = 13;
+
+/// A C expression with the precedence of its outermost operator.
+#[derive(Debug, Clone)]
+struct Expr {
+    text: String,
+    precedence: u8,
+}
+
+impl Expr {
+    fn new(text: impl Into<String>, precedence: u8) -> Self {
+        Self { text: text.into(), precedence }
+    }
+
+    fn primary(text: impl Into<String>) -> Self {
+        Self::new(text, POSTFIX)
+    }
+
+    /// The text, parenthesized unless it binds at least as tight as `precedence`.
+    fn at(&self, precedence: u8) -> String {
+        if self.precedence >= precedence { self.text.clone() } else { format!("({})", self.text) }
+    }
+
+    /// The text as an operand of an operator at `precedence` that compilers warn about when mixed
+    /// with other binary operators: anything but a unary or postfix expression is parenthesized.
+    fn strict(&self, precedence: u8) -> String {
+        if self.precedence == precedence || self.precedence >= UNARY { self.text.clone() } else { format!("({})", self.text) }
+    }
+}
+
+fn binary(lhs: &Expr, op: &str, rhs: &Expr, precedence: u8) -> Expr {
+    Expr::new(format!("{} {op} {}", lhs.at(precedence), rhs.at(precedence + 1)), precedence)
+}
+
+fn strict_binary(lhs: &Expr, op: &str, rhs: &Expr, precedence: u8) -> Expr {
+    let rhs = if rhs.precedence == precedence { format!("({})", rhs.text) } else { rhs.strict(precedence) };
+    Expr::new(format!("{} {op} {rhs}", lhs.strict(precedence)), precedence)
+}
+
+fn not(expr: &Expr) -> Expr {
+    Expr::new(format!("!{}", expr.at(UNARY)), UNARY)
+}
+
+fn negate(expr: &Expr) -> Expr {
+    let operand = expr.at(UNARY);
+    if operand.starts_with('-') { Expr::new(format!("-({operand})"), UNARY) } else { Expr::new(format!("-{operand}"), UNARY) }
+}
+
+/// A call of a run-time support function.
+fn function(name: &str, arguments: &[Expr]) -> Expr {
+    let arguments: Vec<&str> = arguments.iter().map(|argument| argument.text.as_str()).collect();
+    Expr::primary(format!("{name}({})", arguments.join(", ")))
+}
+
+fn cast(ty: &str, expr: &Expr) -> Expr {
+    Expr::new(format!("({ty}){}", expr.at(UNARY)), UNARY)
+}
+
+// --------------------------- STORAGE ---------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Storage {
+    /// The C variable is the Oberon variable.
+    Direct,
+    /// The C variable holds the address of the Oberon variable.
+    Indirect,
+    /// The C variable points to the first element of an open array with this many dimensions.
+    Open(usize),
+}
+
+#[derive(Debug, Clone)]
+struct Variable {
+    /// The scope level of the declaring procedure (1 for module variables).
+    level: usize,
+    name: String,
+    /// The variable is a member of the frame struct of its procedure.
+    in_frame: bool,
+    storage: Storage,
+    /// The variable is a VAR record parameter with its type descriptor in `<name>__type`.
+    tag: bool,
+}
+
+/// How a designated variable is reached. All texts may be followed by a postfix operator.
+#[derive(Debug, Clone)]
+enum Place {
+    /// An lvalue denoting the variable.
+    Value(String),
+    /// A pointer to the variable.
+    Pointer(String),
+    /// A pointer to the first element of an open array, with the lengths of its dimensions.
+    Open { pointer: String, lengths: Vec<String> },
+}
+
+impl Place {
+    fn value(&self) -> Expr {
+        match self {
+            Place::Value(text) => Expr::primary(text),
+            Place::Pointer(pointer) => Expr::new(format!("*{pointer}"), UNARY),
+            Place::Open { pointer, .. } => Expr::primary(pointer),
+        }
+    }
+
+    fn lvalue(&self) -> String {
+        match self {
+            Place::Pointer(pointer) => format!("(*{pointer})"),
+            _ => self.value().text,
+        }
+    }
+
+    fn address(&self) -> Expr {
+        match self {
+            Place::Value(text) => Expr::new(format!("&{text}"), UNARY),
+            Place::Pointer(pointer) | Place::Open { pointer, .. } => Expr::primary(pointer),
+        }
+    }
+}
+
+/// A designated variable, with what is known about it at run time.
+#[derive(Debug, Clone)]
+struct Location {
+    place: Place,
+    ty: TypeId,
+    /// The type descriptor of a record whose dynamic type may differ from `ty`.
+    tag: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Procedure {
+    name: String,
+    level: usize,
+    /// The frame struct of the enclosing procedure, for nested procedures.
+    link: Option<String>,
+}
+
+struct FunctionState {
+    level: usize,
+    body: String,
+    indent: usize,
+}
+
+impl FunctionState {
+    fn new(level: usize) -> Self {
+        Self { level, body: String::new(), indent: 1 }
+    }
+}
+
+struct Generator<'a> {
+    analysis: &'a Analysis,
+    name: String,
+    /// Record types in an order where every struct follows the structs it contains.
+    records: Vec<TypeId>,
+    record_names: HashMap<TypeId, String>,
+    variables: HashMap<SymbolId, Variable>,
+    procedures: HashMap<SymbolId, Procedure>,
+    globals: String,
+    frames: String,
+    prototypes: String,
+    functions: String,
+    f: FunctionState,
+}
+
+impl<'a> Generator<'a> {
+    fn new(analysis: &'a Analysis, name: &str) -> Self {
+        Self {
+            analysis,
+            name: name.to_string(),
+            records: vec![],
+            record_names: HashMap::new(),
+            variables: HashMap::new(),
+            procedures: HashMap::new(),
+            globals: String::new(),
+            frames: String::new(),
+            prototypes: String::new(),
+            functions: String::new(),
+            f: FunctionState::new(1),
+        }
+    }
+
+    fn ty(&self, id: TypeId) -> &'a Type {
+        self.analysis.types.get(id)
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.f.indent {
+            self.f.body.push_str("    ");
+        }
+        self.f.body.push_str(text);
+        self.f.body.push('\n');
+    }
+
+    // --------------------------- TYPES ---------------------------
+    /// Declares `declarator` with type `ty`; an empty declarator gives the type name.
+    fn declare(&self, ty: TypeId, declarator: &str) -> String {
+        match self.ty(ty) {
+            Type::Boolean => join("bool", declarator),
+            Type::Char => join("char", declarator),
+            Type::Byte => join("unsigned char", declarator),
+            Type::Integer => join("int64_t", declarator),
+            Type::Real => join("double", declarator),
+            Type::Set => join("uint64_t", declarator),
+            Type::Nil => join("void", &format!("*{declarator}")),
+            Type::String { length } => join("char", &suffix(declarator, &format!("[{}]", length + 1))),
+            Type::Array { length, element } => self.declare(*element, &suffix(declarator, &format!("[{length}]"))),
+            Type::OpenArray { .. } => self.declare(self.open_element(ty).0, &format!("*{declarator}")),
+            Type::Record { .. } => join(&self.record_names[&ty], declarator),
+            Type::Pointer { base } => self.declare(*base, &format!("*{declarator}")),
+            Type::Procedure { params, result } => {
+                let params = self.parameter_list(params, None);
+                let declarator = suffix(&format!("*{declarator}"), &format!("({params})"));
+                match result {
+                    Some(result) => self.declare(*result, &declarator),
+                    None => join("void", &declarator),
+                }
+            }
+            Type::NoType | Type::Error => join("void", declarator),
+        }
+    }
+
+    /// The first element type of `ty` that is not an open array, and the number of open dimensions.
+    fn open_element(&self, mut ty: TypeId) -> (TypeId, usize) {
+        let mut dimensions = 0;
+        while let Type::OpenArray { element } = self.ty(ty) {
+            ty = *element;
+            dimensions += 1;
+        }
+        (ty, dimensions)
+    }
+
+    fn record_name(&self, ty: TypeId) -> &str {
+        &self.record_names[&self.analysis.types.record_of(ty).expect("record type")]
+    }
+
+    /// The C declarations that pass one parameter; `name` is empty in abstract declarators.
+    fn parameter(&self, param: &Param, name: &str) -> Vec<String> {
+        let named = |suffix: &str| if name.is_empty() { String::new() } else { format!("{name}{suffix}") };
+        match self.ty(param.ty) {
+            Type::OpenArray { .. } => {
+                let (element, dimensions) = self.open_element(param.ty);
+                let mut decls = vec![self.declare(element, &format!("*{name}"))];
+                decls.extend((0..dimensions).map(|d| join("int64_t", &named(&format!("__len{d}")))));
+                decls
+            }
+            Type::Array { .. } => vec![self.declare(param.ty, name)],
+            Type::Record { .. } if param.by_ref => {
+                vec![self.declare(param.ty, &format!("*{name}")), join("const OberonType", &format!("*{}", named("__type")))]
+            }
+            Type::Record { .. } => vec![self.declare(param.ty, &format!("*{name}"))],
+            _ if param.by_ref => vec![self.declare(param.ty, &format!("*{name}"))],
+            _ => vec![self.declare(param.ty, name)],
+        }
+    }
+
+    fn parameter_list(&self, params: &[Param], names: Option<&[String]>) -> String {
+        let decls: Vec<String> = params.iter().enumerate()
+            .flat_map(|(i, param)| self.parameter(param, names.map_or("", |names| names[i].as_str())))
+            .collect();
+        if decls.is_empty() { "void".to_string() } else { decls.join(", ") }
+    }
+
+    /// Names every record type and orders the records of this module so that each struct follows
+    /// the structs it embeds. Imported records are defined in the headers of their modules.
+    fn declare_records(&mut self) {
+        let types = &self.analysis.types;
+        self.record_names = types.record_names(&self.name);
+        let records: Vec<TypeId> = types.ids().filter(|id| matches!(types.get(*id), Type::Record { .. })).collect();
+        let mut visited = HashSet::new();
+        for record in records {
+            self.order_record(record, &mut visited);
+        }
+    }
+
+    fn order_record(&mut self, ty: TypeId, visited: &mut HashSet<TypeId>) {
+        match self.ty(ty) {
+            Type::Array { element, .. } => self.order_record(*element, visited),
+            Type::Record { base, fields } => {
+                if self.analysis.types.link(ty).is_some() || !visited.insert(ty) {
+                    return;
+                }
+                if let Some(base) = base {
+                    self.order_record(*base, visited);
+                }
+                for field in self.analysis.symbols.scope(*fields).symbols() {
+                    self.order_record(self.analysis.symbols.symbol(*field).ty, visited);
+                }
+                self.records.push(ty);
+            }
+            _ => {}
+        }
+    }
+
+    fn struct_definition(&self, record: TypeId) -> String {
+        let Type::Record { base, fields } = self.ty(record) else { unreachable!("record type") };
+        let mut out = format!("struct {} {{\n", self.record_names[&record]);
+        if let Some(base) = base {
+            out.push_str(&format!("    {} base;\n", self.record_names[base]));
+        }
+        let fields = self.analysis.symbols.scope(*fields).symbols();
+        for field in fields {
+            let symbol = self.analysis.symbols.symbol(*field);
+            out.push_str(&format!("    {};\n", self.declare(symbol.ty, &c_name(&symbol.name))));
+        }
+        if base.is_none() && fields.is_empty() {
+            out.push_str("    char empty_;\n");
+        }
+        out.push_str("};\n");
+        out
+    }
+
+    /// Names the variables and procedures of the imported modules as their own headers do.
+    fn declare_imports(&mut self, scope: ScopeId) {
+        let symbols = &self.analysis.symbols;
+        for id in symbols.scope(scope).symbols() {
+            let (SymbolKind::Module { name: module }, Some(exports)) = (&symbols.symbol(*id).kind, symbols.symbol(*id).body) else { continue; };
+            for export in symbols.scope(exports).symbols() {
+                let symbol = symbols.symbol(*export);
+                let name = format!("{module}_{}", symbol.name);
+                match symbol.kind {
+                    SymbolKind::Var => {
+                        let variable = Variable { level: 1, name, in_frame: false, storage: Storage::Direct, tag: false };
+                        self.variables.insert(*export, variable);
+                    }
+                    SymbolKind::Procedure => {
+                        self.procedures.insert(*export, Procedure { name, level: 2, link: None });
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn declare_globals(&mut self, scope: ScopeId) {
+        let symbols = &self.analysis.symbols;
+        for id in symbols.scope(scope).symbols() {
+            let symbol = symbols.symbol(*id);
+            if symbol.kind != SymbolKind::Var {
+                continue;
+            }
+            let name = format!("{}_{}", self.name, symbol.name);
+            let storage = if symbol.exported { "" } else { "static " };
+            self.globals.push_str(&format!("{storage}{};\n", self.declare(symbol.ty, &name)));
+            let variable = Variable { level: 1, name, in_frame: false, storage: Storage::Direct, tag: false };
+            self.variables.insert(*id, variable);
+        }
+    }
+
+    /// Assigns C names to all procedures up front, so calls may precede declarations.
+    fn name_procedures(&mut self, declarations: &Declarations, scope: ScopeId, prefix: &str, link: Option<String>) {
+        let symbols = &self.analysis.symbols;
+        for procedure in &declarations.procedure_declarations {
+            let id = symbols.lookup_local(scope, &procedure.header.name.ident.text).expect("declared procedure");
+            let name = format!("{prefix}_{}", procedure.header.name.ident.text);
+            let body = symbols.symbol(id).body.expect("checked procedure");
+            let level = symbols.scope(body).level;
+            self.name_procedures(&procedure.body.declarations, body, &name, Some(format!("{name}__Frame")));
+            self.procedures.insert(id, Procedure { name, level, link: link.clone() });
+        }
+    }
+
+    // --------------------------- OUTPUT ---------------------------
+    fn header(&self, scope: ScopeId, imports: &[&str]) -> String {
+        let symbols = &self.analysis.symbols;
+        let name = &self.name;
+        let guard = format!("OBERON_{}_H", name.to_uppercase());
+        let mut out = format!("/* Generated from module {name}. */\n#ifndef {guard}\n#define {guard}\n\n");
+        out.push_str("#include <stdbool.h>\n#include <stddef.h>\n#include <stdint.h>\n");
+        for import in imports {
+            out.push_str(&format!("#include \"{import}.h\"\n"));
+        }
+        out.push_str(concat!(
+            "\n#ifndef OBERON_TYPE\n#define OBERON_TYPE\n",
+            "/* A type descriptor: the descriptor of the base type and the size of a record type. */\n",
+            "typedef struct OberonType {\n    const struct OberonType *base;\n    size_t size;\n} OberonType;\n",
+            "#endif\n",
+        ));
+
+        if !self.records.is_empty() {
+            out.push('\n');
+            for record in &self.records {
+                let record = &self.record_names[record];
+                out.push_str(&format!("typedef struct {record} {record};\n"));
+            }
+            for record in &self.records {
+                out.push('\n');
+                out.push_str(&self.struct_definition(*record));
+            }
+            out.push('\n');
+            for record in &self.records {
+                out.push_str(&format!("extern const OberonType {}__type;\n", self.record_names[record]));
+            }
+        }
+
+        let mut exports = String::new();
+        for id in symbols.scope(scope).symbols() {
+            let symbol = symbols.symbol(*id);
+            if !symbol.exported {
+                continue;
+            }
+            let c_name = format!("{name}_{}", symbol.name);
+            match &symbol.kind {
+                SymbolKind::Const(value) => exports.push_str(&format!("#define {c_name} {}\n", self.constant(value).at(POSTFIX))),
+                SymbolKind::Type if self.record_names.get(&symbol.ty) != Some(&c_name) => {
+                    exports.push_str(&format!("typedef {};\n", self.declare(symbol.ty, &c_name)));
+                }
+                SymbolKind::Var => exports.push_str(&format!("extern {};\n", self.declare(symbol.ty, &c_name))),
+                SymbolKind::Procedure => exports.push_str(&format!("{};\n", self.signature(*id))),
+                _ => {}
+            }
+        }
+        exports.push_str(&format!("void {name}__init(void);\n"));
+        out.push('\n');
+        out.push_str(&exports);
+        out.push_str(&format!("\n#endif /* {guard} */\n"));
+        out
+    }
+
+    fn source(&self) -> String {
+        let name = &self.name;
+        let mut out = format!("/* Generated from module {name}. */\n#include <stdlib.h>\n#include <string.h>\n#include \"{name}.h\"\n\n");
+        out.push_str(RUNTIME);
+        let sections = [&self.descriptors(), &self.frames, &self.globals, &self.prototypes];
+        for section in sections.into_iter().filter(|section| !section.is_empty()) {
+            out.push('\n');
+            out.push_str(section);
+        }
+        out.push_str(&self.functions);
+        out
+    }
+
+    fn descriptors(&self) -> String {
+        let mut out = String::new();
+        for record in &self.records {
+            let Type::Record { base, .. } = self.ty(*record) else { unreachable!("record type") };
+            let base = base.map_or("NULL".to_string(), |base| format!("&{}__type", self.record_names[&base]));
+            let record = &self.record_names[record];
+            out.push_str(&format!("const OberonType {record}__type = {{ {base}, sizeof({record}) }};\n"));
+        }
+        out
+    }
+
+    // --------------------------- PROCEDURES ---------------------------
+    fn param_names(&self, body: ScopeId) -> Vec<String> {
+        let symbols = &self.analysis.symbols;
+        symbols.scope(body).symbols().iter()
+            .map(|id| symbols.symbol(*id))
+            .filter(|symbol| matches!(symbol.kind, SymbolKind::Param { .. }))
+            .map(|symbol| c_name(&symbol.name))
+            .collect()
+    }
+
+    /// The C declaration of procedure `id`, `static` unless it is exported.
+    fn signature(&self, id: SymbolId) -> String {
+        let symbol = self.analysis.symbols.symbol(id);
+        let procedure = &self.procedures[&id];
+        let Type::Procedure { params, result } = self.ty(symbol.ty) else { unreachable!("procedure without procedure type") };
+        let names = self.param_names(symbol.body.expect("checked procedure"));
+        let mut list = self.parameter_list(params, Some(&names));
+        if let Some(link) = &procedure.link {
+            list = if params.is_empty() { format!("struct {link} *link") } else { format!("struct {link} *link, {list}") };
+        }
+        let declarator = format!("{}({list})", procedure.name);
+        let declaration = match result {
+            Some(result) => self.declare(*result, &declarator),
+            None => join("void", &declarator),
+        };
+        if symbol.exported { declaration } else { format!("static {declaration}") }
+    }
+
+    fn procedure(&mut self, procedure: &ProcedureDeclaration, scope: ScopeId) {
+        let analysis = self.analysis;
+        let id = analysis.symbols.lookup_local(scope, &procedure.header.name.ident.text).expect("declared procedure");
+        let symbol = analysis.symbols.symbol(id);
+        let body = symbol.body.expect("checked procedure");
+        let info = self.procedures[&id].clone();
+        let Type::Procedure { params, result } = self.ty(symbol.ty) else { unreachable!("procedure without procedure type") };
+        let in_frame = !procedure.body.declarations.procedure_declarations.is_empty();
+
+        let signature = self.signature(id);
+        if !symbol.exported {
+            self.prototypes.push_str(&format!("{signature};\n"));
+        }
+
+        let locals = analysis.symbols.scope(body).symbols();
+        let names = self.param_names(body);
+        let mut members = vec![];
+        if let Some(link) = &info.link {
+            members.push(format!("struct {link} *link"));
+        }
+        let param_ids = locals.iter().filter(|id| matches!(analysis.symbols.symbol(**id).kind, SymbolKind::Param { .. }));
+        for ((param_id, param), name) in param_ids.zip(params).zip(&names) {
+            let storage = match self.ty(param.ty) {
+                Type::OpenArray { .. } => Storage::Open(self.open_element(param.ty).1),
+                Type::Array { .. } => Storage::Direct,
+                Type::Record { .. } => Storage::Indirect,
+                _ if param.by_ref => Storage::Indirect,
+                _ => Storage::Direct,
+            };
+            let tag = param.by_ref && matches!(self.ty(param.ty), Type::Record { .. });
+            self.variables.insert(*param_id, Variable { level: info.level, name: name.clone(), in_frame, storage, tag });
+            match self.ty(param.ty) {
+                // An array parameter is a pointer to its first element.
+                Type::Array { element, .. } => members.push(self.declare(*element, &format!("*{name}"))),
+                _ => members.extend(self.parameter(param, name)),
+            }
+        }
+
+        let mut declarations = vec![];
+        for local in locals {
+            let local_symbol = analysis.symbols.symbol(*local);
+            if local_symbol.kind == SymbolKind::Var {
+                let name = c_name(&local_symbol.name);
+                let variable = Variable { level: info.level, name: name.clone(), in_frame, storage: Storage::Direct, tag: false };
+                self.variables.insert(*local, variable);
+                declarations.push(self.declare(local_symbol.ty, &name));
+            }
+        }
+
+        let outer = std::mem::replace(&mut self.f, FunctionState::new(info.level));
+        if in_frame {
+            members.append(&mut declarations);
+            if members.is_empty() {
+                members.push("char empty_".to_string());
+            }
+            let members: String = members.iter().map(|member| format!("    {member};\n")).collect();
+            self.frames.push_str(&format!("struct {}__Frame {{\n{members}}};\n", info.name));
+            self.line(&format!("struct {}__Frame frame;", info.name));
+            if info.link.is_some() {
+                self.line("frame.link = link;");
+            }
+            for (param, name) in params.iter().zip(&names) {
+                let mut copied = vec![name.clone()];
+                match self.ty(param.ty) {
+                    Type::OpenArray { .. } => copied.extend((0..self.open_element(param.ty).1).map(|d| format!("{name}__len{d}"))),
+                    Type::Record { .. } if param.by_ref => copied.push(format!("{name}__type")),
+                    _ => {}
+                }
+                for name in copied {
+                    self.line(&format!("frame.{name} = {name};"));
+                }
+            }
+        }
+        for declaration in &declarations {
+            self.line(&format!("{declaration};"));
+        }
+
+        for nested in &procedure.body.declarations.procedure_declarations {
+            self.procedure(nested, body);
+        }
+
+        if let Some(stmts) = &procedure.body.stmts {
+            self.statements(stmts);
+        }
+        if let (Some(ret), Some(result)) = (&procedure.body.ret, result) {
+            let value = self.coerced(ret, *result);
+            self.line(&format!("return {};", value.text));
+        }
+
+        let state = std::mem::replace(&mut self.f, outer);
+        self.functions.push_str(&format!("\n{signature} {{\n{}}}\n", state.body));
+    }
+
+    /// The frame of the procedure at `level`, as a pointer.
+    fn frame_pointer(&self, level: usize) -> String {
+        if level == self.f.level {
+            "&frame".to_string()
+        } else {
+            let mut pointer = "link".to_string();
+            for _ in level..self.f.level - 1 {
+                pointer.push_str("->link");
+            }
+            pointer
+        }
+    }
+
+    /// The C expression naming `variable` from the current procedure.
+    fn path(&self, variable: &Variable) -> String {
+        if !variable.in_frame {
+            return variable.name.clone();
+        }
+        if variable.level == self.f.level {
+            return format!("frame.{}", variable.name);
+        }
+        format!("{}->{}", self.frame_pointer(variable.level), variable.name)
+    }
+
+    // --------------------------- DESIGNATORS ---------------------------
+    fn info(&self, expr: &Expression) -> &'a ExpressionInfo {
+        &self.analysis.expressions[&expr.span()]
+    }
+
+    fn expr_ty(&self, expr: &Expression) -> TypeId {
+        self.info(expr).ty
+    }
+
+    fn head_symbol(&self, designator: &Designator) -> SymbolId {
+        self.analysis.symbols.reference(designator.head.ident().span).expect("resolved designator")
+    }
+
+    fn variable_location(&self, id: SymbolId) -> Location {
+        let variable = self.variables.get(&id).expect("designator denotes a variable");
+        let path = self.path(variable);
+        let place = match variable.storage {
+            Storage::Direct => Place::Value(path.clone()),
+            Storage::Indirect => Place::Pointer(path.clone()),
+            Storage::Open(dimensions) => Place::Open {
+                pointer: path.clone(),
+                lengths: (0..dimensions).map(|d| format!("{path}__len{d}")).collect(),
+            },
+        };
+        let tag = variable.tag.then(|| format!("{path}__type"));
+        Location { place, ty: self.analysis.symbols.symbol(id).ty, tag }
+    }
+
+    fn designator(&mut self, designator: &Designator) -> Location {
+        let mut location = self.variable_location(self.head_symbol(designator));
+        let narrowed = self.analysis.designators[&designator.head.span()];
+        if narrowed != location.ty {
+            // Inside a type CASE branch the variable is regarded as having the extended type.
+            let record = self.record_name(narrowed).to_string();
+            location.place = match self.ty(narrowed) {
+                Type::Pointer { .. } => Place::Value(format!("(({record} *){})", location.place.lvalue())),
+                _ => Place::Pointer(format!("(({record} *){})", location.place.address().at(UNARY))),
+            };
+            location.ty = narrowed;
+        }
+        for selector in &designator.selectors {
+            let selected = self.analysis.designators[&selector.span()];
+            location = self.select(location, selector, selected);
+        }
+        location
+    }
+
+    fn select(&mut self, location: Location, selector: &Selector, selected: TypeId) -> Location {
+        match selector {
+            Selector::Field(field) => {
+                let field = self.analysis.symbols.reference(field.span).expect("resolved field");
+                let (record, access) = match (self.ty(location.ty), &location.place) {
+                    (Type::Pointer { base }, place) => (*base, format!("(({} *)oberon_nil({}))->", self.record_name(*base), place.lvalue())),
+                    (_, Place::Pointer(pointer)) => (location.ty, format!("{pointer}->")),
+                    (_, place) => (location.ty, format!("{}.", place.lvalue())),
+                };
+                let path = self.field_path(record, field);
+                Location { place: Place::Value(format!("{access}{path}")), ty: selected, tag: None }
+            }
+            Selector::Index(indices, _) => {
+                let mut location = location;
+                for index in indices {
+                    location = self.index(location, index);
+                }
+                location
+            }
+            Selector::Deref(_) => {
+                let pointer = format!("(({} *)oberon_nil({}))", self.record_name(selected), location.place.lvalue());
+                let tag = format!("oberon_tag({pointer})");
+                Location { place: Place::Pointer(pointer), ty: selected, tag: Some(tag) }
+            }
+            Selector::TypeGuard(..) => {
+                let record = self.record_name(selected).to_string();
+                if matches!(self.ty(location.ty), Type::Pointer { .. }) {
+                    let pointer = location.place.lvalue();
+                    let place = Place::Value(format!("(({record} *)oberon_guard({pointer}, &{record}__type))"));
+                    return Location { place, ty: selected, tag: None };
+                }
+                let tag = self.record_tag(&location);
+                let address = location.place.address().at(UNARY);
+                let place = Place::Pointer(format!("(oberon_check({tag}, &{record}__type), ({record} *){address})"));
+                Location { place, ty: selected, tag: Some(tag) }
+            }
+        }
+    }
+
+    /// The member path to `field` in a record of type `record`, through the embedded bases.
+    fn field_path(&self, mut record: TypeId, field: SymbolId) -> String {
+        let symbol = self.analysis.symbols.symbol(field);
+        let mut path = String::new();
+        while let Type::Record { base, fields } = self.ty(record)
+            && *fields != symbol.scope
+        {
+            path.push_str("base.");
+            record = base.expect("field declared in a base record");
+        }
+        path + &c_name(&symbol.name)
+    }
+
+    fn index(&mut self, location: Location, index: &Expression) -> Location {
+        match (self.ty(location.ty), location.place) {
+            (Type::Array { length, element }, place) => {
+                let index = self.checked_index(index, &length.to_string(), Some(*length));
+                Location { place: Place::Value(format!("{}[{index}]", place.lvalue())), ty: *element, tag: None }
+            }
+            (Type::OpenArray { element }, Place::Open { pointer, lengths }) => {
+                let index = self.checked_index(index, &lengths[0], None);
+                let place = if lengths.len() == 1 {
+                    Place::Value(format!("{pointer}[{index}]"))
+                } else {
+                    let stride = lengths[1..].join(" * ");
+                    Place::Open { pointer: format!("({pointer} + {index} * {stride})"), lengths: lengths[1..].to_vec() }
+                };
+                Location { place, ty: *element, tag: None }
+            }
+            _ => unreachable!("index into a non-array"),
+        }
+    }
+
+    fn checked_index(&mut self, index: &Expression, length: &str, constant: Option<i64>) -> String {
+        if let (Some(Value::Integer(i)), Some(n)) = (&self.info(index).value, constant)
+            && (0..n).contains(i)
+        {
+            return i.to_string();
+        }
+        let index = self.expression(index);
+        format!("oberon_index({}, {length})", index.text)
+    }
+
+    /// The lengths of the first `dimensions` dimensions of the array at `location`.
+    fn array_lengths(&self, location: &Location, dimensions: usize) -> Vec<String> {
+        let (mut ty, mut lengths) = match &location.place {
+            Place::Open { lengths, .. } => (self.open_element(location.ty).0, lengths.clone()),
+            _ => (location.ty, vec![]),
+        };
+        while lengths.len() < dimensions {
+            let Type::Array { length, element } = self.ty(ty) else { break };
+            lengths.push(length.to_string());
+            ty = *element;
+        }
+        lengths.truncate(dimensions);
+        lengths
+    }
+
+    /// The dynamic type descriptor of the record at `location`.
+    fn record_tag(&self, location: &Location) -> String {
+        match &location.tag {
+            Some(tag) => tag.clone(),
+            None => format!("&{}__type", self.record_name(location.ty)),
+        }
+    }
+
+    /// The location of an argument that must be a designator.
+    fn argument_location(&mut self, argument: &Expression) -> Location {
+        let Expression::Designator { designator, actual_parameters: None, .. } = argument else {
+            unreachable!("checked variable argument");
+        };
+        let (designator, _) = self.analysis.designator(designator, None);
+        self.designator(&designator)
+    }
+
+    /// The address of the record at `location`, as a pointer to the record type `target`.
+    fn record_address(&self, location: &Location, target: TypeId) -> Expr {
+        let address = location.place.address();
+        if self.analysis.types.record_of(location.ty) == self.analysis.types.record_of(target) {
+            address
+        } else {
+            cast(&format!("{} *", self.record_name(target)), &address)
+        }
+    }
+
+    // --------------------------- EXPRESSIONS ---------------------------
+    fn constant(&self, value: &Value) -> Expr {
+        match value {
+            Value::Integer(i64::MIN) => Expr::new("-INT64_C(9223372036854775807) - 1", ADDITIVE),
+            Value::Integer(n) if i32::try_from(*n).is_ok() => Expr::new(n.to_string(), if *n < 0 { UNARY } else { POSTFIX }),
+            Value::Integer(n) if *n < 0 => Expr::new(format!("-INT64_C({})", n.unsigned_abs()), UNARY),
+            Value::Integer(n) => Expr::primary(format!("INT64_C({n})")),
+            Value::Real(x) => {
+                let text = if x.is_finite() { format!("{x:?}") } else { format!("{}(1.0 / 0.0)", if *x < 0.0 { "-" } else { "" }) };
+                Expr::new(text, if *x < 0.0 || !x.is_finite() { UNARY } else { POSTFIX })
+            }
+            Value::Boolean(b) => Expr::primary(if *b { "true" } else { "false" }),
+            Value::Char(c) => Expr::primary(char_literal(*c)),
+            Value::Set(bits) => Expr::primary(format!("UINT64_C({bits:#x})")),
+            Value::String(text) => Expr::primary(string_literal(text)),
+            Value::Nil => Expr::primary("NULL"),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> Expr {
+        if let Some(value) = &self.info(expr).value {
+            return self.constant(value);
+        }
+        match expr {
+            Expression::Int { value, .. } => self.constant(&Value::Integer(*value)),
+            Expression::Real { value, .. } => self.constant(&Value::Real(*value)),
+            Expression::String { .. } | Expression::Char { .. } | Expression::Nil { .. } | Expression::True { .. } | Expression::False { .. } => {
+                unreachable!("literals are constant")
+            }
+            Expression::Set { elements, .. } => self.set(elements),
+            Expression::Designator { designator, actual_parameters, .. } => {
+                let (designator, arguments) = self.analysis.designator(designator, actual_parameters.as_deref());
+                if let Some(arguments) = arguments {
+                    return self.call(&designator, &arguments);
+                }
+                let id = self.head_symbol(&designator);
+                if designator.selectors.is_empty() && self.analysis.symbols.symbol(id).kind == SymbolKind::Procedure {
+                    return Expr::primary(self.procedures[&id].name.clone());
+                }
+                self.designator(&designator).place.value()
+            }
+            Expression::Unary { op, operand, .. } => {
+                let ty = self.expr_ty(expr);
+                let value = self.expression(operand);
+                match op {
+                    UnaryOperation::Plus => value,
+                    UnaryOperation::Not => not(&value),
+                    UnaryOperation::Minus if matches!(self.ty(ty), Type::Set) => Expr::new(format!("~{}", value.at(UNARY)), UNARY),
+                    UnaryOperation::Minus if matches!(self.ty(ty), Type::Integer) => function("oberon_neg", &[value]),
+                    UnaryOperation::Minus => negate(&value),
+                }
+            }
+            Expression::Binary { op, lhs, rhs, .. } => self.binary_expression(*op, lhs, rhs, self.expr_ty(expr)),
+            Expression::Error { .. } => unreachable!("syntax errors stop compilation"),
+        }
+    }
+
+    /// The value of `expr` converted to `target`, which it must be assignable to.
+    fn coerced(&mut self, expr: &Expression, target: TypeId) -> Expr {
+        let value = self.expression(expr);
+        let source = self.expr_ty(expr);
+        let types = &self.analysis.types;
+        match (self.ty(source), self.ty(target)) {
+            (Type::Pointer { .. }, Type::Pointer { .. }) if types.record_of(source) != types.record_of(target) => {
+                cast(&format!("{} *", self.record_name(target)), &value)
+            }
+            _ => value,
+        }
+    }
+
+    fn set(&mut self, elements: &[Element]) -> Expr {
+        let mut parts = vec![];
+        for element in elements {
+            let first = self.expression(&element.first);
+            parts.push(match &element.second {
+                None => Expr::new(format!("UINT64_C(1) << {}", first.at(UNARY)), SHIFT),
+                Some(second) => {
+                    let second = self.expression(second);
+                    Expr::primary(format!("oberon_range({}, {})", first.text, second.text))
+                }
+            });
+        }
+        parts.into_iter()
+            .reduce(|set, part| strict_binary(&set, "|", &part, BITWISE_OR))
+            .unwrap_or_else(|| Expr::primary("UINT64_C(0)"))
+    }
+
+    fn binary_expression(&mut self, op: BinaryOperation, lhs: &Expression, rhs: &Expression, ty: TypeId) -> Expr {
+        use BinaryOperation::*;
+        match op {
+            And | Or => {
+                let (lhs, rhs) = (self.expression(lhs), self.expression(rhs));
+                if op == And {
+                    binary(&lhs, "&&", &rhs, LOGICAL_AND)
+                } else {
+                    // Compilers warn about `&&` inside `||` without parentheses.
+                    let operand = |expr: &Expr| if expr.precedence == LOGICAL_AND { format!("({})", expr.text) } else { expr.at(LOGICAL_OR) };
+                    Expr::new(format!("{} || {}", operand(&lhs), operand(&rhs)), LOGICAL_OR)
+                }
+            }
+            Is => {
+                let Expression::Designator { designator, .. } = rhs else { unreachable!("checked type test") };
+                let target = self.analysis.symbols.symbol(self.head_symbol(designator)).ty;
+                let tag = self.dynamic_tag(lhs);
+                Expr::primary(format!("oberon_is({tag}, &{}__type)", self.record_name(target)))
+            }
+            In => {
+                let element = self.expression(lhs);
+                let set = self.expression(rhs);
+                Expr::new(format!("(({} >> {}) & 1) != 0", set.at(UNARY), element.at(UNARY)), EQUALITY)
+            }
+            Eq | Neq | Lt | Le | Gt | Ge => {
+                let (operator, precedence) = match op {
+                    Eq => ("==", EQUALITY),
+                    Neq => ("!=", EQUALITY),
+                    Lt => ("<", RELATIONAL),
+                    Le => ("<=", RELATIONAL),
+                    Gt => (">", RELATIONAL),
+                    _ => (">=", RELATIONAL),
+                };
+                let (lhs_ty, rhs_ty) = (self.expr_ty(lhs), self.expr_ty(rhs));
+                let types = &self.analysis.types;
+                let chars = types.is_char_like(lhs_ty) && types.is_char_like(rhs_ty);
+                let strings = types.is_string_like(lhs_ty) && types.is_string_like(rhs_ty) && !chars;
+                let pointers = matches!(self.ty(lhs_ty), Type::Pointer { .. } | Type::Nil) && types.record_of(lhs_ty) != types.record_of(rhs_ty);
+                let (mut lhs, mut rhs) = if strings {
+                    let (lhs, rhs) = (self.string(lhs), self.string(rhs));
+                    (Expr::primary(format!("strcmp({}, {})", lhs.text, rhs.text)), Expr::primary("0"))
+                } else {
+                    (self.expression(lhs), self.expression(rhs))
+                };
+                if chars && precedence == RELATIONAL {
+                    // CHAR is unsigned in Oberon but may be signed in C.
+                    (lhs, rhs) = (cast("unsigned char", &lhs), cast("unsigned char", &rhs));
+                } else if pointers {
+                    (lhs, rhs) = (cast("void *", &lhs), cast("void *", &rhs));
+                }
+                Expr::new(format!("{} {operator} {}", lhs.at(SHIFT), rhs.at(SHIFT)), precedence)
+            }
+            Addition | Subtraction | Multiplication | Division | Div | Mod => {
+                let lhs = self.expression(lhs);
+                let rhs = self.expression(rhs);
+                if matches!(self.ty(ty), Type::Set) {
+                    return match op {
+                        Addition => strict_binary(&lhs, "|", &rhs, BITWISE_OR),
+                        Subtraction => strict_binary(&lhs, "&", &Expr::new(format!("~{}", rhs.at(UNARY)), UNARY), BITWISE_AND),
+                        Multiplication => strict_binary(&lhs, "&", &rhs, BITWISE_AND),
+                        _ => strict_binary(&lhs, "^", &rhs, BITWISE_XOR),
+                    };
+                }
+                if matches!(self.ty(ty), Type::Integer) {
+                    let values = [lhs, rhs];
+                    return match op {
+                        Addition => function("oberon_add", &values),
+                        Subtraction => function("oberon_sub", &values),
+                        Multiplication => function("oberon_mul", &values),
+                        Div => function("oberon_div", &values),
+                        _ => function("oberon_mod", &values),
+                    };
+                }
+                match op {
+                    Addition => binary(&lhs, "+", &rhs, ADDITIVE),
+                    Subtraction => binary(&lhs, "-", &rhs, ADDITIVE),
+                    Multiplication => binary(&lhs, "*", &rhs, MULTIPLICATIVE),
+                    _ => binary(&lhs, "/", &rhs, MULTIPLICATIVE),
+                }
+            }
+        }
+    }
+
+    /// The type descriptor of the dynamic type of a pointer or record expression.
+    fn dynamic_tag(&mut self, expr: &Expression) -> String {
+        if matches!(self.ty(self.expr_ty(expr)), Type::Pointer { .. }) {
+            format!("oberon_tag({})", self.expression(expr).text)
+        } else {
+            let location = self.argument_location(expr);
+            self.record_tag(&location)
+        }
+    }
+
+    /// A string or character array; a one-character constant becomes a string.
+    fn string(&mut self, expr: &Expression) -> Expr {
+        if let Some(Value::Char(c)) = self.info(expr).value {
+            return Expr::primary(string_literal(&(c as char).to_string()));
+        }
+        self.expression(expr)
+    }
+
+    // --------------------------- CALLS ---------------------------
+    fn call(&mut self, callee: &Designator, arguments: &[Expression]) -> Expr {
+        let id = self.head_symbol(callee);
+        let symbol = self.analysis.symbols.symbol(id);
+        if let SymbolKind::Builtin(builtin) = symbol.kind {
+            return self.builtin(builtin, arguments);
+        }
+
+        let direct = callee.selectors.is_empty() && symbol.kind == SymbolKind::Procedure;
+        let procedure_ty = match callee.selectors.last() {
+            Some(selector) => self.analysis.designators[&selector.span()],
+            None => symbol.ty,
+        };
+        let Type::Procedure { params, .. } = self.ty(procedure_ty) else { unreachable!("checked call") };
+
+        let mut args = vec![];
+        let function = if direct {
+            let procedure = &self.procedures[&id];
+            if procedure.link.is_some() {
+                args.push(self.frame_pointer(procedure.level - 1));
+            }
+            procedure.name.clone()
+        } else {
+            self.designator(callee).place.value().at(POSTFIX)
+        };
+        for (argument, param) in arguments.iter().zip(params) {
+            self.argument(argument, param, &mut args);
+        }
+        Expr::primary(format!("{function}({})", args.join(", ")))
+    }
+
+    fn argument(&mut self, argument: &Expression, param: &Param, args: &mut Vec<String>) {
+        match self.ty(param.ty) {
+            Type::OpenArray { .. } => {
+                let (element, dimensions) = self.open_element(param.ty);
+                if let Some(value) = &self.info(argument).value {
+                    let length = match value {
+                        Value::String(text) => text.chars().count() + 1,
+                        _ => 2,
+                    };
+                    args.push(self.string(argument).text);
+                    args.push(length.to_string());
+                    return;
+                }
+                let location = self.argument_location(argument);
+                let lengths = self.array_lengths(&location, dimensions);
+                let pointer = location.place.value();
+                args.push(match location.place {
+                    Place::Value(_) if dimensions > 1 => cast(&self.declare(element, "*"), &pointer).text,
+                    _ => pointer.text,
+                });
+                args.extend(lengths);
+            }
+            Type::Record { .. } => {
+                let location = self.argument_location(argument);
+                args.push(self.record_address(&location, param.ty).text);
+                if param.by_ref {
+                    args.push(self.record_tag(&location));
+                }
+            }
+            Type::Array { .. } => args.push(self.string(argument).text),
+            _ if param.by_ref => {
+                let location = self.argument_location(argument);
+                args.push(location.place.address().text);
+            }
+            _ => args.push(self.coerced(argument, param.ty).text),
+        }
+    }
+
+    fn builtin(&mut self, builtin: Builtin, arguments: &[Expression]) -> Expr {
+        use Builtin::*;
+        let mut values = vec![];
+        if matches!(builtin, Abs | Odd | Lsl | Asr | Ror | Floor | Flt | Ord | Chr | Assert | Pack) {
+            values = arguments.iter().map(|argument| self.expression(argument)).collect();
+        }
+        match builtin {
+            Abs if self.expr_ty(&arguments[0]) == REAL => function("oberon_fabs", &values),
+            Abs => function("oberon_abs", &values),
+            Odd => Expr::new(format!("({} & 1) != 0", values[0].at(UNARY)), EQUALITY),
+            Len => {
+                let location = self.argument_location(&arguments[0]);
+                Expr::primary(self.array_lengths(&location, 1).remove(0))
+            }
+            Lsl => function("oberon_lsl", &values),
+            Asr => function("oberon_asr", &values),
+            Ror => function("oberon_ror", &values),
+            Floor => function("oberon_floor", &values),
+            Flt => cast("double", &values[0]),
+            Ord if matches!(self.ty(self.expr_ty(&arguments[0])), Type::Char) => cast("int64_t", &cast("unsigned char", &values[0])),
+            Ord => cast("int64_t", &values[0]),
+            Chr => cast("char", &values[0]),
+            Inc | Dec => {
+                let location = self.argument_location(&arguments[0]);
+                let step = match arguments.get(1) {
+                    Some(step) => self.expression(step),
+                    None => Expr::primary("1".to_string()),
+                };
+                let (name, operator) = if builtin == Inc { ("oberon_inc", "+=") } else { ("oberon_dec", "-=") };
+                if matches!(self.ty(location.ty), Type::Integer) {
+                    function(name, &[location.place.address(), step])
+                } else {
+                    Expr::new(format!("{} {operator} {}", location.place.lvalue(), step.text), CONDITIONAL)
+                }
+            }
+            Incl | Excl => {
+                let set = self.argument_location(&arguments[0]).place.lvalue();
+                let element = self.expression(&arguments[1]);
+                let bit = format!("UINT64_C(1) << {}", element.at(UNARY));
+                let text = if builtin == Incl { format!("{set} |= {bit}") } else { format!("{set} &= ~({bit})") };
+                Expr::new(text, CONDITIONAL)
+            }
+            New => {
+                let location = self.argument_location(&arguments[0]);
+                let record = self.record_name(location.ty).to_string();
+                Expr::new(format!("{} = oberon_new(&{record}__type)", location.place.lvalue()), CONDITIONAL)
+            }
+            Assert => function("oberon_assert", &values),
+            Pack => {
+                let x = self.argument_location(&arguments[0]).place.lvalue();
+                Expr::new(format!("{x} = oberon_pack({x}, {})", values[1].text), CONDITIONAL)
+            }
+            Unpk => {
+                let x = self.argument_location(&arguments[0]).place.address();
+                let n = self.argument_location(&arguments[1]).place.address();
+                function("oberon_unpk", &[x, n])
+            }
+        }
+    }
+
+    // --------------------------- STATEMENTS ---------------------------
+    fn statements(&mut self, stmts: &StatementSequence) {
+        for statement in &stmts.statements {
+            self.statement(statement);
+        }
+    }
+
+    fn block(&mut self, stmts: &StatementSequence) {
+        self.f.indent += 1;
+        self.statements(stmts);
+        self.f.indent -= 1;
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Assign { target, value, .. } => self.assign(target, value),
+            Statement::Call { callee, parameters, .. } => {
+                let (callee, arguments) = self.analysis.designator(callee, parameters.as_deref());
+                let call = self.call(&callee, arguments.as_deref().unwrap_or_default());
+                self.line(&format!("{};", call.text));
+            }
+            Statement::If { cond, stmts, elsif_branches, else_branch, .. } => {
+                let cond = self.expression(cond);
+                self.line(&format!("if ({}) {{", cond.text));
+                self.block(stmts);
+                for branch in elsif_branches {
+                    let cond = self.expression(&branch.cond);
+                    self.line(&format!("}} else if ({}) {{", cond.text));
+                    self.block(&branch.stmts);
+                }
+                if let Some(else_branch) = else_branch {
+                    self.line("} else {");
+                    self.block(else_branch);
+                }
+                self.line("}");
+            }
+            Statement::Case { expr, branches, .. } => self.case(expr, branches),
+            Statement::While { cond, stmts, elsif_branches, .. } if elsif_branches.is_empty() => {
+                let cond = self.expression(cond);
+                self.line(&format!("while ({}) {{", cond.text));
+                self.block(stmts);
+                self.line("}");
+            }
+            Statement::While { cond, stmts, elsif_branches, .. } => {
+                // Dijkstra's loop: run the first branch whose guard holds, then test again from
+                // the top; leave once no guard holds.
+                self.line("for (;;) {");
+                self.f.indent += 1;
+                let cond = self.expression(cond);
+                self.line(&format!("if ({}) {{", cond.text));
+                self.block(stmts);
+                for branch in elsif_branches {
+                    let cond = self.expression(&branch.cond);
+                    self.line(&format!("}} else if ({}) {{", cond.text));
+                    self.block(&branch.stmts);
+                }
+                self.line("} else {");
+                self.line("    break;");
+                self.line("}");
+                self.f.indent -= 1;
+                self.line("}");
+            }
+            Statement::Repeat { stmts, cond, .. } => {
+                self.line("do {");
+                self.block(stmts);
+                let cond = self.expression(cond);
+                self.line(&format!("}} while ({});", not(&cond).text));
+            }
+            Statement::For { var, low, high, by, stmts, .. } => {
+                let id = self.analysis.symbols.reference(var.span).expect("resolved control variable");
+                let variable = self.variable_location(id).place.lvalue();
+                let step = match by.as_ref().and_then(|by| self.info(by).value.as_ref()) {
+                    Some(Value::Integer(step)) => *step,
+                    _ => 1,
+                };
+                let start = self.expression(low);
+                // The limit is evaluated once, before the loop.
+                let constant = self.info(high).value.is_some();
+                let limit = self.expression(high);
+                if !constant {
+                    self.line("{");
+                    self.f.indent += 1;
+                    self.line(&format!("const int64_t limit_ = {};", limit.text));
+                }
+                let limit = if constant { limit.text } else { "limit_".to_string() };
+                let comparison = if step > 0 { "<=" } else { ">=" };
+                let increment = format!("{variable} = oberon_add({variable}, {step})");
+                self.line(&format!("for ({variable} = {}; {variable} {comparison} {limit}; {increment}) {{", start.text));
+                self.block(stmts);
+                self.line("}");
+                if !constant {
+                    self.f.indent -= 1;
+                    self.line("}");
+                }
+            }
+            Statement::Error { .. } => unreachable!("syntax errors stop compilation"),
+        }
+    }
+
+    fn assign(&mut self, target: &Designator, value: &Expression) {
+        let (target, _) = self.analysis.designator(target, None);
+        let location = self.designator(&target);
+        let source_ty = self.expr_ty(value);
+        match self.ty(location.ty) {
+            Type::Array { .. } => {
+                let size = match self.ty(source_ty) {
+                    Type::String { length } => (length + 1).to_string(),
+                    Type::OpenArray { .. } => {
+                        let source = self.argument_location(value);
+                        let mut factors = self.array_lengths(&source, self.open_element(source_ty).1);
+                        factors.push(format!("sizeof({})", self.declare(self.open_element(source_ty).0, "")));
+                        factors.join(" * ")
+                    }
+                    _ => format!("sizeof({})", self.declare(location.ty, "")),
+                };
+                let source = self.string(value);
+                self.line(&format!("memcpy({}, {}, {size});", location.place.value().text, source.text));
+            }
+            Type::Record { .. } => {
+                // An extension is assigned through its embedded base record.
+                let source = self.argument_location(value);
+                let mut text = source.place.value().text;
+                let mut ty = source.ty;
+                while self.analysis.types.record_of(ty) != self.analysis.types.record_of(location.ty) {
+                    let Type::Record { base, .. } = self.ty(ty) else { unreachable!("record type") };
+                    text = format!("{}.base", Expr::new(text, UNARY).at(POSTFIX));
+                    ty = base.expect("assignable record extends the target");
+                }
+                self.line(&format!("{} = {text};", location.place.lvalue()));
+            }
+            _ => {
+                let value = self.coerced(value, location.ty);
+                self.line(&format!("{} = {};", location.place.lvalue(), value.text));
+            }
+        }
+    }
+
+    fn label_value(&self, value: &LabelValue) -> i64 {
+        match value {
+            LabelValue::Integer { value, .. } => *value,
+            LabelValue::String { value, .. } => value.chars().next().map_or(0, |c| c as i64),
+            LabelValue::Char { value, .. } => *value as i64,
+            LabelValue::QualifiedIdentifier(name) => {
+                let id = self.analysis.symbols.reference(name.ident().span).expect("resolved label");
+                match &self.analysis.symbols.symbol(id).kind {
+                    SymbolKind::Const(Value::Integer(n)) => *n,
+                    SymbolKind::Const(Value::Char(c)) => *c as i64,
+                    _ => unreachable!("checked case label"),
+                }
+            }
+        }
+    }
+
+    fn case(&mut self, expr: &Expression, branches: &[Case]) {
+        let ty = self.expr_ty(expr);
+        if matches!(self.ty(ty), Type::Pointer { .. } | Type::Record { .. }) {
+            let tag = self.dynamic_tag(expr);
+            for (index, branch) in branches.iter().enumerate() {
+                let tests: Vec<String> = branch.label_list.iter().map(|label| {
+                    let Label::Single { value: LabelValue::QualifiedIdentifier(name) } = label else {
+                        unreachable!("checked type case");
+                    };
+                    let id = self.analysis.symbols.reference(name.ident().span).expect("resolved label");
+                    let target = self.analysis.symbols.symbol(id).ty;
+                    format!("oberon_is({tag}, &{}__type)", self.record_name(target))
+                }).collect();
+                let keyword = if index == 0 { "if" } else { "} else if" };
+                self.line(&format!("{keyword} ({}) {{", tests.join(" || ")));
+                self.block(&branch.statements);
+            }
+            self.line("} else {");
+            self.line(&format!("    oberon_trap({});", trap::CASE));
+            self.line("}");
+            return;
+        }
+
+        let chars = matches!(self.ty(ty), Type::Char);
+        let value = self.expression(expr);
+        let label = |value: i64| match u8::try_from(value) {
+            Ok(c) if chars && (0x20..0x7f).contains(&c) => char_literal(c),
+            _ => value.to_string(),
+        };
+        let ranges: Vec<Vec<(i64, i64)>> = branches.iter().map(|branch| {
+            branch.label_list.iter().map(|label| match label {
+                Label::Single { value } => (self.label_value(value), self.label_value(value)),
+                Label::Range { low, high } => (self.label_value(low), self.label_value(high)),
+            }).collect()
+        }).collect();
+
+        // Short ranges become one case label per value; with a long range, test each branch.
+        if ranges.iter().flatten().all(|(low, high)| high - low < 64) {
+            let value = if chars { cast("unsigned char", &value) } else { value };
+            self.line(&format!("switch ({}) {{", value.text));
+            for (branch, ranges) in branches.iter().zip(&ranges) {
+                let labels: Vec<String> = ranges.iter().flat_map(|(low, high)| *low..=*high).map(|v| format!("case {}:", label(v))).collect();
+                for line in labels.chunks(8) {
+                    self.line(&line.join(" "));
+                }
+                self.block(&branch.statements);
+                self.line("    break;");
+            }
+            self.line("default:");
+            self.line(&format!("    oberon_trap({});", trap::CASE));
+            self.line("}");
+            return;
+        }
+
+        self.line("{");
+        self.f.indent += 1;
+        self.line(&format!("const int64_t case_ = {};", if chars { cast("unsigned char", &value) } else { value }.text));
+        for (index, (branch, ranges)) in branches.iter().zip(&ranges).enumerate() {
+            let tests: Vec<String> = ranges.iter().map(|(low, high)| {
+                if low == high { format!("case_ == {}", label(*low)) } else { format!("(case_ >= {} && case_ <= {})", label(*low), label(*high)) }
+            }).collect();
+            let keyword = if index == 0 { "if" } else { "} else if" };
+            self.line(&format!("{keyword} ({}) {{", tests.join(" || ")));
+            self.block(&branch.statements);
+        }
+        self.line("} else {");
+        self.line(&format!("    oberon_trap({});", trap::CASE));
+        self.line("}");
+        self.f.indent -= 1;
+        self.line("}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+    use crate::frontend::analysis;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+    use crate::frontend::symbol_file::SymbolFile;
+
+    fn compile(source: &str) -> CModule {
+        compile_with(source, &[]).0
+    }
+
+    /// Compiles a module against the symbol files of its imports and returns its own symbol file.
+    fn compile_with(source: &str, imports: &[SymbolFile]) -> (CModule, SymbolFile) {
+        let module = Parser::new(Lexer::new(source)).parse().unwrap();
+        let analysis = analysis::check_with_imports(&module, imports).unwrap();
+        (generate(&module, &analysis), SymbolFile::export(&module.name.text, &analysis))
+    }
+
+    /// Compiles `modules` with a C `harness` as strict C99 and runs the program, returning the
+    /// exit status and output.
+    fn run(modules: &[CModule], harness: &str) -> (i32, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut command = Command::new("cc");
+        command.args(["-std=c99", "-Wall", "-Werror", "-o"]).arg(dir.path().join("program"));
+        for module in modules {
+            fs::write(dir.path().join(format!("{}.h", module.name)), &module.header).unwrap();
+            fs::write(dir.path().join(format!("{}.c", module.name)), &module.source).unwrap();
+            command.arg(dir.path().join(format!("{}.c", module.name)));
+        }
+        fs::write(dir.path().join("harness.c"), harness).unwrap();
+        let compiled = command.arg(dir.path().join("harness.c")).output().unwrap();
+        let sources: Vec<&str> = modules.iter().map(|module| module.source.as_str()).collect();
+        assert!(compiled.status.success(), "{}\n{}", String::from_utf8_lossy(&compiled.stderr), sources.join("\n"));
+        let output = Command::new(dir.path().join("program")).output().unwrap();
+        (output.status.code().unwrap_or(-1), String::from_utf8(output.stdout).unwrap())
+    }
+
+    fn run_module(source: &str, body: &str) -> (i32, String) {
+        let harness = format!("#include <stdio.h>\n#include \"M.h\"\nint main(void) {{ M__init(); {body} return 0; }}\n");
+        run(&[compile(source)], &harness)
+    }
+
+    #[test]
+    fn declares_exports_in_the_header() {
+        let module = compile(r#"
+            MODULE M;
+            CONST Max* = 10; Name* = "Oberon"; Hidden = 3;
+            TYPE Shape* = POINTER TO ShapeDesc;
+              ShapeDesc* = RECORD x*, y*: INTEGER END;
+              Circle* = RECORD (ShapeDesc) r*: REAL END;
+              Vector* = ARRAY Max OF REAL;
+            VAR count*: INTEGER; table: ARRAY 4 OF CHAR;
+            PROCEDURE Area*(c: Circle; VAR s: ShapeDesc; name: ARRAY OF CHAR): REAL;
+            RETURN c.r * c.r
+            END Area;
+            PROCEDURE Helper;
+            END Helper;
+            END M.
+        "#);
+        let header = &module.header;
+        assert!(header.starts_with("/* Generated from module M. */\n#ifndef OBERON_M_H\n#define OBERON_M_H\n"));
+        assert!(header.contains("typedef struct M_ShapeDesc M_ShapeDesc;\n"));
+        assert!(header.contains("struct M_Circle {\n    M_ShapeDesc base;\n    double r;\n};\n"));
+        assert!(header.contains("extern const OberonType M_Circle__type;\n"));
+        assert!(header.contains("#define M_Max 10\n#define M_Name \"Oberon\"\n"));
+        assert!(header.contains("typedef M_ShapeDesc *M_Shape;\n"));
+        assert!(header.contains("typedef double M_Vector[10];\n"));
+        assert!(header.contains("extern int64_t M_count;\n"));
+        assert!(header.contains("double M_Area(M_Circle *c, M_ShapeDesc *s, const OberonType *s__type, char *name, int64_t name__len0);\n"));
+        assert!(header.contains("void M__init(void);\n"));
+        assert!(!header.contains("Hidden") && !header.contains("table") && !header.contains("Helper"));
+
+        let source = &module.source;
+        assert!(source.contains("#include \"M.h\"\n"));
+        assert!(source.contains("const OberonType M_Circle__type = { &M_ShapeDesc__type, sizeof(M_Circle) };\n"));
+        assert!(source.contains("static char M_table[4];\n"));
+        assert!(source.contains("static void M_Helper(void);\n"));
+    }
+
+    #[test]
+    fn runs_module_body() {
+        let (status, output) = run_module(r#"
+            MODULE M;
+            VAR gcd*, sum*, letters*: INTEGER; x*: REAL; s: ARRAY 16 OF CHAR;
+              a, b, i: INTEGER;
+            BEGIN a := 84; b := 36;
+              WHILE a > b DO a := a - b ELSIF b > a DO b := b - a END;
+              gcd := a; sum := 0;
+              FOR i := 10 TO 0 BY -2 DO sum := sum + i * i END;
+              s := "hello world"; letters := 0; i := 0;
+              WHILE s[i] # 0X DO
+                CASE s[i] OF "a".."z": INC(letters) | " ": letters := letters END;
+                INC(i)
+              END;
+              x := FLT(sum) / 4.0 - 0.5;
+              REPEAT DEC(i, 3) UNTIL i < 0;
+              IF (i = -1) & ~(s < "hello") OR (s = "x") THEN INC(sum) END
+            END M.
+        "#, r#"printf("%ld %ld %ld %g\n", (long)M_gcd, (long)M_sum, (long)M_letters, M_x);"#);
+        assert_eq!((status, output.as_str()), (0, "12 221 10 54.5\n"));
+    }
+
+    #[test]
+    fn computes_with_sets_chars_and_floor_semantics() {
+        let (_, output) = run_module(r#"
+            MODULE M;
+            VAR q*, r*, f*, bits*, code*: INTEGER; a, b, n: INTEGER; x: REAL; s: SET; c: CHAR;
+            BEGIN a := -7; b := 2; q := a DIV b; r := a MOD b;
+              x := -2.5; f := FLOOR(x) * 100 + FLOOR(-x);
+              n := 3; s := {1, n..5} - {4}; INCL(s, 9); EXCL(s, 1);
+              IF 9 IN s THEN bits := ORD(s) + LSL(1, 20) + ASR(-16, 2) END;
+              c := 0E9X; IF c > "z" THEN code := ORD(c) ELSE code := -1 END;
+              x := -x; PACK(x, 3); UNPK(x, n); code := code * 100 + n
+            END M.
+        "#, r#"printf("%ld %ld %ld %ld %ld\n", (long)M_q, (long)M_r, (long)M_f, (long)M_bits, (long)M_code);"#);
+        assert_eq!(output, "-4 1 -298 1049124 23304\n");
+    }
+
+    #[test]
+    fn passes_open_arrays_records_and_var_parameters() {
+        let (_, output) = run_module(r#"
+            MODULE M;
+            TYPE Point* = RECORD x*, y*: INTEGER END;
+            VAR total*, length*, dot*: INTEGER; v: ARRAY 3 OF INTEGER; m: ARRAY 2, 3 OF INTEGER; p: Point;
+              word: ARRAY 8 OF CHAR;
+            PROCEDURE Sum(VAR total: INTEGER; v: ARRAY OF INTEGER);
+              VAR i: INTEGER;
+            BEGIN FOR i := 0 TO LEN(v) - 1 DO total := total + v[i] END
+            END Sum;
+            PROCEDURE Grid(m: ARRAY OF ARRAY OF INTEGER): INTEGER;
+            RETURN m[1, 2] * 10 + LEN(m[0])
+            END Grid;
+            PROCEDURE Move(VAR p: Point; dx: INTEGER);
+            BEGIN p.x := p.x + dx
+            END Move;
+            PROCEDURE Dot(a, b: Point): INTEGER;
+            RETURN a.x * b.x + a.y * b.y
+            END Dot;
+            PROCEDURE Length(s: ARRAY OF CHAR): INTEGER;
+              VAR n: INTEGER;
+            BEGIN n := 0; WHILE s[n] # 0X DO INC(n) END
+            RETURN n
+            END Length;
+            BEGIN v[0] := 4; v[1] := 5; v[2] := 6; total := 0; Sum(total, v);
+              m[1, 2] := 7; total := total * 100 + Grid(m);
+              p.x := 1; p.y := 2; Move(p, 2); dot := Dot(p, p);
+              word := "abc"; length := Length(word) * 10 + Length("hello")
+            END M.
+        "#, r#"printf("%ld %ld %ld\n", (long)M_total, (long)M_dot, (long)M_length);"#);
+        assert_eq!(output, "1573 13 35\n");
+    }
+
+    #[test]
+    fn reaches_outer_frames_through_static_links() {
+        let (_, output) = run_module(r#"
+            MODULE M;
+            VAR result*: INTEGER;
+            PROCEDURE Outer(n: INTEGER): INTEGER;
+              VAR acc: INTEGER;
+              PROCEDURE Middle(k: INTEGER);
+                PROCEDURE Inner;
+                BEGIN acc := acc + k * n
+                END Inner;
+              BEGIN Inner; Inner
+              END Middle;
+            BEGIN acc := 1; Middle(2); Middle(3)
+            RETURN acc
+            END Outer;
+            BEGIN result := Outer(10)
+            END M.
+        "#, r#"printf("%ld\n", (long)M_result);"#);
+        assert_eq!(output, "101\n");
+    }
+
+    #[test]
+    fn allocates_records_and_tests_their_types() {
+        let (_, output) = run_module(r#"
+            MODULE M;
+            TYPE Base = POINTER TO BaseDesc; BaseDesc = RECORD k: INTEGER END;
+              Ext = POINTER TO ExtDesc; ExtDesc = RECORD (BaseDesc) e: INTEGER END;
+            VAR count*: INTEGER; list: ARRAY 4 OF Base; q: Ext; i: INTEGER; b: BaseDesc;
+            PROCEDURE Weight(VAR r: BaseDesc): INTEGER;
+              VAR w: INTEGER;
+            BEGIN
+              CASE r OF ExtDesc: w := r.e * 10 | BaseDesc: w := 1 END
+            RETURN w
+            END Weight;
+            BEGIN count := 0;
+              FOR i := 0 TO 3 DO
+                IF ODD(i) THEN NEW(q); q.e := i; q.k := 5; list[i] := q ELSE NEW(list[i]) END
+              END;
+              FOR i := 0 TO 3 DO
+                IF list[i] IS Ext THEN count := count + list[i](Ext).e * 10 ELSE INC(count) END;
+                count := count + Weight(list[i]^) * 1000
+              END;
+              b := list[1]^; count := count + b.k * 100000;
+              IF list[0] # q THEN count := -count END
+            END M.
+        "#, r#"printf("%ld\n", (long)M_count);"#);
+        assert_eq!(output, "-542042\n");
+    }
+
+    #[test]
+    fn exits_with_trap_number() {
+        let source = "MODULE M; VAR a: ARRAY 4 OF INTEGER; i: INTEGER; BEGIN i := 4; a[i] := 0 END M.";
+        assert_eq!(run_module(source, "").0, trap::INDEX as i32);
+        let source = "MODULE M; VAR i: INTEGER; BEGIN i := 2; ASSERT(i = 3) END M.";
+        assert_eq!(run_module(source, "").0, trap::ASSERT as i32);
+        let source = "MODULE M; VAR i: INTEGER; BEGIN i := 2; CASE i OF 0..1: i := 0 | 3: i := 1 END END M.";
+        assert_eq!(run_module(source, "").0, trap::CASE as i32);
+        let source = r#"
+            MODULE M;
+            TYPE P = POINTER TO R; R = RECORD END; Q = POINTER TO S; S = RECORD (R) n: INTEGER END;
+            VAR p: P; q: Q;
+            BEGIN NEW(p); q := p(Q)
+            END M.
+        "#;
+        assert_eq!(run_module(source, "").0, trap::TYPE_GUARD as i32);
+        let source = "MODULE M; TYPE P = POINTER TO R; R = RECORD n: INTEGER END; VAR p: P; BEGIN p.n := 1 END M.";
+        assert_eq!(run_module(source, "").0, trap::NIL as i32);
+        let source = "MODULE M; VAR i: INTEGER; BEGIN i := -9223372036854775807 - 1; i := ABS(i) END M.";
+        assert_eq!(run_module(source, "").0, trap::OVERFLOW as i32);
+        let source = "MODULE M; VAR i: INTEGER; x: REAL; BEGIN x := 1.0E19; i := FLOOR(x) END M.";
+        assert_eq!(run_module(source, "").0, trap::OVERFLOW as i32);
+    }
+
+    #[test]
+    fn wraps_integer_arithmetic_around() {
+        let (_, output) = run_module(r#"
+            MODULE M;
+            VAR gt*: BOOLEAN; sum*, negated*, product*, counted*: INTEGER; big: INTEGER;
+            PROCEDURE Gt(a: INTEGER): BOOLEAN; RETURN a + 1 > a END Gt;
+            BEGIN big := 9223372036854775807; gt := Gt(big); sum := big + 1; negated := -sum; product := big * 2;
+              counted := sum; DEC(counted, 2)
+            END M.
+        "#, r#"printf("%d %ld %ld %ld %ld\n", M_gt, (long)M_sum, (long)M_negated, (long)M_product, (long)M_counted);"#);
+        assert_eq!(output, "0 -9223372036854775808 -9223372036854775808 -2 9223372036854775806\n");
+    }
+
+    #[test]
+    fn takes_shift_counts_modulo_64() {
+        let (_, output) = run_module(r#"
+            MODULE M;
+            VAR l*, a*, r*: INTEGER; n: INTEGER;
+            BEGIN n := 67; l := LSL(1, n); a := ASR(-64, n); r := ROR(8, n)
+            END M.
+        "#, r#"printf("%ld %ld %ld\n", (long)M_l, (long)M_a, (long)M_r);"#);
+        assert_eq!(output, "8 -8 1\n");
+    }
+
+    #[test]
+    fn initializes_imported_modules_first() {
+        let (a, symbols) = compile_with("MODULE A; VAR runs*: INTEGER; BEGIN INC(runs) END A.", &[]);
+        let (b, _) = compile_with("MODULE B; IMPORT A; VAR runs*: INTEGER; BEGIN INC(runs) END B.", &[symbols]);
+        assert!(b.header.contains("#include \"A.h\"\n"));
+        assert!(b.source.contains("    A__init();\n"));
+        let harness = concat!(
+            "#include <stdio.h>\n#include \"B.h\"\n",
+            "int main(void) { B__init(); B__init(); printf(\"%ld %ld\\n\", (long)A_runs, (long)B_runs); return 0; }\n",
+        );
+        assert_eq!(run(&[a, b], harness).1, "1 1\n");
+    }
+
+    #[test]
+    fn uses_the_exports_of_imported_modules() {
+        let (a, symbols) = compile_with(r#"
+            MODULE A;
+            CONST Max* = 10;
+            TYPE Point* = RECORD x*, y*: INTEGER; hidden: INTEGER END; Ref* = POINTER TO Point;
+            VAR origin*: Point; count*: INTEGER;
+            PROCEDURE New*(x, y: INTEGER): Ref;
+              VAR p: Ref;
+            BEGIN NEW(p); p.x := x; p.y := y; p.hidden := x * y; INC(count)
+            RETURN p
+            END New;
+            PROCEDURE Hidden*(p: Ref): INTEGER; RETURN p.hidden END Hidden;
+            BEGIN origin.x := 1
+            END A.
+        "#, &[]);
+        let (b, _) = compile_with(r#"
+            MODULE B;
+            IMPORT P := A;
+            TYPE R = RECORD (P.Point) z: INTEGER END; S = POINTER TO R;
+            VAR r: P.Ref; s: S; a*: ARRAY P.Max OF INTEGER; n*: INTEGER;
+            BEGIN
+              r := P.New(3, 4);
+              n := r.x + r.y + P.Hidden(r) + P.count * 100 + P.origin.x * 1000 + LEN(a) * 10000;
+              NEW(s); s.z := 6; r := s;
+              IF r IS S THEN n := n + r(S).z * 1000000 END
+            END B.
+        "#, &[symbols]);
+        assert!(b.header.contains("#include \"A.h\"\n"));
+        assert!(!b.header.contains("struct A_Point {"), "{}", b.header);
+        assert!(b.header.contains("struct B_R {\n    A_Point base;\n"), "{}", b.header);
+        let harness = "#include <stdio.h>\n#include \"B.h\"\nint main(void) { B__init(); printf(\"%ld\\n\", (long)B_n); return 0; }\n";
+        assert_eq!(run(&[a, b], harness).1, "6101119\n");
+    }
+
+    #[test]
+    fn escapes_names_that_clash_with_c() {
+        let module = compile(r#"
+            MODULE M;
+            TYPE R = RECORD int, base: INTEGER END;
+            VAR x*: INTEGER;
+            PROCEDURE P(char: INTEGER): INTEGER;
+              VAR frame, link: INTEGER; r: R;
+            BEGIN frame := char; link := 1; r.int := frame + link; r.base := 2
+            RETURN r.int * r.base
+            END P;
+            BEGIN x := P(20)
+            END M.
+        "#);
+        assert!(module.header.contains("    int64_t int_;\n    int64_t base_;\n"));
+        assert!(module.source.contains("static int64_t M_P(int64_t char_);\n"));
+        let harness = "#include <stdio.h>\n#include \"M.h\"\nint main(void) { M__init(); printf(\"%ld\\n\", (long)M_x); return 0; }\n";
+        assert_eq!(run(&[module], harness).1, "42\n");
+    }
+}
@@ -0,0 +1,1118 @@
+//! x86-64 code generation for Linux.
+//!
+//! Turns an IR module into GNU assembler text (AT&T syntax) following the System V ABI, so the
+//! output assembles and links with the system `cc`. Static links and VAR parameters are already
+//! explicit in the IR: the frame pointer a nested procedure receives and the address passed for
+//! a by-reference parameter are ordinary pointer arguments here.
+//!
+//! Virtual registers get machine registers from a linear scan over live intervals. Values that
+//! live across a call only get callee-saved registers; whatever does not fit is spilled to the
+//! stack. `rax`, `rcx`, `rdx` and `r11` (and `xmm0`, `xmm1` and `xmm15`) are never allocated:
+//! instructions use them as scratch registers.
+//!
+//! A trap ends the program through `exit`, with the trap number as exit status. `new` allocates
+//! through `calloc`.
+
+use std::collections::{HashMap, HashSet};
+use crate::ir::{self, BinOp, BlockId, Callee, Cond, Conv, Function, Inst, MemTy, Operand, Target, Terminator, Ty, UnOp, VReg};
+
+const ARGUMENT_REGISTERS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+const FLOAT_ARGUMENT_REGISTERS: u8 = 8;
+/// Registers clobbered by calls that may hold values not living across one.
+const CALLER_SAVED: [&str; 5] = ["rsi", "rdi", "r8", "r9", "r10"];
+const CALLEE_SAVED: [&str; 5] = ["rbx", "r12", "r13", "r14", "r15"];
+/// The xmm registers that may hold values. All of them are clobbered by calls.
+const FLOAT_REGISTERS: std::ops::RangeInclusive<u8> = 2..=14;
+
+/// Emits GNU assembler text for an IR module.
+pub fn generate(module: &ir::Module) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("\t.file\t\"{}.Mod\"\n", module.name));
+    out.push_str("\t.text\n");
+
+    let externs: HashSet<&str> = module.externs.iter().map(|e| e.name.as_str()).collect();
+    for function in &module.functions {
+        out.push_str(&FunctionGenerator::new(function, &externs).generate());
+    }
+
+    for global in &module.globals {
+        out.push_str(if global.init.is_some() { "\t.data\n" } else { "\t.bss\n" });
+        if global.exported {
+            out.push_str(&format!("\t.globl\t{}\n", global.name));
+        }
+        out.push_str(&format!("\t.balign\t{}\n", global.align.max(1)));
+        out.push_str(&format!("\t.type\t{}, @object\n", global.name));
+        out.push_str(&format!("\t.size\t{}, {}\n", global.name, global.size));
+        out.push_str(&format!("{}:\n", global.name));
+        match &global.init {
+            Some(bytes) => {
+                for chunk in bytes.chunks(16) {
+                    let bytes: Vec<String> = chunk.iter().map(|b| b.to_string()).collect();
+                    out.push_str(&format!("\t.byte\t{}\n", bytes.join(", ")));
+                }
+                if (bytes.len() as u64) < global.size {
+                    out.push_str(&format!("\t.zero\t{}\n", global.size - bytes.len() as u64));
+                }
+            }
+            None => out.push_str(&format!("\t.zero\t{}\n", global.size.max(1))),
+        }
+    }
+
+    if !module.descriptors.is_empty() {
+        out.push_str("\t.data\n");
+    }
+    for desc in &module.descriptors {
+        if desc.exported {
+            out.push_str(&format!("\t.globl\t{}\n", desc.name));
+        }
+        out.push_str("\t.balign\t8\n");
+        out.push_str(&format!("{}:\n", desc.name));
+        out.push_str(&format!("\t.quad\t{}\n", desc.base.as_deref().unwrap_or("0")));
+        out.push_str(&format!("\t.quad\t{}\n", desc.size));
+    }
+
+    out.push_str("\t.section\t.note.GNU-stack,\"\",@progbits\n");
+    out
+}
+
+/// Emits a `main` function that runs the body of `module` and exits with status 0.
+pub fn entry_point(module: &str) -> String {
+    let mut out = String::new();
+    out.push_str("\t.text\n");
+    out.push_str("\t.globl\tmain\n");
+    out.push_str("\t.type\tmain, @function\n");
+    out.push_str("main:\n");
+    out.push_str("\tsubq\t$8, %rsp\n");
+    out.push_str(&format!("\tcall\t{module}__init\n"));
+    out.push_str("\txorl\t%eax, %eax\n");
+    out.push_str("\taddq\t$8, %rsp\n");
+    out.push_str("\tret\n");
+    out.push_str("\t.size\tmain, .-main\n");
+    out.push_str("\t.section\t.note.GNU-stack,\"\",@progbits\n");
+    out
+}
+
+// --------------------------- REGISTER ALLOCATION ---------------------------
+/// Where a value lives while the function runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Loc {
+    Reg(&'static str),
+    Xmm(u8),
+    /// A stack location at this offset from `rbp`.
+    Mem(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Home {
+    Reg(&'static str),
+    Xmm(u8),
+    Spill(u32),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Interval {
+    reg: VReg,
+    start: usize,
+    end: usize,
+    float: bool,
+    crosses_call: bool,
+}
+
+/// Instructions that call out and so clobber the caller-saved registers.
+fn is_call(inst: &Inst) -> bool {
+    matches!(inst, Inst::Call { .. } | Inst::New { .. } | Inst::MemCopy { .. })
+}
+
+/// Numbers the blocks and instructions of `function` in layout order and computes one interval
+/// per virtual register that covers every position where it is live.
+fn live_intervals(function: &Function) -> Vec<Interval> {
+    let index: HashMap<BlockId, usize> = function.blocks.iter().enumerate().map(|(i, b)| (b.id, i)).collect();
+    let mut starts = vec![];
+    let mut position = 0;
+    for block in &function.blocks {
+        starts.push(position);
+        position += block.insts.len() + 2;
+    }
+    let terminator = |i: usize| starts[i] + function.blocks[i].insts.len() + 1;
+
+    let mut uses = vec![HashSet::new(); function.blocks.len()];
+    let mut defs = vec![HashSet::new(); function.blocks.len()];
+    for (i, block) in function.blocks.iter().enumerate() {
+        defs[i].extend(block.params.iter().map(|(reg, _)| *reg));
+        for inst in &block.insts {
+            for reg in inst.operands().into_iter().filter_map(Operand::reg) {
+                if !defs[i].contains(&reg) {
+                    uses[i].insert(reg);
+                }
+            }
+            if let Some((reg, _)) = inst.def() {
+                defs[i].insert(reg);
+            }
+        }
+        for reg in block.term.operands().into_iter().filter_map(Operand::reg) {
+            if !defs[i].contains(&reg) {
+                uses[i].insert(reg);
+            }
+        }
+    }
+
+    let successors: Vec<Vec<usize>> = function.blocks.iter()
+        .map(|block| block.term.targets().iter().map(|target| index[&target.block]).collect())
+        .collect();
+    let mut live_in: Vec<HashSet<VReg>> = vec![HashSet::new(); function.blocks.len()];
+    let mut live_out: Vec<HashSet<VReg>> = vec![HashSet::new(); function.blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in (0..function.blocks.len()).rev() {
+            let out: HashSet<VReg> = successors[i].iter().flat_map(|s| live_in[*s].iter().copied()).collect();
+            let mut live: HashSet<VReg> = out.difference(&defs[i]).copied().collect();
+            live.extend(uses[i].iter().copied());
+            if live != live_in[i] {
+                live_in[i] = live;
+                changed = true;
+            }
+            live_out[i] = out;
+        }
+    }
+
+    let mut ranges: HashMap<VReg, (usize, usize)> = HashMap::new();
+    let mut extend = |reg: VReg, position: usize| {
+        let range = ranges.entry(reg).or_insert((position, position));
+        range.0 = range.0.min(position);
+        range.1 = range.1.max(position);
+    };
+    let mut calls = vec![];
+    for param in &function.params {
+        extend(param.reg, 0);
+    }
+    for (i, block) in function.blocks.iter().enumerate() {
+        for reg in &live_in[i] {
+            extend(*reg, starts[i]);
+        }
+        for (reg, _) in &block.params {
+            extend(*reg, starts[i]);
+        }
+        for (k, inst) in block.insts.iter().enumerate() {
+            let position = starts[i] + 1 + k;
+            for reg in inst.operands().into_iter().filter_map(Operand::reg) {
+                extend(reg, position);
+            }
+            if let Some((reg, _)) = inst.def() {
+                extend(reg, position);
+            }
+            if is_call(inst) {
+                calls.push(position);
+            }
+        }
+        for reg in block.term.operands().into_iter().filter_map(Operand::reg) {
+            extend(reg, terminator(i));
+        }
+        for reg in &live_out[i] {
+            extend(*reg, terminator(i));
+        }
+    }
+
+    let mut intervals: Vec<Interval> = ranges.into_iter()
+        .map(|(reg, (start, end))| Interval {
+            reg,
+            start,
+            end,
+            float: function.reg_type(reg) == Ty::F64,
+            crosses_call: calls.iter().any(|call| start < *call && *call < end),
+        })
+        .collect();
+    intervals.sort_by_key(|interval| (interval.start, interval.reg.0));
+    intervals
+}
+
+/// Linear scan register allocation. Returns the home of every register and the number of
+/// spill slots used.
+fn allocate(intervals: &[Interval]) -> (HashMap<VReg, Home>, u32) {
+    let mut homes = HashMap::new();
+    let mut spills = 0;
+    let mut active: Vec<Interval> = vec![];
+    let mut spill = |homes: &mut HashMap<VReg, Home>, reg: VReg| {
+        homes.insert(reg, Home::Spill(spills));
+        spills += 1;
+    };
+
+    for interval in intervals {
+        active.retain(|other| other.end >= interval.start);
+        let candidates: Vec<Home> = match (interval.float, interval.crosses_call) {
+            (true, true) => vec![],
+            (true, false) => FLOAT_REGISTERS.map(Home::Xmm).collect(),
+            (false, true) => CALLEE_SAVED.iter().map(|r| Home::Reg(r)).collect(),
+            (false, false) => CALLER_SAVED.iter().chain(&CALLEE_SAVED).map(|r| Home::Reg(r)).collect(),
+        };
+        let taken: HashSet<Home> = active.iter().map(|other| homes[&other.reg]).collect();
+        if let Some(home) = candidates.iter().find(|home| !taken.contains(home)) {
+            homes.insert(interval.reg, *home);
+            active.push(*interval);
+            continue;
+        }
+
+        // No register is free: spill whichever interval ends last.
+        let victim = active.iter().enumerate()
+            .filter(|(_, other)| candidates.contains(&homes[&other.reg]))
+            .max_by_key(|(_, other)| other.end)
+            .map(|(index, other)| (index, *other));
+        match victim {
+            Some((index, victim)) if victim.end > interval.end => {
+                let home = homes[&victim.reg];
+                spill(&mut homes, victim.reg);
+                active.remove(index);
+                homes.insert(interval.reg, home);
+                active.push(*interval);
+            }
+            _ => spill(&mut homes, interval.reg),
+        }
+    }
+    (homes, spills)
+}
+
+// --------------------------- EMISSION ---------------------------
+/// The value a move reads: a location or an immediate (the bits of a REAL).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Source {
+    Loc(Loc),
+    Imm(i64),
+}
+
+fn fits_i32(value: i64) -> bool {
+    i32::try_from(value).is_ok()
+}
+
+fn text(loc: Loc) -> String {
+    match loc {
+        Loc::Reg(reg) => format!("%{reg}"),
+        Loc::Xmm(n) => format!("%xmm{n}"),
+        Loc::Mem(offset) => format!("{offset}(%rbp)"),
+    }
+}
+
+fn align_up(value: u64, align: u64) -> u64 {
+    value.div_ceil(align) * align
+}
+
+struct FunctionGenerator<'a> {
+    function: &'a Function,
+    externs: &'a HashSet<&'a str>,
+    locs: HashMap<VReg, Loc>,
+    saved: Vec<&'static str>,
+    slots: Vec<i64>,
+    frame_size: u64,
+    labels: usize,
+    out: String,
+}
+
+impl<'a> FunctionGenerator<'a> {
+    fn new(function: &'a Function, externs: &'a HashSet<&'a str>) -> Self {
+        let (homes, spills) = allocate(&live_intervals(function));
+        let used: HashSet<&str> = homes.values().filter_map(|home| match home {
+            Home::Reg(reg) => Some(*reg),
+            _ => None,
+        }).collect();
+        let saved: Vec<&'static str> = CALLEE_SAVED.iter().copied().filter(|reg| used.contains(reg)).collect();
+
+        // Below the saved registers: stack slots, spill slots, and at the bottom the area
+        // outgoing arguments are written to.
+        let mut offset = 8 * saved.len() as u64;
+        let mut slots = vec![];
+        for slot in &function.slots {
+            offset = align_up(offset + slot.size, slot.align.max(1));
+            slots.push(-(offset as i64));
+        }
+        let spill_base = offset;
+        offset += 8 * spills as u64;
+        let outgoing = function.blocks.iter().flat_map(|block| &block.insts)
+            .map(|inst| match inst {
+                Inst::Call { args, .. } => 8 * args.len() as u64,
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0);
+        let frame_size = align_up(offset + outgoing, 16) - 8 * saved.len() as u64;
+
+        let locs = homes.into_iter().map(|(reg, home)| {
+            let loc = match home {
+                Home::Reg(reg) => Loc::Reg(reg),
+                Home::Xmm(n) => Loc::Xmm(n),
+                Home::Spill(n) => Loc::Mem(-((spill_base + 8 * (n as u64 + 1)) as i64)),
+            };
+            (reg, loc)
+        }).collect();
+
+        Self { function, externs, locs, saved, slots, frame_size, labels: 0, out: String::new() }
+    }
+
+    fn generate(mut self) -> String {
+        let name = &self.function.name;
+        if self.function.exported {
+            self.out.push_str(&format!("\t.globl\t{name}\n"));
+        }
+        self.out.push_str(&format!("\t.type\t{name}, @function\n"));
+        self.out.push_str(&format!("{name}:\n"));
+        self.emit("pushq\t%rbp");
+        self.emit("movq\t%rsp, %rbp");
+        for reg in self.saved.clone() {
+            self.emit(&format!("pushq\t%{reg}"));
+        }
+        if self.frame_size > 0 {
+            self.emit(&format!("subq\t${}, %rsp", self.frame_size));
+        }
+        self.receive_params();
+
+        for (i, block) in self.function.blocks.iter().enumerate() {
+            let next = self.function.blocks.get(i + 1).map(|next| next.id);
+            self.out.push_str(&format!("{}:\n", self.block_label(block.id)));
+            for inst in &block.insts {
+                self.inst(inst);
+            }
+            self.terminator(&block.term, next);
+        }
+        self.out.push_str(&format!("\t.size\t{name}, .-{name}\n"));
+        self.out
+    }
+
+    fn emit(&mut self, line: &str) {
+        self.out.push('\t');
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn block_label(&self, block: BlockId) -> String {
+        format!(".L{}_bb{}", self.function.name, block.0)
+    }
+
+    fn new_label(&mut self) -> String {
+        self.labels += 1;
+        format!(".L{}_{}", self.function.name, self.labels)
+    }
+
+    fn loc(&self, reg: VReg) -> Loc {
+        self.locs[&reg]
+    }
+
+    fn source(&self, operand: &Operand) -> Source {
+        match operand {
+            Operand::Reg(reg) => Source::Loc(self.loc(*reg)),
+            Operand::Int(value) => Source::Imm(*value),
+            Operand::Real(value) => Source::Imm(value.to_bits() as i64),
+        }
+    }
+
+    /// Moves the function's arguments from where the ABI puts them to their allocated homes.
+    fn receive_params(&mut self) {
+        let mut moves = vec![];
+        let (mut ints, mut floats, mut stack) = (0, 0, 0);
+        for param in &self.function.params {
+            let from = match param.ty {
+                Ty::F64 if floats < FLOAT_ARGUMENT_REGISTERS => {
+                    floats += 1;
+                    Loc::Xmm(floats - 1)
+                }
+                Ty::I64 | Ty::Ptr if ints < ARGUMENT_REGISTERS.len() => {
+                    ints += 1;
+                    Loc::Reg(ARGUMENT_REGISTERS[ints - 1])
+                }
+                _ => {
+                    stack += 1;
+                    Loc::Mem(16 + 8 * (stack - 1))
+                }
+            };
+            if let Some(home) = self.locs.get(&param.reg) {
+                moves.push((*home, Source::Loc(from)));
+            }
+        }
+        self.parallel_move(moves);
+    }
+
+    // --------------------------- MOVES ---------------------------
+    fn move_to(&mut self, to: Loc, from: Source) {
+        let from = match from {
+            Source::Loc(from) if from == to => return,
+            Source::Loc(from) => from,
+            Source::Imm(value) => {
+                match to {
+                    Loc::Reg(reg) if fits_i32(value) => self.emit(&format!("movq\t${value}, %{reg}")),
+                    Loc::Reg(reg) => self.emit(&format!("movabsq\t${value}, %{reg}")),
+                    Loc::Mem(_) if fits_i32(value) => self.emit(&format!("movq\t${value}, {}", text(to))),
+                    Loc::Mem(_) | Loc::Xmm(_) => {
+                        self.move_to(Loc::Reg("rax"), Source::Imm(value));
+                        self.move_to(to, Source::Loc(Loc::Reg("rax")));
+                    }
+                }
+                return;
+            }
+        };
+        let mnemonic = match (to, from) {
+            (Loc::Mem(_), Loc::Mem(_)) => {
+                self.move_to(Loc::Reg("rax"), Source::Loc(from));
+                self.move_to(to, Source::Loc(Loc::Reg("rax")));
+                return;
+            }
+            (Loc::Xmm(_), Loc::Xmm(_)) => "movapd",
+            (Loc::Xmm(_), Loc::Mem(_)) | (Loc::Mem(_), Loc::Xmm(_)) => "movsd",
+            _ => "movq",
+        };
+        self.emit(&format!("{mnemonic}\t{}, {}", text(from), text(to)));
+    }
+
+    /// Performs all `moves` as if at once: no move overwrites a source another one still reads.
+    fn parallel_move(&mut self, mut moves: Vec<(Loc, Source)>) {
+        moves.retain(|(to, from)| *from != Source::Loc(*to));
+        while !moves.is_empty() {
+            let ready = moves.iter().position(|(to, _)| !moves.iter().any(|(_, from)| *from == Source::Loc(*to)));
+            match ready {
+                Some(index) => {
+                    let (to, from) = moves.remove(index);
+                    self.move_to(to, from);
+                }
+                None => {
+                    // Every destination is still read by another move, so the moves form
+                    // cycles. Parking one destination in a scratch register breaks its cycle.
+                    let (to, _) = moves[0];
+                    let scratch = Loc::Reg("r11");
+                    self.move_to(scratch, Source::Loc(to));
+                    for (_, from) in &mut moves {
+                        if *from == Source::Loc(to) {
+                            *from = Source::Loc(scratch);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn load(&mut self, operand: &Operand, reg: &'static str) {
+        let source = self.source(operand);
+        self.move_to(Loc::Reg(reg), source);
+    }
+
+    fn load_real(&mut self, operand: &Operand, xmm: u8) {
+        let source = self.source(operand);
+        self.move_to(Loc::Xmm(xmm), source);
+    }
+
+    /// An integer operand usable as the source of an arithmetic instruction, loaded into
+    /// `scratch` when it cannot be encoded directly.
+    fn int_operand(&mut self, operand: &Operand, scratch: &'static str) -> String {
+        match self.source(operand) {
+            Source::Loc(loc @ (Loc::Reg(_) | Loc::Mem(_))) => text(loc),
+            Source::Imm(value) if fits_i32(value) => format!("${value}"),
+            _ => {
+                self.load(operand, scratch);
+                format!("%{scratch}")
+            }
+        }
+    }
+
+    /// A REAL operand usable as the source of an SSE instruction.
+    fn real_operand(&mut self, operand: &Operand, scratch: u8) -> String {
+        match self.source(operand) {
+            Source::Loc(loc @ (Loc::Xmm(_) | Loc::Mem(_))) => text(loc),
+            _ => {
+                self.load_real(operand, scratch);
+                format!("%xmm{scratch}")
+            }
+        }
+    }
+
+    /// A memory operand for the address in `operand`.
+    fn address(&mut self, operand: &Operand) -> String {
+        match self.source(operand) {
+            Source::Loc(Loc::Reg(reg)) => format!("(%{reg})"),
+            _ => {
+                self.load(operand, "r11");
+                "(%r11)".to_string()
+            }
+        }
+    }
+
+    fn define(&mut self, dst: VReg, from: Loc) {
+        let to = self.loc(dst);
+        self.move_to(to, Source::Loc(from));
+    }
+
+    fn trap(&mut self, code: i64) {
+        self.emit(&format!("movl\t${code}, %edi"));
+        self.emit("call\texit@PLT");
+    }
+
+    // --------------------------- INSTRUCTIONS ---------------------------
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Copy { dst, src, .. } => {
+                let (to, from) = (self.loc(*dst), self.source(src));
+                self.move_to(to, from);
+            }
+            Inst::Unary { dst, op, ty: Ty::F64, operand } => {
+                debug_assert_eq!(*op, UnOp::Neg);
+                self.load_real(operand, 0);
+                self.emit(&format!("movabsq\t${}, %rax", i64::MIN));
+                self.emit("movq\t%rax, %xmm1");
+                self.emit("xorpd\t%xmm1, %xmm0");
+                self.define(*dst, Loc::Xmm(0));
+            }
+            Inst::Unary { dst, op, operand, .. } => {
+                self.load(operand, "rax");
+                self.emit(if *op == UnOp::Neg { "negq\t%rax" } else { "notq\t%rax" });
+                self.define(*dst, Loc::Reg("rax"));
+            }
+            Inst::Binary { dst, op, ty: Ty::F64, lhs, rhs } => {
+                self.load_real(lhs, 0);
+                let rhs = self.real_operand(rhs, 1);
+                let mnemonic = match op {
+                    BinOp::Add => "addsd",
+                    BinOp::Sub => "subsd",
+                    BinOp::Mul => "mulsd",
+                    BinOp::Div => "divsd",
+                    _ => unreachable!("{op:?} on f64"),
+                };
+                self.emit(&format!("{mnemonic}\t{rhs}, %xmm0"));
+                self.define(*dst, Loc::Xmm(0));
+            }
+            Inst::Binary { dst, op, lhs, rhs, .. } => self.int_binary(*dst, *op, lhs, rhs),
+            Inst::Cmp { dst, cond, ty: Ty::F64, lhs, rhs } => {
+                // ucomisd reports "unordered" like "below", so only `above` conditions are
+                // reliable for NaN: less-than comparisons swap their operands.
+                let (first, second) = if matches!(cond, Cond::Lt | Cond::Le) { (rhs, lhs) } else { (lhs, rhs) };
+                self.load_real(first, 0);
+                let second = self.real_operand(second, 1);
+                self.emit(&format!("ucomisd\t{second}, %xmm0"));
+                match cond {
+                    Cond::Eq => {
+                        self.emit("sete\t%al");
+                        self.emit("setnp\t%cl");
+                        self.emit("andb\t%cl, %al");
+                    }
+                    Cond::Ne => {
+                        self.emit("setne\t%al");
+                        self.emit("setp\t%cl");
+                        self.emit("orb\t%cl, %al");
+                    }
+                    Cond::Gt | Cond::Lt => self.emit("seta\t%al"),
+                    Cond::Ge | Cond::Le => self.emit("setae\t%al"),
+                }
+                self.emit("movzbq\t%al, %rax");
+                self.define(*dst, Loc::Reg("rax"));
+            }
+            Inst::Cmp { dst, cond, lhs, rhs, .. } => {
+                self.load(lhs, "rax");
+                let rhs = self.int_operand(rhs, "rcx");
+                self.emit(&format!("cmpq\t{rhs}, %rax"));
+                let suffix = match cond {
+                    Cond::Eq => "e",
+                    Cond::Ne => "ne",
+                    Cond::Lt => "l",
+                    Cond::Le => "le",
+                    Cond::Gt => "g",
+                    Cond::Ge => "ge",
+                };
+                self.emit(&format!("set{suffix}\t%al"));
+                self.emit("movzbq\t%al, %rax");
+                self.define(*dst, Loc::Reg("rax"));
+            }
+            Inst::Convert { dst, conv: Conv::IntToReal, src } => {
+                self.load(src, "rax");
+                self.emit("cvtsi2sdq\t%rax, %xmm0");
+                self.define(*dst, Loc::Xmm(0));
+            }
+            Inst::Convert { dst, conv: Conv::Floor, src } => {
+                // Truncate, then step down if that rounded a negative value up.
+                let done = self.new_label();
+                self.load_real(src, 0);
+                self.emit("cvttsd2siq\t%xmm0, %rax");
+                self.emit("cvtsi2sdq\t%rax, %xmm1");
+                self.emit("ucomisd\t%xmm1, %xmm0");
+                self.emit(&format!("jae\t{done}"));
+                self.emit("decq\t%rax");
+                self.out.push_str(&format!("{done}:\n"));
+                self.define(*dst, Loc::Reg("rax"));
+            }
+            Inst::Load { dst, mem, addr } => {
+                let addr = self.address(addr);
+                match mem {
+                    MemTy::I8 => {
+                        self.emit(&format!("movzbq\t{addr}, %rax"));
+                        self.define(*dst, Loc::Reg("rax"));
+                    }
+                    MemTy::I64 | MemTy::Ptr => match self.loc(*dst) {
+                        Loc::Reg(reg) => self.emit(&format!("movq\t{addr}, %{reg}")),
+                        _ => {
+                            self.emit(&format!("movq\t{addr}, %rax"));
+                            self.define(*dst, Loc::Reg("rax"));
+                        }
+                    },
+                    MemTy::F64 => {
+                        self.emit(&format!("movsd\t{addr}, %xmm0"));
+                        self.define(*dst, Loc::Xmm(0));
+                    }
+                }
+            }
+            Inst::Store { mem, value, addr } => {
+                let addr = self.address(addr);
+                let value = match (mem, self.source(value)) {
+                    (MemTy::I8, _) => {
+                        self.load(value, "rcx");
+                        "%cl".to_string()
+                    }
+                    (_, Source::Loc(loc @ (Loc::Reg(_) | Loc::Xmm(_)))) => text(loc),
+                    (_, Source::Imm(imm)) if fits_i32(imm) => format!("${imm}"),
+                    _ => {
+                        self.load(value, "rcx");
+                        "%rcx".to_string()
+                    }
+                };
+                let mnemonic = match mem {
+                    MemTy::I8 => "movb",
+                    _ if value.starts_with("%xmm") => "movsd",
+                    _ => "movq",
+                };
+                self.emit(&format!("{mnemonic}\t{value}, {addr}"));
+            }
+            Inst::SlotAddr { dst, slot } => {
+                let offset = self.slots[slot.0 as usize];
+                self.emit(&format!("leaq\t{offset}(%rbp), %rax"));
+                self.define(*dst, Loc::Reg("rax"));
+            }
+            Inst::GlobalAddr { dst, name } | Inst::FuncAddr { dst, name } => {
+                self.emit(&format!("leaq\t{name}(%rip), %rax"));
+                self.define(*dst, Loc::Reg("rax"));
+            }
+            Inst::PtrAdd { dst, base, offset } => {
+                self.load(base, "rax");
+                let offset = self.int_operand(offset, "rcx");
+                self.emit(&format!("addq\t{offset}, %rax"));
+                self.define(*dst, Loc::Reg("rax"));
+            }
+            Inst::Call { dst, callee, args } => self.call(*dst, callee, args),
+            Inst::MemCopy { dst, src, size } => {
+                self.load(size, "rcx");
+                self.load(src, "rax");
+                self.load(dst, "rdi");
+                self.emit("movq\t%rax, %rsi");
+                self.emit("rep movsb");
+            }
+            Inst::New { dst, desc } => {
+                self.emit(&format!("movq\t{desc}+8(%rip), %rsi"));
+                self.emit("addq\t$8, %rsi");
+                self.emit("movl\t$1, %edi");
+                self.emit("call\tcalloc@PLT");
+                self.emit(&format!("leaq\t{desc}(%rip), %rcx"));
+                self.emit("movq\t%rcx, (%rax)");
+                self.emit("addq\t$8, %rax");
+                self.define(*dst, Loc::Reg("rax"));
+            }
+            Inst::TypeTest { dst, tag, desc } => {
+                let (yes, no) = (self.new_label(), self.new_label());
+                self.type_test(tag, desc, &yes, &no);
+                self.out.push_str(&format!("{yes}:\n"));
+                self.emit("movq\t$1, %rax");
+                self.out.push_str(&format!("{no}:\n"));
+                self.define(*dst, Loc::Reg("rax"));
+            }
+            Inst::TypeGuard { tag, desc } => {
+                let (yes, no) = (self.new_label(), self.new_label());
+                self.type_test(tag, desc, &yes, &no);
+                self.out.push_str(&format!("{no}:\n"));
+                self.trap(ir::trap::TYPE_GUARD);
+                self.out.push_str(&format!("{yes}:\n"));
+            }
+        }
+    }
+
+    /// Walks the base chain from `tag` looking for `desc`, jumping to `yes` if it is found and
+    /// to `no` (with `rax` zero) otherwise.
+    fn type_test(&mut self, tag: &Operand, desc: &str, yes: &str, no: &str) {
+        let head = self.new_label();
+        self.load(tag, "rax");
+        self.emit(&format!("leaq\t{desc}(%rip), %rcx"));
+        self.out.push_str(&format!("{head}:\n"));
+        self.emit("cmpq\t%rcx, %rax");
+        self.emit(&format!("je\t{yes}"));
+        self.emit("testq\t%rax, %rax");
+        self.emit(&format!("je\t{no}"));
+        self.emit("movq\t(%rax), %rax");
+        self.emit(&format!("jmp\t{head}"));
+    }
+
+    fn int_binary(&mut self, dst: VReg, op: BinOp, lhs: &Operand, rhs: &Operand) {
+        match op {
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::And | BinOp::Or | BinOp::Xor => {
+                self.load(lhs, "rax");
+                let rhs = self.int_operand(rhs, "rcx");
+                let mnemonic = match op {
+                    BinOp::Add => "addq",
+                    BinOp::Sub => "subq",
+                    BinOp::Mul => "imulq",
+                    BinOp::And => "andq",
+                    BinOp::Or => "orq",
+                    _ => "xorq",
+                };
+                self.emit(&format!("{mnemonic}\t{rhs}, %rax"));
+                self.define(dst, Loc::Reg("rax"));
+            }
+            BinOp::Shl | BinOp::Sar | BinOp::Ror => {
+                let mnemonic = match op {
+                    BinOp::Shl => "shlq",
+                    BinOp::Sar => "sarq",
+                    _ => "rorq",
+                };
+                let count = match rhs {
+                    Operand::Int(count) => format!("${}", count & 63),
+                    _ => {
+                        self.load(rhs, "rcx");
+                        "%cl".to_string()
+                    }
+                };
+                self.load(lhs, "rax");
+                self.emit(&format!("{mnemonic}\t{count}, %rax"));
+                self.define(dst, Loc::Reg("rax"));
+            }
+            BinOp::Div | BinOp::Mod => {
+                // idiv truncates; floor division steps the quotient down (and moves the
+                // remainder into the divisor's sign) when the remainder's sign differs.
+                let done = self.new_label();
+                self.load(rhs, "rcx");
+                self.load(lhs, "rax");
+                self.emit("cqto");
+                self.emit("idivq\t%rcx");
+                self.emit("testq\t%rdx, %rdx");
+                self.emit(&format!("je\t{done}"));
+                self.emit("movq\t%rdx, %r11");
+                self.emit("xorq\t%rcx, %r11");
+                self.emit(&format!("jns\t{done}"));
+                self.emit("decq\t%rax");
+                self.emit("addq\t%rcx, %rdx");
+                self.out.push_str(&format!("{done}:\n"));
+                self.define(dst, Loc::Reg(if op == BinOp::Div { "rax" } else { "rdx" }));
+            }
+        }
+    }
+
+    /// Writes every argument to the outgoing area first, so no argument register is
+    /// overwritten while another argument still has to be read from it.
+    fn call(&mut self, dst: Option<(VReg, Ty)>, callee: &Callee, args: &[Operand]) {
+        let (mut ints, mut floats) = (vec![], vec![]);
+        let mut stack = vec![];
+        for arg in args {
+            match self.function.operand_type(arg) {
+                Ty::F64 if floats.len() < FLOAT_ARGUMENT_REGISTERS as usize => floats.push(arg),
+                Ty::I64 | Ty::Ptr if ints.len() < ARGUMENT_REGISTERS.len() => ints.push(arg),
+                _ => stack.push(arg),
+            }
+        }
+        let in_registers = ints.iter().chain(&floats).copied();
+        for (index, arg) in stack.iter().copied().chain(in_registers).enumerate() {
+            let from = self.source(arg);
+            match from {
+                Source::Loc(loc @ Loc::Xmm(_)) => self.emit(&format!("movsd\t{}, {}(%rsp)", text(loc), 8 * index)),
+                Source::Loc(Loc::Reg(reg)) => self.emit(&format!("movq\t%{reg}, {}(%rsp)", 8 * index)),
+                Source::Imm(value) if fits_i32(value) => self.emit(&format!("movq\t${value}, {}(%rsp)", 8 * index)),
+                _ => {
+                    self.move_to(Loc::Reg("rax"), from);
+                    self.emit(&format!("movq\t%rax, {}(%rsp)", 8 * index));
+                }
+            }
+        }
+
+        let target = match callee {
+            Callee::Direct(name) if self.externs.contains(name.as_str()) => format!("{name}@PLT"),
+            Callee::Direct(name) => name.clone(),
+            Callee::Indirect(operand) => {
+                self.load(operand, "r11");
+                "*%r11".to_string()
+            }
+        };
+        for (index, reg) in ARGUMENT_REGISTERS.iter().take(ints.len()).enumerate() {
+            self.emit(&format!("movq\t{}(%rsp), %{reg}", 8 * (stack.len() + index)));
+        }
+        for index in 0..floats.len() {
+            self.emit(&format!("movsd\t{}(%rsp), %xmm{index}", 8 * (stack.len() + ints.len() + index)));
+        }
+        self.emit(&format!("call\t{target}"));
+
+        if let Some((dst, ty)) = dst {
+            self.define(dst, if ty == Ty::F64 { Loc::Xmm(0) } else { Loc::Reg("rax") });
+        }
+    }
+
+    // --------------------------- TERMINATORS ---------------------------
+    fn edge(&mut self, target: &Target) {
+        let params = &self.function.block(target.block).params;
+        let moves = params.iter().zip(&target.args)
+            .filter_map(|((param, _), arg)| self.locs.get(param).map(|home| (*home, self.source(arg))))
+            .collect();
+        self.parallel_move(moves);
+    }
+
+    fn jump(&mut self, target: &Target, next: Option<BlockId>) {
+        self.edge(target);
+        if Some(target.block) != next {
+            let label = self.block_label(target.block);
+            self.emit(&format!("jmp\t{label}"));
+        }
+    }
+
+    fn terminator(&mut self, term: &Terminator, next: Option<BlockId>) {
+        match term {
+            Terminator::Jump(target) => self.jump(target, next),
+            Terminator::Branch { cond: Operand::Int(value), then_target, else_target } => {
+                self.jump(if *value != 0 { then_target } else { else_target }, next);
+            }
+            Terminator::Branch { cond, then_target, else_target } => {
+                match self.source(cond) {
+                    Source::Loc(Loc::Xmm(_)) => {
+                        self.load(cond, "rax");
+                        self.emit("testq\t%rax, %rax");
+                    }
+                    _ => {
+                        let cond = self.int_operand(cond, "rax");
+                        self.emit(&format!("cmpq\t$0, {cond}"));
+                    }
+                }
+                if then_target.args.is_empty() {
+                    let label = self.block_label(then_target.block);
+                    self.emit(&format!("jne\t{label}"));
+                } else {
+                    let skip = self.new_label();
+                    self.emit(&format!("je\t{skip}"));
+                    self.jump(then_target, None);
+                    self.out.push_str(&format!("{skip}:\n"));
+                }
+                self.jump(else_target, next);
+            }
+            Terminator::Return(value) => {
+                if let Some(value) = value {
+                    let to = if self.function.ret == Some(Ty::F64) { Loc::Xmm(0) } else { Loc::Reg("rax") };
+                    let from = self.source(value);
+                    self.move_to(to, from);
+                }
+                if self.saved.is_empty() {
+                    self.emit("movq\t%rbp, %rsp");
+                } else {
+                    self.emit(&format!("leaq\t-{}(%rbp), %rsp", 8 * self.saved.len()));
+                    for reg in self.saved.clone().iter().rev() {
+                        self.emit(&format!("popq\t%{reg}"));
+                    }
+                }
+                self.emit("popq\t%rbp");
+                self.emit("ret");
+            }
+            Terminator::Trap(code) => self.trap(*code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+    use crate::frontend::{analysis, ir_generator};
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+
+    fn compile(source: &str) -> String {
+        let module = Parser::new(Lexer::new(source)).parse().unwrap();
+        let analysis = analysis::check(&module).unwrap();
+        generate(&ir_generator::generate(&module, &analysis))
+    }
+
+    /// Links `assembly` with a C `harness` and runs it, returning the exit status and output.
+    fn run(assembly: &str, harness: &str) -> (i32, String) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("module.s"), assembly).unwrap();
+        fs::write(dir.path().join("harness.c"), harness).unwrap();
+        let program = dir.path().join("program");
+        let compiled = Command::new("cc")
+            .arg("-o").arg(&program)
+            .arg(dir.path().join("module.s"))
+            .arg(dir.path().join("harness.c"))
+            .output()
+            .unwrap();
+        assert!(compiled.status.success(), "{}\n{assembly}", String::from_utf8_lossy(&compiled.stderr));
+        let output = Command::new(&program).output().unwrap();
+        (output.status.code().unwrap_or(-1), String::from_utf8(output.stdout).unwrap())
+    }
+
+    fn run_module(source: &str, declarations: &str, body: &str) -> (i32, String) {
+        let harness = format!("#include <stdio.h>\n{declarations}\nvoid M__init(void);\nint main(void) {{ M__init(); {body} return 0; }}\n");
+        run(&compile(source), &harness)
+    }
+
+    #[test]
+    fn emits_module_skeleton() {
+        let assembly = compile("MODULE M; VAR x*: INTEGER; y: CHAR; END M.");
+        assert!(assembly.contains("\t.globl\tM__init\n"));
+        assert!(assembly.contains("\t.globl\tM_x\n"));
+        assert!(!assembly.contains("\t.globl\tM_y\n"));
+        assert!(assembly.ends_with("\t.section\t.note.GNU-stack,\"\",@progbits\n"));
+    }
+
+    #[test]
+    fn runs_module_body() {
+        let (status, output) = run_module(r#"
+            MODULE M;
+            VAR gcd*, sum*, letters*: INTEGER; x*: REAL;
+              a, b, i: INTEGER; s: ARRAY 16 OF CHAR;
+            BEGIN a := 84; b := 36;
+              WHILE a > b DO a := a - b ELSIF b > a DO b := b - a END;
+              gcd := a; sum := 0;
+              FOR i := 10 TO 0 BY -2 DO sum := sum + i * i END;
+              s := "hello world"; letters := 0; i := 0;
+              WHILE s[i] # 0X DO
+                CASE s[i] OF "a".."z": INC(letters) | " ": letters := letters END;
+                INC(i)
+              END;
+              x := FLT(sum) / 4.0 - 0.5
+            END M.
+        "#, "extern long M_gcd, M_sum, M_letters; extern double M_x;",
+            r#"printf("%ld %ld %ld %g\n", M_gcd, M_sum, M_letters, M_x);"#);
+        assert_eq!((status, output.as_str()), (0, "12 220 10 54.5\n"));
+    }
+
+    #[test]
+    fn follows_floor_semantics_for_div_mod_and_floor() {
+        let (_, output) = run_module(r#"
+            MODULE M;
+            VAR q*, r*, f*: INTEGER; a, b: INTEGER; x: REAL;
+            BEGIN a := -7; b := 2; q := a DIV b; r := a MOD b;
+              x := -2.5; f := FLOOR(x) * 100 + FLOOR(-x)
+            END M.
+        "#, "extern long M_q, M_r, M_f;", r#"printf("%ld %ld %ld\n", M_q, M_r, M_f);"#);
+        assert_eq!(output, "-4 1 -298\n");
+    }
+
+    #[test]
+    fn passes_arguments_in_registers_and_on_the_stack() {
+        let (_, output) = run_module(r#"
+            MODULE M;
+            VAR r*: REAL; n*: INTEGER;
+            PROCEDURE Mix*(a, b, c, d, e, f, g, h: INTEGER; x, y: REAL): REAL;
+            RETURN FLT(a + 2*b + 3*c + 4*d + 5*e + 6*f + 7*g + 8*h) + x * y
+            END Mix;
+            PROCEDURE Count*(VAR total: INTEGER; v: ARRAY OF INTEGER);
+              VAR i: INTEGER;
+            BEGIN FOR i := 0 TO LEN(v) - 1 DO total := total + v[i] END
+            END Count;
+            BEGIN r := Mix(1, 1, 1, 1, 1, 1, 1, 1, 0.5, 3.0); n := 0
+            END M.
+        "#, "extern double M_r; extern long M_n; double M_Mix(long, long, long, long, long, long, long, long, double, double); void M_Count(long *, long *, long);",
+            r#"long v[3] = {4, 5, 6}; M_Count(&M_n, v, 3); printf("%g %g %ld\n", M_r, M_Mix(1, 2, 3, 4, 5, 6, 7, 8, 1.5, 2.0), M_n);"#);
+        assert_eq!(output, "37.5 207 15\n");
+    }
+
+    #[test]
+    fn keeps_values_across_calls_and_spills_under_pressure() {
+        let (_, output) = run_module(r#"
+            MODULE M;
+            VAR r*: INTEGER; x: REAL; a, b, c, d, e, f, g, h, i, j, k, l: INTEGER;
+            PROCEDURE Id(n: INTEGER): INTEGER;
+            RETURN n
+            END Id;
+            PROCEDURE Half(x: REAL): REAL;
+            RETURN x / 2.0
+            END Half;
+            BEGIN
+              a := 1; b := 2; c := 3; d := 4; e := 5; f := 6; g := 7; h := 8; i := 9; j := 10; k := 11; l := 12;
+              r := a + (b + (c + (d + (e + (f + (g + (h + (i + (j + (k + l))))))))));
+              r := r * 1000 + (Id(1) + (Id(2) + (Id(3) + (Id(4) + (Id(5) + (Id(6) + (Id(7) + (Id(8) + (Id(9) + (Id(10) + (Id(11) + (Id(12) + (Id(13) + (Id(14) + (Id(15) + Id(16))))))))))))))));
+              x := 1.0 + (Half(2.0) + (Half(4.0) + Half(8.0)));
+              r := r * 10 + FLOOR(x)
+            END M.
+        "#, "extern long M_r;", r#"printf("%ld\n", M_r);"#);
+        assert_eq!(output, "781368\n");
+    }
+
+    #[test]
+    fn reaches_outer_frames_through_static_links() {
+        let (_, output) = run_module(r#"
+            MODULE M;
+            VAR result*: INTEGER;
+            PROCEDURE Outer(n: INTEGER): INTEGER;
+              VAR acc: INTEGER;
+              PROCEDURE Middle(k: INTEGER);
+                PROCEDURE Inner;
+                BEGIN acc := acc + k * n
+                END Inner;
+              BEGIN Inner; Inner
+              END Middle;
+            BEGIN acc := 1; Middle(2); Middle(3)
+            RETURN acc
+            END Outer;
+            BEGIN result := Outer(10)
+            END M.
+        "#, "extern long M_result;", r#"printf("%ld\n", M_result);"#);
+        assert_eq!(output, "101\n");
+    }
+
+    #[test]
+    fn allocates_records_and_tests_their_types() {
+        let (_, output) = run_module(r#"
+            MODULE M;
+            TYPE Base = POINTER TO BaseDesc; BaseDesc = RECORD k: INTEGER END;
+              Ext = POINTER TO ExtDesc; ExtDesc = RECORD (BaseDesc) e: INTEGER END;
+            VAR count*: INTEGER; list: ARRAY 4 OF Base; q: Ext; i: INTEGER;
+            BEGIN count := 0;
+              FOR i := 0 TO 3 DO
+                IF ODD(i) THEN NEW(q); q.e := i; list[i] := q ELSE NEW(list[i]) END
+              END;
+              FOR i := 0 TO 3 DO
+                IF list[i] IS Ext THEN count := count + list[i](Ext).e * 10 ELSE INC(count) END
+              END
+            END M.
+        "#, "extern long M_count;", r#"printf("%ld\n", M_count);"#);
+        assert_eq!(output, "42\n");
+    }
+
+    #[test]
+    fn exits_with_trap_number() {
+        let source = "MODULE M; VAR a: ARRAY 4 OF INTEGER; i: INTEGER; BEGIN i := 4; a[i] := 0 END M.";
+        assert_eq!(run_module(source, "", "").0, ir::trap::INDEX as i32);
+        let source = "MODULE M; VAR i: INTEGER; BEGIN i := 2; ASSERT(i = 3) END M.";
+        assert_eq!(run_module(source, "", "").0, ir::trap::ASSERT as i32);
+    }
+
+    #[test]
+    fn resolves_cyclic_block_arguments() {
+        let module = ir::parse(r#"module T
+
+extern @labs(i64) -> i64
+
+export func @T_swap(%0: i64, %1: i64, %2: i64) -> i64 {
+bb0:
+  jump bb1(%0, %1, %2)
+bb1(%3: i64, %4: i64, %5: i64):
+  %6 = cmp gt i64 %5, 0
+  br %6, bb2, bb3
+bb2:
+  %7 = sub i64 %5, 1
+  jump bb1(%4, %3, %7)
+bb3:
+  %8 = mul i64 %3, 10
+  %9 = add i64 %8, %4
+  %10 = call i64 @labs(%9)
+  ret %10
+}
+"#).unwrap();
+        let harness = "#include <stdio.h>\nlong T_swap(long, long, long);\nint main(void) { printf(\"%ld %ld\\n\", T_swap(-1, -2, 3), T_swap(-1, -2, 4)); return 0; }\n";
+        assert_eq!(run(&generate(&module), harness).1, "21 12\n");
+    }
+
+    #[test]
+    fn spills_values_when_registers_run_out() {
+        let intervals: Vec<Interval> = (0..20)
+            .map(|n| Interval { reg: VReg(n), start: n as usize, end: 100, float: false, crosses_call: n % 2 == 0 })
+            .collect();
+        let (homes, spills) = allocate(&intervals);
+        assert_eq!(spills, 10);
+        for interval in &intervals {
+            if let Home::Reg(reg) = homes[&interval.reg] {
+                assert!(!interval.crosses_call || CALLEE_SAVED.contains(&reg));
+            }
+        }
+    }
+}
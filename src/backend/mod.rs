@@ -1,2 +1,3 @@
-mod optimizer;
-mod code_generator;
\ No newline at end of file
+pub mod optimizer;
+pub mod code_generator;
+pub mod c_generator;
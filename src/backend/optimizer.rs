@@ -0,0 +1,1054 @@
+//! Optimizations on the IR.
+//!
+//! Every pass rewrites one function at a time and reports whether it changed anything. A
+//! `PassManager` runs the passes of an optimization level in order, and at `-O2` repeats the
+//! whole pipeline until it stops making progress: folding a branch makes blocks unreachable,
+//! merging blocks exposes more constants, and so on.
+//!
+//! The passes rely on the SSA form of the IR. A register has one definition, so replacing its
+//! uses with another operand is always sound as long as that operand is available wherever the
+//! register was; all the substitutions below are of values that dominate the register they
+//! replace.
+
+use std::collections::{HashMap, HashSet};
+use crate::ir::{BinOp, BlockId, Cond, Conv, Function, Inst, Module, Operand, Target, Terminator, Ty, UnOp, VReg};
+
+/// The most times `-O2` repeats the pipeline over a function.
+const MAX_ROUNDS: usize = 8;
+
+// --------------------------- PASS MANAGER ---------------------------
+pub trait Pass {
+    /// The name used in IR dumps.
+    fn name(&self) -> &'static str;
+    /// Optimizes `function`, returning whether anything changed.
+    fn run(&self, function: &mut Function) -> bool;
+}
+
+/// When a dump is taken, relative to the pass it is taken for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Before,
+    After { changed: bool },
+}
+
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    /// Whether to repeat the pipeline until no pass changes the function.
+    repeat: bool,
+}
+
+impl PassManager {
+    pub fn new(passes: Vec<Box<dyn Pass>>, repeat: bool) -> Self {
+        Self { passes, repeat }
+    }
+
+    /// The pipeline for `-O<level>`: nothing at 0, one round of the cheap scalar and CFG passes
+    /// at 1, and at 2 (or above) those plus common subexpression elimination until a fixpoint.
+    pub fn for_level(level: u8) -> Self {
+        match level {
+            0 => Self::new(vec![], false),
+            1 => Self::new(vec![
+                Box::new(ConstantFolding),
+                Box::new(CopyPropagation),
+                Box::new(SimplifyCfg),
+                Box::new(DeadCodeElimination),
+            ], false),
+            _ => Self::new(vec![
+                Box::new(ConstantFolding),
+                Box::new(CopyPropagation),
+                Box::new(CommonSubexpressionElimination),
+                Box::new(SimplifyCfg),
+                Box::new(DeadCodeElimination),
+            ], true),
+        }
+    }
+
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    pub fn run(&self, module: &mut Module) {
+        self.run_with_dump(module, &mut |_, _, _| {});
+    }
+
+    /// Runs the pipeline, handing every function to `dump` before and after each pass.
+    pub fn run_with_dump(&self, module: &mut Module, dump: &mut dyn FnMut(&str, Stage, &Function)) {
+        for function in &mut module.functions {
+            let rounds = if self.repeat { MAX_ROUNDS } else { 1 };
+            for _ in 0..rounds {
+                let mut progress = false;
+                for pass in &self.passes {
+                    dump(pass.name(), Stage::Before, function);
+                    let changed = pass.run(function);
+                    dump(pass.name(), Stage::After { changed }, function);
+                    progress |= changed;
+                }
+                if !progress {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// --------------------------- CONSTANT FOLDING ---------------------------
+/// Evaluates instructions whose operands are constants and propagates the results, including
+/// through block parameters that receive the same constant on every edge. Also applies
+/// algebraic identities such as `x + 0` and `x * 1`.
+pub struct ConstantFolding;
+
+impl Pass for ConstantFolding {
+    fn name(&self) -> &'static str {
+        "const-fold"
+    }
+
+    fn run(&self, function: &mut Function) -> bool {
+        let mut values: HashMap<VReg, Operand> = HashMap::new();
+        loop {
+            let mut progress = substitute(function, &values);
+            for block in &function.blocks {
+                for inst in &block.insts {
+                    if let Some((dst, value)) = fold(inst)
+                        && !values.contains_key(&dst)
+                    {
+                        values.insert(dst, value);
+                        progress = true;
+                    }
+                }
+            }
+            progress |= propagate_params(function, &mut values, is_constant);
+            if !progress {
+                break;
+            }
+        }
+        remove_defs(function, &values);
+        !values.is_empty()
+    }
+}
+
+fn is_constant(operand: &Operand) -> bool {
+    matches!(operand, Operand::Int(_) | Operand::Real(_))
+}
+
+/// The operand an instruction is equivalent to, when that can be decided statically.
+fn fold(inst: &Inst) -> Option<(VReg, Operand)> {
+    use Operand::{Int, Real};
+    let value = match *inst {
+        Inst::Copy { dst, src: src @ (Int(_) | Real(_)), .. } => (dst, src),
+        Inst::Unary { dst, op, operand, .. } => match (op, operand) {
+            (UnOp::Neg, Int(x)) => (dst, Int(x.wrapping_neg())),
+            (UnOp::Neg, Real(x)) => (dst, Real(-x)),
+            (UnOp::Not, Int(x)) => (dst, Int(!x)),
+            _ => return None,
+        },
+        Inst::Binary { dst, op, ty: Ty::I64, lhs, rhs } => (dst, fold_int(op, lhs, rhs)?),
+        Inst::Binary { dst, op, ty: Ty::F64, lhs: Real(a), rhs: Real(b) } => match op {
+            BinOp::Add => (dst, Real(a + b)),
+            BinOp::Sub => (dst, Real(a - b)),
+            BinOp::Mul => (dst, Real(a * b)),
+            BinOp::Div => (dst, Real(a / b)),
+            _ => return None,
+        },
+        Inst::Cmp { dst, cond, ty, lhs, rhs } => {
+            let holds = match (lhs, rhs) {
+                (Int(a), Int(b)) => compare(cond, a.cmp(&b).into()),
+                (Real(a), Real(b)) => compare(cond, a.partial_cmp(&b)),
+                // Only an integer is always equal to itself; a REAL may be a NaN.
+                (Operand::Reg(a), Operand::Reg(b)) if a == b && ty != Ty::F64 => {
+                    compare(cond, Some(std::cmp::Ordering::Equal))
+                }
+                _ => return None,
+            };
+            (dst, Int(holds as i64))
+        }
+        Inst::Convert { dst, conv: Conv::IntToReal, src: Int(x) } => (dst, Real(x as f64)),
+        // Out of range conversions are left to the target's semantics.
+        Inst::Convert { dst, conv: Conv::Floor, src: Real(x) } if x.is_finite() && x.abs() < 9.0e18 => {
+            (dst, Int(x.floor() as i64))
+        }
+        Inst::PtrAdd { dst, base, offset: Int(0) } => (dst, base),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Folds an integer operation, or simplifies it to one of its operands. Division by zero and
+/// overflowing division are left to trap at run time.
+fn fold_int(op: BinOp, lhs: Operand, rhs: Operand) -> Option<Operand> {
+    use Operand::Int;
+    if let (Int(a), Int(b)) = (lhs, rhs) {
+        let count = (b & 63) as u32;
+        let value = match op {
+            BinOp::Add => a.wrapping_add(b),
+            BinOp::Sub => a.wrapping_sub(b),
+            BinOp::Mul => a.wrapping_mul(b),
+            BinOp::Div | BinOp::Mod if b == 0 || (a == i64::MIN && b == -1) => return None,
+            BinOp::Div => a.div_euclid(b) - i64::from(b < 0 && a.rem_euclid(b) != 0),
+            BinOp::Mod => {
+                let r = a.rem_euclid(b);
+                if b < 0 && r != 0 { r + b } else { r }
+            }
+            BinOp::And => a & b,
+            BinOp::Or => a | b,
+            BinOp::Xor => a ^ b,
+            BinOp::Shl => a.wrapping_shl(count),
+            BinOp::Sar => a >> count,
+            BinOp::Ror => (a as u64).rotate_right(count) as i64,
+        };
+        return Some(Int(value));
+    }
+    match (op, lhs, rhs) {
+        (BinOp::Add | BinOp::Or | BinOp::Xor, Int(0), x)
+        | (BinOp::Add | BinOp::Sub | BinOp::Or | BinOp::Xor | BinOp::Shl | BinOp::Sar | BinOp::Ror, x, Int(0))
+        | (BinOp::Mul, Int(1), x)
+        | (BinOp::Mul | BinOp::Div, x, Int(1))
+        | (BinOp::And, Int(-1), x)
+        | (BinOp::And, x, Int(-1)) => Some(x),
+        (BinOp::Mul | BinOp::And, Int(0), _) | (BinOp::Mul | BinOp::And, _, Int(0)) | (BinOp::Mod, _, Int(1)) => Some(Int(0)),
+        (BinOp::Sub | BinOp::Xor, Operand::Reg(a), Operand::Reg(b)) if a == b => Some(Int(0)),
+        _ => None,
+    }
+}
+
+fn compare(cond: Cond, ordering: Option<std::cmp::Ordering>) -> bool {
+    use std::cmp::Ordering::{Equal, Greater, Less};
+    match (cond, ordering) {
+        (Cond::Ne, None) => true,
+        (_, None) => false,
+        (Cond::Eq, Some(ordering)) => ordering == Equal,
+        (Cond::Ne, Some(ordering)) => ordering != Equal,
+        (Cond::Lt, Some(ordering)) => ordering == Less,
+        (Cond::Le, Some(ordering)) => ordering != Greater,
+        (Cond::Gt, Some(ordering)) => ordering == Greater,
+        (Cond::Ge, Some(ordering)) => ordering != Less,
+    }
+}
+
+// --------------------------- COPY PROPAGATION ---------------------------
+/// Replaces the uses of copied registers with the original operand, and removes block
+/// parameters that receive the same value on every incoming edge.
+pub struct CopyPropagation;
+
+impl Pass for CopyPropagation {
+    fn name(&self) -> &'static str {
+        "copy-prop"
+    }
+
+    fn run(&self, function: &mut Function) -> bool {
+        let mut copies: HashMap<VReg, Operand> = HashMap::new();
+        for inst in function.blocks.iter().flat_map(|block| &block.insts) {
+            if let Inst::Copy { dst, src, .. } = inst {
+                copies.insert(*dst, *src);
+            }
+        }
+        while propagate_params(function, &mut copies, |_| true) {}
+        remove_defs(function, &copies);
+        !copies.is_empty()
+    }
+}
+
+// --------------------------- DEAD CODE ELIMINATION ---------------------------
+/// Removes instructions without side effects whose result is never needed, and block
+/// parameters that are only passed around between blocks without being used.
+pub struct DeadCodeElimination;
+
+/// Where a register gets its value.
+#[derive(Clone, Copy)]
+enum Def {
+    Inst(usize, usize),
+    Param(usize, usize),
+}
+
+impl Pass for DeadCodeElimination {
+    fn name(&self) -> &'static str {
+        "dce"
+    }
+
+    fn run(&self, function: &mut Function) -> bool {
+        let mut defs = HashMap::new();
+        let index: HashMap<BlockId, usize> = block_indices(function);
+        let mut live: HashSet<VReg> = HashSet::new();
+        let mut work: Vec<VReg> = vec![];
+        let mark = |reg: Option<VReg>, live: &mut HashSet<VReg>, work: &mut Vec<VReg>| {
+            if let Some(reg) = reg
+                && live.insert(reg)
+            {
+                work.push(reg);
+            }
+        };
+
+        for (b, block) in function.blocks.iter().enumerate() {
+            for (p, (param, _)) in block.params.iter().enumerate() {
+                defs.insert(*param, Def::Param(b, p));
+            }
+            for (i, inst) in block.insts.iter().enumerate() {
+                if let Some((dst, _)) = inst.def() {
+                    defs.insert(dst, Def::Inst(b, i));
+                }
+                if inst.has_side_effects() {
+                    for operand in inst.operands() {
+                        mark(operand.reg(), &mut live, &mut work);
+                    }
+                }
+            }
+            // Arguments are only live if the parameter they are passed to is.
+            match &block.term {
+                Terminator::Branch { cond, .. } | Terminator::Return(Some(cond)) => mark(cond.reg(), &mut live, &mut work),
+                Terminator::Jump(_) | Terminator::Return(None) | Terminator::Trap(_) => {}
+            }
+        }
+
+        let mut edges: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+        for (b, block) in function.blocks.iter().enumerate() {
+            for (t, target) in block.term.targets().into_iter().enumerate() {
+                edges.entry(index[&target.block]).or_default().push((b, t));
+            }
+        }
+        while let Some(reg) = work.pop() {
+            match defs.get(&reg) {
+                Some(Def::Inst(b, i)) => {
+                    for operand in function.blocks[*b].insts[*i].operands() {
+                        mark(operand.reg(), &mut live, &mut work);
+                    }
+                }
+                Some(Def::Param(b, p)) => {
+                    for (from, t) in edges.get(b).into_iter().flatten() {
+                        let target = function.blocks[*from].term.targets()[*t];
+                        mark(target.args[*p].reg(), &mut live, &mut work);
+                    }
+                }
+                None => {}
+            }
+        }
+
+        let mut changed = false;
+        for block in &mut function.blocks {
+            let before = block.insts.len();
+            block.insts.retain(|inst| inst.has_side_effects() || inst.def().is_none_or(|(dst, _)| live.contains(&dst)));
+            changed |= block.insts.len() != before;
+        }
+        let mut dead: HashMap<BlockId, HashSet<usize>> = HashMap::new();
+        for block in &function.blocks {
+            for (index, (param, _)) in block.params.iter().enumerate() {
+                if !live.contains(param) {
+                    dead.entry(block.id).or_default().insert(index);
+                }
+            }
+        }
+        changed |= !dead.is_empty();
+        remove_params(function, &dead);
+        changed
+    }
+}
+
+// --------------------------- CFG SIMPLIFICATION ---------------------------
+/// Turns branches with a known outcome into jumps, removes unreachable blocks, skips blocks
+/// that only jump elsewhere, and merges blocks into their only predecessor.
+pub struct SimplifyCfg;
+
+impl Pass for SimplifyCfg {
+    fn name(&self) -> &'static str {
+        "simplify-cfg"
+    }
+
+    fn run(&self, function: &mut Function) -> bool {
+        let mut changed = false;
+        loop {
+            let progress = fold_branches(function)
+                || remove_unreachable(function)
+                || thread_jumps(function)
+                || merge_blocks(function);
+            if !progress {
+                return changed;
+            }
+            changed = true;
+        }
+    }
+}
+
+fn fold_branches(function: &mut Function) -> bool {
+    let mut changed = false;
+    for block in &mut function.blocks {
+        if let Terminator::Branch { cond, then_target, else_target } = &block.term {
+            let target = match cond {
+                Operand::Int(value) if *value != 0 => then_target,
+                Operand::Int(_) => else_target,
+                _ if then_target == else_target => then_target,
+                _ => continue,
+            };
+            block.term = Terminator::Jump(target.clone());
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn remove_unreachable(function: &mut Function) -> bool {
+    let reachable: HashSet<BlockId> = reverse_postorder(function).into_iter().collect();
+    let before = function.blocks.len();
+    function.blocks.retain(|block| reachable.contains(&block.id));
+    function.blocks.len() != before
+}
+
+/// Redirects jumps to a block that has no parameters or instructions straight to where that
+/// block jumps. Its arguments dominate it, so they are available in its predecessors too.
+fn thread_jumps(function: &mut Function) -> bool {
+    let entry = function.blocks[0].id;
+    let forward: HashMap<BlockId, Target> = function.blocks.iter()
+        .filter(|block| block.id != entry && block.params.is_empty() && block.insts.is_empty())
+        .filter_map(|block| match &block.term {
+            Terminator::Jump(target) => Some((block.id, target.clone())),
+            _ => None,
+        })
+        .collect();
+    // Follow chains of such blocks to their end, leaving cycles of them alone.
+    let mut destinations = HashMap::new();
+    for start in forward.keys() {
+        let mut seen = HashSet::from([*start]);
+        let mut target = &forward[start];
+        while let Some(next) = forward.get(&target.block) {
+            if !seen.insert(target.block) {
+                break;
+            }
+            target = next;
+        }
+        if !forward.contains_key(&target.block) {
+            destinations.insert(*start, target.clone());
+        }
+    }
+
+    let mut changed = false;
+    for block in &mut function.blocks {
+        for target in block.term.targets_mut() {
+            if let Some(destination) = destinations.get(&target.block) {
+                *target = destination.clone();
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// Appends a block to its predecessor when that predecessor is the only one and ends in a
+/// jump to it.
+fn merge_blocks(function: &mut Function) -> bool {
+    let entry = function.blocks[0].id;
+    let predecessors = predecessors(function);
+    let candidate = function.blocks.iter().find_map(|block| match &block.term {
+        Terminator::Jump(target)
+            if target.block != block.id && target.block != entry && predecessors[&target.block].len() == 1 =>
+        {
+            Some((block.id, target.clone()))
+        }
+        _ => None,
+    });
+    let Some((into, target)) = candidate else {
+        return false;
+    };
+
+    let position = function.blocks.iter().position(|block| block.id == target.block).unwrap();
+    let merged = function.blocks.remove(position);
+    let values: HashMap<VReg, Operand> = merged.params.iter().map(|(param, _)| *param).zip(target.args).collect();
+    let block = function.block_mut(into);
+    block.insts.extend(merged.insts);
+    block.term = merged.term;
+    substitute(function, &values);
+    true
+}
+
+// --------------------------- COMMON SUBEXPRESSIONS ---------------------------
+/// Reuses the result of an earlier, identical computation. Pure instructions are matched
+/// against everything that dominates them; loads only against earlier loads in the same block
+/// with no store, call or copy in between.
+pub struct CommonSubexpressionElimination;
+
+impl Pass for CommonSubexpressionElimination {
+    fn name(&self) -> &'static str {
+        "cse"
+    }
+
+    fn run(&self, function: &mut Function) -> bool {
+        let idom = dominators(function);
+        let mut children: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+        for block in reverse_postorder(function) {
+            if let Some(parent) = idom.get(&block)
+                && *parent != block
+            {
+                children.entry(*parent).or_default().push(block);
+            }
+        }
+
+        let mut replaced = HashMap::new();
+        let mut available: Vec<HashMap<String, VReg>> = vec![];
+        let mut stack = vec![(function.blocks[0].id, false)];
+        while let Some((id, done)) = stack.pop() {
+            if done {
+                available.pop();
+                continue;
+            }
+            let mut scope = HashMap::new();
+            let mut loads: HashMap<String, VReg> = HashMap::new();
+            for inst in &function.block(id).insts {
+                if matches!(inst, Inst::Store { .. } | Inst::Call { .. } | Inst::MemCopy { .. } | Inst::New { .. }) {
+                    loads.clear();
+                }
+                let Some((dst, _)) = inst.def() else { continue };
+                let Some(key) = expression_key(inst, &replaced) else { continue };
+                let table = if matches!(inst, Inst::Load { .. }) { &mut loads } else { &mut scope };
+                let earlier = available.iter().rev().find_map(|scope| scope.get(&key)).or_else(|| table.get(&key)).copied();
+                match earlier {
+                    Some(earlier) => {
+                        replaced.insert(dst, Operand::Reg(earlier));
+                    }
+                    None => {
+                        table.insert(key, dst);
+                    }
+                }
+            }
+            available.push(scope);
+            stack.push((id, true));
+            stack.extend(children.get(&id).into_iter().flatten().map(|child| (*child, false)));
+        }
+
+        remove_defs(function, &replaced);
+        !replaced.is_empty()
+    }
+}
+
+/// A key that is equal for instructions computing the same value, or `None` for instructions
+/// that cannot be reused.
+fn expression_key(inst: &Inst, replaced: &HashMap<VReg, Operand>) -> Option<String> {
+    let mut inst = inst.clone();
+    match &mut inst {
+        Inst::Unary { dst, .. }
+        | Inst::Binary { dst, .. }
+        | Inst::Cmp { dst, .. }
+        | Inst::Convert { dst, .. }
+        | Inst::Load { dst, .. }
+        | Inst::SlotAddr { dst, .. }
+        | Inst::GlobalAddr { dst, .. }
+        | Inst::FuncAddr { dst, .. }
+        | Inst::PtrAdd { dst, .. }
+        | Inst::TypeTest { dst, .. } => *dst = VReg(0),
+        _ => return None,
+    }
+    for operand in inst.operands_mut() {
+        *operand = resolve(replaced, *operand);
+    }
+    let commutative = match &inst {
+        Inst::Binary { op, .. } => matches!(op, BinOp::Add | BinOp::Mul | BinOp::And | BinOp::Or | BinOp::Xor),
+        Inst::Cmp { cond, .. } => matches!(cond, Cond::Eq | Cond::Ne),
+        _ => false,
+    };
+    if commutative
+        && let Inst::Binary { lhs, rhs, .. } | Inst::Cmp { lhs, rhs, .. } = &mut inst
+        && lhs.to_string() > rhs.to_string()
+    {
+        std::mem::swap(lhs, rhs);
+    }
+    Some(inst.to_string())
+}
+
+// --------------------------- ANALYSES ---------------------------
+fn block_indices(function: &Function) -> HashMap<BlockId, usize> {
+    function.blocks.iter().enumerate().map(|(i, block)| (block.id, i)).collect()
+}
+
+/// The predecessors of every block, once per edge.
+fn predecessors(function: &Function) -> HashMap<BlockId, Vec<BlockId>> {
+    let mut predecessors: HashMap<BlockId, Vec<BlockId>> =
+        function.blocks.iter().map(|block| (block.id, vec![])).collect();
+    for block in &function.blocks {
+        for target in block.term.targets() {
+            predecessors.entry(target.block).or_default().push(block.id);
+        }
+    }
+    predecessors
+}
+
+/// The blocks reachable from the entry, in reverse postorder.
+fn reverse_postorder(function: &Function) -> Vec<BlockId> {
+    let index = block_indices(function);
+    let mut visited = HashSet::new();
+    let mut order = vec![];
+    let Some(entry) = function.blocks.first() else {
+        return order;
+    };
+    let mut stack = vec![(entry.id, 0)];
+    visited.insert(entry.id);
+    while let Some((id, next)) = stack.pop() {
+        let targets = function.blocks[index[&id]].term.targets();
+        if let Some(target) = targets.get(next) {
+            stack.push((id, next + 1));
+            if visited.insert(target.block) {
+                stack.push((target.block, 0));
+            }
+        } else {
+            order.push(id);
+        }
+    }
+    order.reverse();
+    order
+}
+
+/// The immediate dominator of every reachable block, with the entry as its own (Cooper, Harvey
+/// and Kennedy's iterative algorithm).
+fn dominators(function: &Function) -> HashMap<BlockId, BlockId> {
+    let order = reverse_postorder(function);
+    let position: HashMap<BlockId, usize> = order.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    let predecessors = predecessors(function);
+    let mut idom: HashMap<BlockId, BlockId> = HashMap::new();
+    let Some(entry) = order.first() else {
+        return idom;
+    };
+    idom.insert(*entry, *entry);
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in &order[1..] {
+            let mut processed = predecessors[block].iter().filter(|p| idom.contains_key(p));
+            let Some(first) = processed.next() else { continue };
+            let mut new = *first;
+            for other in processed {
+                let (mut a, mut b) = (new, *other);
+                while a != b {
+                    while position[&a] > position[&b] {
+                        a = idom[&a];
+                    }
+                    while position[&b] > position[&a] {
+                        b = idom[&b];
+                    }
+                }
+                new = a;
+            }
+            if idom.insert(*block, new) != Some(new) {
+                changed = true;
+            }
+        }
+    }
+    idom
+}
+
+/// Adds to `values` the block parameters whose incoming arguments, seen through `values`, are
+/// all the same operand accepted by `accept` (ignoring the parameter being passed back to
+/// itself). Returns whether any were added.
+fn propagate_params(function: &Function, values: &mut HashMap<VReg, Operand>, accept: fn(&Operand) -> bool) -> bool {
+    let reachable: HashSet<BlockId> = reverse_postorder(function).into_iter().collect();
+    let mut incoming: HashMap<BlockId, Vec<&Vec<Operand>>> = HashMap::new();
+    for block in function.blocks.iter().filter(|block| reachable.contains(&block.id)) {
+        for target in block.term.targets() {
+            incoming.entry(target.block).or_default().push(&target.args);
+        }
+    }
+
+    let mut changed = false;
+    for block in &function.blocks {
+        let Some(edges) = incoming.get(&block.id) else { continue };
+        for (index, (param, _)) in block.params.iter().enumerate() {
+            if values.contains_key(param) {
+                continue;
+            }
+            let mut args = edges.iter()
+                .map(|args| resolve(values, args[index]))
+                .filter(|arg| *arg != Operand::Reg(*param));
+            let Some(first) = args.next() else { continue };
+            if args.all(|arg| same(&arg, &first)) && accept(&first) {
+                values.insert(*param, first);
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+// --------------------------- REWRITING ---------------------------
+/// Operands are the same value; unlike `==`, a NaN is the same as itself.
+fn same(a: &Operand, b: &Operand) -> bool {
+    match (a, b) {
+        (Operand::Real(a), Operand::Real(b)) => a.to_bits() == b.to_bits(),
+        _ => a == b,
+    }
+}
+
+fn resolve(values: &HashMap<VReg, Operand>, mut operand: Operand) -> Operand {
+    while let Operand::Reg(reg) = operand
+        && let Some(value) = values.get(&reg)
+    {
+        operand = *value;
+    }
+    operand
+}
+
+/// Replaces every use of a register in `values` with its value. Returns whether anything
+/// changed.
+fn substitute(function: &mut Function, values: &HashMap<VReg, Operand>) -> bool {
+    if values.is_empty() {
+        return false;
+    }
+    let mut changed = false;
+    for block in &mut function.blocks {
+        let operands = block.insts.iter_mut()
+            .flat_map(Inst::operands_mut)
+            .chain(block.term.operands_mut());
+        for operand in operands {
+            let value = resolve(values, *operand);
+            if !same(&value, operand) {
+                *operand = value;
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// Substitutes the registers in `values` and removes the instructions and block parameters
+/// defining them.
+fn remove_defs(function: &mut Function, values: &HashMap<VReg, Operand>) {
+    substitute(function, values);
+    let mut dead: HashMap<BlockId, HashSet<usize>> = HashMap::new();
+    for block in &mut function.blocks {
+        block.insts.retain(|inst| inst.def().is_none_or(|(dst, _)| !values.contains_key(&dst)));
+        for (index, (param, _)) in block.params.iter().enumerate() {
+            if values.contains_key(param) {
+                dead.entry(block.id).or_default().insert(index);
+            }
+        }
+    }
+    remove_params(function, &dead);
+}
+
+/// Removes the given parameters of blocks, along with the arguments passed for them.
+fn remove_params(function: &mut Function, dead: &HashMap<BlockId, HashSet<usize>>) {
+    let keep = |id: BlockId, index: usize| dead.get(&id).is_none_or(|dead| !dead.contains(&index));
+    for block in &mut function.blocks {
+        let id = block.id;
+        let mut index = 0;
+        block.params.retain(|_| {
+            index += 1;
+            keep(id, index - 1)
+        });
+        for target in block.term.targets_mut() {
+            let id = target.block;
+            let mut index = 0;
+            target.args.retain(|_| {
+                index += 1;
+                keep(id, index - 1)
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+    use crate::backend::code_generator;
+    use crate::frontend::{analysis, ir_generator};
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+    use crate::ir;
+
+    /// Runs `pass` over the single function in `text` and prints the result.
+    fn optimize(pass: &dyn Pass, text: &str) -> (bool, String) {
+        let mut module = ir::parse(&format!("module T\n\n{text}")).unwrap();
+        let changed = pass.run(&mut module.functions[0]);
+        (changed, module.functions[0].to_string())
+    }
+
+    fn lower(source: &str) -> ir::Module {
+        let module = Parser::new(Lexer::new(source)).parse().unwrap();
+        let analysis = analysis::check(&module).unwrap();
+        ir_generator::generate(&module, &analysis)
+    }
+
+    #[test]
+    fn folds_constants_and_identities() {
+        let (changed, output) = optimize(&ConstantFolding, "\
+func @f(%0: i64) -> i64 {
+bb0:
+  %1 = add i64 2, 3
+  %2 = mul i64 %1, 4
+  %3 = add i64 %0, 0
+  %4 = div i64 -7, 2
+  %5 = mod i64 -7, 2
+  %6 = div i64 %0, 0
+  %7 = add i64 %2, %4
+  %8 = add i64 %7, %5
+  %9 = add i64 %8, %3
+  %10 = add i64 %9, %6
+  %11 = itof %10
+  %12 = floor -2.5
+  %13 = cmp ne f64 %11, %11
+  %14 = add i64 %12, %13
+  ret %14
+}
+");
+        assert!(changed);
+        assert_eq!(output, "\
+func @f(%0: i64) -> i64 {
+bb0:
+  %6 = div i64 %0, 0
+  %9 = add i64 17, %0
+  %10 = add i64 %9, %6
+  %11 = itof %10
+  %13 = cmp ne f64 %11, %11
+  %14 = add i64 -3, %13
+  ret %14
+}
+");
+    }
+
+    #[test]
+    fn propagates_constants_through_branches() {
+        let mut module = ir::parse("\
+module T
+
+func @g() -> i64 {
+bb0:
+  %0 = cmp lt i64 1, 2
+  br %0, bb1(5), bb2(5)
+bb1(%1: i64):
+  jump bb3(%1)
+bb2(%2: i64):
+  jump bb3(%2)
+bb3(%3: i64):
+  ret %3
+}
+").unwrap();
+        PassManager::for_level(1).run(&mut module);
+        assert_eq!(module.functions[0].to_string(), "func @g() -> i64 {\nbb0:\n  ret 5\n}\n");
+    }
+
+    #[test]
+    fn propagates_copies_into_loops() {
+        let (changed, output) = optimize(&CopyPropagation, "\
+func @h(%0: i64) -> i64 {
+bb0:
+  %1 = copy i64 %0
+  jump bb1(%1)
+bb1(%2: i64):
+  %3 = cmp lt i64 %2, 10
+  br %3, bb1(%2), bb2
+bb2:
+  ret %2
+}
+");
+        assert!(changed);
+        assert_eq!(output, "\
+func @h(%0: i64) -> i64 {
+bb0:
+  jump bb1
+bb1:
+  %3 = cmp lt i64 %0, 10
+  br %3, bb1, bb2
+bb2:
+  ret %0
+}
+");
+    }
+
+    #[test]
+    fn removes_dead_values_and_parameters() {
+        let (changed, output) = optimize(&DeadCodeElimination, "\
+func @k(%0: ptr) {
+bb0:
+  %1 = load i64 %0
+  %2 = add i64 %1, 1
+  %3 = div i64 %1, 0
+  jump bb1(%2, %1)
+bb1(%4: i64, %5: i64):
+  %6 = add i64 %4, 1
+  %7 = mul i64 %6, 2
+  store i64 %5, %0
+  ret
+}
+");
+        assert!(changed);
+        assert_eq!(output, "\
+func @k(%0: ptr) {
+bb0:
+  %1 = load i64 %0
+  %3 = div i64 %1, 0
+  jump bb1(%1)
+bb1(%5: i64):
+  store i64 %5, %0
+  ret
+}
+");
+        assert!(!optimize(&DeadCodeElimination, &output).0);
+    }
+
+    #[test]
+    fn simplifies_control_flow() {
+        let (changed, output) = optimize(&SimplifyCfg, "\
+func @s(%0: i64) -> i64 {
+bb0:
+  br %0, bb1, bb1
+bb1:
+  jump bb2
+bb2:
+  br 0, bb3, bb4(%0)
+bb3:
+  ret 1
+bb4(%1: i64):
+  %2 = add i64 %1, 1
+  br %2, bb5, bb6
+bb5:
+  jump bb7
+bb6:
+  jump bb7
+bb7:
+  ret %2
+}
+");
+        assert!(changed);
+        assert_eq!(output, "\
+func @s(%0: i64) -> i64 {
+bb0:
+  %2 = add i64 %0, 1
+  ret %2
+}
+");
+    }
+
+    #[test]
+    fn leaves_empty_infinite_loops() {
+        let (_, output) = optimize(&SimplifyCfg, "\
+func @spin() {
+bb0:
+  jump bb1
+bb1:
+  jump bb2
+bb2:
+  jump bb1
+}
+");
+        assert_eq!(output, "func @spin() {\nbb0:\n  jump bb1\nbb1:\n  jump bb1\n}\n");
+    }
+
+    #[test]
+    fn eliminates_dominated_subexpressions() {
+        let (changed, output) = optimize(&CommonSubexpressionElimination, "\
+func @c(%0: i64, %1: ptr) -> i64 {
+bb0:
+  %2 = mul i64 %0, 3
+  %3 = load i64 %1
+  %4 = load i64 %1
+  store i64 %2, %1
+  %5 = load i64 %1
+  %6 = cmp lt i64 %3, %4
+  br %6, bb1, bb2
+bb1:
+  %7 = mul i64 3, %0
+  %8 = add i64 %7, %5
+  ret %8
+bb2:
+  %9 = add i64 %5, %2
+  ret %9
+}
+");
+        assert!(changed);
+        assert_eq!(output, "\
+func @c(%0: i64, %1: ptr) -> i64 {
+bb0:
+  %2 = mul i64 %0, 3
+  %3 = load i64 %1
+  store i64 %2, %1
+  %5 = load i64 %1
+  %6 = cmp lt i64 %3, %3
+  br %6, bb1, bb2
+bb1:
+  %8 = add i64 %2, %5
+  ret %8
+bb2:
+  %9 = add i64 %5, %2
+  ret %9
+}
+");
+    }
+
+    #[test]
+    fn dumps_around_every_pass() {
+        let mut module = lower("MODULE M; VAR x: INTEGER; BEGIN x := 2 + 3 END M.");
+        let manager = PassManager::for_level(1);
+        let mut dumps = vec![];
+        manager.run_with_dump(&mut module, &mut |pass, stage, function| {
+            dumps.push((pass.to_string(), stage, function.to_string()));
+        });
+        let names: Vec<&str> = dumps.iter().map(|(pass, _, _)| pass.as_str()).collect();
+        assert_eq!(names, ["const-fold", "const-fold", "copy-prop", "copy-prop", "simplify-cfg", "simplify-cfg", "dce", "dce"]);
+        assert_eq!(dumps[0].1, Stage::Before);
+        assert!(matches!(dumps[1].1, Stage::After { .. }));
+        assert_eq!(dumps.last().unwrap().2, module.functions[0].to_string());
+        assert!(PassManager::for_level(0).pass_names().is_empty());
+    }
+
+    #[test]
+    fn folds_module_body_down_to_stores() {
+        let mut module = lower("MODULE M; VAR x*: INTEGER; BEGIN IF 3 > 2 THEN x := 6 * 7 ELSE x := 0 END END M.");
+        PassManager::for_level(2).run(&mut module);
+        let body = module.functions.iter().find(|function| function.name == "M__init").unwrap();
+        // The entry block checks whether the body ran already; the IF is gone.
+        assert_eq!(body.blocks.len(), 3, "{body}");
+        assert!(body.to_string().contains("store i64 42, "), "{body}");
+    }
+
+    /// Builds `source` at every level and checks that the programs print the same thing.
+    fn assert_same_behaviour(source: &str, declarations: &str, body: &str) -> String {
+        let harness = format!("#include <stdio.h>\n{declarations}\nvoid M__init(void);\nint main(void) {{ M__init(); {body} return 0; }}\n");
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("harness.c"), &harness).unwrap();
+        let mut outputs = vec![];
+        for level in 0..=2 {
+            let mut module = lower(source);
+            PassManager::for_level(level).run(&mut module);
+            let assembly = code_generator::generate(&module);
+            let file = dir.path().join(format!("module{level}.s"));
+            let program = dir.path().join(format!("program{level}"));
+            fs::write(&file, &assembly).unwrap();
+            let compiled = Command::new("cc").arg("-o").arg(&program).arg(&file).arg(dir.path().join("harness.c")).output().unwrap();
+            assert!(compiled.status.success(), "{}\n{assembly}", String::from_utf8_lossy(&compiled.stderr));
+            let output = Command::new(&program).output().unwrap();
+            outputs.push((output.status.code(), String::from_utf8(output.stdout).unwrap()));
+        }
+        assert_eq!(outputs[0], outputs[1]);
+        assert_eq!(outputs[0], outputs[2]);
+        outputs.remove(0).1
+    }
+
+    #[test]
+    fn preserves_behaviour_of_programs() {
+        let output = assert_same_behaviour(r#"
+            MODULE M;
+            TYPE Node = POINTER TO NodeDesc; NodeDesc = RECORD value: INTEGER; next: Node END;
+            VAR sum*, product*, count*, q*, r*: INTEGER; x*: REAL; s*: SET;
+              list: Node; i: INTEGER; a: ARRAY 8 OF INTEGER;
+            PROCEDURE Push(VAR list: Node; value: INTEGER);
+              VAR n: Node;
+            BEGIN NEW(n); n.value := value; n.next := list; list := n
+            END Push;
+            PROCEDURE Fib(n: INTEGER): INTEGER;
+              VAR result: INTEGER;
+            BEGIN IF n < 2 THEN result := n ELSE result := Fib(n - 1) + Fib(n - 2) END
+            RETURN result
+            END Fib;
+            BEGIN
+              FOR i := 0 TO LEN(a) - 1 DO a[i] := i * i - 3 * i; Push(list, a[i] + 0) END;
+              sum := 0; product := 1; count := 0;
+              WHILE list # NIL DO
+                sum := sum + list.value * 1;
+                IF list.value # 0 THEN product := product * list.value END;
+                IF (list.value > 2) & (list.value > 2) OR FALSE THEN INC(count) END;
+                list := list.next
+              END;
+              q := (-7 + sum) DIV 2; r := (-7 + sum) MOD 2;
+              x := FLT(Fib(15)) / 2.0 * 1.0;
+              s := {1, 3..5} + {i MOD 64} - {4}
+            END M.
+        "#, "extern long M_sum, M_product, M_count, M_q, M_r; extern double M_x; extern unsigned long M_s;",
+            r#"printf("%ld %ld %ld %ld %ld %g %lx\n", M_sum, M_product, M_count, M_q, M_r, M_x, M_s);"#);
+        assert_eq!(output, "56 80640 4 24 1 305 12a\n");
+    }
+}
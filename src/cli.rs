@@ -0,0 +1,332 @@
+use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use crate::frontend::literal::IntegerWidth;
+
+#[derive(Debug, Parser)]
+#[command(name = "oberon-compiler", version, about = "An Oberon-07 compiler")]
+pub struct Cli {
+    #[command(flatten)]
+    pub verbosity: Verbosity,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Compile a module and write the generated code
+    Build(BuildArgs),
+    /// Compile a main module and the modules it imports, in dependency order
+    Project(ProjectArgs),
+    /// Run the frontend (lexer, parser, analysis) without writing output
+    Check(CheckArgs),
+    /// Print the AST of a module
+    Parse(ParseArgs),
+    /// Print the token stream of a module
+    Tokens(TokensArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct BuildArgs {
+    /// Oberon source file
+    pub input: PathBuf,
+
+    /// File to write the generated code to; the module's symbol file is written next to it
+    #[arg(short, long)]
+    pub output: PathBuf,
+
+    /// Directory to search for the symbol files of imported modules (repeatable)
+    #[arg(short = 'I', long = "import-path", value_name = "DIR")]
+    pub import_paths: Vec<PathBuf>,
+
+    /// Kind of output to generate
+    #[arg(long, value_enum, default_value_t = Emit::Asm)]
+    pub emit: Emit,
+
+    /// Also emit a `main` function that runs the module body, so the output links into a program
+    #[arg(long)]
+    pub main: bool,
+
+    /// Optimization level for assembly output
+    #[arg(short = 'O', value_enum, default_value_t = OptLevel::O0)]
+    pub opt_level: OptLevel,
+
+    /// Print the IR of every function before and after each optimization pass
+    #[arg(long)]
+    pub dump_ir: bool,
+
+    /// Check integer literals and constant expressions against the range of 32- or 64-bit INTEGERs;
+    /// the generated code has 64-bit INTEGERs either way
+    #[arg(long, value_enum, value_name = "BITS", default_value_t = Width::W64)]
+    pub literal_range: Width,
+}
+
+#[derive(Debug, Args)]
+pub struct ProjectArgs {
+    /// Oberon source file of the main module
+    pub input: PathBuf,
+
+    /// Directory to write the generated code and symbol file of every module to
+    #[arg(short, long, value_name = "DIR")]
+    pub output_dir: PathBuf,
+
+    /// Directory to search for the sources and symbol files of imported modules (repeatable)
+    #[arg(short = 'I', long = "import-path", value_name = "DIR")]
+    pub import_paths: Vec<PathBuf>,
+
+    /// Compile every module, including those whose output is up to date
+    #[arg(long)]
+    pub force: bool,
+
+    /// Kind of output to generate
+    #[arg(long, value_enum, default_value_t = Emit::Asm)]
+    pub emit: Emit,
+
+    /// Optimization level for assembly output
+    #[arg(short = 'O', value_enum, default_value_t = OptLevel::O0)]
+    pub opt_level: OptLevel,
+
+    /// Print the IR of every function before and after each optimization pass
+    #[arg(long)]
+    pub dump_ir: bool,
+
+    /// Check integer literals and constant expressions against the range of 32- or 64-bit INTEGERs;
+    /// the generated code has 64-bit INTEGERs either way
+    #[arg(long, value_enum, value_name = "BITS", default_value_t = Width::W64)]
+    pub literal_range: Width,
+}
+
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    /// Oberon source file
+    pub input: PathBuf,
+
+    /// Directory to search for the symbol files of imported modules (repeatable)
+    #[arg(short = 'I', long = "import-path", value_name = "DIR")]
+    pub import_paths: Vec<PathBuf>,
+
+    /// How errors are reported
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+
+    /// Check integer literals and constant expressions against the range of 32- or 64-bit INTEGERs;
+    /// the generated code has 64-bit INTEGERs either way
+    #[arg(long, value_enum, value_name = "BITS", default_value_t = Width::W64)]
+    pub literal_range: Width,
+}
+
+#[derive(Debug, Args)]
+pub struct ParseArgs {
+    /// Oberon source file
+    pub input: PathBuf,
+
+    /// How the AST is printed
+    #[arg(long, value_enum, default_value_t = AstFormat::Pretty)]
+    pub format: AstFormat,
+
+    /// Check integer literals and constant expressions against the range of 32- or 64-bit INTEGERs;
+    /// the generated code has 64-bit INTEGERs either way
+    #[arg(long, value_enum, value_name = "BITS", default_value_t = Width::W64)]
+    pub literal_range: Width,
+}
+
+#[derive(Debug, Args)]
+pub struct TokensArgs {
+    /// Oberon source file
+    pub input: PathBuf,
+
+    /// Also print comments and whitespace
+    #[arg(long)]
+    pub trivia: bool,
+
+    /// How tokens are printed
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Emit {
+    /// GNU assembler text
+    Asm,
+    /// A C99 source file, with the module's header written next to it
+    C,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OptLevel {
+    /// No optimization
+    #[value(name = "0")]
+    O0,
+    /// Constant folding, copy propagation, CFG simplification and dead code elimination
+    #[value(name = "1")]
+    O1,
+    /// As -O1, plus common subexpression elimination, repeated until nothing changes
+    #[value(name = "2")]
+    O2,
+}
+
+impl OptLevel {
+    pub fn level(self) -> u8 {
+        match self {
+            OptLevel::O0 => 0,
+            OptLevel::O1 => 1,
+            OptLevel::O2 => 2,
+        }
+    }
+}
+
+/// The INTEGER range `--literal-range` checks constants against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Width {
+    /// 32-bit INTEGERs
+    #[value(name = "32")]
+    W32,
+    /// 64-bit INTEGERs
+    #[value(name = "64")]
+    W64,
+}
+
+impl Width {
+    pub fn integer_width(self) -> IntegerWidth {
+        match self {
+            Width::W32 => IntegerWidth::Bits32,
+            Width::W64 => IntegerWidth::Bits64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Human readable, one entry per line
+    Text,
+    /// Rust debug representation
+    Debug,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AstFormat {
+    /// Indented debug tree
+    Pretty,
+    /// Single line debug representation
+    Compact,
+}
+
+#[derive(Debug, Clone, Copy, Args)]
+pub struct Verbosity {
+    /// Print progress information (repeat for more detail)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Only print errors
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+}
+
+impl Verbosity {
+    pub fn level(&self) -> i8 {
+        if self.quiet { -1 } else { self.verbose as i8 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("oberon-compiler").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn parses_build_with_output() {
+        let cli = parse(&["build", "m.Mod", "-o", "m.s"]).unwrap();
+        let Command::Build(args) = cli.command else { panic!("build"); };
+        assert_eq!(args.input, PathBuf::from("m.Mod"));
+        assert_eq!(args.output, PathBuf::from("m.s"));
+        assert_eq!(args.emit, Emit::Asm);
+        assert!(!args.main);
+        assert_eq!(args.opt_level, OptLevel::O0);
+        assert!(!args.dump_ir);
+        assert!(args.import_paths.is_empty());
+    }
+
+    #[test]
+    fn parses_repeated_import_paths() {
+        let cli = parse(&["build", "m.Mod", "-o", "m.s", "-I", "lib", "--import-path", "vendor"]).unwrap();
+        let Command::Build(args) = cli.command else { panic!("build"); };
+        assert_eq!(args.import_paths, [PathBuf::from("lib"), PathBuf::from("vendor")]);
+        let cli = parse(&["check", "m.Mod", "-Ilib"]).unwrap();
+        let Command::Check(args) = cli.command else { panic!("check"); };
+        assert_eq!(args.import_paths, [PathBuf::from("lib")]);
+    }
+
+    #[test]
+    fn parses_project_with_output_directory() {
+        let cli = parse(&["project", "Main.Mod", "-o", "out", "-I", "lib", "--emit", "c", "--force"]).unwrap();
+        let Command::Project(args) = cli.command else { panic!("project"); };
+        assert_eq!(args.input, PathBuf::from("Main.Mod"));
+        assert_eq!(args.output_dir, PathBuf::from("out"));
+        assert_eq!(args.import_paths, [PathBuf::from("lib")]);
+        assert_eq!(args.emit, Emit::C);
+        assert!(args.force);
+        let err = parse(&["project", "Main.Mod"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn parses_optimization_level() {
+        let cli = parse(&["build", "m.Mod", "-o", "m.s", "-O2", "--dump-ir"]).unwrap();
+        let Command::Build(args) = cli.command else { panic!("build"); };
+        assert_eq!(args.opt_level, OptLevel::O2);
+        assert!(args.dump_ir);
+        let err = parse(&["build", "m.Mod", "-o", "m.s", "-O3"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn parses_literal_range() {
+        let cli = parse(&["check", "m.Mod", "--literal-range", "32"]).unwrap();
+        let Command::Check(args) = cli.command else { panic!("check"); };
+        assert_eq!(args.literal_range.integer_width(), IntegerWidth::Bits32);
+        let cli = parse(&["build", "m.Mod", "-o", "m.s"]).unwrap();
+        let Command::Build(args) = cli.command else { panic!("build"); };
+        assert_eq!(args.literal_range.integer_width(), IntegerWidth::Bits64);
+        let err = parse(&["parse", "m.Mod", "--literal-range", "16"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn parses_c_emit() {
+        let cli = parse(&["build", "m.Mod", "-o", "m.c", "--emit", "c", "--main"]).unwrap();
+        let Command::Build(args) = cli.command else { panic!("build"); };
+        assert_eq!(args.emit, Emit::C);
+        assert!(args.main);
+    }
+
+    #[test]
+    fn build_requires_output() {
+        let err = parse(&["build", "m.Mod"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn parses_global_verbosity_after_subcommand() {
+        let cli = parse(&["check", "m.Mod", "-vv"]).unwrap();
+        assert_eq!(cli.verbosity.level(), 2);
+        let cli = parse(&["-q", "tokens", "m.Mod", "--format", "debug"]).unwrap();
+        assert_eq!(cli.verbosity.level(), -1);
+        let Command::Tokens(args) = cli.command else { panic!("tokens"); };
+        assert_eq!(args.format, ReportFormat::Debug);
+    }
+
+    #[test]
+    fn rejects_unknown_flag() {
+        let err = parse(&["parse", "m.Mod", "--frobnicate"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnknownArgument);
+    }
+
+    #[test]
+    fn rejects_missing_subcommand() {
+        assert!(parse(&[]).is_err());
+    }
+}
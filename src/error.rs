@@ -1,6 +1,12 @@
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
+use crate::frontend::analysis::AnalysisError;
+use crate::frontend::diagnostics::Diagnostic;
+use crate::frontend::lexer::LexerError;
+use crate::frontend::parser::ParserError;
+use crate::frontend::symbol_file::SymbolFileError;
+use crate::project::ProjectError;
 
 #[derive(Debug, Error)]
 pub enum CompilerError {
@@ -17,4 +23,60 @@ pub enum CompilerError {
         #[source]
         source: std::string::FromUtf8Error,
     },
+
+    #[error("Invalid symbol file: {path}")]
+    SymbolFile {
+        path: PathBuf,
+        #[source]
+        source: SymbolFileError,
+    },
+
+    #[error("Lexical error in {path}")]
+    Lexer {
+        path: PathBuf,
+        #[source]
+        source: LexerError,
+    },
+
+    #[error("Parsing {path} failed with {} syntax error(s)", errors.len())]
+    Parser {
+        path: PathBuf,
+        errors: Vec<ParserError>,
+    },
+
+    #[error("Semantic analysis of {path} failed with {} error(s)", errors.len())]
+    Analysis {
+        path: PathBuf,
+        errors: Vec<AnalysisError>,
+    },
+
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+}
+
+impl CompilerError {
+    /// The error for a source file that failed to parse; a single lexical error is reported as such.
+    pub fn from_parser(path: PathBuf, mut errors: Vec<ParserError>) -> Self {
+        if let [ParserError::Lexer(_)] = errors.as_slice()
+            && let Some(ParserError::Lexer(source)) = errors.pop()
+        {
+            return CompilerError::Lexer { path, source };
+        }
+        CompilerError::Parser { path, errors }
+    }
+
+    /// The diagnostics to show for an error in source files, each with the file it points into.
+    pub fn diagnostics(&self) -> Vec<(&Path, Diagnostic)> {
+        match self {
+            CompilerError::Lexer { path, source } => in_file(path, [source.into()]),
+            CompilerError::Parser { path, errors } => in_file(path, errors.iter().map(Diagnostic::from)),
+            CompilerError::Analysis { path, errors } => in_file(path, errors.iter().map(Diagnostic::from)),
+            CompilerError::Project(error) => error.diagnostics(),
+            CompilerError::Io { .. } | CompilerError::Utf8 { .. } | CompilerError::SymbolFile { .. } => vec![],
+        }
+    }
+}
+
+fn in_file(path: &Path, diagnostics: impl IntoIterator<Item = Diagnostic>) -> Vec<(&Path, Diagnostic)> {
+    diagnostics.into_iter().map(|diagnostic| (path, diagnostic)).collect()
 }
@@ -0,0 +1,2136 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+use crate::frontend::ast;
+use crate::frontend::ast::{BinaryOperation, Case, ConstDeclaration, Declarations, Designator, Element, Expression, FormalParameters, FormalType, Identifier, IdentifierDef, Import, Label, LabelValue, Module, ProcedureDeclaration, QualifiedIdentifier, Selector, Statement, StatementSequence, TypeDeclaration, UnaryOperation, VarDeclaration};
+use crate::frontend::const_eval::{self, ConstError, Value, SET_BITS};
+use crate::frontend::literal::IntegerWidth;
+use crate::frontend::span::{Span, Spanned};
+use crate::frontend::symbol_file::{Fingerprint, SymbolFile};
+use crate::frontend::symbols::{Builtin, ScopeId, ScopeKind, Symbol, SymbolId, SymbolKind, SymbolTable};
+use crate::frontend::types::{Param, Type, TypeId, TypeTable, BOOLEAN, BYTE, CHAR, ERROR, INTEGER, NIL, NO_TYPE, REAL, SET};
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum AnalysisError {
+    #[error("module '{expected}' is closed with 'END {found}'")]
+    ModuleNameMismatch {
+        expected: String,
+        found: String,
+        span: Span,
+        /// The name in the module header.
+        declared: Span,
+    },
+
+    #[error("procedure '{expected}' is closed with 'END {found}'")]
+    ProcedureNameMismatch {
+        expected: String,
+        found: String,
+        span: Span,
+        /// The name in the procedure heading.
+        declared: Span,
+    },
+
+    #[error("undeclared identifier '{name}'")]
+    Undeclared {
+        name: String,
+        span: Span,
+    },
+
+    #[error("'{name}' is already declared")]
+    Redeclared {
+        name: String,
+        span: Span,
+        previous: Span,
+    },
+
+    #[error("'{name}' is not a type")]
+    NotAType {
+        name: String,
+        span: Span,
+    },
+
+    #[error("module '{module}' has no exported member '{name}'")]
+    UnresolvedImport {
+        module: String,
+        name: String,
+        span: Span,
+    },
+
+    #[error("cannot find module '{module}'")]
+    UnknownModule {
+        module: String,
+        span: Span,
+    },
+
+    #[error("module '{module}' was compiled against a different version of '{changed}'")]
+    StaleImport {
+        module: String,
+        /// The qualified name of the export that changed or was removed.
+        changed: String,
+        span: Span,
+    },
+
+    #[error("only module level declarations can be exported")]
+    InvalidExport {
+        span: Span,
+    },
+
+    #[error("expected {expected}, found {found}")]
+    TypeMismatch {
+        expected: String,
+        found: String,
+        span: Span,
+    },
+
+    #[error("operator '{op}' cannot be applied to {lhs} and {rhs}")]
+    IncompatibleOperands {
+        op: String,
+        lhs: String,
+        rhs: String,
+        span: Span,
+    },
+
+    #[error("operator '{op}' cannot be applied to {operand}")]
+    InvalidOperand {
+        op: String,
+        operand: String,
+        span: Span,
+    },
+
+    #[error(transparent)]
+    Constant(#[from] ConstError),
+
+    #[error("expression is not a value")]
+    NotAValue {
+        span: Span,
+    },
+
+    #[error("cannot assign to this designator")]
+    NotAssignable {
+        span: Span,
+    },
+
+    #[error("expression is not a procedure")]
+    NotAProcedure {
+        span: Span,
+    },
+
+    #[error("function procedure called as a statement")]
+    FunctionAsStatement {
+        span: Span,
+    },
+
+    #[error("proper procedure used in an expression")]
+    ProcedureAsValue {
+        span: Span,
+    },
+
+    #[error("local procedure '{name}' cannot be used as a value")]
+    LocalProcedureAsValue {
+        name: String,
+        span: Span,
+    },
+
+    #[error("expected {expected} argument(s), found {found}")]
+    ArgumentCount {
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+
+    #[error("{ty} has no field '{field}'")]
+    UnknownField {
+        field: String,
+        ty: String,
+        span: Span,
+    },
+
+    #[error("selector cannot be applied to {ty}")]
+    InvalidSelector {
+        ty: String,
+        span: Span,
+    },
+
+    #[error("index {index} is out of range for array of length {length}")]
+    IndexOutOfRange {
+        index: i64,
+        length: i64,
+        span: Span,
+    },
+
+    #[error("{ty} is not an extension of {base}")]
+    NotAnExtension {
+        ty: String,
+        base: String,
+        span: Span,
+    },
+
+    #[error("array length must be a positive integer constant")]
+    InvalidArrayLength {
+        span: Span,
+    },
+
+    #[error("invalid type: {reason}")]
+    InvalidType {
+        reason: String,
+        span: Span,
+    },
+
+    #[error("function procedure '{name}' must end with RETURN")]
+    MissingReturn {
+        name: String,
+        span: Span,
+    },
+
+    #[error("proper procedure '{name}' cannot return a value")]
+    UnexpectedReturn {
+        name: String,
+        span: Span,
+    },
+
+    #[error("case label is used more than once")]
+    DuplicateCaseLabel {
+        span: Span,
+    },
+
+    #[error("FOR step must be a non-zero integer constant")]
+    InvalidForStep {
+        span: Span,
+    },
+}
+
+// --------------------------- CHECKER ---------------------------
+/// How a checked expression or designator can be used.
+#[derive(Debug, Clone, PartialEq)]
+enum Mode {
+    Const(Value),
+    Var { read_only: bool },
+    Value,
+    Type,
+    /// A declared procedure; `local` when it is declared inside another procedure.
+    Procedure { local: bool },
+    Builtin(Builtin),
+    Module(String),
+}
+
+#[derive(Debug, Clone)]
+struct Item {
+    mode: Mode,
+    ty: TypeId,
+    /// A VAR parameter, type guards aside, whose actual record may be an extension of its type.
+    var_param: bool,
+}
+
+impl Item {
+    fn new(mode: Mode, ty: TypeId) -> Self { Self { mode, ty, var_param: false } }
+
+    fn error() -> Self { Self { mode: Mode::Value, ty: ERROR, var_param: false } }
+
+    fn value(&self) -> Option<&Value> {
+        match &self.mode {
+            Mode::Const(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// What the analysis found out about one checked expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpressionInfo {
+    pub ty: TypeId,
+    /// The folded value of a constant expression.
+    pub value: Option<Value>,
+}
+
+/// The result of a successful analysis.
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub symbols: SymbolTable,
+    pub types: TypeTable,
+    /// Every checked expression, keyed by its span.
+    pub expressions: HashMap<Span, ExpressionInfo>,
+    /// The type of each designator head and of the designator after each of its selectors, keyed
+    /// by the span of the head or selector. Inside a type CASE the head has the narrowed type.
+    pub designators: HashMap<Span, TypeId>,
+    /// The spans of designator heads `a.b` whose `a` is not a module, so that `b` selects a field.
+    pub field_heads: HashSet<Span>,
+    /// The spans of trailing type guards `p(x)` that are calls of `p` with the argument `x`.
+    pub guard_calls: HashSet<Span>,
+    /// The fingerprints of the imported modules and of their exports, from their symbol files.
+    pub fingerprints: HashMap<SymbolId, Fingerprint>,
+}
+
+impl Analysis {
+    /// A designator and its actual parameters as the checker read them, which the parser cannot
+    /// always tell: see `field_heads` and `guard_calls`.
+    pub fn designator<'d>(&self, designator: &'d Designator, actual_parameters: Option<&'d [Expression]>) -> (Cow<'d, Designator>, Option<Cow<'d, [Expression]>>) {
+        let field_head = self.field_heads.contains(&designator.head.span());
+        let guard_call = designator.selectors.last().is_some_and(|selector| self.guard_calls.contains(&selector.span()));
+        read_designator(designator, actual_parameters, field_head, guard_call)
+    }
+}
+
+/// Moves the second part of the head into a field selector when `field_head`, and a trailing type
+/// guard into the actual parameters when `guard_call`; the designator is only copied if needed.
+fn read_designator<'d>(
+    designator: &'d Designator,
+    actual_parameters: Option<&'d [Expression]>,
+    field_head: bool,
+    guard_call: bool,
+) -> (Cow<'d, Designator>, Option<Cow<'d, [Expression]>>) {
+    let mut arguments = actual_parameters.map(Cow::Borrowed);
+    if !field_head && !guard_call {
+        return (Cow::Borrowed(designator), arguments);
+    }
+    let mut read = designator.clone();
+    if field_head {
+        let field = read.head.parts.pop().expect("a qualified head has two parts");
+        read.selectors.insert(0, Selector::Field(field));
+    }
+    if guard_call && let Some(Selector::TypeGuard(name, _)) = read.selectors.pop() {
+        let span = name.span();
+        let argument = Expression::Designator {
+            designator: Designator { head: name, selectors: vec![], span },
+            actual_parameters: None,
+            span,
+        };
+        arguments = Some(Cow::Owned(vec![argument]));
+    }
+    (Cow::Owned(read), arguments)
+}
+
+struct Checker {
+    types: TypeTable,
+    symbols: SymbolTable,
+    scope: ScopeId,
+    /// Variables regarded as having an extended type inside a type CASE branch.
+    narrowed: Vec<(SymbolId, TypeId)>,
+    expressions: HashMap<Span, ExpressionInfo>,
+    designators: HashMap<Span, TypeId>,
+    field_heads: HashSet<Span>,
+    guard_calls: HashSet<Span>,
+    fingerprints: HashMap<SymbolId, Fingerprint>,
+    /// The width constant expressions are folded in.
+    integer_width: IntegerWidth,
+    errors: Vec<AnalysisError>,
+}
+
+/// Checks a module that imports nothing; see `check_with_imports`.
+pub fn check(module: &Module) -> Result<Analysis, Vec<AnalysisError>> {
+    check_with_imports(module, &[])
+}
+
+/// Checks `module` against the interfaces of the modules it imports. An import without a symbol
+/// file in `imports` is an error, and so is one compiled against another version of an interface
+/// in `imports` than the one there; `imports` may hold more symbol files than are imported, for
+/// that check.
+pub fn check_with_imports(module: &Module, imports: &[SymbolFile]) -> Result<Analysis, Vec<AnalysisError>> {
+    check_with_integer_width(module, imports, IntegerWidth::default())
+}
+
+/// Checks `module` as `check_with_imports` does, folding constant expressions with INTEGERs of
+/// `width` bits; a constant that does not fit is an overflow. `module` should have been parsed
+/// with the same width.
+pub fn check_with_integer_width(module: &Module, imports: &[SymbolFile], width: IntegerWidth) -> Result<Analysis, Vec<AnalysisError>> {
+    let mut checker = Checker::new(width);
+    checker.check_module(module, imports);
+
+    if checker.errors.is_empty() {
+        Ok(Analysis {
+            symbols: checker.symbols,
+            types: checker.types,
+            expressions: checker.expressions,
+            designators: checker.designators,
+            field_heads: checker.field_heads,
+            guard_calls: checker.guard_calls,
+            fingerprints: checker.fingerprints,
+        })
+    } else {
+        Err(checker.errors)
+    }
+}
+
+impl Checker {
+    fn new(integer_width: IntegerWidth) -> Self {
+        let symbols = SymbolTable::new();
+        let mut checker = Self {
+            types: TypeTable::new(),
+            scope: symbols.universe(),
+            symbols,
+            narrowed: vec![],
+            expressions: HashMap::new(),
+            designators: HashMap::new(),
+            field_heads: HashSet::new(),
+            guard_calls: HashSet::new(),
+            fingerprints: HashMap::new(),
+            integer_width,
+            errors: vec![],
+        };
+
+        for (name, ty) in [
+            ("BOOLEAN", BOOLEAN), ("CHAR", CHAR), ("INTEGER", INTEGER),
+            ("REAL", REAL), ("BYTE", BYTE), ("SET", SET),
+        ] {
+            checker.predeclare(name, SymbolKind::Type, ty);
+        }
+
+        for (name, builtin) in [
+            ("ABS", Builtin::Abs), ("ODD", Builtin::Odd), ("LEN", Builtin::Len),
+            ("LSL", Builtin::Lsl), ("ASR", Builtin::Asr), ("ROR", Builtin::Ror),
+            ("FLOOR", Builtin::Floor), ("FLT", Builtin::Flt), ("ORD", Builtin::Ord),
+            ("CHR", Builtin::Chr), ("INC", Builtin::Inc), ("DEC", Builtin::Dec),
+            ("INCL", Builtin::Incl), ("EXCL", Builtin::Excl), ("NEW", Builtin::New),
+            ("ASSERT", Builtin::Assert), ("PACK", Builtin::Pack), ("UNPK", Builtin::Unpk),
+        ] {
+            checker.predeclare(name, SymbolKind::Builtin(builtin), NO_TYPE);
+        }
+
+        checker
+    }
+
+    fn predeclare(&mut self, name: &str, kind: SymbolKind, ty: TypeId) {
+        let universe = self.symbols.universe();
+        let symbol = Symbol { name: name.to_string(), kind, ty, span: Span::default(), exported: false, scope: universe, body: None };
+        self.symbols.declare(symbol).expect("predeclared identifiers are unique");
+    }
+
+    fn error(&mut self, error: AnalysisError) {
+        self.errors.push(error);
+    }
+
+    // --------------------------- SCOPES ---------------------------
+    fn open_scope(&mut self, kind: ScopeKind) -> ScopeId {
+        self.scope = self.symbols.open_scope(kind, Some(self.scope));
+        self.scope
+    }
+
+    fn close_scope(&mut self) {
+        self.scope = self.symbols.scope(self.scope).parent.expect("cannot close the universe");
+    }
+
+    fn declare_in(&mut self, scope: ScopeId, ident: &Identifier, kind: SymbolKind, ty: TypeId, exported: bool) -> Option<SymbolId> {
+        let symbol = Symbol { name: ident.text.to_string(), kind, ty, span: ident.span, exported, scope, body: None };
+        match self.symbols.declare(symbol) {
+            Ok(id) => Some(id),
+            Err(previous) => {
+                let previous = self.symbols.symbol(previous).span;
+                self.error(AnalysisError::Redeclared { name: ident.text.to_string(), span: ident.span, previous });
+                None
+            }
+        }
+    }
+
+    fn declare(&mut self, ident: &Identifier, kind: SymbolKind, ty: TypeId) -> Option<SymbolId> {
+        self.declare_in(self.scope, ident, kind, ty, false)
+    }
+
+    fn declare_def(&mut self, def: &IdentifierDef, kind: SymbolKind, ty: TypeId) -> Option<SymbolId> {
+        if def.exported && self.symbols.scope(self.scope).kind != ScopeKind::Module {
+            self.error(AnalysisError::InvalidExport { span: def.span });
+        }
+        self.declare_in(self.scope, &def.ident, kind, ty, def.exported)
+    }
+
+    fn lookup(&self, name: &str) -> Option<SymbolId> {
+        self.symbols.lookup(self.scope, name)
+    }
+
+    // --------------------------- TYPE HELPERS ---------------------------
+    /// Finds a field of `record` or its bases; imported records only show their exported fields.
+    fn find_field(&self, record: TypeId, name: &str) -> Option<SymbolId> {
+        let mut current = Some(record);
+        while let Some(id) = current {
+            let Type::Record { base, fields } = self.types.get(id) else { return None; };
+            if let Some(field) = self.symbols.lookup_local(*fields, name)
+                && (self.symbols.symbol(field).exported || self.types.link(id).is_none())
+            {
+                return Some(field);
+            }
+            current = *base;
+        }
+        None
+    }
+
+    // --------------------------- MODULE ---------------------------
+    fn check_module(&mut self, module: &Module, imports: &[SymbolFile]) {
+        if module.name.text != module.end_name.text {
+            self.error(AnalysisError::ModuleNameMismatch {
+                expected: module.name.text.to_string(),
+                found: module.end_name.text.to_string(),
+                span: module.end_name.span,
+                declared: module.name.span,
+            });
+        }
+
+        self.open_scope(ScopeKind::Module);
+        for import in &module.imports {
+            let interface = imports.iter().find(|file| file.module == *import.module.text);
+            self.declare_import(import, interface);
+            if let Some(interface) = interface {
+                self.check_dependencies(import, interface, imports);
+            }
+        }
+        self.check_declarations(&module.declarations);
+        if let Some(stmts) = &module.stmts {
+            self.check_statement_sequence(stmts);
+        }
+        self.close_scope();
+    }
+
+    fn declare_import(&mut self, import: &Import, interface: Option<&SymbolFile>) {
+        let local = import.alias.as_ref().unwrap_or(&import.module);
+        let id = self.declare(local, SymbolKind::Module { name: import.module.text.to_string() }, NO_TYPE);
+        let Some(interface) = interface else {
+            self.error(AnalysisError::UnknownModule { module: import.module.text.to_string(), span: import.module.span });
+            return;
+        };
+        if let Some(id) = id {
+            let exports = self.symbols.open_scope(ScopeKind::Import, None);
+            interface.import(&mut self.types, &mut self.symbols, exports);
+            self.symbols.symbol_mut(id).body = Some(exports);
+            self.fingerprints.insert(id, interface.fingerprint);
+            for export in &interface.exports {
+                let export_id = self.symbols.lookup_local(exports, &export.name).expect("imported above");
+                self.fingerprints.insert(export_id, export.fingerprint);
+            }
+        }
+    }
+
+    /// Reports an imported module that used an export of another module which has changed since.
+    fn check_dependencies(&mut self, import: &Import, interface: &SymbolFile, imports: &[SymbolFile]) {
+        for dependency in &interface.imports {
+            let Some(current) = imports.iter().find(|file| file.module == dependency.module) else { continue };
+            if let Some(name) = dependency.changed_use(current) {
+                self.error(AnalysisError::StaleImport {
+                    module: interface.module.clone(),
+                    changed: format!("{}.{name}", dependency.module),
+                    span: import.module.span,
+                });
+                return;
+            }
+        }
+    }
+
+    fn check_declarations(&mut self, declarations: &Declarations) {
+        for declaration in &declarations.const_declarations {
+            self.check_const_declaration(declaration);
+        }
+        self.check_type_declarations(&declarations.type_declarations);
+        for declaration in &declarations.var_declarations {
+            self.check_var_declaration(declaration);
+        }
+        let ids: Vec<_> = declarations.procedure_declarations.iter().map(|procedure| {
+            let ty = self.procedure_type(procedure.header.params.as_ref());
+            self.declare_def(&procedure.header.name, SymbolKind::Procedure, ty)
+        }).collect();
+        for (procedure, id) in declarations.procedure_declarations.iter().zip(ids) {
+            self.check_procedure(procedure, id);
+        }
+    }
+
+    fn check_const_declaration(&mut self, declaration: &ConstDeclaration) {
+        let item = self.check_expression(&declaration.value);
+        let kind = match item.mode {
+            Mode::Const(value) => SymbolKind::Const(value),
+            _ => {
+                if !self.types.is_error(item.ty) {
+                    self.error(ConstError::NotConstant { span: declaration.value.span() }.into());
+                }
+                SymbolKind::Const(Value::Integer(0))
+            }
+        };
+        self.declare_def(&declaration.ident, kind, item.ty);
+    }
+
+    fn check_type_declarations(&mut self, declarations: &[TypeDeclaration]) {
+        let mut pending = vec![];
+        for declaration in declarations {
+            let ty = self.resolve_type(&declaration.ty, &mut pending);
+            self.types.set_name(ty, &declaration.ident.ident.text);
+            self.declare_def(&declaration.ident, SymbolKind::Type, ty);
+        }
+        for (pointer, name) in pending {
+            let base = self.resolve_named_type(&name);
+            if !self.types.is_error(base) && !matches!(self.types.get(base), Type::Record { .. }) {
+                self.error(AnalysisError::InvalidType { reason: "pointer base must be a record".to_string(), span: name.span() });
+            }
+            self.types.set(pointer, Type::Pointer { base });
+        }
+    }
+
+    fn check_var_declaration(&mut self, declaration: &VarDeclaration) {
+        let ty = self.resolve_type(&declaration.ty, &mut vec![]);
+        for variable in &declaration.variables {
+            self.declare_def(variable, SymbolKind::Var, ty);
+        }
+    }
+
+    /// Checks the body of a procedure; `id` is `None` when its name clashed with an earlier declaration.
+    fn check_procedure(&mut self, procedure: &ProcedureDeclaration, id: Option<SymbolId>) {
+        let name = procedure.header.name.ident.text.clone();
+        if name != procedure.name.text {
+            self.error(AnalysisError::ProcedureNameMismatch {
+                expected: name.to_string(),
+                found: procedure.name.text.to_string(),
+                span: procedure.name.span,
+                declared: procedure.header.name.ident.span,
+            });
+        }
+
+        let Some(id) = id else { return; };
+        let (param_types, result) = match self.types.get(self.symbols.symbol(id).ty) {
+            Type::Procedure { params, result } => (params.clone(), *result),
+            _ => (vec![], None),
+        };
+
+        let body = self.open_scope(ScopeKind::Procedure(id));
+        self.symbols.symbol_mut(id).body = Some(body);
+        let names = procedure.header.params.iter().flat_map(|p| &p.sections).flat_map(|s| &s.names);
+        for (name, param) in names.zip(param_types) {
+            self.declare(name, SymbolKind::Param { by_ref: param.by_ref }, param.ty);
+        }
+
+        self.check_declarations(&procedure.body.declarations);
+        if let Some(stmts) = &procedure.body.stmts {
+            self.check_statement_sequence(stmts);
+        }
+
+        match (&procedure.body.ret, result) {
+            (Some(ret), Some(result)) => {
+                let item = self.check_value(ret);
+                if !self.types.assignable(result, item.ty) {
+                    self.error(AnalysisError::TypeMismatch {
+                        expected: self.types.name(result),
+                        found: self.types.name(item.ty),
+                        span: ret.span(),
+                    });
+                }
+            }
+            (Some(ret), None) => {
+                self.error(AnalysisError::UnexpectedReturn { name: name.to_string(), span: ret.span() });
+            }
+            (None, Some(_)) => {
+                self.error(AnalysisError::MissingReturn { name: name.to_string(), span: procedure.name.span });
+            }
+            (None, None) => {}
+        }
+        self.close_scope();
+    }
+
+    // --------------------------- TYPES ---------------------------
+    fn resolve_named_type(&mut self, name: &QualifiedIdentifier) -> TypeId {
+        let mut item = self.resolve_qualident(name);
+        if item.mode != Mode::Type {
+            if !self.types.is_error(item.ty) {
+                self.error(AnalysisError::NotAType { name: qualident_text(name), span: name.span() });
+            }
+            item.ty = ERROR;
+        }
+        item.ty
+    }
+
+    fn resolve_type(&mut self, ty: &ast::Type, pending: &mut Vec<(TypeId, QualifiedIdentifier)>) -> TypeId {
+        match ty {
+            ast::Type::Named { name } => self.resolve_named_type(name),
+            ast::Type::Array { lengths, element, .. } => {
+                let mut element = self.resolve_type(element, pending);
+                for length in lengths.iter().rev() {
+                    let value = self.constant_integer(length);
+                    let length = match value {
+                        Some(n) if n > 0 => n,
+                        _ => {
+                            self.error(AnalysisError::InvalidArrayLength { span: length.span() });
+                            1
+                        }
+                    };
+                    element = self.types.add(Type::Array { length, element });
+                }
+                element
+            }
+            ast::Type::Record { base, field_lists, .. } => {
+                let base = base.as_ref().map(|base| {
+                    let id = self.resolve_named_type(base);
+                    match self.types.get(id) {
+                        Type::Record { .. } => id,
+                        Type::Pointer { base: record } => *record,
+                        Type::Error => id,
+                        _ => {
+                            self.error(AnalysisError::InvalidType { reason: "base type must be a record".to_string(), span: base.span() });
+                            ERROR
+                        }
+                    }
+                }).filter(|id| !self.types.is_error(*id));
+
+                let fields = self.symbols.open_scope(ScopeKind::Record, None);
+                for field_list in field_lists {
+                    let ty = self.resolve_type(&field_list.ty, pending);
+                    for field in &field_list.fields {
+                        if let Some(inherited) = base.and_then(|b| self.find_field(b, &field.ident.text)) {
+                            let previous = self.symbols.symbol(inherited).span;
+                            self.error(AnalysisError::Redeclared { name: field.ident.text.to_string(), span: field.ident.span, previous });
+                        } else {
+                            self.declare_in(fields, &field.ident, SymbolKind::Field, ty, field.exported);
+                        }
+                    }
+                }
+                self.types.add(Type::Record { base, fields })
+            }
+            ast::Type::Pointer { pointee, span } => {
+                if let ast::Type::Named { name } = pointee.as_ref()
+                    && name.parts.len() == 1
+                    && self.lookup(&name.parts[0].text).is_none()
+                {
+                    let id = self.types.add(Type::Pointer { base: ERROR });
+                    pending.push((id, name.clone()));
+                    return id;
+                }
+                let base = self.resolve_type(pointee, pending);
+                if !self.types.is_error(base) && !matches!(self.types.get(base), Type::Record { .. }) {
+                    self.error(AnalysisError::InvalidType { reason: "pointer base must be a record".to_string(), span: *span });
+                }
+                self.types.add(Type::Pointer { base })
+            }
+            ast::Type::Procedure { params, .. } => self.procedure_type(params.as_ref()),
+            ast::Type::Error { .. } => ERROR,
+        }
+    }
+
+    fn resolve_formal_type(&mut self, ty: &FormalType) -> TypeId {
+        let mut id = self.resolve_named_type(&ty.base);
+        for _ in 0..ty.open_arrays {
+            id = self.types.open_array(id);
+        }
+        id
+    }
+
+    fn procedure_type(&mut self, params: Option<&FormalParameters>) -> TypeId {
+        let mut result_params = vec![];
+        let mut result = None;
+        if let Some(params) = params {
+            for section in &params.sections {
+                let ty = self.resolve_formal_type(&section.ty);
+                for _ in &section.names {
+                    result_params.push(Param { by_ref: section.by_ref, ty });
+                }
+            }
+            if let Some(return_type) = &params.return_type {
+                let ty = self.resolve_named_type(return_type);
+                if matches!(self.types.get(ty), Type::Record { .. } | Type::Array { .. } | Type::OpenArray { .. }) {
+                    self.error(AnalysisError::InvalidType { reason: "function result must not be a record or array".to_string(), span: return_type.span() });
+                }
+                result = Some(ty);
+            }
+        }
+        self.types.add(Type::Procedure { params: result_params, result })
+    }
+
+    fn constant_integer(&mut self, expr: &Expression) -> Option<i64> {
+        let item = self.check_expression(expr);
+        match item.value() {
+            Some(Value::Integer(n)) => Some(*n),
+            _ => {
+                if !self.types.is_error(item.ty) && item.value().is_none() {
+                    self.error(ConstError::NotConstant { span: expr.span() }.into());
+                }
+                None
+            }
+        }
+    }
+
+    // --------------------------- DESIGNATORS ---------------------------
+    fn resolve_qualident(&mut self, name: &QualifiedIdentifier) -> Item {
+        let first = &name.parts[0];
+        let Some(id) = self.lookup(&first.text) else {
+            self.error(AnalysisError::Undeclared { name: first.text.to_string(), span: first.span });
+            return Item::error();
+        };
+        self.symbols.add_reference(first.span, id);
+        let symbol = self.symbols.symbol(id);
+        if let SymbolKind::Module { name: module } = &symbol.kind
+            && let Some(member) = name.parts.get(1)
+        {
+            // Without a body the module could not be found, which has been reported already.
+            let Some(exports) = symbol.body else { return Item::error(); };
+            let Some(member_id) = self.symbols.lookup_local(exports, &member.text) else {
+                let module = module.clone();
+                self.error(AnalysisError::UnresolvedImport { module, name: member.text.to_string(), span: member.span });
+                return Item::error();
+            };
+            self.symbols.add_reference(member.span, member_id);
+            return self.symbol_item(member_id);
+        }
+        self.symbol_item(id)
+    }
+
+    fn symbol_item(&self, id: SymbolId) -> Item {
+        let symbol = self.symbols.symbol(id);
+        let ty = self.narrowed.iter().rev().find(|(s, _)| *s == id).map_or(symbol.ty, |(_, ty)| *ty);
+        let mode = match &symbol.kind {
+            SymbolKind::Const(value) => Mode::Const(value.clone()),
+            SymbolKind::Type => Mode::Type,
+            // Imported variables may only be read.
+            SymbolKind::Var => Mode::Var { read_only: self.symbols.scope(symbol.scope).kind == ScopeKind::Import },
+            SymbolKind::Field => Mode::Var { read_only: false },
+            SymbolKind::Param { by_ref } => {
+                let structured = matches!(self.types.get(symbol.ty), Type::Array { .. } | Type::OpenArray { .. } | Type::Record { .. });
+                Mode::Var { read_only: !by_ref && structured }
+            }
+            SymbolKind::Procedure => Mode::Procedure { local: self.symbols.scope(symbol.scope).level > 1 },
+            SymbolKind::Builtin(builtin) => Mode::Builtin(*builtin),
+            SymbolKind::Module { name } => Mode::Module(name.clone()),
+        };
+        Item { var_param: matches!(symbol.kind, SymbolKind::Param { by_ref: true }), ..Item::new(mode, ty) }
+    }
+
+    /// Resolves a designator and returns the actual parameters of the call it makes, if any. The
+    /// checker decides how to read what the parser cannot tell apart, and records it for
+    /// `Analysis::designator`: a head `a.b` whose `a` is not a module selects field `b`, and a
+    /// trailing type guard applied to a procedure is a call with a single argument.
+    fn check_designator<'d>(&mut self, designator: &'d Designator, actual_parameters: Option<&'d [Expression]>) -> (Item, Option<Cow<'d, [Expression]>>) {
+        let head = &designator.head;
+        let module = self.lookup(&head.parts[0].text).map(|id| &self.symbols.symbol(id).kind);
+        let field_head = head.parts.len() == 2 && !matches!(module, Some(SymbolKind::Module { .. }));
+        if field_head {
+            self.field_heads.insert(head.span());
+        }
+        let (read, _) = read_designator(designator, None, field_head, false);
+        let mut item = self.resolve_qualident(&read.head);
+        self.designators.insert(read.head.span(), item.ty);
+
+        for (index, selector) in read.selectors.iter().enumerate() {
+            if self.types.is_error(item.ty) && item.mode != Mode::Type {
+                return (Item::error(), actual_parameters.map(Cow::Borrowed));
+            }
+            let callable = matches!(item.mode, Mode::Procedure { .. } | Mode::Builtin(_))
+                || matches!(self.types.get(item.ty), Type::Procedure { .. });
+            if let Selector::TypeGuard(..) = selector
+                && callable
+                && index + 1 == read.selectors.len()
+                && actual_parameters.is_none()
+            {
+                self.guard_calls.insert(selector.span());
+                let (_, arguments) = read_designator(designator, None, field_head, true);
+                return (item, arguments);
+            }
+            item = self.apply_selector(item, selector);
+            self.designators.insert(selector.span(), item.ty);
+        }
+        (item, actual_parameters.map(Cow::Borrowed))
+    }
+
+    fn apply_selector(&mut self, item: Item, selector: &Selector) -> Item {
+        let read_only = match item.mode {
+            Mode::Var { read_only } => read_only,
+            _ => true,
+        };
+        match selector {
+            Selector::Field(field) => {
+                let Some(record) = self.types.record_of(item.ty) else {
+                    self.error(AnalysisError::InvalidSelector { ty: self.types.name(item.ty), span: field.span });
+                    return Item::error();
+                };
+                let via_pointer = matches!(self.types.get(item.ty), Type::Pointer { .. });
+                match self.find_field(record, &field.text) {
+                    Some(id) => {
+                        self.symbols.add_reference(field.span, id);
+                        Item::new(Mode::Var { read_only: read_only && !via_pointer }, self.symbols.symbol(id).ty)
+                    }
+                    None => {
+                        self.error(AnalysisError::UnknownField { field: field.text.to_string(), ty: self.types.name(item.ty), span: field.span });
+                        Item::error()
+                    }
+                }
+            }
+            Selector::Index(indices, span) => {
+                let mut ty = item.ty;
+                for index in indices.iter() {
+                    let index_item = self.check_value(index);
+                    if !self.types.is_integer(index_item.ty) && !self.types.is_error(index_item.ty) {
+                        self.error(AnalysisError::TypeMismatch { expected: "INTEGER".to_string(), found: self.types.name(index_item.ty), span: index.span() });
+                    }
+                    ty = match self.types.get(ty).clone() {
+                        Type::Array { length, element } => {
+                            if let Some(Value::Integer(n)) = index_item.value()
+                                && (*n < 0 || *n >= length)
+                            {
+                                self.error(AnalysisError::IndexOutOfRange { index: *n, length, span: index.span() });
+                            }
+                            element
+                        }
+                        Type::OpenArray { element } => element,
+                        Type::Error => ERROR,
+                        _ => {
+                            self.error(AnalysisError::InvalidSelector { ty: self.types.name(ty), span: *span });
+                            return Item::error();
+                        }
+                    };
+                }
+                Item::new(Mode::Var { read_only }, ty)
+            }
+            Selector::Deref(span) => match self.types.get(item.ty) {
+                Type::Pointer { base } => Item::new(Mode::Var { read_only: false }, *base),
+                _ => {
+                    self.error(AnalysisError::InvalidSelector { ty: self.types.name(item.ty), span: *span });
+                    Item::error()
+                }
+            },
+            Selector::TypeGuard(name, span) => {
+                let guard = self.resolve_named_type(name);
+                if self.types.is_error(guard) {
+                    return Item::error();
+                }
+                if !matches!(item.mode, Mode::Var { .. }) || !self.has_dynamic_type(&item) {
+                    self.error(AnalysisError::InvalidSelector { ty: self.types.name(item.ty), span: *span });
+                    return Item::error();
+                }
+                if !self.types.extends(guard, item.ty) {
+                    self.error(AnalysisError::NotAnExtension { ty: self.types.name(guard), base: self.types.name(item.ty), span: *span });
+                    return Item::error();
+                }
+                Item { var_param: item.var_param, ..Item::new(Mode::Var { read_only }, guard) }
+            }
+        }
+    }
+
+    /// Whether `item` may be guarded, tested with IS or switched on in a type CASE: Oberon-07
+    /// allows this only for pointers and for VAR parameters of record type, since other records
+    /// always have exactly their declared type.
+    fn has_dynamic_type(&self, item: &Item) -> bool {
+        match self.types.get(item.ty) {
+            Type::Pointer { .. } => true,
+            Type::Record { .. } => item.var_param,
+            _ => false,
+        }
+    }
+
+    // --------------------------- EXPRESSIONS ---------------------------
+    fn check_value(&mut self, expr: &Expression) -> Item {
+        let item = self.check_expression(expr);
+        match item.mode {
+            Mode::Type | Mode::Module(_) | Mode::Builtin(_) => {
+                self.error(AnalysisError::NotAValue { span: expr.span() });
+                Item::error()
+            }
+            _ => item,
+        }
+    }
+
+    fn check_expression(&mut self, expr: &Expression) -> Item {
+        let item = self.expression_item(expr);
+        let info = ExpressionInfo { ty: item.ty, value: item.value().cloned() };
+        self.expressions.insert(expr.span(), info);
+        item
+    }
+
+    fn expression_item(&mut self, expr: &Expression) -> Item {
+        match expr {
+            Expression::Int { value, .. } => Item::new(Mode::Const(Value::Integer(*value)), INTEGER),
+            Expression::Real { value, .. } => Item::new(Mode::Const(Value::Real(*value)), REAL),
+            Expression::String { value, .. } => {
+                let length = value.chars().count();
+                let ty = self.types.string(length);
+                let value = if length == 1 { Value::Char(value.chars().next().unwrap() as u8) } else { Value::String(value.clone()) };
+                Item::new(Mode::Const(value), ty)
+            }
+            // As in Oberon-07, a character constant and a string of length one may each stand
+            // for the other.
+            Expression::Char { value, .. } => Item::new(Mode::Const(Value::Char(*value)), self.types.string(1)),
+            Expression::Nil { .. } => Item::new(Mode::Const(Value::Nil), NIL),
+            Expression::True { .. } => Item::new(Mode::Const(Value::Boolean(true)), BOOLEAN),
+            Expression::False { .. } => Item::new(Mode::Const(Value::Boolean(false)), BOOLEAN),
+            Expression::Set { elements, .. } => self.check_set(elements),
+            Expression::Designator { designator, actual_parameters, span } => {
+                let span = *span;
+                let (item, arguments) = self.check_designator(designator, actual_parameters.as_deref());
+                match arguments {
+                    Some(arguments) => self.check_call(item, &arguments, span, true),
+                    None => {
+                        if matches!(item.mode, Mode::Builtin(_)) {
+                            self.error(AnalysisError::NotAValue { span });
+                            return Item::error();
+                        }
+                        match item.mode {
+                            // Oberon-07 only lets procedures declared at module level be assigned
+                            // or passed, as a local one would outlive the frame it refers to.
+                            Mode::Procedure { local: true } => {
+                                let name = designator.head.parts.last().map_or_else(String::new, |part| part.text.to_string());
+                                self.error(AnalysisError::LocalProcedureAsValue { name, span });
+                                Item::error()
+                            }
+                            Mode::Procedure { local: false } => Item::new(Mode::Value, item.ty),
+                            _ => item,
+                        }
+                    }
+                }
+            }
+            Expression::Unary { op, operand, span } => {
+                let span = *span;
+                let operand = self.check_value(operand);
+                self.check_unary(*op, operand, span)
+            }
+            Expression::Binary { op, lhs, rhs, span } => {
+                let span = *span;
+                let op = *op;
+                let lhs = self.check_value(lhs);
+                if op == BinaryOperation::Is {
+                    return self.check_type_test(lhs, rhs, span);
+                }
+                let rhs = self.check_value(rhs);
+                self.check_binary(op, lhs, rhs, span)
+            }
+            Expression::Error { .. } => Item::error(),
+        }
+    }
+
+    fn check_set(&mut self, elements: &[Element]) -> Item {
+        let mut constant = Some(0u64);
+        for element in elements {
+            let first = self.set_element(&element.first);
+            let second = match &element.second {
+                Some(second) => self.set_element(second),
+                None => first,
+            };
+            constant = match (constant, first, second) {
+                (Some(bits), Some(low), Some(high)) => Some(bits | const_eval::set_range(low, high)),
+                _ => None,
+            };
+        }
+        match constant {
+            Some(bits) => Item::new(Mode::Const(Value::Set(bits)), SET),
+            None => Item::new(Mode::Value, SET),
+        }
+    }
+
+    fn set_element(&mut self, expr: &Expression) -> Option<i64> {
+        let item = self.check_value(expr);
+        if !self.types.is_integer(item.ty) {
+            if !self.types.is_error(item.ty) {
+                self.error(AnalysisError::TypeMismatch { expected: "INTEGER".to_string(), found: self.types.name(item.ty), span: expr.span() });
+            }
+            return None;
+        }
+        match item.value() {
+            Some(Value::Integer(n)) if (0..SET_BITS).contains(n) => Some(*n),
+            Some(Value::Integer(_)) => {
+                self.error(ConstError::OutOfRange { span: expr.span() }.into());
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn check_unary(&mut self, op: UnaryOperation, operand: Item, span: Span) -> Item {
+        if self.types.is_error(operand.ty) {
+            return Item::error();
+        }
+        let valid = match op {
+            UnaryOperation::Not => operand.ty == BOOLEAN,
+            UnaryOperation::Plus => self.types.is_numeric(operand.ty),
+            UnaryOperation::Minus => self.types.is_numeric(operand.ty) || operand.ty == SET,
+        };
+        if !valid {
+            self.error(AnalysisError::InvalidOperand { op: op.symbol().to_string(), operand: self.types.name(operand.ty), span });
+            return Item::error();
+        }
+        let ty = if operand.ty == BYTE { INTEGER } else { operand.ty };
+        match operand.value() {
+            Some(value) => self.fold(const_eval::unary(op, value, self.integer_width, span), ty),
+            None => Item::new(Mode::Value, ty),
+        }
+    }
+
+    fn check_type_test(&mut self, lhs: Item, rhs: &Expression, span: Span) -> Item {
+        let Expression::Designator { designator, actual_parameters: None, .. } = rhs else {
+            self.error(AnalysisError::NotAType { name: "expression".to_string(), span: rhs.span() });
+            return Item::error();
+        };
+        let ty = self.resolve_named_type(&designator.head);
+        if self.types.is_error(ty) || self.types.is_error(lhs.ty) {
+            return Item::new(Mode::Value, BOOLEAN);
+        }
+        if !self.has_dynamic_type(&lhs) {
+            self.error(AnalysisError::InvalidOperand { op: "IS".to_string(), operand: self.types.name(lhs.ty), span });
+        } else if !self.types.extends(ty, lhs.ty) {
+            self.error(AnalysisError::NotAnExtension { ty: self.types.name(ty), base: self.types.name(lhs.ty), span });
+        }
+        Item::new(Mode::Value, BOOLEAN)
+    }
+
+    fn check_binary(&mut self, op: BinaryOperation, lhs: Item, rhs: Item, span: Span) -> Item {
+        if self.types.is_error(lhs.ty) || self.types.is_error(rhs.ty) {
+            return Item::error();
+        }
+        let Some(ty) = self.binary_result(op, lhs.ty, rhs.ty) else {
+            self.error(AnalysisError::IncompatibleOperands {
+                op: op.symbol().to_string(),
+                lhs: self.types.name(lhs.ty),
+                rhs: self.types.name(rhs.ty),
+                span,
+            });
+            return Item::error();
+        };
+        match (lhs.value(), rhs.value()) {
+            (Some(a), Some(b)) => self.fold(const_eval::binary(op, a, b, self.integer_width, span), ty),
+            _ => Item::new(Mode::Value, ty),
+        }
+    }
+
+    fn binary_result(&self, op: BinaryOperation, lhs: TypeId, rhs: TypeId) -> Option<TypeId> {
+        use BinaryOperation::*;
+        let both_integer = self.types.is_integer(lhs) && self.types.is_integer(rhs);
+        match op {
+            Addition | Subtraction | Multiplication => {
+                if both_integer {
+                    Some(INTEGER)
+                } else if lhs == REAL && rhs == REAL {
+                    Some(REAL)
+                } else if lhs == SET && rhs == SET {
+                    Some(SET)
+                } else {
+                    None
+                }
+            }
+            Division => ((lhs == REAL && rhs == REAL) || (lhs == SET && rhs == SET)).then_some(lhs),
+            Div | Mod => both_integer.then_some(INTEGER),
+            And | Or => (lhs == BOOLEAN && rhs == BOOLEAN).then_some(BOOLEAN),
+            In => (self.types.is_integer(lhs) && rhs == SET).then_some(BOOLEAN),
+            Eq | Neq => self.comparable(lhs, rhs, true).then_some(BOOLEAN),
+            Lt | Le | Gt | Ge => self.comparable(lhs, rhs, false).then_some(BOOLEAN),
+            Is => None,
+        }
+    }
+
+    fn comparable(&self, lhs: TypeId, rhs: TypeId, equality: bool) -> bool {
+        if (self.types.is_integer(lhs) && self.types.is_integer(rhs)) || (lhs == REAL && rhs == REAL) {
+            return true;
+        }
+        if self.types.is_char_like(lhs) && self.types.is_char_like(rhs) {
+            return true;
+        }
+        if self.types.is_string_like(lhs) && self.types.is_string_like(rhs) {
+            return true;
+        }
+        if !equality {
+            return false;
+        }
+        match (self.types.get(lhs), self.types.get(rhs)) {
+            (Type::Boolean, Type::Boolean) | (Type::Set, Type::Set) => true,
+            (Type::Pointer { .. }, Type::Pointer { .. }) => self.types.extends(lhs, rhs) || self.types.extends(rhs, lhs),
+            (Type::Pointer { .. } | Type::Procedure { .. } | Type::Nil, Type::Nil)
+            | (Type::Nil, Type::Pointer { .. } | Type::Procedure { .. }) => true,
+            (Type::Procedure { .. }, Type::Procedure { .. }) => self.types.matching_signatures(lhs, rhs),
+            _ => false,
+        }
+    }
+
+    // --------------------------- CALLS ---------------------------
+    fn check_call(&mut self, callee: Item, arguments: &[Expression], span: Span, as_function: bool) -> Item {
+        if self.types.is_error(callee.ty) && !matches!(callee.mode, Mode::Builtin(_)) {
+            for argument in arguments.iter() {
+                self.check_expression(argument);
+            }
+            return Item::error();
+        }
+        if let Mode::Builtin(builtin) = callee.mode {
+            return self.check_builtin(builtin, arguments, span, as_function);
+        }
+        let Type::Procedure { params, result } = self.types.get(callee.ty).clone() else {
+            self.error(AnalysisError::NotAProcedure { span });
+            return Item::error();
+        };
+        if arguments.len() != params.len() {
+            self.error(AnalysisError::ArgumentCount { expected: params.len(), found: arguments.len(), span });
+        }
+        for (argument, param) in arguments.iter().zip(&params) {
+            self.check_argument(argument, param);
+        }
+        for argument in arguments.iter().skip(params.len()) {
+            self.check_expression(argument);
+        }
+        match (result, as_function) {
+            (Some(result), true) => Item::new(Mode::Value, result),
+            (None, false) => Item::new(Mode::Value, NO_TYPE),
+            (Some(_), false) => {
+                self.error(AnalysisError::FunctionAsStatement { span });
+                Item::error()
+            }
+            (None, true) => {
+                self.error(AnalysisError::ProcedureAsValue { span });
+                Item::error()
+            }
+        }
+    }
+
+    fn check_argument(&mut self, argument: &Expression, param: &Param) {
+        let item = self.check_value(argument);
+        if self.types.is_error(item.ty) || self.types.is_error(param.ty) {
+            return;
+        }
+        let open = matches!(self.types.get(param.ty), Type::OpenArray { .. });
+        if param.by_ref {
+            if item.mode != (Mode::Var { read_only: false }) {
+                self.error(AnalysisError::NotAssignable { span: argument.span() });
+                return;
+            }
+            let compatible = if open {
+                self.types.array_compatible(param.ty, item.ty)
+            } else if matches!(self.types.get(param.ty), Type::Record { .. }) {
+                self.types.extends(item.ty, param.ty)
+            } else {
+                self.types.equal(param.ty, item.ty)
+            };
+            if !compatible {
+                self.mismatch(param.ty, item.ty, argument.span());
+            }
+        } else {
+            let compatible = if open {
+                self.types.array_compatible(param.ty, item.ty)
+            } else {
+                self.types.assignable(param.ty, item.ty)
+            };
+            if !compatible {
+                self.mismatch(param.ty, item.ty, argument.span());
+            }
+        }
+    }
+
+    fn mismatch(&mut self, expected: TypeId, found: TypeId, span: Span) {
+        self.error(AnalysisError::TypeMismatch { expected: self.types.name(expected), found: self.types.name(found), span });
+    }
+
+    fn check_builtin(&mut self, builtin: Builtin, arguments: &[Expression], span: Span, as_function: bool) -> Item {
+        use Builtin::*;
+        let (min, max, function) = match builtin {
+            Abs | Odd | Len | Floor | Flt | Ord | Chr => (1, 1, true),
+            Lsl | Asr | Ror => (2, 2, true),
+            Inc | Dec => (1, 2, false),
+            Incl | Excl | Pack | Unpk => (2, 2, false),
+            New | Assert => (1, 1, false),
+        };
+        if arguments.len() < min || arguments.len() > max {
+            self.error(AnalysisError::ArgumentCount { expected: min, found: arguments.len(), span });
+            return Item::error();
+        }
+        if function != as_function {
+            self.error(if function { AnalysisError::FunctionAsStatement { span } } else { AnalysisError::ProcedureAsValue { span } });
+            return Item::error();
+        }
+
+        let items: Vec<Item> = arguments.iter().map(|a| self.check_value(a)).collect();
+        if items.iter().any(|i| self.types.is_error(i.ty)) {
+            return Item::error();
+        }
+        let spans: Vec<Span> = arguments.iter().map(|a| a.span()).collect();
+        let first = &items[0];
+
+        let ty = match builtin {
+            Abs | Odd | Flt | Chr | Lsl | Asr | Ror => {
+                for (item, span) in items.iter().zip(&spans) {
+                    let valid = if builtin == Abs { self.types.is_numeric(item.ty) } else { self.types.is_integer(item.ty) };
+                    if !valid {
+                        self.mismatch(INTEGER, item.ty, *span);
+                        return Item::error();
+                    }
+                }
+                match builtin {
+                    Abs if first.ty == REAL => REAL,
+                    Odd => BOOLEAN,
+                    Flt => REAL,
+                    Chr => CHAR,
+                    _ => INTEGER,
+                }
+            }
+            Floor => {
+                if first.ty != REAL {
+                    self.mismatch(REAL, first.ty, spans[0]);
+                    return Item::error();
+                }
+                INTEGER
+            }
+            Ord => {
+                if !(self.types.is_char_like(first.ty) || first.ty == BOOLEAN || first.ty == SET) {
+                    self.mismatch(CHAR, first.ty, spans[0]);
+                    return Item::error();
+                }
+                INTEGER
+            }
+            Len => {
+                return match self.types.get(first.ty) {
+                    Type::Array { length, .. } => Item::new(Mode::Const(Value::Integer(*length)), INTEGER),
+                    Type::OpenArray { .. } => Item::new(Mode::Value, INTEGER),
+                    _ => {
+                        self.error(AnalysisError::InvalidOperand { op: "LEN".to_string(), operand: self.types.name(first.ty), span: spans[0] });
+                        Item::error()
+                    }
+                };
+            }
+            _ => return self.check_standard_procedure(builtin, &items, &spans),
+        };
+
+        let values: Option<Vec<Value>> = items.iter().map(|item| item.value().cloned()).collect();
+        match values {
+            Some(values) => self.fold(const_eval::call(builtin, &values, self.integer_width, span), ty),
+            None => Item::new(Mode::Value, ty),
+        }
+    }
+
+    /// The item for a folded constant, or an error item when folding failed.
+    fn fold(&mut self, value: Result<Value, ConstError>, ty: TypeId) -> Item {
+        match value {
+            Ok(value) => Item::new(Mode::Const(value), ty),
+            Err(error) => {
+                self.error(error.into());
+                Item::error()
+            }
+        }
+    }
+
+    fn check_standard_procedure(&mut self, builtin: Builtin, items: &[Item], spans: &[Span]) -> Item {
+        use Builtin::*;
+        let first = &items[0];
+        match builtin {
+            Inc | Dec => {
+                self.expect_variable(first, spans[0]);
+                if !self.types.is_integer(first.ty) {
+                    self.mismatch(INTEGER, first.ty, spans[0]);
+                }
+                if let Some(step) = items.get(1)
+                    && !self.types.is_integer(step.ty)
+                {
+                    self.mismatch(INTEGER, step.ty, spans[1]);
+                }
+                Item::new(Mode::Value, NO_TYPE)
+            }
+            Incl | Excl => {
+                self.expect_variable(first, spans[0]);
+                if first.ty != SET {
+                    self.mismatch(SET, first.ty, spans[0]);
+                }
+                if !self.types.is_integer(items[1].ty) {
+                    self.mismatch(INTEGER, items[1].ty, spans[1]);
+                }
+                Item::new(Mode::Value, NO_TYPE)
+            }
+            New => {
+                self.expect_variable(first, spans[0]);
+                if !matches!(self.types.get(first.ty), Type::Pointer { .. }) {
+                    self.error(AnalysisError::InvalidOperand { op: "NEW".to_string(), operand: self.types.name(first.ty), span: spans[0] });
+                }
+                Item::new(Mode::Value, NO_TYPE)
+            }
+            Assert => {
+                if first.ty != BOOLEAN {
+                    self.mismatch(BOOLEAN, first.ty, spans[0]);
+                }
+                Item::new(Mode::Value, NO_TYPE)
+            }
+            Pack => {
+                self.expect_variable(first, spans[0]);
+                if first.ty != REAL {
+                    self.mismatch(REAL, first.ty, spans[0]);
+                }
+                if !self.types.is_integer(items[1].ty) {
+                    self.mismatch(INTEGER, items[1].ty, spans[1]);
+                }
+                Item::new(Mode::Value, NO_TYPE)
+            }
+            Unpk => {
+                self.expect_variable(first, spans[0]);
+                self.expect_variable(&items[1], spans[1]);
+                if first.ty != REAL {
+                    self.mismatch(REAL, first.ty, spans[0]);
+                }
+                if !self.types.is_integer(items[1].ty) {
+                    self.mismatch(INTEGER, items[1].ty, spans[1]);
+                }
+                Item::new(Mode::Value, NO_TYPE)
+            }
+            Abs | Odd | Len | Lsl | Asr | Ror | Floor | Flt | Ord | Chr => unreachable!("{builtin:?} is a function"),
+        }
+    }
+
+    fn expect_variable(&mut self, item: &Item, span: Span) {
+        if item.mode != (Mode::Var { read_only: false }) {
+            self.error(AnalysisError::NotAssignable { span });
+        }
+    }
+
+    // --------------------------- STATEMENTS ---------------------------
+    fn check_statement_sequence(&mut self, stmts: &StatementSequence) {
+        for statement in &stmts.statements {
+            self.check_statement(statement);
+        }
+    }
+
+    fn check_condition(&mut self, cond: &Expression) {
+        let item = self.check_value(cond);
+        if item.ty != BOOLEAN && !self.types.is_error(item.ty) {
+            self.mismatch(BOOLEAN, item.ty, cond.span());
+        }
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Assign { target, value, .. } => {
+                let (target_item, _) = self.check_designator(target, None);
+                let value_item = self.check_value(value);
+                if self.types.is_error(target_item.ty) || self.types.is_error(value_item.ty) {
+                    return;
+                }
+                if target_item.mode != (Mode::Var { read_only: false }) {
+                    self.error(AnalysisError::NotAssignable { span: target.span });
+                    return;
+                }
+                if !self.types.assignable(target_item.ty, value_item.ty) {
+                    self.mismatch(target_item.ty, value_item.ty, value.span());
+                }
+            }
+            Statement::Call { callee, parameters, span, .. } => {
+                let span = *span;
+                let (item, arguments) = self.check_designator(callee, parameters.as_deref());
+                self.check_call(item, arguments.as_deref().unwrap_or_default(), span, false);
+            }
+            Statement::If { cond, stmts, elsif_branches, else_branch, .. } => {
+                self.check_condition(cond);
+                self.check_statement_sequence(stmts);
+                for branch in elsif_branches {
+                    self.check_condition(&branch.cond);
+                    self.check_statement_sequence(&branch.stmts);
+                }
+                if let Some(else_branch) = else_branch {
+                    self.check_statement_sequence(else_branch);
+                }
+            }
+            Statement::Case { expr, branches, .. } => self.check_case(expr, branches),
+            Statement::While { cond, stmts, elsif_branches, .. } => {
+                self.check_condition(cond);
+                self.check_statement_sequence(stmts);
+                for branch in elsif_branches {
+                    self.check_condition(&branch.cond);
+                    self.check_statement_sequence(&branch.stmts);
+                }
+            }
+            Statement::Repeat { stmts, cond, .. } => {
+                self.check_statement_sequence(stmts);
+                self.check_condition(cond);
+            }
+            Statement::For { var, low, high, by, stmts, .. } => {
+                let control = self.resolve_qualident(&QualifiedIdentifier { parts: vec![var.clone()] });
+                if !self.types.is_error(control.ty) {
+                    if control.mode != (Mode::Var { read_only: false }) {
+                        self.error(AnalysisError::NotAssignable { span: var.span });
+                    } else if !self.types.is_integer(control.ty) {
+                        self.mismatch(INTEGER, control.ty, var.span);
+                    }
+                }
+                for bound in [low, high] {
+                    let item = self.check_value(bound);
+                    if !self.types.is_integer(item.ty) && !self.types.is_error(item.ty) {
+                        self.mismatch(INTEGER, item.ty, bound.span());
+                    }
+                }
+                if let Some(by) = by {
+                    let item = self.check_value(by);
+                    match item.value() {
+                        Some(Value::Integer(n)) if *n != 0 => {}
+                        _ if self.types.is_error(item.ty) => {}
+                        _ => self.error(AnalysisError::InvalidForStep { span: by.span() }),
+                    }
+                }
+                self.check_statement_sequence(stmts);
+            }
+            Statement::Error { .. } => {}
+        }
+    }
+
+    fn check_case(&mut self, expr: &Expression, branches: &[Case]) {
+        let item = self.check_value(expr);
+        if self.types.is_error(item.ty) {
+            for branch in branches {
+                self.check_statement_sequence(&branch.statements);
+            }
+            return;
+        }
+
+        let type_case = matches!(self.types.get(item.ty), Type::Pointer { .. } | Type::Record { .. });
+        if type_case {
+            self.check_type_case(expr, item, branches);
+            return;
+        }
+
+        let char_case = self.types.is_char_like(item.ty);
+        if !char_case && !self.types.is_integer(item.ty) {
+            self.mismatch(INTEGER, item.ty, expr.span());
+        }
+
+        let mut seen: Vec<(i64, i64)> = vec![];
+        for branch in branches {
+            for label in &branch.label_list {
+                let (low_value, high_value) = match label {
+                    Label::Single { value } => {
+                        let value = self.label_value(value, char_case);
+                        (value, value)
+                    }
+                    Label::Range { low, high } => (self.label_value(low, char_case), self.label_value(high, char_case)),
+                };
+                let (Some(low_value), Some(high_value)) = (low_value, high_value) else {
+                    continue;
+                };
+                if seen.iter().any(|(l, h)| low_value <= *h && *l <= high_value) {
+                    self.error(AnalysisError::DuplicateCaseLabel { span: label.span() });
+                }
+                seen.push((low_value, high_value));
+            }
+            self.check_statement_sequence(&branch.statements);
+        }
+    }
+
+    fn label_value(&mut self, label: &LabelValue, char_case: bool) -> Option<i64> {
+        let (value, span) = match label {
+            LabelValue::Integer { value, span } => (Value::Integer(*value), *span),
+            LabelValue::String { value, span } => {
+                if value.chars().count() != 1 {
+                    self.error(AnalysisError::TypeMismatch { expected: "CHAR".to_string(), found: format!("string of length {}", value.chars().count()), span: *span });
+                    return None;
+                }
+                (Value::Char(value.chars().next().unwrap() as u8), *span)
+            }
+            LabelValue::Char { value, span } => (Value::Char(*value), *span),
+            LabelValue::QualifiedIdentifier(name) => {
+                let item = self.resolve_qualident(name);
+                match item.value() {
+                    Some(value) => (value.clone(), name.span()),
+                    None => {
+                        if !self.types.is_error(item.ty) {
+                            self.error(ConstError::NotConstant { span: name.span() }.into());
+                        }
+                        return None;
+                    }
+                }
+            }
+        };
+        match (value, char_case) {
+            (Value::Integer(n), false) => Some(n),
+            (Value::Char(c), true) => Some(c as i64),
+            (Value::Char(_), false) => {
+                self.error(AnalysisError::TypeMismatch { expected: "INTEGER".to_string(), found: "CHAR".to_string(), span });
+                None
+            }
+            (_, true) => {
+                self.error(AnalysisError::TypeMismatch { expected: "CHAR".to_string(), found: "INTEGER".to_string(), span });
+                None
+            }
+            (_, false) => {
+                self.error(AnalysisError::TypeMismatch { expected: "INTEGER".to_string(), found: "constant".to_string(), span });
+                None
+            }
+        }
+    }
+
+    /// `CASE p OF T1: ... | T2: ... END` where each label is an extension of the type of `p`;
+    /// inside a branch `p` is regarded as having the label's type.
+    fn check_type_case(&mut self, expr: &Expression, item: Item, branches: &[Case]) {
+        let variable = match expr {
+            Expression::Designator { designator, actual_parameters: None, .. } if designator.selectors.is_empty() && designator.head.parts.len() == 1 => {
+                Some(designator.head.parts[0].clone())
+            }
+            _ => None,
+        };
+        if variable.is_none() || !matches!(item.mode, Mode::Var { .. }) || !self.has_dynamic_type(&item) {
+            self.error(AnalysisError::InvalidOperand { op: "CASE".to_string(), operand: self.types.name(item.ty), span: expr.span() });
+        }
+        for branch in branches {
+            let mut narrowed = None;
+            for label in &branch.label_list {
+                let Label::Single { value: LabelValue::QualifiedIdentifier(name) } = label else {
+                    self.error(AnalysisError::NotAType { name: "label".to_string(), span: label.span() });
+                    continue;
+                };
+                let ty = self.resolve_named_type(name);
+                if self.types.is_error(ty) {
+                    continue;
+                }
+                if !self.types.extends(ty, item.ty) {
+                    self.error(AnalysisError::NotAnExtension { ty: self.types.name(ty), base: self.types.name(item.ty), span: name.span() });
+                } else {
+                    narrowed = Some(ty);
+                }
+            }
+            let symbol = variable.as_ref().and_then(|v| self.lookup(&v.text));
+            let narrowing = symbol.zip(narrowed);
+            self.narrowed.extend(narrowing);
+            self.check_statement_sequence(&branch.statements);
+            if narrowing.is_some() {
+                self.narrowed.pop();
+            }
+        }
+    }
+}
+
+fn qualident_text(name: &QualifiedIdentifier) -> String {
+    name.parts.iter().map(|p| p.text.as_str()).collect::<Vec<_>>().join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+
+    fn check_source(source: &str) -> Result<(), Vec<AnalysisError>> {
+        let module = Parser::new(Lexer::new(source)).parse().unwrap();
+        check(&module).map(|_| ())
+    }
+
+    fn errors(source: &str) -> Vec<AnalysisError> {
+        check_source(source).unwrap_err()
+    }
+
+    #[test]
+    fn accepts_matching_names() {
+        assert!(check_source("MODULE m; PROCEDURE p; END p; END m .").is_ok());
+    }
+
+    #[test]
+    fn rejects_mismatched_module_name() {
+        let errors = check_source("MODULE m; END n .").unwrap_err();
+        assert!(matches!(errors.as_slice(), [AnalysisError::ModuleNameMismatch { .. }]));
+    }
+
+    #[test]
+    fn reports_every_mismatched_procedure_name() {
+        let errors = check_source("MODULE m; PROCEDURE p; PROCEDURE q; END r; END s; END m .").unwrap_err();
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn ignores_error_nodes_left_by_syntax_errors() {
+        let source = "MODULE m; CONST N = ; VAR x: ARRAY OF INTEGER; y: INTEGER; BEGIN y := N; x[0] := 1; y := + END m .";
+        let (module, syntax_errors) = Parser::new(Lexer::new(source)).parse_with_recovery();
+        assert_eq!(syntax_errors.len(), 3);
+        assert!(check(&module).is_ok());
+    }
+
+    mod symbols {
+        use super::*;
+        use crate::frontend::span::Position;
+
+        fn analyse(source: &str) -> Analysis {
+            let module = Parser::new(Lexer::new(source)).parse().unwrap();
+            check(&module).unwrap()
+        }
+
+        #[test]
+        fn builds_nested_procedure_scopes() {
+            let analysis = analyse("MODULE m; VAR x*: INTEGER; PROCEDURE P; PROCEDURE Q; BEGIN x := 1 END Q; END P; END m.");
+            let table = &analysis.symbols;
+            let module = table.module_scope().unwrap();
+            let p = table.lookup_local(module, "P").unwrap();
+            let p_body = table.symbol(p).body.unwrap();
+            let q = table.lookup_local(p_body, "Q").unwrap();
+            let q_body = table.symbol(q).body.unwrap();
+
+            assert_eq!(table.scope(q_body).kind, ScopeKind::Procedure(q));
+            assert_eq!(table.scope(q_body).level, 3);
+            assert_eq!(table.lookup(q_body, "x"), table.lookup_local(module, "x"));
+            assert!(table.symbol(table.lookup_local(module, "x").unwrap()).exported);
+        }
+
+        #[test]
+        fn resolves_recursive_calls_to_the_procedure_itself() {
+            let source = "MODULE m; PROCEDURE F(n: INTEGER): INTEGER; VAR r: INTEGER; BEGIN r := 1; IF n > 1 THEN r := n * F(n - 1) END RETURN r END F; END m.";
+            let analysis = analyse(source);
+            let table = &analysis.symbols;
+            let f = table.lookup_local(table.module_scope().unwrap(), "F").unwrap();
+            assert_eq!(table.references_to(f).count(), 1);
+        }
+
+        #[test]
+        fn finds_symbols_by_source_position() {
+            let source = "MODULE m; VAR counter: INTEGER; BEGIN counter := 0 END m.";
+            let analysis = analyse(source);
+            let offset = source.rfind("counter").unwrap() + 2;
+            let id = analysis.symbols.symbol_at(Position { offset, line: 1, column: offset + 1 }).unwrap();
+            let symbol = analysis.symbols.symbol(id);
+            assert_eq!(symbol.name, "counter");
+            assert_eq!(symbol.kind, SymbolKind::Var);
+            assert_eq!(symbol.span.start.offset, source.find("counter").unwrap());
+        }
+
+        #[test]
+        fn declares_parameters_and_record_fields() {
+            let source = "MODULE m; TYPE R* = RECORD a*, b: INTEGER END; PROCEDURE P(VAR r: R; n: INTEGER); BEGIN r.a := n END P; END m.";
+            let analysis = analyse(source);
+            let table = &analysis.symbols;
+            let p = table.lookup_local(table.module_scope().unwrap(), "P").unwrap();
+            let body = table.symbol(p).body.unwrap();
+            let kinds: Vec<_> = table.scope(body).symbols().iter().map(|id| table.symbol(*id).kind.clone()).collect();
+            assert_eq!(kinds, [SymbolKind::Param { by_ref: true }, SymbolKind::Param { by_ref: false }]);
+
+            let offset = source.rfind("r.a").unwrap() + 2;
+            let field = table.symbol(table.symbol_at(Position { offset, line: 1, column: offset + 1 }).unwrap());
+            assert_eq!((field.name.as_str(), &field.kind, field.exported), ("a", &SymbolKind::Field, true));
+        }
+    }
+
+    mod declarations {
+        use super::*;
+
+        #[test]
+        fn accepts_well_typed_module() {
+            let source = r#"
+                MODULE m;
+                CONST N = 10; Name = "Oberon";
+                TYPE
+                  Node = POINTER TO NodeDesc;
+                  NodeDesc = RECORD key: INTEGER; next: Node END;
+                  Vector = ARRAY N OF REAL;
+                VAR list: Node; v: Vector; i: INTEGER; s: SET; c: CHAR; b: BOOLEAN;
+                BEGIN
+                  NEW(list); list.key := N; list.next := NIL;
+                  FOR i := 0 TO N - 1 DO v[i] := FLT(i) / 2.0 END;
+                  s := {1, 3..5}; c := "A"; b := (i IN s) OR (c < "Z")
+                END m.
+            "#;
+            assert_eq!(check_source(source), Ok(()));
+        }
+
+        #[test]
+        fn rejects_undeclared_identifier() {
+            let errors = errors("MODULE m; BEGIN x := 1 END m.");
+            let [AnalysisError::Undeclared { name, span }] = errors.as_slice() else {
+                panic!("Expected undeclared error, got {errors:?}");
+            };
+            assert_eq!(name, "x");
+            assert_eq!(span.start.column, 17);
+        }
+
+        #[test]
+        fn rejects_duplicate_declaration() {
+            let errors = errors("MODULE m; VAR x: INTEGER; x: BOOLEAN; END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::Redeclared { name, .. }] if name == "x"));
+        }
+
+        #[test]
+        fn rejects_export_from_procedure() {
+            let errors = errors("MODULE m; PROCEDURE p; VAR x*: INTEGER; END p; END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::InvalidExport { .. }]));
+        }
+
+        #[test]
+        fn rejects_non_constant_array_length() {
+            let errors = errors("MODULE m; VAR n: INTEGER; a: ARRAY n OF INTEGER; END m.");
+            assert!(errors.iter().any(|e| matches!(e, AnalysisError::Constant(ConstError::NotConstant { .. }))));
+        }
+
+        #[test]
+        fn rejects_constant_division_by_zero() {
+            let errors = errors("MODULE m; CONST a = 1 DIV 0; END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::Constant(ConstError::DivisionByZero { .. })]));
+        }
+
+        #[test]
+        fn evaluates_constant_declarations_and_array_lengths() {
+            let source = "MODULE m; CONST N = LSL(1, 3); M = N * 2 + ORD(\"A\") - 64; VAR a: ARRAY M OF INTEGER; BEGIN a[M - 1] := ABS(-N) END m.";
+            let module = Parser::new(Lexer::new(source)).parse().unwrap();
+            let analysis = check(&module).unwrap();
+            let table = &analysis.symbols;
+            let m = table.lookup_local(table.module_scope().unwrap(), "M").unwrap();
+            assert_eq!(table.symbol(m).kind, SymbolKind::Const(Value::Integer(17)));
+        }
+
+        #[test]
+        fn rejects_overflowing_constant() {
+            let errors = errors("MODULE m; CONST a = 4611686018427387904 * 2; END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::Constant(ConstError::Overflow { .. })]));
+        }
+
+        #[test]
+        fn folds_constants_in_the_integer_width() {
+            let check_32 = |source: &str| {
+                let module = Parser::new(Lexer::new(source)).with_integer_width(IntegerWidth::Bits32).parse().unwrap();
+                check_with_integer_width(&module, &[], IntegerWidth::Bits32).map(|_| ())
+            };
+            let source = "MODULE m; CONST a = 2147483647 + 1; END m.";
+            let errors = check_32(source).unwrap_err();
+            assert!(matches!(errors.as_slice(), [AnalysisError::Constant(ConstError::Overflow { .. })]));
+            assert!(check_source(source).is_ok());
+            assert!(check_32("MODULE m; CONST a = -2147483647 - 1; b = LSL(1, 31); END m.").is_ok());
+        }
+
+        #[test]
+        fn rejects_pointer_to_non_record() {
+            let errors = errors("MODULE m; TYPE P = POINTER TO INTEGER; END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::InvalidType { .. }]));
+        }
+    }
+
+    mod expressions {
+        use super::*;
+
+        #[test]
+        fn rejects_boolean_plus_integer() {
+            let errors = errors("MODULE m; VAR x: INTEGER; BEGIN x := TRUE + 3 END m.");
+            let [AnalysisError::IncompatibleOperands { op, lhs, rhs, .. }] = errors.as_slice() else {
+                panic!("Expected incompatible operands, got {errors:?}");
+            };
+            assert_eq!((op.as_str(), lhs.as_str(), rhs.as_str()), ("+", "BOOLEAN", "INTEGER"));
+        }
+
+        #[test]
+        fn rejects_mixed_integer_and_real() {
+            let errors = errors("MODULE m; VAR r: REAL; BEGIN r := r + 1 END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::IncompatibleOperands { .. }]));
+        }
+
+        #[test]
+        fn rejects_assignment_of_wrong_type() {
+            let errors = errors("MODULE m; VAR b: BOOLEAN; BEGIN b := 1 END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::TypeMismatch { expected, found, .. }] if expected == "BOOLEAN" && found == "INTEGER"));
+        }
+
+        #[test]
+        fn rejects_unknown_field() {
+            let errors = errors("MODULE m; VAR r: RECORD a: INTEGER END; BEGIN r.b := 1 END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::UnknownField { field, .. }] if field == "b"));
+        }
+
+        #[test]
+        fn resolves_fields_of_base_records_and_type_tests() {
+            let source = r#"
+                MODULE m;
+                TYPE
+                  Shape = POINTER TO ShapeDesc; ShapeDesc = RECORD x, y: INTEGER END;
+                  Circle = POINTER TO CircleDesc; CircleDesc = RECORD (ShapeDesc) r: INTEGER END;
+                VAR s: Shape; c: Circle;
+                BEGIN
+                  NEW(c); s := c; c.x := 1;
+                  IF s IS Circle THEN s(Circle).r := 2 END
+                END m.
+            "#;
+            assert_eq!(check_source(source), Ok(()));
+        }
+
+        #[test]
+        fn tests_the_type_of_record_var_parameters() {
+            let source = r#"
+                MODULE m;
+                TYPE R = RECORD END; R2 = RECORD (R) b: INTEGER END;
+                PROCEDURE p(VAR r: R);
+                BEGIN IF r IS R2 THEN r(R2).b := 1 END;
+                  CASE r OF R2: r.b := 2 END
+                END p;
+                END m.
+            "#;
+            assert_eq!(check_source(source), Ok(()));
+        }
+
+        #[test]
+        fn rejects_type_tests_on_records_that_are_not_var_parameters() {
+            let declarations = "MODULE m; TYPE R = RECORD END; R2 = RECORD (R) b: INTEGER END; S = RECORD r: R END; VAR r: R; s: S; t: BOOLEAN;";
+            let guard = errors(&format!("{declarations} BEGIN r(R2).b := 1 END m."));
+            assert!(matches!(guard.as_slice(), [AnalysisError::InvalidSelector { .. }]), "{guard:?}");
+            let test = errors(&format!("{declarations} BEGIN t := r IS R2 END m."));
+            assert!(matches!(test.as_slice(), [AnalysisError::InvalidOperand { op, .. }] if op == "IS"), "{test:?}");
+            let case = errors(&format!("{declarations} BEGIN CASE r OF R2: t := TRUE END END m."));
+            assert!(matches!(case.as_slice(), [AnalysisError::InvalidOperand { op, .. }] if op == "CASE"), "{case:?}");
+            let value_parameter = errors(&format!("{declarations} PROCEDURE p(v: R): BOOLEAN; RETURN v IS R2 END p; END m."));
+            assert!(matches!(value_parameter.as_slice(), [AnalysisError::InvalidOperand { .. }]), "{value_parameter:?}");
+            let field = errors(&format!("{declarations} PROCEDURE p(VAR v: S); BEGIN v.r(R2).b := 1 END p; END m."));
+            assert!(matches!(field.as_slice(), [AnalysisError::InvalidSelector { .. }]), "{field:?}");
+        }
+
+        #[test]
+        fn rejects_assignment_of_base_to_extension() {
+            let source = r#"
+                MODULE m;
+                TYPE
+                  Shape = POINTER TO ShapeDesc; ShapeDesc = RECORD END;
+                  Circle = POINTER TO CircleDesc; CircleDesc = RECORD (ShapeDesc) END;
+                VAR s: Shape; c: Circle;
+                BEGIN c := s END m.
+            "#;
+            assert!(matches!(errors(source).as_slice(), [AnalysisError::TypeMismatch { .. }]));
+        }
+
+        #[test]
+        fn rejects_constant_index_out_of_range() {
+            let errors = errors("MODULE m; VAR a: ARRAY 3 OF INTEGER; BEGIN a[3] := 0 END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::IndexOutOfRange { index: 3, length: 3, .. }]));
+        }
+    }
+
+    mod statements {
+        use super::*;
+
+        #[test]
+        fn rejects_non_boolean_condition() {
+            let errors = errors("MODULE m; VAR i: INTEGER; BEGIN WHILE i DO i := 0 END END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::TypeMismatch { expected, .. }] if expected == "BOOLEAN"));
+        }
+
+        #[test]
+        fn rejects_assignment_to_constant() {
+            let errors = errors("MODULE m; CONST c = 1; BEGIN c := 2 END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::NotAssignable { .. }]));
+        }
+
+        #[test]
+        fn rejects_duplicate_case_labels() {
+            let source = "MODULE m; VAR i: INTEGER; BEGIN CASE i OF 1..5: i := 0 | 4: i := 1 END END m.";
+            assert!(matches!(errors(source).as_slice(), [AnalysisError::DuplicateCaseLabel { .. }]));
+        }
+
+        #[test]
+        fn accepts_characters_and_one_character_strings_for_char() {
+            let source = r#"
+                MODULE m;
+                CONST nl = 0AX; a = "a";
+                VAR c: CHAR; s: ARRAY 4 OF CHAR;
+                BEGIN c := "x"; c := 41X; c := nl; c := a; s := 0X;
+                  CASE c OF 0X .. 1FX: c := " " | "b" .. 7AX, a: c := 0FFX END
+                END m.
+            "#;
+            assert_eq!(check_source(source), Ok(()));
+        }
+
+        #[test]
+        fn rejects_longer_strings_for_char() {
+            let assignment = errors("MODULE m; VAR c: CHAR; BEGIN c := \"ab\" END m.");
+            assert!(matches!(assignment.as_slice(), [AnalysisError::TypeMismatch { .. }]), "{assignment:?}");
+            let label = errors("MODULE m; VAR c: CHAR; BEGIN CASE c OF \"ab\": c := 0X END END m.");
+            assert!(matches!(label.as_slice(), [AnalysisError::TypeMismatch { .. }]), "{label:?}");
+        }
+
+        #[test]
+        fn rejects_zero_for_step() {
+            let source = "MODULE m; VAR i: INTEGER; BEGIN FOR i := 0 TO 9 BY 0 DO i := i END END m.";
+            assert!(matches!(errors(source).as_slice(), [AnalysisError::InvalidForStep { .. }]));
+        }
+
+        #[test]
+        fn narrows_variable_in_type_case() {
+            let source = r#"
+                MODULE m;
+                TYPE
+                  Shape = POINTER TO ShapeDesc; ShapeDesc = RECORD END;
+                  Circle = POINTER TO CircleDesc; CircleDesc = RECORD (ShapeDesc) r: INTEGER END;
+                VAR s: Shape;
+                BEGIN CASE s OF Circle: s.r := 1 END END m.
+            "#;
+            assert_eq!(check_source(source), Ok(()));
+        }
+    }
+
+    mod procedures {
+        use super::*;
+
+        #[test]
+        fn checks_calls_against_signature() {
+            let source = r#"
+                MODULE m;
+                VAR x: INTEGER;
+                PROCEDURE Add(a, b: INTEGER): INTEGER; RETURN a + b END Add;
+                PROCEDURE Set(VAR v: INTEGER); BEGIN v := Add(v, 1) END Set;
+                BEGIN Set(x); INC(x); x := Add(x, ABS(x)) END m.
+            "#;
+            assert_eq!(check_source(source), Ok(()));
+        }
+
+        #[test]
+        fn rejects_wrong_argument_count() {
+            let source = "MODULE m; PROCEDURE p(a: INTEGER); END p; BEGIN p(1, 2) END m.";
+            assert!(matches!(errors(source).as_slice(), [AnalysisError::ArgumentCount { expected: 1, found: 2, .. }]));
+        }
+
+        #[test]
+        fn rejects_constant_for_var_parameter() {
+            let source = "MODULE m; PROCEDURE p(VAR a: INTEGER); END p; BEGIN p(1) END m.";
+            assert!(matches!(errors(source).as_slice(), [AnalysisError::NotAssignable { .. }]));
+        }
+
+        #[test]
+        fn reads_a_type_guard_on_a_procedure_as_a_call_without_changing_the_module() {
+            let source = "MODULE m; TYPE R = RECORD a: INTEGER END; VAR r: R; PROCEDURE p(x: INTEGER); END p; BEGIN p(r.a) END m.";
+            let module = Parser::new(Lexer::new(source)).parse().unwrap();
+            let analysis = check(&module).unwrap();
+
+            let Some(stmts) = &module.stmts else { panic!("statements") };
+            let Statement::Call { callee, parameters: None, .. } = &stmts.statements[0] else { panic!("call") };
+            assert!(matches!(callee.selectors.as_slice(), [Selector::TypeGuard(..)]));
+
+            let (callee, arguments) = analysis.designator(callee, None);
+            assert!(callee.selectors.is_empty());
+            let Some([Expression::Designator { designator: argument, actual_parameters: None, .. }]) = arguments.as_deref() else {
+                panic!("one argument");
+            };
+            assert_eq!(argument.head.parts.len(), 2);
+            let (argument, _) = analysis.designator(argument, None);
+            assert_eq!(argument.head.parts.len(), 1);
+            assert!(matches!(argument.selectors.as_slice(), [Selector::Field(field)] if field.text == "a"));
+        }
+
+        #[test]
+        fn rejects_local_procedures_as_values() {
+            let source = r#"
+                MODULE N;
+                TYPE P = PROCEDURE;
+                VAR p: P;
+                PROCEDURE Run(q: P); BEGIN q END Run;
+                PROCEDURE G; END G;
+                PROCEDURE O;
+                  PROCEDURE L; END L;
+                BEGIN L; p := L; Run(L); p := G; Run(G) END O;
+                END N.
+            "#;
+            let errors = errors(source);
+            assert!(matches!(errors.as_slice(), [
+                AnalysisError::LocalProcedureAsValue { name: a, .. },
+                AnalysisError::LocalProcedureAsValue { name: b, .. },
+            ] if a == "L" && b == "L"), "{errors:?}");
+        }
+
+        #[test]
+        fn matches_procedure_types_with_and_without_an_empty_parameter_list() {
+            let source = r#"
+                MODULE m;
+                TYPE P = PROCEDURE; Q = PROCEDURE ();
+                VAR p: P; q: Q;
+                PROCEDURE r; END r;
+                BEGIN p := r; q := p; p := q END m.
+            "#;
+            assert_eq!(check_source(source), Ok(()));
+        }
+
+        #[test]
+        fn passes_arrays_to_open_array_parameters() {
+            let source = r#"
+                MODULE m;
+                VAR a: ARRAY 4 OF INTEGER;
+                PROCEDURE Sum(v: ARRAY OF INTEGER): INTEGER;
+                  VAR i, s: INTEGER;
+                BEGIN s := 0; FOR i := 0 TO LEN(v) - 1 DO s := s + v[i] END
+                RETURN s END Sum;
+                BEGIN a[0] := Sum(a) END m.
+            "#;
+            assert_eq!(check_source(source), Ok(()));
+        }
+
+        #[test]
+        fn requires_return_in_function_procedure() {
+            let errors = errors("MODULE m; PROCEDURE f(): INTEGER; END f; END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::MissingReturn { name, .. }] if name == "f"));
+        }
+
+        #[test]
+        fn rejects_function_called_as_statement() {
+            let errors = errors("MODULE m; PROCEDURE f(): INTEGER; RETURN 1 END f; BEGIN f END m.");
+            assert!(matches!(errors.as_slice(), [AnalysisError::FunctionAsStatement { .. }]));
+        }
+
+        #[test]
+        fn allows_mutual_recursion_and_outer_variables() {
+            let source = r#"
+                MODULE m;
+                PROCEDURE Even(n: INTEGER): BOOLEAN;
+                  VAR r: BOOLEAN;
+                  PROCEDURE Inner; BEGIN r := Odd(n - 1) END Inner;
+                BEGIN IF n = 0 THEN r := TRUE ELSE Inner END
+                RETURN r END Even;
+                PROCEDURE Odd(n: INTEGER): BOOLEAN;
+                RETURN (n # 0) & Even(n - 1) END Odd;
+                END m.
+            "#;
+            assert_eq!(check_source(source), Ok(()));
+        }
+
+        #[test]
+        fn rejects_assignment_to_structured_value_parameter() {
+            let source = "MODULE m; PROCEDURE p(a: ARRAY OF INTEGER); BEGIN a[0] := 1 END p; END m.";
+            assert!(matches!(errors(source).as_slice(), [AnalysisError::NotAssignable { .. }]));
+        }
+    }
+
+    mod imports {
+        use super::*;
+        use crate::frontend::span::Position;
+
+        const SHAPES: &str = r#"
+            MODULE Shapes;
+            CONST Max* = 10;
+            TYPE Point* = RECORD x*, y*: INTEGER; tag: CHAR END; Ref* = POINTER TO Point;
+            VAR origin*: Point; count*: INTEGER;
+            PROCEDURE Make*(x, y: INTEGER): Ref; VAR p: Ref; BEGIN NEW(p); p.x := x; p.y := y RETURN p END Make;
+            END Shapes.
+        "#;
+
+        fn interface(source: &str) -> SymbolFile {
+            let module = Parser::new(Lexer::new(source)).parse().unwrap();
+            let analysis = check(&module).unwrap();
+            SymbolFile::export(&module.name.text, &analysis)
+        }
+
+        fn check_importing(source: &str) -> Result<Analysis, Vec<AnalysisError>> {
+            let module = Parser::new(Lexer::new(source)).parse().unwrap();
+            check_with_imports(&module, &[interface(SHAPES)])
+        }
+
+        #[test]
+        fn resolves_qualified_identifiers_against_the_symbol_file() {
+            let source = r#"
+                MODULE m;
+                IMPORT S := Shapes;
+                TYPE Circle = RECORD (S.Point) radius: INTEGER END;
+                VAR p: S.Ref; c: POINTER TO Circle; a: ARRAY S.Max OF INTEGER; n: INTEGER;
+                BEGIN p := S.Make(1, 2); NEW(c); p := c; n := p.x + S.origin.y + S.count + LEN(a);
+                  IF p IS S.Ref THEN n := 0 END
+                END m.
+            "#;
+            let analysis = check_importing(source).unwrap();
+            let table = &analysis.symbols;
+            let offset = source.find("S.Make").unwrap() + 2;
+            let make = table.symbol(table.symbol_at(Position { offset, line: 1, column: 1 }).unwrap());
+            assert_eq!((make.name.as_str(), &make.kind), ("Make", &SymbolKind::Procedure));
+            assert_eq!(table.scope(make.scope).kind, ScopeKind::Import);
+        }
+
+        #[test]
+        fn rejects_unknown_members_and_modules() {
+            let errors = check_importing("MODULE m; IMPORT Shapes, Lines; BEGIN Shapes.Draw; Lines.Draw END m.").unwrap_err();
+            assert!(matches!(errors.as_slice(), [
+                AnalysisError::UnknownModule { module, .. },
+                AnalysisError::UnresolvedImport { name, .. },
+            ] if module == "Lines" && name == "Draw"), "{errors:?}");
+        }
+
+        #[test]
+        fn makes_imported_variables_read_only() {
+            let errors = check_importing("MODULE m; IMPORT Shapes; BEGIN Shapes.count := 1 END m.").unwrap_err();
+            assert!(matches!(errors.as_slice(), [AnalysisError::NotAssignable { .. }]), "{errors:?}");
+            assert!(check_importing("MODULE m; IMPORT Shapes; BEGIN Shapes.origin.x := 1 END m.").is_err());
+        }
+
+        #[test]
+        fn rejects_modules_compiled_against_a_changed_interface() {
+            let client = Parser::new(Lexer::new("MODULE Client; IMPORT Shapes; VAR n*: INTEGER; BEGIN n := Shapes.count END Client.")).parse().unwrap();
+            let client = SymbolFile::export("Client", &check_with_imports(&client, &[interface(SHAPES)]).unwrap());
+            let check = |shapes: &str| {
+                let module = Parser::new(Lexer::new("MODULE m; IMPORT Client; BEGIN ASSERT(Client.n = 0) END m.")).parse().unwrap();
+                check_with_imports(&module, &[client.clone(), interface(shapes)])
+            };
+
+            // Client does not use Max.
+            assert!(check(&SHAPES.replace("Max* = 10", "Max* = 11")).is_ok());
+            let errors = check(&SHAPES.replace("count*: INTEGER", "count*: REAL")).unwrap_err();
+            assert!(matches!(errors.as_slice(), [
+                AnalysisError::StaleImport { module, changed, .. },
+            ] if module == "Client" && changed == "Shapes.count"), "{errors:?}");
+        }
+
+        #[test]
+        fn hides_fields_that_are_not_exported() {
+            let errors = check_importing(r#"MODULE m; IMPORT Shapes; VAR p: Shapes.Point; BEGIN p.tag := "a" END m."#).unwrap_err();
+            assert!(matches!(errors.as_slice(), [AnalysisError::UnknownField { .. }]), "{errors:?}");
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::frontend::intern::Name;
 use crate::frontend::span;
 use crate::frontend::span::{Span, Spanned};
 
@@ -15,9 +16,11 @@ pub struct Module {
 impl Spanned for Module {
     fn span(&self) -> Span { self.span }
 }
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Import {
     pub module: Identifier,
+    /// The local name in `IMPORT Alias := Module`.
     pub alias: Option<Identifier>,
     pub span: Span,
 }
@@ -39,6 +42,7 @@ pub struct Declarations {
 pub struct ConstDeclaration {
     pub ident: IdentifierDef,
     pub value: Expression,
+    pub comments: Comments,
 }
 
 impl Spanned for ConstDeclaration {
@@ -47,10 +51,15 @@ impl Spanned for ConstDeclaration {
     }
 }
 
+impl Commented for ConstDeclaration {
+    fn comments(&self) -> &Comments { &self.comments }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct TypeDeclaration {
     pub ident: IdentifierDef,
     pub ty: Type,
+    pub comments: Comments,
 }
 
 impl Spanned for TypeDeclaration {
@@ -59,10 +68,15 @@ impl Spanned for TypeDeclaration {
     }
 }
 
+impl Commented for TypeDeclaration {
+    fn comments(&self) -> &Comments { &self.comments }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct VarDeclaration {
     pub variables: Vec<IdentifierDef>,
     pub ty: Type,
+    pub comments: Comments,
 }
 
 impl Spanned for VarDeclaration {
@@ -71,12 +85,17 @@ impl Spanned for VarDeclaration {
     }
 }
 
+impl Commented for VarDeclaration {
+    fn comments(&self) -> &Comments { &self.comments }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct ProcedureDeclaration {
     pub header: ProcedureHeader,
     pub body: ProcedureBody,
     pub name: Identifier,
     pub span: Span,
+    pub comments: Comments,
 }
 
 impl Spanned for ProcedureDeclaration {
@@ -85,6 +104,10 @@ impl Spanned for ProcedureDeclaration {
     }
 }
 
+impl Commented for ProcedureDeclaration {
+    fn comments(&self) -> &Comments { &self.comments }
+}
+
 // --------------------------- EXPRESSIONS ---------------------------
 #[derive(Clone, Debug, PartialEq)]
 pub struct Element {
@@ -101,6 +124,8 @@ pub enum Expression {
     Int { value: i64, span: Span },
     Real   { value: f64, span: Span },
     String { value: String, span: Span },
+    /// A character constant, `41X`.
+    Char { value: u8, span: Span },
     Nil    { span: Span },
     False    { span: Span },
     True    { span: Span },
@@ -108,6 +133,8 @@ pub enum Expression {
     Designator { designator: Designator, actual_parameters: Option<Vec<Expression>>, span: Span },
     Unary { op: UnaryOperation, operand: Box<Expression>, span: Span },
     Binary { op: BinaryOperation, lhs: Box<Expression>, rhs: Box<Expression>, span: Span },
+    /// Source the parser skipped after a syntax error.
+    Error { span: Span },
 }
 
 impl Spanned for Expression {
@@ -116,6 +143,7 @@ impl Spanned for Expression {
             Expression::Int { span, .. } => *span,
             Expression::Real { span, .. } => *span,
             Expression::String { span, .. } => *span,
+            Expression::Char { span, .. } => *span,
             Expression::Nil { span, .. } => *span,
             Expression::True { span, .. } => *span,
             Expression::False { span, .. } => *span,
@@ -123,6 +151,7 @@ impl Spanned for Expression {
             Expression::Designator { span, .. } => *span,
             Expression::Unary { span, .. } => *span,
             Expression::Binary { span, .. } => *span,
+            Expression::Error { span } => *span,
         }
     }
 }
@@ -132,12 +161,45 @@ pub enum UnaryOperation {
     Not, Plus, Minus
 }
 
+impl UnaryOperation {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOperation::Not => "~",
+            UnaryOperation::Plus => "+",
+            UnaryOperation::Minus => "-",
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BinaryOperation {
     Addition, Subtraction, Multiplication, Division, Mod, Div, And, Or,
     Eq, Neq, Lt, Le, Gt, Ge, In, Is
 }
 
+impl BinaryOperation {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOperation::Addition => "+",
+            BinaryOperation::Subtraction => "-",
+            BinaryOperation::Multiplication => "*",
+            BinaryOperation::Division => "/",
+            BinaryOperation::Mod => "MOD",
+            BinaryOperation::Div => "DIV",
+            BinaryOperation::And => "&",
+            BinaryOperation::Or => "OR",
+            BinaryOperation::Eq => "=",
+            BinaryOperation::Neq => "#",
+            BinaryOperation::Lt => "<",
+            BinaryOperation::Le => "<=",
+            BinaryOperation::Gt => ">",
+            BinaryOperation::Ge => ">=",
+            BinaryOperation::In => "IN",
+            BinaryOperation::Is => "IS",
+        }
+    }
+}
+
 // --------------------------- TYPES ---------------------------
 #[derive(Clone, Debug, PartialEq)]
 pub enum Type {
@@ -146,6 +208,8 @@ pub enum Type {
     Record  { base: Option<QualifiedIdentifier>, field_lists: Vec<FieldList>, span: Span },
     Pointer { pointee: Box<Type>, span: Span },
     Procedure { params: Option<FormalParameters>, span: Span },
+    /// Source the parser skipped after a syntax error.
+    Error { span: Span },
 }
 
 impl Spanned for Type {
@@ -156,6 +220,7 @@ impl Spanned for Type {
             Type::Record { span, .. } => *span,
             Type::Pointer { span, .. } => *span,
             Type::Procedure { span, .. } => *span,
+            Type::Error { span } => *span,
         }
     }
 }
@@ -202,13 +267,15 @@ impl Spanned for FormalType { fn span(&self) -> Span { self.span } }
 // --------------------------- STATEMENTS ---------------------------
 #[derive(Clone, Debug, PartialEq)]
 pub enum Statement {
-    Assign { target: Designator, value: Expression, span: Span },
-    Call   { callee: Designator, parameters: Option<Vec<Expression>>, span: Span },
-    If     { cond: Expression, stmts: StatementSequence, elsif_branches: Vec<ElsIf>, else_branch: Option<StatementSequence>, span: Span },
-    Case  { expr: Expression, branches: Vec<Case>, span: Span },
-    While  { cond: Expression, stmts: StatementSequence, elsif_branches: Vec<ElsIf>, span: Span },
-    Repeat { stmts: StatementSequence, cond: Expression, span: Span },
-    For    { var: Identifier, low: Expression, high: Expression, by: Option<Expression>, stmts: StatementSequence, span: Span },
+    Assign { target: Designator, value: Expression, span: Span, comments: Comments },
+    Call   { callee: Designator, parameters: Option<Vec<Expression>>, span: Span, comments: Comments },
+    If     { cond: Expression, stmts: StatementSequence, elsif_branches: Vec<ElsIf>, else_branch: Option<StatementSequence>, span: Span, comments: Comments },
+    Case  { expr: Expression, branches: Vec<Case>, span: Span, comments: Comments },
+    While  { cond: Expression, stmts: StatementSequence, elsif_branches: Vec<ElsIf>, span: Span, comments: Comments },
+    Repeat { stmts: StatementSequence, cond: Expression, span: Span, comments: Comments },
+    For    { var: Identifier, low: Expression, high: Expression, by: Option<Box<Expression>>, stmts: StatementSequence, span: Span, comments: Comments },
+    /// Source the parser skipped after a syntax error.
+    Error  { span: Span, comments: Comments },
 }
 
 impl Spanned for Statement {
@@ -221,6 +288,22 @@ impl Spanned for Statement {
             Statement::While  { span, .. } => *span,
             Statement::Repeat { span, .. } => *span,
             Statement::For    { span, .. } => *span,
+            Statement::Error  { span, .. } => *span,
+        }
+    }
+}
+
+impl Commented for Statement {
+    fn comments(&self) -> &Comments {
+        match self {
+            Statement::Assign { comments, .. } => comments,
+            Statement::Call   { comments, .. } => comments,
+            Statement::If     { comments, .. } => comments,
+            Statement::Case   { comments, .. } => comments,
+            Statement::While  { comments, .. } => comments,
+            Statement::Repeat { comments, .. } => comments,
+            Statement::For    { comments, .. } => comments,
+            Statement::Error  { comments, .. } => comments,
         }
     }
 }
@@ -266,6 +349,7 @@ impl Spanned for Label {
 pub enum LabelValue {
     Integer { value: i64, span: Span },
     String { value: String, span: Span },
+    Char { value: u8, span: Span },
     QualifiedIdentifier (QualifiedIdentifier),
 }
 
@@ -274,6 +358,7 @@ impl Spanned for LabelValue {
         match self {
             LabelValue::Integer { span, .. } => *span,
             LabelValue::String   { span, .. } => *span,
+            LabelValue::Char   { span, .. } => *span,
             LabelValue::QualifiedIdentifier ( ident, .. ) => ident.span()
         }
     }
@@ -308,6 +393,32 @@ pub struct ProcedureBody {
 
 impl Spanned for ProcedureBody { fn span(&self) -> Span { self.span } }
 
+// --------------------------- COMMENTS ---------------------------
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comment {
+    /// The comment including its `(*` and `*)`.
+    pub text: String,
+    pub span: Span,
+}
+
+impl Spanned for Comment {
+    fn span(&self) -> Span { self.span }
+}
+
+/// The comments of a declaration or statement: those between it and the token before it, and
+/// those following it on its last line (after its semicolon, if it has one). Only a parser
+/// reading from a lexer that keeps trivia finds any.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Comments {
+    pub leading: Vec<Comment>,
+    pub trailing: Vec<Comment>,
+}
+
+/// A declaration or statement, which carries the comments attached to it.
+pub trait Commented {
+    fn comments(&self) -> &Comments;
+}
+
 // --------------------------- DESIGNATORS, SELECTORS, IDENTIFIERS ---------------------------
 #[derive(Clone, Debug, PartialEq)]
 pub struct Designator {
@@ -345,6 +456,13 @@ pub struct QualifiedIdentifier {
     pub parts: Vec<Identifier>, // len >= 1
 }
 
+impl QualifiedIdentifier {
+    /// The identifier of the entity itself, after a possible module qualifier.
+    pub fn ident(&self) -> &Identifier {
+        self.parts.last().unwrap()
+    }
+}
+
 impl Spanned for QualifiedIdentifier {
     fn span(&self) -> Span {
         let start = self.parts.first().unwrap().span.start;
@@ -366,7 +484,8 @@ impl Spanned for IdentifierDef {
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Identifier {
-    pub text: String,
+    /// The name as the lexer interned it.
+    pub text: Name,
     pub span: Span,
 }
 
@@ -0,0 +1,441 @@
+use thiserror::Error;
+use crate::frontend::ast::{BinaryOperation, Designator, Element, Expression, LabelValue, QualifiedIdentifier, Selector, UnaryOperation};
+use crate::frontend::literal::IntegerWidth;
+use crate::frontend::span::{Span, Spanned};
+use crate::frontend::symbols::Builtin;
+
+/// Number of elements of a SET.
+pub const SET_BITS: i64 = 64;
+
+/// The value of a constant expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    Real(f64),
+    Boolean(bool),
+    Char(u8),
+    Set(u64),
+    String(String),
+    Nil,
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ConstError {
+    #[error("expression is not constant")]
+    NotConstant {
+        span: Span,
+    },
+
+    #[error("constant expression overflows")]
+    Overflow {
+        span: Span,
+    },
+
+    #[error("division by zero in constant expression")]
+    DivisionByZero {
+        span: Span,
+    },
+
+    #[error("constant is out of range")]
+    OutOfRange {
+        span: Span,
+    },
+
+    #[error("operator '{op}' cannot be applied to these constants")]
+    InvalidOperand {
+        op: String,
+        span: Span,
+    },
+}
+
+/// What a name in a constant expression stands for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Named {
+    Const(Value),
+    Builtin(Builtin),
+}
+
+/// Evaluates a constant expression with INTEGERs of `width` bits; `resolve` gives the meaning of
+/// named constants and predeclared functions, and returns `None` for anything that is not constant.
+pub fn evaluate(expr: &Expression, width: IntegerWidth, resolve: &impl Fn(&QualifiedIdentifier) -> Option<Named>) -> Result<Value, ConstError> {
+    match expr {
+        Expression::Int { value, .. } => Ok(Value::Integer(*value)),
+        Expression::Real { value, .. } => Ok(Value::Real(*value)),
+        Expression::String { value, .. } => Ok(string(value)),
+        Expression::Char { value, .. } => Ok(Value::Char(*value)),
+        Expression::Nil { .. } => Ok(Value::Nil),
+        Expression::True { .. } => Ok(Value::Boolean(true)),
+        Expression::False { .. } => Ok(Value::Boolean(false)),
+        Expression::Set { elements, .. } => {
+            let mut bits = 0;
+            for element in elements {
+                bits |= set_element(element, width, resolve)?;
+            }
+            Ok(Value::Set(bits))
+        }
+        Expression::Designator { designator, actual_parameters, span } => {
+            let not_constant = || ConstError::NotConstant { span: *span };
+            match (designator, actual_parameters) {
+                (Designator { head, selectors, .. }, None) if selectors.is_empty() => match resolve(head) {
+                    Some(Named::Const(value)) => Ok(value),
+                    _ => Err(not_constant()),
+                },
+                // `ABS(x)` with a single named argument parses as a type guard.
+                (Designator { head, selectors, .. }, None) => match (resolve(head), selectors.as_slice()) {
+                    (Some(Named::Builtin(builtin)), [Selector::TypeGuard(argument, _)]) => {
+                        let value = match resolve(argument) {
+                            Some(Named::Const(value)) => value,
+                            _ => return Err(ConstError::NotConstant { span: argument.span() }),
+                        };
+                        call(builtin, &[value], width, *span)
+                    }
+                    _ => Err(not_constant()),
+                },
+                (Designator { head, selectors, .. }, Some(arguments)) if selectors.is_empty() => match resolve(head) {
+                    Some(Named::Builtin(builtin)) => {
+                        let values = arguments.iter().map(|a| evaluate(a, width, resolve)).collect::<Result<Vec<_>, _>>()?;
+                        call(builtin, &values, width, *span)
+                    }
+                    _ => Err(not_constant()),
+                },
+                _ => Err(not_constant()),
+            }
+        }
+        Expression::Unary { op, operand, span } => unary(*op, &evaluate(operand, width, resolve)?, width, *span),
+        Expression::Binary { op, lhs, rhs, span } => {
+            let lhs = evaluate(lhs, width, resolve)?;
+            if *op == BinaryOperation::Is {
+                return Err(ConstError::NotConstant { span: *span });
+            }
+            binary(*op, &lhs, &evaluate(rhs, width, resolve)?, width, *span)
+        }
+        Expression::Error { span } => Err(ConstError::NotConstant { span: *span }),
+    }
+}
+
+/// Evaluates a CASE label.
+pub fn label(value: &LabelValue, resolve: &impl Fn(&QualifiedIdentifier) -> Option<Named>) -> Result<Value, ConstError> {
+    match value {
+        LabelValue::Integer { value, .. } => Ok(Value::Integer(*value)),
+        LabelValue::String { value, .. } => Ok(string(value)),
+        LabelValue::Char { value, .. } => Ok(Value::Char(*value)),
+        LabelValue::QualifiedIdentifier(name) => match resolve(name) {
+            Some(Named::Const(value)) => Ok(value),
+            _ => Err(ConstError::NotConstant { span: name.span() }),
+        },
+    }
+}
+
+/// A string constant of length one is a character.
+pub fn string(value: &str) -> Value {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Value::Char(c as u8),
+        _ => Value::String(value.to_string()),
+    }
+}
+
+fn set_element(element: &Element, width: IntegerWidth, resolve: &impl Fn(&QualifiedIdentifier) -> Option<Named>) -> Result<u64, ConstError> {
+    let low = set_bit(&element.first, width, resolve)?;
+    let high = match &element.second {
+        Some(second) => set_bit(second, width, resolve)?,
+        None => low,
+    };
+    Ok(set_range(low, high))
+}
+
+fn set_bit(expr: &Expression, width: IntegerWidth, resolve: &impl Fn(&QualifiedIdentifier) -> Option<Named>) -> Result<i64, ConstError> {
+    match evaluate(expr, width, resolve)? {
+        Value::Integer(n) if (0..SET_BITS).contains(&n) => Ok(n),
+        Value::Integer(_) => Err(ConstError::OutOfRange { span: expr.span() }),
+        _ => Err(ConstError::InvalidOperand { op: "{}".to_string(), span: expr.span() }),
+    }
+}
+
+/// The set `{low..high}`; empty when `low > high`.
+pub fn set_range(low: i64, high: i64) -> u64 {
+    (low..=high).fold(0, |bits, i| bits | (1u64 << i))
+}
+
+pub fn unary(op: UnaryOperation, value: &Value, width: IntegerWidth, span: Span) -> Result<Value, ConstError> {
+    match (op, value) {
+        (UnaryOperation::Not, Value::Boolean(b)) => Ok(Value::Boolean(!b)),
+        (UnaryOperation::Plus, Value::Integer(_) | Value::Real(_)) => Ok(value.clone()),
+        (UnaryOperation::Minus, Value::Integer(n)) => integer(n.checked_neg(), width, span),
+        (UnaryOperation::Minus, Value::Real(x)) => Ok(Value::Real(-x)),
+        (UnaryOperation::Minus, Value::Set(s)) => Ok(Value::Set(!s)),
+        _ => Err(ConstError::InvalidOperand { op: op.symbol().to_string(), span }),
+    }
+}
+
+pub fn binary(op: BinaryOperation, lhs: &Value, rhs: &Value, width: IntegerWidth, span: Span) -> Result<Value, ConstError> {
+    use BinaryOperation::*;
+    let invalid = || ConstError::InvalidOperand { op: op.symbol().to_string(), span };
+    let overflow = || ConstError::Overflow { span };
+    let value = match (lhs, rhs) {
+        (Value::Integer(a), Value::Integer(b)) => {
+            let (a, b) = (*a, *b);
+            match op {
+                Addition => integer(a.checked_add(b), width, span)?,
+                Subtraction => integer(a.checked_sub(b), width, span)?,
+                Multiplication => integer(a.checked_mul(b), width, span)?,
+                Div | Mod if b == 0 => return Err(ConstError::DivisionByZero { span }),
+                Div => integer(floor_div(a, b), width, span)?,
+                Mod => integer(floor_mod(a, b), width, span)?,
+                _ => Value::Boolean(compare(op, &a, &b).ok_or_else(invalid)?),
+            }
+        }
+        (Value::Integer(a), Value::Set(s)) if op == In => Value::Boolean((0..SET_BITS).contains(a) && s & (1 << a) != 0),
+        (Value::Real(a), Value::Real(b)) => {
+            let (a, b) = (*a, *b);
+            let result = match op {
+                Addition => a + b,
+                Subtraction => a - b,
+                Multiplication => a * b,
+                Division if b == 0.0 => return Err(ConstError::DivisionByZero { span }),
+                Division => a / b,
+                _ => return Ok(Value::Boolean(compare(op, &a, &b).ok_or_else(invalid)?)),
+            };
+            if !result.is_finite() {
+                return Err(overflow());
+            }
+            Value::Real(result)
+        }
+        (Value::Boolean(a), Value::Boolean(b)) => match op {
+            And => Value::Boolean(*a && *b),
+            Or => Value::Boolean(*a || *b),
+            Eq => Value::Boolean(a == b),
+            Neq => Value::Boolean(a != b),
+            _ => return Err(invalid()),
+        },
+        (Value::Set(a), Value::Set(b)) => match op {
+            Addition => Value::Set(a | b),
+            Subtraction => Value::Set(a & !b),
+            Multiplication => Value::Set(a & b),
+            Division => Value::Set(a ^ b),
+            Eq => Value::Boolean(a == b),
+            Neq => Value::Boolean(a != b),
+            _ => return Err(invalid()),
+        },
+        (Value::Char(a), Value::Char(b)) => Value::Boolean(compare(op, a, b).ok_or_else(invalid)?),
+        (Value::Char(_) | Value::String(_), Value::Char(_) | Value::String(_)) => {
+            Value::Boolean(compare(op, &text(lhs), &text(rhs)).ok_or_else(invalid)?)
+        }
+        (Value::Nil, Value::Nil) => match op {
+            Eq => Value::Boolean(true),
+            Neq => Value::Boolean(false),
+            _ => return Err(invalid()),
+        },
+        _ => return Err(invalid()),
+    };
+    Ok(value)
+}
+
+/// An INTEGER result, or an overflow when there is none or it does not fit in `width` bits.
+fn integer(value: Option<i64>, width: IntegerWidth, span: Span) -> Result<Value, ConstError> {
+    match value {
+        Some(n) if (width.min()..=width.max()).contains(&n) => Ok(Value::Integer(n)),
+        _ => Err(ConstError::Overflow { span }),
+    }
+}
+
+/// `DIV` rounds towards negative infinity.
+pub fn floor_div(a: i64, b: i64) -> Option<i64> {
+    let q = a.checked_div(b)?;
+    Some(if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q })
+}
+
+/// `MOD` has the sign of the divisor, so that `a = (a DIV b) * b + a MOD b`.
+pub fn floor_mod(a: i64, b: i64) -> Option<i64> {
+    let r = a.checked_rem(b)?;
+    Some(if r != 0 && (r < 0) != (b < 0) { r + b } else { r })
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::Char(c) => (*c as char).to_string(),
+        Value::String(s) => s.clone(),
+        _ => String::new(),
+    }
+}
+
+fn compare<T: PartialOrd>(op: BinaryOperation, a: &T, b: &T) -> Option<bool> {
+    match op {
+        BinaryOperation::Eq => Some(a == b),
+        BinaryOperation::Neq => Some(a != b),
+        BinaryOperation::Lt => Some(a < b),
+        BinaryOperation::Le => Some(a <= b),
+        BinaryOperation::Gt => Some(a > b),
+        BinaryOperation::Ge => Some(a >= b),
+        _ => None,
+    }
+}
+
+/// Applies one of the predeclared functions allowed in constant expressions. The shifts work on
+/// the `width` bits of an INTEGER: `LSL` drops the bits shifted out and `ROR` rotates within them.
+pub fn call(builtin: Builtin, arguments: &[Value], width: IntegerWidth, span: Span) -> Result<Value, ConstError> {
+    let invalid = || ConstError::InvalidOperand { op: format!("{builtin:?}").to_uppercase(), span };
+    let bits = width.bits();
+    let value = match (builtin, arguments) {
+        (Builtin::Abs, [Value::Integer(n)]) => integer(n.checked_abs(), width, span)?,
+        (Builtin::Abs, [Value::Real(x)]) => Value::Real(x.abs()),
+        (Builtin::Odd, [Value::Integer(n)]) => Value::Boolean(n % 2 != 0),
+        (Builtin::Ord, [Value::Char(c)]) => Value::Integer(*c as i64),
+        (Builtin::Ord, [Value::Boolean(b)]) => Value::Integer(*b as i64),
+        (Builtin::Ord, [Value::Set(s)]) => integer(Some(*s as i64), width, span)?,
+        (Builtin::Chr, [Value::Integer(n)]) => match u8::try_from(*n) {
+            Ok(c) => Value::Char(c),
+            Err(_) => return Err(ConstError::OutOfRange { span }),
+        },
+        (Builtin::Lsl, [Value::Integer(x), Value::Integer(n)]) => Value::Integer(sign_extend(x << shift(*n, width, span)?, bits)),
+        (Builtin::Asr, [Value::Integer(x), Value::Integer(n)]) => Value::Integer(x >> shift(*n, width, span)?),
+        (Builtin::Ror, [Value::Integer(x), Value::Integer(n)]) => {
+            let n = shift(*n, width, span)?;
+            let x = *x as u64 & (u64::MAX >> (64 - bits));
+            let rotated = if n == 0 { x } else { (x >> n) | (x << (bits - n)) };
+            Value::Integer(sign_extend(rotated as i64, bits))
+        }
+        (Builtin::Floor, [Value::Real(x)]) => {
+            let floor = x.floor();
+            // MAX(INTEGER) + 1 is a power of two, so exact as a REAL even where MAX(INTEGER) is not.
+            if !(floor >= width.min() as f64 && floor < -(width.min() as f64)) {
+                return Err(ConstError::Overflow { span });
+            }
+            Value::Integer(floor as i64)
+        }
+        (Builtin::Flt, [Value::Integer(n)]) => Value::Real(*n as f64),
+        (Builtin::Abs | Builtin::Odd | Builtin::Ord | Builtin::Chr | Builtin::Lsl | Builtin::Asr
+            | Builtin::Ror | Builtin::Floor | Builtin::Flt, _) => return Err(invalid()),
+        _ => return Err(ConstError::NotConstant { span }),
+    };
+    Ok(value)
+}
+
+fn shift(n: i64, width: IntegerWidth, span: Span) -> Result<u32, ConstError> {
+    match u32::try_from(n) {
+        Ok(n) if n < width.bits() => Ok(n),
+        _ => Err(ConstError::OutOfRange { span }),
+    }
+}
+
+/// The INTEGER whose `bits` low bits are those of `x`.
+fn sign_extend(x: i64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    (x << shift) >> shift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+
+    fn resolve(name: &QualifiedIdentifier) -> Option<Named> {
+        match name.parts[0].text.as_str() {
+            "N" => Some(Named::Const(Value::Integer(10))),
+            "Max" => Some(Named::Const(Value::Integer(i64::MAX))),
+            "ABS" => Some(Named::Builtin(Builtin::Abs)),
+            "ODD" => Some(Named::Builtin(Builtin::Odd)),
+            "ORD" => Some(Named::Builtin(Builtin::Ord)),
+            "CHR" => Some(Named::Builtin(Builtin::Chr)),
+            "LSL" => Some(Named::Builtin(Builtin::Lsl)),
+            "ASR" => Some(Named::Builtin(Builtin::Asr)),
+            "ROR" => Some(Named::Builtin(Builtin::Ror)),
+            "FLOOR" => Some(Named::Builtin(Builtin::Floor)),
+            "FLT" => Some(Named::Builtin(Builtin::Flt)),
+            "INC" => Some(Named::Builtin(Builtin::Inc)),
+            _ => None,
+        }
+    }
+
+    fn eval(source: &str) -> Result<Value, ConstError> {
+        let module = Parser::new(Lexer::new(&format!("MODULE m; CONST c = {source}; END m."))).parse().unwrap();
+        evaluate(&module.declarations.const_declarations[0].value, IntegerWidth::default(), &resolve)
+    }
+
+    fn eval_32(source: &str) -> Result<Value, ConstError> {
+        let source = format!("MODULE m; CONST c = {source}; END m.");
+        let module = Parser::new(Lexer::new(&source)).with_integer_width(IntegerWidth::Bits32).parse().unwrap();
+        evaluate(&module.declarations.const_declarations[0].value, IntegerWidth::Bits32, &resolve)
+    }
+
+    #[test]
+    fn evaluates_arithmetic() {
+        assert_eq!(eval("N * 3 - 4 DIV 3"), Ok(Value::Integer(29)));
+        assert_eq!(eval("-7 MOD 3"), Ok(Value::Integer(-1)));
+        assert_eq!(eval("(-7) MOD 3"), Ok(Value::Integer(2)));
+        assert_eq!(eval("(-7) DIV 2"), Ok(Value::Integer(-4)));
+        assert_eq!(eval("7 DIV (-2)"), Ok(Value::Integer(-4)));
+        assert_eq!(eval("7 MOD (-2)"), Ok(Value::Integer(-1)));
+        assert_eq!(eval("1.5 * 2.0"), Ok(Value::Real(3.0)));
+        assert_eq!(eval("-9223372036854775808"), Ok(Value::Integer(i64::MIN)));
+        assert_eq!(eval("-9223372036854775807 - 1"), Ok(Value::Integer(i64::MIN)));
+    }
+
+    #[test]
+    fn evaluates_booleans_sets_chars_and_strings() {
+        assert_eq!(eval("(N > 5) & ~FALSE"), Ok(Value::Boolean(true)));
+        assert_eq!(eval("{0, 2..4} - {3}"), Ok(Value::Set(0b10101)));
+        assert_eq!(eval("3 IN {1..N}"), Ok(Value::Boolean(true)));
+        assert_eq!(eval("\"a\" < \"b\""), Ok(Value::Boolean(true)));
+        assert_eq!(eval("\"abc\" = \"abd\""), Ok(Value::Boolean(false)));
+        assert_eq!(eval("\"hello\""), Ok(Value::String("hello".to_string())));
+    }
+
+    #[test]
+    fn evaluates_predeclared_functions() {
+        assert_eq!(eval("ABS(-N)"), Ok(Value::Integer(10)));
+        assert_eq!(eval("ODD(N)"), Ok(Value::Boolean(false)));
+        assert_eq!(eval("ORD(\"A\")"), Ok(Value::Integer(65)));
+        assert_eq!(eval("CHR(66)"), Ok(Value::Char(b'B')));
+        assert_eq!(eval("LSL(1, 4) + ASR(-16, 2)"), Ok(Value::Integer(12)));
+        assert_eq!(eval("ROR(1, 1)"), Ok(Value::Integer(i64::MIN)));
+        assert_eq!(eval("FLOOR(-1.5)"), Ok(Value::Integer(-2)));
+        assert_eq!(eval("FLT(N)"), Ok(Value::Real(10.0)));
+        assert_eq!(eval("ABS(N)"), Ok(Value::Integer(10)));
+    }
+
+    #[test]
+    fn reports_overflow_with_span() {
+        let Err(ConstError::Overflow { span }) = eval("1 + Max * 2") else {
+            panic!("Expected overflow");
+        };
+        assert_eq!((span.start.column, span.end.column), (25, 32));
+    }
+
+    #[test]
+    fn checks_overflow_against_the_integer_width() {
+        assert!(matches!(eval_32("2147483647 + 1"), Err(ConstError::Overflow { .. })));
+        assert!(matches!(eval_32("-2147483648 - 1"), Err(ConstError::Overflow { .. })));
+        assert!(matches!(eval_32("65536 * 65536"), Err(ConstError::Overflow { .. })));
+        assert!(matches!(eval_32("ABS(-2147483648)"), Err(ConstError::Overflow { .. })));
+        assert!(matches!(eval_32("FLOOR(2147483648.0)"), Err(ConstError::Overflow { .. })));
+        assert!(matches!(eval_32("LSL(1, 32)"), Err(ConstError::OutOfRange { .. })));
+        assert_eq!(eval_32("2147483646 + 1"), Ok(Value::Integer(i32::MAX as i64)));
+        assert_eq!(eval_32("FLOOR(-2147483648.0)"), Ok(Value::Integer(i32::MIN as i64)));
+        assert_eq!(eval_32("LSL(1, 31)"), Ok(Value::Integer(i32::MIN as i64)));
+        assert_eq!(eval_32("ROR(1, 1)"), Ok(Value::Integer(i32::MIN as i64)));
+        assert_eq!(eval("2147483647 + 1"), Ok(Value::Integer(1 << 31)));
+    }
+
+    #[test]
+    fn reports_division_by_zero() {
+        assert!(matches!(eval("N DIV (N - 10)"), Err(ConstError::DivisionByZero { .. })));
+        assert!(matches!(eval("1.0 / 0.0"), Err(ConstError::DivisionByZero { .. })));
+    }
+
+    #[test]
+    fn reports_non_constant_operands() {
+        let Err(ConstError::NotConstant { span }) = eval("N + x") else {
+            panic!("Expected non-constant error");
+        };
+        assert_eq!(span.start.column, 25);
+        assert!(matches!(eval("INC(N)"), Err(ConstError::NotConstant { .. })));
+    }
+
+    #[test]
+    fn reports_out_of_range_arguments() {
+        assert!(matches!(eval("CHR(256)"), Err(ConstError::OutOfRange { .. })));
+        assert!(matches!(eval("{64}"), Err(ConstError::OutOfRange { .. })));
+    }
+}
@@ -0,0 +1,309 @@
+//! Diagnostics: what the frontend reports about a source file, rendered with the lines they point
+//! at.
+//!
+//! Every error of the lexer, the parser and the analysis converts into a `Diagnostic` with a
+//! stable error code, a message and a primary span. Some also carry secondary labels, such as the
+//! earlier declaration of a name that is declared twice. Rendering looks like this:
+//!
+//! ```text
+//! Test.Mod:3:12: error[E0101]: expected THEN after IF condition, found DO
+//!   |
+//! 3 |   IF x > 0 DO x := 1 END
+//!   |            ^^ expected THEN
+//! ```
+
+use std::fmt::Write as _;
+use crate::frontend::analysis::AnalysisError;
+use crate::frontend::const_eval::ConstError;
+use crate::frontend::lexer::LexerError;
+use crate::frontend::parser::{one_of, ParserError};
+use crate::frontend::span::Span;
+
+/// A span with a short explanation of its part in a diagnostic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// A stable identifier of the kind of error, such as `E0101`.
+    pub code: &'static str,
+    pub message: String,
+    /// The primary location, underlined with carets.
+    pub span: Span,
+    /// Printed under the carets of the primary location.
+    pub label: Option<String>,
+    /// Related locations, underlined with dashes.
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        Self { code, message: message.into(), span, label: None, secondary: vec![], notes: vec![] }
+    }
+
+    pub fn with_label(mut self, message: impl Into<String>) -> Self {
+        self.label = Some(message.into());
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label { span, message: message.into() });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Renders the diagnostic for the file `path` whose text is `source`.
+    pub fn render(&self, path: &str, source: &str) -> String {
+        let mut out = String::new();
+        let start = self.span.start;
+        writeln!(out, "{path}:{}:{}: error[{}]: {}", start.line, start.column, self.code, self.message).unwrap();
+
+        let marks = std::iter::once((self.span, '^', self.label.as_deref()))
+            .chain(self.secondary.iter().map(|label| (label.span, '-', Some(label.message.as_str()))));
+        let width = marks.clone().map(|(span, ..)| span.start.line.to_string().len()).max().unwrap_or(1);
+        let mut previous_line = None;
+        for (span, marker, label) in marks {
+            // Labels on the line just shown are underlined below it, not shown again.
+            let show_line = previous_line != Some(span.start.line);
+            snippet(&mut out, source, width, span, marker, label, show_line);
+            previous_line = Some(span.start.line);
+        }
+        for note in &self.notes {
+            writeln!(out, "{:width$} = note: {note}", "").unwrap();
+        }
+        out
+    }
+}
+
+/// Writes the line `span` starts on (unless `show_line` is false), underlining the part of it the
+/// span covers.
+fn snippet(out: &mut String, source: &str, width: usize, span: Span, marker: char, label: Option<&str>, show_line: bool) {
+    let offset = span.start.offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[offset..].find('\n').map_or(source.len(), |i| offset + i);
+    let line = source[line_start..line_end].trim_end_matches('\r');
+    let end = span.end.offset.clamp(offset, line_start + line.len());
+
+    // Tabs stay tabs in the margin, so the underline lines up however they are displayed.
+    let margin: String = source[line_start..offset].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+    let length = source[offset..end].chars().count().max(1);
+    let underline: String = std::iter::repeat_n(marker, length).collect();
+
+    if show_line {
+        writeln!(out, "{:width$} |", "").unwrap();
+        writeln!(out, "{:>width$} | {line}", span.start.line).unwrap();
+    }
+    match label {
+        Some(label) => writeln!(out, "{:width$} | {margin}{underline} {label}", "").unwrap(),
+        None => writeln!(out, "{:width$} | {margin}{underline}", "").unwrap(),
+    }
+}
+
+// --------------------------- ERROR CODES ---------------------------
+// E00xx: lexical errors, E01xx: syntax errors, E02xx: semantic errors, E03xx: constant
+// evaluation errors.
+
+impl From<&LexerError> for Diagnostic {
+    fn from(error: &LexerError) -> Self {
+        let (code, span, label) = match error {
+            LexerError::UnexpectedCharacter { span, .. } => ("E0001", span, "not valid in Oberon source"),
+            LexerError::UnterminatedString { span } => ("E0002", span, "string starts here"),
+            LexerError::UnterminatedComment { span } => ("E0003", span, "comment starts here"),
+            LexerError::InvalidNumber { span } => ("E0004", span, "invalid number"),
+            LexerError::UnexpectedEof { span } => ("E0005", span, "file ends here"),
+        };
+        Diagnostic::error(code, error.to_string(), *span).with_label(label)
+    }
+}
+
+impl From<&ParserError> for Diagnostic {
+    fn from(error: &ParserError) -> Self {
+        match error {
+            ParserError::UnexpectedToken { expected, found, .. } => {
+                Diagnostic::error("E0101", error.to_string(), found.span).with_label(format!("expected {}", one_of(expected)))
+            }
+            ParserError::InvalidLabelValue { token } => {
+                Diagnostic::error("E0102", error.to_string(), token.span)
+                    .with_note("case labels are integers, characters or the names of such constants")
+            }
+            ParserError::IntegerOutOfRange { token, width } => {
+                Diagnostic::error("E0103", error.to_string(), token.span)
+                    .with_label("not a valid INTEGER")
+                    .with_note(format!("INTEGER ranges from {} to {}", width.min(), width.max()))
+            }
+            ParserError::RealOutOfRange { token } => {
+                Diagnostic::error("E0104", error.to_string(), token.span)
+                    .with_label("not a valid REAL")
+                    .with_note(format!("REAL ranges up to {:E}", f64::MAX))
+            }
+            ParserError::InvalidCharacter { token } => {
+                Diagnostic::error("E0105", error.to_string(), token.span)
+                    .with_label("not a valid CHAR")
+                    .with_note("character codes range from 0X to 0FFX")
+            }
+            ParserError::Lexer(error) => error.into(),
+        }
+    }
+}
+
+impl From<&ConstError> for Diagnostic {
+    fn from(error: &ConstError) -> Self {
+        let (code, span) = match error {
+            ConstError::NotConstant { span } => ("E0301", span),
+            ConstError::Overflow { span } => ("E0302", span),
+            ConstError::DivisionByZero { span } => ("E0303", span),
+            ConstError::OutOfRange { span } => ("E0304", span),
+            ConstError::InvalidOperand { span, .. } => ("E0305", span),
+        };
+        Diagnostic::error(code, error.to_string(), *span)
+    }
+}
+
+impl From<&AnalysisError> for Diagnostic {
+    fn from(error: &AnalysisError) -> Self {
+        use AnalysisError as E;
+        let message = error.to_string();
+        let diagnostic = |code, span: &Span| Diagnostic::error(code, message.clone(), *span);
+        match error {
+            E::ModuleNameMismatch { expected, span, declared, .. } => diagnostic("E0201", span)
+                .with_label(format!("expected '{expected}'"))
+                .with_secondary(*declared, "module is named here"),
+            E::ProcedureNameMismatch { expected, span, declared, .. } => diagnostic("E0202", span)
+                .with_label(format!("expected '{expected}'"))
+                .with_secondary(*declared, "procedure is named here"),
+            E::Undeclared { span, .. } => diagnostic("E0203", span).with_label("not found in this scope"),
+            E::Redeclared { span, previous, .. } => diagnostic("E0204", span)
+                .with_label("declared again here")
+                .with_secondary(*previous, "first declared here"),
+            E::NotAType { span, .. } => diagnostic("E0205", span).with_label("expected a type"),
+            E::UnresolvedImport { span, .. } => diagnostic("E0206", span),
+            E::InvalidExport { span } => diagnostic("E0207", span).with_label("remove the export mark"),
+            E::TypeMismatch { expected, span, .. } => diagnostic("E0208", span).with_label(format!("expected {expected}")),
+            E::IncompatibleOperands { span, .. } => diagnostic("E0209", span),
+            E::InvalidOperand { span, .. } => diagnostic("E0210", span),
+            E::Constant(error) => error.into(),
+            E::NotAValue { span } => diagnostic("E0211", span),
+            E::NotAssignable { span } => diagnostic("E0212", span).with_label("cannot be assigned to"),
+            E::NotAProcedure { span } => diagnostic("E0213", span),
+            E::FunctionAsStatement { span } => diagnostic("E0214", span).with_label("the result is discarded"),
+            E::ProcedureAsValue { span } => diagnostic("E0215", span).with_label("has no value"),
+            E::ArgumentCount { span, .. } => diagnostic("E0216", span),
+            E::UnknownField { span, .. } => diagnostic("E0217", span).with_label("unknown field"),
+            E::InvalidSelector { span, .. } => diagnostic("E0218", span),
+            E::IndexOutOfRange { span, .. } => diagnostic("E0219", span),
+            E::NotAnExtension { span, .. } => diagnostic("E0220", span),
+            E::InvalidArrayLength { span } => diagnostic("E0221", span),
+            E::InvalidType { span, .. } => diagnostic("E0222", span),
+            E::MissingReturn { span, .. } => diagnostic("E0223", span),
+            E::UnexpectedReturn { span, .. } => diagnostic("E0224", span),
+            E::DuplicateCaseLabel { span } => diagnostic("E0225", span),
+            E::InvalidForStep { span } => diagnostic("E0226", span),
+            E::UnknownModule { span, .. } => diagnostic("E0227", span)
+                .with_label("no symbol file found")
+                .with_note("compile the module first, and pass the directory of its symbol file with -I"),
+            E::StaleImport { module, changed, span } => diagnostic("E0228", span)
+                .with_label(format!("'{changed}' has changed since '{module}' was compiled"))
+                .with_note(format!("recompile '{module}' against the current symbol files")),
+            E::LocalProcedureAsValue { span, .. } => diagnostic("E0229", span)
+                .with_label("declared inside another procedure")
+                .with_note("only procedures declared at module level can be assigned or passed as arguments"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::analysis;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+
+    fn parse_error(source: &str) -> String {
+        let error = Parser::new(Lexer::new(source)).parse().unwrap_err();
+        Diagnostic::from(&error).render("Test.Mod", source)
+    }
+
+    fn analysis_errors(source: &str) -> Vec<String> {
+        let module = Parser::new(Lexer::new(source)).parse().unwrap();
+        analysis::check(&module).unwrap_err().iter().map(|error| Diagnostic::from(error).render("Test.Mod", source)).collect()
+    }
+
+    #[test]
+    fn names_the_expected_token() {
+        let source = "MODULE Test;\nVAR x: INTEGER;\nBEGIN IF x > 0 DO x := 1 END\nEND Test.";
+        assert_eq!(parse_error(source), "\
+Test.Mod:3:16: error[E0101]: expected THEN after IF condition, found DO
+  |
+3 | BEGIN IF x > 0 DO x := 1 END
+  |                ^^ expected THEN
+");
+    }
+
+    #[test]
+    fn lists_every_token_that_could_continue() {
+        let source = "MODULE Test; BEGIN\n  WHILE TRUE DO Foo Bar END END Test.";
+        let rendered = parse_error(source);
+        assert!(rendered.starts_with("Test.Mod:2:21: error[E0101]: expected ';', ELSIF or END, found identifier 'Bar'\n"), "{rendered}");
+    }
+
+    #[test]
+    fn reports_end_of_file() {
+        let rendered = parse_error("MODULE Test; BEGIN x := (1 + 2");
+        assert!(rendered.starts_with("Test.Mod:1:31: error[E0101]: expected ')' to close '(', found end of file\n"), "{rendered}");
+        assert!(rendered.ends_with("1 | MODULE Test; BEGIN x := (1 + 2\n  |                               ^ expected ')'\n"), "{rendered}");
+    }
+
+    #[test]
+    fn renders_lexer_errors() {
+        let source = "MODULE Test;\n\tBEGIN x := @ END Test.";
+        assert_eq!(parse_error(source), "\
+Test.Mod:2:13: error[E0001]: unexpected character '@'
+  |
+2 | \tBEGIN x := @ END Test.
+  | \t           ^ not valid in Oberon source
+");
+    }
+
+    #[test]
+    fn points_at_previous_declaration() {
+        let source = "MODULE Test;\nVAR x: INTEGER;\n  y: CHAR;\n  x: REAL;\nEND Test.";
+        let errors = analysis_errors(source);
+        assert_eq!(errors, ["\
+Test.Mod:4:3: error[E0204]: 'x' is already declared
+  |
+4 |   x: REAL;
+  |   ^ declared again here
+  |
+2 | VAR x: INTEGER;
+  |     - first declared here
+"]);
+    }
+
+    #[test]
+    fn reports_every_analysis_error_with_a_code() {
+        let source = "MODULE Test;\nVAR b: BOOLEAN;\nBEGIN b := 1; y := 2\nEND Tset.";
+        let errors = analysis_errors(source);
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        assert!(errors[0].starts_with("Test.Mod:4:5: error[E0201]: module 'Test' is closed with 'END Tset'\n"), "{}", errors[0]);
+        assert!(errors[0].ends_with("1 | MODULE Test;\n  |        ---- module is named here\n"), "{}", errors[0]);
+        assert!(errors[1].starts_with("Test.Mod:3:12: error[E0208]: expected BOOLEAN, found INTEGER\n"), "{}", errors[1]);
+        assert!(errors[2].starts_with("Test.Mod:3:15: error[E0203]: undeclared identifier 'y'\n"), "{}", errors[2]);
+    }
+
+    #[test]
+    fn pads_line_numbers_to_the_widest() {
+        let mut source = "MODULE Test;\nVAR x: INTEGER;\n".to_string();
+        source.push_str(&"\n".repeat(8));
+        source.push_str("  x: CHAR;\nEND Test.");
+        let rendered = analysis_errors(&source).remove(0);
+        assert!(rendered.contains("\n   |\n11 |   x: CHAR;\n   |   ^ declared again here\n   |\n 2 | VAR x: INTEGER;\n"), "{rendered}");
+    }
+}
@@ -0,0 +1,534 @@
+//! Re-lexing and re-parsing a source text after an edit, for editors that parse on every
+//! keystroke. Only the tokens around the edit are lexed again, and the declarations (procedures
+//! included) whose tokens the edit left alone are taken over from the previous parse, moved to
+//! their new place. The outcome is the same as lexing and parsing the edited text afresh.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::Arc;
+use crate::frontend::ast::Module;
+use crate::frontend::lexer::{Lexed, Lexer, LexerError};
+use crate::frontend::literal::IntegerWidth;
+use crate::frontend::lower;
+use crate::frontend::parser::{Parser, ParserError};
+use crate::frontend::span::{Position, Span};
+use crate::frontend::syntax::{GreenNode, NodeKind, SyntaxNode};
+use crate::frontend::token::{Token, TokenKind};
+
+/// The furthest the parser looks past the current token.
+const PARSER_LOOKAHEAD: usize = 4;
+
+/// A change to a source text: the bytes in `range` replaced by `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> Self {
+        Self { range, text: text.into() }
+    }
+}
+
+/// A source text with its tokens, trivia included, its concrete syntax tree and the module derived
+/// from it, kept up to date through edits.
+pub struct Document {
+    text: String,
+    tokens: Vec<Lexed>,
+    green: Arc<GreenNode>,
+    module: Module,
+    errors: Vec<ParserError>,
+    /// The declarations that parsed without errors, by the number of their first token: how many
+    /// tokens they take, semicolon included.
+    units: BTreeMap<usize, usize>,
+    integer_width: IntegerWidth,
+    /// How many declarations the last edit took over from the parse before it.
+    reused: usize,
+}
+
+impl Document {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self::with_integer_width(text, IntegerWidth::default())
+    }
+
+    /// A document whose integer literals are checked against INTEGERs of `width` bits.
+    pub fn with_integer_width(text: impl Into<String>, width: IntegerWidth) -> Self {
+        let text = text.into();
+        let tokens = Lexer::with_trivia(&text).lex_all();
+        let mut reuse = Reuse::default();
+        let (syntax, errors) = Parser::from_tokens(&tokens, &text).with_integer_width(width).with_reuse(&mut reuse).parse_syntax();
+        let module = lower::module(&syntax, width, true);
+        Self { text, tokens, green: syntax.green().clone(), module, errors, units: reuse.units, integer_width: width, reused: 0 }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn tokens(&self) -> &[Lexed] {
+        &self.tokens
+    }
+
+    pub fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green.clone())
+    }
+
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// The syntax and lexical errors, in the order they were found.
+    pub fn errors(&self) -> &[ParserError] {
+        &self.errors
+    }
+
+    /// Applies `edit`, re-lexing the text around it and re-parsing all but the declarations it
+    /// left alone.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range of the edit is out of bounds or does not lie on character boundaries.
+    pub fn edit(&mut self, edit: &TextEdit) {
+        let prefix = self.tokens.partition_point(|lexed| reach(&self.text, lexed.end.offset) <= edit.range.start);
+        self.text.replace_range(edit.range.clone(), &edit.text);
+        let relexed = relex(&self.tokens, &self.text, prefix, edit);
+
+        let mut reuse = Reuse::default();
+        if let Some(declarations) = self.syntax().child(NodeKind::Declarations) {
+            reuse.collect(&declarations, &self.tokens, &self.units, &relexed);
+        }
+        self.tokens = relexed.splice(std::mem::take(&mut self.tokens));
+
+        let (syntax, errors) = Parser::from_tokens(&self.tokens, &self.text)
+            .with_integer_width(self.integer_width)
+            .with_reuse(&mut reuse)
+            .parse_syntax();
+        self.module = lower::module(&syntax, self.integer_width, true);
+        self.green = syntax.green().clone();
+        self.errors = errors;
+        self.units = reuse.units;
+        self.reused = reuse.reused;
+    }
+}
+
+// --------------------------- RE-LEXING ---------------------------
+/// How far into `text` the lexer looked to end a token at `offset`: the two characters after it.
+/// Where fewer are left it saw the end of the text, which an edit anywhere may move.
+fn reach(text: &str, offset: usize) -> usize {
+    let mut rest = text[offset..].chars();
+    match (rest.next(), rest.next()) {
+        (Some(first), Some(second)) => offset + first.len_utf8() + second.len_utf8(),
+        _ => usize::MAX,
+    }
+}
+
+/// The tokens of an edited text: the first `prefix` old ones, which the lexer found before
+/// reaching the edit, then `fresh` ones lexed again, then the old ones from `suffix` on, moved by
+/// `shift`.
+struct Relexed {
+    prefix: usize,
+    fresh: Vec<Lexed>,
+    suffix: usize,
+    shift: Option<Shift>,
+}
+
+/// Lexes the edited `text` from where the lexer stopped after the first `prefix` of the old
+/// `tokens`, until it stops at the place of an old stop behind the edit. From there on the text
+/// is the same as before, and so are the tokens.
+fn relex(tokens: &[Lexed], text: &str, prefix: usize, edit: &TextEdit) -> Relexed {
+    let resumes_at = |index: usize| if index == 0 { Position::initial() } else { tokens[index - 1].end };
+    let edit_end = edit.range.start + edit.text.len();
+
+    let mut lexer = Lexer::with_trivia(text).starting_at(resumes_at(prefix));
+    let mut fresh = vec![];
+    let mut suffix = prefix;
+    loop {
+        let lexed = lexer.next_lexed();
+        let end = lexed.end;
+        let eof = lexed.token.kind == TokenKind::Eof;
+        fresh.push(lexed);
+        if eof {
+            return Relexed { prefix, fresh, suffix: tokens.len(), shift: None };
+        }
+        if end.offset < edit_end {
+            continue;
+        }
+        let old_end = end.offset - edit_end + edit.range.end;
+        while suffix < tokens.len() && resumes_at(suffix).offset < old_end {
+            suffix += 1;
+        }
+        if suffix < tokens.len() && resumes_at(suffix).offset == old_end {
+            let shift = Shift { from: resumes_at(suffix), to: end };
+            return Relexed { prefix, fresh, suffix, shift: Some(shift) };
+        }
+    }
+}
+
+impl Relexed {
+    /// The new tokens, made of the old ones and the fresh.
+    fn splice(self, mut tokens: Vec<Lexed>) -> Vec<Lexed> {
+        let mut suffix = tokens.split_off(self.suffix);
+        if let Some(shift) = self.shift {
+            suffix.relocate(&shift);
+        }
+        tokens.truncate(self.prefix);
+        tokens.extend(self.fresh);
+        tokens.extend(suffix);
+        tokens
+    }
+
+    /// The new number of the old token `index` of `old`, if the old tokens from there on to
+    /// `tokens` later are unchanged, along with those the parser might look at past them.
+    fn moved(&self, index: usize, tokens: usize, old: &[Lexed]) -> Option<usize> {
+        let end = index + tokens;
+        let lookahead = || old[end..self.prefix].iter().filter(|lexed| !lexed.token.kind.is_trivia()).count();
+        if end <= self.prefix && lookahead() >= PARSER_LOOKAHEAD {
+            Some(index)
+        } else if index >= self.suffix {
+            Some(index - self.suffix + self.prefix + self.fresh.len())
+        } else {
+            None
+        }
+    }
+}
+
+/// Where text behind an edit moved: a position `from` before the edit is at `to` after it, and so
+/// is everything that follows it, on its line and on those below.
+#[derive(Debug, Clone, Copy)]
+struct Shift {
+    from: Position,
+    to: Position,
+}
+
+impl Shift {
+    fn position(&self, position: Position) -> Position {
+        let column = if position.line == self.from.line {
+            position.column - self.from.column + self.to.column
+        } else {
+            position.column
+        };
+        Position {
+            offset: position.offset - self.from.offset + self.to.offset,
+            line: position.line - self.from.line + self.to.line,
+            column,
+        }
+    }
+}
+
+// --------------------------- REUSE ---------------------------
+/// The declarations of the previous parse whose tokens an edit left alone, for the parser to take
+/// over, and the record of the declarations the next edit may take over in turn.
+///
+/// A declaration that parsed without errors comes out the same from the same tokens, whatever
+/// came before it: the parser's state only matters once there is an error. So its green node can
+/// be taken over when the parser gets to its first token and expects a declaration of its kind.
+#[derive(Default)]
+pub(crate) struct Reuse {
+    /// By the number of their first token in the new tokens.
+    subtrees: HashMap<usize, (Arc<GreenNode>, Place)>,
+    /// The declarations parsed or taken over without errors, by the number of their first token:
+    /// how many tokens they take, semicolon included.
+    units: BTreeMap<usize, usize>,
+    reused: usize,
+}
+
+/// Where a declaration of the previous parse goes in the new tokens.
+struct Place {
+    /// The number of its first token.
+    index: usize,
+    /// How many tokens it takes, semicolon included.
+    tokens: usize,
+    /// The declarations nested in it, as entries of `units`.
+    nested: Vec<(usize, usize)>,
+}
+
+impl Reuse {
+    /// Takes over the declaration of `kind` whose first token is `index`, if there is one; returns
+    /// its node with the number of its tokens.
+    pub(crate) fn take(&mut self, index: usize, kind: NodeKind) -> Option<(Arc<GreenNode>, usize)> {
+        let (node, Place { tokens, nested, .. }) = self.subtrees.remove(&index)?;
+        if node.kind() != kind {
+            return None;
+        }
+        self.units.insert(index, tokens);
+        self.units.extend(nested);
+        self.reused += 1;
+        Some((node, tokens))
+    }
+
+    /// Records that the declaration of `tokens` tokens from `index` parsed without errors.
+    pub(crate) fn remember(&mut self, index: usize, tokens: usize) {
+        self.units.insert(index, tokens);
+    }
+
+    /// Collects the declarations in `declarations`, a node of the previous tree over `tokens`,
+    /// that the edit left alone. A procedure the edit touched is searched for declarations inside it.
+    fn collect(&mut self, declarations: &SyntaxNode, tokens: &[Lexed], units: &BTreeMap<usize, usize>, relexed: &Relexed) {
+        for child in declarations.children() {
+            let nodes: Vec<SyntaxNode> = match child.kind() {
+                NodeKind::ConstSection | NodeKind::TypeSection | NodeKind::VarSection => child.children().collect(),
+                _ => vec![child],
+            };
+            for node in nodes {
+                match Self::place(&node, tokens, units, relexed) {
+                    Some(place) => {
+                        self.subtrees.insert(place.index, (node.green().clone(), place));
+                    }
+                    None => {
+                        let body = node.child(NodeKind::ProcedureBody);
+                        if let Some(declarations) = body.and_then(|body| body.child(NodeKind::Declarations)) {
+                            self.collect(&declarations, tokens, units, relexed);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Where the declaration at `node` goes in the new tokens, if it parsed without errors and its
+    /// tokens are unchanged.
+    fn place(node: &SyntaxNode, tokens: &[Lexed], units: &BTreeMap<usize, usize>, relexed: &Relexed) -> Option<Place> {
+        let first = tokens.partition_point(|lexed| lexed.end.offset <= node.text_range().start);
+        let count = *units.get(&first)?;
+        let index = relexed.moved(first, count, tokens)?;
+        let nested = units.range(first + 1..first + count)
+            .map(|(&nested, &count)| (nested - first + index, count))
+            .collect();
+        Some(Place { index, tokens: count, nested })
+    }
+}
+
+// --------------------------- RELOCATION ---------------------------
+/// Moving the positions in tokens behind an edit to where the edit put them.
+trait Relocate {
+    fn relocate(&mut self, shift: &Shift);
+}
+
+impl<T: Relocate> Relocate for Vec<T> {
+    fn relocate(&mut self, shift: &Shift) {
+        self.iter_mut().for_each(|item| item.relocate(shift));
+    }
+}
+
+impl<T: Relocate> Relocate for Option<T> {
+    fn relocate(&mut self, shift: &Shift) {
+        if let Some(item) = self {
+            item.relocate(shift);
+        }
+    }
+}
+
+impl Relocate for Span {
+    fn relocate(&mut self, shift: &Shift) {
+        self.start = shift.position(self.start);
+        self.end = shift.position(self.end);
+    }
+}
+
+impl Relocate for Lexed {
+    fn relocate(&mut self, shift: &Shift) {
+        let Lexed { token, error, end } = self;
+        token.relocate(shift);
+        error.relocate(shift);
+        *end = shift.position(*end);
+    }
+}
+
+impl Relocate for Token {
+    fn relocate(&mut self, shift: &Shift) {
+        self.span.relocate(shift);
+    }
+}
+
+impl Relocate for LexerError {
+    fn relocate(&mut self, shift: &Shift) {
+        match self {
+            LexerError::UnexpectedCharacter { span, .. }
+            | LexerError::UnterminatedString { span }
+            | LexerError::UnterminatedComment { span }
+            | LexerError::InvalidNumber { span }
+            | LexerError::UnexpectedEof { span } => span.relocate(shift),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::span::Spanned;
+
+    const SOURCE: &str = r#"MODULE Shapes;
+IMPORT Out;
+
+CONST
+  Max* = 10;
+  Mask = 0FFH;
+  Name = "shapes";
+  Tab = 9X;
+
+TYPE
+  Point* = RECORD x*, y*: INTEGER END;
+  Shape = POINTER TO ShapeDesc;
+  ShapeDesc = RECORD (Point) area: REAL; next: Shape END;
+  Grid = ARRAY Max, Max OF BOOLEAN;
+  Visit = PROCEDURE (s: Shape): BOOLEAN;
+
+VAR
+  first, last: Shape;
+  grid: Grid;
+  count: INTEGER;
+
+PROCEDURE Area(s: Shape): REAL;
+  VAR a: REAL;
+BEGIN
+  IF s = NIL THEN a := 0.0
+  ELSIF s IS Shape THEN a := FLT(s.x * s.y)
+  ELSE a := 1.5E2
+  END
+  RETURN a
+END Area;
+
+PROCEDURE Count(VAR n: INTEGER; visit: Visit);
+  CONST Limit = Max * 2;
+  VAR s: Shape;
+
+  PROCEDURE Skip(s: Shape): BOOLEAN;
+    RETURN (s.x < 0) OR ~(s.y IN {1, 3..5})
+  END Skip;
+
+BEGIN
+  n := 0; s := first;
+  WHILE (s # NIL) & (n < Limit) DO
+    IF ~Skip(s) & visit(s) THEN INC(n) END;
+    s := s.next
+  END
+END Count;
+
+PROCEDURE Fill*(c: CHAR);
+  VAR i, j: INTEGER;
+BEGIN
+  FOR i := 0 TO Max - 1 DO
+    FOR j := Max - 1 TO 0 BY -1 DO grid[i, j] := ODD(i + j) END
+  END;
+  CASE c OF
+    "a" .. "z": count := 1
+  | 0X, Tab: count := 2
+  END;
+  REPEAT DEC(count) UNTIL count <= 0
+END Fill;
+
+BEGIN
+  first := NIL; last := first;
+  Out.String(Name); Out.Ln
+END Shapes.
+"#;
+
+    /// Snippets that make and break tokens, comments, strings and declarations.
+    const SNIPPETS: &[&str] = &[
+        "", "", " ", "\n", "x", "Q", "1", "0", "H", "X", ".", "..", ":", "=", ";", ",", "(", ")",
+        "(*", "*)", "\"", "*", "E", "END", "BEGIN", "PROCEDURE", "CONST", "VAR", "TYPE", "RECORD",
+        "x := 1;", "; y", "P(a, b)", "(* c *)", "VAR v: INTEGER;", "CONST k = 3;", "END P;",
+        "PROCEDURE P; BEGIN END P;", "9223372036854775808", "1.0E400", "$", "ä",
+    ];
+
+    /// A linear congruential generator, enough to pick edits.
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((self.0 >> 33) % bound as u64) as usize
+        }
+
+        /// A character boundary of `text`.
+        fn boundary(&mut self, text: &str) -> usize {
+            let mut offset = self.below(text.len() + 1);
+            while !text.is_char_boundary(offset) {
+                offset -= 1;
+            }
+            offset
+        }
+
+        fn edit(&mut self, text: &str) -> TextEdit {
+            let start = self.boundary(text);
+            let mut end = (start + self.below(12)).min(text.len());
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            TextEdit::new(start..end, SNIPPETS[self.below(SNIPPETS.len())])
+        }
+    }
+
+    fn assert_same_as_full_parse(document: &Document, edits: &[TextEdit]) {
+        let text = document.text();
+        let (syntax, errors) = Parser::new(Lexer::with_trivia(text)).parse_syntax();
+        assert_eq!(document.tokens(), Lexer::with_trivia(text).lex_all(), "tokens after {edits:?}");
+        assert_eq!(document.syntax().green(), syntax.green(), "syntax tree after {edits:?}");
+        assert_eq!(document.syntax().to_string(), text, "text after {edits:?}");
+        assert_eq!(document.module(), &lower::module(&syntax, IntegerWidth::default(), true), "tree after {edits:?}");
+        assert_eq!(document.errors(), errors, "errors after {edits:?}");
+    }
+
+    #[test]
+    fn equals_a_full_parse_after_random_edits() {
+        let mut random = Random(0x5EED);
+        for _ in 0..400 {
+            let mut document = Document::new(SOURCE);
+            let mut edits = vec![];
+            for _ in 0..1 + random.below(4) {
+                let edit = random.edit(document.text());
+                edits.push(edit.clone());
+                document.edit(&edit);
+                assert_same_as_full_parse(&document, &edits);
+            }
+        }
+    }
+
+    #[test]
+    fn relexes_only_around_the_edit() {
+        let document = Document::new(SOURCE);
+        let offset = SOURCE.find("n := 0").unwrap();
+        let edit = TextEdit::new(offset..offset + 1, "count");
+        let prefix = document.tokens.partition_point(|lexed| reach(SOURCE, lexed.end.offset) <= offset);
+        let mut text = SOURCE.to_string();
+        text.replace_range(edit.range.clone(), &edit.text);
+
+        let relexed = relex(&document.tokens, &text, prefix, &edit);
+
+        // The whitespace before `n` as well, since the lexer looked at `n` to end it.
+        assert_eq!(relexed.fresh.len(), 2);
+        assert_eq!(relexed.fresh[0].token.kind, TokenKind::Whitespace);
+        assert_eq!(relexed.fresh[1].token.text_in(&text), "count");
+        assert_eq!(relexed.suffix, relexed.prefix + 2);
+    }
+
+    #[test]
+    fn takes_over_declarations_the_edit_left_alone() {
+        let mut document = Document::new(SOURCE);
+        let offset = SOURCE.find("INC(n)").unwrap();
+
+        document.edit(&TextEdit::new(offset..offset, "\n      "));
+
+        // Everything but Count: 4 constants, 5 types, 3 variables and 2 procedures, and inside
+        // Count its constant, variable and nested procedure.
+        assert!(document.errors().is_empty());
+        assert_eq!(document.reused, 17);
+        assert_same_as_full_parse(&document, &[]);
+        let fill = SOURCE.find("PROCEDURE Fill").unwrap();
+        let start = document.module().declarations.procedure_declarations[2].span.start;
+        assert_eq!(start.offset, fill + 7);
+        assert_eq!(start.line, SOURCE[..fill].matches('\n').count() + 2);
+    }
+
+    #[test]
+    fn moves_columns_on_the_line_of_the_edit() {
+        let mut document = Document::new("MODULE M; VAR a: INTEGER; b: INTEGER; END M.");
+
+        document.edit(&TextEdit::new(14..15, "\n  aa"));
+
+        let b = &document.module().declarations.var_declarations[1];
+        assert_eq!(b.span().start, Position { offset: 30, line: 2, column: 16 });
+        assert_same_as_full_parse(&document, &[]);
+    }
+}
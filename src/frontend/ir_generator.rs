@@ -0,0 +1,2037 @@
+//! Lowers a checked module to IR.
+//!
+//! Every Oberon variable lives in memory: globals in IR globals, locals and parameters in stack
+//! slots. A procedure that declares nested procedures keeps its variables in a single frame slot
+//! instead, with the frame of the enclosing procedure (the static link) stored at offset 0, so
+//! nested procedures can reach the variables of every enclosing level.
+//!
+//! Parameters are passed as follows: scalars by value; VAR parameters and structured value
+//! parameters as the address of the argument; open arrays as an address followed by one length
+//! per dimension; VAR records as an address followed by their type descriptor. Nested procedures
+//! take the frame of their parent as a hidden first parameter.
+//!
+//! Imported variables, procedures and record descriptors are referred to by the names their own
+//! module gives them (`A_x`), so separately compiled modules link together. The module body
+//! becomes `M__init`, which runs only once and first calls the bodies of the imported modules.
+
+use std::collections::{HashMap, HashSet};
+use crate::frontend::analysis::Analysis;
+use crate::frontend::ast::{BinaryOperation, Case, Declarations, Designator, Element, Expression, Label, LabelValue, Module, ProcedureDeclaration, Selector, Statement, StatementSequence, UnaryOperation};
+use crate::frontend::const_eval::Value;
+use crate::frontend::span::Spanned;
+use crate::frontend::symbols::{Builtin, ScopeId, SymbolId, SymbolKind};
+use crate::frontend::types::{Param, Type, TypeId, REAL};
+use crate::ir::{self, BinOp, BlockId, Callee, Cond, Conv, Function, Global, Inst, MemTy, Operand, SlotId, Target, Terminator, Ty, TypeDesc, UnOp, VReg};
+
+/// Lowers `module` to IR. The module must have passed `analysis::check`, which produced `analysis`.
+pub fn generate(module: &Module, analysis: &Analysis) -> ir::Module {
+    let name = &module.name.text;
+    let mut generator = Generator::new(analysis, name);
+    let scope = analysis.symbols.module_scope().expect("analysis opens the module scope");
+
+    generator.declare_types(scope);
+    generator.declare_imports(scope);
+    generator.declare_globals(scope);
+    generator.name_procedures(&module.declarations, scope, name);
+    for procedure in &module.declarations.procedure_declarations {
+        generator.lower_procedure(procedure, scope);
+    }
+
+    // The body runs once, after the bodies of the imported modules.
+    generator.f = FunctionState::new(&format!("{name}__init"), true, None, 1);
+    let done = format!("{name}__done");
+    generator.module.globals.push(Global { name: done.clone(), exported: false, size: 1, align: 1, init: None });
+    let flag = generator.global_addr(&done);
+    let value = generator.load(MemTy::I8, flag);
+    let first = generator.cmp(Cond::Eq, Ty::I64, value, Operand::Int(0));
+    let (run, skip) = (generator.new_block(), generator.new_block());
+    generator.branch(first, Target::new(run), Target::new(skip));
+    generator.switch_to(skip);
+    generator.terminate(Terminator::Return(None));
+    generator.switch_to(run);
+    generator.store(MemTy::I8, Operand::Int(1), flag);
+    for import in &module.imports {
+        let callee = Callee::Direct(format!("{}__init", import.module.text));
+        generator.emit(Inst::Call { dst: None, callee, args: vec![] });
+    }
+    if let Some(stmts) = &module.stmts {
+        generator.statements(stmts);
+    }
+    generator.terminate(Terminator::Return(None));
+    let init = std::mem::replace(&mut generator.f, FunctionState::new("", false, None, 0));
+    generator.finish(init);
+
+    generator.module
+}
+
+// --------------------------- STORAGE ---------------------------
+#[derive(Debug, Clone)]
+enum Place {
+    Global(String),
+    Slot(SlotId),
+    /// An offset into the frame slot of the procedure at the variable's level.
+    Frame(u64),
+}
+
+#[derive(Debug, Clone)]
+struct Variable {
+    /// The scope level of the declaring procedure (1 for module variables).
+    level: usize,
+    place: Place,
+    /// The place holds the address of the variable rather than the variable itself.
+    indirect: bool,
+    /// Places holding the lengths of an open array parameter, outermost dimension first.
+    lengths: Vec<Place>,
+    /// The place holding the type descriptor of a VAR record parameter.
+    tag: Option<Place>,
+}
+
+/// The address of a designated variable, with what is known about it at run time.
+#[derive(Debug, Clone)]
+struct Location {
+    addr: Operand,
+    ty: TypeId,
+    /// The lengths of the remaining dimensions of an open array.
+    lengths: Vec<Operand>,
+    /// The type descriptor of a record whose dynamic type may differ from `ty`.
+    tag: Option<Operand>,
+}
+
+struct FunctionState {
+    function: Function,
+    block: BlockId,
+    level: usize,
+    /// The frame slot and the number of bytes used so far.
+    frame: Option<(SlotId, u64)>,
+    /// The frame of the enclosing procedure.
+    link: Option<VReg>,
+}
+
+impl FunctionState {
+    fn new(name: &str, exported: bool, ret: Option<Ty>, level: usize) -> Self {
+        let mut function = Function::new(name, exported, ret);
+        let block = function.add_block();
+        Self { function, block, level, frame: None, link: None }
+    }
+}
+
+struct Generator<'a> {
+    analysis: &'a Analysis,
+    name: String,
+    module: ir::Module,
+    /// Size and alignment of every record type.
+    layouts: HashMap<TypeId, (u64, u64)>,
+    field_offsets: HashMap<SymbolId, u64>,
+    descriptors: HashMap<TypeId, String>,
+    variables: HashMap<SymbolId, Variable>,
+    procedures: HashMap<SymbolId, String>,
+    strings: HashMap<Vec<u8>, String>,
+    f: FunctionState,
+}
+
+fn align_up(value: u64, align: u64) -> u64 {
+    value.div_ceil(align) * align
+}
+
+impl<'a> Generator<'a> {
+    fn new(analysis: &'a Analysis, name: &str) -> Self {
+        Self {
+            analysis,
+            name: name.to_string(),
+            module: ir::Module::new(name),
+            layouts: HashMap::new(),
+            field_offsets: HashMap::new(),
+            descriptors: HashMap::new(),
+            variables: HashMap::new(),
+            procedures: HashMap::new(),
+            strings: HashMap::new(),
+            f: FunctionState::new("", false, None, 0),
+        }
+    }
+
+    fn ty(&self, id: TypeId) -> &'a Type {
+        self.analysis.types.get(id)
+    }
+
+    // --------------------------- LAYOUT ---------------------------
+    fn layout(&mut self, ty: TypeId) -> (u64, u64) {
+        match self.ty(ty) {
+            Type::Boolean | Type::Char | Type::Byte => (1, 1),
+            Type::Integer | Type::Real | Type::Set | Type::Nil | Type::Pointer { .. } | Type::Procedure { .. } => (8, 8),
+            Type::String { length } => (*length as u64 + 1, 1),
+            Type::Array { length, element } => {
+                let (size, align) = self.layout(*element);
+                (size * *length as u64, align)
+            }
+            Type::Record { base, fields } => {
+                if let Some(layout) = self.layouts.get(&ty) {
+                    return *layout;
+                }
+                let (mut size, mut align) = base.map_or((0, 1), |base| self.layout(base));
+                for field in self.analysis.symbols.scope(*fields).symbols() {
+                    let (field_size, field_align) = self.layout(self.analysis.symbols.symbol(*field).ty);
+                    let offset = align_up(size, field_align);
+                    self.field_offsets.insert(*field, offset);
+                    size = offset + field_size;
+                    align = align.max(field_align);
+                }
+                let layout = (align_up(size, align), align);
+                self.layouts.insert(ty, layout);
+                layout
+            }
+            Type::OpenArray { .. } | Type::NoType | Type::Error => unreachable!("type without a layout"),
+        }
+    }
+
+    fn size(&mut self, ty: TypeId) -> u64 {
+        self.layout(ty).0
+    }
+
+    fn mem_ty(&self, ty: TypeId) -> Option<MemTy> {
+        match self.ty(ty) {
+            Type::Boolean | Type::Char | Type::Byte | Type::String { length: 1 } => Some(MemTy::I8),
+            Type::Integer | Type::Set => Some(MemTy::I64),
+            Type::Real => Some(MemTy::F64),
+            Type::Pointer { .. } | Type::Procedure { .. } | Type::Nil => Some(MemTy::Ptr),
+            _ => None,
+        }
+    }
+
+    /// The register type of a value of type `ty`; structured values are represented by their address.
+    fn value_ty(&self, ty: TypeId) -> Ty {
+        self.mem_ty(ty).map_or(Ty::Ptr, MemTy::value_ty)
+    }
+
+    /// Creates a type descriptor for every record type and computes all record layouts.
+    fn declare_types(&mut self, scope: ScopeId) {
+        let types = &self.analysis.types;
+        let symbols = &self.analysis.symbols;
+        let exported: HashSet<TypeId> = symbols.scope(scope).symbols().iter()
+            .map(|id| symbols.symbol(*id))
+            .filter(|s| s.kind == SymbolKind::Type && s.exported)
+            .map(|s| s.ty)
+            .collect();
+
+        // Imported records have their descriptors in the module that declares them.
+        self.descriptors = types.record_names(&self.name);
+        let records: Vec<TypeId> = types.ids()
+            .filter(|id| matches!(types.get(*id), Type::Record { .. }) && types.link(*id).is_none())
+            .collect();
+        for record in records {
+            let size = self.size(record);
+            let Type::Record { base, .. } = types.get(record) else { unreachable!() };
+            self.module.descriptors.push(TypeDesc {
+                name: self.descriptors[&record].clone(),
+                exported: exported.contains(&record),
+                size,
+                base: base.map(|base| self.descriptors[&base].clone()),
+            });
+        }
+    }
+
+    /// Names the variables and procedures of the imported modules as their own modules do.
+    fn declare_imports(&mut self, scope: ScopeId) {
+        let symbols = &self.analysis.symbols;
+        for id in symbols.scope(scope).symbols() {
+            let (SymbolKind::Module { name: module }, Some(exports)) = (&symbols.symbol(*id).kind, symbols.symbol(*id).body) else { continue; };
+            for export in symbols.scope(exports).symbols() {
+                let symbol = symbols.symbol(*export);
+                let name = format!("{module}_{}", symbol.name);
+                match symbol.kind {
+                    SymbolKind::Var => {
+                        let variable = Variable { level: 1, place: Place::Global(name), indirect: false, lengths: vec![], tag: None };
+                        self.variables.insert(*export, variable);
+                    }
+                    SymbolKind::Procedure => {
+                        self.procedures.insert(*export, name);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn declare_globals(&mut self, scope: ScopeId) {
+        let symbols = &self.analysis.symbols;
+        for id in symbols.scope(scope).symbols() {
+            let symbol = symbols.symbol(*id);
+            if symbol.kind != SymbolKind::Var {
+                continue;
+            }
+            let name = format!("{}_{}", self.name, symbol.name);
+            let (size, align) = self.layout(symbol.ty);
+            self.module.globals.push(Global { name: name.clone(), exported: symbol.exported, size, align, init: None });
+            let variable = Variable { level: 1, place: Place::Global(name), indirect: false, lengths: vec![], tag: None };
+            self.variables.insert(*id, variable);
+        }
+    }
+
+    /// Assigns IR names to all procedures up front, so calls may precede declarations.
+    fn name_procedures(&mut self, declarations: &Declarations, scope: ScopeId, prefix: &str) {
+        let symbols = &self.analysis.symbols;
+        for procedure in &declarations.procedure_declarations {
+            let id = symbols.lookup_local(scope, &procedure.header.name.ident.text).expect("declared procedure");
+            let name = format!("{prefix}_{}", procedure.header.name.ident.text);
+            let body = symbols.symbol(id).body.expect("checked procedure");
+            self.name_procedures(&procedure.body.declarations, body, &name);
+            self.procedures.insert(id, name);
+        }
+    }
+
+    fn string_global(&mut self, bytes: &[u8]) -> String {
+        if let Some(name) = self.strings.get(bytes) {
+            return name.clone();
+        }
+        let name = format!("{}__str{}", self.name, self.strings.len());
+        let mut init = bytes.to_vec();
+        init.push(0);
+        self.module.globals.push(Global { name: name.clone(), exported: false, size: init.len() as u64, align: 1, init: Some(init) });
+        self.strings.insert(bytes.to_vec(), name.clone());
+        name
+    }
+
+    // --------------------------- PROCEDURES ---------------------------
+    fn lower_procedure(&mut self, procedure: &ProcedureDeclaration, scope: ScopeId) {
+        let analysis = self.analysis;
+        let id = analysis.symbols.lookup_local(scope, &procedure.header.name.ident.text).expect("declared procedure");
+        let symbol = analysis.symbols.symbol(id);
+        let body = symbol.body.expect("checked procedure");
+        let level = analysis.symbols.scope(body).level;
+        let Type::Procedure { params, result } = self.ty(symbol.ty) else { unreachable!("procedure without procedure type") };
+        let ret = result.map(|result| self.value_ty(result));
+
+        let state = FunctionState::new(&self.procedures[&id], symbol.exported, ret, level);
+        let outer = std::mem::replace(&mut self.f, state);
+        if !procedure.body.declarations.procedure_declarations.is_empty() {
+            let slot = self.f.function.add_slot(0, 8);
+            self.f.frame = Some((slot, 8));
+        }
+        if level > 2 {
+            let link = self.f.function.add_param(Ty::Ptr, false);
+            self.f.link = Some(link);
+            if self.f.frame.is_some() {
+                let frame = self.frame_base(level);
+                self.store(MemTy::Ptr, Operand::Reg(link), frame);
+            }
+        }
+
+        let locals = analysis.symbols.scope(body).symbols();
+        let param_ids = locals.iter().filter(|id| matches!(analysis.symbols.symbol(**id).kind, SymbolKind::Param { .. }));
+        for (param_id, param) in param_ids.zip(params) {
+            self.declare_param(*param_id, param);
+        }
+        for local in locals {
+            let local_symbol = analysis.symbols.symbol(*local);
+            if local_symbol.kind == SymbolKind::Var {
+                let (size, align) = self.layout(local_symbol.ty);
+                let place = self.cell(size, align);
+                self.variables.insert(*local, Variable { level, place, indirect: false, lengths: vec![], tag: None });
+            }
+        }
+
+        for nested in &procedure.body.declarations.procedure_declarations {
+            self.lower_procedure(nested, body);
+        }
+
+        if let Some(stmts) = &procedure.body.stmts {
+            self.statements(stmts);
+        }
+        let value = procedure.body.ret.as_ref().map(|ret| self.expression(ret));
+        self.terminate(Terminator::Return(value));
+
+        let state = std::mem::replace(&mut self.f, outer);
+        self.finish(state);
+    }
+
+    fn finish(&mut self, mut state: FunctionState) {
+        if let Some((slot, size)) = state.frame {
+            state.function.slots[slot.0 as usize].size = align_up(size, 8);
+        }
+        self.module.functions.push(state.function);
+    }
+
+    /// Reserves storage for a local value in the frame or in a fresh slot.
+    fn cell(&mut self, size: u64, align: u64) -> Place {
+        match &mut self.f.frame {
+            Some((_, used)) => {
+                let offset = align_up(*used, align);
+                *used = offset + size;
+                Place::Frame(offset)
+            }
+            None => Place::Slot(self.f.function.add_slot(size, align)),
+        }
+    }
+
+    /// Adds an IR parameter and stores it in a new cell.
+    fn param_cell(&mut self, ty: Ty, by_ref: bool) -> Place {
+        let reg = self.f.function.add_param(ty, by_ref);
+        let place = self.cell(8, 8);
+        let mem = match ty {
+            Ty::I64 => MemTy::I64,
+            Ty::F64 => MemTy::F64,
+            Ty::Ptr => MemTy::Ptr,
+        };
+        let addr = self.place_addr(self.f.level, &place);
+        self.store(mem, Operand::Reg(reg), addr);
+        place
+    }
+
+    fn declare_param(&mut self, id: SymbolId, param: &Param) {
+        let level = self.f.level;
+        let variable = match self.ty(param.ty) {
+            Type::OpenArray { .. } => {
+                let place = self.param_cell(Ty::Ptr, true);
+                let mut lengths = vec![];
+                let mut ty = param.ty;
+                while let Type::OpenArray { element } = self.ty(ty) {
+                    lengths.push(self.param_cell(Ty::I64, false));
+                    ty = *element;
+                }
+                Variable { level, place, indirect: true, lengths, tag: None }
+            }
+            Type::Array { .. } | Type::Record { .. } => {
+                let place = self.param_cell(Ty::Ptr, true);
+                let record = matches!(self.ty(param.ty), Type::Record { .. });
+                let tag = (param.by_ref && record).then(|| self.param_cell(Ty::Ptr, false));
+                Variable { level, place, indirect: true, lengths: vec![], tag }
+            }
+            _ if param.by_ref => {
+                let place = self.param_cell(Ty::Ptr, true);
+                Variable { level, place, indirect: true, lengths: vec![], tag: None }
+            }
+            _ => {
+                let (size, align) = self.layout(param.ty);
+                let mem = self.mem_ty(param.ty).expect("scalar parameter");
+                let reg = self.f.function.add_param(mem.value_ty(), false);
+                let place = self.cell(size, align);
+                let addr = self.place_addr(level, &place);
+                self.store(mem, Operand::Reg(reg), addr);
+                Variable { level, place, indirect: false, lengths: vec![], tag: None }
+            }
+        };
+        self.variables.insert(id, variable);
+    }
+
+    // --------------------------- EMITTING ---------------------------
+    fn reg(&mut self, ty: Ty) -> VReg {
+        self.f.function.new_reg(ty)
+    }
+
+    fn emit(&mut self, inst: Inst) {
+        let block = self.f.block;
+        self.f.function.block_mut(block).insts.push(inst);
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.f.function.add_block()
+    }
+
+    /// A new block with one parameter of type `ty`.
+    fn join_block(&mut self, ty: Ty) -> (BlockId, VReg) {
+        let block = self.new_block();
+        let param = self.reg(ty);
+        self.f.function.block_mut(block).params.push((param, ty));
+        (block, param)
+    }
+
+    fn switch_to(&mut self, block: BlockId) {
+        self.f.block = block;
+    }
+
+    fn terminate(&mut self, term: Terminator) {
+        let block = self.f.block;
+        self.f.function.block_mut(block).term = term;
+    }
+
+    fn jump(&mut self, block: BlockId, args: Vec<Operand>) {
+        self.terminate(Terminator::Jump(Target { block, args }));
+    }
+
+    fn branch(&mut self, cond: Operand, then_target: Target, else_target: Target) {
+        self.terminate(Terminator::Branch { cond, then_target, else_target });
+    }
+
+    /// Continues only if `cond` holds and traps with `code` otherwise.
+    fn guard(&mut self, cond: Operand, code: i64) {
+        let fail = self.new_block();
+        let next = self.new_block();
+        self.branch(cond, Target::new(next), Target::new(fail));
+        self.switch_to(fail);
+        self.terminate(Terminator::Trap(code));
+        self.switch_to(next);
+    }
+
+    fn binary(&mut self, op: BinOp, ty: Ty, lhs: Operand, rhs: Operand) -> Operand {
+        let dst = self.reg(ty);
+        self.emit(Inst::Binary { dst, op, ty, lhs, rhs });
+        Operand::Reg(dst)
+    }
+
+    fn unary(&mut self, op: UnOp, ty: Ty, operand: Operand) -> Operand {
+        let dst = self.reg(ty);
+        self.emit(Inst::Unary { dst, op, ty, operand });
+        Operand::Reg(dst)
+    }
+
+    fn cmp(&mut self, cond: Cond, ty: Ty, lhs: Operand, rhs: Operand) -> Operand {
+        let dst = self.reg(Ty::I64);
+        self.emit(Inst::Cmp { dst, cond, ty, lhs, rhs });
+        Operand::Reg(dst)
+    }
+
+    fn convert(&mut self, conv: Conv, src: Operand) -> Operand {
+        let dst = self.reg(if conv == Conv::IntToReal { Ty::F64 } else { Ty::I64 });
+        self.emit(Inst::Convert { dst, conv, src });
+        Operand::Reg(dst)
+    }
+
+    fn load(&mut self, mem: MemTy, addr: Operand) -> Operand {
+        let dst = self.reg(mem.value_ty());
+        self.emit(Inst::Load { dst, mem, addr });
+        Operand::Reg(dst)
+    }
+
+    fn store(&mut self, mem: MemTy, value: Operand, addr: Operand) {
+        self.emit(Inst::Store { mem, value, addr });
+    }
+
+    fn ptr_add(&mut self, base: Operand, offset: Operand) -> Operand {
+        if offset == Operand::Int(0) {
+            return base;
+        }
+        let dst = self.reg(Ty::Ptr);
+        self.emit(Inst::PtrAdd { dst, base, offset });
+        Operand::Reg(dst)
+    }
+
+    fn global_addr(&mut self, name: &str) -> Operand {
+        let dst = self.reg(Ty::Ptr);
+        self.emit(Inst::GlobalAddr { dst, name: name.to_string() });
+        Operand::Reg(dst)
+    }
+
+    fn null(&mut self) -> Operand {
+        let dst = self.reg(Ty::Ptr);
+        self.emit(Inst::Copy { dst, ty: Ty::Ptr, src: Operand::Int(0) });
+        Operand::Reg(dst)
+    }
+
+    /// The address of the frame of the procedure at `level`, following static links as needed.
+    fn frame_base(&mut self, level: usize) -> Operand {
+        if level == self.f.level {
+            let (slot, _) = self.f.frame.expect("procedure with nested procedures has a frame");
+            let dst = self.reg(Ty::Ptr);
+            self.emit(Inst::SlotAddr { dst, slot });
+            return Operand::Reg(dst);
+        }
+        let mut frame = Operand::Reg(self.f.link.expect("nested procedure has a static link"));
+        for _ in level..self.f.level - 1 {
+            frame = self.load(MemTy::Ptr, frame);
+        }
+        frame
+    }
+
+    fn place_addr(&mut self, level: usize, place: &Place) -> Operand {
+        match place {
+            Place::Global(name) => self.global_addr(name),
+            Place::Slot(slot) => {
+                let dst = self.reg(Ty::Ptr);
+                self.emit(Inst::SlotAddr { dst, slot: *slot });
+                Operand::Reg(dst)
+            }
+            Place::Frame(offset) => {
+                let frame = self.frame_base(level);
+                self.ptr_add(frame, Operand::Int(*offset as i64))
+            }
+        }
+    }
+
+    /// The type descriptor of the record type `ty` (or of the record a pointer type points to).
+    fn descriptor(&mut self, ty: TypeId) -> Operand {
+        let record = self.analysis.types.record_of(ty).expect("record type");
+        let name = self.descriptors[&record].clone();
+        self.global_addr(&name)
+    }
+
+    /// Traps unless `pointer` points to a heap object.
+    fn check_nil(&mut self, pointer: Operand) {
+        let valid = self.cmp(Cond::Ne, Ty::Ptr, pointer, Operand::Int(0));
+        self.guard(valid, ir::trap::NIL);
+    }
+
+    /// The type descriptor stored in the header of the heap object `pointer` points to.
+    fn heap_tag(&mut self, pointer: Operand) -> Operand {
+        self.check_nil(pointer);
+        let header = self.ptr_add(pointer, Operand::Int(-8));
+        self.load(MemTy::Ptr, header)
+    }
+
+    // --------------------------- DESIGNATORS ---------------------------
+    fn info(&self, expr: &Expression) -> &'a crate::frontend::analysis::ExpressionInfo {
+        &self.analysis.expressions[&expr.span()]
+    }
+
+    fn expr_ty(&self, expr: &Expression) -> TypeId {
+        self.info(expr).ty
+    }
+
+    fn head_symbol(&self, designator: &Designator) -> SymbolId {
+        self.analysis.symbols.reference(designator.head.ident().span).expect("resolved designator")
+    }
+
+    fn designator(&mut self, designator: &Designator) -> Location {
+        let id = self.head_symbol(designator);
+        let variable = self.variables.get(&id).cloned().expect("designator denotes a variable");
+        let mut addr = self.place_addr(variable.level, &variable.place);
+        if variable.indirect {
+            addr = self.load(MemTy::Ptr, addr);
+        }
+        let mut lengths = vec![];
+        for place in &variable.lengths {
+            let length = self.place_addr(variable.level, place);
+            lengths.push(self.load(MemTy::I64, length));
+        }
+        let tag = variable.tag.map(|place| {
+            let tag = self.place_addr(variable.level, &place);
+            self.load(MemTy::Ptr, tag)
+        });
+        let ty = self.analysis.designators[&designator.head.span()];
+        let mut location = Location { addr, ty, lengths, tag };
+
+        for selector in &designator.selectors {
+            let selected = self.analysis.designators[&selector.span()];
+            location = self.select(location, selector, selected);
+        }
+        location
+    }
+
+    fn select(&mut self, mut location: Location, selector: &Selector, selected: TypeId) -> Location {
+        match selector {
+            Selector::Field(field) => {
+                if matches!(self.ty(location.ty), Type::Pointer { .. }) {
+                    location.addr = self.load(MemTy::Ptr, location.addr);
+                    self.check_nil(location.addr);
+                }
+                let field = self.analysis.symbols.reference(field.span).expect("resolved field");
+                let offset = self.field_offsets[&field];
+                let addr = self.ptr_add(location.addr, Operand::Int(offset as i64));
+                Location { addr, ty: selected, lengths: vec![], tag: None }
+            }
+            Selector::Index(indices, _) => {
+                for index in indices {
+                    let value = self.expression(index);
+                    location = self.index(location, value);
+                }
+                location
+            }
+            Selector::Deref(_) => {
+                let pointer = self.load(MemTy::Ptr, location.addr);
+                let tag = self.heap_tag(pointer);
+                Location { addr: pointer, ty: selected, lengths: vec![], tag: Some(tag) }
+            }
+            Selector::TypeGuard(..) => {
+                let tag = if matches!(self.ty(location.ty), Type::Pointer { .. }) {
+                    let pointer = self.load(MemTy::Ptr, location.addr);
+                    self.heap_tag(pointer)
+                } else {
+                    self.record_tag(&location)
+                };
+                let desc = self.descriptors[&self.analysis.types.record_of(selected).expect("record type")].clone();
+                self.emit(Inst::TypeGuard { tag, desc });
+                if matches!(self.ty(selected), Type::Record { .. }) {
+                    location.tag = Some(tag);
+                }
+                location.ty = selected;
+                location
+            }
+        }
+    }
+
+    fn index(&mut self, location: Location, index: Operand) -> Location {
+        let (length, element, mut lengths) = match self.ty(location.ty) {
+            Type::Array { length, element } => (Operand::Int(*length), *element, vec![]),
+            Type::OpenArray { element } => {
+                let mut lengths = location.lengths;
+                let length = lengths.remove(0);
+                (length, *element, lengths)
+            }
+            _ => unreachable!("index into a non-array"),
+        };
+        self.check_index(index, length);
+        let size = self.element_size(element, &lengths);
+        let offset = match (index, size) {
+            (Operand::Int(i), Operand::Int(s)) => Operand::Int(i * s),
+            (_, Operand::Int(1)) => index,
+            _ => self.binary(BinOp::Mul, Ty::I64, index, size),
+        };
+        let addr = self.ptr_add(location.addr, offset);
+        if !matches!(self.ty(element), Type::OpenArray { .. }) {
+            lengths.clear();
+        }
+        Location { addr, ty: element, lengths, tag: None }
+    }
+
+    fn check_index(&mut self, index: Operand, length: Operand) {
+        if let (Operand::Int(i), Operand::Int(n)) = (index, length)
+            && (0..n).contains(&i)
+        {
+            return;
+        }
+        let low = self.cmp(Cond::Ge, Ty::I64, index, Operand::Int(0));
+        let high = self.cmp(Cond::Lt, Ty::I64, index, length);
+        let valid = self.binary(BinOp::And, Ty::I64, low, high);
+        self.guard(valid, ir::trap::INDEX);
+    }
+
+    /// The size of an array element; `lengths` are those of the element if it is an open array.
+    fn element_size(&mut self, element: TypeId, lengths: &[Operand]) -> Operand {
+        match self.ty(element) {
+            Type::OpenArray { element: inner } => {
+                let inner = self.element_size(*inner, &lengths[1..]);
+                match (lengths[0], inner) {
+                    (Operand::Int(n), Operand::Int(s)) => Operand::Int(n * s),
+                    (length, inner) => self.binary(BinOp::Mul, Ty::I64, length, inner),
+                }
+            }
+            _ => Operand::Int(self.size(element) as i64),
+        }
+    }
+
+    /// The lengths of the first `dimensions` dimensions of the array at `location`.
+    fn array_lengths(&self, location: &Location, dimensions: usize) -> Vec<Operand> {
+        let mut lengths = location.lengths.clone();
+        let mut ty = location.ty;
+        for _ in 0..lengths.len() {
+            if let Type::OpenArray { element } = self.ty(ty) {
+                ty = *element;
+            }
+        }
+        while lengths.len() < dimensions {
+            let Type::Array { length, element } = self.ty(ty) else { break };
+            lengths.push(Operand::Int(*length));
+            ty = *element;
+        }
+        lengths.truncate(dimensions);
+        lengths
+    }
+
+    /// The dynamic type descriptor of the record at `location`.
+    fn record_tag(&mut self, location: &Location) -> Operand {
+        match location.tag {
+            Some(tag) => tag,
+            None => self.descriptor(location.ty),
+        }
+    }
+
+    /// The value of a designator: loaded for scalars, the address for structured variables.
+    fn designator_value(&mut self, designator: &Designator) -> Operand {
+        let id = self.head_symbol(designator);
+        if designator.selectors.is_empty() && self.analysis.symbols.symbol(id).kind == SymbolKind::Procedure {
+            let dst = self.reg(Ty::Ptr);
+            self.emit(Inst::FuncAddr { dst, name: self.procedures[&id].clone() });
+            return Operand::Reg(dst);
+        }
+        let location = self.designator(designator);
+        match self.mem_ty(location.ty) {
+            Some(mem) => self.load(mem, location.addr),
+            None => location.addr,
+        }
+    }
+
+    /// The location of an argument that must be a designator.
+    fn argument_location(&mut self, argument: &Expression) -> Location {
+        let Expression::Designator { designator, actual_parameters: None, .. } = argument else {
+            unreachable!("checked variable argument");
+        };
+        let (designator, _) = self.analysis.designator(designator, None);
+        self.designator(&designator)
+    }
+
+    // --------------------------- EXPRESSIONS ---------------------------
+    fn constant(&mut self, value: &Value) -> Operand {
+        match value {
+            Value::Integer(n) => Operand::Int(*n),
+            Value::Real(x) => Operand::Real(*x),
+            Value::Boolean(b) => Operand::Int(*b as i64),
+            Value::Char(c) => Operand::Int(*c as i64),
+            Value::Set(bits) => Operand::Int(*bits as i64),
+            Value::String(text) => {
+                let bytes: Vec<u8> = text.chars().map(|c| c as u8).collect();
+                let name = self.string_global(&bytes);
+                self.global_addr(&name)
+            }
+            Value::Nil => self.null(),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> Operand {
+        if let Some(value) = &self.info(expr).value {
+            return self.constant(value);
+        }
+        match expr {
+            Expression::Int { value, .. } => Operand::Int(*value),
+            Expression::Real { value, .. } => Operand::Real(*value),
+            Expression::String { .. } | Expression::Char { .. } | Expression::Nil { .. } | Expression::True { .. } | Expression::False { .. } => {
+                unreachable!("literals are constant")
+            }
+            Expression::Set { elements, .. } => self.set(elements),
+            Expression::Designator { designator, actual_parameters, .. } => {
+                match self.analysis.designator(designator, actual_parameters.as_deref()) {
+                    (designator, Some(arguments)) => self.call(&designator, &arguments).expect("function call has a result"),
+                    (designator, None) => self.designator_value(&designator),
+                }
+            }
+            Expression::Unary { op, operand, .. } => {
+                let ty = self.expr_ty(expr);
+                let value = self.expression(operand);
+                match op {
+                    UnaryOperation::Plus => value,
+                    UnaryOperation::Not => self.binary(BinOp::Xor, Ty::I64, value, Operand::Int(1)),
+                    UnaryOperation::Minus if ty == REAL => self.unary(UnOp::Neg, Ty::F64, value),
+                    UnaryOperation::Minus if matches!(self.ty(ty), Type::Set) => self.unary(UnOp::Not, Ty::I64, value),
+                    UnaryOperation::Minus => self.unary(UnOp::Neg, Ty::I64, value),
+                }
+            }
+            Expression::Binary { op, lhs, rhs, .. } => self.binary_expression(*op, lhs, rhs, self.expr_ty(expr)),
+            Expression::Error { .. } => unreachable!("syntax errors stop compilation"),
+        }
+    }
+
+    fn set(&mut self, elements: &[Element]) -> Operand {
+        let mut bits = Operand::Int(0);
+        for element in elements {
+            let first = self.expression(&element.first);
+            let mask = match &element.second {
+                None => self.binary(BinOp::Shl, Ty::I64, Operand::Int(1), first),
+                Some(second) => {
+                    // Bits first..second: all bits from `first` up, minus those above `second`.
+                    let second = self.expression(second);
+                    let low = self.binary(BinOp::Shl, Ty::I64, Operand::Int(-1), first);
+                    let high = self.binary(BinOp::Shl, Ty::I64, Operand::Int(-2), second);
+                    let high = self.unary(UnOp::Not, Ty::I64, high);
+                    self.binary(BinOp::And, Ty::I64, low, high)
+                }
+            };
+            bits = if bits == Operand::Int(0) { mask } else { self.binary(BinOp::Or, Ty::I64, bits, mask) };
+        }
+        bits
+    }
+
+    fn binary_expression(&mut self, op: BinaryOperation, lhs: &Expression, rhs: &Expression, ty: TypeId) -> Operand {
+        use BinaryOperation::*;
+        match op {
+            And | Or => self.short_circuit(op == And, lhs, rhs),
+            Is => {
+                let Expression::Designator { designator, .. } = rhs else { unreachable!("checked type test") };
+                let target = self.analysis.symbols.symbol(self.head_symbol(designator)).ty;
+                let tag = self.dynamic_tag(lhs);
+                let desc = self.descriptors[&self.analysis.types.record_of(target).expect("record type")].clone();
+                let dst = self.reg(Ty::I64);
+                self.emit(Inst::TypeTest { dst, tag, desc });
+                Operand::Reg(dst)
+            }
+            In => {
+                let element = self.expression(lhs);
+                let set = self.expression(rhs);
+                let shifted = self.binary(BinOp::Sar, Ty::I64, set, element);
+                self.binary(BinOp::And, Ty::I64, shifted, Operand::Int(1))
+            }
+            Eq | Neq | Lt | Le | Gt | Ge => {
+                let cond = match op {
+                    Eq => Cond::Eq,
+                    Neq => Cond::Ne,
+                    Lt => Cond::Lt,
+                    Le => Cond::Le,
+                    Gt => Cond::Gt,
+                    _ => Cond::Ge,
+                };
+                let (lhs_ty, rhs_ty) = (self.expr_ty(lhs), self.expr_ty(rhs));
+                let types = &self.analysis.types;
+                let strings = types.is_string_like(lhs_ty) && types.is_string_like(rhs_ty)
+                    && !(types.is_char_like(lhs_ty) && types.is_char_like(rhs_ty));
+                if strings {
+                    let lhs = self.string_address(lhs);
+                    let rhs = self.string_address(rhs);
+                    let difference = self.compare_strings(lhs, rhs);
+                    return self.cmp(cond, Ty::I64, difference, Operand::Int(0));
+                }
+                let operand_ty = if lhs_ty == REAL { Ty::F64 } else { self.value_ty(lhs_ty) };
+                let lhs = self.expression(lhs);
+                let rhs = self.expression(rhs);
+                self.cmp(cond, operand_ty, lhs, rhs)
+            }
+            Addition | Subtraction | Multiplication | Division | Div | Mod => {
+                let lhs = self.expression(lhs);
+                let rhs = self.expression(rhs);
+                if ty == REAL {
+                    let op = match op {
+                        Addition => BinOp::Add,
+                        Subtraction => BinOp::Sub,
+                        Multiplication => BinOp::Mul,
+                        _ => BinOp::Div,
+                    };
+                    return self.binary(op, Ty::F64, lhs, rhs);
+                }
+                if matches!(self.ty(ty), Type::Set) {
+                    return match op {
+                        Addition => self.binary(BinOp::Or, Ty::I64, lhs, rhs),
+                        Subtraction => {
+                            let complement = self.unary(UnOp::Not, Ty::I64, rhs);
+                            self.binary(BinOp::And, Ty::I64, lhs, complement)
+                        }
+                        Multiplication => self.binary(BinOp::And, Ty::I64, lhs, rhs),
+                        _ => self.binary(BinOp::Xor, Ty::I64, lhs, rhs),
+                    };
+                }
+                let op = match op {
+                    Addition => BinOp::Add,
+                    Subtraction => BinOp::Sub,
+                    Multiplication => BinOp::Mul,
+                    Div => BinOp::Div,
+                    _ => BinOp::Mod,
+                };
+                self.binary(op, Ty::I64, lhs, rhs)
+            }
+        }
+    }
+
+    /// `lhs & rhs` or `lhs OR rhs`, evaluating `rhs` only when needed.
+    fn short_circuit(&mut self, and: bool, lhs: &Expression, rhs: &Expression) -> Operand {
+        let lhs = self.expression(lhs);
+        let right = self.new_block();
+        let (join, result) = self.join_block(Ty::I64);
+        let short = Target { block: join, args: vec![Operand::Int(!and as i64)] };
+        if and {
+            self.branch(lhs, Target::new(right), short);
+        } else {
+            self.branch(lhs, short, Target::new(right));
+        }
+        self.switch_to(right);
+        let rhs = self.expression(rhs);
+        self.jump(join, vec![rhs]);
+        self.switch_to(join);
+        Operand::Reg(result)
+    }
+
+    /// The type descriptor of the dynamic type of a pointer or record expression.
+    fn dynamic_tag(&mut self, expr: &Expression) -> Operand {
+        if matches!(self.ty(self.expr_ty(expr)), Type::Pointer { .. }) {
+            let pointer = self.expression(expr);
+            self.heap_tag(pointer)
+        } else {
+            let location = self.argument_location(expr);
+            self.record_tag(&location)
+        }
+    }
+
+    /// The address of a string or character array; a one-character constant becomes a string.
+    fn string_address(&mut self, expr: &Expression) -> Operand {
+        if let Some(Value::Char(c)) = self.info(expr).value {
+            let name = self.string_global(&[c]);
+            return self.global_addr(&name);
+        }
+        self.expression(expr)
+    }
+
+    /// Compares two 0X-terminated strings, giving the difference of the first differing characters.
+    fn compare_strings(&mut self, lhs: Operand, rhs: Operand) -> Operand {
+        let (head, index) = self.join_block(Ty::I64);
+        let more = self.new_block();
+        let next = self.new_block();
+        let (done, difference) = self.join_block(Ty::I64);
+        self.jump(head, vec![Operand::Int(0)]);
+
+        self.switch_to(head);
+        let left_addr = self.ptr_add(lhs, Operand::Reg(index));
+        let left = self.load(MemTy::I8, left_addr);
+        let right_addr = self.ptr_add(rhs, Operand::Reg(index));
+        let right = self.load(MemTy::I8, right_addr);
+        let delta = self.binary(BinOp::Sub, Ty::I64, left, right);
+        let differ = self.cmp(Cond::Ne, Ty::I64, left, right);
+        self.branch(differ, Target { block: done, args: vec![delta] }, Target::new(more));
+
+        self.switch_to(more);
+        let end = self.cmp(Cond::Eq, Ty::I64, left, Operand::Int(0));
+        self.branch(end, Target { block: done, args: vec![Operand::Int(0)] }, Target::new(next));
+
+        self.switch_to(next);
+        let following = self.binary(BinOp::Add, Ty::I64, Operand::Reg(index), Operand::Int(1));
+        self.jump(head, vec![following]);
+
+        self.switch_to(done);
+        Operand::Reg(difference)
+    }
+
+    // --------------------------- CALLS ---------------------------
+    /// Lowers a call and returns its result, if the procedure has one.
+    fn call(&mut self, callee: &Designator, arguments: &[Expression]) -> Option<Operand> {
+        let id = self.head_symbol(callee);
+        let symbol = self.analysis.symbols.symbol(id);
+        if let SymbolKind::Builtin(builtin) = symbol.kind {
+            return self.builtin(builtin, arguments);
+        }
+
+        let direct = callee.selectors.is_empty() && symbol.kind == SymbolKind::Procedure;
+        let procedure_ty = match callee.selectors.last() {
+            Some(selector) => self.analysis.designators[&selector.span()],
+            None => symbol.ty,
+        };
+        let Type::Procedure { params, result } = self.ty(procedure_ty) else { unreachable!("checked call") };
+
+        let mut args = vec![];
+        let target = if direct {
+            // Imported procedures have no body here; they are never nested.
+            let level = symbol.body.map_or(2, |body| self.analysis.symbols.scope(body).level);
+            if level > 2 {
+                args.push(self.frame_base(level - 1));
+            }
+            Callee::Direct(self.procedures[&id].clone())
+        } else {
+            Callee::Indirect(self.designator_value(callee))
+        };
+
+        for (argument, param) in arguments.iter().zip(params) {
+            self.argument(argument, param, &mut args);
+        }
+
+        let dst = result.map(|result| (self.reg(self.value_ty(result)), self.value_ty(result)));
+        self.emit(Inst::Call { dst, callee: target, args });
+        dst.map(|(reg, _)| Operand::Reg(reg))
+    }
+
+    fn argument(&mut self, argument: &Expression, param: &Param, args: &mut Vec<Operand>) {
+        match self.ty(param.ty) {
+            Type::OpenArray { .. } => {
+                let mut dimensions = 0;
+                let mut ty = param.ty;
+                while let Type::OpenArray { element } = self.ty(ty) {
+                    dimensions += 1;
+                    ty = *element;
+                }
+                if let Some(value) = &self.info(argument).value {
+                    let length = match value {
+                        Value::String(text) => text.chars().count() as i64 + 1,
+                        _ => 2,
+                    };
+                    args.push(self.string_address(argument));
+                    args.push(Operand::Int(length));
+                    return;
+                }
+                let location = self.argument_location(argument);
+                let lengths = self.array_lengths(&location, dimensions);
+                args.push(location.addr);
+                args.extend(lengths);
+            }
+            Type::Record { .. } if param.by_ref => {
+                let location = self.argument_location(argument);
+                let tag = self.record_tag(&location);
+                args.push(location.addr);
+                args.push(tag);
+            }
+            Type::Array { .. } if self.info(argument).value.is_some() => args.push(self.string_address(argument)),
+            Type::Array { .. } | Type::Record { .. } => args.push(self.expression(argument)),
+            _ if param.by_ref => {
+                let location = self.argument_location(argument);
+                args.push(location.addr);
+            }
+            _ => args.push(self.expression(argument)),
+        }
+    }
+
+    fn builtin(&mut self, builtin: Builtin, arguments: &[Expression]) -> Option<Operand> {
+        use Builtin::*;
+        let value = match builtin {
+            Abs => {
+                let x = self.expression(&arguments[0]);
+                if self.expr_ty(&arguments[0]) == REAL {
+                    let negative = self.cmp(Cond::Lt, Ty::F64, x, Operand::Real(0.0));
+                    let negate = self.new_block();
+                    let (join, result) = self.join_block(Ty::F64);
+                    self.branch(negative, Target::new(negate), Target { block: join, args: vec![x] });
+                    self.switch_to(negate);
+                    let negated = self.unary(UnOp::Neg, Ty::F64, x);
+                    self.jump(join, vec![negated]);
+                    self.switch_to(join);
+                    Operand::Reg(result)
+                } else {
+                    let in_range = self.cmp(Cond::Ne, Ty::I64, x, Operand::Int(i64::MIN));
+                    self.guard(in_range, ir::trap::OVERFLOW);
+                    // (x XOR sign) - sign, where sign is 0 or -1.
+                    let sign = self.binary(BinOp::Sar, Ty::I64, x, Operand::Int(63));
+                    let flipped = self.binary(BinOp::Xor, Ty::I64, x, sign);
+                    self.binary(BinOp::Sub, Ty::I64, flipped, sign)
+                }
+            }
+            Odd => {
+                let x = self.expression(&arguments[0]);
+                self.binary(BinOp::And, Ty::I64, x, Operand::Int(1))
+            }
+            Len => {
+                let location = self.argument_location(&arguments[0]);
+                self.array_lengths(&location, 1)[0]
+            }
+            Lsl | Asr | Ror => {
+                let x = self.expression(&arguments[0]);
+                let n = self.expression(&arguments[1]);
+                let op = match builtin {
+                    Lsl => BinOp::Shl,
+                    Asr => BinOp::Sar,
+                    _ => BinOp::Ror,
+                };
+                self.binary(op, Ty::I64, x, n)
+            }
+            Floor => {
+                let x = self.expression(&arguments[0]);
+                // Both bounds are powers of two, exact as REALs; NaN fails the first comparison.
+                let low = self.cmp(Cond::Ge, Ty::F64, x, Operand::Real(i64::MIN as f64));
+                let high = self.cmp(Cond::Lt, Ty::F64, x, Operand::Real(-(i64::MIN as f64)));
+                let in_range = self.binary(BinOp::And, Ty::I64, low, high);
+                self.guard(in_range, ir::trap::OVERFLOW);
+                self.convert(Conv::Floor, x)
+            }
+            Flt => {
+                let x = self.expression(&arguments[0]);
+                self.convert(Conv::IntToReal, x)
+            }
+            Ord | Chr => self.expression(&arguments[0]),
+            Inc | Dec => {
+                let location = self.argument_location(&arguments[0]);
+                let mem = self.mem_ty(location.ty).expect("integer variable");
+                let step = match arguments.get(1) {
+                    Some(step) => self.expression(step),
+                    None => Operand::Int(1),
+                };
+                let old = self.load(mem, location.addr);
+                let new = self.binary(if builtin == Inc { BinOp::Add } else { BinOp::Sub }, Ty::I64, old, step);
+                self.store(mem, new, location.addr);
+                return None;
+            }
+            Incl | Excl => {
+                let location = self.argument_location(&arguments[0]);
+                let element = self.expression(&arguments[1]);
+                let bit = self.binary(BinOp::Shl, Ty::I64, Operand::Int(1), element);
+                let old = self.load(MemTy::I64, location.addr);
+                let new = if builtin == Incl {
+                    self.binary(BinOp::Or, Ty::I64, old, bit)
+                } else {
+                    let mask = self.unary(UnOp::Not, Ty::I64, bit);
+                    self.binary(BinOp::And, Ty::I64, old, mask)
+                };
+                self.store(MemTy::I64, new, location.addr);
+                return None;
+            }
+            New => {
+                let location = self.argument_location(&arguments[0]);
+                let record = self.analysis.types.record_of(location.ty).expect("pointer to record");
+                let dst = self.reg(Ty::Ptr);
+                self.emit(Inst::New { dst, desc: self.descriptors[&record].clone() });
+                self.store(MemTy::Ptr, Operand::Reg(dst), location.addr);
+                return None;
+            }
+            Assert => {
+                let cond = self.expression(&arguments[0]);
+                self.guard(cond, ir::trap::ASSERT);
+                return None;
+            }
+            Pack => {
+                let location = self.argument_location(&arguments[0]);
+                let exponent = self.expression(&arguments[1]);
+                let factor = self.power_of_two(exponent);
+                let x = self.load(MemTy::F64, location.addr);
+                let packed = self.binary(BinOp::Mul, Ty::F64, x, factor);
+                self.store(MemTy::F64, packed, location.addr);
+                return None;
+            }
+            Unpk => {
+                self.unpack(&arguments[0], &arguments[1]);
+                return None;
+            }
+        };
+        Some(value)
+    }
+
+    /// 2.0 raised to the integer `exponent`, by repeated doubling or halving.
+    fn power_of_two(&mut self, exponent: Operand) -> Operand {
+        let head = self.new_block();
+        let (count, power) = (self.reg(Ty::I64), self.reg(Ty::F64));
+        self.f.function.block_mut(head).params = vec![(count, Ty::I64), (power, Ty::F64)];
+        let (up, check, down) = (self.new_block(), self.new_block(), self.new_block());
+        let (done, result) = self.join_block(Ty::F64);
+        self.jump(head, vec![exponent, Operand::Real(1.0)]);
+
+        self.switch_to(head);
+        let positive = self.cmp(Cond::Gt, Ty::I64, Operand::Reg(count), Operand::Int(0));
+        self.branch(positive, Target::new(up), Target::new(check));
+
+        self.switch_to(up);
+        let doubled = self.binary(BinOp::Mul, Ty::F64, Operand::Reg(power), Operand::Real(2.0));
+        let fewer = self.binary(BinOp::Sub, Ty::I64, Operand::Reg(count), Operand::Int(1));
+        self.jump(head, vec![fewer, doubled]);
+
+        self.switch_to(check);
+        let negative = self.cmp(Cond::Lt, Ty::I64, Operand::Reg(count), Operand::Int(0));
+        self.branch(negative, Target::new(down), Target { block: done, args: vec![Operand::Reg(power)] });
+
+        self.switch_to(down);
+        let halved = self.binary(BinOp::Div, Ty::F64, Operand::Reg(power), Operand::Real(2.0));
+        let more = self.binary(BinOp::Add, Ty::I64, Operand::Reg(count), Operand::Int(1));
+        self.jump(head, vec![more, halved]);
+
+        self.switch_to(done);
+        Operand::Reg(result)
+    }
+
+    /// UNPK(x, n): scales a positive `x` into [1.0, 2.0) and stores the exponent in `n`.
+    fn unpack(&mut self, x: &Expression, n: &Expression) {
+        let x = self.argument_location(x);
+        let n = self.argument_location(n);
+        let start = self.load(MemTy::F64, x.addr);
+
+        let head = self.new_block();
+        let (value, exponent) = (self.reg(Ty::F64), self.reg(Ty::I64));
+        self.f.function.block_mut(head).params = vec![(value, Ty::F64), (exponent, Ty::I64)];
+        let (halve, small, positive, double) = (self.new_block(), self.new_block(), self.new_block(), self.new_block());
+        let done = self.new_block();
+        let (result, result_exponent) = (self.reg(Ty::F64), self.reg(Ty::I64));
+        self.f.function.block_mut(done).params = vec![(result, Ty::F64), (result_exponent, Ty::I64)];
+        let finished = Target { block: done, args: vec![Operand::Reg(value), Operand::Reg(exponent)] };
+        self.jump(head, vec![start, Operand::Int(0)]);
+
+        self.switch_to(head);
+        let large = self.cmp(Cond::Ge, Ty::F64, Operand::Reg(value), Operand::Real(2.0));
+        self.branch(large, Target::new(halve), Target::new(small));
+
+        self.switch_to(halve);
+        let halved = self.binary(BinOp::Div, Ty::F64, Operand::Reg(value), Operand::Real(2.0));
+        let up = self.binary(BinOp::Add, Ty::I64, Operand::Reg(exponent), Operand::Int(1));
+        self.jump(head, vec![halved, up]);
+
+        self.switch_to(small);
+        let below = self.cmp(Cond::Lt, Ty::F64, Operand::Reg(value), Operand::Real(1.0));
+        self.branch(below, Target::new(positive), finished.clone());
+
+        self.switch_to(positive);
+        let above_zero = self.cmp(Cond::Gt, Ty::F64, Operand::Reg(value), Operand::Real(0.0));
+        self.branch(above_zero, Target::new(double), finished);
+
+        self.switch_to(double);
+        let doubled = self.binary(BinOp::Mul, Ty::F64, Operand::Reg(value), Operand::Real(2.0));
+        let down = self.binary(BinOp::Sub, Ty::I64, Operand::Reg(exponent), Operand::Int(1));
+        self.jump(head, vec![doubled, down]);
+
+        self.switch_to(done);
+        self.store(MemTy::F64, Operand::Reg(result), x.addr);
+        let mem = self.mem_ty(n.ty).expect("integer variable");
+        self.store(mem, Operand::Reg(result_exponent), n.addr);
+    }
+
+    // --------------------------- STATEMENTS ---------------------------
+    fn statements(&mut self, stmts: &StatementSequence) {
+        for statement in &stmts.statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Assign { target, value, .. } => self.assign(target, value),
+            Statement::Call { callee, parameters, .. } => {
+                let (callee, arguments) = self.analysis.designator(callee, parameters.as_deref());
+                self.call(&callee, arguments.as_deref().unwrap_or_default());
+            }
+            Statement::If { cond, stmts, elsif_branches, else_branch, .. } => {
+                let exit = self.new_block();
+                let branches = std::iter::once((cond, stmts)).chain(elsif_branches.iter().map(|b| (&b.cond, &b.stmts)));
+                for (cond, stmts) in branches {
+                    let then_block = self.new_block();
+                    let next = self.new_block();
+                    let cond = self.expression(cond);
+                    self.branch(cond, Target::new(then_block), Target::new(next));
+                    self.switch_to(then_block);
+                    self.statements(stmts);
+                    self.jump(exit, vec![]);
+                    self.switch_to(next);
+                }
+                if let Some(else_branch) = else_branch {
+                    self.statements(else_branch);
+                }
+                self.jump(exit, vec![]);
+                self.switch_to(exit);
+            }
+            Statement::Case { expr, branches, .. } => self.case(expr, branches),
+            Statement::While { cond, stmts, elsif_branches, .. } => {
+                // Dijkstra's loop: run the first branch whose guard holds, then test again from
+                // the top; leave once no guard holds.
+                let head = self.new_block();
+                self.jump(head, vec![]);
+                self.switch_to(head);
+                let branches = std::iter::once((cond, stmts)).chain(elsif_branches.iter().map(|b| (&b.cond, &b.stmts)));
+                for (cond, stmts) in branches {
+                    let body = self.new_block();
+                    let next = self.new_block();
+                    let cond = self.expression(cond);
+                    self.branch(cond, Target::new(body), Target::new(next));
+                    self.switch_to(body);
+                    self.statements(stmts);
+                    self.jump(head, vec![]);
+                    self.switch_to(next);
+                }
+            }
+            Statement::Repeat { stmts, cond, .. } => {
+                let body = self.new_block();
+                self.jump(body, vec![]);
+                self.switch_to(body);
+                self.statements(stmts);
+                let cond = self.expression(cond);
+                let exit = self.new_block();
+                self.branch(cond, Target::new(exit), Target::new(body));
+                self.switch_to(exit);
+            }
+            Statement::For { var, low, high, by, stmts, .. } => {
+                let id = self.analysis.symbols.reference(var.span).expect("resolved control variable");
+                let variable = self.variables[&id].clone();
+                let mem = self.mem_ty(self.analysis.symbols.symbol(id).ty).expect("integer control variable");
+                let step = match by.as_ref().and_then(|by| self.info(by).value.as_ref()) {
+                    Some(Value::Integer(step)) => *step,
+                    _ => 1,
+                };
+
+                let start = self.expression(low);
+                let limit = self.expression(high);
+                let addr = self.control_address(&variable);
+                self.store(mem, start, addr);
+
+                let head = self.new_block();
+                let body = self.new_block();
+                let exit = self.new_block();
+                self.jump(head, vec![]);
+                self.switch_to(head);
+                let addr = self.control_address(&variable);
+                let current = self.load(mem, addr);
+                let cond = if step > 0 { Cond::Le } else { Cond::Ge };
+                let more = self.cmp(cond, Ty::I64, current, limit);
+                self.branch(more, Target::new(body), Target::new(exit));
+
+                self.switch_to(body);
+                self.statements(stmts);
+                let addr = self.control_address(&variable);
+                let current = self.load(mem, addr);
+                let next = self.binary(BinOp::Add, Ty::I64, current, Operand::Int(step));
+                self.store(mem, next, addr);
+                self.jump(head, vec![]);
+                self.switch_to(exit);
+            }
+            Statement::Error { .. } => unreachable!("syntax errors stop compilation"),
+        }
+    }
+
+    fn control_address(&mut self, variable: &Variable) -> Operand {
+        let addr = self.place_addr(variable.level, &variable.place);
+        if variable.indirect { self.load(MemTy::Ptr, addr) } else { addr }
+    }
+
+    fn assign(&mut self, target: &Designator, value: &Expression) {
+        let (target, _) = self.analysis.designator(target, None);
+        let location = self.designator(&target);
+        if let Some(mem) = self.mem_ty(location.ty) {
+            let value = self.expression(value);
+            self.store(mem, value, location.addr);
+            return;
+        }
+
+        let source_ty = self.expr_ty(value);
+        let size = match self.ty(source_ty) {
+            Type::String { length } => Operand::Int(*length as i64 + 1),
+            Type::OpenArray { element } => {
+                let source = self.argument_location(value);
+                let element = self.element_size(*element, &source.lengths[1..]);
+                self.binary(BinOp::Mul, Ty::I64, source.lengths[0], element)
+            }
+            _ => Operand::Int(self.size(location.ty) as i64),
+        };
+        let source = self.string_address(value);
+        self.emit(Inst::MemCopy { dst: location.addr, src: source, size });
+    }
+
+    fn label_value(&self, value: &LabelValue) -> i64 {
+        match value {
+            LabelValue::Integer { value, .. } => *value,
+            LabelValue::String { value, .. } => value.chars().next().map_or(0, |c| c as i64),
+            LabelValue::Char { value, .. } => *value as i64,
+            LabelValue::QualifiedIdentifier(name) => {
+                let id = self.analysis.symbols.reference(name.ident().span).expect("resolved label");
+                match &self.analysis.symbols.symbol(id).kind {
+                    SymbolKind::Const(Value::Integer(n)) => *n,
+                    SymbolKind::Const(Value::Char(c)) => *c as i64,
+                    _ => unreachable!("checked case label"),
+                }
+            }
+        }
+    }
+
+    fn case(&mut self, expr: &Expression, branches: &[Case]) {
+        let ty = self.expr_ty(expr);
+        let type_case = matches!(self.ty(ty), Type::Pointer { .. } | Type::Record { .. });
+        let value = if type_case { self.dynamic_tag(expr) } else { self.expression(expr) };
+
+        let mut bodies = vec![];
+        for branch in branches {
+            let body = self.new_block();
+            for label in &branch.label_list {
+                let matches = if type_case {
+                    let Label::Single { value: LabelValue::QualifiedIdentifier(name) } = label else {
+                        unreachable!("checked type case");
+                    };
+                    let id = self.analysis.symbols.reference(name.ident().span).expect("resolved label");
+                    let target = self.analysis.symbols.symbol(id).ty;
+                    let desc = self.descriptors[&self.analysis.types.record_of(target).expect("record type")].clone();
+                    let dst = self.reg(Ty::I64);
+                    self.emit(Inst::TypeTest { dst, tag: value, desc });
+                    Operand::Reg(dst)
+                } else {
+                    match label {
+                        Label::Single { value: label } => {
+                            let label = self.label_value(label);
+                            self.cmp(Cond::Eq, Ty::I64, value, Operand::Int(label))
+                        }
+                        Label::Range { low, high } => {
+                            let (low, high) = (self.label_value(low), self.label_value(high));
+                            let above = self.cmp(Cond::Ge, Ty::I64, value, Operand::Int(low));
+                            let below = self.cmp(Cond::Le, Ty::I64, value, Operand::Int(high));
+                            self.binary(BinOp::And, Ty::I64, above, below)
+                        }
+                    }
+                };
+                let next = self.new_block();
+                self.branch(matches, Target::new(body), Target::new(next));
+                self.switch_to(next);
+            }
+            bodies.push((body, &branch.statements));
+        }
+        self.terminate(Terminator::Trap(ir::trap::CASE));
+
+        let exit = self.new_block();
+        for (body, stmts) in bodies {
+            self.switch_to(body);
+            self.statements(stmts);
+            self.jump(exit, vec![]);
+        }
+        self.switch_to(exit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::analysis;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+
+    fn lower(source: &str) -> ir::Module {
+        let module = Parser::new(Lexer::new(source)).parse().unwrap();
+        let analysis = analysis::check(&module).unwrap();
+        let lowered = generate(&module, &analysis);
+        assert_eq!(ir::parse(&lowered.to_string()).unwrap(), lowered);
+        lowered
+    }
+
+    /// A small interpreter for lowered modules, so tests can observe what the IR computes.
+    mod machine {
+        use super::*;
+
+        const GLOBALS: u64 = 0x1000;
+        const HEAP: u64 = 0x10000;
+        const STACK: u64 = 0x40000;
+        const FUNCTIONS: u64 = 0x80000;
+
+        pub struct Machine<'m> {
+            module: &'m ir::Module,
+            pub memory: Vec<u8>,
+            symbols: HashMap<String, u64>,
+            heap: u64,
+            stack: u64,
+        }
+
+        impl<'m> Machine<'m> {
+            pub fn new(module: &'m ir::Module) -> Self {
+                let mut machine = Self { module, memory: vec![0; FUNCTIONS as usize], symbols: HashMap::new(), heap: HEAP, stack: STACK };
+                let mut next = GLOBALS;
+                for global in &module.globals {
+                    next = align_up(next, global.align.max(1));
+                    machine.symbols.insert(global.name.clone(), next);
+                    if let Some(init) = &global.init {
+                        machine.memory[next as usize..next as usize + init.len()].copy_from_slice(init);
+                    }
+                    next += global.size.max(1);
+                }
+                for desc in &module.descriptors {
+                    next = align_up(next, 8);
+                    machine.symbols.insert(desc.name.clone(), next);
+                    next += 16;
+                }
+                for desc in &module.descriptors {
+                    let addr = machine.symbols[&desc.name];
+                    let base = desc.base.as_ref().map_or(0, |base| machine.symbols[base]);
+                    machine.write(addr, 8, base);
+                    machine.write(addr + 8, 8, desc.size);
+                }
+                for (index, function) in module.functions.iter().enumerate() {
+                    machine.symbols.insert(function.name.clone(), FUNCTIONS + index as u64);
+                }
+                machine
+            }
+
+            pub fn read(&self, addr: u64, size: u64) -> u64 {
+                let mut bytes = [0u8; 8];
+                bytes[..size as usize].copy_from_slice(&self.memory[addr as usize..(addr + size) as usize]);
+                u64::from_le_bytes(bytes)
+            }
+
+            fn write(&mut self, addr: u64, size: u64, value: u64) {
+                self.memory[addr as usize..(addr + size) as usize].copy_from_slice(&value.to_le_bytes()[..size as usize]);
+            }
+
+            pub fn global(&self, name: &str) -> i64 {
+                self.read(self.symbols[&format!("{}_{name}", self.module.name)], 8) as i64
+            }
+
+            pub fn global_byte(&self, name: &str) -> u8 {
+                self.read(self.symbols[&format!("{}_{name}", self.module.name)], 1) as u8
+            }
+
+            pub fn global_real(&self, name: &str) -> f64 {
+                f64::from_bits(self.global(name) as u64)
+            }
+
+            pub fn global_addr(&self, name: &str) -> u64 {
+                self.symbols[&format!("{}_{name}", self.module.name)]
+            }
+
+            /// Runs a function; `Err` carries the trap number.
+            pub fn call(&mut self, name: &str, args: &[u64]) -> Result<Option<u64>, i64> {
+                let function = self.module.function(name).unwrap_or_else(|| panic!("no function {name}"));
+                let saved = self.stack;
+                let mut slots = vec![];
+                for slot in &function.slots {
+                    self.stack = align_up(self.stack, slot.align.max(1));
+                    slots.push(self.stack);
+                    self.stack += slot.size.max(1);
+                }
+                let mut regs = vec![0u64; function.reg_count()];
+                for (param, arg) in function.params.iter().zip(args) {
+                    regs[param.reg.0 as usize] = *arg;
+                }
+                let mut block = function.blocks[0].id;
+                let result = loop {
+                    let current = function.block(block);
+                    for inst in &current.insts {
+                        self.execute(inst, &mut regs, &slots)?;
+                    }
+                    let value = |regs: &Vec<u64>, operand: &Operand| operand_value(regs, operand);
+                    let target = match &current.term {
+                        Terminator::Jump(target) => target,
+                        Terminator::Branch { cond, then_target, else_target } => {
+                            if value(&regs, cond) != 0 { then_target } else { else_target }
+                        }
+                        Terminator::Return(result) => break result.as_ref().map(|r| value(&regs, r)),
+                        Terminator::Trap(code) => return Err(*code),
+                    };
+                    let args: Vec<u64> = target.args.iter().map(|a| value(&regs, a)).collect();
+                    for ((param, _), arg) in function.block(target.block).params.iter().zip(args) {
+                        regs[param.0 as usize] = arg;
+                    }
+                    block = target.block;
+                };
+                self.stack = saved;
+                Ok(result)
+            }
+
+            fn execute(&mut self, inst: &Inst, regs: &mut [u64], slots: &[u64]) -> Result<(), i64> {
+                let value = |operand: &Operand| operand_value(regs, operand);
+                let result = match inst {
+                    Inst::Copy { src, .. } => Some(value(src)),
+                    Inst::Unary { op, ty, operand, .. } => Some(match (op, ty) {
+                        (UnOp::Neg, Ty::F64) => (-f64::from_bits(value(operand))).to_bits(),
+                        (UnOp::Neg, _) => (value(operand) as i64).wrapping_neg() as u64,
+                        (UnOp::Not, _) => !value(operand),
+                    }),
+                    Inst::Binary { op, ty, lhs, rhs, .. } => Some(binary(*op, *ty, value(lhs), value(rhs))?),
+                    Inst::Cmp { cond, ty, lhs, rhs, .. } => {
+                        let ordering = if *ty == Ty::F64 {
+                            f64::from_bits(value(lhs)).partial_cmp(&f64::from_bits(value(rhs))).unwrap()
+                        } else {
+                            (value(lhs) as i64).cmp(&(value(rhs) as i64))
+                        };
+                        let holds = match cond {
+                            Cond::Eq => ordering.is_eq(),
+                            Cond::Ne => ordering.is_ne(),
+                            Cond::Lt => ordering.is_lt(),
+                            Cond::Le => ordering.is_le(),
+                            Cond::Gt => ordering.is_gt(),
+                            Cond::Ge => ordering.is_ge(),
+                        };
+                        Some(holds as u64)
+                    }
+                    Inst::Convert { conv: Conv::IntToReal, src, .. } => Some((value(src) as i64 as f64).to_bits()),
+                    Inst::Convert { conv: Conv::Floor, src, .. } => Some(f64::from_bits(value(src)).floor() as i64 as u64),
+                    Inst::Load { mem, addr, .. } => Some(self.read(value(addr), mem.size())),
+                    Inst::Store { mem, value: stored, addr } => {
+                        self.write(value(addr), mem.size(), value(stored));
+                        None
+                    }
+                    Inst::SlotAddr { slot, .. } => Some(slots[slot.0 as usize]),
+                    Inst::GlobalAddr { name, .. } | Inst::FuncAddr { name, .. } => Some(self.symbols[name]),
+                    Inst::PtrAdd { base, offset, .. } => Some(value(base).wrapping_add(value(offset))),
+                    Inst::Call { dst, callee, args } => {
+                        let name = match callee {
+                            Callee::Direct(name) => name.clone(),
+                            Callee::Indirect(target) => {
+                                let index = value(target) - FUNCTIONS;
+                                self.module.functions[index as usize].name.clone()
+                            }
+                        };
+                        let args: Vec<u64> = args.iter().map(value).collect();
+                        let result = self.call(&name, &args)?;
+                        dst.and(result)
+                    }
+                    Inst::MemCopy { dst, src, size } => {
+                        let (dst, src, size) = (value(dst) as usize, value(src) as usize, value(size) as usize);
+                        self.memory.copy_within(src..src + size, dst);
+                        None
+                    }
+                    Inst::New { desc, .. } => {
+                        let desc = self.symbols[desc];
+                        let size = self.read(desc + 8, 8);
+                        self.write(self.heap, 8, desc);
+                        let object = self.heap + 8;
+                        self.heap = align_up(object + size.max(1), 8);
+                        Some(object)
+                    }
+                    Inst::TypeTest { tag, desc, .. } => Some(self.extends(value(tag), desc) as u64),
+                    Inst::TypeGuard { tag, desc } => {
+                        if !self.extends(value(tag), desc) {
+                            return Err(ir::trap::TYPE_GUARD);
+                        }
+                        None
+                    }
+                };
+                if let (Some(result), Some((dst, _))) = (result, inst.def()) {
+                    regs[dst.0 as usize] = result;
+                }
+                Ok(())
+            }
+
+            fn extends(&self, mut tag: u64, desc: &str) -> bool {
+                let desc = self.symbols[desc];
+                while tag != 0 {
+                    if tag == desc {
+                        return true;
+                    }
+                    tag = self.read(tag, 8);
+                }
+                false
+            }
+        }
+
+        fn operand_value(regs: &[u64], operand: &Operand) -> u64 {
+            match operand {
+                Operand::Reg(reg) => regs[reg.0 as usize],
+                Operand::Int(n) => *n as u64,
+                Operand::Real(x) => x.to_bits(),
+            }
+        }
+
+        fn binary(op: BinOp, ty: Ty, lhs: u64, rhs: u64) -> Result<u64, i64> {
+            if ty == Ty::F64 {
+                let (a, b) = (f64::from_bits(lhs), f64::from_bits(rhs));
+                let result = match op {
+                    BinOp::Add => a + b,
+                    BinOp::Sub => a - b,
+                    BinOp::Mul => a * b,
+                    BinOp::Div => a / b,
+                    _ => panic!("{op:?} on REAL"),
+                };
+                return Ok(result.to_bits());
+            }
+            let (a, b) = (lhs as i64, rhs as i64);
+            let result = match op {
+                BinOp::Add => a.wrapping_add(b),
+                BinOp::Sub => a.wrapping_sub(b),
+                BinOp::Mul => a.wrapping_mul(b),
+                BinOp::Div | BinOp::Mod if b == 0 => return Err(-1),
+                BinOp::Div => a.div_euclid(b) - if b < 0 && a.rem_euclid(b) != 0 { 1 } else { 0 },
+                BinOp::Mod => a - b * (a.div_euclid(b) - if b < 0 && a.rem_euclid(b) != 0 { 1 } else { 0 }),
+                BinOp::And => a & b,
+                BinOp::Or => a | b,
+                BinOp::Xor => a ^ b,
+                BinOp::Shl => a.wrapping_shl(b as u32),
+                BinOp::Sar => a.wrapping_shr(b as u32),
+                BinOp::Ror => (a as u64).rotate_right(b as u32) as i64,
+            };
+            Ok(result as u64)
+        }
+    }
+
+    use machine::Machine;
+
+    fn run(module: &ir::Module) -> Result<Machine<'_>, i64> {
+        let mut machine = Machine::new(module);
+        machine.call(&format!("{}__init", module.name), &[])?;
+        Ok(machine)
+    }
+
+    #[test]
+    fn lowers_parameters_and_records() {
+        let module = lower(r#"
+            MODULE M;
+            TYPE R = RECORD a: INTEGER; b: CHAR END;
+            VAR x: INTEGER; r: R;
+            PROCEDURE P(VAR v: R; n: INTEGER): INTEGER;
+            BEGIN v.a := n
+            RETURN v.a * 2
+            END P;
+            BEGIN x := P(r, 21)
+            END M.
+        "#);
+        let function = module.function("M_P").unwrap();
+        let by_ref: Vec<bool> = function.params.iter().map(|p| p.by_ref).collect();
+        assert_eq!(by_ref, [true, false, false]);
+        assert_eq!(module.descriptors[0].name, "M_R");
+        assert_eq!(module.descriptors[0].size, 16);
+    }
+
+    mod expressions {
+        use super::*;
+
+        #[test]
+        fn evaluates_arithmetic_with_floor_division() {
+            let module = lower(r#"
+                MODULE M;
+                VAR a, b, q, r, s: INTEGER; x: REAL;
+                BEGIN a := -7; b := 2;
+                  q := a DIV b; r := a MOD b; s := -a * b + ABS(a);
+                  x := FLT(a) / 2.0
+                END M.
+            "#);
+            let machine = run(&module).unwrap();
+            assert_eq!(machine.global("q"), -4);
+            assert_eq!(machine.global("r"), 1);
+            assert_eq!(machine.global("s"), 21);
+            assert_eq!(machine.global_real("x"), -3.5);
+        }
+
+        #[test]
+        fn short_circuits_and_and_or() {
+            let module = lower(r#"
+                MODULE M;
+                VAR calls: INTEGER; a, b: BOOLEAN;
+                PROCEDURE T(): BOOLEAN;
+                BEGIN INC(calls)
+                RETURN TRUE
+                END T;
+                BEGIN calls := 0;
+                  a := (calls > 5) & T();
+                  b := (calls = 0) OR T()
+                END M.
+            "#);
+            let machine = run(&module).unwrap();
+            assert_eq!(machine.global("calls"), 0);
+            assert_eq!(machine.global_byte("a"), 0);
+            assert_eq!(machine.global_byte("b"), 1);
+        }
+
+        #[test]
+        fn builds_and_tests_sets() {
+            let module = lower(r#"
+                MODULE M;
+                VAR s: SET; i, n: INTEGER; in, out: BOOLEAN;
+                BEGIN i := 3; n := 5;
+                  s := {1, i..n, 63} - {4};
+                  in := 5 IN s; out := 4 IN s;
+                  INCL(s, 10); EXCL(s, 1)
+                END M.
+            "#);
+            let machine = run(&module).unwrap();
+            assert_eq!(machine.global("s") as u64, (1 << 3) | (1 << 5) | (1 << 10) | (1 << 63));
+            assert_eq!(machine.global_byte("in"), 1);
+            assert_eq!(machine.global_byte("out"), 0);
+        }
+
+        #[test]
+        fn compares_strings_and_characters() {
+            let module = lower(r#"
+                MODULE M;
+                VAR s: ARRAY 8 OF CHAR; c: CHAR; lt, eq, ch: BOOLEAN;
+                BEGIN s := "abc"; c := "b";
+                  lt := s < "abd"; eq := s = "abc"; ch := (c = "b") & (s[1] = c)
+                END M.
+            "#);
+            let machine = run(&module).unwrap();
+            assert_eq!(machine.global_byte("lt"), 1);
+            assert_eq!(machine.global_byte("eq"), 1);
+            assert_eq!(machine.global_byte("ch"), 1);
+        }
+
+        #[test]
+        fn tests_dynamic_types_with_is() {
+            let module = lower(r#"
+                MODULE M;
+                TYPE Base = POINTER TO BaseDesc; BaseDesc = RECORD k: INTEGER END;
+                  Ext = POINTER TO ExtDesc; ExtDesc = RECORD (BaseDesc) e: INTEGER END;
+                VAR p: Base; q: Ext; isExt, isBase: BOOLEAN; e: INTEGER;
+                BEGIN NEW(q); q.e := 7; p := q;
+                  isExt := p IS Ext; isBase := p IS Base; e := p(Ext).e
+                END M.
+            "#);
+            let machine = run(&module).unwrap();
+            assert_eq!(machine.global_byte("isExt"), 1);
+            assert_eq!(machine.global_byte("isBase"), 1);
+            assert_eq!(machine.global("e"), 7);
+        }
+
+        #[test]
+        fn failing_type_guard_traps() {
+            let module = lower(r#"
+                MODULE M;
+                TYPE Base = POINTER TO BaseDesc; BaseDesc = RECORD END;
+                  Ext = POINTER TO ExtDesc; ExtDesc = RECORD (BaseDesc) e: INTEGER END;
+                VAR p: Base; e: INTEGER;
+                BEGIN NEW(p); e := p(Ext).e
+                END M.
+            "#);
+            assert_eq!(run(&module).err(), Some(ir::trap::TYPE_GUARD));
+        }
+    }
+
+    mod statements {
+        use super::*;
+
+        #[test]
+        fn runs_while_with_elsif_as_guarded_loop() {
+            // Euclid's algorithm as a Dijkstra loop.
+            let module = lower(r#"
+                MODULE M;
+                VAR a, b: INTEGER;
+                BEGIN a := 84; b := 36;
+                  WHILE a > b DO a := a - b
+                  ELSIF b > a DO b := b - a
+                  END
+                END M.
+            "#);
+            let machine = run(&module).unwrap();
+            assert_eq!((machine.global("a"), machine.global("b")), (12, 12));
+        }
+
+        #[test]
+        fn selects_case_branches_by_value_and_range() {
+            let module = lower(r#"
+                MODULE M;
+                CONST Ten = 10;
+                VAR i, small, large, letters: INTEGER; c: CHAR;
+                BEGIN small := 0; large := 0; letters := 0;
+                  FOR i := 0 TO 12 DO
+                    CASE i OF 0..3, 5: INC(small) | 4: small := small | 6..Ten, 11, 12: INC(large) END
+                  END;
+                  c := "q";
+                  CASE c OF "a".."p": letters := 1 | "q", "r": letters := 2 END
+                END M.
+            "#);
+            let machine = run(&module).unwrap();
+            assert_eq!(machine.global("small"), 5);
+            assert_eq!(machine.global("large"), 7);
+            assert_eq!(machine.global("letters"), 2);
+        }
+
+        #[test]
+        fn unmatched_case_traps() {
+            let module = lower("MODULE M; VAR i: INTEGER; BEGIN i := 9; CASE i OF 1: i := 0 END END M.");
+            assert_eq!(run(&module).err(), Some(ir::trap::CASE));
+        }
+
+        #[test]
+        fn counts_for_loops_with_constant_steps() {
+            let module = lower(r#"
+                MODULE M;
+                CONST Step = -3;
+                VAR i, up, down, last: INTEGER;
+                BEGIN up := 0; down := 0;
+                  FOR i := 1 TO 10 BY 2 DO up := up + i END;
+                  FOR i := 10 TO 0 BY Step DO down := down + 1; last := i END
+                END M.
+            "#);
+            let machine = run(&module).unwrap();
+            assert_eq!(machine.global("up"), 25);
+            assert_eq!(machine.global("down"), 4);
+            assert_eq!(machine.global("last"), 1);
+        }
+
+        #[test]
+        fn runs_if_chains_and_repeat() {
+            let module = lower(r#"
+                MODULE M;
+                VAR i, n, kind: INTEGER;
+                BEGIN i := 0; n := 0;
+                  REPEAT i := i + 1; n := n + i UNTIL i = 4;
+                  IF n < 5 THEN kind := 1 ELSIF n < 20 THEN kind := 2 ELSE kind := 3 END
+                END M.
+            "#);
+            let machine = run(&module).unwrap();
+            assert_eq!(machine.global("n"), 10);
+            assert_eq!(machine.global("kind"), 2);
+        }
+
+        #[test]
+        fn checks_array_bounds_and_assertions() {
+            let module = lower("MODULE M; VAR a: ARRAY 4 OF INTEGER; i: INTEGER; BEGIN i := 4; a[i] := 1 END M.");
+            assert_eq!(run(&module).err(), Some(ir::trap::INDEX));
+            let module = lower("MODULE M; VAR i: INTEGER; BEGIN i := 4; ASSERT(i < 3) END M.");
+            assert_eq!(run(&module).err(), Some(ir::trap::ASSERT));
+        }
+
+        #[test]
+        fn traps_on_nil_dereference() {
+            let prefix = "MODULE M; TYPE P = POINTER TO R; R = RECORD n: INTEGER END; Q = POINTER TO S; S = RECORD (R) END;
+                VAR p: P; q: Q; r: R; b: BOOLEAN; k: INTEGER; BEGIN";
+            for body in ["p.n := 1", "r := p^", "q := p(Q)", "b := p IS Q", "CASE p OF Q: b := TRUE END"] {
+                let module = lower(&format!("{prefix} {body} END M."));
+                assert_eq!(run(&module).err(), Some(ir::trap::NIL), "{body}");
+            }
+            let module = lower(&format!("{prefix} NEW(p); p.n := 2; r := p^; IF p IS Q THEN k := 1 ELSE k := r.n END END M."));
+            assert_eq!(run(&module).unwrap().global("k"), 2);
+        }
+
+        #[test]
+        fn traps_on_abs_and_floor_overflow() {
+            let module = lower("MODULE M; VAR i: INTEGER; BEGIN i := -9223372036854775807 - 1; i := ABS(i) END M.");
+            assert_eq!(run(&module).err(), Some(ir::trap::OVERFLOW));
+            let module = lower("MODULE M; VAR i: INTEGER; x: REAL; BEGIN x := 9.3E18; i := FLOOR(x) END M.");
+            assert_eq!(run(&module).err(), Some(ir::trap::OVERFLOW));
+            let module = lower("MODULE M; VAR i, j: INTEGER; x: REAL; BEGIN x := -9.2E18; i := FLOOR(x); j := ABS(1 - i) END M.");
+            assert_eq!(run(&module).unwrap().global("i"), -9200000000000000000);
+        }
+
+        #[test]
+        fn dispatches_type_case_on_dynamic_type() {
+            let module = lower(r#"
+                MODULE M;
+                TYPE Base = POINTER TO BaseDesc; BaseDesc = RECORD END;
+                  Ext = POINTER TO ExtDesc; ExtDesc = RECORD (BaseDesc) e: INTEGER END;
+                VAR p: Base; q: Ext; seen: INTEGER;
+                BEGIN NEW(q); q.e := 5; p := q;
+                  CASE p OF Ext: seen := p.e | Base: seen := -1 END
+                END M.
+            "#);
+            assert_eq!(run(&module).unwrap().global("seen"), 5);
+        }
+    }
+
+    mod procedures {
+        use super::*;
+
+        #[test]
+        fn passes_var_parameters_and_open_arrays() {
+            let module = lower(r#"
+                MODULE M;
+                VAR a: ARRAY 5 OF INTEGER; sum, i: INTEGER;
+                PROCEDURE Sum(v: ARRAY OF INTEGER; VAR total: INTEGER);
+                  VAR i: INTEGER;
+                BEGIN total := 0;
+                  FOR i := 0 TO LEN(v) - 1 DO total := total + v[i] END
+                END Sum;
+                BEGIN
+                  FOR i := 0 TO 4 DO a[i] := i * i END;
+                  Sum(a, sum)
+                END M.
+            "#);
+            assert_eq!(run(&module).unwrap().global("sum"), 30);
+        }
+
+        #[test]
+        fn reaches_outer_variables_through_static_links() {
+            let module = lower(r#"
+                MODULE M;
+                VAR result: INTEGER;
+                PROCEDURE Outer(n: INTEGER): INTEGER;
+                  VAR acc: INTEGER;
+                  PROCEDURE Middle(k: INTEGER);
+                    PROCEDURE Inner;
+                    BEGIN acc := acc + k * n
+                    END Inner;
+                  BEGIN Inner; Inner
+                  END Middle;
+                BEGIN acc := 1; Middle(2); Middle(3)
+                RETURN acc
+                END Outer;
+                BEGIN result := Outer(10)
+                END M.
+            "#);
+            assert_eq!(run(&module).unwrap().global("result"), 101);
+        }
+
+        #[test]
+        fn recurses_and_calls_through_procedure_variables() {
+            let module = lower(r#"
+                MODULE M;
+                TYPE F = PROCEDURE (n: INTEGER): INTEGER;
+                VAR f: F; r: INTEGER;
+                PROCEDURE Fact(n: INTEGER): INTEGER;
+                  VAR r: INTEGER;
+                BEGIN IF n <= 1 THEN r := 1 ELSE r := n * Fact(n - 1) END
+                RETURN r
+                END Fact;
+                BEGIN f := Fact; r := f(10)
+                END M.
+            "#);
+            assert_eq!(run(&module).unwrap().global("r"), 3628800);
+        }
+
+        #[test]
+        fn passes_var_records_with_their_dynamic_type() {
+            let module = lower(r#"
+                MODULE M;
+                TYPE Base = RECORD k: INTEGER END; Ext = RECORD (Base) e: INTEGER END;
+                VAR x: Ext; b: Base; isExt, other: BOOLEAN;
+                PROCEDURE Test(VAR r: Base): BOOLEAN;
+                BEGIN r.k := 3
+                RETURN r IS Ext
+                END Test;
+                BEGIN isExt := Test(x); other := Test(b)
+                END M.
+            "#);
+            let machine = run(&module).unwrap();
+            assert_eq!(machine.global_byte("isExt"), 1);
+            assert_eq!(machine.global_byte("other"), 0);
+            assert_eq!(machine.global("x"), 3);
+        }
+
+        #[test]
+        fn copies_records_and_arrays_on_assignment() {
+            let module = lower(r#"
+                MODULE M;
+                TYPE R = RECORD a, b: INTEGER END;
+                VAR r, s: R; x, y: ARRAY 3 OF CHAR;
+                BEGIN r.a := 1; r.b := 2; s := r; r.a := 5;
+                  x := "hi"; y := x; x[0] := "H"
+                END M.
+            "#);
+            let machine = run(&module).unwrap();
+            let s = machine.global_addr("s");
+            assert_eq!((machine.read(s, 8), machine.read(s + 8, 8)), (1, 2));
+            let y = machine.global_addr("y");
+            assert_eq!(&machine.memory[y as usize..y as usize + 3], b"hi\0");
+        }
+
+        #[test]
+        fn packs_and_unpacks_reals() {
+            let module = lower(r#"
+                MODULE M;
+                VAR x, y: REAL; n: INTEGER;
+                BEGIN x := 1.5; PACK(x, 3); y := 24.0; UNPK(y, n)
+                END M.
+            "#);
+            let machine = run(&module).unwrap();
+            assert_eq!(machine.global_real("x"), 12.0);
+            assert_eq!((machine.global_real("y"), machine.global("n")), (1.5, 4));
+        }
+    }
+}
@@ -1,37 +1,50 @@
 use std::str::CharIndices;
 use thiserror::Error;
-use crate::frontend::span::{Position, Span};
+use crate::frontend::intern::{Interner, Name};
+use crate::frontend::span::{Position, Span, Spanned};
 use crate::frontend::token::{Token, TokenKind};
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, PartialEq, Error)]
 pub enum LexerError {
-    #[error("Unexpected character '{ch}' at {span:?}")]
+    #[error("unexpected character '{ch}'")]
     UnexpectedCharacter {
         ch: char,
         span: Span,
     },
 
-    #[error("Unterminated string literal at {span:?}")]
+    #[error("unterminated string literal")]
     UnterminatedString {
         span: Span,
     },
 
-    #[error("Unterminated comment at {span:?}")]
+    #[error("unterminated comment")]
     UnterminatedComment {
         span: Span,
     },
 
-    #[error("Invalid number literal at {span:?}")]
+    #[error("invalid number literal")]
     InvalidNumber {
         span: Span,
     },
 
-    #[error("Unexpected end of file at {span:?}")]
+    #[error("unexpected end of file")]
     UnexpectedEof{
         span: Span,
     }
 }
 
+impl Spanned for LexerError {
+    fn span(&self) -> Span {
+        match self {
+            LexerError::UnexpectedCharacter { span, .. }
+            | LexerError::UnterminatedString { span }
+            | LexerError::UnterminatedComment { span }
+            | LexerError::InvalidNumber { span }
+            | LexerError::UnexpectedEof { span } => *span,
+        }
+    }
+}
+
 struct Cursor<'a> {
     input: &'a str,
     chars: CharIndices<'a>,
@@ -42,7 +55,12 @@ struct Cursor<'a> {
 
 impl<'a> Cursor<'a> {
     fn new(input: &'a str) -> Self {
-        let mut chars = input.char_indices();
+        Self::at(input, Position::initial())
+    }
+
+    /// A cursor at `pos`, which must be the position of a character boundary in `input`.
+    fn at(input: &'a str, pos: Position) -> Self {
+        let mut chars = input[pos.offset..].char_indices();
         let current = chars.next();
         let next = chars.next();
 
@@ -51,7 +69,7 @@ impl<'a> Cursor<'a> {
             chars,
             current,
             next,
-            pos: Position::initial(),
+            pos,
         }
     }
 }
@@ -112,30 +130,90 @@ impl<'a> Cursor<'a> {
     }
 }
 
+/// A token as the lexer produced it, for lexing a text once and parsing it again after edits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lexed {
+    /// The token, or an `Invalid` one covering the offending text where lexing failed.
+    pub token: Token,
+    pub error: Option<LexerError>,
+    /// Where the lexer stopped, and so where it carries on for the next token.
+    pub end: Position,
+}
+
 pub struct Lexer<'a> {
     cursor: Cursor<'a>,
+    /// Whether comments and whitespace are returned as tokens instead of skipped.
+    trivia: bool,
+    names: Interner,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
         Self {
             cursor: Cursor::new(input),
+            trivia: false,
+            names: Interner::new(),
+        }
+    }
+
+    /// A lexer that also returns `Comment` and `Whitespace` tokens, so that together the tokens
+    /// cover the whole input. The parser attaches the comments to the declarations and statements
+    /// around them.
+    pub fn with_trivia(input: &'a str) -> Self {
+        Self {
+            cursor: Cursor::new(input),
+            trivia: true,
+            names: Interner::new(),
+        }
+    }
+
+    /// The lexer carrying on from `position`, as one that stopped there would. The position must
+    /// be one a lexer of the same input stopped at, such as the `end` of a `Lexed`.
+    pub fn starting_at(self, position: Position) -> Self {
+        Self {
+            cursor: Cursor::at(self.cursor.input, position),
+            ..self
         }
     }
+
+    pub fn keeps_trivia(&self) -> bool {
+        self.trivia
+    }
+
+    /// The lexer returning trivia as well, whether or not it did.
+    pub(crate) fn keeping_trivia(self) -> Self {
+        Self { trivia: true, ..self }
+    }
+
+    /// Where the next token (or the whitespace before it) starts.
+    pub fn position(&self) -> Position {
+        self.cursor.position()
+    }
+
+    /// The whole input, which holds the text of trivia and invalid tokens.
+    pub fn source(&self) -> &'a str {
+        self.cursor.input
+    }
 }
 
 impl<'a> Lexer<'a> {
     pub fn next_token(&mut self) -> Result<Token, LexerError> {
-        self.skip_whitespace()?;
+        if self.trivia {
+            if let Some(token) = self.lex_trivia()? {
+                return Ok(token);
+            }
+        } else {
+            self.skip_whitespace()?;
+        }
 
         let start = self.cursor.position();
 
         match self.cursor.peek() {
-            None => Ok(Token::new(TokenKind::Eof, "", Span::new(start, start))),
+            None => Ok(Token::new(TokenKind::Eof, Span::new(start, start))),
 
             Some(c) if c.is_ascii_alphabetic() => self.lex_identifier_or_keyword(start),
             Some(c) if c.is_ascii_digit() => self.lex_digits(start),
-            Some(c) if c == '"' => self.lex_string(start),
+            Some('"') => self.lex_string(start),
             Some(c) if self.is_symbol(c) => self.lex_symbol(start),
             Some(c) =>
                 {
@@ -150,6 +228,31 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// The next token along with where the lexer stopped. Text that fails to lex gives an
+    /// `Invalid` token with the error, and the lexer carries on behind it. The token spans all the
+    /// text the lexer passed over, which may be more than the error's span.
+    pub fn next_lexed(&mut self) -> Lexed {
+        let start = self.position();
+        let (token, error) = match self.next_token() {
+            Ok(token) => (token, None),
+            Err(error) => (Token::new(TokenKind::Invalid, Span::new(start, self.position())), Some(error)),
+        };
+        Lexed { token, error, end: self.position() }
+    }
+
+    /// The tokens up to and including the end of file.
+    pub fn lex_all(mut self) -> Vec<Lexed> {
+        let mut result = vec![];
+        loop {
+            let lexed = self.next_lexed();
+            let eof = lexed.token.kind == TokenKind::Eof;
+            result.push(lexed);
+            if eof {
+                return result;
+            }
+        }
+    }
+
     fn skip_whitespace(&mut self) -> Result<(), LexerError> {
         loop {
             self.cursor.take_while(|c| c.is_ascii_whitespace());
@@ -164,6 +267,20 @@ impl<'a> Lexer<'a> {
         Ok(())
     }
 
+    /// The whitespace or comment at the cursor, if there is one.
+    fn lex_trivia(&mut self) -> Result<Option<Token>, LexerError> {
+        let start = self.cursor.position();
+        let kind = if self.starts_comment() {
+            self.skip_comment()?;
+            TokenKind::Comment
+        } else if !self.cursor.take_while(|c| c.is_ascii_whitespace()).is_empty() {
+            TokenKind::Whitespace
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(Token::new(kind, Span::new(start, self.cursor.position()))))
+    }
+
     fn starts_comment(&self) -> bool {
         self.cursor.peek() == Some('(')
             && self.cursor.peek_next() == Some('*')
@@ -221,9 +338,9 @@ impl<'a> Lexer<'a> {
             Some('H') => {
                 self.cursor.bump();
 
-                Ok(Token::new(
+                Ok(Token::with_text(
                     TokenKind::Number,
-                    self.cursor.slice_from(start),
+                    Name::new(self.cursor.slice_from(start)),
                     Span::new(start, self.cursor.position()),
                 ))
             }
@@ -231,14 +348,14 @@ impl<'a> Lexer<'a> {
             Some('X') => {
                 self.cursor.bump();
 
-                Ok(Token::new(
-                    TokenKind::String,
-                    self.cursor.slice_from(start),
+                Ok(Token::with_text(
+                    TokenKind::Char,
+                    Name::new(self.cursor.slice_from(start)),
                     Span::new(start, self.cursor.position()),
                 ))
             }
 
-            Some('.') => {
+            Some('.') if self.cursor.peek_next() != Some('.') => {
                 if saw_hex_letter {
                     return Err(LexerError::InvalidNumber {
                         span: Span::new(start, self.cursor.position()),
@@ -255,9 +372,9 @@ impl<'a> Lexer<'a> {
                     });
                 }
 
-                Ok(Token::new(
+                Ok(Token::with_text(
                     TokenKind::Number,
-                    self.cursor.slice_from(start),
+                    Name::new(self.cursor.slice_from(start)),
                     Span::new(start, self.cursor.position()),
                 ))
             }
@@ -286,9 +403,9 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        Ok(Token::new(
+        Ok(Token::with_text(
             TokenKind::Number,
-            self.cursor.slice_from(start),
+            Name::new(self.cursor.slice_from(start)),
             Span::new(start, self.cursor.position()),
         ))
     }
@@ -300,8 +417,8 @@ impl<'a> Lexer<'a> {
             if ch == '"' {
                 self.cursor.bump(); // consume closing quote
                 let span = Span::new(start, self.cursor.position());
-                let lexeme = self.cursor.slice_from(start);
-                return Ok(Token::new(TokenKind::String, lexeme, span));
+                let lexeme = Name::new(self.cursor.slice_from(start));
+                return Ok(Token::with_text(TokenKind::String, lexeme, span));
             }
             if ch.is_ascii_whitespace() && ch != ' ' && ch != '\t' {
                 break;
@@ -321,68 +438,50 @@ impl<'a> Lexer<'a> {
             });
         };
         self.cursor.bump();
-        let lexeme = self.cursor.slice_from(start);
 
-        match ch {
-            '+' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '-' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '*' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '/' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '~' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '&' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '.' =>
-                if self.cursor.peek() == Some('.') {
-                    self.cursor.bump();
-                    let lexeme = self.cursor.slice_from(start);
-                    Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position())))
-                } else {
-                    Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position())))
-                }
-            ',' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            ';' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '|' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '(' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '[' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '{' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            ':' =>
-                if self.cursor.peek() == Some('=') {
-                    self.cursor.bump();
-                    let lexeme = self.cursor.slice_from(start);
-                    Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position())))
-                } else {
-                    Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position())))
-                }
-            '^' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '=' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '#' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '<' =>
-                if self.cursor.peek() == Some('=') {
-                    self.cursor.bump();
-                    let lexeme = self.cursor.slice_from(start);
-                    Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position())))
-                } else {
-                    Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position())))
-                }
-            '>' =>
-                if self.cursor.peek() == Some('=') {
-                    self.cursor.bump();
-                    let lexeme = self.cursor.slice_from(start);
-                    Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position())))
-                } else {
-                    Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position())))
-                }
-            ')' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            ']' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            '}' => Ok(Token::new(TokenKind::OperatorOrDelimiter, lexeme, Span::new(start, self.cursor.position()))),
-            _ => Err(LexerError::UnexpectedCharacter {
+        let kind = match ch {
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '~' => TokenKind::Tilde,
+            '&' => TokenKind::Ampersand,
+            '.' => self.followed_by('.', TokenKind::DotDot, TokenKind::Dot),
+            ',' => TokenKind::Comma,
+            ';' => TokenKind::Semicolon,
+            '|' => TokenKind::Pipe,
+            '(' => TokenKind::LParen,
+            '[' => TokenKind::LBracket,
+            '{' => TokenKind::LBrace,
+            ':' => self.followed_by('=', TokenKind::Assign, TokenKind::Colon),
+            '^' => TokenKind::Caret,
+            '=' => TokenKind::Equal,
+            '#' => TokenKind::NotEqual,
+            '<' => self.followed_by('=', TokenKind::LessEqual, TokenKind::Less),
+            '>' => self.followed_by('=', TokenKind::GreaterEqual, TokenKind::Greater),
+            ')' => TokenKind::RParen,
+            ']' => TokenKind::RBracket,
+            '}' => TokenKind::RBrace,
+            _ => return Err(LexerError::UnexpectedCharacter {
                 ch,
                 span: Span::new(start, self.cursor.position()),
             }),
-        }
+        };
+
+        Ok(Token::new(kind, Span::new(start, self.cursor.position())))
+    }
 
+    /// `long` if the next character is `next`, which is consumed; `short` otherwise.
+    fn followed_by(&mut self, next: char, long: TokenKind, short: TokenKind) -> TokenKind {
+        if self.cursor.peek() == Some(next) {
+            self.cursor.bump();
+            long
+        } else {
+            short
+        }
     }
 
-    fn lex_identifier(&mut self, start: Position) -> (&str, Span) {
+    fn lex_identifier(&mut self, start: Position) -> (&'a str, Span) {
         self.cursor.bump();
         self.cursor.take_while(|c| c.is_ascii_alphanumeric() );
         let end = self.cursor.position();
@@ -393,31 +492,14 @@ impl<'a> Lexer<'a> {
     fn lex_identifier_or_keyword(&mut self, start: Position) -> Result<Token, LexerError> {
         let (lexeme, span) = self.lex_identifier(start);
 
-        let kind = Self::keyword_kind(lexeme)
-            .unwrap_or(TokenKind::Identifier);
-
-        Ok(Token::new(kind, lexeme, span))
-    }
-
-    fn keyword_kind(s: &str) -> Option<TokenKind> {
-        match s {
-            "ARRAY" | "BEGIN" | "BY" | "CASE" | "CONST" | "DIV" |
-            "DO" | "ELSE" | "ELSIF" | "END" | "FALSE" | "FOR" |
-            "IF" | "IMPORT" | "IN" | "IS" | "MOD" | "MODULE" |
-            "NIL" | "OF" | "OR" | "POINTER" | "PROCEDURE" |
-            "RECORD" | "REPEAT" | "RETURN" | "THEN" | "TO" |
-            "TRUE" | "TYPE" | "UNTIL" | "VAR" | "WHILE" => {
-                Some(TokenKind::OperatorOrDelimiter)
-            }
-            _ => None,
+        match TokenKind::keyword(lexeme) {
+            Some(kind) => Ok(Token::new(kind, span)),
+            None => Ok(Token::with_text(TokenKind::Identifier, self.names.intern(lexeme), span)),
         }
     }
 
     fn is_symbol(&self, c: char) -> bool {
-        match c {
-            '+' | '-' | '*' | '/' | '~' | '&' | '.' | ',' | ';' | '|' | '(' | '[' | '{' | ':' | '^' | '=' | '#' | '<' | '>' | ')' | ']' | '}' => true,
-            _ => false
-        }
+        matches!(c, '+' | '-' | '*' | '/' | '~' | '&' | '.' | ',' | ';' | '|' | '(' | '[' | '{' | ':' | '^' | '=' | '#' | '<' | '>' | ')' | ']' | '}')
     }
 }
 
@@ -431,9 +513,9 @@ mod tests {
 
         loop {
             let token = lexer.next_token().unwrap();
-            let kind = token.kind.clone();
+            let kind = token.kind;
 
-            result.push(kind.clone());
+            result.push(kind);
 
             if kind == TokenKind::Eof {
                 break;
@@ -449,7 +531,7 @@ mod tests {
 
         loop {
             let token = lexer.next_token().unwrap();
-            result.push(token.lexeme.to_string());
+            result.push(token.lexeme().to_string());
 
             if token.kind == TokenKind::Eof {
                 break;
@@ -466,7 +548,7 @@ mod tests {
         let token = lexer.next_token().unwrap();
 
         assert_eq!(token.kind, TokenKind::Identifier);
-        assert_eq!(token.lexeme, "foo123");
+        assert_eq!(token.lexeme(), "foo123");
     }
 
     #[test]
@@ -477,21 +559,56 @@ mod tests {
         assert_eq!(
             tokens,
             vec![
-                TokenKind::OperatorOrDelimiter,
-                TokenKind::OperatorOrDelimiter,
-                TokenKind::OperatorOrDelimiter,
-                TokenKind::OperatorOrDelimiter,
-                TokenKind::OperatorOrDelimiter,
-                TokenKind::OperatorOrDelimiter,
-                TokenKind::OperatorOrDelimiter,
-                TokenKind::OperatorOrDelimiter,
-                TokenKind::OperatorOrDelimiter,
-                TokenKind::OperatorOrDelimiter,
+                TokenKind::Module,
+                TokenKind::Begin,
+                TokenKind::End,
+                TokenKind::Var,
+                TokenKind::Procedure,
+                TokenKind::If,
+                TokenKind::Then,
+                TokenKind::Else,
+                TokenKind::While,
+                TokenKind::Do,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_each_symbol_as_its_own_kind() {
+        let tokens = kinds(".. . := : <= < # ^");
+
+        assert_eq!(
+            tokens,
+            vec![
+                TokenKind::DotDot,
+                TokenKind::Dot,
+                TokenKind::Assign,
+                TokenKind::Colon,
+                TokenKind::LessEqual,
+                TokenKind::Less,
+                TokenKind::NotEqual,
+                TokenKind::Caret,
                 TokenKind::Eof,
             ]
         );
     }
 
+    #[test]
+    fn interns_identifiers() {
+        let mut lexer = Lexer::new("count BEGIN count");
+
+        let first = lexer.next_token().unwrap();
+        let keyword = lexer.next_token().unwrap();
+        let second = lexer.next_token().unwrap();
+
+        let (Some(first), Some(second)) = (first.text, second.text) else { panic!("identifiers have names") };
+        assert!(std::ptr::eq(first.as_str(), second.as_str()));
+        assert_eq!(first, "count");
+        assert_eq!(keyword.text, None);
+        assert_eq!(keyword.lexeme(), "BEGIN");
+    }
+
     #[test]
     fn lexes_integer_number() {
         let mut lexer = Lexer::new("12345");
@@ -499,7 +616,7 @@ mod tests {
         let token = lexer.next_token().unwrap();
 
         assert_eq!(token.kind, TokenKind::Number);
-        assert_eq!(token.lexeme, "12345");
+        assert_eq!(token.lexeme(), "12345");
     }
 
     #[test]
@@ -509,17 +626,17 @@ mod tests {
         let token = lexer.next_token().unwrap();
 
         assert_eq!(token.kind, TokenKind::Number);
-        assert_eq!(token.lexeme, "12AFH");
+        assert_eq!(token.lexeme(), "12AFH");
     }
 
     #[test]
-    fn lexes_hex_string() {
+    fn lexes_character_constant() {
         let mut lexer = Lexer::new("12AFX");
 
         let token = lexer.next_token().unwrap();
 
-        assert_eq!(token.kind, TokenKind::String);
-        assert_eq!(token.lexeme, "12AFX");
+        assert_eq!(token.kind, TokenKind::Char);
+        assert_eq!(token.lexeme(), "12AFX");
     }
 
     #[test]
@@ -529,7 +646,7 @@ mod tests {
         let token = lexer.next_token().unwrap();
 
         assert_eq!(token.kind, TokenKind::Number);
-        assert_eq!(token.lexeme, "123.45");
+        assert_eq!(token.lexeme(), "123.45");
     }
 
     #[test]
@@ -539,7 +656,7 @@ mod tests {
         let token = lexer.next_token().unwrap();
 
         assert_eq!(token.kind, TokenKind::Number);
-        assert_eq!(token.lexeme, "123.45E-6");
+        assert_eq!(token.lexeme(), "123.45E-6");
     }
 
     #[test]
@@ -576,7 +693,7 @@ mod tests {
         let token = lexer.next_token().unwrap();
 
         assert_eq!(token.kind, TokenKind::String);
-        assert_eq!(token.lexeme, "\"hello\"");
+        assert_eq!(token.lexeme(), "\"hello\"");
     }
 
     #[test]
@@ -586,7 +703,7 @@ mod tests {
         let token = lexer.next_token().unwrap();
 
         assert_eq!(token.kind, TokenKind::String);
-        assert_eq!(token.lexeme, "\"\thello \"");
+        assert_eq!(token.lexeme(), "\"\thello \"");
     }
 
     #[test]
@@ -615,7 +732,7 @@ mod tests {
         let token = lexer.next_token().unwrap();
 
         assert_eq!(token.kind, TokenKind::Identifier);
-        assert_eq!(token.lexeme, "foo");
+        assert_eq!(token.lexeme(), "foo");
     }
 
     #[test]
@@ -640,6 +757,13 @@ mod tests {
         assert_eq!(result, vec!["..", ":=", "<=", ">=", ""]);
     }
 
+    #[test]
+    fn lexes_integer_range_without_blanks() {
+        let result = lexemes("1..3");
+
+        assert_eq!(result, vec!["1", "..", "3", ""]);
+    }
+
     #[test]
     fn returns_unexpected_character() {
         let mut lexer = Lexer::new("@");
@@ -729,6 +853,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn keeps_comments_and_whitespace_as_trivia() {
+        let input = "x (* a (* nested *) comment *)\n\t:= 1";
+        let mut lexer = Lexer::with_trivia(input);
+        let mut tokens = vec![];
+        loop {
+            let token = lexer.next_token().unwrap();
+            if token.kind == TokenKind::Eof {
+                break;
+            }
+            tokens.push((token.kind, token.text_in(input).to_string()));
+        }
+
+        assert_eq!(tokens, [
+            (TokenKind::Identifier, "x".to_string()),
+            (TokenKind::Whitespace, " ".to_string()),
+            (TokenKind::Comment, "(* a (* nested *) comment *)".to_string()),
+            (TokenKind::Whitespace, "\n\t".to_string()),
+            (TokenKind::Assign, ":=".to_string()),
+            (TokenKind::Whitespace, " ".to_string()),
+            (TokenKind::Number, "1".to_string()),
+        ]);
+        let text: String = tokens.iter().map(|(_, lexeme)| lexeme.as_str()).collect();
+        assert_eq!(text, input);
+        assert!(matches!(Lexer::with_trivia("(* open").next_token(), Err(LexerError::UnterminatedComment { .. })));
+    }
+
+    #[test]
+    fn resumes_where_a_lexer_stopped() {
+        let input = "a :=\n  12.5 (* c *) \"s\" $ b";
+        let tokens = Lexer::new(input).lex_all();
+
+        let resumed = Lexer::new(input).starting_at(tokens[1].end).lex_all();
+
+        assert_eq!(resumed, tokens[2..]);
+        assert_eq!(resumed[0].token.span.start, Position { offset: 7, line: 2, column: 3 });
+        assert!(matches!(resumed[2].error, Some(LexerError::UnexpectedCharacter { ch: '$', .. })));
+        assert_eq!(resumed[2].token.kind, TokenKind::Invalid);
+        assert_eq!(resumed[2].token.text_in(input), " $");
+        let invalid = Lexer::with_trivia(input).lex_all().into_iter().find(|lexed| lexed.error.is_some()).unwrap();
+        assert_eq!(invalid.token.text_in(input), "$");
+    }
+
     #[test]
     fn rejects_unterminated_comment() {
         let mut lexer = Lexer::new("(* hello)");
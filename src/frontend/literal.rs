@@ -0,0 +1,113 @@
+//! The values of integer and real literals.
+
+use std::fmt;
+
+/// The number of bits of an INTEGER that integer literals and constant expressions are checked
+/// against. The code generators work with 64-bit INTEGERs whatever the width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegerWidth {
+    Bits32,
+    #[default]
+    Bits64,
+}
+
+impl IntegerWidth {
+    pub fn bits(self) -> u32 {
+        match self {
+            IntegerWidth::Bits32 => 32,
+            IntegerWidth::Bits64 => 64,
+        }
+    }
+
+    pub fn min(self) -> i64 {
+        match self {
+            IntegerWidth::Bits32 => i32::MIN as i64,
+            IntegerWidth::Bits64 => i64::MIN,
+        }
+    }
+
+    pub fn max(self) -> i64 {
+        match self {
+            IntegerWidth::Bits32 => i32::MAX as i64,
+            IntegerWidth::Bits64 => i64::MAX,
+        }
+    }
+}
+
+impl fmt::Display for IntegerWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-bit", self.bits())
+    }
+}
+
+/// The value of an integer literal, decimal (`123`) or hexadecimal (`7BH`), or of its negation
+/// if `negated`; `None` if that does not fit an INTEGER of `width` bits.
+///
+/// A hexadecimal literal gives the bits of the INTEGER, so with 32 bits `0FFFFFFFFH` is -1. A
+/// decimal literal must not exceed MAX(INTEGER), except that a negated one may be the magnitude of
+/// MIN(INTEGER): `-2147483648` is allowed with 32 bits.
+pub fn integer(text: &str, width: IntegerWidth, negated: bool) -> Option<i64> {
+    let bits = width.bits();
+    let value = match text.strip_suffix('H') {
+        Some(hex) => {
+            let magnitude = digits(hex, 16)?;
+            if magnitude >> bits != 0 {
+                return None;
+            }
+            // Sign-extend from `bits` bits.
+            let shift = 128 - bits;
+            ((magnitude as i128) << shift) >> shift
+        }
+        None => i128::try_from(digits(text, 10)?).ok()?,
+    };
+    let value = if negated { -value } else { value };
+    (width.min() as i128..=width.max() as i128).contains(&value).then_some(value as i64)
+}
+
+/// The value of digits in `radix`, `None` if it exceeds 128 bits.
+fn digits(text: &str, radix: u32) -> Option<u128> {
+    text.chars().try_fold(0u128, |value, c| {
+        value.checked_mul(radix as u128)?.checked_add(c.to_digit(radix)? as u128)
+    })
+}
+
+/// The value of a real literal such as `1.5E10`; `None` if it is beyond the range of REAL.
+/// Exponents below the range give zero.
+pub fn real(text: &str) -> Option<f64> {
+    text.parse::<f64>().ok().filter(|value| value.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_decimal_literals_up_to_max_integer() {
+        assert_eq!(integer("2147483647", IntegerWidth::Bits32, false), Some(2147483647));
+        assert_eq!(integer("2147483648", IntegerWidth::Bits32, false), None);
+        assert_eq!(integer("2147483648", IntegerWidth::Bits32, true), Some(-2147483648));
+        assert_eq!(integer("2147483649", IntegerWidth::Bits32, true), None);
+        assert_eq!(integer("9223372036854775808", IntegerWidth::Bits64, false), None);
+        assert_eq!(integer("9223372036854775808", IntegerWidth::Bits64, true), Some(i64::MIN));
+        assert_eq!(integer("99999999999999999999999999999999999999999", IntegerWidth::Bits64, false), None);
+    }
+
+    #[test]
+    fn wraps_hexadecimal_literals_into_the_integer_bits() {
+        assert_eq!(integer("0FFFFFFFFH", IntegerWidth::Bits32, false), Some(-1));
+        assert_eq!(integer("80000000H", IntegerWidth::Bits32, false), Some(i32::MIN as i64));
+        assert_eq!(integer("0FFFFFFFFH", IntegerWidth::Bits64, false), Some(0xFFFFFFFF));
+        assert_eq!(integer("0FFFFFFFFFFFFFFFFH", IntegerWidth::Bits64, false), Some(-1));
+        assert_eq!(integer("100000000H", IntegerWidth::Bits32, false), None);
+        assert_eq!(integer("0FFFFFFFFH", IntegerWidth::Bits32, true), Some(1));
+        assert_eq!(integer("80000000H", IntegerWidth::Bits32, true), None);
+    }
+
+    #[test]
+    fn rejects_reals_beyond_the_range_of_real() {
+        assert_eq!(real("1.5E3"), Some(1500.0));
+        assert_eq!(real("1.0E308"), Some(1.0E308));
+        assert_eq!(real("1.0E309"), None);
+        assert_eq!(real("1.0E-400"), Some(0.0));
+    }
+}
@@ -0,0 +1,561 @@
+//! Deriving the AST from the concrete syntax tree. The parser only makes a node once its construct
+//! is complete, so every node of a kind has the parts that kind calls for; positions come from the
+//! offsets of its tokens and values from their text.
+
+use std::collections::BTreeMap;
+use crate::frontend::ast::{BinaryOperation, Case, Comment, Comments, ConstDeclaration, Declarations, Designator, Element, ElsIf, Expression, FPSection, FieldList, FormalParameters, FormalType, Identifier, IdentifierDef, Import, Label, LabelValue, Module, ProcedureBody, ProcedureDeclaration, ProcedureHeader, QualifiedIdentifier, Selector, Statement, StatementSequence, Type, TypeDeclaration, UnaryOperation, VarDeclaration};
+use crate::frontend::intern::Name;
+use crate::frontend::literal::{self, IntegerWidth};
+use crate::frontend::span::{Position, Span, Spanned};
+use crate::frontend::syntax::{NodeKind, SyntaxNode, SyntaxToken};
+use crate::frontend::token::TokenKind;
+
+/// The module of the syntax tree `root`, with integer literals taken as INTEGERs of `width` bits,
+/// and the comments attached to its declarations and statements if `comments`.
+pub fn module(root: &SyntaxNode, width: IntegerWidth, comments: bool) -> Module {
+    let tokens = root.descendant_tokens();
+    let text: String = tokens.iter().map(SyntaxToken::text).collect();
+    let mut lines = vec![0];
+    lines.extend(text.match_indices('\n').map(|(offset, _)| offset + 1));
+    let mut lowering = Lowering {
+        comments: comments.then(BTreeMap::new),
+        tokens: vec![],
+        text,
+        lines,
+        width,
+    };
+    for token in tokens {
+        if token.kind() == TokenKind::Comment {
+            let span = lowering.token_span(&token);
+            if let Some(comments) = &mut lowering.comments {
+                comments.insert(span.start.offset, Comment { text: token.text().to_string(), span });
+            }
+        } else if !token.kind().is_trivia() {
+            lowering.tokens.push(token);
+        }
+    }
+    lowering.module(root)
+}
+
+struct Lowering {
+    text: String,
+    /// The offsets at which lines start.
+    lines: Vec<usize>,
+    width: IntegerWidth,
+    /// The tokens of the tree other than trivia.
+    tokens: Vec<SyntaxToken>,
+    /// The comments not attached yet, by offset; `None` if comments are not attached.
+    comments: Option<BTreeMap<usize, Comment>>,
+}
+
+/// The first child of `kind`, which a node of its parent's kind has.
+fn child(node: &SyntaxNode, kind: NodeKind) -> SyntaxNode {
+    node.child(kind).unwrap_or_else(|| panic!("{node:?} has no {kind:?}"))
+}
+
+/// The first token of `kind` among the children of `node`, which a node of its kind has.
+fn token(node: &SyntaxNode, kind: TokenKind) -> SyntaxToken {
+    node.token(kind).unwrap_or_else(|| panic!("{node:?} has no {kind:?}"))
+}
+
+fn children(node: &SyntaxNode, kind: NodeKind) -> impl Iterator<Item = SyntaxNode> + '_ {
+    node.children().filter(move |child| child.kind() == kind)
+}
+
+fn is_statement(kind: NodeKind) -> bool {
+    matches!(
+        kind,
+        NodeKind::Assignment | NodeKind::ProcedureCall | NodeKind::IfStatement | NodeKind::CaseStatement
+            | NodeKind::WhileStatement | NodeKind::RepeatStatement | NodeKind::ForStatement | NodeKind::Error
+    )
+}
+
+fn is_expression(kind: NodeKind) -> bool {
+    matches!(
+        kind,
+        NodeKind::Literal | NodeKind::Set | NodeKind::DesignatorExpression | NodeKind::Parenthesized
+            | NodeKind::Unary | NodeKind::Binary | NodeKind::Error
+    )
+}
+
+impl Lowering {
+    // --------------------------- POSITIONS ---------------------------
+    fn position(&self, offset: usize) -> Position {
+        let line = self.lines.partition_point(|&start| start <= offset);
+        let column = self.text[self.lines[line - 1]..offset].chars().count() + 1;
+        Position { offset, line, column }
+    }
+
+    fn token_span(&self, token: &SyntaxToken) -> Span {
+        let range = token.text_range();
+        Span::new(self.position(range.start), self.position(range.end))
+    }
+
+    /// The number of the first token at or after `offset`; the number of tokens if there is none.
+    fn token_at(&self, offset: usize) -> usize {
+        self.tokens.partition_point(|token| token.text_range().start < offset)
+    }
+
+    /// From the first token of `node` to its last, or empty where it starts if it has none.
+    fn span(&self, node: &SyntaxNode) -> Span {
+        let range = node.text_range();
+        let first = self.token_at(range.start);
+        let end = self.token_at(range.end);
+        if first == end {
+            let start = self.position(range.start);
+            return Span::new(start, start);
+        }
+        Span::new(self.position(self.tokens[first].text_range().start), self.position(self.tokens[end - 1].text_range().end))
+    }
+
+    fn start(&self, node: &SyntaxNode) -> Position {
+        self.position(node.text_range().start)
+    }
+
+    // --------------------------- COMMENTS ---------------------------
+    /// The comments between the token before `node`, a declaration or statement, and its first.
+    fn leading_comments(&mut self, node: &SyntaxNode) -> Vec<Comment> {
+        let first = self.token_at(node.text_range().start);
+        let from = first.checked_sub(1).map_or(0, |previous| self.tokens[previous].text_range().end);
+        let to = self.tokens.get(first).map_or(self.text.len(), |token| token.text_range().start);
+        let Some(comments) = &mut self.comments else {
+            return vec![];
+        };
+        let offsets: Vec<usize> = comments.range(from..to).map(|(&offset, _)| offset).collect();
+        offsets.iter().filter_map(|offset| comments.remove(offset)).collect()
+    }
+
+    /// `leading` and the comments after `node` on the line of the token before token number
+    /// `next`, the first after the declaration or statement at `node`.
+    fn comments(&mut self, node: Span, next: usize, leading: Vec<Comment>) -> Comments {
+        let line = next.checked_sub(1).map_or(1, |previous| self.position(self.tokens[previous].text_range().end).line);
+        let to = self.tokens.get(next).map_or(self.text.len(), |token| token.text_range().start);
+        let Some(comments) = &mut self.comments else {
+            return Comments { leading, trailing: vec![] };
+        };
+        let offsets: Vec<usize> = comments
+            .range(node.end.offset..to.max(node.end.offset))
+            .filter(|(_, comment)| comment.span.start.line == line)
+            .map(|(&offset, _)| offset)
+            .collect();
+        let trailing = offsets.iter().filter_map(|offset| comments.remove(offset)).collect();
+        Comments { leading, trailing }
+    }
+
+    // --------------------------- MODULES ---------------------------
+    fn module(mut self, root: &SyntaxNode) -> Module {
+        let start = self.position(self.tokens.first().map_or(self.text.len(), |token| token.text_range().start));
+        let name = match root.child(NodeKind::ModuleHeading) {
+            Some(heading) => self.ident(&token(&heading, TokenKind::Identifier)),
+            None => Identifier { text: Name::new(""), span: Span::new(start, start) },
+        };
+        let imports = root.child(NodeKind::ImportList)
+            .map(|list| children(&list, NodeKind::Import).map(|import| self.import(&import)).collect())
+            .unwrap_or_default();
+        let declarations = self.declarations(&child(root, NodeKind::Declarations));
+        let stmts = root.child(NodeKind::StatementSequence).map(|sequence| self.statement_sequence(&sequence));
+        let (end_name, end) = match root.child(NodeKind::ModuleEnd) {
+            Some(end) => (self.ident(&token(&end, TokenKind::Identifier)), self.token_span(&token(&end, TokenKind::Dot)).end),
+            None => {
+                let end = self.tokens.last().map_or(Position::initial(), |token| self.token_span(token).end);
+                (name.clone(), end)
+            }
+        };
+
+        Module {
+            name,
+            imports,
+            declarations,
+            stmts,
+            end_name,
+            span: Span::new(start, end),
+        }
+    }
+
+    /// `Module` or `Alias := Module`.
+    fn import(&self, node: &SyntaxNode) -> Import {
+        let mut names = node.tokens().filter(|token| token.kind() == TokenKind::Identifier).map(|token| self.ident(&token));
+        let first = names.next().expect("an import names a module");
+        match names.next() {
+            Some(module) => Import { module, alias: Some(first), span: self.span(node) },
+            None => Import { span: first.span, module: first, alias: None },
+        }
+    }
+
+    // --------------------------- DECLARATIONS ---------------------------
+    fn declarations(&mut self, node: &SyntaxNode) -> Declarations {
+        let const_declarations = self.section(node, NodeKind::ConstSection, NodeKind::ConstDeclaration, Self::const_declaration);
+        let type_declarations = self.section(node, NodeKind::TypeSection, NodeKind::TypeDeclaration, Self::type_declaration);
+        let var_declarations = self.section(node, NodeKind::VarSection, NodeKind::VarDeclaration, Self::var_declaration);
+        let procedure_declarations = children(node, NodeKind::ProcedureDeclaration)
+            .map(|procedure| self.procedure_declaration(&procedure))
+            .collect();
+        Declarations { const_declarations, type_declarations, var_declarations, procedure_declarations }
+    }
+
+    /// The declarations of `kind` in the section of `section` kind.
+    fn section<T>(
+        &mut self,
+        declarations: &SyntaxNode,
+        section: NodeKind,
+        kind: NodeKind,
+        lower: fn(&mut Self, &SyntaxNode) -> T,
+    ) -> Vec<T> {
+        let Some(section) = declarations.child(section) else {
+            return vec![];
+        };
+        children(&section, kind).map(|node| lower(self, &node)).collect()
+    }
+
+    /// The comments of the declaration at `node`.
+    fn declaration_comments(&mut self, node: &SyntaxNode) -> Comments {
+        let leading = self.leading_comments(node);
+        let next = self.token_at(node.text_range().end);
+        self.comments(self.span(node), next, leading)
+    }
+
+    fn const_declaration(&mut self, node: &SyntaxNode) -> ConstDeclaration {
+        let comments = self.declaration_comments(node);
+        let mut parts = node.children();
+        let ident = self.identdef(&parts.next().expect("a constant has a name"));
+        let value = self.expression(&parts.next().expect("a constant has a value"));
+        ConstDeclaration { ident, value, comments }
+    }
+
+    fn type_declaration(&mut self, node: &SyntaxNode) -> TypeDeclaration {
+        let comments = self.declaration_comments(node);
+        let mut parts = node.children();
+        let ident = self.identdef(&parts.next().expect("a type declaration has a name"));
+        let ty = self.ty(&parts.next().expect("a type declaration has a type"));
+        TypeDeclaration { ident, ty, comments }
+    }
+
+    fn var_declaration(&mut self, node: &SyntaxNode) -> VarDeclaration {
+        let comments = self.declaration_comments(node);
+        let variables = children(node, NodeKind::IdentDef).map(|ident| self.identdef(&ident)).collect();
+        let ty = node.children().find(|child| child.kind() != NodeKind::IdentDef).expect("variables have a type");
+        VarDeclaration { variables, ty: self.ty(&ty), comments }
+    }
+
+    fn procedure_declaration(&mut self, node: &SyntaxNode) -> ProcedureDeclaration {
+        let leading = self.leading_comments(node);
+        let header = self.procedure_heading(&child(node, NodeKind::ProcedureHeading));
+        let body = self.procedure_body(&child(node, NodeKind::ProcedureBody));
+        let name = self.ident(&token(node, TokenKind::Identifier));
+        let span = Span::new(header.span.start, name.span.end);
+        let next = self.token_at(node.text_range().end);
+        let comments = self.comments(span, next, leading);
+        ProcedureDeclaration { header, body, name, span, comments }
+    }
+
+    fn procedure_heading(&mut self, node: &SyntaxNode) -> ProcedureHeader {
+        let name = self.identdef(&child(node, NodeKind::IdentDef));
+        let params = node.child(NodeKind::FormalParameters).map(|params| self.formal_parameters(&params));
+        ProcedureHeader { name, params, span: self.span(node) }
+    }
+
+    fn procedure_body(&mut self, node: &SyntaxNode) -> ProcedureBody {
+        let declarations = self.declarations(&child(node, NodeKind::Declarations));
+        let stmts = node.child(NodeKind::StatementSequence).map(|sequence| self.statement_sequence(&sequence));
+        let ret = node.children().find(|child| is_expression(child.kind())).map(|ret| self.expression(&ret));
+        let end = self.token_span(&token(node, TokenKind::End)).end;
+        ProcedureBody { declarations, stmts, ret, span: Span::new(self.start(node), end) }
+    }
+
+    fn formal_parameters(&mut self, node: &SyntaxNode) -> FormalParameters {
+        let sections = children(node, NodeKind::FPSection).map(|section| self.fp_section(&section)).collect();
+        let return_type = node.child(NodeKind::QualIdent).map(|name| self.qualident(&name));
+        FormalParameters { sections, return_type, span: self.span(node) }
+    }
+
+    fn fp_section(&mut self, node: &SyntaxNode) -> FPSection {
+        let by_ref = node.token(TokenKind::Var).is_some();
+        let names = node.tokens().filter(|token| token.kind() == TokenKind::Identifier).map(|name| self.ident(&name)).collect();
+        let ty = self.formal_type(&child(node, NodeKind::FormalType));
+        FPSection { by_ref, names, ty, span: self.span(node) }
+    }
+
+    fn formal_type(&mut self, node: &SyntaxNode) -> FormalType {
+        let open_arrays = node.tokens().filter(|token| token.kind() == TokenKind::Array).count();
+        let base = self.qualident(&child(node, NodeKind::QualIdent));
+        FormalType { open_arrays, base, span: self.span(node) }
+    }
+
+    // --------------------------- TYPES ---------------------------
+    fn ty(&mut self, node: &SyntaxNode) -> Type {
+        let span = self.span(node);
+        match node.kind() {
+            NodeKind::NamedType => Type::Named { name: self.qualident(&child(node, NodeKind::QualIdent)) },
+            NodeKind::ArrayType => {
+                let mut parts: Vec<SyntaxNode> = node.children().collect();
+                let element = parts.pop().expect("an array type has an element type");
+                let lengths = parts.iter().map(|length| self.expression(length)).collect();
+                Type::Array { lengths, element: Box::new(self.ty(&element)), span }
+            }
+            NodeKind::RecordType => {
+                let base = node.child(NodeKind::BaseType).map(|base| self.qualident(&child(&base, NodeKind::QualIdent)));
+                let field_lists = children(node, NodeKind::FieldList).map(|fields| self.field_list(&fields)).collect();
+                Type::Record { base, field_lists, span }
+            }
+            NodeKind::PointerType => {
+                let pointee = node.children().last().expect("a pointer type has a pointee");
+                Type::Pointer { pointee: Box::new(self.ty(&pointee)), span }
+            }
+            NodeKind::ProcedureType => {
+                let params = node.child(NodeKind::FormalParameters).map(|params| self.formal_parameters(&params));
+                Type::Procedure { params, span }
+            }
+            NodeKind::Error => Type::Error { span },
+            kind => unreachable!("{kind:?} is not a type"),
+        }
+    }
+
+    fn field_list(&mut self, node: &SyntaxNode) -> FieldList {
+        let fields = children(node, NodeKind::IdentDef).map(|field| self.identdef(&field)).collect();
+        let ty = node.children().find(|child| child.kind() != NodeKind::IdentDef).expect("fields have a type");
+        FieldList { fields, ty: self.ty(&ty) }
+    }
+
+    // --------------------------- STATEMENTS ---------------------------
+    /// The statements of `node`, each with its comments, which take in those up to the next
+    /// statement, or up to the token after the sequence for the last.
+    fn statement_sequence(&mut self, node: &SyntaxNode) -> StatementSequence {
+        let nodes: Vec<SyntaxNode> = node.children().filter(|child| is_statement(child.kind())).collect();
+        let mut statements = vec![];
+        for (number, statement) in nodes.iter().enumerate() {
+            let leading = self.leading_comments(statement);
+            let next = match nodes.get(number + 1) {
+                Some(next) => self.token_at(next.text_range().start),
+                None => self.token_at(node.text_range().end),
+            };
+            let comments = self.comments(self.span(statement), next, leading);
+            statements.push(self.statement(statement, comments));
+        }
+        let end = statements.last().expect("a statement sequence has a statement").span().end;
+        StatementSequence { statements, span: Span::new(self.start(node), end) }
+    }
+
+    fn statement(&mut self, node: &SyntaxNode, comments: Comments) -> Statement {
+        let span = self.span(node);
+        let mut parts = node.children();
+        match node.kind() {
+            NodeKind::Assignment => {
+                let target = self.designator(&parts.next().expect("an assignment has a target"));
+                let value = self.expression(&parts.next().expect("an assignment has a value"));
+                Statement::Assign { target, value, span, comments }
+            }
+            NodeKind::ProcedureCall => {
+                let callee = self.designator(&child(node, NodeKind::Designator));
+                let parameters = node.child(NodeKind::ActualParameters).map(|parameters| self.actual_parameters(&parameters));
+                Statement::Call { callee, parameters, span, comments }
+            }
+            NodeKind::IfStatement => {
+                let cond = self.expression(&parts.next().expect("an IF has a condition"));
+                let mut sequences = children(node, NodeKind::StatementSequence);
+                let stmts = self.statement_sequence(&sequences.next().expect("an IF has statements"));
+                let elsif_branches = children(node, NodeKind::ElsIf).map(|branch| self.elsif(&branch)).collect();
+                let else_branch = sequences.next().map(|sequence| self.statement_sequence(&sequence));
+                Statement::If { cond, stmts, elsif_branches, else_branch, span, comments }
+            }
+            NodeKind::CaseStatement => {
+                let expr = self.expression(&parts.next().expect("a CASE has an expression"));
+                let branches = children(node, NodeKind::Case).map(|branch| self.case(&branch)).collect();
+                Statement::Case { expr, branches, span, comments }
+            }
+            NodeKind::WhileStatement => {
+                let cond = self.expression(&parts.next().expect("a WHILE has a condition"));
+                let stmts = self.statement_sequence(&child(node, NodeKind::StatementSequence));
+                let elsif_branches = children(node, NodeKind::ElsIf).map(|branch| self.elsif(&branch)).collect();
+                Statement::While { cond, stmts, elsif_branches, span, comments }
+            }
+            NodeKind::RepeatStatement => {
+                let stmts = self.statement_sequence(&parts.next().expect("a REPEAT has statements"));
+                let cond = self.expression(&parts.next().expect("a REPEAT has a condition"));
+                Statement::Repeat { stmts, cond, span, comments }
+            }
+            NodeKind::ForStatement => {
+                let var = self.ident(&token(node, TokenKind::Identifier));
+                let low = self.expression(&parts.next().expect("a FOR has a start value"));
+                let high = self.expression(&parts.next().expect("a FOR has an end value"));
+                let by = node.token(TokenKind::By).map(|_| Box::new(self.expression(&parts.next().expect("a FOR with BY has a step"))));
+                let stmts = self.statement_sequence(&child(node, NodeKind::StatementSequence));
+                Statement::For { var, low, high, by, stmts, span, comments }
+            }
+            NodeKind::Error => Statement::Error { span, comments },
+            kind => unreachable!("{kind:?} is not a statement"),
+        }
+    }
+
+    fn elsif(&mut self, node: &SyntaxNode) -> ElsIf {
+        let cond = self.expression(&node.children().next().expect("an ELSIF has a condition"));
+        let stmts = self.statement_sequence(&child(node, NodeKind::StatementSequence));
+        let span = Span::new(self.start(node), stmts.span.end);
+        ElsIf { cond, stmts, span }
+    }
+
+    fn case(&mut self, node: &SyntaxNode) -> Case {
+        let label_list = children(node, NodeKind::Label).map(|label| self.label(&label)).collect();
+        let statements = self.statement_sequence(&child(node, NodeKind::StatementSequence));
+        let span = Span::new(self.start(node), statements.span.end);
+        Case { label_list, statements, span }
+    }
+
+    fn label(&self, node: &SyntaxNode) -> Label {
+        let mut values = node.children().map(|value| self.label_value(&value));
+        let value = values.next().expect("a label has a value");
+        match values.next() {
+            Some(high) => Label::Range { low: value, high },
+            None => Label::Single { value },
+        }
+    }
+
+    fn label_value(&self, node: &SyntaxNode) -> LabelValue {
+        if node.kind() == NodeKind::QualIdent {
+            return LabelValue::QualifiedIdentifier(self.qualident(node));
+        }
+        let span = self.span(node);
+        let literal = node.tokens().find(|token| !token.kind().is_trivia()).expect("a literal has a token");
+        match literal.kind() {
+            TokenKind::Number => LabelValue::Integer { value: self.integer(literal.text(), false), span },
+            TokenKind::String => LabelValue::String { value: Self::string(literal.text()), span },
+            TokenKind::Char => LabelValue::Char { value: Self::char(literal.text()), span },
+            kind => unreachable!("{kind:?} is not a case label"),
+        }
+    }
+
+    // --------------------------- EXPRESSIONS ---------------------------
+    fn expression(&self, node: &SyntaxNode) -> Expression {
+        let span = self.span(node);
+        let mut parts = node.children();
+        match node.kind() {
+            NodeKind::Literal => self.literal(node, span),
+            NodeKind::Set => {
+                let elements = children(node, NodeKind::Element).map(|element| self.element(&element)).collect();
+                Expression::Set { elements, span }
+            }
+            NodeKind::DesignatorExpression => {
+                let designator = self.designator(&child(node, NodeKind::Designator));
+                let actual_parameters = node.child(NodeKind::ActualParameters).map(|parameters| self.actual_parameters(&parameters));
+                Expression::Designator { designator, actual_parameters, span }
+            }
+            NodeKind::Parenthesized => self.expression(&parts.next().expect("parentheses hold an expression")),
+            NodeKind::Unary => {
+                let op = match node.tokens().find(|token| !token.kind().is_trivia()).map(|token| token.kind()) {
+                    Some(TokenKind::Plus) => UnaryOperation::Plus,
+                    Some(TokenKind::Minus) => UnaryOperation::Minus,
+                    _ => UnaryOperation::Not,
+                };
+                let operand = self.expression(&parts.next().expect("a unary operation has an operand"));
+                Expression::Unary { op, operand: Box::new(operand), span }
+            }
+            NodeKind::Binary => {
+                let operator = node.tokens().find(|token| !token.kind().is_trivia()).expect("a binary operation has an operator");
+                let lhs = self.expression(&parts.next().expect("a binary operation has two operands"));
+                let rhs = self.expression(&parts.next().expect("a binary operation has two operands"));
+                Expression::Binary { op: binary_operation(operator.kind()), lhs: Box::new(lhs), rhs: Box::new(rhs), span }
+            }
+            NodeKind::Error => Expression::Error { span },
+            kind => unreachable!("{kind:?} is not an expression"),
+        }
+    }
+
+    fn literal(&self, node: &SyntaxNode, span: Span) -> Expression {
+        let mut tokens = node.tokens().filter(|token| !token.kind().is_trivia());
+        let first = tokens.next().expect("a literal has a token");
+        match first.kind() {
+            TokenKind::Minus => {
+                let number = tokens.next().expect("a negated literal has a number");
+                Expression::Int { value: self.integer(number.text(), true), span }
+            }
+            TokenKind::Number if first.text().contains('.') => {
+                let value = literal::real(first.text()).expect("the parser checks the range of reals");
+                Expression::Real { value, span }
+            }
+            TokenKind::Number => Expression::Int { value: self.integer(first.text(), false), span },
+            TokenKind::String => Expression::String { value: Self::string(first.text()), span },
+            TokenKind::Char => Expression::Char { value: Self::char(first.text()), span },
+            TokenKind::Nil => Expression::Nil { span },
+            TokenKind::True => Expression::True { span },
+            TokenKind::False => Expression::False { span },
+            kind => unreachable!("{kind:?} is not a literal"),
+        }
+    }
+
+    fn integer(&self, text: &str, negated: bool) -> i64 {
+        literal::integer(text, self.width, negated).expect("the parser checks the range of integers")
+    }
+
+    fn string(text: &str) -> String {
+        text.strip_prefix('"').unwrap().strip_suffix('"').unwrap().to_string()
+    }
+
+    fn char(text: &str) -> u8 {
+        u8::from_str_radix(text.strip_suffix('X').unwrap(), 16).expect("the parser checks character codes")
+    }
+
+    fn element(&self, node: &SyntaxNode) -> Element {
+        let mut parts = node.children();
+        let first = self.expression(&parts.next().expect("an element has a value"));
+        let second = parts.next().map(|second| self.expression(&second));
+        Element { first, second, span: self.span(node) }
+    }
+
+    fn actual_parameters(&self, node: &SyntaxNode) -> Vec<Expression> {
+        node.children().map(|parameter| self.expression(&parameter)).collect()
+    }
+
+    // --------------------------- DESIGNATORS, SELECTORS, IDENTIFIERS ---------------------------
+    fn designator(&self, node: &SyntaxNode) -> Designator {
+        let mut parts = node.children();
+        let head = self.qualident(&parts.next().expect("a designator has a name"));
+        let selectors = parts.map(|selector| self.selector(&selector)).collect();
+        Designator { head, selectors, span: self.span(node) }
+    }
+
+    fn selector(&self, node: &SyntaxNode) -> Selector {
+        let span = self.span(node);
+        match node.kind() {
+            NodeKind::FieldSelector => Selector::Field(self.ident(&token(node, TokenKind::Identifier))),
+            NodeKind::IndexSelector => Selector::Index(node.children().map(|index| self.expression(&index)).collect(), span),
+            NodeKind::DerefSelector => Selector::Deref(span),
+            NodeKind::TypeGuardSelector => Selector::TypeGuard(self.qualident(&child(node, NodeKind::QualIdent)), span),
+            kind => unreachable!("{kind:?} is not a selector"),
+        }
+    }
+
+    fn qualident(&self, node: &SyntaxNode) -> QualifiedIdentifier {
+        let parts = node.tokens().filter(|token| token.kind() == TokenKind::Identifier).map(|part| self.ident(&part)).collect();
+        QualifiedIdentifier { parts }
+    }
+
+    fn identdef(&self, node: &SyntaxNode) -> IdentifierDef {
+        let ident = self.ident(&token(node, TokenKind::Identifier));
+        IdentifierDef { ident, exported: node.token(TokenKind::Star).is_some(), span: self.span(node) }
+    }
+
+    fn ident(&self, token: &SyntaxToken) -> Identifier {
+        Identifier { text: token.name().expect("identifiers have a name").clone(), span: self.token_span(token) }
+    }
+}
+
+fn binary_operation(kind: TokenKind) -> BinaryOperation {
+    match kind {
+        TokenKind::Equal => BinaryOperation::Eq,
+        TokenKind::NotEqual => BinaryOperation::Neq,
+        TokenKind::Less => BinaryOperation::Lt,
+        TokenKind::LessEqual => BinaryOperation::Le,
+        TokenKind::Greater => BinaryOperation::Gt,
+        TokenKind::GreaterEqual => BinaryOperation::Ge,
+        TokenKind::Star => BinaryOperation::Multiplication,
+        TokenKind::Slash => BinaryOperation::Division,
+        TokenKind::Plus => BinaryOperation::Addition,
+        TokenKind::Minus => BinaryOperation::Subtraction,
+        TokenKind::Mod => BinaryOperation::Mod,
+        TokenKind::Div => BinaryOperation::Div,
+        TokenKind::Ampersand => BinaryOperation::And,
+        TokenKind::Or => BinaryOperation::Or,
+        TokenKind::In => BinaryOperation::In,
+        TokenKind::Is => BinaryOperation::Is,
+        kind => unreachable!("{kind:?} is not a binary operator"),
+    }
+}
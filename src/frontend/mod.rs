@@ -1,7 +1,19 @@
-mod span;
-mod token;
-mod lexer;
-mod parser;
-mod ast;
-mod analysis;
-mod ir_generator;
\ No newline at end of file
+pub mod span;
+pub mod intern;
+pub mod token;
+pub mod lexer;
+pub mod literal;
+pub mod syntax;
+pub mod parser;
+pub mod lower;
+pub mod incremental;
+pub mod ast;
+pub mod visit;
+pub mod printer;
+pub mod types;
+pub mod symbols;
+pub mod symbol_file;
+pub mod const_eval;
+pub mod analysis;
+pub mod ir_generator;
+pub mod diagnostics;
@@ -1,42 +1,101 @@
-use crate::frontend::ast::{BinaryOperation, Case, ConstDeclaration, Declarations, Designator, Element, ElsIf, Expression, FPSection, FieldList, FormalParameters, FormalType, Identifier, IdentifierDef, Import, Label, LabelValue, Module, ProcedureBody, ProcedureDeclaration, ProcedureHeader, QualifiedIdentifier, Selector, Statement, StatementSequence, Type, TypeDeclaration, UnaryOperation, VarDeclaration};
-use crate::frontend::lexer::{Lexer, LexerError};
-use crate::frontend::span::{Span, Spanned};
+use crate::frontend::ast::Module;
+use crate::frontend::incremental::Reuse;
+use crate::frontend::lexer::{Lexed, Lexer, LexerError};
+use crate::frontend::literal::{self, IntegerWidth};
+use crate::frontend::lower;
+use crate::frontend::span::Position;
+use crate::frontend::syntax::{Builder, Checkpoint, NodeKind, SyntaxNode};
 use crate::frontend::token::{Token, TokenKind};
 use std::collections::VecDeque;
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+#[derive(Debug, PartialEq, Error)]
 pub enum ParserError {
-    #[error("Unexpected token: '{token:?}'")]
-    UnexpectedToken { token: Token },
-
-    #[error("Unexpected end of input")]
-    UnexpectedEof,
-
-    #[error("Invalid label value: '{token:?}'")]
+    #[error("expected {}{}, found {found}", one_of(expected), context.map(|c| format!(" {c}")).unwrap_or_default())]
+    UnexpectedToken {
+        /// The names of the tokens or constructs that could have come next.
+        expected: Vec<&'static str>,
+        /// Where in the construct being parsed, such as "after IF condition".
+        context: Option<&'static str>,
+        found: Token,
+    },
+
+    #[error("{token} is not a valid case label")]
     InvalidLabelValue { token: Token },
 
     #[error(transparent)]
     Lexer(#[from] LexerError),
 
-    #[error(transparent)]
-    InvalidIntegerNumber(#[from] std::num::ParseIntError),
+    #[error("integer literal {} does not fit a {width} INTEGER", token.lexeme())]
+    IntegerOutOfRange { token: Token, width: IntegerWidth },
 
-    #[error(transparent)]
-    InvalidRealNumber(#[from] std::num::ParseFloatError),
+    #[error("real literal {} is beyond the range of REAL", token.lexeme())]
+    RealOutOfRange { token: Token },
+
+    #[error("character code {} is above 0FFX", token.lexeme())]
+    InvalidCharacter { token: Token },
+}
+
+/// Joins names as "A", "A or B", "A, B or C".
+pub(crate) fn one_of(names: &[&str]) -> String {
+    match names {
+        [] => String::new(),
+        [name] => name.to_string(),
+        [init @ .., last] => format!("{} or {last}", init.join(", ")),
+    }
 }
 
 pub struct TokenStream<'a> {
-    lexer: Lexer<'a>,
+    source: Source<'a>,
+    /// The text the tokens were lexed from.
+    text: &'a str,
+    /// How many tokens were read from the source, trivia included.
+    read: usize,
     current: Token,
-    lookahead: VecDeque<Token>,
+    /// The number of the current token among those read.
+    index: usize,
+    /// The comments and whitespace between the previous token and the current one.
+    trivia: Vec<Token>,
+    lookahead: VecDeque<Lookahead>,
+}
+
+enum Source<'a> {
+    Lexer(Lexer<'a>),
+    /// Tokens lexed beforehand. The last one is the end of file, which is read again at will.
+    Tokens(&'a [Lexed]),
+}
+
+/// A token read ahead of the current one, with the trivia before it. Where the lexer failed, the
+/// token is an `Invalid` one covering the offending text, kept with the error for `advance` to
+/// report once it gets there.
+struct Lookahead {
+    trivia: Vec<Token>,
+    token: Token,
+    error: Option<LexerError>,
+    index: usize,
 }
 
 impl<'a> TokenStream<'a> {
+    /// A stream over the tokens of `lexer`, which is made to return trivia as well.
     pub fn new(lexer: Lexer<'a>) -> Self {
+        let text = lexer.source();
+        Self::with_source(Source::Lexer(lexer.keeping_trivia()), text)
+    }
+
+    /// A stream over tokens lexed from `text` beforehand by a lexer that keeps trivia, ending with
+    /// the end of file.
+    pub fn from_tokens(tokens: &'a [Lexed], text: &'a str) -> Self {
+        Self::with_source(Source::Tokens(tokens), text)
+    }
+
+    fn with_source(source: Source<'a>, text: &'a str) -> Self {
         Self {
-            lexer,
+            source,
+            text,
+            read: 0,
             current: Token::invalid(),
+            index: 0,
+            trivia: vec![],
             lookahead: VecDeque::new(),
         }
     }
@@ -45,926 +104,1039 @@ impl<'a> TokenStream<'a> {
         &self.current
     }
 
+    /// The text the tokens were lexed from, which holds the text of trivia and invalid tokens.
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    /// The number of the current token, counting from 0 and counting trivia.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The comments and whitespace before the current token.
+    pub fn trivia(&self) -> &[Token] {
+        &self.trivia
+    }
+
+    /// The `n` tokens after the current one. Text that fails to lex shows up as an `Invalid`
+    /// token, and its error is reported when `advance` reaches it.
     pub fn peek_n(&mut self, n: usize) -> Vec<&Token> {
         while self.lookahead.len() < n {
-            match self.lexer.next_token() {
-                Ok(token) => self.lookahead.push_back(token),
-                Err(_) => break,
-            }
+            let lookahead = self.read();
+            self.lookahead.push_back(lookahead);
         }
 
-        self.lookahead.iter().take(n).collect()
+        self.lookahead.iter().take(n).map(|lookahead| &lookahead.token).collect()
     }
 
+    /// Moves to the next token. After a lexical error the current token is an `Invalid` one
+    /// covering the offending text, and the lexer carries on behind it.
     pub fn advance(&mut self) -> Result<(), ParserError> {
-        if let Some(next) = self.lookahead.pop_front() {
-            self.current = next;
-        } else {
-            self.current = self.lexer.next_token()?;
+        let Lookahead { trivia, token, error, index } = match self.lookahead.pop_front() {
+            Some(lookahead) => lookahead,
+            None => self.read(),
+        };
+        self.trivia = trivia;
+        self.current = token;
+        self.index = index;
+        match error {
+            Some(error) => Err(error.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// The next token that is not trivia, with the trivia before it.
+    fn read(&mut self) -> Lookahead {
+        let mut trivia = vec![];
+        loop {
+            let index = self.read;
+            let Lexed { token, error, .. } = match &mut self.source {
+                Source::Lexer(lexer) => lexer.next_lexed(),
+                Source::Tokens(tokens) => tokens[index.min(tokens.len() - 1)].clone(),
+            };
+            self.read += 1;
+            if !token.kind.is_trivia() {
+                return Lookahead { trivia, token, error, index };
+            }
+            trivia.push(token);
         }
-        Ok(())
     }
 }
 
+/// Errors closer together than this many tokens are taken to be one mistake, and only the first
+/// is reported.
+const ERROR_DISTANCE: usize = 3;
+
 pub struct Parser<'a> {
-    token_stream: TokenStream<'a>
+    token_stream: TokenStream<'a>,
+    errors: Vec<ParserError>,
+    /// Tokens consumed since the last syntax error.
+    since_error: usize,
+    /// The end of the last token consumed.
+    previous_end: Position,
+    /// The number of the last token consumed.
+    previous_index: usize,
+    /// Constructs (structured statements and records) whose END or UNTIL is still to come.
+    open: usize,
+    /// The syntax tree so far.
+    builder: Builder,
+    /// Whether the module gets the comments attached, which it does where the lexer keeps trivia.
+    comments: bool,
+    integer_width: IntegerWidth,
+    /// Syntax and lexical errors met so far, reported or not.
+    faults: usize,
+    /// The declarations of a previous parse to take over, and the record of those that could be
+    /// taken over by the next.
+    reuse: Option<&'a mut Reuse>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(lexer: Lexer<'a>) -> Self {
-        Self { token_stream: TokenStream::new(lexer) }
+        let comments = lexer.keeps_trivia();
+        Self::with_stream(TokenStream::new(lexer), comments)
+    }
+
+    /// A parser of tokens lexed from `text` beforehand by a lexer that keeps trivia.
+    pub fn from_tokens(tokens: &'a [Lexed], text: &'a str) -> Self {
+        Self::with_stream(TokenStream::from_tokens(tokens, text), true)
+    }
+
+    fn with_stream(token_stream: TokenStream<'a>, comments: bool) -> Self {
+        Self {
+            token_stream,
+            errors: vec![],
+            since_error: usize::MAX,
+            previous_end: Position::initial(),
+            previous_index: 0,
+            open: 0,
+            builder: Builder::default(),
+            comments,
+            integer_width: IntegerWidth::default(),
+            faults: 0,
+            reuse: None,
+        }
+    }
+
+    /// Checks integer literals against INTEGERs of `width` bits instead of 64.
+    pub fn with_integer_width(mut self, width: IntegerWidth) -> Self {
+        self.integer_width = width;
+        self
+    }
+
+    /// Takes over the declarations in `reuse` where their tokens come up, and records in it the
+    /// declarations that parse without errors.
+    pub(crate) fn with_reuse(mut self, reuse: &'a mut Reuse) -> Self {
+        self.reuse = Some(reuse);
+        self
     }
 }
 
+/// Whether a token is of one of the given kinds, as in `pred!(Plus | Minus)`.
 macro_rules! pred {
-    (AMPERSAND) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "&" };
-    (ARRAY) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "ARRAY" };
-    (ASSIGN) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == ":=" };
-    (BEGIN) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "BEGIN" };
-    (BY) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "BY" };
-    (CARET) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "^" };
-    (CASE) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "CASE" };
-    (COLON) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == ":" };
-    (COMMA) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "," };
-    (CONST) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "CONST" };
-    (DIV) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "DIV" };
-    (DO) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "DO" };
-    (DOT) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "." };
-    (DOTDOT) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == ".." };
-    (ELSE) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "ELSE" };
-    (ELSIF) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "ELSIF" };
-    (END) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "END" };
-    (EQUAL) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "=" };
-    (FALSE) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "FALSE" };
-    (FOR) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "FOR" };
-    (GREATER) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == ">" };
-    (GREATEREQUAL) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == ">=" };
-    (IDENT) => { |token: &Token| token.kind == TokenKind::Identifier };
-    (IF) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "IF" };
-    (IMPORT) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "IMPORT" };
-    (IN) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "IN" };
-    (IS) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "IS" };
-    (LBRACKET) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "[" };
-    (LCURLY) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "{" };
-    (LESS) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "<" };
-    (LESSEQUAL) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "<=" };
-    (LPAREN) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "(" };
-    (MINUS) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "-" };
-    (MOD) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "MOD" };
-    (MODULE) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "MODULE" };
-    (NIL) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "NIL" };
-    (NONEQUAL) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "#" };
-    (NUMBER) => { |token: &Token| token.kind == TokenKind::Number };
-    (OF) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "OF" };
-    (OR) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "OR" };
-    (PIPE) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "|" };
-    (PLUS) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "+" };
-    (POINTER) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "POINTER" };
-    (PROCEDURE) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "PROCEDURE" };
-    (RBRACKET) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "]" };
-    (RCURLY) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "}" };
-    (RECORD) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "RECORD" };
-    (REPEAT) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "REPEAT" };
-    (RETURN) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "RETURN" };
-    (RPAREN) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == ")" };
-    (SEMICOLON) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == ";" };
-    (SLASH) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "/" };
-    (STAR) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "*" };
-    (STRING) => { |token: &Token| token.kind == TokenKind::String };
-    (THEN) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "THEN" };
-    (TILDE) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "~" };
-    (TO) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "TO" };
-    (TRUE) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "TRUE" };
-    (TYPE) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "TYPE" };
-    (UNTIL) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "UNTIL" };
-    (VAR) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "VAR" };
-    (WHILE) => { |token: &Token| token.kind == TokenKind::OperatorOrDelimiter && token.lexeme == "WHILE" };
+    ($($kind:ident)|+) => { |token: &Token| matches!(token.kind, $(TokenKind::$kind)|+) };
 }
+
 impl<'a> Parser<'a> {
 
+    /// Parses a module, failing with the first syntax error.
     pub fn parse(&mut self) -> Result<Module, ParserError> {
-        self.token_stream.advance()?;
-        self.parse_module()
-    }
-
-    fn parse_module(&mut self)-> Result<Module, ParserError> {
-        let start = self.expect(pred!(MODULE))?.span;
-        let name = self.parse_ident()?;
-        self.expect(pred!(SEMICOLON))?;
-        let imports = self.parse_imports()?;
-        let declarations = self.parse_declarations()?;
-        let stmts = self.parse_statement_sequence_with_begin(pred!(END))?;
-        self.expect(pred!(END))?;
-        let end_name = self.parse_ident()?;
-        let end = self.expect(pred!(DOT))?.span;
-
-        Ok(Module {
-            name,
-            imports,
-            declarations,
-            stmts,
-            end_name,
-            span: Span::new(start.start, end.end),
-        })
-    }
-
-    fn parse_imports(&mut self) -> Result<Vec<Import>, ParserError> {
-        let Some(_) = self.eat(pred!(IMPORT))? else {
-            return Ok(vec![]);
-        };
-        let mut result = vec![self.parse_import()?];
-        while self.eat(pred!(COMMA))?.is_some() {
-            result.push(self.parse_import()?);
-        }
-        self.expect(pred!(SEMICOLON))?;
-        Ok(result)
-    }
-
-    fn parse_import(&mut self) -> Result<Import, ParserError> {
-        let module = self.parse_ident()?;
-        let start = module.span.start;
-        let mut end = module.span.end;
-        let alias = if self.eat(pred!(ASSIGN))?.is_some() {
-            let alias = self.parse_ident()?;
-            end = alias.span.end;
-            Some(alias)
-        } else {
-            None
-        };
-        Ok(Import { module, alias, span: Span::new(start, end) })
-    }
-
-    fn parse_declarations(&mut self) -> Result<Declarations, ParserError> {
-        let mut const_declarations = vec![];
-        if self.eat(pred!(CONST))?.is_some() {
-            const_declarations = self.parse_const_declarations()?;
-        }
-        let mut type_declarations = vec![];
-        if self.eat(pred!(TYPE))?.is_some() {
-            type_declarations = self.parse_type_declarations()?;
+        let (module, errors) = self.parse_with_recovery();
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(module),
+        }
+    }
+
+    /// Parses a module, carrying on after syntax errors. Each error is recorded, the tokens up to
+    /// the next `;`, END, ELSIF, ELSE, PROCEDURE, BEGIN (and the like) are skipped, and an `Error`
+    /// node stands in for the broken statement, expression or type. Returns the module, partial
+    /// if there were errors, with the errors in the order they were found.
+    pub fn parse_with_recovery(&mut self) -> (Module, Vec<ParserError>) {
+        let (syntax, errors) = self.parse_syntax();
+        (lower::module(&syntax, self.integer_width, self.comments), errors)
+    }
+
+    /// Parses a module into its concrete syntax tree, carrying on after syntax errors as
+    /// `parse_with_recovery` does. The tree holds all the source text, whatever the errors.
+    pub fn parse_syntax(&mut self) -> (SyntaxNode, Vec<ParserError>) {
+        self.step();
+        self.parse_module();
+        let trailing = self.checkpoint();
+        while !self.at_eof() {
+            self.builder.token(self.token_stream.current(), self.token_stream.text());
+            let _ = self.token_stream.advance();
+            self.builder.trivia(self.token_stream.trivia(), self.token_stream.text());
+        }
+        self.skipped(trailing);
+        let builder = std::mem::take(&mut self.builder);
+        (SyntaxNode::new_root(builder.finish(NodeKind::Module)), std::mem::take(&mut self.errors))
+    }
+
+    /// Parses the module up to its final `.`; the text after it is left alone.
+    fn parse_module(&mut self) {
+        let heading = self.checkpoint();
+        match self.parse_module_heading() {
+            Ok(()) => self.wrap(heading, NodeKind::ModuleHeading),
+            Err(error) => {
+                self.recover(error, self.open);
+                self.eat(pred!(Semicolon));
+                self.skipped(heading);
+            }
         }
-        let mut var_declarations = vec![];
-        if self.eat(pred!(VAR))?.is_some() {
-            var_declarations = self.parse_var_declarations()?;
+        let imports = self.checkpoint();
+        match self.parse_imports() {
+            Ok(true) => self.wrap(imports, NodeKind::ImportList),
+            Ok(false) => {}
+            Err(error) => {
+                self.recover(error, self.open);
+                self.eat(pred!(Semicolon));
+                self.skipped(imports);
+            }
         }
-
-        let mut procedure_declarations = vec![];
-        if self.peek(pred!(PROCEDURE)).is_some() {
-            procedure_declarations = self.parse_procedure_declarations()?;
+        self.parse_declarations();
+        self.parse_statement_sequence_with_begin(&[TokenKind::End]);
+        let end = self.checkpoint();
+        match self.parse_module_end() {
+            Ok(()) => self.wrap(end, NodeKind::ModuleEnd),
+            Err(error) => {
+                self.record(error);
+                while !self.at_eof() {
+                    self.advance();
+                }
+                self.skipped(end);
+            }
         }
-
-        Ok(Declarations { const_declarations, type_declarations, var_declarations, procedure_declarations })
     }
 
-    fn parse_const_declarations(&mut self) -> Result<Vec<ConstDeclaration>, ParserError> {
-        let mut result = vec![];
-        while self.peek(|t| {
-            pred!(TYPE)(t)
-                || pred!(VAR)(t)
-                || pred!(PROCEDURE)(t)
-                || pred!(BEGIN)(t)
-                || pred!(END)(t)
-                || pred!(RETURN)(t)
-        }).is_none() {
-            result.push(self.parse_const_declaration()?);
-            self.expect(pred!(SEMICOLON))?;
-        }
-
-        Ok(result)
+    fn parse_module_heading(&mut self) -> Result<(), ParserError> {
+        self.expect(TokenKind::Module)?;
+        self.parse_ident()?;
+        self.expect_after(TokenKind::Semicolon, "after module name")?;
+        Ok(())
     }
 
-    fn parse_const_declaration(&mut self) -> Result<ConstDeclaration, ParserError> {
-        let ident = self.parse_identdef()?;
-        self.expect(pred!(EQUAL))?;
-        let value = self.parse_expression()?;
-
-        Ok(ConstDeclaration { ident, value })
+    fn parse_module_end(&mut self) -> Result<(), ParserError> {
+        self.expect(TokenKind::End)?;
+        self.parse_ident()?;
+        self.expect_after(TokenKind::Dot, "at end of module")?;
+        Ok(())
     }
 
-    fn parse_type_declarations(&mut self) -> Result<Vec<TypeDeclaration>, ParserError> {
-        let mut result = vec![];
-        while self.peek(|t| {
-            pred!(VAR)(t)
-                || pred!(PROCEDURE)(t)
-                || pred!(BEGIN)(t)
-                || pred!(END)(t)
-                || pred!(RETURN)(t)
-        }).is_none() {
-            result.push(self.parse_type_declaration()?);
-            self.expect(pred!(SEMICOLON))?;
+    /// Parses the import list, if there is one.
+    fn parse_imports(&mut self) -> Result<bool, ParserError> {
+        let Some(_) = self.eat(pred!(Import)) else {
+            return Ok(false);
+        };
+        self.parse_import()?;
+        while self.eat(pred!(Comma)).is_some() {
+            self.parse_import()?;
         }
-
-        Ok(result)
+        self.expect_after(TokenKind::Semicolon, "after import list")?;
+        Ok(true)
     }
 
-    fn parse_type_declaration(&mut self) -> Result<TypeDeclaration, ParserError> {
-        let ident = self.parse_identdef()?;
-        self.expect(pred!(EQUAL))?;
-        let ty = self.parse_type()?;
-
-        Ok(TypeDeclaration { ident, ty })
+    /// `Module` or `Alias := Module`.
+    fn parse_import(&mut self) -> Result<(), ParserError> {
+        let import = self.checkpoint();
+        self.parse_ident()?;
+        if self.eat(pred!(Assign)).is_some() {
+            self.parse_ident()?;
+        }
+        self.wrap(import, NodeKind::Import);
+        Ok(())
     }
 
-    fn parse_var_declarations(&mut self) -> Result<Vec<VarDeclaration>, ParserError> {
-        let mut result = vec![];
-        while self.peek(|t| {
-            pred!(PROCEDURE)(t)
-                || pred!(BEGIN)(t)
-                || pred!(END)(t)
-                || pred!(RETURN)(t)
-        }).is_none() {
-            result.push(self.parse_var_declaration()?);
-            self.expect(pred!(SEMICOLON))?;
+    fn parse_declarations(&mut self) {
+        let declarations = self.checkpoint();
+        let section = self.checkpoint();
+        if self.eat(pred!(Const)).is_some() {
+            self.parse_declaration_list(
+                pred!(Type | Var | Procedure | Begin | End | Return),
+                Self::parse_const_declaration,
+                NodeKind::ConstDeclaration,
+                "after constant declaration",
+            );
+            self.wrap(section, NodeKind::ConstSection);
+        }
+        let section = self.checkpoint();
+        if self.eat(pred!(Type)).is_some() {
+            self.parse_declaration_list(
+                pred!(Var | Procedure | Begin | End | Return),
+                Self::parse_type_declaration,
+                NodeKind::TypeDeclaration,
+                "after type declaration",
+            );
+            self.wrap(section, NodeKind::TypeSection);
+        }
+        let section = self.checkpoint();
+        if self.eat(pred!(Var)).is_some() {
+            self.parse_declaration_list(
+                pred!(Procedure | Begin | End | Return),
+                Self::parse_var_declaration,
+                NodeKind::VarDeclaration,
+                "after variable declaration",
+            );
+            self.wrap(section, NodeKind::VarSection);
+        }
+
+        if self.peek(pred!(Procedure)).is_some() {
+            self.parse_procedure_declarations();
+        }
+        self.wrap(declarations, NodeKind::Declarations);
+    }
+
+    /// Parses declarations of `kind`, each followed by a semicolon, up to a token `stop` accepts. A
+    /// broken declaration is reported and skipped up to its semicolon.
+    fn parse_declaration_list(
+        &mut self,
+        stop: fn(&Token) -> bool,
+        parse: fn(&mut Self) -> Result<(), ParserError>,
+        kind: NodeKind,
+        context: &'static str,
+    ) {
+        while self.peek(stop).is_none() && !self.at_eof() {
+            if self.take_over(kind) {
+                continue;
+            }
+            let mark = self.mark();
+            let declaration = self.checkpoint();
+            let open = self.open;
+            match parse(self) {
+                Ok(()) => {
+                    let semicolon = self.eat(pred!(Semicolon));
+                    self.wrap(declaration, kind);
+                    if semicolon.is_none() {
+                        let error = self.unexpected(vec![TokenKind::Semicolon.name()], Some(context));
+                        self.record(error);
+                        // A missing semicolon between two declarations needs no skipping.
+                        if self.peek(pred!(Identifier)).is_none() {
+                            let skipped = self.checkpoint();
+                            self.synchronize(0);
+                            self.eat(pred!(Semicolon));
+                            self.skipped(skipped);
+                        }
+                    }
+                }
+                Err(error) => {
+                    self.recover(error, open);
+                    self.eat(pred!(Semicolon));
+                    if self.builder.is_empty_since(declaration) {
+                        self.advance();
+                    }
+                    self.skipped(declaration);
+                }
+            }
+            self.remember(mark);
         }
+    }
 
-        Ok(result)
+    fn parse_const_declaration(&mut self) -> Result<(), ParserError> {
+        self.parse_identdef()?;
+        self.expect_after(TokenKind::Equal, "after constant name")?;
+        self.parse_expression_or_error();
+        Ok(())
     }
 
-    fn parse_var_declaration(&mut self) -> Result<VarDeclaration, ParserError> {
-        let variables = self.parse_identdef_list()?;
-        self.expect(pred!(COLON))?;
-        let ty = self.parse_type()?;
+    fn parse_type_declaration(&mut self) -> Result<(), ParserError> {
+        self.parse_identdef()?;
+        self.expect_after(TokenKind::Equal, "after type name")?;
+        self.parse_type_or_error();
+        Ok(())
+    }
 
-        Ok(VarDeclaration { variables, ty })
+    fn parse_var_declaration(&mut self) -> Result<(), ParserError> {
+        self.parse_identdef_list()?;
+        self.expect_after(TokenKind::Colon, "after variable names")?;
+        self.parse_type_or_error();
+        Ok(())
     }
 
-    fn parse_procedure_declarations(&mut self) -> Result<Vec<ProcedureDeclaration>, ParserError> {
-        let mut result = vec![];
-        while self.peek(|t| {
-            pred!(PROCEDURE)(t)
-        }).is_some() {
-            result.push(self.parse_procedure_declaration()?);
-            self.expect(pred!(SEMICOLON))?;
+    fn parse_procedure_declarations(&mut self) {
+        while self.peek(pred!(Procedure)).is_some() {
+            if self.take_over(NodeKind::ProcedureDeclaration) {
+                continue;
+            }
+            let mark = self.mark();
+            let procedure = self.checkpoint();
+            let open = self.open;
+            match self.parse_procedure_declaration() {
+                Ok(()) => {
+                    let semicolon = self.expect_after(TokenKind::Semicolon, "after procedure declaration");
+                    self.wrap(procedure, NodeKind::ProcedureDeclaration);
+                    if let Err(error) = semicolon {
+                        let skipped = self.checkpoint();
+                        self.recover(error, open);
+                        self.eat(pred!(Semicolon));
+                        self.skipped(skipped);
+                    }
+                }
+                Err(error) => {
+                    self.record(error);
+                    self.skip_procedure();
+                    self.open = open;
+                    self.skipped(procedure);
+                }
+            }
+            self.remember(mark);
         }
-
-        Ok(result)
     }
 
-    fn parse_procedure_declaration(&mut self) -> Result<ProcedureDeclaration, ParserError> {
-        let header = self.parse_procedure_heading()?;
-        self.expect(pred!(SEMICOLON))?;
-        let body = self.parse_procedure_body()?;
-        let name = self.parse_ident()?;
-        let span = Span::new(header.span.start, name.span.end);
-        Ok(ProcedureDeclaration { header, body, name, span })
+    fn parse_procedure_declaration(&mut self) -> Result<(), ParserError> {
+        self.parse_procedure_heading()?;
+        self.expect_after(TokenKind::Semicolon, "after procedure heading")?;
+        self.parse_procedure_body()?;
+        self.parse_ident()?;
+        Ok(())
     }
 
-    fn parse_expression_list(&mut self) -> Result<Vec<Expression>, ParserError> {
-        let mut result = vec![self.parse_expression()?];
-        while self.eat(pred!(COMMA))?.is_some() {
-            result.push(self.parse_expression()?);
+    fn parse_expression_list(&mut self) -> Result<(), ParserError> {
+        self.parse_expression()?;
+        while self.eat(pred!(Comma)).is_some() {
+            self.parse_expression()?;
         }
-        Ok(result)
+        Ok(())
     }
-
-    fn parse_binary_operator(token: Token) -> BinaryOperation {
-        match token.lexeme.as_str() {
-            "=" => BinaryOperation::Eq,
-            "#" => BinaryOperation::Neq,
-            "<" => BinaryOperation::Lt,
-            "<=" => BinaryOperation::Le,
-            ">" => BinaryOperation::Gt,
-            ">=" => BinaryOperation::Ge,
-            "*" => BinaryOperation::Multiplication,
-            "/" => BinaryOperation::Division,
-            "+" => BinaryOperation::Addition,
-            "-" => BinaryOperation::Subtraction,
-            "MOD" => BinaryOperation::Mod,
-            "DIV" => BinaryOperation::Div,
-            "&" => BinaryOperation::And,
-            "OR" => BinaryOperation::Or,
-            "IN" => BinaryOperation::In,
-            "IS" => BinaryOperation::Is,
-            _ => panic!("Invalid binary operator: {}", token.lexeme),
+
+    /// Parses an expression, standing in an `Error` node for it on a syntax error.
+    fn parse_expression_or_error(&mut self) {
+        let expression = self.checkpoint();
+        let open = self.open;
+        if let Err(error) = self.parse_expression() {
+            self.recover(error, open);
+            self.wrap(expression, NodeKind::Error);
         }
     }
 
-    fn parse_expression(&mut self) -> Result<Expression, ParserError> {
-        let simple_expression = self.parse_simple_expression()?;
-        if let Some(token) = self.eat(|t| pred!(EQUAL)(t)
-            || pred!(NONEQUAL)(t)
-            || pred!(LESS)(t)
-            || pred!(LESSEQUAL)(t)
-            || pred!(GREATER)(t)
-            || pred!(GREATEREQUAL)(t)
-            || pred!(IN)(t)
-            || pred!(IS)(t)
-        )? {
-            let rhs = self.parse_simple_expression()?;
-            let span = Span::new(simple_expression.span().start, rhs.span().end);
-            Ok(Expression::Binary {
-                op: Self::parse_binary_operator(token),
-                lhs: Box::new(simple_expression), rhs: Box::new(rhs), span })
-        }
-        else {
-            Ok(simple_expression)
+    fn parse_expression(&mut self) -> Result<(), ParserError> {
+        let expression = self.checkpoint();
+        self.parse_simple_expression()?;
+        if self.eat(pred!(Equal | NotEqual | Less | LessEqual | Greater | GreaterEqual | In | Is)).is_some() {
+            self.parse_simple_expression()?;
+            self.wrap(expression, NodeKind::Binary);
         }
-
+        Ok(())
     }
 
-    fn parse_simple_expression(&mut self) -> Result<Expression, ParserError> {
-        let sign_token = self.eat(|t| pred!(PLUS)(t) || pred!(MINUS)(t))?;
-
-        let mut expr = self.parse_term()?;
-
-        if let Some(token) = sign_token {
-            let span = Span::new(token.span.start, expr.span().end);
-            let op = if pred!(PLUS)(&token) { UnaryOperation::Plus } else { UnaryOperation::Minus };
-            expr = Expression::Unary { op, operand: Box::new(expr), span };
+    fn parse_simple_expression(&mut self) -> Result<(), ParserError> {
+        let expression = self.checkpoint();
+        match self.eat(pred!(Plus | Minus)) {
+            Some(sign) if sign.kind == TokenKind::Minus && self.negated_min_integer() => {
+                self.expect(TokenKind::Number)?;
+                self.wrap(expression, NodeKind::Literal);
+            }
+            Some(_) => {
+                self.parse_term()?;
+                self.wrap(expression, NodeKind::Unary);
+            }
+            None => self.parse_term()?,
         }
 
-        if let Some(token) = self.eat(|t| pred!(PLUS)(t) || pred!(MINUS)(t) || pred!(OR)(t))? {
-            let rhs = self.parse_term()?;
-            let span = Span::new(expr.span().start, rhs.span().end);
-
-            expr = Expression::Binary {
-                op: Self::parse_binary_operator(token),
-                lhs: Box::new(expr),
-                rhs: Box::new(rhs),
-                span,
-            };
+        while self.eat(pred!(Plus | Minus | Or)).is_some() {
+            self.parse_term()?;
+            self.wrap(expression, NodeKind::Binary);
         }
 
-        Ok(expr)
+        Ok(())
     }
 
-    fn parse_term(&mut self) -> Result<Expression, ParserError> {
-        let factor = self.parse_factor()?;
-        if let Some(token) = self.eat(|t| pred!(STAR)(t)
-            || pred!(SLASH)(t)
-            || pred!(MOD)(t)
-            || pred!(DIV)(t)
-            || pred!(AMPERSAND)(t)
-        )? {
-            let rhs = self.parse_factor()?;
-            let span = Span::new(factor.span().start, rhs.span().end);
-            Ok(Expression::Binary {
-                op: Self::parse_binary_operator(token),
-                lhs: Box::new(factor), rhs: Box::new(rhs), span })
-        }
-        else {
-            Ok(factor)
+    fn parse_term(&mut self) -> Result<(), ParserError> {
+        let term = self.checkpoint();
+        self.parse_factor()?;
+        while self.eat(pred!(Star | Slash | Mod | Div | Ampersand)).is_some() {
+            self.parse_factor()?;
+            self.wrap(term, NodeKind::Binary);
         }
+        Ok(())
     }
 
-    fn parse_factor(&mut self) -> Result<Expression, ParserError> {
-        if self.peek(pred!(NUMBER)).is_some() {
-            self.parse_number()
-        }
-        else if self.peek(pred!(STRING)).is_some() {
-            self.parse_string()
+    fn parse_factor(&mut self) -> Result<(), ParserError> {
+        let factor = self.checkpoint();
+        if self.peek(pred!(Number)).is_some() {
+            self.parse_number()?;
         }
-        else if let Some(token) = self.eat(pred!(NIL))? {
-            Ok(Expression::Nil { span: token.span })
+        else if self.peek(pred!(String)).is_some() {
+            self.parse_string()?;
         }
-        else if let Some(token) = self.eat(pred!(TRUE))? {
-            Ok(Expression::True { span: token.span })
+        else if self.peek(pred!(Char)).is_some() {
+            self.parse_char()?;
         }
-        else if let Some(token) = self.eat(pred!(FALSE))? {
-            Ok(Expression::False { span: token.span })
+        else if self.eat(pred!(Nil | True | False)).is_some() {
+            self.wrap(factor, NodeKind::Literal);
         }
-        else if self.peek(pred!(LCURLY)).is_some() {
-            self.parse_set()
+        else if self.peek(pred!(LBrace)).is_some() {
+            self.parse_set()?;
         }
-        else if let Some(start) = self.peek(pred!(IDENT)) {
-            let designator = self.parse_designator()?;
-            let actual_parameters =
-                if self.peek(pred!(LPAREN)).is_some() {
-                    Some(self.parse_actual_parameters()?)
-                } else {
-                    None
-                };
-
-            let end = self.token_stream.current();
-            Ok(Expression::Designator { designator, actual_parameters, span: Span::new(start.span.start, end.span.end) })
+        else if self.peek(pred!(Identifier)).is_some() {
+            self.parse_designator()?;
+            if self.peek(pred!(LParen)).is_some() {
+                self.parse_actual_parameters()?;
+            }
+            self.wrap(factor, NodeKind::DesignatorExpression);
         }
-        else if self.eat(pred!(LPAREN))?.is_some() {
-            let expr = self.parse_expression()?;
-            self.expect(pred!(RPAREN))?;
-            Ok(expr)
+        else if self.eat(pred!(LParen)).is_some() {
+            self.parse_expression()?;
+            self.expect_after(TokenKind::RParen, "to close '('")?;
+            self.wrap(factor, NodeKind::Parenthesized);
         }
-        else if let Some(token) = self.eat(pred!(TILDE))? {
-            let operand = self.parse_factor()?;
-            Ok(Expression::Unary { op: UnaryOperation::Not, operand: Box::new(operand), span: token.span })
+        else if self.eat(pred!(Tilde)).is_some() {
+            self.parse_factor()?;
+            self.wrap(factor, NodeKind::Unary);
         }
         else {
-            Err(ParserError::UnexpectedToken { token: self.token_stream.current().clone() })
+            return Err(self.unexpected(vec!["expression"], None));
         }
+        Ok(())
     }
 
-    fn parse_number(&mut self) -> Result<Expression, ParserError> {
-        let token = self.expect(pred!(NUMBER))?;
-        if token.lexeme.ends_with("H") {
-            let hex = token.lexeme.strip_suffix("H").unwrap();
-            Ok(Expression::Int { value: i64::from_str_radix(hex, 16)?, span: token.span })
-        } else if  token.lexeme.contains('.') {
-            Ok(Expression::Real{ value: token.lexeme.parse::<f64>()?, span: token.span })
+    /// A number, whose value must be in range; returns its token.
+    fn parse_number(&mut self) -> Result<Token, ParserError> {
+        let number = self.checkpoint();
+        let token = self.expect(TokenKind::Number)?;
+        let lexeme = token.lexeme();
+        if lexeme.contains('.') {
+            if literal::real(lexeme).is_none() {
+                return Err(ParserError::RealOutOfRange { token });
+            }
         }
         else {
-            Ok(Expression::Int { value: token.lexeme.parse::<i64>()?, span: token.span })
+            let width = self.integer_width;
+            if literal::integer(lexeme, width, false).is_none() {
+                return Err(ParserError::IntegerOutOfRange { token, width });
+            }
         }
+        self.wrap(number, NodeKind::Literal);
+        Ok(token)
     }
 
-    fn parse_string(&mut self) -> Result<Expression, ParserError> {
-        let token = self.expect(pred!(STRING))?;
-        if token.lexeme.ends_with("X") {
-            let hex = token.lexeme.strip_suffix("X").unwrap();
-            let byte_value = u8::from_str_radix(hex, 16)?;
-            Ok(Expression::String { value: (byte_value as char).to_string(), span: token.span })
-        } else {
-            Ok(Expression::String { value:
-            token.lexeme.strip_prefix('\"').unwrap().strip_suffix('\"').unwrap().to_string(),
-                span: token.span })
+    /// Whether the number after a minus sign is the magnitude of MIN(INTEGER), which is only in
+    /// range negated, and makes up the whole term.
+    fn negated_min_integer(&mut self) -> bool {
+        let token = self.token_stream.current().clone();
+        if token.kind != TokenKind::Number
+            || literal::integer(token.lexeme(), self.integer_width, false).is_some()
+            || self.token_stream.peek_n(1).first().is_some_and(|next| pred!(Star | Slash | Mod | Div | Ampersand)(next))
+        {
+            return false;
         }
+        literal::integer(token.lexeme(), self.integer_width, true).is_some()
+    }
 
+    fn parse_string(&mut self) -> Result<(), ParserError> {
+        let string = self.checkpoint();
+        self.expect(TokenKind::String)?;
+        self.wrap(string, NodeKind::Literal);
+        Ok(())
     }
 
-    fn parse_set(&mut self) -> Result<Expression, ParserError> {
-        let start =self.expect(pred!(LCURLY))?;
-        let mut elements = vec![];
-        while self.peek(pred!(RCURLY)).is_none() {
-            if !elements.is_empty() {
-                self.expect(pred!(COMMA))?;
+    /// A character constant, `41X`; its code must fit a CHAR.
+    fn parse_char(&mut self) -> Result<(), ParserError> {
+        let char = self.checkpoint();
+        let token = self.expect(TokenKind::Char)?;
+        let hex = token.lexeme().strip_suffix('X').unwrap();
+        if u8::from_str_radix(hex, 16).is_err() {
+            return Err(ParserError::InvalidCharacter { token });
+        }
+        self.wrap(char, NodeKind::Literal);
+        Ok(())
+    }
+
+    fn parse_set(&mut self) -> Result<(), ParserError> {
+        let set = self.checkpoint();
+        self.expect(TokenKind::LBrace)?;
+        let mut first = true;
+        while self.peek(pred!(RBrace)).is_none() {
+            if !first {
+                self.expect_separator(TokenKind::Comma, TokenKind::RBrace)?;
             }
-            elements.push(self.parse_element()?);
+            self.parse_element()?;
+            first = false;
         }
-        let end = self.expect(pred!(RCURLY))?;
-        Ok(Expression::Set { elements, span: Span::new(start.span.start, end.span.end) })
+        self.expect(TokenKind::RBrace)?;
+        self.wrap(set, NodeKind::Set);
+        Ok(())
     }
 
-    fn parse_element(&mut self) -> Result<Element, ParserError> {
-        let first = self.parse_expression()?;
-        if self.eat(pred!(DOTDOT))?.is_some() {
-            let second = self.parse_expression()?;
-            let span = Span::new(first.span().start, second.span().end);
-            Ok(Element { first, second: Some(second), span })
-        } else {
-            let span = first.span();
-            Ok(Element { first, second: None, span })
-        }
-    }
-
-    fn parse_type(&mut self) -> Result<Type, ParserError> {
-        if self.peek(pred!(IDENT)).is_some() {
-            Ok(Type::Named { name: self.parse_qualident()? })
-        } else if let Some(start) = self.eat(pred!(ARRAY))? {
-            let lengths = self.parse_lengths()?;
-            self.expect(pred!(OF))?;
-            let element = Box::new(self.parse_type()?);
-            let span = Span::new(start.span.start, self.token_stream.current().span.end);
-            Ok(Type::Array { lengths, element, span })
-        } else if let Some(start) = self.eat(pred!(RECORD))? {
-            let base = self.parse_base_type()?;
-            let field_lists = self.parse_field_lists()?;
-            self.expect(pred!(END))?;
-            let span = Span::new(start.span.start, self.token_stream.current().span.end);
-            Ok(Type::Record { base, field_lists, span })
-        } else if let Some(start) = self.eat(pred!(POINTER))? {
-            self.expect(pred!(TO))?;
-            let pointee = self.parse_type()?;
-            let span = Span::new(start.span.start, pointee.span().end);
-            Ok(Type::Pointer { pointee: Box::new(pointee), span })
-        } else if let Some(start) = self.eat(pred!(PROCEDURE))? {
-            let params = self.parse_formal_parameters()?;
-            let span = Span::new(start.span.start, self.token_stream.current().span.end);
-            Ok(Type::Procedure { params , span })
+    fn parse_element(&mut self) -> Result<(), ParserError> {
+        let element = self.checkpoint();
+        self.parse_expression()?;
+        if self.eat(pred!(DotDot)).is_some() {
+            self.parse_expression()?;
+        }
+        self.wrap(element, NodeKind::Element);
+        Ok(())
+    }
+
+    fn parse_type(&mut self) -> Result<(), ParserError> {
+        let ty = self.checkpoint();
+        let kind = if self.peek(pred!(Identifier)).is_some() {
+            self.parse_qualident()?;
+            NodeKind::NamedType
+        } else if self.eat(pred!(Array)).is_some() {
+            self.parse_lengths()?;
+            self.expect_after(TokenKind::Of, "after array lengths")?;
+            self.parse_type()?;
+            NodeKind::ArrayType
+        } else if self.eat_opening(pred!(Record)).is_some() {
+            self.parse_base_type()?;
+            self.parse_field_lists()?;
+            self.expect_closing(TokenKind::End, Some("at end of record"))?;
+            NodeKind::RecordType
+        } else if self.eat(pred!(Pointer)).is_some() {
+            self.expect_after(TokenKind::To, "after POINTER")?;
+            self.parse_type()?;
+            NodeKind::PointerType
+        } else if self.eat(pred!(Procedure)).is_some() {
+            self.parse_formal_parameters()?;
+            NodeKind::ProcedureType
         }
         else {
-            Err(ParserError::UnexpectedToken { token: self.token_stream.current().clone() })
+            return Err(self.unexpected(vec!["type"], None));
+        };
+        self.wrap(ty, kind);
+        Ok(())
+    }
+
+    /// Parses a type, standing in an `Error` node for it on a syntax error.
+    fn parse_type_or_error(&mut self) {
+        let ty = self.checkpoint();
+        let open = self.open;
+        if let Err(error) = self.parse_type() {
+            self.recover(error, open);
+            self.wrap(ty, NodeKind::Error);
         }
     }
 
-    fn parse_lengths(&mut self) -> Result<Vec<Expression>, ParserError> {
-        let mut result = vec![self.parse_expression()?];
-        while self.eat(pred!(COMMA))?.is_some() {
-            result.push(self.parse_expression()?);
+    fn parse_lengths(&mut self) -> Result<(), ParserError> {
+        self.parse_expression()?;
+        while self.eat(pred!(Comma)).is_some() {
+            self.parse_expression()?;
         }
-        Ok(result)
+        Ok(())
     }
 
-    fn parse_base_type(&mut self) -> Result<Option<QualifiedIdentifier>, ParserError> {
-        if self.eat(pred!(LPAREN))?.is_some() {
-            let element = self.parse_qualident()?;
-            self.eat(pred!(RPAREN))?;
-            Ok(Some(element))
-        } else {
-            Ok(None)
+    fn parse_base_type(&mut self) -> Result<(), ParserError> {
+        let base = self.checkpoint();
+        if self.eat(pred!(LParen)).is_some() {
+            self.parse_qualident()?;
+            self.expect_after(TokenKind::RParen, "after base type")?;
+            self.wrap(base, NodeKind::BaseType);
         }
+        Ok(())
     }
 
-    fn parse_field_lists(&mut self) -> Result<Vec<FieldList>, ParserError> {
-        let mut result = vec![];
-        while self.peek(pred!(END)).is_none() {
-            if !result.is_empty() {
-                self.expect(pred!(SEMICOLON))?;
+    fn parse_field_lists(&mut self) -> Result<(), ParserError> {
+        let mut first = true;
+        while self.peek(pred!(End)).is_none() {
+            if !first {
+                self.expect_separator(TokenKind::Semicolon, TokenKind::End)?;
             }
-            result.push(self.parse_field_list()?);
+            self.parse_field_list()?;
+            first = false;
         }
-        Ok(result)
+        Ok(())
     }
 
-    fn parse_field_list(&mut self) -> Result<FieldList, ParserError> {
-        let fields = self.parse_identdef_list()?;
-        self.expect(pred!(COLON))?;
-        let ty = self.parse_type()?;
-        Ok(FieldList{ fields, ty })
+    fn parse_field_list(&mut self) -> Result<(), ParserError> {
+        let field_list = self.checkpoint();
+        self.parse_identdef_list()?;
+        self.expect_after(TokenKind::Colon, "after field names")?;
+        self.parse_type()?;
+        self.wrap(field_list, NodeKind::FieldList);
+        Ok(())
     }
 
-    fn parse_formal_parameters(&mut self) -> Result<Option<FormalParameters>, ParserError> {
-        if self.peek(pred!(LPAREN)).is_none() {
-            return Ok(None);
+    fn parse_formal_parameters(&mut self) -> Result<(), ParserError> {
+        if self.peek(pred!(LParen)).is_none() {
+            return Ok(());
         }
-        let start_span = self.expect(pred!(LPAREN))?.span;
-        let sections = self.parse_fp_sections()?;
-        let mut end_span = self.expect(pred!(RPAREN))?.span;
-        let return_type =
-            if self.eat(pred!(COLON))?.is_some() {
-                let qualident = self.parse_qualident()?;
-                end_span = qualident.span();
-                Some(qualident)
-            } else { None };
-
-        let span = Span::new(start_span.start, end_span.end);
-        Ok(Some(FormalParameters { sections, return_type, span }))
+        let parameters = self.checkpoint();
+        self.expect(TokenKind::LParen)?;
+        self.parse_fp_sections()?;
+        self.expect(TokenKind::RParen)?;
+        if self.eat(pred!(Colon)).is_some() {
+            self.parse_qualident()?;
+        }
+        self.wrap(parameters, NodeKind::FormalParameters);
+        Ok(())
     }
 
-    fn parse_fp_sections(&mut self) -> Result<Vec<FPSection>, ParserError> {
-        let mut result = vec![];
-        while self.peek(pred!(RPAREN)).is_none() {
-            if !result.is_empty() { self.expect(pred!(SEMICOLON))?; }
-            result.push(self.parse_fp_section()?);
+    fn parse_fp_sections(&mut self) -> Result<(), ParserError> {
+        let mut first = true;
+        while self.peek(pred!(RParen)).is_none() {
+            if !first { self.expect_separator(TokenKind::Semicolon, TokenKind::RParen)?; }
+            self.parse_fp_section()?;
+            first = false;
         }
-        Ok(result)
+        Ok(())
     }
 
-    fn parse_fp_section(&mut self) -> Result<FPSection, ParserError> {
-        let start_span = self.token_stream.current.span;
-        let by_ref = self.eat(pred!(VAR))?.is_some();
-        let names = self.parse_ident_list()?;
-        self.expect(pred!(COLON))?;
-        let ty = self.parse_formal_type()?;
-        let span = Span::new(start_span.start, ty.span.end);
-        Ok(FPSection{ names, by_ref, ty, span })
+    fn parse_fp_section(&mut self) -> Result<(), ParserError> {
+        let section = self.checkpoint();
+        self.eat(pred!(Var));
+        self.parse_ident_list()?;
+        self.expect_after(TokenKind::Colon, "after parameter names")?;
+        self.parse_formal_type()?;
+        self.wrap(section, NodeKind::FPSection);
+        Ok(())
     }
 
-    fn parse_formal_type(&mut self) -> Result<FormalType, ParserError> {
-        let start_span = self.token_stream.current.span;
-        let mut open_arrays = 0;
-        while self.eat(pred!(ARRAY))?.is_some() {
-            self.expect(pred!(OF))?;
-            open_arrays += 1;
+    fn parse_formal_type(&mut self) -> Result<(), ParserError> {
+        let ty = self.checkpoint();
+        while self.eat(pred!(Array)).is_some() {
+            self.expect_after(TokenKind::Of, "after ARRAY")?;
         }
-        let base = self.parse_qualident()?;
-        let span = Span::new(start_span.start, base.span().end);
-        Ok(FormalType{ open_arrays, base, span })
+        self.parse_qualident()?;
+        self.wrap(ty, NodeKind::FormalType);
+        Ok(())
     }
 
-    fn parse_statement_sequence_with_begin<F>(&mut self, end_predicate: F) -> Result<Option<StatementSequence>, ParserError>
-    where
-        F: Fn(&Token) -> bool,
-    {
-        if let Some(begin) = self.eat(pred!(BEGIN))? {
-            let block = self.parse_statement_sequence(end_predicate)?;
-            let span = Span::new(begin.span.start, block.span.end);
-            Ok(Some(StatementSequence { statements: block.statements, span }))
-        } else {
-            Ok(None)
+    fn parse_statement_sequence_with_begin(&mut self, ends: &[TokenKind]) {
+        if self.peek(pred!(Begin)).is_none() {
+            return;
+        }
+        let sequence = self.checkpoint();
+        self.advance();
+        self.parse_statements(ends);
+        self.wrap(sequence, NodeKind::StatementSequence);
+    }
+
+    fn parse_statement_sequence(&mut self, ends: &[TokenKind]) {
+        let sequence = self.checkpoint();
+        self.parse_statements(ends);
+        self.wrap(sequence, NodeKind::StatementSequence);
+    }
+
+    /// Parses statements separated by semicolons, up to one of the `ends` tokens. A broken
+    /// statement becomes an `Error` node; a token that may belong to an enclosing construct ends
+    /// the sequence early, and that construct deals with it.
+    fn parse_statements(&mut self, ends: &[TokenKind]) {
+        self.parse_statement_or_error();
+        while self.peek(|t| ends.contains(&t.kind)).is_none() {
+            if self.eat(pred!(Semicolon)).is_none() {
+                let expected = std::iter::once(TokenKind::Semicolon).chain(ends.iter().copied()).map(TokenKind::name).collect();
+                let error = self.unexpected(expected, None);
+                self.record(error);
+                // A missing semicolon between two statements needs no skipping.
+                if !self.starts_statement() {
+                    let skipped = self.checkpoint();
+                    self.synchronize(0);
+                    self.skipped(skipped);
+                    if self.peek(pred!(Semicolon)).is_none() {
+                        break;
+                    }
+                    continue;
+                }
+            }
+            self.parse_statement_or_error();
         }
     }
 
-    fn parse_statement_sequence<F>(&mut self, end_predicate: F) -> Result<StatementSequence, ParserError>
-    where
-        F: Fn(&Token) -> bool,
-    {
-        let start = self.token_stream.current().span;
-        let statement = self.parse_statement()?;
-        let mut end = statement.span();
-        let mut statements = vec![statement];
-        while !self.peek(|t|end_predicate(t)).is_some() {
-            self.expect(pred!(SEMICOLON))?;
-            let statement = self.parse_statement()?;
-            end = statement.span();
-            statements.push(statement);
-        }
-
-        Ok(StatementSequence { statements, span: Span::new(start.start, end.end) })
-    }
-
-    fn parse_statement(&mut self) -> Result<Statement, ParserError> {
-        let start_span = self.token_stream.current().span;
-        if self.peek(pred!(IDENT)).is_some() {
-            let target = self.parse_designator()?;
-            if self.eat(pred!(ASSIGN))?.is_some() {
-                let value = self.parse_expression()?;
-                let span = Span::new(target.span.start, value.span().end);
-                Ok(Statement::Assign { target, value, span })
+    fn parse_statement_or_error(&mut self) {
+        let statement = self.checkpoint();
+        let open = self.open;
+        if let Err(error) = self.parse_statement() {
+            self.recover(error, open);
+            self.wrap(statement, NodeKind::Error);
+        }
+    }
+
+    fn starts_statement(&mut self) -> bool {
+        self.peek(pred!(Identifier | If | Case | While | Repeat | For)).is_some()
+    }
+
+    fn parse_statement(&mut self) -> Result<(), ParserError> {
+        let statement = self.checkpoint();
+        let kind = if self.peek(pred!(Identifier)).is_some() {
+            self.parse_designator()?;
+            if self.eat(pred!(Assign)).is_some() {
+                self.parse_expression()?;
+                NodeKind::Assignment
             }
             else {
-                let mut end_span = target.span;
-                let parameters =
-                    if let Some(token) =self.peek(pred!(LPAREN)) {
-                        end_span = token.span;
-                        Some(self.parse_actual_parameters()?)
-                    } else {
-                        None
-                    };
-                Ok(Statement::Call { callee: target, parameters, span: Span::new(start_span.start, end_span.end) })
+                if self.peek(pred!(LParen)).is_some() {
+                    self.parse_actual_parameters()?;
+                }
+                NodeKind::ProcedureCall
             }
         }
-        else if let Some(start) = self.eat(pred!(IF))? {
-            let cond = self.parse_expression()?;
-            self.expect(pred!(THEN))?;
-            let stmts = self.parse_statement_sequence(|t| pred!(ELSE)(t)
-                || pred!(ELSIF)(t)
-                || pred!(END)(t)
-            )?;
-            let elsif_branches = self.parse_elsif_branches(pred!(THEN))?;
-            let else_branch = if self.eat(pred!(ELSE))?.is_some() {
-                Some(self.parse_statement_sequence(pred!(END))?)
+        else if self.eat_opening(pred!(If)).is_some() {
+            self.parse_expression()?;
+            self.expect_after(TokenKind::Then, "after IF condition")?;
+            self.parse_statement_sequence(&[TokenKind::Elsif, TokenKind::Else, TokenKind::End]);
+            self.parse_elsif_branches(TokenKind::Then)?;
+            if self.eat(pred!(Else)).is_some() {
+                self.parse_statement_sequence(&[TokenKind::End]);
             }
-            else {
-                None
-            };
-            let end = self.expect(pred!(END))?;
-            Ok(Statement::If { cond, stmts, else_branch, elsif_branches, span: Span::new(start.span.start, end.span.end) })
-        } else if let Some(start) = self.eat(pred!(CASE))? {
-            let expr = self.parse_expression()?;
-            self.expect(pred!(OF))?;
-            let branches = self.parse_case_branches()?;
-            let end = self.expect(pred!(END))?;
-            Ok(Statement::Case { expr, branches, span: Span::new(start.span.start, end.span.end) })
-        } else if let Some(start) = self.eat(pred!(WHILE))? {
-            let cond = self.parse_expression()?;
-            self.expect(pred!(DO))?;
-            let stmts = self.parse_statement_sequence(|t|pred!(ELSIF)(t)
-            || pred!(END)(t))?;
-            let elsif_branches = self.parse_elsif_branches(pred!(DO))?;
-            let end = self.expect(pred!(END))?;
-            Ok(Statement::While { cond, stmts, elsif_branches, span: Span::new(start.span.start, end.span.end)  })
-        } else if let Some(start) = self.eat(pred!(REPEAT))? {
-            let stmts = self.parse_statement_sequence(pred!(UNTIL))?;
-            self.expect(pred!(UNTIL))?;
-            let cond = self.parse_expression()?;
-            let span = Span::new(start.span.start, cond.span().end);
-            Ok(Statement::Repeat { cond, stmts, span  })
-        } else if let Some(start) = self.eat(pred!(FOR))? {
-            let var = self.parse_ident()?;
-            self.expect(pred!(ASSIGN))?;
-            let low = self.parse_expression()?;
-            self.expect(pred!(TO))?;
-            let high = self.parse_expression()?;
-            let by = if self.eat(pred!(BY))?.is_some() {
-                Some(self.parse_expression()?)
-            } else {
-                None
-            };
-            self.expect(pred!(DO))?;
-            let stmts = self.parse_statement_sequence(pred!(END))?;
-            let span = Span::new(start.span.start, stmts.span().end);
-            Ok(Statement::For { var, low, high, by, stmts, span })
+            self.expect_closing(TokenKind::End, None)?;
+            NodeKind::IfStatement
+        } else if self.eat_opening(pred!(Case)).is_some() {
+            self.parse_expression()?;
+            self.expect_after(TokenKind::Of, "after CASE expression")?;
+            self.parse_case_branches()?;
+            self.expect_closing(TokenKind::End, None)?;
+            NodeKind::CaseStatement
+        } else if self.eat_opening(pred!(While)).is_some() {
+            self.parse_expression()?;
+            self.expect_after(TokenKind::Do, "after WHILE condition")?;
+            self.parse_statement_sequence(&[TokenKind::Elsif, TokenKind::End]);
+            self.parse_elsif_branches(TokenKind::Do)?;
+            self.expect_closing(TokenKind::End, None)?;
+            NodeKind::WhileStatement
+        } else if self.eat_opening(pred!(Repeat)).is_some() {
+            self.parse_statement_sequence(&[TokenKind::Until]);
+            self.expect_closing(TokenKind::Until, None)?;
+            self.parse_expression()?;
+            NodeKind::RepeatStatement
+        } else if self.eat_opening(pred!(For)).is_some() {
+            self.parse_ident()?;
+            self.expect_after(TokenKind::Assign, "after FOR variable")?;
+            self.parse_expression()?;
+            self.expect_after(TokenKind::To, "after FOR start value")?;
+            self.parse_expression()?;
+            if self.eat(pred!(By)).is_some() {
+                self.parse_expression()?;
+            }
+            self.expect_after(TokenKind::Do, "after FOR range")?;
+            self.parse_statement_sequence(&[TokenKind::End]);
+            self.expect_closing(TokenKind::End, None)?;
+            NodeKind::ForStatement
         }
-        else { Err(ParserError::UnexpectedToken { token: self.token_stream.current().clone() }) }
+        else { return Err(self.unexpected(vec!["statement"], None)) };
+        self.wrap(statement, kind);
+        Ok(())
     }
-    fn parse_elsif_branches<F>(&mut self, predicate: F) -> Result<Vec<ElsIf>, ParserError>
-    where
-        F: Fn(&Token) -> bool,
-    {
-        let mut elsif_branches = vec![];
-        while self.peek(pred!(ELSIF)).is_some() {
-            elsif_branches.push(self.parse_elsif_branch(&predicate)?);
+    /// Parses the ELSIF branches of an IF (`then` is THEN) or a WHILE (`then` is DO).
+    fn parse_elsif_branches(&mut self, then: TokenKind) -> Result<(), ParserError> {
+        while self.peek(pred!(Elsif)).is_some() {
+            self.parse_elsif_branch(then)?;
         }
-        Ok(elsif_branches)
+        Ok(())
     }
 
-    fn parse_elsif_branch<F>(&mut self, predicate: F) -> Result<ElsIf, ParserError>
-    where
-        F: Fn(&Token) -> bool,
-    {
-        let elsif = self.expect(pred!(ELSIF))?;
-        let cond = self.parse_expression()?;
-        self.expect(predicate)?;
-        let stmts = self.parse_statement_sequence(|t|
-            pred!(ELSIF)(t) || pred!(ELSE)(t) || pred!(END)(t))?;
-        let span = Span::new(elsif.span.start, stmts.span().end);
-        Ok(ElsIf { cond, stmts, span })
-    }
-
-    fn parse_case_branches(&mut self) -> Result<Vec<Case>, ParserError> {
-        let mut branches = vec![self.parse_case_branch()?];
-        while self.eat(pred!(PIPE))?.is_some() {
-            branches.push(self.parse_case_branch()?);
-        }
-        Ok(branches)
-    }
-
-    fn parse_case_branch(&mut self) -> Result<Case, ParserError> {
-        let start = self.token_stream.current().span;
-        let label_list = self.parse_label_list()?;
-        self.expect(pred!(COLON))?;
-        let statements = self.parse_statement_sequence(
-            |t| pred!(PIPE)(t) || pred!(END)(t))?;
-        let end = statements.span;
-        let span = Span { start: start.start, end: end.end};
-        Ok(Case {
-            label_list,
-            statements,
-            span,
-        })
-    }
-
-    fn parse_label_list(&mut self) -> Result<Vec<Label>, ParserError> {
-        let mut result = vec![self.parse_label()?];
-        while self.eat(pred!(COMMA))?.is_some() {
-            result.push(self.parse_label()?);
-        }
-        Ok(result)
-    }
-
-    fn parse_label(&mut self) -> Result<Label, ParserError> {
-        let value = self.parse_label_value()?;
-        if self.eat(pred!(DOTDOT))?.is_some() {
-            let value2 = self.parse_label_value()?;
-            Ok(Label::Range { low: value, high: value2 })
+    fn parse_elsif_branch(&mut self, then: TokenKind) -> Result<(), ParserError> {
+        let branch = self.checkpoint();
+        self.expect(TokenKind::Elsif)?;
+        self.parse_expression()?;
+        self.expect_after(then, "after ELSIF condition")?;
+        let ends = if then == TokenKind::Then {
+            &[TokenKind::Elsif, TokenKind::Else, TokenKind::End][..]
         } else {
-            Ok(Label::Single { value })
+            &[TokenKind::Elsif, TokenKind::End][..]
+        };
+        self.parse_statement_sequence(ends);
+        self.wrap(branch, NodeKind::ElsIf);
+        Ok(())
+    }
+
+    fn parse_case_branches(&mut self) -> Result<(), ParserError> {
+        self.parse_case_branch()?;
+        while self.eat(pred!(Pipe)).is_some() {
+            self.parse_case_branch()?;
         }
+        Ok(())
     }
 
-    fn parse_label_value(&mut self) -> Result<LabelValue, ParserError> {
-        if self.peek(pred!(NUMBER)).is_some() {
-            let v = self.parse_number()?;
-            let Expression::Int {value, span } = v else {
-                return Err(ParserError::InvalidLabelValue { token: self.token_stream.current().clone() })?
-            };
-            Ok(LabelValue::Integer { value, span })
-        } else if self.peek(pred!(STRING)).is_some() {
-            let v = self.parse_string()?;
-            let Expression::String { value, span } = v else {
-                return Err(ParserError::InvalidLabelValue { token: self.token_stream.current().clone() })?
-            };
-            Ok(LabelValue::String { value, span })
-        } else if self.peek(pred!(IDENT)) .is_some(){
-            let v = self.parse_qualident()?;
-            Ok(LabelValue::QualifiedIdentifier(v))
+    fn parse_case_branch(&mut self) -> Result<(), ParserError> {
+        let branch = self.checkpoint();
+        self.parse_label_list()?;
+        self.expect_after(TokenKind::Colon, "after case labels")?;
+        self.parse_statement_sequence(&[TokenKind::Pipe, TokenKind::End]);
+        self.wrap(branch, NodeKind::Case);
+        Ok(())
+    }
+
+    fn parse_label_list(&mut self) -> Result<(), ParserError> {
+        self.parse_label()?;
+        while self.eat(pred!(Comma)).is_some() {
+            self.parse_label()?;
+        }
+        Ok(())
+    }
+
+    fn parse_label(&mut self) -> Result<(), ParserError> {
+        let label = self.checkpoint();
+        self.parse_label_value()?;
+        if self.eat(pred!(DotDot)).is_some() {
+            self.parse_label_value()?;
+        }
+        self.wrap(label, NodeKind::Label);
+        Ok(())
+    }
+
+    fn parse_label_value(&mut self) -> Result<(), ParserError> {
+        if self.peek(pred!(Number)).is_some() {
+            let token = self.parse_number()?;
+            if token.lexeme().contains('.') {
+                return Err(ParserError::InvalidLabelValue { token });
+            }
+        } else if self.peek(pred!(String)).is_some() {
+            self.parse_string()?;
+        } else if self.peek(pred!(Char)).is_some() {
+            self.parse_char()?;
+        } else if self.peek(pred!(Identifier)) .is_some(){
+            self.parse_qualident()?;
         } else {
-            Err(ParserError::UnexpectedToken { token: self.token_stream.current().clone() })
+            return Err(self.unexpected(vec!["case label"], None));
         }
+        Ok(())
     }
-    fn parse_designator(&mut self) -> Result<Designator, ParserError> {
-        let start = self.token_stream.current().span;
-        let head = self.parse_qualident()?;
-        let selectors = self.parse_selectors()?;
-        let end = self.token_stream.current().span;
-        Ok(Designator{ head, selectors, span: Span::new(start.start, end.end)})
+    fn parse_designator(&mut self) -> Result<(), ParserError> {
+        let designator = self.checkpoint();
+        self.parse_qualident()?;
+        self.parse_selectors()?;
+        self.wrap(designator, NodeKind::Designator);
+        Ok(())
     }
 
-    fn parse_qualident(&mut self) -> Result<QualifiedIdentifier, ParserError> {
-        let mut parts = vec![self.parse_ident()?];
-        if self.eat(pred!(DOT))?.is_some() {
-            parts.push(self.parse_ident()?);
+    fn parse_qualident(&mut self) -> Result<(), ParserError> {
+        let qualident = self.checkpoint();
+        self.parse_ident()?;
+        if self.eat(pred!(Dot)).is_some() {
+            self.parse_ident()?;
         }
-        Ok(QualifiedIdentifier{ parts })
+        self.wrap(qualident, NodeKind::QualIdent);
+        Ok(())
     }
 
-    fn parse_selectors(&mut self) -> Result<Vec<Selector>, ParserError> {
-        let mut result = vec![];
-        while self.peek(|t| pred!(DOT)(t)
-            || pred!(LBRACKET)(t)
-            || pred!(CARET)(t)
-        ).is_some() || self.type_guard_selector() {
-            result.push(self.parse_selector()?);
+    fn parse_selectors(&mut self) -> Result<(), ParserError> {
+        while self.peek(pred!(Dot | LBracket | Caret)).is_some() || self.type_guard_selector() {
+            self.parse_selector()?;
         }
-        Ok(result)
+        Ok(())
     }
 
     fn type_guard_selector(&mut self) -> bool {
-        if !pred!(LPAREN)(self.token_stream.current()) {
+        if !pred!(LParen)(self.token_stream.current()) {
             return false;
         }
         match self.token_stream.peek_n(4).as_slice() {
-            [t0, t1, ..] if pred!(IDENT)(t0) && pred!(RPAREN)(t1) => true,
+            [t0, t1, ..] if pred!(Identifier)(t0) && pred!(RParen)(t1) => true,
 
             [t0, t1, t2, t3]
-            if pred!(IDENT)(t0) && pred!(DOT)(t1) && pred!(IDENT)(t2) && pred!(RPAREN)(t3) => true,
+            if pred!(Identifier)(t0) && pred!(Dot)(t1) && pred!(Identifier)(t2) && pred!(RParen)(t3) => true,
 
             _ => false,
         }
     }
 
-    fn parse_selector(&mut self) -> Result<Selector, ParserError> {
-        if self.eat(pred!(DOT))?.is_some() {
-            Ok(Selector::Field(self.parse_ident()?))
-        } else if let Some(start) =self.eat(pred!(LBRACKET))? {
-            let index = self.parse_expression_list()?;
-            let end = self.expect(pred!(RBRACKET))?;
-            Ok(Selector::Index(index, Span::new(start.span.start, end.span.end)))
-        } else if let Some(token) =self.eat(pred!(CARET))? {
-            Ok(Selector::Deref(token.span))
-        } else if let Some(start) =self.eat(pred!(LPAREN))? {
-            let guard = self.parse_qualident()?;
-            let end =self.expect(pred!(RPAREN))?;
-            Ok(Selector::TypeGuard(guard, Span::new(start.span.start, end.span.end)))
+    fn parse_selector(&mut self) -> Result<(), ParserError> {
+        let selector = self.checkpoint();
+        let kind = if self.eat(pred!(Dot)).is_some() {
+            self.parse_ident()?;
+            NodeKind::FieldSelector
+        } else if self.eat(pred!(LBracket)).is_some() {
+            self.parse_expression_list()?;
+            self.expect_after(TokenKind::RBracket, "after index")?;
+            NodeKind::IndexSelector
+        } else if self.eat(pred!(Caret)).is_some() {
+            NodeKind::DerefSelector
+        } else if self.eat(pred!(LParen)).is_some() {
+            self.parse_qualident()?;
+            self.expect_after(TokenKind::RParen, "after type guard")?;
+            NodeKind::TypeGuardSelector
         }
         else {
-            Err(ParserError::UnexpectedToken { token: self.token_stream.current().clone() })
-        }
+            return Err(self.unexpected(vec!["selector"], None));
+        };
+        self.wrap(selector, kind);
+        Ok(())
     }
 
-    fn parse_actual_parameters(&mut self) -> Result<Vec<Expression>, ParserError> {
-        self.expect(pred!(LPAREN))?;
-        if self.eat(pred!(RPAREN))?.is_some() {
-            return Ok(vec![]);
+    fn parse_actual_parameters(&mut self) -> Result<(), ParserError> {
+        let parameters = self.checkpoint();
+        self.expect(TokenKind::LParen)?;
+        if self.eat(pred!(RParen)).is_none() {
+            self.parse_expression_list()?;
+            self.expect_after(TokenKind::RParen, "after arguments")?;
         }
-        let result = self.parse_expression_list()?;
-        self.expect(pred!(RPAREN))?;
-        Ok(result)
+        self.wrap(parameters, NodeKind::ActualParameters);
+        Ok(())
     }
 
-    fn parse_identdef_list(&mut self) -> Result<Vec<IdentifierDef>, ParserError> {
-        let mut result = vec![self.parse_identdef()?];
-        while self.eat(pred!(COMMA))?.is_some() {
-            result.push(self.parse_identdef()?);
+    fn parse_identdef_list(&mut self) -> Result<(), ParserError> {
+        self.parse_identdef()?;
+        while self.eat(pred!(Comma)).is_some() {
+            self.parse_identdef()?;
         }
-        Ok(result)
+        Ok(())
     }
 
-    fn parse_identdef(&mut self) -> Result<IdentifierDef, ParserError> {
-        let ident = self.parse_ident()?;
-        let star = self.eat(pred!(STAR))?;
-        if star.is_some() {
-            let star = star.unwrap();
-            let span = Span::new(ident.span.start, star.span.end);
-            Ok(IdentifierDef {
-                ident,
-                exported: true,
-                span,
-            })
-        } else {
-            let span = ident.span;
-            Ok(IdentifierDef {
-                ident,
-                exported: false,
-                span,
-            })
-        }
+    fn parse_identdef(&mut self) -> Result<(), ParserError> {
+        let identdef = self.checkpoint();
+        self.parse_ident()?;
+        self.eat(pred!(Star));
+        self.wrap(identdef, NodeKind::IdentDef);
+        Ok(())
     }
 
-    fn parse_ident_list(&mut self) -> Result<Vec<Identifier>, ParserError> {
-        let mut result = vec![self.parse_ident()?];
-        while self.eat(pred!(COMMA))?.is_some() {
-            result.push(self.parse_ident()?);
+    fn parse_ident_list(&mut self) -> Result<(), ParserError> {
+        self.parse_ident()?;
+        while self.eat(pred!(Comma)).is_some() {
+            self.parse_ident()?;
         }
-        Ok(result)
+        Ok(())
     }
 
-    fn parse_ident(&mut self) -> Result<Identifier, ParserError> {
-        let token = self.expect(pred!(IDENT))?;
-        Ok(Identifier { text: token.lexeme, span: token.span })
+    fn parse_ident(&mut self) -> Result<(), ParserError> {
+        self.expect(TokenKind::Identifier)?;
+        Ok(())
     }
 
-    fn parse_procedure_heading(&mut self) -> Result<ProcedureHeader, ParserError> {
-        let start = self.expect(pred!(PROCEDURE))?;
-        let name = self.parse_identdef()?;
-        let params = self.parse_formal_parameters()?;
-        let p = params.clone();
-        let end = if p.is_some() {
-            p.unwrap().span
-        } else { name.span };
-        let span = Span::new(start.span.start, end.end);
-        Ok(ProcedureHeader { name, params, span })
+    fn parse_procedure_heading(&mut self) -> Result<(), ParserError> {
+        let heading = self.checkpoint();
+        self.expect(TokenKind::Procedure)?;
+        self.parse_identdef()?;
+        self.parse_formal_parameters()?;
+        self.wrap(heading, NodeKind::ProcedureHeading);
+        Ok(())
     }
 
-    fn parse_procedure_body(&mut self) -> Result<ProcedureBody, ParserError> {
-        let start = self.token_stream.current().span.start;
-        let declarations = self.parse_declarations()?;
-        let stmts = self.parse_statement_sequence_with_begin(
-            |t| pred!(RETURN)(t) || pred!(END)(t)
-        )?;
-        let ret = if self.eat(pred!(RETURN))?.is_some() {
-            Some(self.parse_expression()?)
-        } else {
-            None
-        };
-        let end = self.expect(pred!(END))?.span.end;
+    fn parse_procedure_body(&mut self) -> Result<(), ParserError> {
+        let body = self.checkpoint();
+        self.parse_declarations();
+        self.parse_statement_sequence_with_begin(&[TokenKind::Return, TokenKind::End]);
+        if self.eat(pred!(Return)).is_some() {
+            self.parse_expression_or_error();
+        }
+        self.expect(TokenKind::End)?;
+        self.wrap(body, NodeKind::ProcedureBody);
+        Ok(())
+    }
+    fn expect(&mut self, expected: TokenKind) -> Result<Token, ParserError> {
+        self.expect_in(expected, None)
+    }
 
-        Ok(ProcedureBody { declarations, stmts, ret, span: Span::new(start, end) })
+    /// Like `expect`, naming where the token belongs in the error, e.g. "after IF condition".
+    fn expect_after(&mut self, expected: TokenKind, context: &'static str) -> Result<Token, ParserError> {
+        self.expect_in(expected, Some(context))
     }
-    fn expect<F>(&mut self, predicate: F) -> Result<Token, ParserError>
-    where
-        F: Fn(&Token) -> bool,
-    {
+
+    fn expect_in(&mut self, expected: TokenKind, context: Option<&'static str>) -> Result<Token, ParserError> {
         let token = self.token_stream.current();
 
-        if predicate(token) {
+        if token.kind == expected {
             let token = token.clone();
-            self.token_stream.advance()?;
+            self.advance();
             Ok(token)
         } else {
-            Err(ParserError::UnexpectedToken{token: token.clone()})
+            Err(self.unexpected(vec![expected.name()], context))
+        }
+    }
+
+    /// Expects `separator` between the items of a list that `close` ends.
+    fn expect_separator(&mut self, separator: TokenKind, close: TokenKind) -> Result<(), ParserError> {
+        if self.eat(|token| token.kind == separator).is_none() {
+            return Err(self.unexpected(vec![separator.name(), close.name()], None));
         }
+        Ok(())
+    }
+
+    fn unexpected(&self, expected: Vec<&'static str>, context: Option<&'static str>) -> ParserError {
+        ParserError::UnexpectedToken { expected, context, found: self.token_stream.current().clone() }
     }
 
-    fn eat<F>(&mut self, predicate: F) -> Result<Option<Token>, ParserError>
+    fn eat<F>(&mut self, predicate: F) -> Option<Token>
     where
         F: Fn(&Token) -> bool,
     {
@@ -972,10 +1144,10 @@ impl<'a> Parser<'a> {
 
         if predicate(token) {
             let token = token.clone();
-            self.token_stream.advance()?;
-            Ok(Some(token))
+            self.advance();
+            Some(token)
         } else {
-            Ok(None)
+            None
         }
     }
 
@@ -992,7 +1164,168 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn at_eof(&self) -> bool {
+        self.token_stream.current().kind == TokenKind::Eof
+    }
 
+    /// Consumes the current token into the syntax tree and moves to the next.
+    fn advance(&mut self) {
+        self.builder.token(self.token_stream.current(), self.token_stream.text());
+        self.step();
+    }
+
+    /// Moves to the next token, recording a lexical error and carrying on past it.
+    fn step(&mut self) {
+        self.previous_end = self.token_stream.current().span.end;
+        self.previous_index = self.token_stream.index();
+        self.since_error = self.since_error.saturating_add(1);
+        if let Err(error) = self.token_stream.advance() {
+            self.errors.push(error);
+            self.since_error = 0;
+            self.faults += 1;
+        }
+        self.builder.trivia(self.token_stream.trivia(), self.token_stream.text());
+    }
+
+    // --------------------------- SYNTAX TREE ---------------------------
+    fn checkpoint(&mut self) -> Checkpoint {
+        self.builder.checkpoint()
+    }
+
+    /// Makes the tokens consumed since `checkpoint` a node of `kind`.
+    fn wrap(&mut self, checkpoint: Checkpoint, kind: NodeKind) {
+        self.builder.wrap(checkpoint, kind);
+    }
+
+    /// Makes the tokens consumed since `checkpoint`, if any, a `Skipped` node.
+    fn skipped(&mut self, checkpoint: Checkpoint) {
+        if !self.builder.is_empty_since(checkpoint) {
+            self.builder.wrap(checkpoint, NodeKind::Skipped);
+        }
+    }
+
+    // --------------------------- ERROR RECOVERY ---------------------------
+    /// Eats a token that opens a construct closed by END (or UNTIL), counting it as open.
+    fn eat_opening<F>(&mut self, predicate: F) -> Option<Token>
+    where
+        F: Fn(&Token) -> bool,
+    {
+        let token = self.eat(predicate)?;
+        self.open += 1;
+        Some(token)
+    }
+
+    /// Expects the END (or UNTIL) closing the innermost open construct.
+    fn expect_closing(&mut self, expected: TokenKind, context: Option<&'static str>) -> Result<Token, ParserError> {
+        let token = self.expect_in(expected, context)?;
+        self.open -= 1;
+        Ok(token)
+    }
+
+    /// Records `error`, unless it follows the previous one so closely that it is most likely a
+    /// consequence of it.
+    fn record(&mut self, error: ParserError) {
+        if self.since_error >= ERROR_DISTANCE {
+            self.errors.push(error);
+        }
+        self.since_error = 0;
+        self.faults += 1;
+    }
+
+    /// Records `error` and skips to where parsing can resume. `open` is the number of open
+    /// constructs where the recovering construct began; any opened since are skipped to their end.
+    fn recover(&mut self, error: ParserError, open: usize) {
+        self.record(error);
+        self.synchronize(self.open - open);
+        self.open = open;
+    }
+    /// Skips tokens until one parsing can resume at: past the END (or UNTIL) of `depth` constructs
+    /// left open, then up to a synchronizing token outside any construct skipped along the way. The
+    /// start of a declaration section ends the skipping regardless.
+    fn synchronize(&mut self, mut depth: usize) {
+        let mut nested = 0;
+        loop {
+            let token = self.token_stream.current();
+            if matches!(token.kind, TokenKind::Eof | TokenKind::Begin | TokenKind::Const | TokenKind::Type) {
+                return;
+            }
+            if Self::opens(token) {
+                nested += 1;
+            } else if matches!(token.kind, TokenKind::End | TokenKind::Until) {
+                if nested > 0 {
+                    nested -= 1;
+                } else if depth > 0 {
+                    depth -= 1;
+                } else {
+                    return;
+                }
+            } else if depth == 0 && nested == 0 && Self::synchronizes(token) {
+                return;
+            }
+            self.advance();
+        }
+    }
+
+    fn opens(token: &Token) -> bool {
+        pred!(If | Case | While | Repeat | For | Record)(token)
+    }
+
+    fn synchronizes(token: &Token) -> bool {
+        pred!(Semicolon | Elsif | Else | Pipe | Return | Procedure | Var)(token)
+    }
+
+    /// Skips the rest of a procedure declaration that failed to parse: past its `END name;`, or
+    /// up to the next procedure or the end of the module.
+    fn skip_procedure(&mut self) {
+        loop {
+            let token = self.token_stream.current();
+            if matches!(token.kind, TokenKind::Eof | TokenKind::Procedure) {
+                return;
+            }
+            if token.kind == TokenKind::End {
+                match self.token_stream.peek_n(2).as_slice() {
+                    [name, semicolon] if pred!(Identifier)(name) && pred!(Semicolon)(semicolon) => {
+                        for _ in 0..3 {
+                            self.advance();
+                        }
+                        return;
+                    }
+                    [name, dot] if pred!(Identifier)(name) && pred!(Dot)(dot) => return,
+                    _ => {}
+                }
+            }
+            self.advance();
+        }
+    }
+
+    // --------------------------- REUSE ---------------------------
+    /// Takes over the declaration of `kind` at the current token from a previous parse, if its
+    /// tokens are unchanged, and moves past it and its semicolon.
+    fn take_over(&mut self, kind: NodeKind) -> bool {
+        let index = self.token_stream.index();
+        let Some((node, tokens)) = self.reuse.as_mut().and_then(|reuse| reuse.take(index, kind)) else {
+            return false;
+        };
+        self.builder.node(node);
+        while self.token_stream.index() < index + tokens {
+            self.step();
+        }
+        true
+    }
+
+    /// The current token and the number of faults so far, where a declaration starts.
+    fn mark(&self) -> (usize, usize) {
+        (self.token_stream.index(), self.faults)
+    }
+
+    /// Records the declaration from `mark` up to the last token consumed, semicolon included, as
+    /// one the next parse may take over, provided it parsed without errors.
+    fn remember(&mut self, (index, faults): (usize, usize)) {
+        let tokens = self.previous_index + 1 - index;
+        if self.faults == faults && let Some(reuse) = self.reuse.as_mut() {
+            reuse.remember(index, tokens);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1011,6 +1344,8 @@ mod tests {
     mod expressions {
         use super::*;
         use crate::frontend::ast::{BinaryOperation, ConstDeclaration, Expression, Selector, UnaryOperation};
+        use crate::frontend::literal::IntegerWidth;
+        use crate::frontend::parser::ParserError;
 
         #[test]
         fn parse_integer_const() {
@@ -1028,6 +1363,47 @@ mod tests {
             let Expression::Int { value: 256, .. } = value else { panic!("hex"); };
         }
 
+        #[test]
+        fn wraps_hex_constants_into_the_integer_width() {
+            let source = "MODULE m; CONST foo=0FFFFFFFFH; END m .";
+            let module = Parser::new(Lexer::new(source)).with_integer_width(IntegerWidth::Bits32).parse().unwrap();
+            let ConstDeclaration { value, .. } = &module.declarations.const_declarations[0];
+            let Expression::Int { value: -1, .. } = value else { panic!("hex"); };
+
+            let ConstDeclaration { value, .. } = &parse(source).declarations.const_declarations[0];
+            let Expression::Int { value: 0xFFFFFFFF, .. } = value else { panic!("hex"); };
+        }
+
+        #[test]
+        fn parse_min_integer_const() {
+            let source = "MODULE m; CONST foo=-2147483648; END m .";
+            let module = Parser::new(Lexer::new(source)).with_integer_width(IntegerWidth::Bits32).parse().unwrap();
+            let ConstDeclaration { value, .. } = &module.declarations.const_declarations[0];
+            let Expression::Int { value: -2147483648, span } = value else { panic!("{value:?}"); };
+            assert_eq!((span.start.column, span.end.column), (21, 32));
+
+            let module = parse("MODULE m; CONST foo=-9223372036854775808 + 1; END m .");
+            let ConstDeclaration { value, .. } = &module.declarations.const_declarations[0];
+            let Expression::Binary { lhs, .. } = value else { panic!("{value:?}"); };
+            let Expression::Int { value: i64::MIN, .. } = **lhs else { panic!("{lhs:?}"); };
+        }
+
+        #[test]
+        fn rejects_literals_out_of_range() {
+            let errors = |source: &str| {
+                let source = format!("MODULE m; CONST foo={source}; END m .");
+                Parser::new(Lexer::new(&source)).with_integer_width(IntegerWidth::Bits32).parse_with_recovery().1
+            };
+
+            let [ParserError::IntegerOutOfRange { token, width: IntegerWidth::Bits32 }] = &errors("2147483648")[..] else { panic!(); };
+            assert_eq!((token.span.start.column, token.span.end.column), (21, 31));
+            assert!(matches!(&errors("-2147483648 * 2")[..], [ParserError::IntegerOutOfRange { .. }]));
+            assert!(matches!(&errors("-(2147483648)")[..], [ParserError::IntegerOutOfRange { .. }]));
+            assert!(matches!(&errors("100000000H")[..], [ParserError::IntegerOutOfRange { .. }]));
+            assert!(matches!(&errors("1.0E400")[..], [ParserError::RealOutOfRange { .. }]));
+            assert!(errors("-2147483648 - 0").is_empty());
+        }
+
         #[test]
         fn parse_real_const() {
             let module = parse("MODULE m; CONST foo=12.3; END m .");
@@ -1058,8 +1434,17 @@ mod tests {
             let module = parse("MODULE m; CONST foo=20X; END m .");
             let decls = module.declarations;
             let ConstDeclaration { value, .. } = &decls.const_declarations[0];
-            let Expression::String { value, .. } = value else { panic!("character"); };
-            assert_eq!(value, " ");
+            let Expression::Char { value, .. } = value else { panic!("character"); };
+            assert_eq!(*value, b' ');
+        }
+
+        #[test]
+        fn rejects_character_codes_above_0ffx() {
+            let mut parser = Parser::new(Lexer::new("MODULE m; CONST a = 0FFX; b = 100X; END m ."));
+            let (_, errors) = parser.parse_with_recovery();
+
+            let [ParserError::InvalidCharacter { token }] = &errors[..] else { panic!("{errors:?}"); };
+            assert_eq!(token.lexeme(), "100X");
         }
 
         #[test]
@@ -1256,7 +1641,7 @@ mod tests {
             assert_eq!(designator.head.parts.len(), 1);
             assert_eq!(designator.head.parts[0].text, "bar");
             assert_eq!(designator.selectors.len(), 0);
-            assert_eq!(actual_parameters.is_some(), true);
+            assert!(actual_parameters.is_some());
             let parameters = actual_parameters.clone().unwrap();
             assert_eq!(parameters.len(), 0);
         }
@@ -1270,7 +1655,7 @@ mod tests {
             assert_eq!(designator.head.parts.len(), 1);
             assert_eq!(designator.head.parts[0].text, "bar");
             assert_eq!(designator.selectors.len(), 0);
-            assert_eq!(actual_parameters.is_some(), true);
+            assert!(actual_parameters.is_some());
             let parameters = actual_parameters.clone().unwrap();
             assert_eq!(parameters.len(), 2);
             let mut value = &parameters[0];
@@ -1289,7 +1674,7 @@ mod tests {
             assert_eq!(designator.head.parts[0].text, "bar");
             assert_eq!(designator.head.parts[1].text, "baz");
             assert_eq!(designator.selectors.len(), 0);
-            assert_eq!(actual_parameters.is_some(), true);
+            assert!(actual_parameters.is_some());
             let parameters = actual_parameters.clone().unwrap();
             assert_eq!(parameters.len(), 0);
         }
@@ -1307,7 +1692,7 @@ mod tests {
             let Selector::TypeGuard (type_guard, ..) = selector else { panic!("Designator with simple type guard selector and selector"); };
             assert_eq!(type_guard.parts.len(), 1);
             assert_eq!(type_guard.parts[0].text, "baz");
-            assert_eq!(actual_parameters.is_some(), true);
+            assert!(actual_parameters.is_some());
             let parameters = actual_parameters.clone().unwrap();
             assert_eq!(parameters.len(), 0);
         }
@@ -1325,7 +1710,7 @@ mod tests {
             let Selector::TypeGuard (type_guard, ..) = selector else { panic!("Designator with simple type guard selector and selector"); };
             assert_eq!(type_guard.parts.len(), 1);
             assert_eq!(type_guard.parts[0].text, "fez");
-            assert_eq!(actual_parameters.is_some(), false);
+            assert!(actual_parameters.is_none());
         }
 
         #[test]
@@ -1341,7 +1726,7 @@ mod tests {
             let Selector::TypeGuard (type_guard, ..) = selector else { panic!("Designator with simple type guard selector and selector"); };
             assert_eq!(type_guard.parts.len(), 1);
             assert_eq!(type_guard.parts[0].text, "baz");
-            assert_eq!(actual_parameters.is_some(), true);
+            assert!(actual_parameters.is_some());
             let parameters = actual_parameters.clone().unwrap();
             assert_eq!(parameters.len(), 2);
             let mut value = &parameters[0];
@@ -1478,6 +1863,17 @@ mod tests {
             let Expression::Int { value: 2,  .. } = &**operand else { panic!("-2"); };
         }
 
+        #[test]
+        fn parse_left_associative_chain() {
+            let module = parse("MODULE m; CONST foo=1 - 2 - 3 * 4 * 5; END m .");
+            let decls = module.declarations;
+            let ConstDeclaration { value, .. } = &decls.const_declarations[0];
+            let Expression::Binary { op: BinaryOperation::Subtraction, lhs, rhs, .. } = value else { panic!("Subtraction"); };
+            let Expression::Binary { op: BinaryOperation::Subtraction, .. } = &**lhs else { panic!("1 - 2"); };
+            let Expression::Binary { op: BinaryOperation::Multiplication, lhs, .. } = &**rhs else { panic!("3 * 4 * 5"); };
+            let Expression::Binary { op: BinaryOperation::Multiplication, .. } = &**lhs else { panic!("3 * 4"); };
+        }
+
         #[test]
         fn parse_equal() {
             let module = parse("MODULE m; CONST foo=1 = 2; END m .");
@@ -1727,8 +2123,18 @@ mod tests {
             let module = parse("MODULE m; TYPE foo=PROCEDURE(); END m .");
             let decls = module.declarations;
             let TypeDeclaration { ty, .. } = &decls.type_declarations[0];
-            let Type::Procedure { params: None, .. } = ty else { panic!("Procedure type"); };
+            let Type::Procedure { params: Some(params), .. } = ty else { panic!("Procedure type"); };
 
+            assert!(params.sections.is_empty());
+            assert!(params.return_type.is_none());
+        }
+
+        #[test]
+        fn parse_procedure_type_without_parameter_list() {
+            let module = parse("MODULE m; TYPE foo=PROCEDURE; END m .");
+            let decls = module.declarations;
+            let TypeDeclaration { ty, .. } = &decls.type_declarations[0];
+            let Type::Procedure { params: None, .. } = ty else { panic!("Procedure type"); };
         }
 
         #[test]
@@ -1978,6 +2384,17 @@ mod tests {
             assert_eq!(branches[0].statements.statements.len(), 2);
         }
 
+        #[test]
+        fn parse_character_case_labels() {
+            let module = parse("MODULE m; BEGIN CASE c OF 41X .. \"Z\": foo := 1 END END m .");
+            let stmts = module.stmts.unwrap();
+            let Statement::Case  { branches, .. } = &stmts.statements[0] else { panic!("Expected case statement"); };
+            let Label::Range { low, high } = &branches[0].label_list[0] else { panic!("Label"); };
+            let LabelValue::Char { value: b'A', .. } = low else { panic!("Label value"); };
+            let LabelValue::String { value, .. } = high else { panic!("Label value"); };
+            assert_eq!(value, "Z");
+        }
+
         #[test]
         fn parse_multiple_cases_statement() {
             let module = parse("MODULE m; BEGIN CASE TRUE OF 1 .. 3: foo := 1 | 4: bar := 45 END END m .");
@@ -2045,7 +2462,7 @@ mod tests {
             assert!(module.stmts.is_some());
             let stmts = module.stmts.unwrap();
             let Statement::For { by, .. } = &stmts.statements[0] else { panic!("Expected for statement"); };
-            let Expression::Int { value: 2, .. } = by.as_ref().unwrap() else { panic!("By"); };
+            let Expression::Int { value: 2, .. } = by.as_deref().unwrap() else { panic!("By"); };
         }
     }
     mod procedures {
@@ -2063,18 +2480,18 @@ mod tests {
 
             assert_eq!(procedure.name.text, "add");
             assert_eq!(header.name.ident.text, "add");
-            assert_eq!(header.name.exported, true);
+            assert!(header.name.exported);
             let Some(ref parameters) = header.params else { panic!("Expected parameters"); };
             assert_eq!(parameters.sections.len(), 2);
             assert_eq!(parameters.sections[0].names.len(), 2);
             assert_eq!(parameters.sections[0].names[0].text, "x");
             assert_eq!(parameters.sections[0].names[1].text, "y");
-            assert_eq!(parameters.sections[0].by_ref, false);
+            assert!(!parameters.sections[0].by_ref);
             assert_eq!(parameters.sections[0].ty.base.parts.len(), 1);
             assert_eq!(parameters.sections[0].ty.base.parts[0].text, "INTEGER");
             assert_eq!(parameters.sections[1].names.len(), 1);
             assert_eq!(parameters.sections[1].names[0].text, "z");
-            assert_eq!(parameters.sections[1].by_ref, true);
+            assert!(parameters.sections[1].by_ref);
             assert_eq!(parameters.sections[1].ty.base.parts.len(), 1);
             assert_eq!(parameters.sections[1].ty.base.parts[0].text, "INTEGER");
             let Some(ref return_type) = parameters.return_type else { panic!("Expected return type"); };
@@ -2086,7 +2503,7 @@ mod tests {
             let var = &vars[0];
             assert_eq!(var.variables.len(), 1);
             assert_eq!(var.variables[0].ident.text, "t");
-            assert_eq!(var.variables[0].exported, false);
+            assert!(!var.variables[0].exported);
             let Type::Named { ref name } = var.ty else { panic!("Expected type"); };
             assert_eq!(name.parts.len(), 1);
             assert_eq!(name.parts[0].text, "INTEGER");
@@ -2142,7 +2559,7 @@ mod tests {
 
             let Some(ref parameters) = header.params else { panic!("Expected parameters"); };
             assert_eq!(parameters.sections.len(), 0);
-            assert_eq!(parameters.return_type.is_none(), true);
+            assert!(parameters.return_type.is_none());
         }
 
         #[test]
@@ -2153,7 +2570,7 @@ mod tests {
             let procedure = &decls.procedure_declarations[0];
             let header = &procedure.header;
 
-            assert_eq!(header.params.is_none(), true);
+            assert!(header.params.is_none());
         }
 
         #[test]
@@ -2165,7 +2582,7 @@ mod tests {
             let procedure = &decls.procedure_declarations[0];
             let body = &procedure.body;
 
-            assert_eq!(body.stmts.is_none(), true);
+            assert!(body.stmts.is_none());
         }
 
         #[test]
@@ -2208,18 +2625,18 @@ mod tests {
         fn parse_imports_with_alias() {
             let module = parse("MODULE m; IMPORT m1 := m3, m2, m4 := m6; END m .");
             assert_eq!(module.imports.len(), 3);
-            assert_eq!(module.imports[0].module.text, "m1");
+            assert_eq!(module.imports[0].module.text, "m3");
             let Some(alias) = &module.imports[0].alias else { panic!("Expected alias"); };
-            assert_eq!(alias.text, "m3");
+            assert_eq!(alias.text, "m1");
             assert_eq!(module.imports[1].module.text, "m2");
-            assert_eq!(module.imports[2].module.text, "m4");
+            assert_eq!(module.imports[2].module.text, "m6");
             let Some(alias) = &module.imports[2].alias else { panic!("Expected alias"); };
-            assert_eq!(alias.text, "m6");
+            assert_eq!(alias.text, "m4");
         }
 
         #[test]
         fn parse_module() {
-            let module = parse("MODULE m1; IMPORT m1 := m3, m2; CONST N = 100; TYPE TABLE = ARRAY N OF REAL; VAR x, y: REAL; PROCEDURE add(i, j: REAL): REAL; RETURN i+j END add; BEGIN foo := 0; FOR i := 1 TO N BY 2 DO foo := add(foo, i) END m2 .");
+            let module = parse("MODULE m1; IMPORT m1 := m3, m2; CONST N = 100; TYPE TABLE = ARRAY N OF REAL; VAR x, y: REAL; PROCEDURE add(i, j: REAL): REAL; RETURN i+j END add; BEGIN foo := 0; FOR i := 1 TO N BY 2 DO foo := add(foo, i) END END m2 .");
             assert_eq!(module.name.text, "m1");
             assert_eq!(module.end_name.text, "m2");
             assert_eq!(module.imports.len(), 2);
@@ -2244,4 +2661,211 @@ mod tests {
             assert_eq!(module.end_name.text, "m");
         }
     }
-}
\ No newline at end of file
+
+    mod comments {
+        use crate::frontend::ast::{Comment, Comments, Module};
+        use crate::frontend::lexer::Lexer;
+        use crate::frontend::parser::Parser;
+        use crate::frontend::visit::{Visitor, VisitorMut};
+
+        const SOURCE: &str = "MODULE M;
+CONST
+  (* the answer *)
+  a = 42; (* not 41 *)
+  b = 1;
+VAR x: INTEGER; (* counter *)
+
+(* Resets x. *)
+PROCEDURE P; BEGIN x := (* inside *) 0 (* zero *) END P;
+BEGIN
+  (* first *)
+  x := a; (* set *)
+  IF x > 0 THEN
+    (* inner *)
+    P
+  END (* after if *)
+END M.";
+
+        fn texts(comments: &[Comment]) -> Vec<&str> {
+            comments.iter().map(|comment| comment.text.as_str()).collect()
+        }
+
+        /// The comments attached to the declarations and statements of `module`, in source order.
+        fn attached_comments(module: &Module) -> Vec<Comments> {
+            struct Collect(Vec<Comments>);
+            impl Visitor for Collect {
+                fn visit_comments(&mut self, comments: &Comments) {
+                    if !comments.leading.is_empty() || !comments.trailing.is_empty() {
+                        self.0.push(comments.clone());
+                    }
+                }
+            }
+            let mut collect = Collect(vec![]);
+            collect.visit_module(module);
+            collect.0
+        }
+
+        #[test]
+        fn attaches_leading_and_trailing_comments() {
+            let module = Parser::new(Lexer::with_trivia(SOURCE)).parse().unwrap();
+            let comments = attached_comments(&module);
+            let attached: Vec<_> = comments.iter().map(|comments| (texts(&comments.leading), texts(&comments.trailing))).collect();
+            let none: Vec<&str> = vec![];
+            assert_eq!(attached, [
+                (vec!["(* the answer *)"], vec!["(* not 41 *)"]),
+                (none.clone(), vec!["(* counter *)"]),
+                (vec!["(* Resets x. *)"], none.clone()),
+                (none.clone(), vec!["(* zero *)"]),
+                (vec!["(* first *)"], vec!["(* set *)"]),
+                (none.clone(), vec!["(* after if *)"]),
+                (vec!["(* inner *)"], none.clone()),
+            ]);
+
+            let procedure = &module.declarations.procedure_declarations[0];
+            assert_eq!(procedure.comments.leading[0].span.start.line, 8);
+        }
+
+        #[test]
+        fn trivia_only_adds_comments() {
+            let mut with_trivia = Parser::new(Lexer::with_trivia(SOURCE)).parse().unwrap();
+            let plain = Parser::new(Lexer::new(SOURCE)).parse().unwrap();
+            struct Clear;
+            impl VisitorMut for Clear {
+                fn visit_comments_mut(&mut self, comments: &mut Comments) {
+                    *comments = Comments::default();
+                }
+            }
+            assert!(attached_comments(&plain).is_empty());
+            Clear.visit_module_mut(&mut with_trivia);
+            assert_eq!(with_trivia, plain);
+        }
+
+        #[test]
+        fn recovers_from_errors_with_trivia() {
+            let mut parser = Parser::new(Lexer::with_trivia("MODULE M; (* c *) BEGIN x := ; (* d *) y := 1 END M."));
+            let (module, errors) = parser.parse_with_recovery();
+            assert_eq!(errors.len(), 1);
+            let comments = attached_comments(&module);
+            let trailing: Vec<Vec<&str>> = comments.iter().map(|comments| texts(&comments.trailing)).collect();
+            assert_eq!(trailing, [vec!["(* d *)"]]);
+        }
+    }
+
+    mod recovery {
+        use crate::frontend::ast::{Expression, Module, Statement, Type};
+        use crate::frontend::lexer::{Lexer, LexerError};
+        use crate::frontend::parser::{Parser, ParserError};
+
+        fn parse_with_errors(source: &str) -> (Module, Vec<ParserError>) {
+            Parser::new(Lexer::new(source)).parse_with_recovery()
+        }
+
+        fn found(errors: &[ParserError]) -> Vec<(usize, String)> {
+            errors.iter().map(|error| match error {
+                ParserError::UnexpectedToken { found, .. } => (found.span.start.column, found.lexeme().to_string()),
+                other => panic!("unexpected error {other:?}"),
+            }).collect()
+        }
+
+        #[test]
+        fn reports_an_error_in_each_broken_statement() {
+            let (module, errors) = parse_with_errors("MODULE m; BEGIN x := ; x := 1; x := 2 + ; IF x THEN x := ) END END m.");
+            assert_eq!(found(&errors), vec![(22, ";".to_string()), (41, ";".to_string()), (58, ")".to_string())]);
+            let statements = module.stmts.unwrap().statements;
+            assert!(matches!(statements.as_slice(), [Statement::Error { .. }, Statement::Assign { .. }, Statement::Error { .. }, Statement::If { .. }]));
+            let Statement::If { stmts, .. } = &statements[3] else { unreachable!() };
+            assert!(matches!(stmts.statements.as_slice(), [Statement::Error { .. }]));
+        }
+
+        #[test]
+        fn keeps_the_declarations_around_broken_ones() {
+            let (module, errors) = parse_with_errors("MODULE m; CONST A = 1; B = ; C = 3; TYPE T = ARRAY OF INTEGER; U = INTEGER; VAR x: ; y: INTEGER; \
+                PROCEDURE P(; BEGIN y := 0 END P; PROCEDURE Q; BEGIN y := 1 END Q; END m.");
+            assert_eq!(errors.len(), 4);
+            let declarations = &module.declarations;
+            let names: Vec<_> = declarations.const_declarations.iter().map(|c| c.ident.ident.text.as_str()).collect();
+            assert_eq!(names, ["A", "B", "C"]);
+            assert!(matches!(declarations.const_declarations[1].value, Expression::Error { .. }));
+            assert!(matches!(declarations.type_declarations[0].ty, Type::Error { .. }));
+            assert!(matches!(declarations.type_declarations[1].ty, Type::Named { .. }));
+            assert!(matches!(declarations.var_declarations[0].ty, Type::Error { .. }));
+            assert_eq!(declarations.var_declarations[1].variables[0].ident.text, "y");
+            assert_eq!(declarations.procedure_declarations.len(), 1);
+            assert_eq!(declarations.procedure_declarations[0].name.text, "Q");
+            assert_eq!(module.end_name.text, "m");
+        }
+
+        #[test]
+        fn skips_a_broken_structured_statement_to_its_end() {
+            let (module, errors) = parse_with_errors("MODULE m; BEGIN WHILE x DO x := 1 ELSE x := 2 END; y := 3 END m.");
+            assert_eq!(found(&errors), vec![(35, "ELSE".to_string())]);
+            let statements = module.stmts.unwrap().statements;
+            let [Statement::Error { span, .. }, Statement::Assign { .. }] = statements.as_slice() else { panic!("{statements:?}") };
+            assert_eq!((span.start.column, span.end.column), (17, 50));
+        }
+
+        #[test]
+        fn continues_after_a_missing_semicolon() {
+            let (module, errors) = parse_with_errors("MODULE m; VAR a: INTEGER b: INTEGER; BEGIN a := 1 b := 2 END m.");
+            assert_eq!(found(&errors), vec![(26, "b".to_string()), (51, "b".to_string())]);
+            assert_eq!(module.declarations.var_declarations.len(), 2);
+            assert_eq!(module.stmts.unwrap().statements.len(), 2);
+        }
+
+        #[test]
+        fn reports_one_error_for_a_mistake_that_derails_the_parse() {
+            let (_, errors) = parse_with_errors("MODULE Bad; BEGIN IF x THEN END Bad.");
+            assert_eq!(found(&errors), vec![(29, "END".to_string())]);
+        }
+
+        #[test]
+        fn reports_every_lexical_error() {
+            let (module, errors) = parse_with_errors("MODULE m; BEGIN x := @; y := 1; z := $ END m.");
+            assert!(matches!(errors.as_slice(), [
+                ParserError::Lexer(LexerError::UnexpectedCharacter { ch: '@', .. }),
+                ParserError::Lexer(LexerError::UnexpectedCharacter { ch: '$', .. }),
+            ]), "{errors:?}");
+            assert_eq!(module.stmts.unwrap().statements.len(), 3);
+        }
+
+        #[test]
+        fn reports_lexical_errors_met_while_looking_ahead() {
+            let (module, errors) = parse_with_errors("MODULE m; BEGIN x(T@); y(T.U$) END m.");
+            assert!(matches!(errors.as_slice(), [
+                ParserError::Lexer(LexerError::UnexpectedCharacter { ch: '@', .. }),
+                ParserError::Lexer(LexerError::UnexpectedCharacter { ch: '$', .. }),
+            ]), "{errors:?}");
+            let statements = module.stmts.unwrap().statements;
+            assert!(!matches!(&statements[0], Statement::Call { callee, .. } if !callee.selectors.is_empty()), "{statements:?}");
+
+            let (_, errors) = parse_with_errors("MODULE m; PROCEDURE P; BEGIN x := ( END P@; END m.");
+            assert!(errors.iter().any(|error| matches!(error, ParserError::Lexer(LexerError::UnexpectedCharacter { ch: '@', .. }))), "{errors:?}");
+        }
+
+        #[test]
+        fn terminates_on_truncated_and_broken_modules() {
+            let source = "MODULE m; IMPORT Out; CONST N = 10; TYPE R = RECORD a, b: INTEGER END; P = POINTER TO R; \
+                VAR r: P; i: INTEGER; PROCEDURE F(VAR x: ARRAY OF INTEGER; y: INTEGER): INTEGER; VAR k: INTEGER; \
+                BEGIN k := 0; REPEAT k := k + 1 UNTIL k > y; CASE k OF 1..3: k := 2 | 4: k := 0 END RETURN k END F; \
+                BEGIN FOR i := 0 TO N BY 2 DO IF i = 0 THEN r.a := i ELSIF i > 5 THEN r^.b := {1, 2..i} ELSE Out.Int(i, 0) END END; \
+                WHILE i > 0 DO DEC(i) END END m.";
+            for end in 0..source.len() {
+                let (_, errors) = parse_with_errors(&source[..end]);
+                assert!(!errors.is_empty(), "no error for {:?}", &source[..end]);
+            }
+            assert!(parse_with_errors(source).1.is_empty());
+            let words: Vec<_> = source.split(' ').collect();
+            for i in 0..words.len() {
+                let mut broken = words.clone();
+                broken.remove(i);
+                parse_with_errors(&broken.join(" "));
+            }
+        }
+
+        #[test]
+        fn parse_stops_at_the_first_error() {
+            let error = Parser::new(Lexer::new("MODULE m; BEGIN x := ; y := END m.")).parse().unwrap_err();
+            assert_eq!(found(&[error]), vec![(22, ";".to_string())]);
+        }
+    }
+}
@@ -0,0 +1,775 @@
+//! Printing the AST back as Oberon source, in one canonical layout: a declaration or statement a
+//! line, two spaces of indentation a level, and parentheses only where the precedence of the
+//! operators calls for them. Attached comments are printed where the parser attaches them again,
+//! so parsing the output gives back the same tree, spans aside.
+
+use std::fmt;
+use crate::frontend::ast::{BinaryOperation, Commented, Declarations, Designator, Element, Expression, FPSection, FieldList, FormalParameters, FormalType, IdentifierDef, Import, Label, LabelValue, Module, ProcedureDeclaration, QualifiedIdentifier, Selector, Statement, StatementSequence, Type, UnaryOperation};
+use crate::frontend::literal::IntegerWidth;
+
+const INDENT: &str = "  ";
+
+/// Prints modules as source text.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Printer {
+    integer_width: IntegerWidth,
+}
+
+impl Printer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prints integers for INTEGERs of `width` bits: MIN(INTEGER) as a negated decimal literal and
+    /// other negative integers as the hexadecimal literals that give them.
+    pub fn with_integer_width(mut self, width: IntegerWidth) -> Self {
+        self.integer_width = width;
+        self
+    }
+
+    pub fn print(&self, module: &Module) -> String {
+        let mut writer = Writer { out: String::new(), indent: 0, line_start: true, integer_width: self.integer_width, module };
+        writer.module();
+        writer.out
+    }
+}
+
+/// The source text of the module, for 64-bit INTEGERs.
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Printer::new().print(self))
+    }
+}
+
+/// How tightly an expression binds: the operands of a relation are simple expressions, those of an
+/// addition terms and those of a multiplication factors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Expression,
+    SimpleExpression,
+    Term,
+    Factor,
+}
+
+fn precedence(expr: &Expression) -> Precedence {
+    match expr {
+        Expression::Binary { op, .. } => match op {
+            BinaryOperation::Eq | BinaryOperation::Neq | BinaryOperation::Lt | BinaryOperation::Le
+            | BinaryOperation::Gt | BinaryOperation::Ge | BinaryOperation::In | BinaryOperation::Is => Precedence::Expression,
+            BinaryOperation::Addition | BinaryOperation::Subtraction | BinaryOperation::Or => Precedence::SimpleExpression,
+            BinaryOperation::Multiplication | BinaryOperation::Division | BinaryOperation::Div
+            | BinaryOperation::Mod | BinaryOperation::And => Precedence::Term,
+        },
+        // A sign applies to the term after it, at the start of a simple expression.
+        Expression::Unary { op: UnaryOperation::Plus | UnaryOperation::Minus, .. } => Precedence::SimpleExpression,
+        _ => Precedence::Factor,
+    }
+}
+
+/// Hexadecimal digits that lex as a number, starting with a decimal digit.
+fn hex(value: u64) -> String {
+    let digits = format!("{value:X}");
+    if digits.starts_with(|c: char| c.is_ascii_digit()) { digits } else { format!("0{digits}") }
+}
+
+/// The shortest real literal with the value, which always has a fraction: `1.0E300` for 1e300.
+fn real(value: f64) -> String {
+    let text = format!("{value:?}");
+    let (mantissa, exponent) = match text.split_once('e') {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (text.as_str(), None),
+    };
+    let mut real = mantissa.to_string();
+    if !real.contains('.') {
+        real.push_str(".0");
+    }
+    if let Some(exponent) = exponent {
+        real.push('E');
+        real.push_str(exponent);
+    }
+    real
+}
+
+struct Writer<'a> {
+    out: String,
+    indent: usize,
+    /// Whether nothing was written on the current line yet, not even its indentation.
+    line_start: bool,
+    integer_width: IntegerWidth,
+    module: &'a Module,
+}
+
+impl<'a> Writer<'a> {
+    fn write(&mut self, text: &str) {
+        if self.line_start {
+            self.out.push_str(&INDENT.repeat(self.indent));
+            self.line_start = false;
+        }
+        self.out.push_str(text);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.line_start = true;
+    }
+
+    fn indented(&mut self, write: impl FnOnce(&mut Self)) {
+        self.indent += 1;
+        write(self);
+        self.indent -= 1;
+    }
+
+    /// Writes `items` separated by `separator`.
+    fn list<T>(&mut self, items: &[T], separator: &str, mut write: impl FnMut(&mut Self, &T)) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.write(separator);
+            }
+            write(self, item);
+        }
+    }
+
+    // --------------------------- COMMENTS ---------------------------
+    /// The comments before the declaration or statement `node`, one a line, before its own.
+    fn leading_comments(&mut self, node: &impl Commented) {
+        for comment in &node.comments().leading {
+            self.write(&comment.text);
+            self.newline();
+        }
+    }
+
+    /// The comments after `node`, on the line it ends on.
+    fn trailing_comments(&mut self, node: &impl Commented) {
+        for comment in &node.comments().trailing {
+            self.write(" ");
+            self.write(&comment.text);
+        }
+    }
+
+    // --------------------------- MODULES ---------------------------
+    fn module(&mut self) {
+        let module = self.module;
+        self.write("MODULE ");
+        self.write(&module.name.text);
+        self.write(";");
+        if !module.imports.is_empty() {
+            self.newline();
+            self.write("IMPORT ");
+            self.list(&module.imports, ", ", Self::import);
+            self.write(";");
+        }
+        self.declarations(&module.declarations, true);
+        self.newline();
+        self.newline();
+        if let Some(stmts) = &module.stmts {
+            self.write("BEGIN");
+            self.block(stmts);
+            self.newline();
+        }
+        self.write("END ");
+        self.write(&module.end_name.text);
+        self.write(".");
+        self.newline();
+    }
+
+    fn import(&mut self, import: &Import) {
+        if let Some(alias) = &import.alias {
+            self.write(&alias.text);
+            self.write(" := ");
+        }
+        self.write(&import.module.text);
+    }
+
+    // --------------------------- DECLARATIONS ---------------------------
+    /// The sections of `declarations`, each on the lines after the current one; a blank line
+    /// before each procedure, and before each section too if `spaced`.
+    fn declarations(&mut self, declarations: &Declarations, spaced: bool) {
+        self.section(spaced, "CONST", &declarations.const_declarations, |writer, declaration| {
+            writer.identifier_def(&declaration.ident);
+            writer.write(" = ");
+            writer.expression(&declaration.value, Precedence::Expression);
+        });
+        self.section(spaced, "TYPE", &declarations.type_declarations, |writer, declaration| {
+            writer.identifier_def(&declaration.ident);
+            writer.write(" = ");
+            writer.ty(&declaration.ty);
+        });
+        self.section(spaced, "VAR", &declarations.var_declarations, |writer, declaration| {
+            writer.list(&declaration.variables, ", ", Self::identifier_def);
+            writer.write(": ");
+            writer.ty(&declaration.ty);
+        });
+        for procedure in &declarations.procedure_declarations {
+            self.newline();
+            self.newline();
+            self.leading_comments(procedure);
+            self.procedure_declaration(procedure);
+            self.trailing_comments(procedure);
+        }
+    }
+
+    fn section<T: Commented>(&mut self, spaced: bool, keyword: &str, declarations: &[T], mut write: impl FnMut(&mut Self, &T)) {
+        if declarations.is_empty() {
+            return;
+        }
+        self.newline();
+        if spaced {
+            self.newline();
+        }
+        self.write(keyword);
+        self.indented(|writer| {
+            for declaration in declarations {
+                writer.newline();
+                writer.leading_comments(declaration);
+                write(writer, declaration);
+                writer.write(";");
+                writer.trailing_comments(declaration);
+            }
+        });
+    }
+
+    fn procedure_declaration(&mut self, procedure: &ProcedureDeclaration) {
+        let ProcedureDeclaration { header, body, name, .. } = procedure;
+        self.write("PROCEDURE ");
+        self.identifier_def(&header.name);
+        if let Some(params) = &header.params {
+            self.formal_parameters(params);
+        }
+        self.write(";");
+        self.indented(|writer| writer.declarations(&body.declarations, false));
+        if let Some(stmts) = &body.stmts {
+            self.newline();
+            self.write("BEGIN");
+            self.block(stmts);
+        }
+        if let Some(ret) = &body.ret {
+            self.indented(|writer| {
+                writer.newline();
+                writer.write("RETURN ");
+                writer.expression(ret, Precedence::Expression);
+            });
+        }
+        self.newline();
+        self.write("END ");
+        self.write(&name.text);
+        self.write(";");
+    }
+
+    fn formal_parameters(&mut self, params: &FormalParameters) {
+        self.write("(");
+        self.list(&params.sections, "; ", Self::fp_section);
+        self.write(")");
+        if let Some(return_type) = &params.return_type {
+            self.write(": ");
+            self.qualified_identifier(return_type);
+        }
+    }
+
+    fn fp_section(&mut self, section: &FPSection) {
+        if section.by_ref {
+            self.write("VAR ");
+        }
+        self.list(&section.names, ", ", |writer, name| writer.write(&name.text));
+        self.write(": ");
+        self.formal_type(&section.ty);
+    }
+
+    fn formal_type(&mut self, ty: &FormalType) {
+        for _ in 0..ty.open_arrays {
+            self.write("ARRAY OF ");
+        }
+        self.qualified_identifier(&ty.base);
+    }
+
+    // --------------------------- TYPES ---------------------------
+    fn ty(&mut self, ty: &Type) {
+        match ty {
+            Type::Named { name } => self.qualified_identifier(name),
+            Type::Array { lengths, element, .. } => {
+                self.write("ARRAY ");
+                self.list(lengths, ", ", |writer, length| writer.expression(length, Precedence::Expression));
+                self.write(" OF ");
+                self.ty(element);
+            }
+            Type::Record { base, field_lists, .. } => {
+                self.write("RECORD");
+                if let Some(base) = base {
+                    self.write(" (");
+                    self.qualified_identifier(base);
+                    self.write(")");
+                }
+                if field_lists.is_empty() {
+                    self.write(" END");
+                    return;
+                }
+                self.indented(|writer| {
+                    for (i, fields) in field_lists.iter().enumerate() {
+                        if i > 0 {
+                            writer.write(";");
+                        }
+                        writer.newline();
+                        writer.field_list(fields);
+                    }
+                });
+                self.newline();
+                self.write("END");
+            }
+            Type::Pointer { pointee, .. } => {
+                self.write("POINTER TO ");
+                self.ty(pointee);
+            }
+            Type::Procedure { params, .. } => {
+                self.write("PROCEDURE");
+                if let Some(params) = params {
+                    self.write(" ");
+                    self.formal_parameters(params);
+                }
+            }
+            Type::Error { .. } => self.write("(* error *)"),
+        }
+    }
+
+    fn field_list(&mut self, fields: &FieldList) {
+        self.list(&fields.fields, ", ", Self::identifier_def);
+        self.write(": ");
+        self.ty(&fields.ty);
+    }
+
+    // --------------------------- STATEMENTS ---------------------------
+    /// The statements on the lines after the current one, one level further in.
+    fn block(&mut self, stmts: &StatementSequence) {
+        self.indented(|writer| {
+            let statements = &stmts.statements;
+            for (i, statement) in statements.iter().enumerate() {
+                writer.newline();
+                writer.leading_comments(statement);
+                writer.statement(statement);
+                if i + 1 < statements.len() {
+                    writer.write(";");
+                }
+                writer.trailing_comments(statement);
+            }
+        });
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Assign { target, value, .. } => {
+                self.designator(target);
+                self.write(" := ");
+                self.expression(value, Precedence::Expression);
+            }
+            Statement::Call { callee, parameters, .. } => {
+                self.designator(callee);
+                if let Some(parameters) = parameters {
+                    self.actual_parameters(parameters);
+                }
+            }
+            Statement::If { cond, stmts, elsif_branches, else_branch, .. } => {
+                self.write("IF ");
+                self.expression(cond, Precedence::Expression);
+                self.write(" THEN");
+                self.block(stmts);
+                for branch in elsif_branches {
+                    self.newline();
+                    self.write("ELSIF ");
+                    self.expression(&branch.cond, Precedence::Expression);
+                    self.write(" THEN");
+                    self.block(&branch.stmts);
+                }
+                if let Some(else_branch) = else_branch {
+                    self.newline();
+                    self.write("ELSE");
+                    self.block(else_branch);
+                }
+                self.newline();
+                self.write("END");
+            }
+            Statement::Case { expr, branches, .. } => {
+                self.write("CASE ");
+                self.expression(expr, Precedence::Expression);
+                self.write(" OF");
+                for (i, branch) in branches.iter().enumerate() {
+                    self.newline();
+                    self.write(if i == 0 { INDENT } else { "| " });
+                    self.list(&branch.label_list, ", ", Self::label);
+                    self.write(":");
+                    self.indented(|writer| writer.block(&branch.statements));
+                }
+                self.newline();
+                self.write("END");
+            }
+            Statement::While { cond, stmts, elsif_branches, .. } => {
+                self.write("WHILE ");
+                self.expression(cond, Precedence::Expression);
+                self.write(" DO");
+                self.block(stmts);
+                for branch in elsif_branches {
+                    self.newline();
+                    self.write("ELSIF ");
+                    self.expression(&branch.cond, Precedence::Expression);
+                    self.write(" DO");
+                    self.block(&branch.stmts);
+                }
+                self.newline();
+                self.write("END");
+            }
+            Statement::Repeat { stmts, cond, .. } => {
+                self.write("REPEAT");
+                self.block(stmts);
+                self.newline();
+                self.write("UNTIL ");
+                self.expression(cond, Precedence::Expression);
+            }
+            Statement::For { var, low, high, by, stmts, .. } => {
+                self.write("FOR ");
+                self.write(&var.text);
+                self.write(" := ");
+                self.expression(low, Precedence::Expression);
+                self.write(" TO ");
+                self.expression(high, Precedence::Expression);
+                if let Some(by) = by {
+                    self.write(" BY ");
+                    self.expression(by, Precedence::Expression);
+                }
+                self.write(" DO");
+                self.block(stmts);
+                self.newline();
+                self.write("END");
+            }
+            Statement::Error { .. } => self.write("(* error *)"),
+        }
+    }
+
+    fn label(&mut self, label: &Label) {
+        match label {
+            Label::Single { value } => self.label_value(value),
+            Label::Range { low, high } => {
+                self.label_value(low);
+                self.write("..");
+                self.label_value(high);
+            }
+        }
+    }
+
+    fn label_value(&mut self, value: &LabelValue) {
+        match value {
+            LabelValue::Integer { value, .. } => self.integer(*value),
+            LabelValue::String { value, .. } => self.string(value),
+            LabelValue::Char { value, .. } => self.char(*value),
+            LabelValue::QualifiedIdentifier(ident) => self.qualified_identifier(ident),
+        }
+    }
+
+    // --------------------------- EXPRESSIONS ---------------------------
+    /// Writes `expr` where the grammar calls for one of `context` precedence, in parentheses if it
+    /// binds less tightly.
+    fn expression(&mut self, expr: &Expression, context: Precedence) {
+        if let Expression::Int { value, .. } = expr
+            && *value == self.integer_width.min()
+        {
+            // The sign only reads back as part of the literal where it may start a simple
+            // expression.
+            let text = format!("-{}", value.unsigned_abs());
+            if context < Precedence::Term { self.write(&text) } else { self.write(&format!("({text})")) }
+            return;
+        }
+        if precedence(expr) < context {
+            self.write("(");
+            self.expression(expr, Precedence::Expression);
+            self.write(")");
+            return;
+        }
+        match expr {
+            Expression::Int { value, .. } => self.integer(*value),
+            Expression::Real { value, .. } => self.write(&real(*value)),
+            Expression::String { value, .. } => self.string(value),
+            Expression::Char { value, .. } => self.char(*value),
+            Expression::Nil { .. } => self.write("NIL"),
+            Expression::True { .. } => self.write("TRUE"),
+            Expression::False { .. } => self.write("FALSE"),
+            Expression::Set { elements, .. } => {
+                self.write("{");
+                self.list(elements, ", ", Self::element);
+                self.write("}");
+            }
+            Expression::Designator { designator, actual_parameters, .. } => {
+                self.designator(designator);
+                if let Some(parameters) = actual_parameters {
+                    self.actual_parameters(parameters);
+                }
+            }
+            Expression::Unary { op, operand, .. } => {
+                self.write(op.symbol());
+                let context = match op {
+                    UnaryOperation::Not => Precedence::Factor,
+                    UnaryOperation::Plus | UnaryOperation::Minus => Precedence::Term,
+                };
+                self.expression(operand, context);
+            }
+            Expression::Binary { op, lhs, rhs, .. } => {
+                // Operators of a level associate to the left, and relations not at all.
+                let precedence = precedence(expr);
+                let (left, right) = match precedence {
+                    Precedence::Expression => (Precedence::SimpleExpression, Precedence::SimpleExpression),
+                    Precedence::SimpleExpression => (Precedence::SimpleExpression, Precedence::Term),
+                    _ => (Precedence::Term, Precedence::Factor),
+                };
+                self.expression(lhs, left);
+                self.write(" ");
+                self.write(op.symbol());
+                self.write(" ");
+                self.expression(rhs, right);
+            }
+            Expression::Error { .. } => self.write("(* error *)"),
+        }
+    }
+
+    /// A negative integer as the hexadecimal literal with its bits, as it must have been written:
+    /// only MIN(INTEGER) has a negated literal, and CASE labels have none.
+    fn integer(&mut self, value: i64) {
+        if value >= 0 {
+            self.write(&value.to_string());
+            return;
+        }
+        let bits = match self.integer_width {
+            IntegerWidth::Bits32 => value as u32 as u64,
+            IntegerWidth::Bits64 => value as u64,
+        };
+        self.write(&hex(bits));
+        self.write("H");
+    }
+
+    fn string(&mut self, value: &str) {
+        self.write("\"");
+        self.write(value);
+        self.write("\"");
+    }
+
+    fn char(&mut self, value: u8) {
+        self.write(&hex(value as u64));
+        self.write("X");
+    }
+
+    fn element(&mut self, element: &Element) {
+        self.expression(&element.first, Precedence::Expression);
+        if let Some(second) = &element.second {
+            self.write("..");
+            self.expression(second, Precedence::Expression);
+        }
+    }
+
+    /// A lone parameter that is a qualified identifier goes in parentheses of its own, since the
+    /// parser takes `P(x)` for a type guard.
+    fn actual_parameters(&mut self, parameters: &[Expression]) {
+        self.write("(");
+        match parameters {
+            [Expression::Designator { designator, actual_parameters: None, .. }] if designator.selectors.is_empty() => {
+                self.write("(");
+                self.designator(designator);
+                self.write(")");
+            }
+            _ => self.list(parameters, ", ", |writer, parameter| writer.expression(parameter, Precedence::Expression)),
+        }
+        self.write(")");
+    }
+
+    // --------------------------- DESIGNATORS, SELECTORS, IDENTIFIERS ---------------------------
+    fn designator(&mut self, designator: &Designator) {
+        self.qualified_identifier(&designator.head);
+        for selector in &designator.selectors {
+            match selector {
+                Selector::Field(ident) => {
+                    self.write(".");
+                    self.write(&ident.text);
+                }
+                Selector::Index(indices, _) => {
+                    self.write("[");
+                    self.list(indices, ", ", |writer, index| writer.expression(index, Precedence::Expression));
+                    self.write("]");
+                }
+                Selector::Deref(_) => self.write("^"),
+                Selector::TypeGuard(ty, _) => {
+                    self.write("(");
+                    self.qualified_identifier(ty);
+                    self.write(")");
+                }
+            }
+        }
+    }
+
+    fn qualified_identifier(&mut self, ident: &QualifiedIdentifier) {
+        self.list(&ident.parts, ".", |writer, part| writer.write(&part.text));
+    }
+
+    fn identifier_def(&mut self, ident: &IdentifierDef) {
+        self.write(&ident.ident.text);
+        if ident.exported {
+            self.write("*");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+    use crate::frontend::span::{Position, Span};
+    use crate::frontend::visit::VisitorMut;
+
+    fn parse(source: &str, width: IntegerWidth) -> Module {
+        Parser::new(Lexer::with_trivia(source)).with_integer_width(width).parse().unwrap()
+    }
+
+    /// Puts every span at the start of the text.
+    struct Unplace;
+
+    impl VisitorMut for Unplace {
+        fn visit_span_mut(&mut self, span: &mut Span) {
+            *span = Span::new(Position::initial(), Position::initial());
+        }
+    }
+
+    /// Prints `module`, checks that the output parses back into it and prints the same again.
+    fn assert_round_trip(module: &Module, width: IntegerWidth) -> String {
+        let printed = Printer::new().with_integer_width(width).print(module);
+        let reparsed = parse(&printed, width);
+        assert_eq!(Printer::new().with_integer_width(width).print(&reparsed), printed);
+        let (mut expected, mut actual) = (module.clone(), reparsed);
+        Unplace.visit_module_mut(&mut expected);
+        Unplace.visit_module_mut(&mut actual);
+        assert_eq!(actual, expected, "{printed}");
+        printed
+    }
+
+    const SOURCE: &str = r#"MODULE Shapes; IMPORT Out, F := Files;
+CONST Max* = 10; Mask = 0FFH; Name = "shapes"; Tab = 9X; Big = 0FFX; Pi = 3.14159; Tiny = 1.0E-300;
+  Min = -9223372036854775808; Bits = 0FFFFFFFFFFFFFFFFH;
+TYPE (* a point *)
+  Point* = RECORD x*, y*: INTEGER END;
+  Shape = POINTER TO ShapeDesc; ShapeDesc = RECORD (Point) area: REAL; next: Shape END;
+  Empty = RECORD END; Derived = RECORD (F.File) END;
+  Grid = ARRAY Max, Max OF ARRAY 2 OF BOOLEAN;
+  Visit = PROCEDURE (s: Shape): BOOLEAN; Action = PROCEDURE; Reset = PROCEDURE (); Get = PROCEDURE (): INTEGER;
+VAR first, last: Shape; grid: Grid; count: INTEGER; (* how many *)
+PROCEDURE Area(s: Shape): REAL; VAR a: REAL;
+BEGIN IF s = NIL THEN a := 0.0 ELSIF s IS Shape THEN a := FLT(s.x * s.y) ELSE a := 1.5E2 END
+  RETURN a END Area;
+PROCEDURE Count*(VAR n: INTEGER; visit: Visit; VAR m: ARRAY OF ARRAY OF CHAR);
+  CONST Limit = Max * 2;
+  PROCEDURE Skip(s: Shape): BOOLEAN; RETURN (s.x < 0) OR ~(s.y IN {1, 3..5}) END Skip;
+BEGIN n := 0; (* start *) first(ShapeDesc).next^.x := -n;
+  WHILE (s # NIL) & (n < Limit) DO IF ~Skip(s) & visit(s) THEN INC(n) END; s := s.next
+  ELSIF n > 0 DO DEC(n) END
+END Count;
+PROCEDURE Fill(c: CHAR); VAR i, j: INTEGER;
+BEGIN
+  FOR i := 0 TO Max - 1 DO FOR j := Max - 1 TO 0 BY -1 DO grid[i, j][0] := ODD(i + j) END END;
+  CASE c OF "a" .. "z": count := 1 | 0X, Tab: count := 2 | Out.Char: count := 3 END;
+  REPEAT DEC(count) UNTIL count <= 0; Out.Ln(); F.Close
+END Fill;
+BEGIN first := NIL; last := first; (* done *)
+  Out.String(Name); Out.Ln
+END Shapes."#;
+
+    #[test]
+    fn prints_what_parses_back_into_the_module() {
+        let printed = assert_round_trip(&parse(SOURCE, IntegerWidth::Bits64), IntegerWidth::Bits64);
+
+        assert!(printed.starts_with("MODULE Shapes;\nIMPORT Out, F := Files;\n\nCONST\n  Max* = 10;\n"));
+        assert!(printed.contains("\nTYPE\n  (* a point *)\n  Point* = RECORD\n    x*, y*: INTEGER\n  END;\n"));
+        assert!(printed.contains("  Empty = RECORD END;\n  Derived = RECORD (F.File) END;\n"));
+        assert!(printed.contains("  count: INTEGER; (* how many *)\n"));
+        assert!(printed.contains("  Visit = PROCEDURE (s: Shape): BOOLEAN;\n  Action = PROCEDURE;\n  Reset = PROCEDURE ();\n"));
+        assert!(printed.contains("  Big = 0FFX;\n  Pi = 3.14159;\n  Tiny = 1.0E-300;\n"));
+        assert!(printed.contains("  CASE c OF\n    \"a\"..\"z\":\n      count := 1\n  | 0X, Tab:\n"));
+        assert!(printed.ends_with("BEGIN\n  first := NIL;\n  last := first; (* done *)\n  Out.String(Name);\n  Out.Ln\nEND Shapes.\n"));
+    }
+
+    #[test]
+    fn keeps_the_comments_of_a_tree_whose_spans_are_rewritten() {
+        let mut module = parse(SOURCE, IntegerWidth::Bits64);
+        let printed = Printer::new().print(&module);
+
+        Unplace.visit_module_mut(&mut module);
+
+        assert_eq!(Printer::new().print(&module), printed);
+    }
+
+    #[test]
+    fn prints_procedures_with_their_declarations_inside() {
+        let module = parse("MODULE M; PROCEDURE P(x: INTEGER): INTEGER; VAR y: INTEGER; \
+            PROCEDURE Q; END Q; BEGIN y := x RETURN y END P; END M.", IntegerWidth::Bits64);
+
+        assert_eq!(assert_round_trip(&module, IntegerWidth::Bits64), "MODULE M;
+
+PROCEDURE P(x: INTEGER): INTEGER;
+  VAR
+    y: INTEGER;
+
+  PROCEDURE Q;
+  END Q;
+BEGIN
+  y := x
+  RETURN y
+END P;
+
+END M.
+");
+    }
+
+    #[test]
+    fn parenthesizes_by_precedence() {
+        let module = parse("MODULE M; BEGIN
+            x := (a + b) * c; x := a - (b - c); x := (a - b) - c; x := -(a + b); x := (-a) * b; x := -a * b;
+            x := ~(p & q); x := ~p & q; x := (a = b) = c; x := a = -b; x := a + (-b); x := -(-a); x := (a DIV b) MOD (c * d)
+        END M.", IntegerWidth::Bits64);
+
+        let printed = assert_round_trip(&module, IntegerWidth::Bits64);
+
+        let statements: Vec<&str> = printed.lines().filter_map(|line| line.trim().strip_prefix("x := ")).collect();
+        assert_eq!(statements, [
+            "(a + b) * c;", "a - (b - c);", "a - b - c;", "-(a + b);", "(-a) * b;", "-a * b;",
+            "~(p & q);", "~p & q;", "(a = b) = c;", "a = -b;", "a + (-b);", "-(-a);", "a DIV b MOD (c * d)",
+        ]);
+    }
+
+    #[test]
+    fn prints_min_integer_in_decimal_and_other_negative_integers_in_hexadecimal() {
+        let source = "MODULE M; BEGIN x := -9223372036854775808; x := 8000000000000000H + 1; \
+            x := 2 * (-9223372036854775808); x := a - (-9223372036854775808) * 2; x := -(-9223372036854775808); \
+            x := 0FFFFFFFFFFFFFFFFH - 1; x := 2 * (-9223372036854775807) END M.";
+        let printed = assert_round_trip(&parse(source, IntegerWidth::Bits64), IntegerWidth::Bits64);
+        let statements: Vec<&str> = printed.lines().filter_map(|line| line.trim().strip_prefix("x := ")).collect();
+        assert_eq!(statements, [
+            "-9223372036854775808;", "-9223372036854775808 + 1;", "2 * (-9223372036854775808);",
+            "a - (-9223372036854775808) * 2;", "-(-9223372036854775808);", "0FFFFFFFFFFFFFFFFH - 1;",
+            "2 * (-9223372036854775807)",
+        ]);
+
+        let source = "MODULE M; BEGIN x := -2147483648; CASE x OF 80000000H, 0FFFFFFFFH: x := 1 END END M.";
+        let printed = assert_round_trip(&parse(source, IntegerWidth::Bits32), IntegerWidth::Bits32);
+        assert!(printed.contains("x := -2147483648;\n  CASE x OF\n    80000000H, 0FFFFFFFFH:\n"));
+    }
+
+    #[test]
+    fn keeps_a_lone_qualified_parameter_apart_from_a_type_guard() {
+        let module = parse("MODULE M; BEGIN P((x)); x := F((a.b)) + F(x); P((x), y) END M.", IntegerWidth::Bits64);
+        let Some(stmts) = &module.stmts else { unreachable!() };
+        assert!(matches!(&stmts.statements[0], Statement::Call { parameters: Some(parameters), .. } if parameters.len() == 1));
+
+        let printed = assert_round_trip(&module, IntegerWidth::Bits64);
+
+        assert!(printed.contains("  P((x));\n  x := F((a.b)) + F(x);\n  P(x, y)\n"), "{printed}");
+    }
+
+    #[test]
+    fn keeps_a_type_guard_on_a_call_result() {
+        let module = parse("MODULE M; BEGIN y := f(a.b)(T) END M.", IntegerWidth::Bits64);
+
+        let printed = assert_round_trip(&module, IntegerWidth::Bits64);
+
+        assert!(printed.contains("  y := f(a.b)(T)\n"));
+    }
+}
@@ -0,0 +1,868 @@
+//! Symbol files: the interface of a compiled module, read by the modules that import it.
+//!
+//! A symbol file lists the exported constants, types, variables and procedures of a module,
+//! together with every type they refer to. Types are numbered structures (`#0`, `#1`, ...) or the
+//! predeclared basic types; records carry all their fields, exported or not, because importers
+//! need the complete layout. The text form has one entry per line:
+//!
+//! ```text
+//! module Shapes 5d0c6a2b9e4f7d31
+//! import Math 0b6e4cf1a2d3e597
+//! use Math sqrt 9f1e22c4a7b6d803
+//! struct #0 record Shapes_Point name Shapes.Point
+//! field #0 x* INTEGER
+//! field #0 y* INTEGER
+//! struct #1 pointer #0 name Shapes.Ref
+//! struct #2 procedure value #1 result INTEGER
+//! const Max INTEGER integer 100 fp 3c91d0b7e25a6f48
+//! type Point #0 fp 81f7a65c0d2e9b34
+//! var origin #0 fp e40b3d9a6c1f7258
+//! procedure Norm #2 fp 2a6d8e1f95c04b73
+//! ```
+//!
+//! Named types are identified by their qualified name and records by their link name, so a type
+//! reached through the symbol files of several modules is imported only once.
+//!
+//! Every export carries a fingerprint of its declaration, which covers the complete structure of
+//! its type, and the module line carries one of the whole interface. A symbol file also records
+//! the interfaces its module was compiled against, down to the fingerprints of the imported
+//! objects it used (the `import` and `use` lines). The analysis compares those against the
+//! symbol files at hand, so a module compiled against an interface that has changed since is
+//! reported instead of being linked against code it no longer matches.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write as _};
+use thiserror::Error;
+use crate::frontend::analysis::Analysis;
+use crate::frontend::const_eval::Value;
+use crate::frontend::span::Span;
+use crate::frontend::symbols::{ScopeId, ScopeKind, Symbol, SymbolKind, SymbolTable};
+use crate::frontend::types::{Param, Type, TypeId, TypeTable, BOOLEAN, BYTE, CHAR, INTEGER, NIL, REAL, SET};
+
+/// The predeclared types, which symbol files refer to by name.
+const BASIC: [(TypeId, &str); 7] = [
+    (BOOLEAN, "BOOLEAN"), (CHAR, "CHAR"), (INTEGER, "INTEGER"), (REAL, "REAL"),
+    (BYTE, "BYTE"), (SET, "SET"), (NIL, "NIL"),
+];
+
+/// A 64-bit FNV-1a hash. Unlike the standard library's hashers it is the same in every build of
+/// the compiler, so it can be written to files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint(pub u64);
+
+impl Fingerprint {
+    pub fn of(bytes: &[u8]) -> Self {
+        Fingerprint(bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)))
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// The interface of one module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolFile {
+    pub module: String,
+    /// The fingerprint of the whole interface, made of those of the exports.
+    pub fingerprint: Fingerprint,
+    /// The interfaces the module was compiled against.
+    pub imports: Vec<Dependency>,
+    /// The structured types the exports refer to; `TypeRef::Struct` indexes into this list.
+    pub structs: Vec<Struct>,
+    pub exports: Vec<Export>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypeRef {
+    Basic(TypeId),
+    Struct(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Struct {
+    /// The qualified name of the type declaration that denotes the type, e.g. `Shapes.Point`.
+    pub name: Option<String>,
+    pub kind: StructKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StructKind {
+    /// `link` is the name of the record's type descriptor and C struct.
+    Record { link: String, base: Option<TypeRef>, fields: Vec<Field> },
+    Pointer { base: TypeRef },
+    Array { length: i64, element: TypeRef },
+    OpenArray { element: TypeRef },
+    String { length: usize },
+    Procedure { params: Vec<(bool, TypeRef)>, result: Option<TypeRef> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub exported: bool,
+    pub ty: TypeRef,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Export {
+    pub name: String,
+    pub kind: ExportKind,
+    pub ty: TypeRef,
+    pub fingerprint: Fingerprint,
+}
+
+/// An imported interface as it was when the module was compiled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub module: String,
+    pub fingerprint: Fingerprint,
+    /// The exports of `module` that were used, with their fingerprints.
+    pub uses: Vec<(String, Fingerprint)>,
+}
+
+impl Dependency {
+    /// The first export this module used from `current`, a later version of the imported
+    /// interface, that has changed or been removed since.
+    pub fn changed_use<'a>(&'a self, current: &SymbolFile) -> Option<&'a str> {
+        if current.fingerprint == self.fingerprint {
+            return None;
+        }
+        self.uses.iter().find(|(name, fingerprint)| {
+            !current.exports.iter().any(|export| export.name == *name && export.fingerprint == *fingerprint)
+        }).map(|(name, _)| name.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportKind {
+    Const(Value),
+    Type,
+    Var,
+    Procedure,
+}
+
+// --------------------------- EXPORT ---------------------------
+impl SymbolFile {
+    /// The interface of `module`, which produced `analysis`.
+    pub fn export(module: &str, analysis: &Analysis) -> Self {
+        let mut exporter = Exporter {
+            analysis,
+            module,
+            records: analysis.types.record_names(module),
+            indices: HashMap::new(),
+            structs: vec![],
+        };
+        let mut exports = vec![];
+        for id in analysis.symbols.exports() {
+            let symbol = analysis.symbols.symbol(id);
+            let kind = match &symbol.kind {
+                SymbolKind::Const(value) => ExportKind::Const(value.clone()),
+                SymbolKind::Type => ExportKind::Type,
+                SymbolKind::Var => ExportKind::Var,
+                SymbolKind::Procedure => ExportKind::Procedure,
+                _ => continue,
+            };
+            let ty = exporter.type_ref(symbol.ty);
+            let fingerprint = declaration_fingerprint(&exporter.structs, &symbol.name, &kind, ty);
+            exports.push(Export { name: symbol.name.clone(), kind, ty, fingerprint });
+        }
+        SymbolFile {
+            module: module.to_string(),
+            fingerprint: interface_fingerprint(module, &exports),
+            imports: dependencies(analysis),
+            structs: exporter.structs,
+            exports,
+        }
+    }
+}
+
+/// The imported interfaces, with the exports of each that were referred to.
+fn dependencies(analysis: &Analysis) -> Vec<Dependency> {
+    let symbols = &analysis.symbols;
+    let mut dependencies: Vec<Dependency> = vec![];
+    let Some(scope) = symbols.module_scope() else { return dependencies };
+    for &id in symbols.scope(scope).symbols() {
+        let symbol = symbols.symbol(id);
+        let (SymbolKind::Module { name }, Some(exports)) = (&symbol.kind, symbol.body) else { continue };
+        let Some(&fingerprint) = analysis.fingerprints.get(&id) else { continue };
+        let uses = symbols.scope(exports).symbols().iter()
+            .filter(|&&export| symbols.references_to(export).next().is_some())
+            .filter_map(|export| Some((symbols.symbol(*export).name.clone(), *analysis.fingerprints.get(export)?)));
+        match dependencies.iter_mut().find(|dependency| dependency.module == *name) {
+            // The same module imported under two names.
+            Some(dependency) => {
+                for used in uses {
+                    if !dependency.uses.contains(&used) {
+                        dependency.uses.push(used);
+                    }
+                }
+            }
+            None => dependencies.push(Dependency { module: name.clone(), fingerprint, uses: uses.collect() }),
+        }
+    }
+    dependencies
+}
+
+fn interface_fingerprint(module: &str, exports: &[Export]) -> Fingerprint {
+    let mut text = format!("{module}\n");
+    for export in exports {
+        writeln!(text, "{} {}", export.name, export.fingerprint).unwrap();
+    }
+    Fingerprint::of(text.as_bytes())
+}
+
+/// The fingerprint of an exported declaration; the type is described in full, as the numbering
+/// of the structures differs from one symbol file to the next.
+fn declaration_fingerprint(structs: &[Struct], name: &str, kind: &ExportKind, ty: TypeRef) -> Fingerprint {
+    let mut text = match kind {
+        ExportKind::Const(_) => "const",
+        ExportKind::Type => "type",
+        ExportKind::Var => "var",
+        ExportKind::Procedure => "procedure",
+    }.to_string();
+    write!(text, " {name} ").unwrap();
+    describe(structs, ty, &mut vec![], &mut text);
+    if let ExportKind::Const(value) = kind {
+        write!(text, " {}", ConstValue(value)).unwrap();
+    }
+    Fingerprint::of(text.as_bytes())
+}
+
+/// Writes the structure of `ty` to `out`; a structure met again is written as `@n`, its place
+/// in `seen`.
+fn describe(structs: &[Struct], ty: TypeRef, seen: &mut Vec<usize>, out: &mut String) {
+    let TypeRef::Struct(index) = ty else {
+        write!(out, "{ty}").unwrap();
+        return;
+    };
+    if let Some(position) = seen.iter().position(|&other| other == index) {
+        write!(out, "@{position}").unwrap();
+        return;
+    }
+    seen.push(index);
+    let structure = &structs[index];
+    if let Some(name) = &structure.name {
+        write!(out, "{name}=").unwrap();
+    }
+    match &structure.kind {
+        StructKind::Record { link, base, fields } => {
+            write!(out, "record {link} (").unwrap();
+            if let Some(base) = base {
+                describe(structs, *base, seen, out);
+            }
+            out.push(')');
+            for field in fields {
+                write!(out, " {}{}: ", field.name, if field.exported { "*" } else { "" }).unwrap();
+                describe(structs, field.ty, seen, out);
+                out.push(';');
+            }
+            out.push_str(" end");
+        }
+        StructKind::Pointer { base } => {
+            out.push_str("pointer ");
+            describe(structs, *base, seen, out);
+        }
+        StructKind::Array { length, element } => {
+            write!(out, "array {length} ").unwrap();
+            describe(structs, *element, seen, out);
+        }
+        StructKind::OpenArray { element } => {
+            out.push_str("openarray ");
+            describe(structs, *element, seen, out);
+        }
+        StructKind::String { length } => write!(out, "string {length}").unwrap(),
+        StructKind::Procedure { params, result } => {
+            out.push_str("procedure (");
+            for (by_ref, ty) in params {
+                out.push_str(if *by_ref { "var " } else { "value " });
+                describe(structs, *ty, seen, out);
+                out.push(';');
+            }
+            out.push(')');
+            if let Some(result) = result {
+                out.push_str(": ");
+                describe(structs, *result, seen, out);
+            }
+        }
+    }
+}
+
+struct Exporter<'a> {
+    analysis: &'a Analysis,
+    module: &'a str,
+    records: HashMap<TypeId, String>,
+    indices: HashMap<TypeId, usize>,
+    structs: Vec<Struct>,
+}
+
+impl Exporter<'_> {
+    fn type_ref(&mut self, id: TypeId) -> TypeRef {
+        if BASIC.iter().any(|(basic, _)| *basic == id) {
+            return TypeRef::Basic(id);
+        }
+        if let Some(index) = self.indices.get(&id) {
+            return TypeRef::Struct(*index);
+        }
+
+        // Registered before its components, which may point back to it.
+        let index = self.structs.len();
+        self.indices.insert(id, index);
+        let name = self.analysis.types.declared_name(id).map(|name| {
+            if name.contains('.') { name.to_string() } else { format!("{}.{name}", self.module) }
+        });
+        self.structs.push(Struct { name, kind: StructKind::String { length: 0 } });
+
+        let symbols = &self.analysis.symbols;
+        let kind = match self.analysis.types.get(id) {
+            Type::Record { base, fields } => {
+                let base = base.map(|base| self.type_ref(base));
+                let fields = symbols.scope(*fields).symbols().iter()
+                    .map(|field| symbols.symbol(*field))
+                    .map(|field| Field { name: field.name.clone(), exported: field.exported, ty: self.type_ref(field.ty) })
+                    .collect();
+                StructKind::Record { link: self.records[&id].clone(), base, fields }
+            }
+            Type::Pointer { base } => StructKind::Pointer { base: self.type_ref(*base) },
+            Type::Array { length, element } => StructKind::Array { length: *length, element: self.type_ref(*element) },
+            Type::OpenArray { element } => StructKind::OpenArray { element: self.type_ref(*element) },
+            Type::String { length } => StructKind::String { length: *length },
+            Type::Procedure { params, result } => StructKind::Procedure {
+                params: params.iter().map(|param| (param.by_ref, self.type_ref(param.ty))).collect(),
+                result: result.map(|result| self.type_ref(result)),
+            },
+            _ => unreachable!("checked exports have proper types"),
+        };
+        self.structs[index].kind = kind;
+        TypeRef::Struct(index)
+    }
+}
+
+// --------------------------- IMPORT ---------------------------
+impl SymbolFile {
+    /// Adds the types of the interface to `types` and declares its exports in `scope`. Types
+    /// already imported through another module are reused.
+    pub fn import(&self, types: &mut TypeTable, symbols: &mut SymbolTable, scope: ScopeId) {
+        let mut ids = Vec::with_capacity(self.structs.len());
+        let mut fresh = vec![];
+        for (index, structure) in self.structs.iter().enumerate() {
+            let existing = structure.name.as_deref().and_then(|name| types.named(name)).or_else(|| match &structure.kind {
+                StructKind::Record { link, .. } => types.linked(link),
+                _ => None,
+            });
+            let id = match (existing, &structure.kind) {
+                (Some(id), _) => id,
+                (None, StructKind::String { length }) => types.string(*length),
+                (None, kind) => {
+                    let id = types.add(Type::Error);
+                    if let Some(name) = &structure.name {
+                        types.set_name(id, name);
+                    }
+                    if let StructKind::Record { link, .. } = kind {
+                        types.set_link(id, link);
+                    }
+                    fresh.push(index);
+                    id
+                }
+            };
+            ids.push(id);
+        }
+
+        let resolve = |ty: &TypeRef| match ty {
+            TypeRef::Basic(id) => *id,
+            TypeRef::Struct(index) => ids[*index],
+        };
+        for index in fresh {
+            let ty = match &self.structs[index].kind {
+                StructKind::Record { base, fields, .. } => {
+                    let scope = symbols.open_scope(ScopeKind::Record, None);
+                    for field in fields {
+                        let symbol = Symbol {
+                            name: field.name.clone(), kind: SymbolKind::Field, ty: resolve(&field.ty),
+                            span: Span::default(), exported: field.exported, scope, body: None,
+                        };
+                        symbols.declare(symbol).expect("parsing rejects duplicate fields");
+                    }
+                    Type::Record { base: base.as_ref().map(resolve), fields: scope }
+                }
+                StructKind::Pointer { base } => Type::Pointer { base: resolve(base) },
+                StructKind::Array { length, element } => Type::Array { length: *length, element: resolve(element) },
+                StructKind::OpenArray { element } => Type::OpenArray { element: resolve(element) },
+                StructKind::String { .. } => unreachable!("string types are interned"),
+                StructKind::Procedure { params, result } => Type::Procedure {
+                    params: params.iter().map(|(by_ref, ty)| Param { by_ref: *by_ref, ty: resolve(ty) }).collect(),
+                    result: result.as_ref().map(resolve),
+                },
+            };
+            types.set(ids[index], ty);
+        }
+
+        for export in &self.exports {
+            let kind = match &export.kind {
+                ExportKind::Const(value) => SymbolKind::Const(value.clone()),
+                ExportKind::Type => SymbolKind::Type,
+                ExportKind::Var => SymbolKind::Var,
+                ExportKind::Procedure => SymbolKind::Procedure,
+            };
+            let symbol = Symbol {
+                name: export.name.clone(), kind, ty: resolve(&export.ty),
+                span: Span::default(), exported: true, scope, body: None,
+            };
+            symbols.declare(symbol).expect("parsing rejects duplicate exports");
+        }
+    }
+}
+
+// --------------------------- TEXT FORM ---------------------------
+impl fmt::Display for TypeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeRef::Basic(id) => {
+                let (_, name) = BASIC.iter().find(|(basic, _)| basic == id).expect("basic type");
+                write!(f, "{name}")
+            }
+            TypeRef::Struct(index) => write!(f, "#{index}"),
+        }
+    }
+}
+
+/// A constant as written in symbol files.
+struct ConstValue<'a>(&'a Value);
+
+impl fmt::Display for ConstValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Value::Integer(n) => write!(f, "integer {n}"),
+            Value::Real(x) => write!(f, "real {:016X}", x.to_bits()),
+            Value::Boolean(b) => write!(f, "boolean {}", if *b { "TRUE" } else { "FALSE" }),
+            Value::Char(c) => write!(f, "char {c}"),
+            Value::Set(bits) => write!(f, "set {bits}"),
+            Value::String(text) => {
+                write!(f, "string x")?;
+                text.bytes().try_for_each(|byte| write!(f, "{byte:02X}"))
+            }
+            Value::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+impl fmt::Display for SymbolFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "module {} {}", self.module, self.fingerprint)?;
+        for dependency in &self.imports {
+            writeln!(f, "import {} {}", dependency.module, dependency.fingerprint)?;
+            for (name, fingerprint) in &dependency.uses {
+                writeln!(f, "use {} {name} {fingerprint}", dependency.module)?;
+            }
+        }
+        for (index, structure) in self.structs.iter().enumerate() {
+            write!(f, "struct #{index} ")?;
+            match &structure.kind {
+                StructKind::Record { link, base, .. } => {
+                    write!(f, "record {link}")?;
+                    if let Some(base) = base {
+                        write!(f, " base {base}")?;
+                    }
+                }
+                StructKind::Pointer { base } => write!(f, "pointer {base}")?,
+                StructKind::Array { length, element } => write!(f, "array {length} {element}")?,
+                StructKind::OpenArray { element } => write!(f, "openarray {element}")?,
+                StructKind::String { length } => write!(f, "string {length}")?,
+                StructKind::Procedure { params, result } => {
+                    write!(f, "procedure")?;
+                    for (by_ref, ty) in params {
+                        write!(f, " {} {ty}", if *by_ref { "var" } else { "value" })?;
+                    }
+                    if let Some(result) = result {
+                        write!(f, " result {result}")?;
+                    }
+                }
+            }
+            if let Some(name) = &structure.name {
+                write!(f, " name {name}")?;
+            }
+            writeln!(f)?;
+            if let StructKind::Record { fields, .. } = &structure.kind {
+                for field in fields {
+                    writeln!(f, "field #{index} {}{} {}", field.name, if field.exported { "*" } else { "" }, field.ty)?;
+                }
+            }
+        }
+        for export in &self.exports {
+            match &export.kind {
+                ExportKind::Const(value) => write!(f, "const {} {} {}", export.name, export.ty, ConstValue(value))?,
+                ExportKind::Type => write!(f, "type {} {}", export.name, export.ty)?,
+                ExportKind::Var => write!(f, "var {} {}", export.name, export.ty)?,
+                ExportKind::Procedure => write!(f, "procedure {} {}", export.name, export.ty)?,
+            }
+            writeln!(f, " fp {}", export.fingerprint)?;
+        }
+        Ok(())
+    }
+}
+
+// --------------------------- PARSER ---------------------------
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("symbol file syntax error on line {line}: {message}")]
+pub struct SymbolFileError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Parses the textual form produced by printing a `SymbolFile`.
+pub fn parse(text: &str) -> Result<SymbolFile, SymbolFileError> {
+    let mut lines = text.lines().enumerate()
+        .map(|(number, text)| Line { number: number + 1, words: text.split_whitespace().collect(), position: 0 })
+        .filter(|line| !line.words.is_empty());
+
+    let Some(mut line) = lines.next() else {
+        return Err(SymbolFileError { line: 1, message: "expected 'module'".to_string() });
+    };
+    line.expect("module")?;
+    let module = line.next()?.to_string();
+    let fingerprint = line.fingerprint()?;
+    line.end()?;
+    let mut file = SymbolFile { module, fingerprint, imports: vec![], structs: vec![], exports: vec![] };
+
+    // Structures may refer to later ones, so references are checked once all are known.
+    let mut references = vec![];
+    let mut names = HashSet::new();
+    for mut line in lines {
+        match line.next()? {
+            "import" => {
+                let module = line.next()?.to_string();
+                let fingerprint = line.fingerprint()?;
+                line.end()?;
+                if file.imports.iter().any(|dependency| dependency.module == module) {
+                    return line.error(format!("duplicate import '{module}'"));
+                }
+                file.imports.push(Dependency { module, fingerprint, uses: vec![] });
+            }
+            "use" => {
+                let module = line.next()?;
+                let name = line.next()?.to_string();
+                let fingerprint = line.fingerprint()?;
+                line.end()?;
+                let Some(dependency) = file.imports.iter_mut().find(|dependency| dependency.module == module) else {
+                    return line.error(format!("'{module}' is not imported"));
+                };
+                dependency.uses.push((name, fingerprint));
+            }
+            "struct" => {
+                let index = line.reference()?;
+                if index != file.structs.len() {
+                    return line.error(format!("expected struct #{}", file.structs.len()));
+                }
+                let kind = match line.next()? {
+                    "record" => {
+                        let link = line.next()?.to_string();
+                        let base = if line.eat("base") { Some(line.ty(&mut references)?) } else { None };
+                        StructKind::Record { link, base, fields: vec![] }
+                    }
+                    "pointer" => StructKind::Pointer { base: line.ty(&mut references)? },
+                    "array" => StructKind::Array { length: line.number()?, element: line.ty(&mut references)? },
+                    "openarray" => StructKind::OpenArray { element: line.ty(&mut references)? },
+                    "string" => StructKind::String { length: line.number()? },
+                    "procedure" => {
+                        let mut params = vec![];
+                        loop {
+                            let by_ref = match line.peek() {
+                                Some("var") => true,
+                                Some("value") => false,
+                                _ => break,
+                            };
+                            line.next()?;
+                            params.push((by_ref, line.ty(&mut references)?));
+                        }
+                        let result = if line.eat("result") { Some(line.ty(&mut references)?) } else { None };
+                        StructKind::Procedure { params, result }
+                    }
+                    other => return line.error(format!("unknown structure '{other}'")),
+                };
+                let name = if line.eat("name") { Some(line.next()?.to_string()) } else { None };
+                line.end()?;
+                file.structs.push(Struct { name, kind });
+            }
+            "field" => {
+                let index = line.reference()?;
+                let name = line.next()?;
+                let (name, exported) = match name.strip_suffix('*') {
+                    Some(name) => (name.to_string(), true),
+                    None => (name.to_string(), false),
+                };
+                let ty = line.ty(&mut references)?;
+                line.end()?;
+                let Some(Struct { kind: StructKind::Record { fields, .. }, .. }) = file.structs.get_mut(index) else {
+                    return line.error(format!("#{index} is not a record"));
+                };
+                if fields.iter().any(|field| field.name == name) {
+                    return line.error(format!("duplicate field '{name}'"));
+                }
+                fields.push(Field { name, exported, ty });
+            }
+            keyword @ ("const" | "type" | "var" | "procedure") => {
+                let name = line.next()?.to_string();
+                let ty = line.ty(&mut references)?;
+                let kind = match keyword {
+                    "const" => ExportKind::Const(line.value()?),
+                    "type" => ExportKind::Type,
+                    "var" => ExportKind::Var,
+                    _ => ExportKind::Procedure,
+                };
+                line.expect("fp")?;
+                let fingerprint = line.fingerprint()?;
+                line.end()?;
+                if !names.insert(name.clone()) {
+                    return line.error(format!("duplicate export '{name}'"));
+                }
+                file.exports.push(Export { name, kind, ty, fingerprint });
+            }
+            other => return line.error(format!("unexpected '{other}'")),
+        }
+    }
+
+    if let Some((line, index)) = references.into_iter().find(|(_, index)| *index >= file.structs.len()) {
+        return Err(SymbolFileError { line, message: format!("undefined struct #{index}") });
+    }
+    Ok(file)
+}
+
+struct Line<'a> {
+    number: usize,
+    words: Vec<&'a str>,
+    position: usize,
+}
+
+impl<'a> Line<'a> {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, SymbolFileError> {
+        Err(SymbolFileError { line: self.number, message: message.into() })
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.words.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Result<&'a str, SymbolFileError> {
+        match self.peek() {
+            Some(word) => {
+                self.position += 1;
+                Ok(word)
+            }
+            None => self.error("unexpected end of line"),
+        }
+    }
+
+    fn eat(&mut self, word: &str) -> bool {
+        if self.peek() == Some(word) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), SymbolFileError> {
+        if self.eat(word) { Ok(()) } else { self.error(format!("expected '{word}'")) }
+    }
+
+    fn end(&self) -> Result<(), SymbolFileError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(word) => self.error(format!("unexpected '{word}'")),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, SymbolFileError> {
+        let word = self.next()?;
+        word.parse().or_else(|_| self.error(format!("expected a number, found '{word}'")))
+    }
+
+    fn reference(&mut self) -> Result<usize, SymbolFileError> {
+        let word = self.next()?;
+        match word.strip_prefix('#').and_then(|n| n.parse().ok()) {
+            Some(index) => Ok(index),
+            None => self.error(format!("expected '#index', found '{word}'")),
+        }
+    }
+
+    fn fingerprint(&mut self) -> Result<Fingerprint, SymbolFileError> {
+        let word = self.next()?;
+        match u64::from_str_radix(word, 16) {
+            Ok(hash) if word.len() == 16 => Ok(Fingerprint(hash)),
+            _ => self.error(format!("expected a fingerprint, found '{word}'")),
+        }
+    }
+
+    /// A type; references to structures are collected in `references` with their line.
+    fn ty(&mut self, references: &mut Vec<(usize, usize)>) -> Result<TypeRef, SymbolFileError> {
+        if self.peek().is_some_and(|word| word.starts_with('#')) {
+            let index = self.reference()?;
+            references.push((self.number, index));
+            return Ok(TypeRef::Struct(index));
+        }
+        let word = self.next()?;
+        match BASIC.iter().find(|(_, name)| *name == word) {
+            Some((id, _)) => Ok(TypeRef::Basic(*id)),
+            None => self.error(format!("unknown type '{word}'")),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, SymbolFileError> {
+        let value = match self.next()? {
+            "integer" => Value::Integer(self.number()?),
+            "real" => {
+                let word = self.next()?;
+                match u64::from_str_radix(word, 16) {
+                    Ok(bits) => Value::Real(f64::from_bits(bits)),
+                    Err(_) => return self.error(format!("expected the bits of a REAL, found '{word}'")),
+                }
+            }
+            "boolean" => match self.next()? {
+                "TRUE" => Value::Boolean(true),
+                "FALSE" => Value::Boolean(false),
+                other => return self.error(format!("expected TRUE or FALSE, found '{other}'")),
+            },
+            "char" => Value::Char(self.number()?),
+            "set" => Value::Set(self.number()?),
+            "string" => {
+                let word = self.next()?;
+                let bytes = word.strip_prefix('x').filter(|hex| hex.len() % 2 == 0).and_then(|hex| {
+                    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect::<Option<Vec<u8>>>()
+                });
+                match bytes {
+                    Some(bytes) => Value::String(bytes.into_iter().map(char::from).collect()),
+                    None => return self.error(format!("expected a hex string, found '{word}'")),
+                }
+            }
+            "nil" => Value::Nil,
+            other => return self.error(format!("unknown constant '{other}'")),
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::analysis;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+
+    fn export(source: &str) -> SymbolFile {
+        let module = Parser::new(Lexer::new(source)).parse().unwrap();
+        let analysis = analysis::check(&module).unwrap();
+        SymbolFile::export(&module.name.text, &analysis)
+    }
+
+    const SHAPES: &str = r#"
+        MODULE Shapes;
+        CONST Max* = 100; Scale* = 2.5; Title* = "Shapes"; Hidden = 1;
+        TYPE
+          Point* = RECORD x*, y*: INTEGER; tag: CHAR END;
+          Ref* = POINTER TO Point;
+          Path* = ARRAY 4 OF Point;
+          Visit* = PROCEDURE (VAR p: Point; name: ARRAY OF CHAR): BOOLEAN;
+        VAR origin*: Point; count: INTEGER; shape*: RECORD n*: INTEGER END;
+        PROCEDURE Norm*(r: Ref): INTEGER; RETURN ABS(r.x) + ABS(r.y) END Norm;
+        PROCEDURE Helper; END Helper;
+        END Shapes.
+    "#;
+
+    #[test]
+    fn exports_only_marked_declarations() {
+        let file = export(SHAPES);
+        let names: Vec<&str> = file.exports.iter().map(|export| export.name.as_str()).collect();
+        assert_eq!(names, ["Max", "Scale", "Title", "Point", "Ref", "Path", "Visit", "origin", "shape", "Norm"]);
+        assert_eq!(file.exports[0].kind, ExportKind::Const(Value::Integer(100)));
+    }
+
+    #[test]
+    fn writes_records_with_all_fields() {
+        let text = export(SHAPES).to_string();
+        assert!(text.starts_with(&format!("module Shapes {}\n", export(SHAPES).fingerprint)), "{text}");
+        assert!(text.contains("struct #1 record Shapes_Point name Shapes.Point\n"), "{text}");
+        assert!(text.contains("field #1 x* INTEGER\nfield #1 y* INTEGER\nfield #1 tag CHAR\n"), "{text}");
+        assert!(text.contains("record Shapes__Rec1\n"), "{text}");
+        assert!(text.contains("procedure var #1 value #5 result BOOLEAN name Shapes.Visit\n"), "{text}");
+        assert!(text.contains("const Title #0 string x536861706573 fp "), "{text}");
+    }
+
+    #[test]
+    fn parses_what_it_prints() {
+        let file = export(SHAPES);
+        assert_eq!(parse(&file.to_string()), Ok(file));
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        const MODULE: &str = "module M 0123456789abcdef\n";
+        let error = parse(&format!("{MODULE}struct #0 pointer #1\n")).unwrap_err();
+        assert_eq!(error, SymbolFileError { line: 2, message: "undefined struct #1".to_string() });
+        let error = parse(&format!("{MODULE}var x INTEGER fp 0000000000000000\nvar x REAL fp 0000000000000001\n")).unwrap_err();
+        assert_eq!(error.line, 3);
+        assert!(parse("struct #0 string 3").is_err());
+        assert!(parse("module M\n").is_err());
+        assert!(parse(&format!("{MODULE}field #0 x INTEGER\n")).is_err());
+        assert!(parse(&format!("{MODULE}var x INTEGER fp 12\n")).is_err());
+        assert!(parse(&format!("{MODULE}use A x 0000000000000000\n")).is_err());
+    }
+
+    #[test]
+    fn fingerprints_follow_the_interface_only() {
+        let before = export(SHAPES);
+        let after = export(&SHAPES.replace("ABS(r.x) + ABS(r.y)", "ABS(r.y) + ABS(r.x)").replace("count: INTEGER", "count: REAL"));
+        assert_eq!(after.fingerprint, before.fingerprint);
+
+        // A hidden field changes the layout of the record, and every export whose type uses it.
+        let after = export(&SHAPES.replace("tag: CHAR", "tag: INTEGER"));
+        assert_ne!(after.fingerprint, before.fingerprint);
+        let changed: Vec<&str> = before.exports.iter().zip(&after.exports)
+            .filter(|(old, new)| old.fingerprint != new.fingerprint)
+            .map(|(old, _)| old.name.as_str())
+            .collect();
+        assert_eq!(changed, ["Point", "Ref", "Path", "Visit", "origin", "Norm"]);
+        assert_eq!(Fingerprint::of(b"a").to_string(), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn records_the_imported_exports_that_were_used() {
+        let shapes = export(SHAPES);
+        let source = "MODULE Client; IMPORT S := Shapes, Shapes; VAR r: S.Ref; BEGIN r := NIL; r.x := S.Max + Shapes.Norm(r) END Client.";
+        let module = Parser::new(Lexer::new(source)).parse().unwrap();
+        let analysis = analysis::check_with_imports(&module, std::slice::from_ref(&shapes)).unwrap();
+        let file = SymbolFile::export("Client", &analysis);
+
+        let fingerprint = |name| shapes.exports.iter().find(|export| export.name == name).unwrap().fingerprint;
+        let uses = vec![("Max".to_string(), fingerprint("Max")), ("Ref".to_string(), fingerprint("Ref")), ("Norm".to_string(), fingerprint("Norm"))];
+        assert_eq!(file.imports, [Dependency { module: "Shapes".to_string(), fingerprint: shapes.fingerprint, uses }]);
+        assert_eq!(parse(&file.to_string()), Ok(file.clone()));
+
+        let unused = export(&SHAPES.replace("Path* = ARRAY 4", "Path* = ARRAY 5"));
+        assert_eq!(file.imports[0].changed_use(&unused), None);
+        let changed = export(&SHAPES.replace("Max* = 100", "Max* = 99"));
+        assert_eq!(file.imports[0].changed_use(&changed), Some("Max"));
+        let removed = export(&SHAPES.replace("PROCEDURE Norm*", "PROCEDURE Norm"));
+        assert_eq!(file.imports[0].changed_use(&removed), Some("Norm"));
+    }
+
+    #[test]
+    fn imports_each_type_once() {
+        let file = export(SHAPES);
+        let (mut types, mut symbols) = (TypeTable::new(), SymbolTable::new());
+        let first = symbols.open_scope(ScopeKind::Import, None);
+        file.import(&mut types, &mut symbols, first);
+        let second = symbols.open_scope(ScopeKind::Import, None);
+        file.import(&mut types, &mut symbols, second);
+
+        let ty = |scope, name| symbols.symbol(symbols.lookup_local(scope, name).unwrap()).ty;
+        assert_eq!(ty(first, "Point"), ty(second, "Point"));
+        assert_eq!(ty(first, "origin"), ty(second, "Point"));
+        assert_eq!(types.name(ty(first, "Ref")), "Shapes.Ref");
+        assert_eq!(types.link(ty(first, "Point")), Some("Shapes_Point"));
+        let Type::Pointer { base } = types.get(ty(first, "Ref")) else { panic!("pointer") };
+        assert_eq!(*base, ty(first, "Point"));
+        assert!(types.matching_signatures(ty(first, "Visit"), ty(second, "Visit")));
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use crate::frontend::const_eval::Value;
+use crate::frontend::types::TypeId;
+use crate::frontend::span::{Position, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScopeId(usize);
+
+/// The predeclared procedures of Oberon-07.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    Abs, Odd, Len, Lsl, Asr, Ror, Floor, Flt, Ord, Chr,
+    Inc, Dec, Incl, Excl, New, Assert, Pack, Unpk,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolKind {
+    Const(Value),
+    Type,
+    Var,
+    Param { by_ref: bool },
+    Field,
+    Procedure,
+    Builtin(Builtin),
+    /// An imported module; `name` is the real module name behind a possible alias.
+    Module { name: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub ty: TypeId,
+    pub span: Span,
+    pub exported: bool,
+    /// The scope the symbol is declared in.
+    pub scope: ScopeId,
+    /// For procedures, the scope holding their parameters and local declarations; for imported
+    /// modules, the scope holding their exports.
+    pub body: Option<ScopeId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    Universe,
+    Module,
+    Procedure(SymbolId),
+    Record,
+    /// The exports of an imported module.
+    Import,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub parent: Option<ScopeId>,
+    /// Nesting depth: 0 for the universe, 1 for the module, one more per enclosing procedure.
+    pub level: usize,
+    names: HashMap<String, SymbolId>,
+    symbols: Vec<SymbolId>,
+}
+
+impl Scope {
+    /// The symbols of this scope in declaration order.
+    pub fn symbols(&self) -> &[SymbolId] {
+        &self.symbols
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    scopes: Vec<Scope>,
+    references: HashMap<Span, SymbolId>,
+    /// The spans in `references`, ordered by where they start.
+    reference_spans: Vec<Span>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        let universe = Scope { kind: ScopeKind::Universe, parent: None, level: 0, names: HashMap::new(), symbols: vec![] };
+        Self { symbols: vec![], scopes: vec![universe], references: HashMap::new(), reference_spans: vec![] }
+    }
+
+    pub fn universe(&self) -> ScopeId {
+        ScopeId(0)
+    }
+
+    /// The scope of the module itself, if one has been opened.
+    pub fn module_scope(&self) -> Option<ScopeId> {
+        self.scopes.iter().position(|s| s.kind == ScopeKind::Module).map(ScopeId)
+    }
+
+    pub fn open_scope(&mut self, kind: ScopeKind, parent: Option<ScopeId>) -> ScopeId {
+        let level = match (kind, parent) {
+            (ScopeKind::Record | ScopeKind::Import, _) | (_, None) => 0,
+            (_, Some(parent)) => self.scope(parent).level + 1,
+        };
+        self.scopes.push(Scope { kind, parent, level, names: HashMap::new(), symbols: vec![] });
+        ScopeId(self.scopes.len() - 1)
+    }
+
+    /// Declares `symbol` in its scope, or returns the symbol that already uses the name there.
+    pub fn declare(&mut self, symbol: Symbol) -> Result<SymbolId, SymbolId> {
+        let id = SymbolId(self.symbols.len());
+        let scope = &mut self.scopes[symbol.scope.0];
+        if let Some(previous) = scope.names.get(&symbol.name) {
+            return Err(*previous);
+        }
+        scope.names.insert(symbol.name.clone(), id);
+        scope.symbols.push(id);
+        self.symbols.push(symbol);
+        Ok(id)
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id.0]
+    }
+
+    pub fn symbol_mut(&mut self, id: SymbolId) -> &mut Symbol {
+        &mut self.symbols[id.0]
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id.0]
+    }
+
+    /// Looks `name` up in `scope` only.
+    pub fn lookup_local(&self, scope: ScopeId, name: &str) -> Option<SymbolId> {
+        self.scope(scope).names.get(name).copied()
+    }
+
+    /// Looks `name` up in `scope` and then its enclosing scopes.
+    pub fn lookup(&self, scope: ScopeId, name: &str) -> Option<SymbolId> {
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            if let Some(symbol) = self.lookup_local(id, name) {
+                return Some(symbol);
+            }
+            current = self.scope(id).parent;
+        }
+        None
+    }
+
+    /// The exported symbols of the module, in declaration order.
+    pub fn exports(&self) -> impl Iterator<Item = SymbolId> + '_ {
+        self.module_scope()
+            .into_iter()
+            .flat_map(|scope| self.scope(scope).symbols().iter().copied())
+            .filter(|id| self.symbol(*id).exported)
+    }
+
+    /// Records that the identifier at `span` refers to `symbol`.
+    pub fn add_reference(&mut self, span: Span, symbol: SymbolId) {
+        if self.references.insert(span, symbol).is_none() {
+            // References mostly arrive in source order, so this nearly always appends.
+            let index = self.reference_spans.partition_point(|s| s.start.offset <= span.start.offset);
+            self.reference_spans.insert(index, span);
+        }
+    }
+
+    /// The symbol referred to by the identifier occurrence exactly at `span`.
+    pub fn reference(&self, span: Span) -> Option<SymbolId> {
+        self.references.get(&span).copied()
+    }
+
+    /// All places that refer to `symbol`, excluding its declaration.
+    pub fn references_to(&self, symbol: SymbolId) -> impl Iterator<Item = Span> + '_ {
+        self.reference_spans.iter().filter(move |span| self.references[span] == symbol).copied()
+    }
+
+    /// The symbol declared or referenced at `position`, if any.
+    pub fn symbol_at(&self, position: Position) -> Option<SymbolId> {
+        let contains = |span: &Span| span.start.offset <= position.offset && position.offset < span.end.offset;
+        let index = self.reference_spans.partition_point(|span| span.start.offset <= position.offset);
+        index.checked_sub(1)
+            .map(|index| self.reference_spans[index])
+            .filter(contains)
+            .map(|span| self.references[&span])
+            .or_else(|| self.symbols.iter().position(|s| s.scope != self.universe() && contains(&s.span)).map(SymbolId))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::types::INTEGER;
+
+    fn symbol(name: &str, kind: SymbolKind, scope: ScopeId) -> Symbol {
+        Symbol { name: name.to_string(), kind, ty: INTEGER, span: Span::default(), exported: false, scope, body: None }
+    }
+
+    #[test]
+    fn lookup_walks_enclosing_scopes() {
+        let mut table = SymbolTable::new();
+        let module = table.open_scope(ScopeKind::Module, Some(table.universe()));
+        let x = table.declare(symbol("x", SymbolKind::Var, module)).unwrap();
+        let p = table.declare(symbol("p", SymbolKind::Procedure, module)).unwrap();
+        let body = table.open_scope(ScopeKind::Procedure(p), Some(module));
+
+        assert_eq!(table.scope(body).level, 2);
+        assert_eq!(table.lookup(body, "x"), Some(x));
+        assert_eq!(table.lookup_local(body, "x"), None);
+    }
+
+    #[test]
+    fn rejects_duplicate_names_in_one_scope() {
+        let mut table = SymbolTable::new();
+        let module = table.open_scope(ScopeKind::Module, Some(table.universe()));
+        let first = table.declare(symbol("x", SymbolKind::Var, module)).unwrap();
+        assert_eq!(table.declare(symbol("x", SymbolKind::Type, module)).unwrap_err(), first);
+    }
+
+    #[test]
+    fn lists_exports_in_declaration_order() {
+        let mut table = SymbolTable::new();
+        let module = table.open_scope(ScopeKind::Module, Some(table.universe()));
+        for (name, exported) in [("b", true), ("a", false), ("c", true)] {
+            table.declare(Symbol { exported, ..symbol(name, SymbolKind::Var, module) }).unwrap();
+        }
+        let names: Vec<_> = table.exports().map(|id| table.symbol(id).name.as_str()).collect();
+        assert_eq!(names, ["b", "c"]);
+    }
+}
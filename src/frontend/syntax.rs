@@ -0,0 +1,469 @@
+//! The concrete syntax tree: every token of the source, comments and whitespace included, grouped
+//! into nodes for the constructs the parser recognized, so that the tree gives back the source
+//! text byte for byte. As in rowan, the green tree holds kinds and texts only and can be shared
+//! between trees, and the red tree over it adds offsets and parents. The AST is derived from it.
+
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::Arc;
+use crate::frontend::intern::Name;
+use crate::frontend::token::{Token, TokenKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    /// The whole source text: the module and whatever follows its final `.`.
+    Module,
+    ModuleHeading,
+    ImportList,
+    Import,
+    ModuleEnd,
+    Declarations,
+    ConstSection,
+    TypeSection,
+    VarSection,
+    /// A constant declaration with its semicolon; likewise the other declarations.
+    ConstDeclaration,
+    TypeDeclaration,
+    VarDeclaration,
+    ProcedureDeclaration,
+    ProcedureHeading,
+    ProcedureBody,
+    FormalParameters,
+    FPSection,
+    FormalType,
+    IdentDef,
+    QualIdent,
+
+    // Types
+    NamedType,
+    ArrayType,
+    RecordType,
+    /// The `(Base)` of a record type.
+    BaseType,
+    FieldList,
+    PointerType,
+    ProcedureType,
+
+    // Statements
+    /// Statements and the semicolons between them, after BEGIN where there is one.
+    StatementSequence,
+    Assignment,
+    ProcedureCall,
+    IfStatement,
+    ElsIf,
+    CaseStatement,
+    Case,
+    Label,
+    WhileStatement,
+    RepeatStatement,
+    ForStatement,
+
+    // Expressions
+    /// A number, string, character, NIL, TRUE or FALSE; a minus sign and a number for MIN(INTEGER).
+    Literal,
+    Set,
+    Element,
+    /// A designator, with actual parameters where it is a function call.
+    DesignatorExpression,
+    ActualParameters,
+    Parenthesized,
+    Unary,
+    Binary,
+    Designator,
+    FieldSelector,
+    IndexSelector,
+    DerefSelector,
+    TypeGuardSelector,
+
+    /// A type, statement or expression that failed to parse, which the AST keeps as an `Error`.
+    Error,
+    /// Tokens passed over while recovering from a syntax error, which the AST leaves out.
+    Skipped,
+}
+
+// --------------------------- GREEN TREE ---------------------------
+/// A token without a position.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    kind: TokenKind,
+    /// As in `Token`, but trivia and invalid tokens keep their text too, as the tree has no source
+    /// to take it from.
+    text: Option<Name>,
+}
+
+impl GreenToken {
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        self.kind.spelling().or(self.text.as_deref()).unwrap_or_default()
+    }
+
+    /// `token`, lexed from `source`.
+    fn new(token: &Token, source: &str) -> Self {
+        let text = match (&token.text, token.kind.spelling()) {
+            (Some(text), _) => Some(text.clone()),
+            (None, Some(_)) => None,
+            (None, None) => Some(Name::new(token.text_in(source))),
+        };
+        GreenToken { kind: token.kind, text }
+    }
+}
+
+/// A node without a position. Green nodes are immutable and shared, so that the tree of an edited
+/// text can take over the nodes of the tree before.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    kind: NodeKind,
+    /// The length of its text in bytes.
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(GreenToken),
+}
+
+impl GreenElement {
+    fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len,
+            GreenElement::Token(token) => token.text().len(),
+        }
+    }
+}
+
+impl GreenNode {
+    pub fn new(kind: NodeKind, children: Vec<GreenElement>) -> Self {
+        let len = children.iter().map(GreenElement::len).sum();
+        Self { kind, len, children }
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+}
+
+/// Builds a green tree bottom-up as the parser goes. Tokens are appended as they are consumed, and
+/// once a construct is complete the elements appended since its checkpoint become its node; a
+/// construct given up on leaves them to the node around it. Trivia wait for the next token or
+/// checkpoint, so that nodes start at their first token and trailing trivia stay outside.
+#[derive(Default)]
+pub(crate) struct Builder {
+    children: Vec<GreenElement>,
+    trivia: Vec<GreenToken>,
+}
+
+/// Where a node starts among the elements of a `Builder`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Checkpoint(usize);
+
+impl Builder {
+    /// Sets the trivia before the next token, in place of any not appended yet. The tokens were
+    /// lexed from `source`.
+    pub(crate) fn trivia(&mut self, trivia: &[Token], source: &str) {
+        self.trivia = trivia.iter().map(|token| GreenToken::new(token, source)).collect();
+    }
+
+    pub(crate) fn token(&mut self, token: &Token, source: &str) {
+        self.flush();
+        self.children.push(GreenElement::Token(GreenToken::new(token, source)));
+    }
+
+    /// Appends a node of an earlier tree.
+    pub(crate) fn node(&mut self, node: Arc<GreenNode>) {
+        self.flush();
+        self.children.push(GreenElement::Node(node));
+    }
+
+    pub(crate) fn checkpoint(&mut self) -> Checkpoint {
+        self.flush();
+        Checkpoint(self.children.len())
+    }
+
+    /// Makes the elements appended since `checkpoint` a node of `kind`.
+    pub(crate) fn wrap(&mut self, checkpoint: Checkpoint, kind: NodeKind) {
+        let children = self.children.split_off(checkpoint.0);
+        self.children.push(GreenElement::Node(Arc::new(GreenNode::new(kind, children))));
+    }
+
+    /// Whether nothing was appended since `checkpoint`.
+    pub(crate) fn is_empty_since(&self, checkpoint: Checkpoint) -> bool {
+        self.children.len() == checkpoint.0
+    }
+
+    /// The root node of `kind`, made of everything appended.
+    pub(crate) fn finish(mut self, kind: NodeKind) -> Arc<GreenNode> {
+        self.flush();
+        Arc::new(GreenNode::new(kind, self.children))
+    }
+
+    fn flush(&mut self) {
+        self.children.extend(self.trivia.drain(..).map(GreenElement::Token));
+    }
+}
+
+// --------------------------- RED TREE ---------------------------
+/// A green node at its place in a tree: with its offset in the source and its parent.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Arc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    offset: usize,
+}
+
+/// A green token at its place in a tree.
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: GreenToken,
+    parent: SyntaxNode,
+    offset: usize,
+}
+
+#[derive(Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Arc<GreenNode>) -> Self {
+        SyntaxNode(Rc::new(NodeData { green, parent: None, offset: 0 }))
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    /// The byte range of its text in the source.
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.len
+    }
+
+    pub fn text(&self) -> String {
+        self.descendant_tokens().iter().map(SyntaxToken::text).collect()
+    }
+
+    pub fn children_with_tokens(&self) -> impl Iterator<Item = SyntaxElement> + '_ {
+        let mut offset = self.0.offset;
+        self.0.green.children.iter().map(move |child| {
+            let element = match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                    green: green.clone(),
+                    parent: Some(self.clone()),
+                    offset,
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken { green: green.clone(), parent: self.clone(), offset }),
+            };
+            offset += child.len();
+            element
+        })
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        self.children_with_tokens().filter_map(|element| match element {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// The tokens among its children, trivia included.
+    pub fn tokens(&self) -> impl Iterator<Item = SyntaxToken> + '_ {
+        self.children_with_tokens().filter_map(|element| match element {
+            SyntaxElement::Node(_) => None,
+            SyntaxElement::Token(token) => Some(token),
+        })
+    }
+
+    /// The first child of `kind`.
+    pub fn child(&self, kind: NodeKind) -> Option<SyntaxNode> {
+        self.children().find(|child| child.kind() == kind)
+    }
+
+    /// The first token of `kind` among its children.
+    pub fn token(&self, kind: TokenKind) -> Option<SyntaxToken> {
+        self.tokens().find(|token| token.kind() == kind)
+    }
+
+    /// The node and the nodes inside it, each before its children.
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut result = vec![self.clone()];
+        let mut next = 0;
+        while next < result.len() {
+            let children: Vec<_> = result[next].children().collect();
+            next += 1;
+            result.splice(next..next, children);
+        }
+        result
+    }
+
+    /// All the tokens inside the node, in the order of the source.
+    pub fn descendant_tokens(&self) -> Vec<SyntaxToken> {
+        let mut result = vec![];
+        for element in self.children_with_tokens() {
+            match element {
+                SyntaxElement::Node(node) => result.extend(node.descendant_tokens()),
+                SyntaxElement::Token(token) => result.push(token),
+            }
+        }
+        result
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> TokenKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        self.green.text()
+    }
+
+    /// The text of the token, shared, for the kinds whose text varies: the interned name of an
+    /// identifier, for instance.
+    pub fn name(&self) -> Option<&Name> {
+        self.green.text.as_ref()
+    }
+
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+
+    /// The byte range of its text in the source.
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.text().len()
+    }
+}
+
+/// Nodes are equal when they are the same node of the same tree.
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+impl Eq for SyntaxNode {}
+
+/// The source text of the node.
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in self.descendant_tokens() {
+            f.write_str(token.text())?;
+        }
+        Ok(())
+    }
+}
+
+/// The kind and range of the node, as `IfStatement@12..40`; with `{:#?}` the whole subtree, one
+/// element a line.
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = self.text_range();
+        if !f.alternate() {
+            return write!(f, "{:?}@{}..{}", self.kind(), range.start, range.end);
+        }
+        fn dump(node: &SyntaxNode, depth: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let range = node.text_range();
+            writeln!(f, "{:indent$}{:?}@{}..{}", "", node.kind(), range.start, range.end, indent = depth * 2)?;
+            for element in node.children_with_tokens() {
+                match element {
+                    SyntaxElement::Node(child) => dump(&child, depth + 1, f)?,
+                    SyntaxElement::Token(token) => writeln!(f, "{:indent$}{token:?}", "", indent = depth * 2 + 2)?,
+                }
+            }
+            Ok(())
+        }
+        dump(self, 0, f)
+    }
+}
+
+/// The kind, range and text of the token, as `Identifier@3..8 "Shape"`.
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = self.text_range();
+        write!(f, "{:?}@{}..{} {:?}", self.kind(), range.start, range.end, self.text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+
+    fn parse(text: &str) -> SyntaxNode {
+        Parser::new(Lexer::with_trivia(text)).parse_syntax().0
+    }
+
+    #[test]
+    fn gives_back_the_source_text() {
+        let sources = [
+            "",
+            "  (* nothing but a comment *)\n",
+            "MODULE M; (* c *)\nIMPORT Out, F := Files;\nCONST k* = -1; s = \"s\"; c = 41X;\nTYPE P = POINTER TO RECORD (Base) a, b: ARRAY 3, k OF INTEGER END;\n  Q = PROCEDURE (VAR x: ARRAY OF CHAR): BOOLEAN;\nVAR v: P;\n\nPROCEDURE F(a: INTEGER): INTEGER;\n  VAR i: INTEGER;\nBEGIN\n  FOR i := 0 TO a BY 2 DO v.a[i] := v^.b[0] END;\n  CASE a OF 1..2, k: Out.Ln | 3: END;\n  WHILE a > 0 DO DEC(a) ELSIF a < 0 DO INC(a) END;\n  REPEAT a := a DIV 2 UNTIL ~(a IN {0, 2..4}) OR (v IS P)\n  RETURN a\nEND F;\n\nBEGIN\n  IF F(1) = 2 THEN v := NIL ELSE v(P).a[0] := 1 END\nEND M.\n  trailing junk",
+            "MODULE M; VAR x: ; BEGIN x := (1 + ; y := $ \"open\nEND M",
+            "MODULE M; PROCEDURE P; BEGIN (* unterminated",
+            "MODULE ä; BEGIN x := 1.0E400 END ä.",
+        ];
+        for source in sources {
+            let syntax = parse(source);
+            assert_eq!(syntax.to_string(), source);
+            assert_eq!(syntax.text_range(), 0..source.len());
+            assert_eq!(syntax.kind(), NodeKind::Module);
+        }
+    }
+
+    #[test]
+    fn groups_tokens_into_nodes() {
+        let syntax = parse("MODULE M;\nVAR x: INTEGER; (* x *)\nBEGIN x := 1 END M.");
+
+        let var = syntax.descendants().into_iter().find(|node| node.kind() == NodeKind::VarDeclaration).unwrap();
+        assert_eq!(var.to_string(), "x: INTEGER;");
+        assert_eq!(var.text_range(), 14..25);
+        assert_eq!(var.parent().unwrap().kind(), NodeKind::VarSection);
+        let assignment = syntax.descendants().into_iter().find(|node| node.kind() == NodeKind::Assignment).unwrap();
+        let kinds: Vec<NodeKind> = assignment.children().map(|child| child.kind()).collect();
+        assert_eq!(kinds, [NodeKind::Designator, NodeKind::Literal]);
+        assert_eq!(format!("{:?}", assignment.token(TokenKind::Assign).unwrap()), "Assign@42..44 \":=\"");
+        let comment = syntax.descendant_tokens().into_iter().find(|token| token.kind() == TokenKind::Comment).unwrap();
+        // Trivia between constructs stay outside of both.
+        assert_eq!(comment.parent(), &syntax);
+    }
+
+    #[test]
+    fn keeps_tokens_passed_over_in_recovery() {
+        let syntax = parse("MODULE M; BEGIN x := ; y := 2 END M. rest");
+
+        let kinds: Vec<NodeKind> = syntax.descendants().iter().map(SyntaxNode::kind).collect();
+        assert!(kinds.contains(&NodeKind::Error));
+        let skipped = syntax.children().last().unwrap();
+        assert_eq!(skipped.kind(), NodeKind::Skipped);
+        assert_eq!(skipped.to_string(), "rest");
+    }
+}
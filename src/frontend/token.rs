@@ -1,30 +1,291 @@
+use std::fmt;
+use crate::frontend::intern::Name;
 use crate::frontend::span::{Span};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TokenKind {
     Identifier,
     Number,
     String,
-    OperatorOrDelimiter,
+    /// A character constant given by its code, `41X`.
+    Char,
+
+    // Keywords
+    Array,
+    Begin,
+    By,
+    Case,
+    Const,
+    Div,
+    Do,
+    Else,
+    Elsif,
+    End,
+    False,
+    For,
+    If,
+    Import,
+    In,
+    Is,
+    Mod,
+    Module,
+    Nil,
+    Of,
+    Or,
+    Pointer,
+    Procedure,
+    Record,
+    Repeat,
+    Return,
+    Then,
+    To,
+    True,
+    Type,
+    Until,
+    Var,
+    While,
+
+    // Operators and delimiters
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Tilde,
+    Ampersand,
+    Dot,
+    DotDot,
+    Comma,
+    Semicolon,
+    Pipe,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Colon,
+    Assign,
+    Caret,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+
     Eof,
-    Invalid
+    Invalid,
+    /// A comment, `(* ... *)`, from a lexer that keeps trivia.
+    Comment,
+    /// Blanks and line breaks, from a lexer that keeps trivia.
+    Whitespace,
+}
+
+impl TokenKind {
+    /// Whether tokens of this kind are comments or whitespace, which the parser passes over.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenKind::Comment | TokenKind::Whitespace)
+    }
+
+    /// The keyword spelled `text`, if it is one.
+    pub fn keyword(text: &str) -> Option<TokenKind> {
+        let kind = match text {
+            "ARRAY" => TokenKind::Array,
+            "BEGIN" => TokenKind::Begin,
+            "BY" => TokenKind::By,
+            "CASE" => TokenKind::Case,
+            "CONST" => TokenKind::Const,
+            "DIV" => TokenKind::Div,
+            "DO" => TokenKind::Do,
+            "ELSE" => TokenKind::Else,
+            "ELSIF" => TokenKind::Elsif,
+            "END" => TokenKind::End,
+            "FALSE" => TokenKind::False,
+            "FOR" => TokenKind::For,
+            "IF" => TokenKind::If,
+            "IMPORT" => TokenKind::Import,
+            "IN" => TokenKind::In,
+            "IS" => TokenKind::Is,
+            "MOD" => TokenKind::Mod,
+            "MODULE" => TokenKind::Module,
+            "NIL" => TokenKind::Nil,
+            "OF" => TokenKind::Of,
+            "OR" => TokenKind::Or,
+            "POINTER" => TokenKind::Pointer,
+            "PROCEDURE" => TokenKind::Procedure,
+            "RECORD" => TokenKind::Record,
+            "REPEAT" => TokenKind::Repeat,
+            "RETURN" => TokenKind::Return,
+            "THEN" => TokenKind::Then,
+            "TO" => TokenKind::To,
+            "TRUE" => TokenKind::True,
+            "TYPE" => TokenKind::Type,
+            "UNTIL" => TokenKind::Until,
+            "VAR" => TokenKind::Var,
+            "WHILE" => TokenKind::While,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// How keywords and symbols are written; `None` for the kinds whose text varies.
+    pub fn spelling(self) -> Option<&'static str> {
+        let text = match self {
+            TokenKind::Array => "ARRAY",
+            TokenKind::Begin => "BEGIN",
+            TokenKind::By => "BY",
+            TokenKind::Case => "CASE",
+            TokenKind::Const => "CONST",
+            TokenKind::Div => "DIV",
+            TokenKind::Do => "DO",
+            TokenKind::Else => "ELSE",
+            TokenKind::Elsif => "ELSIF",
+            TokenKind::End => "END",
+            TokenKind::False => "FALSE",
+            TokenKind::For => "FOR",
+            TokenKind::If => "IF",
+            TokenKind::Import => "IMPORT",
+            TokenKind::In => "IN",
+            TokenKind::Is => "IS",
+            TokenKind::Mod => "MOD",
+            TokenKind::Module => "MODULE",
+            TokenKind::Nil => "NIL",
+            TokenKind::Of => "OF",
+            TokenKind::Or => "OR",
+            TokenKind::Pointer => "POINTER",
+            TokenKind::Procedure => "PROCEDURE",
+            TokenKind::Record => "RECORD",
+            TokenKind::Repeat => "REPEAT",
+            TokenKind::Return => "RETURN",
+            TokenKind::Then => "THEN",
+            TokenKind::To => "TO",
+            TokenKind::True => "TRUE",
+            TokenKind::Type => "TYPE",
+            TokenKind::Until => "UNTIL",
+            TokenKind::Var => "VAR",
+            TokenKind::While => "WHILE",
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Star => "*",
+            TokenKind::Slash => "/",
+            TokenKind::Tilde => "~",
+            TokenKind::Ampersand => "&",
+            TokenKind::Dot => ".",
+            TokenKind::DotDot => "..",
+            TokenKind::Comma => ",",
+            TokenKind::Semicolon => ";",
+            TokenKind::Pipe => "|",
+            TokenKind::LParen => "(",
+            TokenKind::RParen => ")",
+            TokenKind::LBracket => "[",
+            TokenKind::RBracket => "]",
+            TokenKind::LBrace => "{",
+            TokenKind::RBrace => "}",
+            TokenKind::Colon => ":",
+            TokenKind::Assign => ":=",
+            TokenKind::Caret => "^",
+            TokenKind::Equal => "=",
+            TokenKind::NotEqual => "#",
+            TokenKind::Less => "<",
+            TokenKind::LessEqual => "<=",
+            TokenKind::Greater => ">",
+            TokenKind::GreaterEqual => ">=",
+            TokenKind::Identifier | TokenKind::Number | TokenKind::String | TokenKind::Char | TokenKind::Eof
+            | TokenKind::Invalid | TokenKind::Comment | TokenKind::Whitespace => return None,
+        };
+        Some(text)
+    }
+
+    /// How the kind is named in error messages: keywords as themselves, symbols in quotes.
+    pub fn name(self) -> &'static str {
+        match self {
+            TokenKind::Identifier => "identifier",
+            TokenKind::Number => "number",
+            TokenKind::String => "string",
+            TokenKind::Char => "character",
+            TokenKind::Plus => "'+'",
+            TokenKind::Minus => "'-'",
+            TokenKind::Star => "'*'",
+            TokenKind::Slash => "'/'",
+            TokenKind::Tilde => "'~'",
+            TokenKind::Ampersand => "'&'",
+            TokenKind::Dot => "'.'",
+            TokenKind::DotDot => "'..'",
+            TokenKind::Comma => "','",
+            TokenKind::Semicolon => "';'",
+            TokenKind::Pipe => "'|'",
+            TokenKind::LParen => "'('",
+            TokenKind::RParen => "')'",
+            TokenKind::LBracket => "'['",
+            TokenKind::RBracket => "']'",
+            TokenKind::LBrace => "'{'",
+            TokenKind::RBrace => "'}'",
+            TokenKind::Colon => "':'",
+            TokenKind::Assign => "':='",
+            TokenKind::Caret => "'^'",
+            TokenKind::Equal => "'='",
+            TokenKind::NotEqual => "'#'",
+            TokenKind::Less => "'<'",
+            TokenKind::LessEqual => "'<='",
+            TokenKind::Greater => "'>'",
+            TokenKind::GreaterEqual => "'>='",
+            TokenKind::Eof => "end of file",
+            TokenKind::Invalid => "invalid token",
+            TokenKind::Comment => "comment",
+            TokenKind::Whitespace => "whitespace",
+            keyword => keyword.spelling().unwrap_or_default(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
-    pub lexeme: String,
+    /// The interned name of an identifier, or the text of a number, string or character. `None`
+    /// for the other kinds: the kind determines their text, or for trivia and invalid tokens it is
+    /// the source over `span`.
+    pub text: Option<Name>,
     pub span: Span,
 }
 
 impl Token {
     pub(crate) fn invalid() -> Token {
-        Token { kind: TokenKind::Invalid, lexeme: "".to_string(), span: Span::default() }
+        Token::new(TokenKind::Invalid, Span::default())
+    }
+
+    pub(crate) fn new(kind: TokenKind, span: Span) -> Token {
+        Token { kind, text: None, span }
+    }
+
+    pub(crate) fn with_text(kind: TokenKind, text: Name, span: Span) -> Token {
+        Token { kind, text: Some(text), span }
+    }
+
+    /// The token as it appears in the source; empty for trivia and invalid tokens, whose text is
+    /// only in the source.
+    pub fn lexeme(&self) -> &str {
+        self.kind.spelling().or(self.text.as_deref()).unwrap_or_default()
+    }
+
+    /// The token as it appears in `source`, the text it was lexed from.
+    pub fn text_in<'s>(&'s self, source: &'s str) -> &'s str {
+        match (self.kind.spelling(), &self.text) {
+            (Some(spelling), _) => spelling,
+            (None, Some(text)) => text,
+            (None, None) => &source[self.span.start.offset..self.span.end.offset],
+        }
     }
 }
 
-impl Token {
-    pub(crate) fn new(kind: TokenKind, lexeme: &str, span: Span) -> Token {
-        Token { kind, lexeme: lexeme.to_string(), span }
+/// How a token is named in error messages: literals with their text, everything else by kind.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            TokenKind::Identifier => write!(f, "identifier '{}'", self.lexeme()),
+            TokenKind::Number => write!(f, "number {}", self.lexeme()),
+            TokenKind::String => write!(f, "string {}", self.lexeme()),
+            TokenKind::Char => write!(f, "character {}", self.lexeme()),
+            kind => write!(f, "{}", kind.name()),
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,383 @@
+use std::collections::{HashMap, HashSet};
+use crate::frontend::symbols::ScopeId;
+
+/// A resolved type. Unlike `ast::Type`, names are resolved and array lengths evaluated;
+/// types refer to each other through `TypeId`s into a `TypeTable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TypeId(usize);
+
+pub const BOOLEAN: TypeId = TypeId(0);
+pub const CHAR: TypeId = TypeId(1);
+pub const INTEGER: TypeId = TypeId(2);
+pub const REAL: TypeId = TypeId(3);
+pub const BYTE: TypeId = TypeId(4);
+pub const SET: TypeId = TypeId(5);
+/// The type of `NIL`.
+pub const NIL: TypeId = TypeId(6);
+/// The "result type" of proper procedures and standard procedures.
+pub const NO_TYPE: TypeId = TypeId(7);
+/// Given to expressions that failed to check, so one mistake is reported only once.
+pub const ERROR: TypeId = TypeId(8);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Boolean,
+    Char,
+    Integer,
+    Real,
+    Byte,
+    Set,
+    Nil,
+    NoType,
+    Error,
+    /// A string constant of `length` characters.
+    String { length: usize },
+    Array { length: i64, element: TypeId },
+    OpenArray { element: TypeId },
+    /// The fields are the symbols of a record scope in the symbol table.
+    Record { base: Option<TypeId>, fields: ScopeId },
+    Pointer { base: TypeId },
+    Procedure { params: Vec<Param>, result: Option<TypeId> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub by_ref: bool,
+    pub ty: TypeId,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeTable {
+    types: Vec<Type>,
+    names: HashMap<TypeId, String>,
+    /// The link names of record types declared in imported modules.
+    links: HashMap<TypeId, String>,
+    strings: HashMap<usize, TypeId>,
+    open_arrays: HashMap<TypeId, TypeId>,
+}
+
+impl Default for TypeTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeTable {
+    pub fn new() -> Self {
+        let mut table = Self {
+            types: vec![
+                Type::Boolean, Type::Char, Type::Integer, Type::Real, Type::Byte, Type::Set,
+                Type::Nil, Type::NoType, Type::Error,
+            ],
+            names: HashMap::new(),
+            links: HashMap::new(),
+            strings: HashMap::new(),
+            open_arrays: HashMap::new(),
+        };
+        for (id, name) in [(BOOLEAN, "BOOLEAN"), (CHAR, "CHAR"), (INTEGER, "INTEGER"), (REAL, "REAL"), (BYTE, "BYTE"), (SET, "SET")] {
+            table.names.insert(id, name.to_string());
+        }
+        table
+    }
+
+    /// Adds a new, distinct type. Structured types are only ever the same type as themselves.
+    pub fn add(&mut self, ty: Type) -> TypeId {
+        self.types.push(ty);
+        TypeId(self.types.len() - 1)
+    }
+
+    /// Replaces a type added earlier, e.g. to fill in a forward-declared pointer base.
+    pub fn set(&mut self, id: TypeId, ty: Type) {
+        self.types[id.0] = ty;
+    }
+
+    pub fn get(&self, id: TypeId) -> &Type {
+        &self.types[id.0]
+    }
+
+    /// All types in the order they were added.
+    pub fn ids(&self) -> impl Iterator<Item = TypeId> + use<> {
+        (0..self.types.len()).map(TypeId)
+    }
+
+    /// The name of the type declaration that denotes `id`, if any.
+    pub fn declared_name(&self, id: TypeId) -> Option<&str> {
+        self.names.get(&id).map(|name| name.as_str())
+    }
+
+    /// The interned type of string constants with `length` characters.
+    pub fn string(&mut self, length: usize) -> TypeId {
+        if let Some(id) = self.strings.get(&length) {
+            return *id;
+        }
+        let id = self.add(Type::String { length });
+        self.strings.insert(length, id);
+        id
+    }
+
+    /// The interned open array type with the given element type.
+    pub fn open_array(&mut self, element: TypeId) -> TypeId {
+        if let Some(id) = self.open_arrays.get(&element) {
+            return *id;
+        }
+        let id = self.add(Type::OpenArray { element });
+        self.open_arrays.insert(element, id);
+        id
+    }
+
+    /// Remembers the name of the first type declaration that denotes `id`.
+    pub fn set_name(&mut self, id: TypeId, name: &str) {
+        self.names.entry(id).or_insert_with(|| name.to_string());
+    }
+
+    /// The type whose declaration is called `name`; imported types are called `Module.Name`.
+    pub fn named(&self, name: &str) -> Option<TypeId> {
+        self.names.iter().find(|(_, n)| n.as_str() == name).map(|(id, _)| *id)
+    }
+
+    /// Marks `id` as a record type declared in an imported module under the link name `link`.
+    pub fn set_link(&mut self, id: TypeId, link: &str) {
+        self.links.insert(id, link.to_string());
+    }
+
+    /// The link name of an imported record type.
+    pub fn link(&self, id: TypeId) -> Option<&str> {
+        self.links.get(&id).map(|link| link.as_str())
+    }
+
+    /// The imported record type with link name `link`.
+    pub fn linked(&self, link: &str) -> Option<TypeId> {
+        self.links.iter().find(|(_, l)| l.as_str() == link).map(|(id, _)| *id)
+    }
+
+    /// The names under which the record types are known to the linker: `M_T` for a record declared
+    /// as `T` in module `module`, `M__Rec<n>` for an anonymous one, and their link names for
+    /// imported records.
+    pub fn record_names(&self, module: &str) -> HashMap<TypeId, String> {
+        let mut names = HashMap::new();
+        let mut used = HashSet::new();
+        let records = self.ids().filter(|id| matches!(self.get(*id), Type::Record { .. }));
+        for (index, record) in records.filter(|id| !self.links.contains_key(id)).enumerate() {
+            let mut name = match self.declared_name(record) {
+                Some(declared) => format!("{module}_{declared}"),
+                None => format!("{module}__Rec{index}"),
+            };
+            if !used.insert(name.clone()) {
+                name = format!("{name}_{index}");
+                used.insert(name.clone());
+            }
+            names.insert(record, name);
+        }
+        names.extend(self.links.iter().map(|(id, link)| (*id, link.clone())));
+        names
+    }
+
+    /// A readable description of `id` for diagnostics.
+    pub fn name(&self, id: TypeId) -> String {
+        if let Some(name) = self.names.get(&id) {
+            return name.clone();
+        }
+        match self.get(id) {
+            Type::Boolean => "BOOLEAN".to_string(),
+            Type::Char => "CHAR".to_string(),
+            Type::Integer => "INTEGER".to_string(),
+            Type::Real => "REAL".to_string(),
+            Type::Byte => "BYTE".to_string(),
+            Type::Set => "SET".to_string(),
+            Type::Nil => "NIL".to_string(),
+            Type::NoType => "no value".to_string(),
+            Type::Error => "<error>".to_string(),
+            Type::String { length } => format!("string of length {length}"),
+            Type::Array { length, element } => format!("ARRAY {length} OF {}", self.name(*element)),
+            Type::OpenArray { element } => format!("ARRAY OF {}", self.name(*element)),
+            Type::Record { .. } => "RECORD".to_string(),
+            Type::Pointer { base } => format!("POINTER TO {}", self.name(*base)),
+            Type::Procedure { .. } => "PROCEDURE".to_string(),
+        }
+    }
+
+    pub fn is_error(&self, id: TypeId) -> bool {
+        matches!(self.get(id), Type::Error)
+    }
+
+    pub fn is_integer(&self, id: TypeId) -> bool {
+        matches!(self.get(id), Type::Integer | Type::Byte)
+    }
+
+    pub fn is_numeric(&self, id: TypeId) -> bool {
+        matches!(self.get(id), Type::Integer | Type::Byte | Type::Real)
+    }
+
+    /// CHAR, or a string constant of length one which may stand for a CHAR.
+    pub fn is_char_like(&self, id: TypeId) -> bool {
+        matches!(self.get(id), Type::Char | Type::String { length: 1 })
+    }
+
+    /// A string constant or an array of CHAR.
+    pub fn is_string_like(&self, id: TypeId) -> bool {
+        match self.get(id) {
+            Type::String { .. } => true,
+            Type::Array { element, .. } | Type::OpenArray { element } => *element == CHAR,
+            _ => false,
+        }
+    }
+
+    /// The record type of a record or of a pointer to a record.
+    pub fn record_of(&self, id: TypeId) -> Option<TypeId> {
+        match self.get(id) {
+            Type::Record { .. } => Some(id),
+            Type::Pointer { base } if matches!(self.get(*base), Type::Record { .. }) => Some(*base),
+            _ => None,
+        }
+    }
+
+    /// Same type: both denote the same declared type (aliases included).
+    pub fn same(&self, a: TypeId, b: TypeId) -> bool {
+        a == b
+    }
+
+    /// Equal types: the same type, open arrays with equal element types, or procedure types
+    /// with matching formal parameters.
+    pub fn equal(&self, a: TypeId, b: TypeId) -> bool {
+        if self.same(a, b) {
+            return true;
+        }
+        match (self.get(a), self.get(b)) {
+            (Type::OpenArray { element: ea }, Type::OpenArray { element: eb }) => self.equal(*ea, *eb),
+            (Type::Procedure { .. }, Type::Procedure { .. }) => self.matching_signatures(a, b),
+            _ => false,
+        }
+    }
+
+    /// Both procedure types have the same result and pairwise equal parameters of the same kind.
+    pub fn matching_signatures(&self, a: TypeId, b: TypeId) -> bool {
+        match (self.get(a), self.get(b)) {
+            (Type::Procedure { params: pa, result: ra }, Type::Procedure { params: pb, result: rb }) => {
+                ra == rb
+                    && pa.len() == pb.len()
+                    && pa.iter().zip(pb).all(|(x, y)| x.by_ref == y.by_ref && self.equal(x.ty, y.ty))
+            }
+            _ => false,
+        }
+    }
+
+    /// `extension` is `base` or (transitively) extends it; pointers compare by their record base.
+    pub fn extends(&self, extension: TypeId, base: TypeId) -> bool {
+        let (extension, base) = match (self.get(extension), self.get(base)) {
+            (Type::Pointer { base: e }, Type::Pointer { base: b }) => (*e, *b),
+            _ => (extension, base),
+        };
+        let mut current = Some(extension);
+        while let Some(id) = current {
+            if id == base {
+                return true;
+            }
+            current = match self.get(id) {
+                Type::Record { base, .. } => *base,
+                _ => None,
+            };
+        }
+        false
+    }
+
+    /// Whether a value of type `source` may be assigned to a variable of type `target`.
+    pub fn assignable(&self, target: TypeId, source: TypeId) -> bool {
+        if self.is_error(target) || self.is_error(source) || self.equal(target, source) {
+            return true;
+        }
+        match (self.get(target), self.get(source)) {
+            (Type::Integer | Type::Byte, Type::Integer | Type::Byte) => true,
+            (Type::Char, Type::String { length: 1 }) => true,
+            (Type::Array { length, element }, Type::String { length: n }) => *element == CHAR && (*n as i64) < *length,
+            (Type::Array { element: et, .. }, Type::OpenArray { element: es }) => self.equal(*et, *es),
+            (Type::Pointer { .. } | Type::Procedure { .. }, Type::Nil) => true,
+            (Type::Pointer { .. }, Type::Pointer { .. }) | (Type::Record { .. }, Type::Record { .. }) => self.extends(source, target),
+            _ => false,
+        }
+    }
+
+    /// Whether an actual parameter of type `actual` may be passed for a formal of type `formal`
+    /// where the formal is an open array.
+    pub fn array_compatible(&self, formal: TypeId, actual: TypeId) -> bool {
+        if self.equal(formal, actual) {
+            return true;
+        }
+        match (self.get(formal), self.get(actual)) {
+            (Type::OpenArray { element: ef }, Type::Array { element: ea, .. } | Type::OpenArray { element: ea }) => {
+                self.array_compatible(*ef, *ea)
+            }
+            (Type::OpenArray { element }, Type::String { .. }) => *element == CHAR,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::symbols::{ScopeKind, SymbolTable};
+
+    fn record(types: &mut TypeTable, symbols: &mut SymbolTable, base: Option<TypeId>) -> TypeId {
+        let fields = symbols.open_scope(ScopeKind::Record, None);
+        types.add(Type::Record { base, fields })
+    }
+
+    #[test]
+    fn interns_basic_string_and_open_array_types() {
+        let mut types = TypeTable::new();
+        assert_eq!(types.get(INTEGER), &Type::Integer);
+        assert_eq!(types.string(3), types.string(3));
+        assert_ne!(types.string(3), types.string(4));
+        assert_eq!(types.open_array(CHAR), types.open_array(CHAR));
+    }
+
+    #[test]
+    fn distinguishes_same_and_equal_types() {
+        let mut types = TypeTable::new();
+        let a = types.add(Type::Array { length: 4, element: INTEGER });
+        let b = types.add(Type::Array { length: 4, element: INTEGER });
+        assert!(!types.same(a, b) && !types.equal(a, b));
+
+        let p = types.add(Type::Procedure { params: vec![Param { by_ref: true, ty: INTEGER }], result: None });
+        let q = types.add(Type::Procedure { params: vec![Param { by_ref: true, ty: INTEGER }], result: None });
+        let r = types.add(Type::Procedure { params: vec![Param { by_ref: false, ty: INTEGER }], result: None });
+        assert!(!types.same(p, q));
+        assert!(types.equal(p, q));
+        assert!(!types.equal(p, r));
+    }
+
+    #[test]
+    fn follows_record_extension_chains() {
+        let (mut types, mut symbols) = (TypeTable::new(), SymbolTable::new());
+        let base = record(&mut types, &mut symbols, None);
+        let middle = record(&mut types, &mut symbols, Some(base));
+        let leaf = record(&mut types, &mut symbols, Some(middle));
+        let (base_ptr, leaf_ptr) = (types.add(Type::Pointer { base }), types.add(Type::Pointer { base: leaf }));
+
+        assert!(types.extends(leaf, base));
+        assert!(!types.extends(base, leaf));
+        assert!(types.assignable(base_ptr, leaf_ptr));
+        assert!(!types.assignable(leaf_ptr, base_ptr));
+        assert!(types.assignable(leaf_ptr, NIL));
+    }
+
+    #[test]
+    fn checks_assignment_and_array_compatibility() {
+        let mut types = TypeTable::new();
+        let text = types.add(Type::Array { length: 8, element: CHAR });
+        let (short, long, single) = (types.string(7), types.string(8), types.string(1));
+        assert!(types.assignable(text, short));
+        assert!(!types.assignable(text, long));
+        assert!(types.assignable(CHAR, single));
+        assert!(types.assignable(INTEGER, BYTE));
+        assert!(!types.assignable(REAL, INTEGER));
+
+        let open = types.open_array(CHAR);
+        let matrix = types.add(Type::Array { length: 2, element: text });
+        let open_open = types.open_array(open);
+        assert!(types.array_compatible(open, text));
+        assert!(types.array_compatible(open, long));
+        assert!(types.array_compatible(open_open, matrix));
+        assert!(!types.array_compatible(open, matrix));
+    }
+}
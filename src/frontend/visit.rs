@@ -0,0 +1,1013 @@
+//! Walking the AST. `Visitor` looks at every node of a module and `VisitorMut` may change them in
+//! place. Each `visit_` method by default calls the `walk_` function for its node, which visits
+//! the node's children in source order; a pass overrides the methods for the nodes it is after and
+//! calls the `walk_` function from them to carry on below.
+
+use crate::frontend::ast::{Case, Comment, Comments, ConstDeclaration, Declarations, Designator, Element, ElsIf, Expression, FPSection, FieldList, FormalParameters, FormalType, Identifier, IdentifierDef, Import, Label, LabelValue, Module, ProcedureBody, ProcedureDeclaration, ProcedureHeader, QualifiedIdentifier, Selector, Statement, StatementSequence, Type, TypeDeclaration, VarDeclaration};
+use crate::frontend::span::Span;
+
+// --------------------------- VISITOR ---------------------------
+pub trait Visitor {
+    fn visit_module(&mut self, module: &Module) {
+        walk_module(self, module);
+    }
+
+    fn visit_import(&mut self, import: &Import) {
+        walk_import(self, import);
+    }
+
+    fn visit_declarations(&mut self, declarations: &Declarations) {
+        walk_declarations(self, declarations);
+    }
+
+    fn visit_const_declaration(&mut self, declaration: &ConstDeclaration) {
+        walk_const_declaration(self, declaration);
+    }
+
+    fn visit_type_declaration(&mut self, declaration: &TypeDeclaration) {
+        walk_type_declaration(self, declaration);
+    }
+
+    fn visit_var_declaration(&mut self, declaration: &VarDeclaration) {
+        walk_var_declaration(self, declaration);
+    }
+
+    fn visit_procedure_declaration(&mut self, declaration: &ProcedureDeclaration) {
+        walk_procedure_declaration(self, declaration);
+    }
+
+    fn visit_procedure_header(&mut self, header: &ProcedureHeader) {
+        walk_procedure_header(self, header);
+    }
+
+    fn visit_procedure_body(&mut self, body: &ProcedureBody) {
+        walk_procedure_body(self, body);
+    }
+
+    fn visit_formal_parameters(&mut self, params: &FormalParameters) {
+        walk_formal_parameters(self, params);
+    }
+
+    fn visit_fp_section(&mut self, section: &FPSection) {
+        walk_fp_section(self, section);
+    }
+
+    fn visit_formal_type(&mut self, ty: &FormalType) {
+        walk_formal_type(self, ty);
+    }
+
+    fn visit_type(&mut self, ty: &Type) {
+        walk_type(self, ty);
+    }
+
+    fn visit_field_list(&mut self, fields: &FieldList) {
+        walk_field_list(self, fields);
+    }
+
+    fn visit_statement_sequence(&mut self, stmts: &StatementSequence) {
+        walk_statement_sequence(self, stmts);
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        walk_statement(self, stmt);
+    }
+
+    fn visit_elsif(&mut self, elsif: &ElsIf) {
+        walk_elsif(self, elsif);
+    }
+
+    fn visit_case(&mut self, case: &Case) {
+        walk_case(self, case);
+    }
+
+    fn visit_label(&mut self, label: &Label) {
+        walk_label(self, label);
+    }
+
+    fn visit_label_value(&mut self, value: &LabelValue) {
+        walk_label_value(self, value);
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        walk_expression(self, expr);
+    }
+
+    fn visit_element(&mut self, element: &Element) {
+        walk_element(self, element);
+    }
+
+    fn visit_designator(&mut self, designator: &Designator) {
+        walk_designator(self, designator);
+    }
+
+    fn visit_selector(&mut self, selector: &Selector) {
+        walk_selector(self, selector);
+    }
+
+    fn visit_qualified_identifier(&mut self, ident: &QualifiedIdentifier) {
+        walk_qualified_identifier(self, ident);
+    }
+
+    fn visit_identifier_def(&mut self, ident: &IdentifierDef) {
+        walk_identifier_def(self, ident);
+    }
+
+    fn visit_identifier(&mut self, ident: &Identifier) {
+        walk_identifier(self, ident);
+    }
+
+    fn visit_comments(&mut self, comments: &Comments) {
+        walk_comments(self, comments);
+    }
+
+    fn visit_comment(&mut self, comment: &Comment) {
+        walk_comment(self, comment);
+    }
+
+    /// Every span of the tree, after the rest of the node it belongs to.
+    fn visit_span(&mut self, _span: &Span) {}
+}
+
+pub fn walk_module<V: Visitor + ?Sized>(visitor: &mut V, module: &Module) {
+    let Module { name, end_name, imports, declarations, stmts, span } = module;
+    visitor.visit_identifier(name);
+    imports.iter().for_each(|import| visitor.visit_import(import));
+    visitor.visit_declarations(declarations);
+    if let Some(stmts) = stmts {
+        visitor.visit_statement_sequence(stmts);
+    }
+    visitor.visit_identifier(end_name);
+    visitor.visit_span(span);
+}
+
+pub fn walk_import<V: Visitor + ?Sized>(visitor: &mut V, import: &Import) {
+    let Import { module, alias, span } = import;
+    if let Some(alias) = alias {
+        visitor.visit_identifier(alias);
+    }
+    visitor.visit_identifier(module);
+    visitor.visit_span(span);
+}
+
+pub fn walk_declarations<V: Visitor + ?Sized>(visitor: &mut V, declarations: &Declarations) {
+    let Declarations { const_declarations, type_declarations, var_declarations, procedure_declarations } = declarations;
+    const_declarations.iter().for_each(|declaration| visitor.visit_const_declaration(declaration));
+    type_declarations.iter().for_each(|declaration| visitor.visit_type_declaration(declaration));
+    var_declarations.iter().for_each(|declaration| visitor.visit_var_declaration(declaration));
+    procedure_declarations.iter().for_each(|declaration| visitor.visit_procedure_declaration(declaration));
+}
+
+pub fn walk_const_declaration<V: Visitor + ?Sized>(visitor: &mut V, declaration: &ConstDeclaration) {
+    let ConstDeclaration { ident, value, comments } = declaration;
+    visitor.visit_comments(comments);
+    visitor.visit_identifier_def(ident);
+    visitor.visit_expression(value);
+}
+
+pub fn walk_type_declaration<V: Visitor + ?Sized>(visitor: &mut V, declaration: &TypeDeclaration) {
+    let TypeDeclaration { ident, ty, comments } = declaration;
+    visitor.visit_comments(comments);
+    visitor.visit_identifier_def(ident);
+    visitor.visit_type(ty);
+}
+
+pub fn walk_var_declaration<V: Visitor + ?Sized>(visitor: &mut V, declaration: &VarDeclaration) {
+    let VarDeclaration { variables, ty, comments } = declaration;
+    visitor.visit_comments(comments);
+    variables.iter().for_each(|variable| visitor.visit_identifier_def(variable));
+    visitor.visit_type(ty);
+}
+
+pub fn walk_procedure_declaration<V: Visitor + ?Sized>(visitor: &mut V, declaration: &ProcedureDeclaration) {
+    let ProcedureDeclaration { header, body, name, span, comments } = declaration;
+    visitor.visit_comments(comments);
+    visitor.visit_procedure_header(header);
+    visitor.visit_procedure_body(body);
+    visitor.visit_identifier(name);
+    visitor.visit_span(span);
+}
+
+pub fn walk_procedure_header<V: Visitor + ?Sized>(visitor: &mut V, header: &ProcedureHeader) {
+    let ProcedureHeader { name, params, span } = header;
+    visitor.visit_identifier_def(name);
+    if let Some(params) = params {
+        visitor.visit_formal_parameters(params);
+    }
+    visitor.visit_span(span);
+}
+
+pub fn walk_procedure_body<V: Visitor + ?Sized>(visitor: &mut V, body: &ProcedureBody) {
+    let ProcedureBody { declarations, stmts, ret, span } = body;
+    visitor.visit_declarations(declarations);
+    if let Some(stmts) = stmts {
+        visitor.visit_statement_sequence(stmts);
+    }
+    if let Some(ret) = ret {
+        visitor.visit_expression(ret);
+    }
+    visitor.visit_span(span);
+}
+
+pub fn walk_formal_parameters<V: Visitor + ?Sized>(visitor: &mut V, params: &FormalParameters) {
+    let FormalParameters { sections, return_type, span } = params;
+    sections.iter().for_each(|section| visitor.visit_fp_section(section));
+    if let Some(return_type) = return_type {
+        visitor.visit_qualified_identifier(return_type);
+    }
+    visitor.visit_span(span);
+}
+
+pub fn walk_fp_section<V: Visitor + ?Sized>(visitor: &mut V, section: &FPSection) {
+    let FPSection { by_ref: _, names, ty, span } = section;
+    names.iter().for_each(|name| visitor.visit_identifier(name));
+    visitor.visit_formal_type(ty);
+    visitor.visit_span(span);
+}
+
+pub fn walk_formal_type<V: Visitor + ?Sized>(visitor: &mut V, ty: &FormalType) {
+    let FormalType { open_arrays: _, base, span } = ty;
+    visitor.visit_qualified_identifier(base);
+    visitor.visit_span(span);
+}
+
+pub fn walk_type<V: Visitor + ?Sized>(visitor: &mut V, ty: &Type) {
+    match ty {
+        Type::Named { name } => visitor.visit_qualified_identifier(name),
+        Type::Array { lengths, element, span } => {
+            lengths.iter().for_each(|length| visitor.visit_expression(length));
+            visitor.visit_type(element);
+            visitor.visit_span(span);
+        }
+        Type::Record { base, field_lists, span } => {
+            if let Some(base) = base {
+                visitor.visit_qualified_identifier(base);
+            }
+            field_lists.iter().for_each(|fields| visitor.visit_field_list(fields));
+            visitor.visit_span(span);
+        }
+        Type::Pointer { pointee, span } => {
+            visitor.visit_type(pointee);
+            visitor.visit_span(span);
+        }
+        Type::Procedure { params, span } => {
+            if let Some(params) = params {
+                visitor.visit_formal_parameters(params);
+            }
+            visitor.visit_span(span);
+        }
+        Type::Error { span } => visitor.visit_span(span),
+    }
+}
+
+pub fn walk_field_list<V: Visitor + ?Sized>(visitor: &mut V, fields: &FieldList) {
+    let FieldList { fields, ty } = fields;
+    fields.iter().for_each(|field| visitor.visit_identifier_def(field));
+    visitor.visit_type(ty);
+}
+
+pub fn walk_statement_sequence<V: Visitor + ?Sized>(visitor: &mut V, stmts: &StatementSequence) {
+    let StatementSequence { statements, span } = stmts;
+    statements.iter().for_each(|stmt| visitor.visit_statement(stmt));
+    visitor.visit_span(span);
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Statement) {
+    match stmt {
+        Statement::Assign { target, value, span, comments } => {
+            visitor.visit_comments(comments);
+            visitor.visit_designator(target);
+            visitor.visit_expression(value);
+            visitor.visit_span(span);
+        }
+        Statement::Call { callee, parameters, span, comments } => {
+            visitor.visit_comments(comments);
+            visitor.visit_designator(callee);
+            parameters.iter().flatten().for_each(|parameter| visitor.visit_expression(parameter));
+            visitor.visit_span(span);
+        }
+        Statement::If { cond, stmts, elsif_branches, else_branch, span, comments } => {
+            visitor.visit_comments(comments);
+            visitor.visit_expression(cond);
+            visitor.visit_statement_sequence(stmts);
+            elsif_branches.iter().for_each(|elsif| visitor.visit_elsif(elsif));
+            if let Some(else_branch) = else_branch {
+                visitor.visit_statement_sequence(else_branch);
+            }
+            visitor.visit_span(span);
+        }
+        Statement::Case { expr, branches, span, comments } => {
+            visitor.visit_comments(comments);
+            visitor.visit_expression(expr);
+            branches.iter().for_each(|case| visitor.visit_case(case));
+            visitor.visit_span(span);
+        }
+        Statement::While { cond, stmts, elsif_branches, span, comments } => {
+            visitor.visit_comments(comments);
+            visitor.visit_expression(cond);
+            visitor.visit_statement_sequence(stmts);
+            elsif_branches.iter().for_each(|elsif| visitor.visit_elsif(elsif));
+            visitor.visit_span(span);
+        }
+        Statement::Repeat { stmts, cond, span, comments } => {
+            visitor.visit_comments(comments);
+            visitor.visit_statement_sequence(stmts);
+            visitor.visit_expression(cond);
+            visitor.visit_span(span);
+        }
+        Statement::For { var, low, high, by, stmts, span, comments } => {
+            visitor.visit_comments(comments);
+            visitor.visit_identifier(var);
+            visitor.visit_expression(low);
+            visitor.visit_expression(high);
+            if let Some(by) = by {
+                visitor.visit_expression(by);
+            }
+            visitor.visit_statement_sequence(stmts);
+            visitor.visit_span(span);
+        }
+        Statement::Error { span, comments } => {
+            visitor.visit_comments(comments);
+            visitor.visit_span(span);
+        }
+    }
+}
+
+pub fn walk_elsif<V: Visitor + ?Sized>(visitor: &mut V, elsif: &ElsIf) {
+    let ElsIf { cond, stmts, span } = elsif;
+    visitor.visit_expression(cond);
+    visitor.visit_statement_sequence(stmts);
+    visitor.visit_span(span);
+}
+
+pub fn walk_case<V: Visitor + ?Sized>(visitor: &mut V, case: &Case) {
+    let Case { label_list, statements, span } = case;
+    label_list.iter().for_each(|label| visitor.visit_label(label));
+    visitor.visit_statement_sequence(statements);
+    visitor.visit_span(span);
+}
+
+pub fn walk_label<V: Visitor + ?Sized>(visitor: &mut V, label: &Label) {
+    match label {
+        Label::Single { value } => visitor.visit_label_value(value),
+        Label::Range { low, high } => {
+            visitor.visit_label_value(low);
+            visitor.visit_label_value(high);
+        }
+    }
+}
+
+pub fn walk_label_value<V: Visitor + ?Sized>(visitor: &mut V, value: &LabelValue) {
+    match value {
+        LabelValue::Integer { value: _, span } | LabelValue::String { value: _, span } | LabelValue::Char { value: _, span } => {
+            visitor.visit_span(span);
+        }
+        LabelValue::QualifiedIdentifier(ident) => visitor.visit_qualified_identifier(ident),
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expression) {
+    match expr {
+        Expression::Int { value: _, span }
+        | Expression::Real { value: _, span }
+        | Expression::String { value: _, span }
+        | Expression::Char { value: _, span }
+        | Expression::Nil { span }
+        | Expression::False { span }
+        | Expression::True { span }
+        | Expression::Error { span } => visitor.visit_span(span),
+        Expression::Set { elements, span } => {
+            elements.iter().for_each(|element| visitor.visit_element(element));
+            visitor.visit_span(span);
+        }
+        Expression::Designator { designator, actual_parameters, span } => {
+            visitor.visit_designator(designator);
+            actual_parameters.iter().flatten().for_each(|parameter| visitor.visit_expression(parameter));
+            visitor.visit_span(span);
+        }
+        Expression::Unary { op: _, operand, span } => {
+            visitor.visit_expression(operand);
+            visitor.visit_span(span);
+        }
+        Expression::Binary { op: _, lhs, rhs, span } => {
+            visitor.visit_expression(lhs);
+            visitor.visit_expression(rhs);
+            visitor.visit_span(span);
+        }
+    }
+}
+
+pub fn walk_element<V: Visitor + ?Sized>(visitor: &mut V, element: &Element) {
+    let Element { first, second, span } = element;
+    visitor.visit_expression(first);
+    if let Some(second) = second {
+        visitor.visit_expression(second);
+    }
+    visitor.visit_span(span);
+}
+
+pub fn walk_designator<V: Visitor + ?Sized>(visitor: &mut V, designator: &Designator) {
+    let Designator { head, selectors, span } = designator;
+    visitor.visit_qualified_identifier(head);
+    selectors.iter().for_each(|selector| visitor.visit_selector(selector));
+    visitor.visit_span(span);
+}
+
+pub fn walk_selector<V: Visitor + ?Sized>(visitor: &mut V, selector: &Selector) {
+    match selector {
+        Selector::Field(ident) => visitor.visit_identifier(ident),
+        Selector::Index(indices, span) => {
+            indices.iter().for_each(|index| visitor.visit_expression(index));
+            visitor.visit_span(span);
+        }
+        Selector::Deref(span) => visitor.visit_span(span),
+        Selector::TypeGuard(ty, span) => {
+            visitor.visit_qualified_identifier(ty);
+            visitor.visit_span(span);
+        }
+    }
+}
+
+pub fn walk_qualified_identifier<V: Visitor + ?Sized>(visitor: &mut V, ident: &QualifiedIdentifier) {
+    ident.parts.iter().for_each(|part| visitor.visit_identifier(part));
+}
+
+pub fn walk_identifier_def<V: Visitor + ?Sized>(visitor: &mut V, ident: &IdentifierDef) {
+    let IdentifierDef { ident, exported: _, span } = ident;
+    visitor.visit_identifier(ident);
+    visitor.visit_span(span);
+}
+
+pub fn walk_identifier<V: Visitor + ?Sized>(visitor: &mut V, ident: &Identifier) {
+    visitor.visit_span(&ident.span);
+}
+
+pub fn walk_comments<V: Visitor + ?Sized>(visitor: &mut V, comments: &Comments) {
+    let Comments { leading, trailing } = comments;
+    leading.iter().for_each(|comment| visitor.visit_comment(comment));
+    trailing.iter().for_each(|comment| visitor.visit_comment(comment));
+}
+
+pub fn walk_comment<V: Visitor + ?Sized>(visitor: &mut V, comment: &Comment) {
+    visitor.visit_span(&comment.span);
+}
+
+// --------------------------- VISITOR MUT ---------------------------
+pub trait VisitorMut {
+    fn visit_module_mut(&mut self, module: &mut Module) {
+        walk_module_mut(self, module);
+    }
+
+    fn visit_import_mut(&mut self, import: &mut Import) {
+        walk_import_mut(self, import);
+    }
+
+    fn visit_declarations_mut(&mut self, declarations: &mut Declarations) {
+        walk_declarations_mut(self, declarations);
+    }
+
+    fn visit_const_declaration_mut(&mut self, declaration: &mut ConstDeclaration) {
+        walk_const_declaration_mut(self, declaration);
+    }
+
+    fn visit_type_declaration_mut(&mut self, declaration: &mut TypeDeclaration) {
+        walk_type_declaration_mut(self, declaration);
+    }
+
+    fn visit_var_declaration_mut(&mut self, declaration: &mut VarDeclaration) {
+        walk_var_declaration_mut(self, declaration);
+    }
+
+    fn visit_procedure_declaration_mut(&mut self, declaration: &mut ProcedureDeclaration) {
+        walk_procedure_declaration_mut(self, declaration);
+    }
+
+    fn visit_procedure_header_mut(&mut self, header: &mut ProcedureHeader) {
+        walk_procedure_header_mut(self, header);
+    }
+
+    fn visit_procedure_body_mut(&mut self, body: &mut ProcedureBody) {
+        walk_procedure_body_mut(self, body);
+    }
+
+    fn visit_formal_parameters_mut(&mut self, params: &mut FormalParameters) {
+        walk_formal_parameters_mut(self, params);
+    }
+
+    fn visit_fp_section_mut(&mut self, section: &mut FPSection) {
+        walk_fp_section_mut(self, section);
+    }
+
+    fn visit_formal_type_mut(&mut self, ty: &mut FormalType) {
+        walk_formal_type_mut(self, ty);
+    }
+
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        walk_type_mut(self, ty);
+    }
+
+    fn visit_field_list_mut(&mut self, fields: &mut FieldList) {
+        walk_field_list_mut(self, fields);
+    }
+
+    fn visit_statement_sequence_mut(&mut self, stmts: &mut StatementSequence) {
+        walk_statement_sequence_mut(self, stmts);
+    }
+
+    fn visit_statement_mut(&mut self, stmt: &mut Statement) {
+        walk_statement_mut(self, stmt);
+    }
+
+    fn visit_elsif_mut(&mut self, elsif: &mut ElsIf) {
+        walk_elsif_mut(self, elsif);
+    }
+
+    fn visit_case_mut(&mut self, case: &mut Case) {
+        walk_case_mut(self, case);
+    }
+
+    fn visit_label_mut(&mut self, label: &mut Label) {
+        walk_label_mut(self, label);
+    }
+
+    fn visit_label_value_mut(&mut self, value: &mut LabelValue) {
+        walk_label_value_mut(self, value);
+    }
+
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        walk_expression_mut(self, expr);
+    }
+
+    fn visit_element_mut(&mut self, element: &mut Element) {
+        walk_element_mut(self, element);
+    }
+
+    fn visit_designator_mut(&mut self, designator: &mut Designator) {
+        walk_designator_mut(self, designator);
+    }
+
+    fn visit_selector_mut(&mut self, selector: &mut Selector) {
+        walk_selector_mut(self, selector);
+    }
+
+    fn visit_qualified_identifier_mut(&mut self, ident: &mut QualifiedIdentifier) {
+        walk_qualified_identifier_mut(self, ident);
+    }
+
+    fn visit_identifier_def_mut(&mut self, ident: &mut IdentifierDef) {
+        walk_identifier_def_mut(self, ident);
+    }
+
+    fn visit_identifier_mut(&mut self, ident: &mut Identifier) {
+        walk_identifier_mut(self, ident);
+    }
+
+    fn visit_comments_mut(&mut self, comments: &mut Comments) {
+        walk_comments_mut(self, comments);
+    }
+
+    fn visit_comment_mut(&mut self, comment: &mut Comment) {
+        walk_comment_mut(self, comment);
+    }
+
+    /// Every span of the tree, after the rest of the node it belongs to.
+    fn visit_span_mut(&mut self, _span: &mut Span) {}
+}
+
+pub fn walk_module_mut<V: VisitorMut + ?Sized>(visitor: &mut V, module: &mut Module) {
+    let Module { name, end_name, imports, declarations, stmts, span } = module;
+    visitor.visit_identifier_mut(name);
+    imports.iter_mut().for_each(|import| visitor.visit_import_mut(import));
+    visitor.visit_declarations_mut(declarations);
+    if let Some(stmts) = stmts {
+        visitor.visit_statement_sequence_mut(stmts);
+    }
+    visitor.visit_identifier_mut(end_name);
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_import_mut<V: VisitorMut + ?Sized>(visitor: &mut V, import: &mut Import) {
+    let Import { module, alias, span } = import;
+    if let Some(alias) = alias {
+        visitor.visit_identifier_mut(alias);
+    }
+    visitor.visit_identifier_mut(module);
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_declarations_mut<V: VisitorMut + ?Sized>(visitor: &mut V, declarations: &mut Declarations) {
+    let Declarations { const_declarations, type_declarations, var_declarations, procedure_declarations } = declarations;
+    const_declarations.iter_mut().for_each(|declaration| visitor.visit_const_declaration_mut(declaration));
+    type_declarations.iter_mut().for_each(|declaration| visitor.visit_type_declaration_mut(declaration));
+    var_declarations.iter_mut().for_each(|declaration| visitor.visit_var_declaration_mut(declaration));
+    procedure_declarations.iter_mut().for_each(|declaration| visitor.visit_procedure_declaration_mut(declaration));
+}
+
+pub fn walk_const_declaration_mut<V: VisitorMut + ?Sized>(visitor: &mut V, declaration: &mut ConstDeclaration) {
+    let ConstDeclaration { ident, value, comments } = declaration;
+    visitor.visit_comments_mut(comments);
+    visitor.visit_identifier_def_mut(ident);
+    visitor.visit_expression_mut(value);
+}
+
+pub fn walk_type_declaration_mut<V: VisitorMut + ?Sized>(visitor: &mut V, declaration: &mut TypeDeclaration) {
+    let TypeDeclaration { ident, ty, comments } = declaration;
+    visitor.visit_comments_mut(comments);
+    visitor.visit_identifier_def_mut(ident);
+    visitor.visit_type_mut(ty);
+}
+
+pub fn walk_var_declaration_mut<V: VisitorMut + ?Sized>(visitor: &mut V, declaration: &mut VarDeclaration) {
+    let VarDeclaration { variables, ty, comments } = declaration;
+    visitor.visit_comments_mut(comments);
+    variables.iter_mut().for_each(|variable| visitor.visit_identifier_def_mut(variable));
+    visitor.visit_type_mut(ty);
+}
+
+pub fn walk_procedure_declaration_mut<V: VisitorMut + ?Sized>(visitor: &mut V, declaration: &mut ProcedureDeclaration) {
+    let ProcedureDeclaration { header, body, name, span, comments } = declaration;
+    visitor.visit_comments_mut(comments);
+    visitor.visit_procedure_header_mut(header);
+    visitor.visit_procedure_body_mut(body);
+    visitor.visit_identifier_mut(name);
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_procedure_header_mut<V: VisitorMut + ?Sized>(visitor: &mut V, header: &mut ProcedureHeader) {
+    let ProcedureHeader { name, params, span } = header;
+    visitor.visit_identifier_def_mut(name);
+    if let Some(params) = params {
+        visitor.visit_formal_parameters_mut(params);
+    }
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_procedure_body_mut<V: VisitorMut + ?Sized>(visitor: &mut V, body: &mut ProcedureBody) {
+    let ProcedureBody { declarations, stmts, ret, span } = body;
+    visitor.visit_declarations_mut(declarations);
+    if let Some(stmts) = stmts {
+        visitor.visit_statement_sequence_mut(stmts);
+    }
+    if let Some(ret) = ret {
+        visitor.visit_expression_mut(ret);
+    }
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_formal_parameters_mut<V: VisitorMut + ?Sized>(visitor: &mut V, params: &mut FormalParameters) {
+    let FormalParameters { sections, return_type, span } = params;
+    sections.iter_mut().for_each(|section| visitor.visit_fp_section_mut(section));
+    if let Some(return_type) = return_type {
+        visitor.visit_qualified_identifier_mut(return_type);
+    }
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_fp_section_mut<V: VisitorMut + ?Sized>(visitor: &mut V, section: &mut FPSection) {
+    let FPSection { by_ref: _, names, ty, span } = section;
+    names.iter_mut().for_each(|name| visitor.visit_identifier_mut(name));
+    visitor.visit_formal_type_mut(ty);
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_formal_type_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ty: &mut FormalType) {
+    let FormalType { open_arrays: _, base, span } = ty;
+    visitor.visit_qualified_identifier_mut(base);
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_type_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ty: &mut Type) {
+    match ty {
+        Type::Named { name } => visitor.visit_qualified_identifier_mut(name),
+        Type::Array { lengths, element, span } => {
+            lengths.iter_mut().for_each(|length| visitor.visit_expression_mut(length));
+            visitor.visit_type_mut(element);
+            visitor.visit_span_mut(span);
+        }
+        Type::Record { base, field_lists, span } => {
+            if let Some(base) = base {
+                visitor.visit_qualified_identifier_mut(base);
+            }
+            field_lists.iter_mut().for_each(|fields| visitor.visit_field_list_mut(fields));
+            visitor.visit_span_mut(span);
+        }
+        Type::Pointer { pointee, span } => {
+            visitor.visit_type_mut(pointee);
+            visitor.visit_span_mut(span);
+        }
+        Type::Procedure { params, span } => {
+            if let Some(params) = params {
+                visitor.visit_formal_parameters_mut(params);
+            }
+            visitor.visit_span_mut(span);
+        }
+        Type::Error { span } => visitor.visit_span_mut(span),
+    }
+}
+
+pub fn walk_field_list_mut<V: VisitorMut + ?Sized>(visitor: &mut V, fields: &mut FieldList) {
+    let FieldList { fields, ty } = fields;
+    fields.iter_mut().for_each(|field| visitor.visit_identifier_def_mut(field));
+    visitor.visit_type_mut(ty);
+}
+
+pub fn walk_statement_sequence_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmts: &mut StatementSequence) {
+    let StatementSequence { statements, span } = stmts;
+    statements.iter_mut().for_each(|stmt| visitor.visit_statement_mut(stmt));
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Statement) {
+    match stmt {
+        Statement::Assign { target, value, span, comments } => {
+            visitor.visit_comments_mut(comments);
+            visitor.visit_designator_mut(target);
+            visitor.visit_expression_mut(value);
+            visitor.visit_span_mut(span);
+        }
+        Statement::Call { callee, parameters, span, comments } => {
+            visitor.visit_comments_mut(comments);
+            visitor.visit_designator_mut(callee);
+            parameters.iter_mut().flatten().for_each(|parameter| visitor.visit_expression_mut(parameter));
+            visitor.visit_span_mut(span);
+        }
+        Statement::If { cond, stmts, elsif_branches, else_branch, span, comments } => {
+            visitor.visit_comments_mut(comments);
+            visitor.visit_expression_mut(cond);
+            visitor.visit_statement_sequence_mut(stmts);
+            elsif_branches.iter_mut().for_each(|elsif| visitor.visit_elsif_mut(elsif));
+            if let Some(else_branch) = else_branch {
+                visitor.visit_statement_sequence_mut(else_branch);
+            }
+            visitor.visit_span_mut(span);
+        }
+        Statement::Case { expr, branches, span, comments } => {
+            visitor.visit_comments_mut(comments);
+            visitor.visit_expression_mut(expr);
+            branches.iter_mut().for_each(|case| visitor.visit_case_mut(case));
+            visitor.visit_span_mut(span);
+        }
+        Statement::While { cond, stmts, elsif_branches, span, comments } => {
+            visitor.visit_comments_mut(comments);
+            visitor.visit_expression_mut(cond);
+            visitor.visit_statement_sequence_mut(stmts);
+            elsif_branches.iter_mut().for_each(|elsif| visitor.visit_elsif_mut(elsif));
+            visitor.visit_span_mut(span);
+        }
+        Statement::Repeat { stmts, cond, span, comments } => {
+            visitor.visit_comments_mut(comments);
+            visitor.visit_statement_sequence_mut(stmts);
+            visitor.visit_expression_mut(cond);
+            visitor.visit_span_mut(span);
+        }
+        Statement::For { var, low, high, by, stmts, span, comments } => {
+            visitor.visit_comments_mut(comments);
+            visitor.visit_identifier_mut(var);
+            visitor.visit_expression_mut(low);
+            visitor.visit_expression_mut(high);
+            if let Some(by) = by {
+                visitor.visit_expression_mut(by);
+            }
+            visitor.visit_statement_sequence_mut(stmts);
+            visitor.visit_span_mut(span);
+        }
+        Statement::Error { span, comments } => {
+            visitor.visit_comments_mut(comments);
+            visitor.visit_span_mut(span);
+        }
+    }
+}
+
+pub fn walk_elsif_mut<V: VisitorMut + ?Sized>(visitor: &mut V, elsif: &mut ElsIf) {
+    let ElsIf { cond, stmts, span } = elsif;
+    visitor.visit_expression_mut(cond);
+    visitor.visit_statement_sequence_mut(stmts);
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_case_mut<V: VisitorMut + ?Sized>(visitor: &mut V, case: &mut Case) {
+    let Case { label_list, statements, span } = case;
+    label_list.iter_mut().for_each(|label| visitor.visit_label_mut(label));
+    visitor.visit_statement_sequence_mut(statements);
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_label_mut<V: VisitorMut + ?Sized>(visitor: &mut V, label: &mut Label) {
+    match label {
+        Label::Single { value } => visitor.visit_label_value_mut(value),
+        Label::Range { low, high } => {
+            visitor.visit_label_value_mut(low);
+            visitor.visit_label_value_mut(high);
+        }
+    }
+}
+
+pub fn walk_label_value_mut<V: VisitorMut + ?Sized>(visitor: &mut V, value: &mut LabelValue) {
+    match value {
+        LabelValue::Integer { value: _, span } | LabelValue::String { value: _, span } | LabelValue::Char { value: _, span } => {
+            visitor.visit_span_mut(span);
+        }
+        LabelValue::QualifiedIdentifier(ident) => visitor.visit_qualified_identifier_mut(ident),
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expression) {
+    match expr {
+        Expression::Int { value: _, span }
+        | Expression::Real { value: _, span }
+        | Expression::String { value: _, span }
+        | Expression::Char { value: _, span }
+        | Expression::Nil { span }
+        | Expression::False { span }
+        | Expression::True { span }
+        | Expression::Error { span } => visitor.visit_span_mut(span),
+        Expression::Set { elements, span } => {
+            elements.iter_mut().for_each(|element| visitor.visit_element_mut(element));
+            visitor.visit_span_mut(span);
+        }
+        Expression::Designator { designator, actual_parameters, span } => {
+            visitor.visit_designator_mut(designator);
+            actual_parameters.iter_mut().flatten().for_each(|parameter| visitor.visit_expression_mut(parameter));
+            visitor.visit_span_mut(span);
+        }
+        Expression::Unary { op: _, operand, span } => {
+            visitor.visit_expression_mut(operand);
+            visitor.visit_span_mut(span);
+        }
+        Expression::Binary { op: _, lhs, rhs, span } => {
+            visitor.visit_expression_mut(lhs);
+            visitor.visit_expression_mut(rhs);
+            visitor.visit_span_mut(span);
+        }
+    }
+}
+
+pub fn walk_element_mut<V: VisitorMut + ?Sized>(visitor: &mut V, element: &mut Element) {
+    let Element { first, second, span } = element;
+    visitor.visit_expression_mut(first);
+    if let Some(second) = second {
+        visitor.visit_expression_mut(second);
+    }
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_designator_mut<V: VisitorMut + ?Sized>(visitor: &mut V, designator: &mut Designator) {
+    let Designator { head, selectors, span } = designator;
+    visitor.visit_qualified_identifier_mut(head);
+    selectors.iter_mut().for_each(|selector| visitor.visit_selector_mut(selector));
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_selector_mut<V: VisitorMut + ?Sized>(visitor: &mut V, selector: &mut Selector) {
+    match selector {
+        Selector::Field(ident) => visitor.visit_identifier_mut(ident),
+        Selector::Index(indices, span) => {
+            indices.iter_mut().for_each(|index| visitor.visit_expression_mut(index));
+            visitor.visit_span_mut(span);
+        }
+        Selector::Deref(span) => visitor.visit_span_mut(span),
+        Selector::TypeGuard(ty, span) => {
+            visitor.visit_qualified_identifier_mut(ty);
+            visitor.visit_span_mut(span);
+        }
+    }
+}
+
+pub fn walk_qualified_identifier_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ident: &mut QualifiedIdentifier) {
+    ident.parts.iter_mut().for_each(|part| visitor.visit_identifier_mut(part));
+}
+
+pub fn walk_identifier_def_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ident: &mut IdentifierDef) {
+    let IdentifierDef { ident, exported: _, span } = ident;
+    visitor.visit_identifier_mut(ident);
+    visitor.visit_span_mut(span);
+}
+
+pub fn walk_identifier_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ident: &mut Identifier) {
+    visitor.visit_span_mut(&mut ident.span);
+}
+
+pub fn walk_comments_mut<V: VisitorMut + ?Sized>(visitor: &mut V, comments: &mut Comments) {
+    let Comments { leading, trailing } = comments;
+    leading.iter_mut().for_each(|comment| visitor.visit_comment_mut(comment));
+    trailing.iter_mut().for_each(|comment| visitor.visit_comment_mut(comment));
+}
+
+pub fn walk_comment_mut<V: VisitorMut + ?Sized>(visitor: &mut V, comment: &mut Comment) {
+    visitor.visit_span_mut(&mut comment.span);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::intern::Name;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+    use crate::frontend::span::Position;
+
+    fn parse(source: &str) -> Module {
+        Parser::new(Lexer::new(source)).parse().unwrap()
+    }
+
+    const SOURCE: &str = "MODULE M;
+CONST k = 1;
+TYPE A = ARRAY 2 OF RECORD (B) f: POINTER TO A; p: PROCEDURE (VAR x: ARRAY OF INTEGER): BOOLEAN END;
+VAR a: A; s: SET;
+PROCEDURE P(i: INTEGER): INTEGER;
+BEGIN
+  CASE i OF 3, 4..5: i := 6 | k: s := s END
+  RETURN i + 7
+END P;
+BEGIN
+  s := {8, 9..10};
+  a[11].f^(A).f := NIL;
+  IF P(12) = 13 THEN P(14) ELSIF ~(15 IN s) THEN s := s ELSE s := s END;
+  WHILE 16 > 17 DO s := s ELSIF 18 < 19 DO s := s END;
+  REPEAT s := s UNTIL TRUE;
+  FOR i := 20 TO 21 BY 22 DO s := s END
+END M.";
+
+    /// The integer literals, in the order visited.
+    #[derive(Default)]
+    struct Integers(Vec<i64>);
+
+    impl Visitor for Integers {
+        fn visit_expression(&mut self, expr: &Expression) {
+            if let Expression::Int { value, .. } = expr {
+                self.0.push(*value);
+            }
+            walk_expression(self, expr);
+        }
+
+        fn visit_label_value(&mut self, value: &LabelValue) {
+            if let LabelValue::Integer { value, .. } = value {
+                self.0.push(*value);
+            }
+        }
+    }
+
+    #[test]
+    fn visits_every_expression_in_source_order() {
+        let mut integers = Integers::default();
+
+        integers.visit_module(&parse(SOURCE));
+
+        assert_eq!(integers.0, (1..=22).collect::<Vec<_>>());
+    }
+
+    /// Puts every span at the start of the text.
+    struct Unplace;
+
+    impl VisitorMut for Unplace {
+        fn visit_span_mut(&mut self, span: &mut Span) {
+            *span = Span::new(Position::initial(), Position::initial());
+        }
+    }
+
+    #[test]
+    fn reaches_every_span() {
+        let mut module = parse(SOURCE);
+        let mut respaced = parse(&SOURCE.replace(' ', "  ").replace('\n', "\n\n"));
+        assert_ne!(module, respaced);
+
+        Unplace.visit_module_mut(&mut module);
+        Unplace.visit_module_mut(&mut respaced);
+
+        assert_eq!(module, respaced);
+    }
+
+    /// Renames the uses of one identifier, leaving its declarations alone.
+    struct Rename(&'static str, &'static str);
+
+    impl VisitorMut for Rename {
+        fn visit_identifier_mut(&mut self, ident: &mut Identifier) {
+            if ident.text == self.0 {
+                ident.text = Name::new(self.1);
+            }
+        }
+
+        fn visit_identifier_def_mut(&mut self, _ident: &mut IdentifierDef) {}
+    }
+
+    /// The names of the qualified identifiers.
+    #[derive(Default)]
+    struct Uses(Vec<String>);
+
+    impl Visitor for Uses {
+        fn visit_qualified_identifier(&mut self, ident: &QualifiedIdentifier) {
+            self.0.push(ident.ident().text.to_string());
+        }
+    }
+
+    #[test]
+    fn rewrites_the_nodes_a_pass_overrides() {
+        let mut module = parse(SOURCE);
+
+        Rename("A", "T").visit_module_mut(&mut module);
+
+        let mut uses = Uses::default();
+        uses.visit_module(&module);
+        assert_eq!(module.declarations.type_declarations[0].ident.ident.text, "A");
+        assert_eq!(uses.0.iter().filter(|name| *name == "T").count(), 3);
+        assert!(!uses.0.contains(&"A".to_string()));
+    }
+}
@@ -0,0 +1,1243 @@
+//! The intermediate representation between the checked AST and the backends.
+//!
+//! Functions are made of basic blocks in SSA form: every virtual register is defined exactly once,
+//! either by an instruction or as a block parameter, and values flow between blocks through the
+//! arguments of the jump that ends a block. Oberon variables live in memory (stack slots and
+//! globals); their layout is explicit, so loads and stores only see addresses and sizes.
+//!
+//! Heap objects created by `new` carry a pointer to their type descriptor in the word just
+//! before the object. A type descriptor is two words: the descriptor of the base type (or null)
+//! and the size of the record.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write as _;
+use thiserror::Error;
+
+// --------------------------- TYPES ---------------------------
+/// The type of a virtual register. BOOLEAN, CHAR, BYTE, INTEGER and SET values are all `I64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ty {
+    I64,
+    F64,
+    Ptr,
+}
+
+/// The width of a memory access. `I8` is zero-extended when loaded and truncated when stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemTy {
+    I8,
+    I64,
+    F64,
+    Ptr,
+}
+
+impl MemTy {
+    pub fn value_ty(self) -> Ty {
+        match self {
+            MemTy::I8 | MemTy::I64 => Ty::I64,
+            MemTy::F64 => Ty::F64,
+            MemTy::Ptr => Ty::Ptr,
+        }
+    }
+
+    pub fn size(self) -> u64 {
+        match self {
+            MemTy::I8 => 1,
+            MemTy::I64 | MemTy::F64 | MemTy::Ptr => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VReg(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SlotId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Reg(VReg),
+    Int(i64),
+    Real(f64),
+}
+
+impl Operand {
+    pub fn reg(&self) -> Option<VReg> {
+        match self {
+            Operand::Reg(reg) => Some(*reg),
+            _ => None,
+        }
+    }
+}
+
+// --------------------------- INSTRUCTIONS ---------------------------
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    /// Division rounding towards negative infinity (Oberon's DIV); plain division for `F64`.
+    Div,
+    /// Remainder with the sign of the divisor (Oberon's MOD).
+    Mod,
+    And,
+    Or,
+    Xor,
+    Shl,
+    /// Arithmetic shift right.
+    Sar,
+    /// Rotate right.
+    Ror,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnOp {
+    Neg,
+    /// Bitwise complement.
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cond {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Conv {
+    /// INTEGER to REAL (FLT).
+    IntToReal,
+    /// REAL to INTEGER rounding down (FLOOR).
+    Floor,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Callee {
+    Direct(String),
+    Indirect(Operand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inst {
+    Copy { dst: VReg, ty: Ty, src: Operand },
+    Unary { dst: VReg, op: UnOp, ty: Ty, operand: Operand },
+    Binary { dst: VReg, op: BinOp, ty: Ty, lhs: Operand, rhs: Operand },
+    /// Compares two values of type `ty`, giving 1 or 0.
+    Cmp { dst: VReg, cond: Cond, ty: Ty, lhs: Operand, rhs: Operand },
+    Convert { dst: VReg, conv: Conv, src: Operand },
+    Load { dst: VReg, mem: MemTy, addr: Operand },
+    Store { mem: MemTy, value: Operand, addr: Operand },
+    SlotAddr { dst: VReg, slot: SlotId },
+    GlobalAddr { dst: VReg, name: String },
+    FuncAddr { dst: VReg, name: String },
+    /// `base` plus a byte offset.
+    PtrAdd { dst: VReg, base: Operand, offset: Operand },
+    /// Arguments for by-reference parameters are addresses.
+    Call { dst: Option<(VReg, Ty)>, callee: Callee, args: Vec<Operand> },
+    MemCopy { dst: Operand, src: Operand, size: Operand },
+    /// Allocates a zeroed heap object of the record type described by `desc`.
+    New { dst: VReg, desc: String },
+    /// Whether the type with descriptor `tag` is the type of `desc` or an extension of it.
+    TypeTest { dst: VReg, tag: Operand, desc: String },
+    /// Traps unless the type with descriptor `tag` is the type of `desc` or an extension of it.
+    TypeGuard { tag: Operand, desc: String },
+}
+
+impl Inst {
+    /// The register defined by this instruction and its type.
+    pub fn def(&self) -> Option<(VReg, Ty)> {
+        match self {
+            Inst::Copy { dst, ty, .. } | Inst::Unary { dst, ty, .. } | Inst::Binary { dst, ty, .. } => Some((*dst, *ty)),
+            Inst::Cmp { dst, .. } | Inst::TypeTest { dst, .. } => Some((*dst, Ty::I64)),
+            Inst::Convert { dst, conv, .. } => Some((*dst, match conv { Conv::IntToReal => Ty::F64, Conv::Floor => Ty::I64 })),
+            Inst::Load { dst, mem, .. } => Some((*dst, mem.value_ty())),
+            Inst::SlotAddr { dst, .. } | Inst::GlobalAddr { dst, .. } | Inst::FuncAddr { dst, .. }
+            | Inst::PtrAdd { dst, .. } | Inst::New { dst, .. } => Some((*dst, Ty::Ptr)),
+            Inst::Call { dst, .. } => *dst,
+            Inst::Store { .. } | Inst::MemCopy { .. } | Inst::TypeGuard { .. } => None,
+        }
+    }
+
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Inst::Copy { src, .. } | Inst::Convert { src, .. } => vec![src],
+            Inst::Unary { operand, .. } => vec![operand],
+            Inst::Binary { lhs, rhs, .. } | Inst::Cmp { lhs, rhs, .. } => vec![lhs, rhs],
+            Inst::Load { addr, .. } => vec![addr],
+            Inst::Store { value, addr, .. } => vec![value, addr],
+            Inst::PtrAdd { base, offset, .. } => vec![base, offset],
+            Inst::Call { callee, args, .. } => {
+                let mut operands: Vec<&Operand> = args.iter().collect();
+                if let Callee::Indirect(target) = callee {
+                    operands.insert(0, target);
+                }
+                operands
+            }
+            Inst::MemCopy { dst, src, size } => vec![dst, src, size],
+            Inst::TypeTest { tag, .. } | Inst::TypeGuard { tag, .. } => vec![tag],
+            Inst::SlotAddr { .. } | Inst::GlobalAddr { .. } | Inst::FuncAddr { .. } | Inst::New { .. } => vec![],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Inst::Copy { src, .. } | Inst::Convert { src, .. } => vec![src],
+            Inst::Unary { operand, .. } => vec![operand],
+            Inst::Binary { lhs, rhs, .. } | Inst::Cmp { lhs, rhs, .. } => vec![lhs, rhs],
+            Inst::Load { addr, .. } => vec![addr],
+            Inst::Store { value, addr, .. } => vec![value, addr],
+            Inst::PtrAdd { base, offset, .. } => vec![base, offset],
+            Inst::Call { callee, args, .. } => {
+                let mut operands: Vec<&mut Operand> = args.iter_mut().collect();
+                if let Callee::Indirect(target) = callee {
+                    operands.insert(0, target);
+                }
+                operands
+            }
+            Inst::MemCopy { dst, src, size } => vec![dst, src, size],
+            Inst::TypeTest { tag, .. } | Inst::TypeGuard { tag, .. } => vec![tag],
+            Inst::SlotAddr { .. } | Inst::GlobalAddr { .. } | Inst::FuncAddr { .. } | Inst::New { .. } => vec![],
+        }
+    }
+
+    /// Whether the instruction does anything besides defining its register.
+    pub fn has_side_effects(&self) -> bool {
+        matches!(self, Inst::Store { .. } | Inst::Call { .. } | Inst::MemCopy { .. } | Inst::New { .. } | Inst::TypeGuard { .. })
+            || matches!(self, Inst::Binary { op: BinOp::Div | BinOp::Mod, ty: Ty::I64, .. })
+    }
+}
+
+// --------------------------- CONTROL FLOW ---------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub block: BlockId,
+    pub args: Vec<Operand>,
+}
+
+impl Target {
+    pub fn new(block: BlockId) -> Self {
+        Self { block, args: vec![] }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Jump(Target),
+    /// Continues at `then_target` if `cond` is non-zero.
+    Branch { cond: Operand, then_target: Target, else_target: Target },
+    Return(Option<Operand>),
+    /// Aborts the program with the given trap number.
+    Trap(i64),
+}
+
+impl Terminator {
+    pub fn targets(&self) -> Vec<&Target> {
+        match self {
+            Terminator::Jump(target) => vec![target],
+            Terminator::Branch { then_target, else_target, .. } => vec![then_target, else_target],
+            Terminator::Return(_) | Terminator::Trap(_) => vec![],
+        }
+    }
+
+    pub fn targets_mut(&mut self) -> Vec<&mut Target> {
+        match self {
+            Terminator::Jump(target) => vec![target],
+            Terminator::Branch { then_target, else_target, .. } => vec![then_target, else_target],
+            Terminator::Return(_) | Terminator::Trap(_) => vec![],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Terminator::Jump(target) => target.args.iter_mut().collect(),
+            Terminator::Branch { cond, then_target, else_target } => {
+                let mut operands = vec![cond];
+                operands.extend(then_target.args.iter_mut());
+                operands.extend(else_target.args.iter_mut());
+                operands
+            }
+            Terminator::Return(value) => value.iter_mut().collect(),
+            Terminator::Trap(_) => vec![],
+        }
+    }
+
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Terminator::Jump(target) => target.args.iter().collect(),
+            Terminator::Branch { cond, then_target, else_target } => {
+                let mut operands = vec![cond];
+                operands.extend(then_target.args.iter());
+                operands.extend(else_target.args.iter());
+                operands
+            }
+            Terminator::Return(value) => value.iter().collect(),
+            Terminator::Trap(_) => vec![],
+        }
+    }
+}
+
+/// Trap numbers used by generated code.
+pub mod trap {
+    pub const INDEX: i64 = 1;
+    pub const TYPE_GUARD: i64 = 2;
+    pub const CASE: i64 = 3;
+    pub const NIL: i64 = 4;
+    pub const ASSERT: i64 = 7;
+    /// A result outside the range of INTEGER, from ABS or FLOOR.
+    pub const OVERFLOW: i64 = 8;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub id: BlockId,
+    pub params: Vec<(VReg, Ty)>,
+    pub insts: Vec<Inst>,
+    pub term: Terminator,
+}
+
+// --------------------------- FUNCTIONS AND MODULES ---------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slot {
+    pub size: u64,
+    pub align: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Param {
+    pub reg: VReg,
+    pub ty: Ty,
+    /// The parameter is the address of the caller's variable (a VAR parameter).
+    pub by_ref: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub exported: bool,
+    pub params: Vec<Param>,
+    pub ret: Option<Ty>,
+    pub slots: Vec<Slot>,
+    /// The first block is the entry block; it has no parameters.
+    pub blocks: Vec<Block>,
+    reg_types: Vec<Ty>,
+}
+
+impl Function {
+    pub fn new(name: &str, exported: bool, ret: Option<Ty>) -> Self {
+        Self { name: name.to_string(), exported, params: vec![], ret, slots: vec![], blocks: vec![], reg_types: vec![] }
+    }
+
+    pub fn new_reg(&mut self, ty: Ty) -> VReg {
+        self.reg_types.push(ty);
+        VReg(self.reg_types.len() as u32 - 1)
+    }
+
+    pub fn add_param(&mut self, ty: Ty, by_ref: bool) -> VReg {
+        let reg = self.new_reg(ty);
+        self.params.push(Param { reg, ty, by_ref });
+        reg
+    }
+
+    pub fn add_slot(&mut self, size: u64, align: u64) -> SlotId {
+        self.slots.push(Slot { size, align });
+        SlotId(self.slots.len() as u32 - 1)
+    }
+
+    /// Adds an empty block that ends in a trap until its terminator is set.
+    pub fn add_block(&mut self) -> BlockId {
+        let id = BlockId(self.blocks.iter().map(|b| b.id.0 + 1).max().unwrap_or(0));
+        self.blocks.push(Block { id, params: vec![], insts: vec![], term: Terminator::Trap(0) });
+        id
+    }
+
+    pub fn block(&self, id: BlockId) -> &Block {
+        self.blocks.iter().find(|b| b.id == id).expect("unknown block")
+    }
+
+    pub fn block_mut(&mut self, id: BlockId) -> &mut Block {
+        self.blocks.iter_mut().find(|b| b.id == id).expect("unknown block")
+    }
+
+    pub fn reg_type(&self, reg: VReg) -> Ty {
+        self.reg_types[reg.0 as usize]
+    }
+
+    pub fn reg_count(&self) -> usize {
+        self.reg_types.len()
+    }
+
+    pub fn operand_type(&self, operand: &Operand) -> Ty {
+        match operand {
+            Operand::Reg(reg) => self.reg_type(*reg),
+            Operand::Int(_) => Ty::I64,
+            Operand::Real(_) => Ty::F64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub exported: bool,
+    pub size: u64,
+    pub align: u64,
+    /// Initial bytes; zero-initialized when absent.
+    pub init: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDesc {
+    pub name: String,
+    pub exported: bool,
+    pub size: u64,
+    pub base: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extern {
+    pub name: String,
+    pub params: Vec<Ty>,
+    pub ret: Option<Ty>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+    pub name: String,
+    pub globals: Vec<Global>,
+    pub descriptors: Vec<TypeDesc>,
+    pub externs: Vec<Extern>,
+    pub functions: Vec<Function>,
+}
+
+impl Module {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), ..Self::default() }
+    }
+
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+}
+
+// --------------------------- PRINTER ---------------------------
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Ty::I64 => "i64",
+            Ty::F64 => "f64",
+            Ty::Ptr => "ptr",
+        })
+    }
+}
+
+impl fmt::Display for MemTy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MemTy::I8 => "i8",
+            MemTy::I64 => "i64",
+            MemTy::F64 => "f64",
+            MemTy::Ptr => "ptr",
+        })
+    }
+}
+
+impl fmt::Display for VReg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+impl fmt::Display for SlotId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "${}", self.0)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Reg(reg) => write!(f, "{reg}"),
+            Operand::Int(value) => write!(f, "{value}"),
+            Operand::Real(value) => write!(f, "{value:?}"),
+        }
+    }
+}
+
+const BIN_OPS: [(BinOp, &str); 11] = [
+    (BinOp::Add, "add"), (BinOp::Sub, "sub"), (BinOp::Mul, "mul"), (BinOp::Div, "div"),
+    (BinOp::Mod, "mod"), (BinOp::And, "and"), (BinOp::Or, "or"), (BinOp::Xor, "xor"),
+    (BinOp::Shl, "shl"), (BinOp::Sar, "sar"), (BinOp::Ror, "ror"),
+];
+const UN_OPS: [(UnOp, &str); 2] = [(UnOp::Neg, "neg"), (UnOp::Not, "not")];
+const CONDS: [(Cond, &str); 6] = [
+    (Cond::Eq, "eq"), (Cond::Ne, "ne"), (Cond::Lt, "lt"), (Cond::Le, "le"), (Cond::Gt, "gt"), (Cond::Ge, "ge"),
+];
+const CONVS: [(Conv, &str); 2] = [(Conv::IntToReal, "itof"), (Conv::Floor, "floor")];
+
+fn name_of<T: PartialEq + Copy>(table: &[(T, &'static str)], value: T) -> &'static str {
+    table.iter().find(|(v, _)| *v == value).map(|(_, name)| *name).unwrap()
+}
+
+fn join<T: fmt::Display>(items: &[T]) -> String {
+    items.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.args.is_empty() {
+            write!(f, "{}", self.block)
+        } else {
+            write!(f, "{}({})", self.block, join(&self.args))
+        }
+    }
+}
+
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inst::Copy { dst, ty, src } => write!(f, "{dst} = copy {ty} {src}"),
+            Inst::Unary { dst, op, ty, operand } => write!(f, "{dst} = {} {ty} {operand}", name_of(&UN_OPS, *op)),
+            Inst::Binary { dst, op, ty, lhs, rhs } => write!(f, "{dst} = {} {ty} {lhs}, {rhs}", name_of(&BIN_OPS, *op)),
+            Inst::Cmp { dst, cond, ty, lhs, rhs } => write!(f, "{dst} = cmp {} {ty} {lhs}, {rhs}", name_of(&CONDS, *cond)),
+            Inst::Convert { dst, conv, src } => write!(f, "{dst} = {} {src}", name_of(&CONVS, *conv)),
+            Inst::Load { dst, mem, addr } => write!(f, "{dst} = load {mem} {addr}"),
+            Inst::Store { mem, value, addr } => write!(f, "store {mem} {value}, {addr}"),
+            Inst::SlotAddr { dst, slot } => write!(f, "{dst} = slotaddr {slot}"),
+            Inst::GlobalAddr { dst, name } => write!(f, "{dst} = globaladdr @{name}"),
+            Inst::FuncAddr { dst, name } => write!(f, "{dst} = funcaddr @{name}"),
+            Inst::PtrAdd { dst, base, offset } => write!(f, "{dst} = ptradd {base}, {offset}"),
+            Inst::Call { dst, callee, args } => {
+                match dst {
+                    Some((dst, ty)) => write!(f, "{dst} = call {ty} ")?,
+                    None => write!(f, "call ")?,
+                }
+                match callee {
+                    Callee::Direct(name) => write!(f, "@{name}")?,
+                    Callee::Indirect(target) => write!(f, "{target}")?,
+                }
+                write!(f, "({})", join(args))
+            }
+            Inst::MemCopy { dst, src, size } => write!(f, "memcopy {dst}, {src}, {size}"),
+            Inst::New { dst, desc } => write!(f, "{dst} = new @{desc}"),
+            Inst::TypeTest { dst, tag, desc } => write!(f, "{dst} = typetest {tag}, @{desc}"),
+            Inst::TypeGuard { tag, desc } => write!(f, "typeguard {tag}, @{desc}"),
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jump {target}"),
+            Terminator::Branch { cond, then_target, else_target } => write!(f, "br {cond}, {then_target}, {else_target}"),
+            Terminator::Return(Some(value)) => write!(f, "ret {value}"),
+            Terminator::Return(None) => write!(f, "ret"),
+            Terminator::Trap(code) => write!(f, "trap {code}"),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params: Vec<String> = self.params.iter()
+            .map(|p| format!("{}: {}{}", p.reg, p.ty, if p.by_ref { " byref" } else { "" }))
+            .collect();
+        write!(f, "{}func @{}({})", if self.exported { "export " } else { "" }, self.name, params.join(", "))?;
+        if let Some(ret) = self.ret {
+            write!(f, " -> {ret}")?;
+        }
+        writeln!(f, " {{")?;
+        for (i, slot) in self.slots.iter().enumerate() {
+            writeln!(f, "  slot {} {} align {}", SlotId(i as u32), slot.size, slot.align)?;
+        }
+        for block in &self.blocks {
+            if block.params.is_empty() {
+                writeln!(f, "{}:", block.id)?;
+            } else {
+                let params: Vec<String> = block.params.iter().map(|(reg, ty)| format!("{reg}: {ty}")).collect();
+                writeln!(f, "{}({}):", block.id, params.join(", "))?;
+            }
+            for inst in &block.insts {
+                writeln!(f, "  {inst}")?;
+            }
+            writeln!(f, "  {}", block.term)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "module {}", self.name)?;
+        let mut header = String::new();
+        for global in &self.globals {
+            let _ = write!(header, "{}global @{} {} align {}", if global.exported { "export " } else { "" }, global.name, global.size, global.align);
+            if let Some(init) = &global.init {
+                let _ = write!(header, " = \"{}\"", escape(init));
+            }
+            header.push('\n');
+        }
+        for desc in &self.descriptors {
+            let _ = write!(header, "{}typedesc @{} size {}", if desc.exported { "export " } else { "" }, desc.name, desc.size);
+            if let Some(base) = &desc.base {
+                let _ = write!(header, " base @{base}");
+            }
+            header.push('\n');
+        }
+        for ext in &self.externs {
+            let _ = write!(header, "extern @{}({})", ext.name, join(&ext.params));
+            if let Some(ret) = ext.ret {
+                let _ = write!(header, " -> {ret}");
+            }
+            header.push('\n');
+        }
+        if !header.is_empty() {
+            write!(f, "\n{header}")?;
+        }
+        for function in &self.functions {
+            write!(f, "\n{function}")?;
+        }
+        Ok(())
+    }
+}
+
+fn escape(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for byte in bytes {
+        match byte {
+            b' '..=b'~' if *byte != b'"' && *byte != b'\\' => text.push(*byte as char),
+            _ => { let _ = write!(text, "\\{byte:02X}"); }
+        }
+    }
+    text
+}
+
+// --------------------------- PARSER ---------------------------
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("IR syntax error on line {line}: {message}")]
+pub struct IrParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Parses the textual form produced by printing a `Module`.
+pub fn parse(text: &str) -> Result<Module, IrParseError> {
+    IrParser { lines: text.lines().enumerate().peekable() }.module()
+}
+
+struct IrParser<'a> {
+    lines: std::iter::Peekable<std::iter::Enumerate<std::str::Lines<'a>>>,
+}
+
+/// Splits a line into words, keeping punctuation as separate tokens and quoted strings whole.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == ';' {
+            break;
+        } else if "(),:=".contains(c) {
+            tokens.push(c.to_string());
+            chars.next();
+        } else if c == '"' {
+            let mut text = String::from('"');
+            chars.next();
+            for c in chars.by_ref() {
+                text.push(c);
+                if c == '"' {
+                    break;
+                }
+            }
+            tokens.push(text);
+        } else if c == '-' && chars.clone().nth(1) == Some('>') {
+            chars.next();
+            chars.next();
+            tokens.push("->".to_string());
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || "(),:=;\"".contains(c) {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(word);
+        }
+    }
+    tokens
+}
+
+struct Line {
+    number: usize,
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl Line {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, IrParseError> {
+        Err(IrParseError { line: self.number, message: message.into() })
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(|t| t.as_str())
+    }
+
+    fn next(&mut self) -> Result<String, IrParseError> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => self.error("unexpected end of line"),
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), IrParseError> {
+        if self.eat(token) { Ok(()) } else { self.error(format!("expected '{token}'")) }
+    }
+
+    fn end(&self) -> Result<(), IrParseError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => self.error(format!("unexpected '{token}'")),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, IrParseError> {
+        let token = self.next()?;
+        token.parse().or_else(|_| self.error(format!("expected a number, found '{token}'")))
+    }
+
+    fn symbol(&mut self) -> Result<String, IrParseError> {
+        let token = self.next()?;
+        match token.strip_prefix('@') {
+            Some(name) if !name.is_empty() => Ok(name.to_string()),
+            _ => self.error(format!("expected '@name', found '{token}'")),
+        }
+    }
+
+    fn ty(&mut self) -> Result<Ty, IrParseError> {
+        match self.next()?.as_str() {
+            "i64" => Ok(Ty::I64),
+            "f64" => Ok(Ty::F64),
+            "ptr" => Ok(Ty::Ptr),
+            other => self.error(format!("unknown type '{other}'")),
+        }
+    }
+
+    fn mem_ty(&mut self) -> Result<MemTy, IrParseError> {
+        match self.next()?.as_str() {
+            "i8" => Ok(MemTy::I8),
+            "i64" => Ok(MemTy::I64),
+            "f64" => Ok(MemTy::F64),
+            "ptr" => Ok(MemTy::Ptr),
+            other => self.error(format!("unknown memory type '{other}'")),
+        }
+    }
+
+    fn reg(&mut self) -> Result<VReg, IrParseError> {
+        let token = self.next()?;
+        match token.strip_prefix('%').and_then(|n| n.parse().ok()) {
+            Some(n) => Ok(VReg(n)),
+            None => self.error(format!("expected a register, found '{token}'")),
+        }
+    }
+
+    fn block_id(&mut self) -> Result<BlockId, IrParseError> {
+        let token = self.next()?;
+        match token.strip_prefix("bb").and_then(|n| n.parse().ok()) {
+            Some(n) => Ok(BlockId(n)),
+            None => self.error(format!("expected a block, found '{token}'")),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, IrParseError> {
+        let token = self.next()?;
+        if let Some(n) = token.strip_prefix('%') {
+            return n.parse().map(|n| Operand::Reg(VReg(n))).or_else(|_| self.error(format!("bad register '{token}'")));
+        }
+        if let Ok(value) = token.parse::<i64>() {
+            return Ok(Operand::Int(value));
+        }
+        match token.parse::<f64>() {
+            Ok(value) => Ok(Operand::Real(value)),
+            Err(_) => self.error(format!("expected an operand, found '{token}'")),
+        }
+    }
+
+    fn operand_list(&mut self) -> Result<Vec<Operand>, IrParseError> {
+        self.expect("(")?;
+        let mut operands = vec![];
+        if !self.eat(")") {
+            loop {
+                operands.push(self.operand()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        Ok(operands)
+    }
+
+    fn target(&mut self) -> Result<Target, IrParseError> {
+        let block = self.block_id()?;
+        let args = if self.peek() == Some("(") { self.operand_list()? } else { vec![] };
+        Ok(Target { block, args })
+    }
+
+    fn lookup<T: Copy>(&self, table: &[(T, &'static str)], name: &str) -> Option<T> {
+        table.iter().find(|(_, n)| *n == name).map(|(v, _)| *v)
+    }
+}
+
+impl IrParser<'_> {
+    fn next_line(&mut self) -> Option<Line> {
+        for (number, text) in self.lines.by_ref() {
+            let tokens = tokenize(text);
+            if !tokens.is_empty() {
+                return Some(Line { number: number + 1, tokens, position: 0 });
+            }
+        }
+        None
+    }
+
+    fn module(&mut self) -> Result<Module, IrParseError> {
+        let Some(mut line) = self.next_line() else {
+            return Err(IrParseError { line: 1, message: "expected 'module'".to_string() });
+        };
+        line.expect("module")?;
+        let mut module = Module::new(&line.next()?);
+        line.end()?;
+
+        while let Some(mut line) = self.next_line() {
+            let exported = line.eat("export");
+            match line.next()?.as_str() {
+                "global" => {
+                    let name = line.symbol()?;
+                    let size = line.number()?;
+                    line.expect("align")?;
+                    let align = line.number()?;
+                    let init = if line.eat("=") {
+                        let text = line.next()?;
+                        match text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+                            Some(text) => Some(unescape(text).or_else(|message| line.error(message))?),
+                            None => return line.error("expected a string"),
+                        }
+                    } else {
+                        None
+                    };
+                    line.end()?;
+                    module.globals.push(Global { name, exported, size, align, init });
+                }
+                "typedesc" => {
+                    let name = line.symbol()?;
+                    line.expect("size")?;
+                    let size = line.number()?;
+                    let base = if line.eat("base") { Some(line.symbol()?) } else { None };
+                    line.end()?;
+                    module.descriptors.push(TypeDesc { name, exported, size, base });
+                }
+                "extern" => {
+                    let name = line.symbol()?;
+                    line.expect("(")?;
+                    let mut params = vec![];
+                    if !line.eat(")") {
+                        loop {
+                            params.push(line.ty()?);
+                            if line.eat(")") {
+                                break;
+                            }
+                            line.expect(",")?;
+                        }
+                    }
+                    let ret = if line.eat("->") { Some(line.ty()?) } else { None };
+                    line.end()?;
+                    module.externs.push(Extern { name, params, ret });
+                }
+                "func" => {
+                    let function = self.function(line, exported)?;
+                    module.functions.push(function);
+                }
+                other => return line.error(format!("unexpected '{other}'")),
+            }
+        }
+        Ok(module)
+    }
+
+    fn function(&mut self, mut line: Line, exported: bool) -> Result<Function, IrParseError> {
+        let name = line.symbol()?;
+        let mut types: HashMap<u32, Ty> = HashMap::new();
+        let mut params = vec![];
+        line.expect("(")?;
+        if !line.eat(")") {
+            loop {
+                let reg = line.reg()?;
+                line.expect(":")?;
+                let ty = line.ty()?;
+                let by_ref = line.eat("byref");
+                types.insert(reg.0, ty);
+                params.push(Param { reg, ty, by_ref });
+                if line.eat(")") {
+                    break;
+                }
+                line.expect(",")?;
+            }
+        }
+        let ret = if line.eat("->") { Some(line.ty()?) } else { None };
+        line.expect("{")?;
+        line.end()?;
+
+        let mut function = Function::new(&name, exported, ret);
+        function.params = params;
+        let mut current: Option<Block> = None;
+        loop {
+            let Some(mut line) = self.next_line() else {
+                return Err(IrParseError { line: line.number, message: format!("function @{name} is not closed") });
+            };
+            let first = line.peek().unwrap().to_string();
+            if first == "}" {
+                if current.is_some() {
+                    return line.error("block has no terminator");
+                }
+                break;
+            }
+            if first == "slot" {
+                line.next()?;
+                let slot = line.next()?;
+                if slot != format!("${}", function.slots.len()) {
+                    return line.error(format!("expected slot ${}", function.slots.len()));
+                }
+                let size = line.number()?;
+                line.expect("align")?;
+                let align = line.number()?;
+                line.end()?;
+                function.add_slot(size, align);
+                continue;
+            }
+            if first.starts_with("bb") {
+                if current.is_some() {
+                    return line.error("previous block has no terminator");
+                }
+                let id = line.block_id()?;
+                let mut block_params = vec![];
+                if line.eat("(") {
+                    loop {
+                        let reg = line.reg()?;
+                        line.expect(":")?;
+                        let ty = line.ty()?;
+                        types.insert(reg.0, ty);
+                        block_params.push((reg, ty));
+                        if line.eat(")") {
+                            break;
+                        }
+                        line.expect(",")?;
+                    }
+                }
+                line.expect(":")?;
+                line.end()?;
+                current = Some(Block { id, params: block_params, insts: vec![], term: Terminator::Trap(0) });
+                continue;
+            }
+            let Some(block) = current.as_mut() else {
+                return line.error("instruction outside of a block");
+            };
+            if let Some(term) = terminator(&mut line)? {
+                line.end()?;
+                block.term = term;
+                function.blocks.push(current.take().unwrap());
+                continue;
+            }
+            let inst = instruction(&mut line)?;
+            line.end()?;
+            if let Some((reg, ty)) = inst.def()
+                && types.insert(reg.0, ty).is_some()
+            {
+                return line.error(format!("{reg} is defined more than once"));
+            }
+            block.insts.push(inst);
+        }
+
+        let count = types.keys().max().map_or(0, |n| n + 1);
+        for n in 0..count {
+            let ty = types.get(&n).copied().unwrap_or(Ty::I64);
+            function.new_reg(ty);
+        }
+        Ok(function)
+    }
+}
+
+fn terminator(line: &mut Line) -> Result<Option<Terminator>, IrParseError> {
+    let term = match line.peek() {
+        Some("jump") => {
+            line.next()?;
+            Terminator::Jump(line.target()?)
+        }
+        Some("br") => {
+            line.next()?;
+            let cond = line.operand()?;
+            line.expect(",")?;
+            let then_target = line.target()?;
+            line.expect(",")?;
+            let else_target = line.target()?;
+            Terminator::Branch { cond, then_target, else_target }
+        }
+        Some("ret") => {
+            line.next()?;
+            Terminator::Return(if line.peek().is_some() { Some(line.operand()?) } else { None })
+        }
+        Some("trap") => {
+            line.next()?;
+            Terminator::Trap(line.number()?)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(term))
+}
+
+fn instruction(line: &mut Line) -> Result<Inst, IrParseError> {
+    match line.peek() {
+        Some("store") => {
+            line.next()?;
+            let mem = line.mem_ty()?;
+            let value = line.operand()?;
+            line.expect(",")?;
+            let addr = line.operand()?;
+            return Ok(Inst::Store { mem, value, addr });
+        }
+        Some("memcopy") => {
+            line.next()?;
+            let dst = line.operand()?;
+            line.expect(",")?;
+            let src = line.operand()?;
+            line.expect(",")?;
+            let size = line.operand()?;
+            return Ok(Inst::MemCopy { dst, src, size });
+        }
+        Some("typeguard") => {
+            line.next()?;
+            let tag = line.operand()?;
+            line.expect(",")?;
+            let desc = line.symbol()?;
+            return Ok(Inst::TypeGuard { tag, desc });
+        }
+        Some("call") => {
+            line.next()?;
+            let (callee, args) = call(line)?;
+            return Ok(Inst::Call { dst: None, callee, args });
+        }
+        _ => {}
+    }
+
+    let dst = line.reg()?;
+    line.expect("=")?;
+    let opcode = line.next()?;
+    let inst = match opcode.as_str() {
+        "copy" => Inst::Copy { dst, ty: line.ty()?, src: line.operand()? },
+        "cmp" => {
+            let cond = line.next()?;
+            let Some(cond) = line.lookup(&CONDS, &cond) else {
+                return line.error(format!("unknown condition '{cond}'"));
+            };
+            let ty = line.ty()?;
+            let lhs = line.operand()?;
+            line.expect(",")?;
+            Inst::Cmp { dst, cond, ty, lhs, rhs: line.operand()? }
+        }
+        "load" => Inst::Load { dst, mem: line.mem_ty()?, addr: line.operand()? },
+        "slotaddr" => {
+            let token = line.next()?;
+            match token.strip_prefix('$').and_then(|n| n.parse().ok()) {
+                Some(n) => Inst::SlotAddr { dst, slot: SlotId(n) },
+                None => return line.error(format!("expected a slot, found '{token}'")),
+            }
+        }
+        "globaladdr" => Inst::GlobalAddr { dst, name: line.symbol()? },
+        "funcaddr" => Inst::FuncAddr { dst, name: line.symbol()? },
+        "ptradd" => {
+            let base = line.operand()?;
+            line.expect(",")?;
+            Inst::PtrAdd { dst, base, offset: line.operand()? }
+        }
+        "call" => {
+            let ty = line.ty()?;
+            let (callee, args) = call(line)?;
+            Inst::Call { dst: Some((dst, ty)), callee, args }
+        }
+        "new" => Inst::New { dst, desc: line.symbol()? },
+        "typetest" => {
+            let tag = line.operand()?;
+            line.expect(",")?;
+            Inst::TypeTest { dst, tag, desc: line.symbol()? }
+        }
+        other => {
+            if let Some(op) = line.lookup(&BIN_OPS, other) {
+                let ty = line.ty()?;
+                let lhs = line.operand()?;
+                line.expect(",")?;
+                Inst::Binary { dst, op, ty, lhs, rhs: line.operand()? }
+            } else if let Some(op) = line.lookup(&UN_OPS, other) {
+                Inst::Unary { dst, op, ty: line.ty()?, operand: line.operand()? }
+            } else if let Some(conv) = line.lookup(&CONVS, other) {
+                Inst::Convert { dst, conv, src: line.operand()? }
+            } else {
+                return line.error(format!("unknown instruction '{other}'"));
+            }
+        }
+    };
+    Ok(inst)
+}
+
+fn call(line: &mut Line) -> Result<(Callee, Vec<Operand>), IrParseError> {
+    let callee = if line.peek().is_some_and(|t| t.starts_with('@')) {
+        Callee::Direct(line.symbol()?)
+    } else {
+        Callee::Indirect(line.operand()?)
+    };
+    Ok((callee, line.operand_list()?))
+}
+
+fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let hex: String = chars.by_ref().take(2).collect();
+            bytes.push(u8::from_str_radix(&hex, 16).map_err(|_| format!("bad escape '\\{hex}'"))?);
+        } else {
+            bytes.push(c as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"module Sample
+
+export global @Sample_count 8 align 8
+global @Sample_greeting 6 align 1 = "hello\00"
+typedesc @Sample_Base size 8
+export typedesc @Sample_Node size 16 base @Sample_Base
+extern @Out_Int(i64, i64)
+
+export func @Sample_Bump(%0: ptr byref, %1: i64) -> i64 {
+  slot $0 8 align 8
+bb0:
+  %2 = load i64 %0
+  %3 = add i64 %2, %1
+  store i64 %3, %0
+  %4 = cmp lt i64 %3, 10
+  br %4, bb1(%3), bb2
+bb1(%5: i64):
+  %6 = slotaddr $0
+  store i64 %5, %6
+  call @Out_Int(%5, 0)
+  ret %5
+bb2:
+  %7 = new @Sample_Node
+  %8 = ptradd %7, -8
+  %9 = load ptr %8
+  %10 = typetest %9, @Sample_Base
+  typeguard %9, @Sample_Node
+  %11 = itof %10
+  %12 = mul f64 %11, 2.5
+  %13 = floor %12
+  %14 = funcaddr @Sample_Bump
+  %15 = globaladdr @Sample_count
+  %16 = call i64 %14(%15, %13)
+  memcopy %15, %15, 8
+  jump bb3(%16)
+bb3(%17: i64):
+  trap 3
+}
+"#;
+
+    #[test]
+    fn parses_and_prints_round_trip() {
+        let module = parse(SAMPLE).unwrap();
+        assert_eq!(module.to_string(), SAMPLE);
+        assert_eq!(parse(&module.to_string()).unwrap(), module);
+    }
+
+    #[test]
+    fn infers_register_types() {
+        let module = parse(SAMPLE).unwrap();
+        let function = module.function("Sample_Bump").unwrap();
+        assert_eq!(function.reg_count(), 18);
+        assert_eq!(function.reg_type(VReg(0)), Ty::Ptr);
+        assert_eq!(function.reg_type(VReg(5)), Ty::I64);
+        assert_eq!(function.reg_type(VReg(12)), Ty::F64);
+        assert_eq!(function.reg_type(VReg(13)), Ty::I64);
+        assert!(function.params[0].by_ref);
+    }
+
+    #[test]
+    fn reads_global_initializers() {
+        let module = parse(SAMPLE).unwrap();
+        assert_eq!(module.globals[1].init.as_deref(), Some(&b"hello\0"[..]));
+        assert_eq!(module.descriptors[1].base.as_deref(), Some("Sample_Base"));
+    }
+
+    #[test]
+    fn builds_functions_programmatically() {
+        let mut function = Function::new("M_Twice", false, Some(Ty::I64));
+        let x = function.add_param(Ty::I64, false);
+        let entry = function.add_block();
+        let doubled = function.new_reg(Ty::I64);
+        let block = function.block_mut(entry);
+        block.insts.push(Inst::Binary { dst: doubled, op: BinOp::Mul, ty: Ty::I64, lhs: Operand::Reg(x), rhs: Operand::Int(2) });
+        block.term = Terminator::Return(Some(Operand::Reg(doubled)));
+
+        let text = function.to_string();
+        assert_eq!(text, "func @M_Twice(%0: i64) -> i64 {\nbb0:\n  %1 = mul i64 %0, 2\n  ret %1\n}\n");
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        let error = parse("module M\n\nfunc @f() {\nbb0:\n  %0 = frobnicate i64 1\n  ret\n}\n").unwrap_err();
+        assert_eq!(error.line, 5);
+        assert!(error.message.contains("frobnicate"));
+
+        let error = parse("module M\nfunc @f() {\nbb0:\n  %0 = copy i64 1\n  %0 = copy i64 2\n  ret\n}\n").unwrap_err();
+        assert_eq!(error.line, 5);
+    }
+
+    #[test]
+    fn keeps_real_operands_exact() {
+        let text = "module M\n\nfunc @f() -> f64 {\nbb0:\n  %0 = add f64 0.1, 1e300\n  ret %0\n}\n";
+        let module = parse(text).unwrap();
+        let Inst::Binary { lhs, rhs, .. } = &module.functions[0].blocks[0].insts[0] else {
+            panic!("Expected binary instruction");
+        };
+        assert_eq!((*lhs, *rhs), (Operand::Real(0.1), Operand::Real(1e300)));
+    }
+}
@@ -0,0 +1,6 @@
+pub mod frontend;
+pub mod backend;
+pub mod ir;
+pub mod error;
+pub mod cli;
+pub mod project;
@@ -1,47 +1,324 @@
-use std::{env, fs};
+use std::collections::HashSet;
+use std::fs;
 use std::path::{Path, PathBuf};
-use crate::error::CompilerError;
+use clap::Parser as _;
+use oberon_compiler::backend::{c_generator, code_generator};
+use oberon_compiler::backend::optimizer::{PassManager, Stage};
+use oberon_compiler::cli::{AstFormat, BuildArgs, CheckArgs, Cli, Command, Emit, OptLevel, ParseArgs, ProjectArgs, ReportFormat, TokensArgs, Verbosity};
+use oberon_compiler::error::CompilerError;
+use oberon_compiler::frontend::analysis::{self, Analysis};
+use oberon_compiler::frontend::ir_generator;
+use oberon_compiler::frontend::ast::Module;
+use oberon_compiler::frontend::lexer::Lexer;
+use oberon_compiler::frontend::literal::IntegerWidth;
+use oberon_compiler::frontend::parser::Parser;
+use oberon_compiler::frontend::symbol_file::{self, SymbolFile, SymbolFileError};
+use oberon_compiler::frontend::token::TokenKind;
+use oberon_compiler::project::{self, Project};
 use std::error::Error;
 
-mod frontend;
-mod backend;
-mod ir;
-mod error;
-
 fn main() {
-    if let Err(err) = run() {
-        eprintln!("Error: {err}");
+    let cli = Cli::parse();
+
+    if let Err(err) = run(&cli) {
+        report(&err, error_format(&cli.command));
+        std::process::exit(1);
+    }
+}
 
-        let mut current = err.source();
-        while let Some(source) = current {
-            eprintln!("  caused by: {source}");
-            current = source.source();
+fn run(cli: &Cli) -> Result<(), CompilerError> {
+    match &cli.command {
+        Command::Build(args) => build(args, cli.verbosity),
+        Command::Project(args) => build_project(args, cli.verbosity),
+        Command::Check(args) => check(args, cli.verbosity),
+        Command::Parse(args) => parse(args, cli.verbosity),
+        Command::Tokens(args) => tokens(args, cli.verbosity),
+    }
+}
+
+fn error_format(command: &Command) -> ReportFormat {
+    match command {
+        Command::Check(args) => args.format,
+        Command::Tokens(args) => args.format,
+        Command::Build(_) | Command::Project(_) | Command::Parse(_) => ReportFormat::Text,
+    }
+}
+
+fn report(err: &CompilerError, format: ReportFormat) {
+    match format {
+        ReportFormat::Text => {
+            let diagnostics = err.diagnostics();
+            if !diagnostics.is_empty() {
+                let mut file: Option<(&Path, String)> = None;
+                for (path, diagnostic) in diagnostics {
+                    if file.as_ref().is_none_or(|(shown, _)| *shown != path) {
+                        // The file was read successfully moments ago; without it only the snippets are lost.
+                        file = Some((path, fs::read_to_string(path).unwrap_or_default()));
+                    }
+                    let source = file.as_ref().map_or("", |(_, source)| source.as_str());
+                    eprintln!("{}", diagnostic.render(&path.display().to_string(), source));
+                }
+                eprintln!("Error: {err}");
+                return;
+            }
+
+            eprintln!("Error: {err}");
+
+            let mut current = err.source();
+            while let Some(source) = current {
+                eprintln!("  caused by: {source}");
+                current = source.source();
+            }
         }
+        ReportFormat::Debug => eprintln!("{err:#?}"),
+    }
+}
 
-        std::process::exit(1);
+fn info(verbosity: Verbosity, level: i8, message: impl FnOnce() -> String) {
+    if verbosity.level() >= level {
+        eprintln!("{}", message());
     }
 }
 
-fn run() -> Result<(), CompilerError> {
-    let input = env::args()
-        .nth(1)
-        .expect("please provide an input filename");
+fn build(args: &BuildArgs, verbosity: Verbosity) -> Result<(), CompilerError> {
+    let source = read_source_file(&args.input)?;
+    let width = args.literal_range.integer_width();
+    let module = parse_module(&args.input, &source, width, verbosity)?;
+    let imports = load_imports(&args.input, &module, &args.import_paths, verbosity)?;
+    let analysis = check_module(&args.input, &module, &imports, width, verbosity)?;
 
-    let output = env::args()
-        .nth(2)
-        .expect("please provide an output filename");
+    // Importers find the symbol file by module name, so it goes next to the output.
+    let symbols = args.output.with_file_name(format!("{}.sym", module.name.text));
+    write_output_file(&symbols, &SymbolFile::export(&module.name.text, &analysis).to_string())?;
+    info(verbosity, 1, || format!("wrote symbol file {}", symbols.display()));
 
-    let input_path = PathBuf::from(input);
-    let output_path = PathBuf::from(output);
+    let options = CodeOptions { emit: args.emit, opt_level: args.opt_level, dump_ir: args.dump_ir, main: args.main };
+    generate(&args.output, &module, &analysis, &options, verbosity)?;
 
-    let source = read_source_file(&input_path)?;
-    write_output_file(&output_path, &source)?;
+    info(verbosity, 0, || format!("Compiled {} to {}", args.input.display(), args.output.display()));
+    Ok(())
+}
+
+fn build_project(args: &ProjectArgs, verbosity: Verbosity) -> Result<(), CompilerError> {
+    let width = args.literal_range.integer_width();
+    let project = Project::load(&args.input, &args.import_paths, width)?;
+    let count = project.units.len();
+    info(verbosity, 1, || {
+        let names: Vec<&str> = project.units.iter().map(|unit| unit.name.as_str()).collect();
+        format!("build order: {}", names.join(", "))
+    });
+    fs::create_dir_all(&args.output_dir).map_err(|source| CompilerError::Io { path: args.output_dir.clone(), source })?;
+
+    // The symbol files written here win over stale ones elsewhere.
+    let import_paths: Vec<PathBuf> = std::iter::once(args.output_dir.clone()).chain(args.import_paths.iter().cloned()).collect();
+    let extension = match args.emit {
+        Emit::Asm => "s",
+        Emit::C => "c",
+    };
+    let mut compiled = 0;
+    for (index, unit) in project.units.into_iter().enumerate() {
+        let output = args.output_dir.join(format!("{}.{extension}", unit.name));
+        let symbols = args.output_dir.join(format!("{}.sym", unit.name));
+        let mut outputs = vec![output.clone()];
+        if args.emit == Emit::C {
+            outputs.push(output.with_extension("h"));
+        }
+        let imported = unit.module.imports.iter().filter_map(|import| find_symbol_file(&unit.path, &import.module.text, &import_paths));
+        // The outputs are stamped too, so one overwritten since, say by a `build` into the
+        // output directory, is compiled again rather than left for importers to compile against.
+        let files: Vec<PathBuf> = std::iter::once(unit.path.clone()).chain(imported).chain(outputs).chain([symbols.clone()]).collect();
+        let main = index + 1 == count;
+        // So are the options that change the outputs.
+        let settings = format!("emit {extension} -O{} literal-range {} main {main}", args.opt_level.level(), width.bits());
+        let stamp_path = args.output_dir.join(format!("{}.stamp", unit.name));
+        let stamp = project::stamp(&settings, &files);
+        if !args.force && stamp.is_some() && fs::read_to_string(&stamp_path).ok() == stamp {
+            info(verbosity, 1, || format!("{} is up to date", unit.name));
+            continue;
+        }
 
-    println!("Copied {} to {}", input_path.display(), output_path.display());
+        let imports = load_imports(&unit.path, &unit.module, &import_paths, verbosity)?;
+        let analysis = check_module(&unit.path, &unit.module, &imports, width, verbosity)?;
+        write_output_file(&symbols, &SymbolFile::export(&unit.name, &analysis).to_string())?;
+        info(verbosity, 1, || format!("wrote symbol file {}", symbols.display()));
+        let options = CodeOptions { emit: args.emit, opt_level: args.opt_level, dump_ir: args.dump_ir, main };
+        generate(&output, &unit.module, &analysis, &options, verbosity)?;
+        // Written last, so a module whose build failed halfway is compiled again.
+        if let Some(stamp) = project::stamp(&settings, &files) {
+            write_output_file(&stamp_path, &stamp)?;
+        }
+        info(verbosity, 0, || format!("Compiled {} to {}", unit.path.display(), output.display()));
+        compiled += 1;
+    }
 
+    info(verbosity, 0, || format!("Built {}: compiled {compiled} of {count} modules", args.input.display()));
     Ok(())
 }
 
+/// What `generate` makes of a checked module.
+struct CodeOptions {
+    emit: Emit,
+    opt_level: OptLevel,
+    dump_ir: bool,
+    /// Whether to add a `main` function that runs the module body.
+    main: bool,
+}
+
+/// Generates the code for `module` and writes it to `output`.
+fn generate(output: &Path, module: &Module, analysis: &Analysis, options: &CodeOptions, verbosity: Verbosity) -> Result<(), CompilerError> {
+    let code = match options.emit {
+        Emit::Asm => {
+            let mut ir = ir_generator::generate(module, analysis);
+            info(verbosity, 2, || format!("lowered {} functions to IR", ir.functions.len()));
+            let passes = PassManager::for_level(options.opt_level.level());
+            info(verbosity, 2, || format!("optimization passes: {}", passes.pass_names().join(", ")));
+            if options.dump_ir {
+                passes.run_with_dump(&mut ir, &mut |pass, stage, function| {
+                    let when = match stage {
+                        Stage::Before => "before",
+                        Stage::After { changed: true } => "after",
+                        Stage::After { changed: false } => "after (unchanged)",
+                    };
+                    eprint!(";; {} {when} {pass}\n{function}", function.name);
+                });
+            } else {
+                passes.run(&mut ir);
+            }
+            let mut code = code_generator::generate(&ir);
+            if options.main {
+                code.push_str(&code_generator::entry_point(&ir.name));
+            }
+            code
+        }
+        Emit::C => {
+            // The source includes the header by module name, so it goes next to the output.
+            let c = c_generator::generate(module, analysis);
+            let header = output.with_file_name(format!("{}.h", c.name));
+            write_output_file(&header, &c.header)?;
+            info(verbosity, 1, || format!("wrote header {}", header.display()));
+            let mut code = c.source;
+            if options.main {
+                code.push_str(&c_generator::entry_point(&c.name));
+            }
+            code
+        }
+    };
+    info(verbosity, 1, || format!("generated {} bytes of code", code.len()));
+    write_output_file(output, &code)
+}
+
+fn check(args: &CheckArgs, verbosity: Verbosity) -> Result<(), CompilerError> {
+    let source = read_source_file(&args.input)?;
+    let width = args.literal_range.integer_width();
+    let module = parse_module(&args.input, &source, width, verbosity)?;
+    let imports = load_imports(&args.input, &module, &args.import_paths, verbosity)?;
+    check_module(&args.input, &module, &imports, width, verbosity)?;
+
+    info(verbosity, 0, || format!("{}: no errors", args.input.display()));
+    Ok(())
+}
+
+fn parse(args: &ParseArgs, verbosity: Verbosity) -> Result<(), CompilerError> {
+    let source = read_source_file(&args.input)?;
+    let module = parse_module(&args.input, &source, args.literal_range.integer_width(), verbosity)?;
+
+    match args.format {
+        AstFormat::Pretty => println!("{module:#?}"),
+        AstFormat::Compact => println!("{module:?}"),
+    }
+    Ok(())
+}
+
+fn tokens(args: &TokensArgs, verbosity: Verbosity) -> Result<(), CompilerError> {
+    let source = read_source_file(&args.input)?;
+    let mut lexer = if args.trivia { Lexer::with_trivia(&source) } else { Lexer::new(&source) };
+    let mut count = 0;
+
+    loop {
+        let token = lexer.next_token().map_err(|source| CompilerError::Lexer {
+            path: args.input.clone(),
+            source,
+        })?;
+        count += 1;
+
+        match args.format {
+            ReportFormat::Text => {
+                let (start, end) = (token.span.start, token.span.end);
+                // Whitespace and comments may span lines; escaped, each token stays on one.
+                let lexeme = if token.kind.is_trivia() { token.text_in(&source).escape_debug().to_string() } else { token.lexeme().to_string() };
+                println!(
+                    "{}:{}-{}:{}\t{:?}\t{}",
+                    start.line, start.column, end.line, end.column, token.kind, lexeme
+                );
+            }
+            ReportFormat::Debug => println!("{token:?}"),
+        }
+
+        if token.kind == TokenKind::Eof {
+            break;
+        }
+    }
+
+    info(verbosity, 1, || format!("lexed {count} tokens"));
+    Ok(())
+}
+
+fn parse_module(path: &Path, source: &str, width: IntegerWidth, verbosity: Verbosity) -> Result<Module, CompilerError> {
+    let (module, errors) = Parser::new(Lexer::new(source)).with_integer_width(width).parse_with_recovery();
+    if !errors.is_empty() {
+        return Err(CompilerError::from_parser(path.to_path_buf(), errors));
+    }
+
+    info(verbosity, 1, || format!("parsed module {}", module.name.text));
+    Ok(module)
+}
+
+/// Reads the symbol files of the modules `module` imports, searching the import paths and then the
+/// directory of `input`. Modules without a symbol file are left to the analysis to report. The
+/// symbol files of the modules those were compiled against are read as well, so the analysis can
+/// tell whether they are out of date.
+fn load_imports(input: &Path, module: &Module, import_paths: &[PathBuf], verbosity: Verbosity) -> Result<Vec<SymbolFile>, CompilerError> {
+    let mut files: Vec<SymbolFile> = vec![];
+    let mut pending: Vec<String> = module.imports.iter().rev().map(|import| import.module.text.to_string()).collect();
+    let mut seen = HashSet::new();
+    while let Some(name) = pending.pop() {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        let Some(path) = find_symbol_file(input, &name, import_paths) else {
+            continue;
+        };
+        let text = read_source_file(&path)?;
+        let file = symbol_file::parse(&text).and_then(|file| {
+            if file.module == name {
+                Ok(file)
+            } else {
+                Err(SymbolFileError { line: 1, message: format!("expected module '{name}', found '{}'", file.module) })
+            }
+        });
+        let file = file.map_err(|source| CompilerError::SymbolFile { path: path.clone(), source })?;
+        info(verbosity, 2, || format!("read symbol file {}", path.display()));
+        pending.extend(file.imports.iter().map(|dependency| dependency.module.clone()));
+        files.push(file);
+    }
+    Ok(files)
+}
+
+fn find_symbol_file(input: &Path, module: &str, import_paths: &[PathBuf]) -> Option<PathBuf> {
+    let directories = import_paths.iter().map(PathBuf::as_path).chain(input.parent());
+    directories.map(|directory| directory.join(format!("{module}.sym"))).find(|path| path.is_file())
+}
+
+fn check_module(path: &Path, module: &Module, imports: &[SymbolFile], width: IntegerWidth, verbosity: Verbosity) -> Result<Analysis, CompilerError> {
+    let analysis = analysis::check_with_integer_width(module, imports, width).map_err(|errors| CompilerError::Analysis {
+        path: path.to_path_buf(),
+        errors,
+    })?;
+
+    info(verbosity, 1, || format!("checked module {}", module.name.text));
+    Ok(analysis)
+}
+
 fn read_source_file(path: &Path) -> Result<String, CompilerError> {
     let bytes = fs::read(path).map_err(|source| CompilerError::Io {
         path: path.to_path_buf(),
@@ -59,4 +336,4 @@ fn write_output_file(path: &Path, text: &str) -> Result<(), CompilerError> {
         path: path.to_path_buf(),
         source,
     })
-}
\ No newline at end of file
+}
@@ -0,0 +1,333 @@
+//! Project mode: a main module together with every module it imports.
+//!
+//! `Project::load` starts from the source file of the main module and follows the `IMPORT` lists
+//! to the source files of the imported modules, `<Module>.Mod` in the directory of the main module
+//! or else in one of the search paths. Imported modules without a source file are taken to be
+//! compiled already; the analysis looks for their symbol files. The modules come out in build
+//! order, every module after the modules it imports and the main module last. Imports that form
+//! a cycle have no such order and are reported as a `ProjectError::ImportCycle`.
+//!
+//! A rebuild skips the modules whose source and imported symbol files are the same as the last
+//! time. `stamp` sums those files up in a short text that the driver keeps next to the output and
+//! compares against; it is based on the contents, as modification times may be too coarse to
+//! tell apart a source from the output written right after it.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use crate::error::CompilerError;
+use crate::frontend::ast::Module;
+use crate::frontend::diagnostics::Diagnostic;
+use crate::frontend::lexer::Lexer;
+use crate::frontend::literal::IntegerWidth;
+use crate::frontend::parser::Parser;
+use crate::frontend::span::Span;
+use crate::frontend::symbol_file::Fingerprint;
+
+/// A module of a project.
+#[derive(Debug)]
+pub struct Unit {
+    pub name: String,
+    pub path: PathBuf,
+    pub module: Module,
+    /// The imported modules that are part of the project, as indices into `Project::units`.
+    pub dependencies: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub struct Project {
+    /// The modules in build order; the main module is the last one.
+    pub units: Vec<Unit>,
+}
+
+/// An import that is part of a cycle: `module`, in the file `path`, imports `import` at `span`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CycleImport {
+    pub path: PathBuf,
+    pub module: String,
+    pub import: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ProjectError {
+    #[error("import cycle: {}", cycle_names(cycle))]
+    ImportCycle { cycle: Vec<CycleImport> },
+
+    #[error("file for module '{expected}' declares module '{declared}'")]
+    FileNameMismatch {
+        path: PathBuf,
+        expected: String,
+        declared: String,
+        span: Span,
+    },
+}
+
+fn cycle_names(cycle: &[CycleImport]) -> String {
+    let mut names: Vec<&str> = cycle.iter().map(|import| import.module.as_str()).collect();
+    names.extend(cycle.first().map(|import| import.module.as_str()));
+    names.join(" -> ")
+}
+
+impl ProjectError {
+    /// The diagnostics for the error, each with the file it points into.
+    pub fn diagnostics(&self) -> Vec<(&Path, Diagnostic)> {
+        match self {
+            ProjectError::ImportCycle { cycle } => cycle
+                .iter()
+                .map(|import| {
+                    let diagnostic = Diagnostic::error("E0401", self.to_string(), import.span)
+                        .with_label(format!("'{}' imports '{}'", import.module, import.import));
+                    (import.path.as_path(), diagnostic)
+                })
+                .collect(),
+            ProjectError::FileNameMismatch { path, expected, span, .. } => {
+                let diagnostic = Diagnostic::error("E0402", self.to_string(), *span)
+                    .with_label(format!("expected '{expected}'"))
+                    .with_note(format!("the source of module '{expected}' is looked up as {expected}.Mod"));
+                vec![(path.as_path(), diagnostic)]
+            }
+        }
+    }
+}
+
+impl Project {
+    /// Reads and parses the main module at `main` and every module it imports, directly or not,
+    /// whose source is found in `search_paths` or next to it, searched in that order as for symbol
+    /// files, with INTEGERs of `width` bits.
+    pub fn load(main: &Path, search_paths: &[PathBuf], width: IntegerWidth) -> Result<Project, CompilerError> {
+        let directories: Vec<&Path> = search_paths.iter().map(PathBuf::as_path).chain(main.parent()).collect();
+        let mut units = vec![parse_unit(main, None, width)?];
+        let mut indices = HashMap::from([(units[0].name.clone(), 0)]);
+        // For every unit, the units it imports and the spans of the imports.
+        let mut edges: Vec<Vec<(usize, Span)>> = vec![];
+
+        while edges.len() < units.len() {
+            let mut imports = vec![];
+            let names: Vec<(String, Span)> = units[edges.len()].module.imports.iter().map(|import| (import.module.text.to_string(), import.module.span)).collect();
+            for (name, span) in names {
+                let index = match indices.get(&name) {
+                    Some(&index) => index,
+                    None => {
+                        let found = directories.iter().map(|directory| directory.join(format!("{name}.Mod"))).find(|path| path.is_file());
+                        let Some(path) = found else { continue };
+                        indices.insert(name.clone(), units.len());
+                        units.push(parse_unit(&path, Some(&name), width)?);
+                        units.len() - 1
+                    }
+                };
+                imports.push((index, span));
+            }
+            edges.push(imports);
+        }
+
+        let order = build_order(&units, &edges)?;
+        let mut positions = vec![0; units.len()];
+        for (position, &index) in order.iter().enumerate() {
+            positions[index] = position;
+        }
+        let mut units: Vec<Option<Unit>> = units.into_iter().map(Some).collect();
+        let units = order
+            .iter()
+            .map(|&index| {
+                let mut unit = units[index].take().expect("every unit is ordered once");
+                unit.dependencies = edges[index].iter().map(|&(dependency, _)| positions[dependency]).collect();
+                unit
+            })
+            .collect();
+        Ok(Project { units })
+    }
+}
+
+fn parse_unit(path: &Path, expected: Option<&str>, width: IntegerWidth) -> Result<Unit, CompilerError> {
+    let bytes = fs::read(path).map_err(|source| CompilerError::Io { path: path.to_path_buf(), source })?;
+    let source = String::from_utf8(bytes).map_err(|source| CompilerError::Utf8 { path: path.to_path_buf(), source })?;
+    let (module, errors) = Parser::new(Lexer::new(&source)).with_integer_width(width).parse_with_recovery();
+    if !errors.is_empty() {
+        return Err(CompilerError::from_parser(path.to_path_buf(), errors));
+    }
+    if let Some(expected) = expected
+        && module.name.text != expected
+    {
+        return Err(ProjectError::FileNameMismatch {
+            path: path.to_path_buf(),
+            expected: expected.to_string(),
+            declared: module.name.text.to_string(),
+            span: module.name.span,
+        }
+        .into());
+    }
+    Ok(Unit { name: module.name.text.to_string(), path: path.to_path_buf(), module, dependencies: vec![] })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    New,
+    Visiting,
+    Done,
+}
+
+/// Orders the units depth first from the main module, each after the units it imports.
+fn build_order(units: &[Unit], edges: &[Vec<(usize, Span)>]) -> Result<Vec<usize>, ProjectError> {
+    struct Search<'a> {
+        units: &'a [Unit],
+        edges: &'a [Vec<(usize, Span)>],
+        marks: Vec<Mark>,
+        /// The imports followed from the main module to the unit being visited: the importing
+        /// unit, the span of the import and the imported unit.
+        path: Vec<(usize, Span, usize)>,
+        order: Vec<usize>,
+    }
+
+    impl Search<'_> {
+        fn visit(&mut self, index: usize) -> Result<(), ProjectError> {
+            self.marks[index] = Mark::Visiting;
+            for &(dependency, span) in &self.edges[index] {
+                match self.marks[dependency] {
+                    Mark::Done => {}
+                    Mark::New => {
+                        self.path.push((index, span, dependency));
+                        self.visit(dependency)?;
+                        self.path.pop();
+                    }
+                    Mark::Visiting => {
+                        // The dependency is on the path, so the imports from it to here close a cycle.
+                        let start = self.path.iter().position(|&(unit, ..)| unit == dependency).unwrap_or(self.path.len());
+                        let imports = self.path[start..].iter().copied().chain([(index, span, dependency)]);
+                        let cycle = imports
+                            .map(|(unit, span, import)| CycleImport {
+                                path: self.units[unit].path.clone(),
+                                module: self.units[unit].name.clone(),
+                                import: self.units[import].name.clone(),
+                                span,
+                            })
+                            .collect();
+                        return Err(ProjectError::ImportCycle { cycle });
+                    }
+                }
+            }
+            self.marks[index] = Mark::Done;
+            self.order.push(index);
+            Ok(())
+        }
+    }
+
+    let mut search = Search { units, edges, marks: vec![Mark::New; units.len()], path: vec![], order: vec![] };
+    search.visit(0)?;
+    Ok(search.order)
+}
+
+/// A summary of the build `options` and of the contents of `files`, one line per file, or `None`
+/// if one of them cannot be read.
+pub fn stamp(options: &str, files: &[PathBuf]) -> Option<String> {
+    let mut stamp = format!("{options}\n");
+    for path in files {
+        let contents = fs::read(path).ok()?;
+        stamp.push_str(&format!("{} {}\n", Fingerprint::of(&contents), path.display()));
+    }
+    Some(stamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write(directory: &Path, name: &str, source: &str) -> PathBuf {
+        let path = directory.join(format!("{name}.Mod"));
+        fs::write(&path, source).unwrap();
+        path
+    }
+
+    fn names(project: &Project) -> Vec<&str> {
+        project.units.iter().map(|unit| unit.name.as_str()).collect()
+    }
+
+    #[test]
+    fn orders_modules_after_their_imports() {
+        let dir = tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        fs::create_dir(&lib).unwrap();
+        let main = write(dir.path(), "Main", "MODULE Main; IMPORT A, B, Out; END Main.");
+        write(dir.path(), "A", "MODULE A; IMPORT C := Base; END A.");
+        write(&lib, "B", "MODULE B; IMPORT A, Base; END B.");
+        write(&lib, "Base", "MODULE Base; END Base.");
+
+        let project = Project::load(&main, std::slice::from_ref(&lib), IntegerWidth::default()).unwrap();
+
+        // Out has no source, so it is left to its symbol file.
+        assert_eq!(names(&project), ["Base", "A", "B", "Main"]);
+        assert_eq!(project.units[2].path, lib.join("B.Mod"));
+        assert_eq!(project.units[2].dependencies, [1, 0]);
+        assert_eq!(project.units[3].dependencies, [1, 2]);
+    }
+
+    #[test]
+    fn prefers_sources_in_the_search_paths_to_those_next_to_the_main_module() {
+        let dir = tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        fs::create_dir(&lib).unwrap();
+        let main = write(dir.path(), "Main", "MODULE Main; IMPORT A; END Main.");
+        write(dir.path(), "A", "MODULE A; END A.");
+        write(&lib, "A", "MODULE A; END A.");
+
+        let project = Project::load(&main, std::slice::from_ref(&lib), IntegerWidth::default()).unwrap();
+
+        assert_eq!(project.units[0].path, lib.join("A.Mod"));
+    }
+
+    #[test]
+    fn reports_import_cycles_with_every_import() {
+        let dir = tempdir().unwrap();
+        let main = write(dir.path(), "Main", "MODULE Main; IMPORT A; END Main.");
+        let a = write(dir.path(), "A", "MODULE A; IMPORT B; END A.");
+        let b = write(dir.path(), "B", "MODULE B; IMPORT Main, C; END B.");
+        write(dir.path(), "C", "MODULE C; IMPORT A; END C.");
+
+        let Err(CompilerError::Project(error)) = Project::load(&main, &[], IntegerWidth::default()) else { panic!("expected a cycle") };
+
+        assert_eq!(error.to_string(), "import cycle: Main -> A -> B -> Main");
+        let ProjectError::ImportCycle { cycle } = &error else { panic!("{error:?}") };
+        let imports: Vec<_> = cycle.iter().map(|import| (import.path.clone(), import.import.as_str(), import.span.start.column)).collect();
+        assert_eq!(imports, [(main, "A", 21), (a, "B", 18), (b, "Main", 18)]);
+        assert_eq!(error.diagnostics().len(), 3);
+    }
+
+    #[test]
+    fn reports_cycles_that_do_not_pass_through_the_main_module() {
+        let dir = tempdir().unwrap();
+        let main = write(dir.path(), "Main", "MODULE Main; IMPORT A; END Main.");
+        write(dir.path(), "A", "MODULE A; IMPORT A; END A.");
+
+        let Err(CompilerError::Project(error)) = Project::load(&main, &[], IntegerWidth::default()) else { panic!("expected a cycle") };
+        assert_eq!(error.to_string(), "import cycle: A -> A");
+    }
+
+    #[test]
+    fn rejects_files_declaring_another_module() {
+        let dir = tempdir().unwrap();
+        let main = write(dir.path(), "Main", "MODULE Main; IMPORT A; END Main.");
+        write(dir.path(), "A", "MODULE B; END B.");
+
+        let Err(CompilerError::Project(error)) = Project::load(&main, &[], IntegerWidth::default()) else { panic!("expected an error") };
+        assert!(matches!(&error, ProjectError::FileNameMismatch { expected, declared, .. } if expected == "A" && declared == "B"));
+    }
+
+    #[test]
+    fn stamps_change_with_the_options_and_the_contents_of_the_inputs() {
+        let dir = tempdir().unwrap();
+        let (source, symbols) = (dir.path().join("A.Mod"), dir.path().join("B.sym"));
+        fs::write(&source, "MODULE A; END A.").unwrap();
+        assert_eq!(stamp("-O0", &[source.clone(), symbols.clone()]), None);
+
+        fs::write(&symbols, "module B\n").unwrap();
+        let before = stamp("-O0", &[source.clone(), symbols.clone()]).unwrap();
+        assert_eq!(before.lines().count(), 3);
+        fs::write(&source, "MODULE A; END A.").unwrap();
+        assert_eq!(stamp("-O0", &[source.clone(), symbols.clone()]).unwrap(), before);
+        assert_ne!(stamp("-O2", &[source.clone(), symbols.clone()]).unwrap(), before);
+        fs::write(&symbols, "module B\nexport var x 4\n").unwrap();
+        assert_ne!(stamp("-O0", &[source, symbols]).unwrap(), before);
+    }
+}
@@ -0,0 +1,434 @@
+use std::fs;
+use std::process::Command;
+use tempfile::tempdir;
+
+fn compiler() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_oberon-compiler"))
+}
+
+#[test]
+fn compiles_valid_module_and_writes_output() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Hello.Mod");
+    let output = dir.path().join("Hello.s");
+    fs::write(&input, "MODULE Hello; VAR x: INTEGER; BEGIN x := 1 + 2 + 3 END Hello.").unwrap();
+
+    let status = compiler().arg("build").arg(&input).arg("-o").arg(&output).status().unwrap();
+
+    assert!(status.success());
+    let assembly = fs::read_to_string(&output).unwrap();
+    assert!(assembly.contains("Hello__init"));
+}
+
+#[test]
+fn builds_program_that_links_and_runs() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Prog.Mod");
+    let output = dir.path().join("Prog.s");
+    let program = dir.path().join("prog");
+    fs::write(&input, "MODULE Prog; VAR i, n: INTEGER; BEGIN n := 0; FOR i := 1 TO 10 DO n := n + i END; ASSERT(n = 56) END Prog.").unwrap();
+
+    let status = compiler().arg("build").arg(&input).arg("-o").arg(&output).arg("--main").status().unwrap();
+    assert!(status.success());
+    let linked = Command::new("cc").arg("-o").arg(&program).arg(&output).status().unwrap();
+    assert!(linked.success());
+
+    // The failing ASSERT ends the program with trap number 7.
+    let result = Command::new(&program).status().unwrap();
+    assert_eq!(result.code(), Some(7));
+}
+
+#[test]
+fn builds_c_source_with_header() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Prog.Mod");
+    let output = dir.path().join("prog.c");
+    let program = dir.path().join("prog");
+    fs::write(&input, "MODULE Prog; VAR i, n*: INTEGER; BEGIN n := 0; FOR i := 1 TO 10 DO n := n + i END; ASSERT(n = 56) END Prog.").unwrap();
+
+    let status = compiler().arg("build").arg(&input).arg("-o").arg(&output).args(["--emit", "c", "--main"]).status().unwrap();
+    assert!(status.success());
+    let header = fs::read_to_string(dir.path().join("Prog.h")).unwrap();
+    assert!(header.contains("extern int64_t Prog_n;"), "{header}");
+    let compiled = Command::new("cc").args(["-std=c99", "-o"]).arg(&program).arg(&output).status().unwrap();
+    assert!(compiled.success());
+
+    let result = Command::new(&program).status().unwrap();
+    assert_eq!(result.code(), Some(7));
+}
+
+/// Builds `source` into a program with each backend at `-O2`, the C one compiled with `cc -O2`,
+/// and returns the exit status of the assembly program and of the C one.
+fn exit_codes_of_both_backends(source: &str) -> (Option<i32>, Option<i32>) {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Prog.Mod");
+    fs::write(&input, source).unwrap();
+    let mut codes = vec![];
+    for (emit, output) in [("asm", "prog.s"), ("c", "prog.c")] {
+        let output = dir.path().join(output);
+        let result = compiler().arg("build").arg(&input).arg("-o").arg(&output).args(["--emit", emit, "--main", "-O2"]).output().unwrap();
+        assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+        let program = dir.path().join(format!("prog-{emit}"));
+        let compiled = Command::new("cc").args(["-std=c99", "-O2", "-o"]).arg(&program).arg(&output).status().unwrap();
+        assert!(compiled.success());
+        codes.push(Command::new(&program).status().unwrap().code());
+    }
+    (codes[0], codes[1])
+}
+
+#[test]
+fn both_backends_wrap_integer_overflow_around() {
+    let source = "MODULE Prog; VAR big, n: INTEGER;
+        PROCEDURE Gt(a: INTEGER): BOOLEAN; RETURN a + 1 > a END Gt;
+        BEGIN big := 9223372036854775807; ASSERT(~Gt(big)); ASSERT(big + 1 < 0); ASSERT(-(big + 1) = big + 1);
+          ASSERT(big * 2 = -2); n := big; INC(n); ASSERT(n = -big - 1)
+        END Prog.";
+    assert_eq!(exit_codes_of_both_backends(source), (Some(0), Some(0)));
+}
+
+#[test]
+fn both_backends_trap_on_abs_and_floor_overflow() {
+    let source = "MODULE Prog; VAR i: INTEGER; BEGIN i := 9223372036854775807; i := ABS(-i - 1) END Prog.";
+    assert_eq!(exit_codes_of_both_backends(source), (Some(8), Some(8)));
+    let source = "MODULE Prog; VAR i: INTEGER; x: REAL; BEGIN x := 1.0E19; i := FLOOR(x) END Prog.";
+    assert_eq!(exit_codes_of_both_backends(source), (Some(8), Some(8)));
+    let source = "MODULE Prog; VAR i: INTEGER; x: REAL; BEGIN x := -1.0; i := FLOOR(x); ASSERT(ABS(i) = 1) END Prog.";
+    assert_eq!(exit_codes_of_both_backends(source), (Some(0), Some(0)));
+}
+
+#[test]
+fn both_backends_trap_on_nil_dereference() {
+    let prefix = "MODULE Prog; TYPE P = POINTER TO R; R = RECORD n: INTEGER END; Q = POINTER TO S; S = RECORD (R) END;
+        VAR p: P; q: Q; r: R; b: BOOLEAN; BEGIN";
+    for body in ["p.n := 1", "r := p^", "q := p(Q)", "b := p IS Q"] {
+        assert_eq!(exit_codes_of_both_backends(&format!("{prefix} {body} END Prog.")), (Some(4), Some(4)), "{body}");
+    }
+}
+
+#[test]
+fn optimizes_and_dumps_ir() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Prog.Mod");
+    let output = dir.path().join("prog.s");
+    let program = dir.path().join("prog");
+    fs::write(&input, "MODULE Prog; VAR i, n: INTEGER; BEGIN n := 2 * 3; FOR i := 1 TO 10 DO n := n + i END; ASSERT(n = 60) END Prog.").unwrap();
+
+    let result = compiler().arg("build").arg(&input).arg("-o").arg(&output).args(["-O2", "--dump-ir", "--main"]).output().unwrap();
+    assert!(result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains(";; Prog__init before const-fold\n"), "{stderr}");
+    assert!(stderr.contains(";; Prog__init after cse"), "{stderr}");
+    let compiled = Command::new("cc").arg("-o").arg(&program).arg(&output).status().unwrap();
+    assert!(compiled.success());
+
+    let result = Command::new(&program).status().unwrap();
+    assert_eq!(result.code(), Some(7));
+}
+
+#[test]
+fn links_modules_compiled_against_symbol_files() {
+    let dir = tempdir().unwrap();
+    let (src, lib) = (dir.path().join("src"), dir.path().join("lib"));
+    fs::create_dir_all(&src).unwrap();
+    fs::create_dir_all(&lib).unwrap();
+    fs::write(src.join("Counters.Mod"), r#"
+        MODULE Counters;
+        CONST Step* = 5;
+        TYPE Counter* = RECORD value*: INTEGER; hidden: INTEGER END; Ref* = POINTER TO Counter;
+        VAR created*: INTEGER;
+        PROCEDURE New*(): Ref; VAR c: Ref; BEGIN NEW(c); c.value := 0; c.hidden := 1; INC(created) RETURN c END New;
+        PROCEDURE Bump*(c: Ref); BEGIN c.value := c.value + Step * c.hidden END Bump;
+        BEGIN created := 100
+        END Counters.
+    "#).unwrap();
+    fs::write(src.join("Main.Mod"), r#"
+        MODULE Main;
+        IMPORT C := Counters;
+        TYPE Named = RECORD (C.Counter) id: INTEGER END;
+        VAR c: C.Ref; n: POINTER TO Named;
+        BEGIN
+          c := C.New(); C.Bump(c); C.Bump(c);
+          NEW(n); n.id := 7; c := n;
+          ASSERT((C.created = 101) & (c IS C.Ref) & (n.value = 0) & (C.Step = 5))
+        END Main.
+    "#).unwrap();
+
+    let status = compiler().arg("build").arg(src.join("Counters.Mod")).arg("-o").arg(lib.join("Counters.s")).status().unwrap();
+    assert!(status.success());
+    let symbols = fs::read_to_string(lib.join("Counters.sym")).unwrap();
+    assert!(symbols.starts_with("module Counters "), "{symbols}");
+    assert!(symbols.contains("procedure Bump "), "{symbols}");
+
+    let output = dir.path().join("Main.s");
+    let status = compiler().arg("build").arg(src.join("Main.Mod")).arg("-o").arg(&output).arg("-I").arg(&lib).arg("--main").status().unwrap();
+    assert!(status.success());
+    let program = dir.path().join("main");
+    let linked = Command::new("cc").arg("-o").arg(&program).arg(&output).arg(lib.join("Counters.s")).status().unwrap();
+    assert!(linked.success());
+    assert_eq!(Command::new(&program).status().unwrap().code(), Some(0));
+}
+
+fn compiled_modules(stderr: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .filter_map(|line| line.strip_prefix("Compiled "))
+        .map(|line| line.split(".Mod").next().unwrap().rsplit('/').next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn builds_projects_in_dependency_order_and_only_rebuilds_what_changed() {
+    let dir = tempdir().unwrap();
+    let (lib, out) = (dir.path().join("lib"), dir.path().join("out"));
+    fs::create_dir_all(&lib).unwrap();
+    fs::write(lib.join("Base.Mod"), "MODULE Base; VAR n*: INTEGER; BEGIN n := 2 END Base.").unwrap();
+    fs::write(dir.path().join("Twice.Mod"), "MODULE Twice; IMPORT Base; VAR n*: INTEGER; BEGIN n := Base.n * 2 END Twice.").unwrap();
+    let main = dir.path().join("Main.Mod");
+    fs::write(&main, "MODULE Main; IMPORT Twice, Base; BEGIN ASSERT(Twice.n + Base.n = 6) END Main.").unwrap();
+    let project = || compiler().arg("project").arg(&main).arg("-o").arg(&out).arg("-I").arg(&lib).output().unwrap();
+
+    let result = project();
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert_eq!(compiled_modules(&result.stderr), ["Base", "Twice", "Main"]);
+    let program = dir.path().join("main");
+    let objects = ["Base.s", "Twice.s", "Main.s"].map(|name| out.join(name));
+    let linked = Command::new("cc").arg("-o").arg(&program).args(&objects).status().unwrap();
+    assert!(linked.success());
+    assert_eq!(Command::new(&program).status().unwrap().code(), Some(0));
+
+    let result = project();
+    assert!(result.status.success());
+    assert!(compiled_modules(&result.stderr).is_empty());
+
+    // A change to the body leaves the symbol file, and so the importers, as they were.
+    fs::write(lib.join("Base.Mod"), "MODULE Base; VAR n*: INTEGER; BEGIN n := 1 + 1 END Base.").unwrap();
+    assert_eq!(compiled_modules(&project().stderr), ["Base"]);
+
+    // A change to the exports reaches every module that imports it.
+    fs::write(lib.join("Base.Mod"), "MODULE Base; VAR n*, m*: INTEGER; BEGIN n := 2 END Base.").unwrap();
+    assert_eq!(compiled_modules(&project().stderr), ["Base", "Twice", "Main"]);
+
+    fs::write(&main, "MODULE Main; IMPORT Twice, Base; BEGIN ASSERT(Twice.n = 4) END Main.").unwrap();
+    assert_eq!(compiled_modules(&project().stderr), ["Main"]);
+
+    // Outputs overwritten since the last build are written again.
+    fs::write(out.join("Twice.s"), "").unwrap();
+    assert_eq!(compiled_modules(&project().stderr), ["Twice"]);
+    let other = dir.path().join("Other.Mod");
+    fs::write(&other, "MODULE Base; VAR k*: INTEGER; END Base.").unwrap();
+    let build = compiler().arg("build").arg(&other).arg("-o").arg(out.join("Base.s")).output().unwrap();
+    assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+    assert_eq!(compiled_modules(&project().stderr), ["Base"]);
+    assert!(fs::read_to_string(out.join("Base.sym")).unwrap().contains("var m INTEGER"));
+
+    // So are those built with other options.
+    let optimized = compiler().arg("project").arg(&main).arg("-o").arg(&out).arg("-I").arg(&lib).arg("-O1").output().unwrap();
+    assert_eq!(compiled_modules(&optimized.stderr), ["Base", "Twice", "Main"]);
+    assert_eq!(compiled_modules(&project().stderr).len(), 3);
+}
+
+#[test]
+fn reports_import_cycles_at_every_import() {
+    let dir = tempdir().unwrap();
+    let main = dir.path().join("Main.Mod");
+    fs::write(&main, "MODULE Main; IMPORT A; END Main.").unwrap();
+    fs::write(dir.path().join("A.Mod"), "MODULE A;\nIMPORT B;\nEND A.").unwrap();
+    fs::write(dir.path().join("B.Mod"), "MODULE B; IMPORT A; END B.").unwrap();
+
+    let result = compiler().arg("project").arg(&main).arg("-o").arg(dir.path().join("out")).output().unwrap();
+
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("A.Mod:2:8: error[E0401]: import cycle: A -> B -> A\n"), "{stderr}");
+    assert!(stderr.contains("B.Mod:1:18: error[E0401]: import cycle: A -> B -> A\n"), "{stderr}");
+    assert!(stderr.contains("^ 'B' imports 'A'"), "{stderr}");
+    assert!(!dir.path().join("out").exists());
+}
+
+#[test]
+fn reports_modules_compiled_against_an_older_interface() {
+    let dir = tempdir().unwrap();
+    let base = dir.path().join("Base.Mod");
+    let build = |name: &str| compiler().arg("build").arg(dir.path().join(format!("{name}.Mod"))).arg("-o").arg(dir.path().join(format!("{name}.s"))).output().unwrap();
+    fs::write(&base, "MODULE Base; VAR n*: INTEGER; END Base.").unwrap();
+    fs::write(dir.path().join("Twice.Mod"), "MODULE Twice; IMPORT Base; VAR n*: INTEGER; BEGIN n := Base.n * 2 END Twice.").unwrap();
+    fs::write(dir.path().join("Main.Mod"), "MODULE Main; IMPORT Twice; BEGIN ASSERT(Twice.n = 0) END Main.").unwrap();
+    assert!(build("Base").status.success());
+    assert!(build("Twice").status.success());
+    assert!(build("Main").status.success());
+
+    fs::write(&base, "MODULE Base; VAR n*: BYTE; END Base.").unwrap();
+    assert!(build("Base").status.success());
+    let result = build("Main");
+
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("Main.Mod:1:21: error[E0228]: module 'Twice' was compiled against a different version of 'Base.n'\n"), "{stderr}");
+    assert!(stderr.contains("= note: recompile 'Twice' against the current symbol files"), "{stderr}");
+    assert!(build("Twice").status.success());
+    assert!(build("Main").status.success());
+}
+
+#[test]
+fn reports_imports_without_symbol_file() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Main.Mod");
+    fs::write(&input, "MODULE Main; IMPORT Missing; END Main.").unwrap();
+
+    let result = compiler().arg("check").arg(&input).output().unwrap();
+
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("Main.Mod:1:21: error[E0227]: cannot find module 'Missing'\n"), "{stderr}");
+}
+
+#[test]
+fn reports_lexer_error_without_writing_output() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Bad.Mod");
+    let output = dir.path().join("Bad.s");
+    fs::write(&input, "MODULE Bad; BEGIN x := @ END Bad.").unwrap();
+
+    let result = compiler().arg("build").arg(&input).arg("-o").arg(&output).output().unwrap();
+
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("Lexical error"), "{stderr}");
+    assert!(stderr.contains("Bad.Mod:1:24: error[E0001]: unexpected character '@'\n"), "{stderr}");
+    assert!(stderr.contains("1 | MODULE Bad; BEGIN x := @ END Bad.\n  |                        ^"), "{stderr}");
+    assert!(!output.exists());
+}
+
+#[test]
+fn reports_parser_error_without_writing_output() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Bad.Mod");
+    let output = dir.path().join("Bad.s");
+    fs::write(&input, "MODULE Bad; BEGIN IF x THEN END Bad.").unwrap();
+
+    let result = compiler().arg("build").arg(&input).arg("-o").arg(&output).output().unwrap();
+
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("failed with 1 syntax error(s)"), "{stderr}");
+    assert!(stderr.contains("Bad.Mod:1:29: error[E0101]: expected statement, found END\n"), "{stderr}");
+    assert!(!output.exists());
+}
+
+#[test]
+fn reports_every_syntax_error() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Bad.Mod");
+    fs::write(&input, "MODULE Bad;\nVAR x: INTEGER\nBEGIN\n  x := ;\n  IF x THEN x := 1 DO END\nEND Bad.\n").unwrap();
+
+    let result = compiler().arg("check").arg(&input).output().unwrap();
+
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("Bad.Mod:3:1: error[E0101]: expected ';' after variable declaration, found BEGIN\n"), "{stderr}");
+    assert!(stderr.contains("Bad.Mod:4:8: error[E0101]: expected expression, found ';'\n"), "{stderr}");
+    assert!(stderr.contains("Bad.Mod:5:20: error[E0101]: expected ';', ELSIF, ELSE or END, found DO\n"), "{stderr}");
+    assert!(stderr.contains("failed with 3 syntax error(s)"), "{stderr}");
+}
+
+#[test]
+fn reports_analysis_error_without_writing_output() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Bad.Mod");
+    let output = dir.path().join("Bad.s");
+    fs::write(&input, "MODULE Bad; END Good.").unwrap();
+
+    let result = compiler().arg("build").arg(&input).arg("-o").arg(&output).output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(!output.exists());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("Bad.Mod:1:17: error[E0201]: module 'Bad' is closed with 'END Good'\n"), "{stderr}");
+    assert!(stderr.contains("^^^^ expected 'Bad'\n  |        --- module is named here\n"), "{stderr}");
+}
+
+#[test]
+fn checks_constants_against_the_selected_literal_range() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Wide.Mod");
+    fs::write(&input, "MODULE Wide; CONST N = 2147483647 + 1; END Wide.").unwrap();
+
+    assert!(compiler().arg("check").arg(&input).output().unwrap().status.success());
+    let result = compiler().arg("check").arg(&input).args(["--literal-range", "32"]).output().unwrap();
+
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("constant expression overflows"), "{stderr}");
+}
+
+#[test]
+fn check_does_not_write_output() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Ok.Mod");
+    fs::write(&input, "MODULE Ok; END Ok.").unwrap();
+
+    let result = compiler().arg("check").arg(&input).output().unwrap();
+
+    assert!(result.status.success());
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn parse_dumps_module_ast() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Ok.Mod");
+    fs::write(&input, "MODULE Ok; END Ok.").unwrap();
+
+    let result = compiler().args(["parse", "--format", "compact"]).arg(&input).output().unwrap();
+
+    assert!(result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.starts_with("Module {"), "{stdout}");
+}
+
+#[test]
+fn tokens_dumps_spans() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Ok.Mod");
+    fs::write(&input, "MODULE Ok;\nEND Ok.").unwrap();
+
+    let result = compiler().arg("tokens").arg(&input).output().unwrap();
+
+    assert!(result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 7);
+    assert!(lines[3].starts_with("2:1-2:4\t"), "{}", lines[3]);
+}
+
+#[test]
+fn tokens_dumps_trivia_on_request() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("Ok.Mod");
+    fs::write(&input, "MODULE Ok; (* empty *)\nEND Ok.").unwrap();
+
+    let result = compiler().arg("tokens").arg(&input).arg("--trivia").output().unwrap();
+
+    assert!(result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 12, "{stdout}");
+    assert_eq!(lines[5], "1:12-1:23\tComment\t(* empty *)");
+    assert_eq!(lines[6], "1:23-2:1\tWhitespace\t\\n");
+}
+
+#[test]
+fn unknown_flag_is_a_usage_error() {
+    let result = compiler().args(["check", "x.Mod", "--no-such-flag"]).output().unwrap();
+
+    assert_eq!(result.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("Usage"), "{stderr}");
+}
+
+#[test]
+fn missing_arguments_are_a_usage_error() {
+    let result = compiler().arg("build").output().unwrap();
+
+    assert_eq!(result.status.code(), Some(2));
+}